@@ -4,5 +4,9 @@ use vergen::EmitBuilder;
 fn main() -> Result<(), Box<dyn Error>> {
     // Emit the instructions
     EmitBuilder::builder().all_git().emit()?;
+    #[cfg(feature = "grpc")]
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/prover.proto"], &["proto"])?;
     Ok(())
 }
@@ -0,0 +1,62 @@
+/*
+ * The C interface of Lurk, built from the `capi` module of the `lurk` crate:
+ *
+ *     cargo rustc --release --lib --features capi --crate-type cdylib
+ *
+ * Handles are opaque and freed by their `_free` function. Functions returning a pointer return NULL on failure, and
+ * functions returning an int32_t return a negative number; `lurk_last_error` then describes the failure.
+ */
+
+#ifndef LURK_H
+#define LURK_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct LurkStore LurkStore;
+typedef struct LurkExpr LurkExpr;
+typedef struct LurkProver LurkProver;
+
+/* Bytes owned by the caller, released with `lurk_buffer_free` */
+typedef struct LurkBuffer {
+    uint8_t *data;
+    size_t len;
+} LurkBuffer;
+
+/* The last failure on the calling thread, valid until the next one */
+const char *lurk_last_error(void);
+
+LurkStore *lurk_store_new(void);
+void lurk_store_free(LurkStore *store);
+
+/* Expressions are only meaningful along with the store they were read into */
+LurkExpr *lurk_read(const LurkStore *store, const char *src);
+LurkExpr *lurk_eval(const LurkStore *store, const LurkExpr *expr, size_t limit);
+void lurk_expr_free(LurkExpr *expr);
+
+/* Released with `lurk_string_free` */
+char *lurk_expr_print(const LurkStore *store, const LurkExpr *expr);
+void lurk_string_free(char *s);
+
+/* Generating the public parameters of a prover can take a while */
+LurkProver *lurk_prover_new(size_t rc);
+void lurk_prover_free(LurkProver *prover);
+int32_t lurk_prover_verifying_key(const LurkProver *prover, LurkBuffer *out);
+int32_t lurk_prove(const LurkProver *prover, const LurkStore *store, const LurkExpr *expr, size_t limit,
+                   LurkBuffer *out);
+
+/* 1 if the proof shows that `expr` evaluates to `result`, 0 if it's well-formed but doesn't, -1 on failure */
+int32_t lurk_verify(const uint8_t *vk, size_t vk_len, const uint8_t *proof, size_t proof_len, const LurkStore *store,
+                    const LurkExpr *expr, const LurkExpr *result);
+
+void lurk_buffer_free(LurkBuffer buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LURK_H */
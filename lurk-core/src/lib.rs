@@ -0,0 +1,14 @@
+//! The header format of encoded Lurk proofs, without the standard library.
+//!
+//! This crate is `no_std` and only needs `alloc`, so that environments such as enclaves can read and check the
+//! [`wire`] header of an encoded proof, such as its format version, field and parameters digest, before handing it to a
+//! verifier. It does not verify proofs: that still takes the `lurk` crate, which builds upon this one.
+//!
+//! The fields, tags, `ZPtr`s and SNARK verification stay in `lurk`: the BN256 field comes from `halo2curves` and the
+//! verifier from `arecibo`, both of which require `std`.
+#![cfg_attr(not(test), no_std)]
+#![deny(unreachable_pub)]
+
+extern crate alloc;
+
+pub mod wire;
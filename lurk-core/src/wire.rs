@@ -0,0 +1,195 @@
+//! The header of encoded Lurk proofs.
+//!
+//! Every encoded proof starts with a fixed header, followed by the proof itself:
+//!
+//! | bytes       | content                                          |
+//! |-------------|--------------------------------------------------|
+//! | 4           | magic bytes `LRKP`                               |
+//! | 2           | format version, little-endian                    |
+//! | 1           | field id                                         |
+//! | 1           | proof kind                                       |
+//! | 8           | reduction count, little-endian                   |
+//! | 1 + n       | length and bytes of the public parameters digest |
+//! | rest        | payload                                          |
+
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The magic bytes starting every encoded proof
+pub const MAGIC: [u8; 4] = *b"LRKP";
+
+/// The current version of the format. Proofs encoded with other versions are rejected.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// The kinds of proofs that can be encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ProofKind {
+    /// A folded Nova proof
+    NovaRecursive = 0,
+    /// A compressed Nova proof
+    NovaCompressed = 1,
+    /// A folded SuperNova proof
+    SuperNovaRecursive = 2,
+    /// A compressed SuperNova proof
+    SuperNovaCompressed = 3,
+}
+
+impl ProofKind {
+    pub fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::NovaRecursive),
+            1 => Some(Self::NovaCompressed),
+            2 => Some(Self::SuperNovaRecursive),
+            3 => Some(Self::SuperNovaCompressed),
+            _ => None,
+        }
+    }
+
+    /// Whether both kinds come from the same proving system
+    pub fn same_system(&self, other: &Self) -> bool {
+        let is_nova = |kind: &Self| matches!(kind, Self::NovaRecursive | Self::NovaCompressed);
+        is_nova(self) == is_nova(other)
+    }
+}
+
+/// Errors found when reading a header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The bytes don't start with `MAGIC`
+    BadMagic,
+    /// The proof was encoded with another version of the format
+    UnsupportedVersion(u16),
+    /// The proof kind isn't known
+    UnknownKind(u8),
+    /// The header ends before the named field
+    Truncated(&'static str),
+}
+
+impl fmt::Display for HeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "Not an encoded Lurk proof"),
+            Self::UnsupportedVersion(found) => write!(
+                f,
+                "Unsupported proof format version {found}, expected {FORMAT_VERSION}"
+            ),
+            Self::UnknownKind(id) => write!(f, "Unknown proof kind id {id}"),
+            Self::Truncated(field) => write!(f, "Truncated proof header: `{field}` is missing"),
+        }
+    }
+}
+
+/// The header of an encoded proof, with the field as its id in the format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawHeader {
+    /// The format version
+    pub version: u16,
+    /// The id of the field the proof is over
+    pub field_id: u8,
+    /// The kind of proof
+    pub kind: ProofKind,
+    /// The reduction count of the proved circuit
+    pub rc: u64,
+    /// The digest of the public parameters, as the bytes of a field element
+    pub digest: Vec<u8>,
+}
+
+impl RawHeader {
+    /// Reads the header of `bytes`, returning it along with the remaining payload. The field id isn't checked, as
+    /// which fields are known is up to the caller.
+    pub fn read(bytes: &[u8]) -> Result<(Self, &[u8]), HeaderError> {
+        fn take<'a>(
+            bytes: &mut &'a [u8],
+            n: usize,
+            field: &'static str,
+        ) -> Result<&'a [u8], HeaderError> {
+            if bytes.len() < n {
+                return Err(HeaderError::Truncated(field));
+            }
+            let (head, tail) = bytes.split_at(n);
+            *bytes = tail;
+            Ok(head)
+        }
+        let mut rest = bytes;
+        if take(&mut rest, 4, "magic")? != MAGIC {
+            return Err(HeaderError::BadMagic);
+        }
+        let version = u16::from_le_bytes(take(&mut rest, 2, "version")?.try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(HeaderError::UnsupportedVersion(version));
+        }
+        let field_id = take(&mut rest, 1, "field")?[0];
+        let kind_byte = take(&mut rest, 1, "kind")?[0];
+        let kind = ProofKind::from_u8(kind_byte).ok_or(HeaderError::UnknownKind(kind_byte))?;
+        let rc = u64::from_le_bytes(take(&mut rest, 8, "rc")?.try_into().unwrap());
+        let digest_len = take(&mut rest, 1, "digest length")?[0] as usize;
+        let digest = take(&mut rest, digest_len, "digest")?.to_vec();
+        let header = Self {
+            version,
+            field_id,
+            kind,
+            rc,
+            digest,
+        };
+        Ok((header, rest))
+    }
+
+    /// Appends the header to `bytes`
+    ///
+    /// # Panics
+    /// Panics if the digest is longer than 255 bytes
+    pub fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend(MAGIC);
+        bytes.extend(self.version.to_le_bytes());
+        bytes.push(self.field_id);
+        bytes.push(self.kind as u8);
+        bytes.extend(self.rc.to_le_bytes());
+        bytes.push(u8::try_from(self.digest.len()).expect("digest too long"));
+        bytes.extend(&self.digest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_header_roundtrip() {
+        let header = RawHeader {
+            version: FORMAT_VERSION,
+            field_id: 2,
+            kind: ProofKind::SuperNovaCompressed,
+            rc: 10,
+            digest: vec![7; 32],
+        };
+        let mut bytes = vec![];
+        header.write(&mut bytes);
+        bytes.extend([1, 2, 3]);
+        assert_eq!(
+            Ok((header.clone(), &[1u8, 2, 3][..])),
+            RawHeader::read(&bytes)
+        );
+
+        assert_eq!(
+            Err(HeaderError::Truncated("digest")),
+            RawHeader::read(&bytes[..20])
+        );
+        assert_eq!(
+            Err(HeaderError::Truncated("rc")),
+            RawHeader::read(&bytes[..10])
+        );
+        assert_eq!(Err(HeaderError::BadMagic), RawHeader::read(b"not a proof"));
+        let mut unknown_kind = bytes.clone();
+        unknown_kind[7] = 9;
+        assert_eq!(
+            Err(HeaderError::UnknownKind(9)),
+            RawHeader::read(&unknown_kind)
+        );
+        // unknown fields are left to the caller
+        let mut unknown_field = bytes;
+        unknown_field[6] = 9;
+        assert_eq!(9, RawHeader::read(&unknown_field).unwrap().0.field_id);
+    }
+}
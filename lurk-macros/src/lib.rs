@@ -36,6 +36,7 @@ fn impl_enum_coproc(name: &Ident, variants: &DataEnum) -> TokenStream {
     let evaluate_arms = evaluate_match_arms(name, variants);
     let evaluate_simple_arms = evaluate_simple_match_arms(name, variants);
     let has_circuit_arms = has_circuit_match_arms(name, variants);
+    let batchable_arms = batchable_match_arms(name, variants);
 
     let arity_arms = arity_match_arms(name, variants);
     let synthesize_internal_arms = synthesize_internal_match_arms(name, variants);
@@ -52,19 +53,33 @@ fn impl_enum_coproc(name: &Ident, variants: &DataEnum) -> TokenStream {
                 }
             }
 
-            fn evaluate_internal(&self, s: &lurk::lem::store::Store<F>, ptrs: &[lurk::lem::pointers::Ptr]) -> Vec<lurk::lem::pointers::Ptr> {
+            fn evaluate_internal(
+                &self,
+                s: &lurk::lem::store::Store<F>,
+                ptrs: &[lurk::lem::pointers::Ptr],
+            ) -> Vec<lurk::lem::pointers::Ptr> {
                 match self {
                     #evaluate_internal_arms
                 }
             }
 
-            fn evaluate(&self, s: &lurk::lem::store::Store<F>, args: &[lurk::lem::pointers::Ptr], env: &lurk::lem::pointers::Ptr, cont: &lurk::lem::pointers::Ptr) -> Vec<lurk::lem::pointers::Ptr> {
+            fn evaluate(
+                &self,
+                s: &lurk::lem::store::Store<F>,
+                args: &[lurk::lem::pointers::Ptr],
+                env: &lurk::lem::pointers::Ptr,
+                cont: &lurk::lem::pointers::Ptr,
+            ) -> Vec<lurk::lem::pointers::Ptr> {
                 match self {
                     #evaluate_arms
                 }
             }
 
-            fn evaluate_simple(&self, s: &lurk::lem::store::Store<F>, args: &[lurk::lem::pointers::Ptr]) -> lurk::lem::pointers::Ptr {
+            fn evaluate_simple(
+                &self,
+                s: &lurk::lem::store::Store<F>,
+                args: &[lurk::lem::pointers::Ptr],
+            ) -> lurk::lem::pointers::Ptr {
                 match self {
                     #evaluate_simple_arms
                 }
@@ -75,6 +90,12 @@ fn impl_enum_coproc(name: &Ident, variants: &DataEnum) -> TokenStream {
                     #has_circuit_arms
                 }
             }
+
+            fn batchable(&self) -> bool {
+                match self {
+                    #batchable_arms
+                }
+            }
         }
 
         impl<F: lurk::field::LurkField> lurk::coprocessor::CoCircuit<F> for #name<F> {
@@ -191,6 +212,18 @@ fn has_circuit_match_arms(name: &Ident, variants: &DataEnum) -> proc_macro2::Tok
     match_arms
 }
 
+fn batchable_match_arms(name: &Ident, variants: &DataEnum) -> proc_macro2::TokenStream {
+    let mut match_arms = quote! {};
+    for variant in variants.variants.iter() {
+        let variant_ident = &variant.ident;
+
+        match_arms.extend(quote! {
+            #name::#variant_ident(coprocessor) => coprocessor.batchable(),
+        });
+    }
+    match_arms
+}
+
 fn arity_match_arms(name: &Ident, variants: &DataEnum) -> proc_macro2::TokenStream {
     let mut match_arms = quote! {};
     for variant in variants.variants.iter() {
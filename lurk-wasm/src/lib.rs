@@ -0,0 +1,211 @@
+//! JavaScript bindings for Lurk
+//!
+//! This crate compiles to `wasm32-unknown-unknown` and exposes, through [wasm-bindgen](https://docs.rs/wasm-bindgen),
+//! what a browser or a node service needs to run Lurk programs and check Lurk proofs without a native toolchain:
+//! - `evaluate` reads and evaluates a program, returning its printed result;
+//! - `read` and `hash` read an expression, returning its canonical form and its content hash, tag and digest;
+//! - `verify` checks a compressed Nova proof against a verifying key extracted by the prover (see
+//!   [`lurk::verifier`]), so verification needs neither the public parameters nor the evaluator.
+//!
+//! Everything is over the BN256 scalar field, the default of the Lurk CLI. Build with
+//! ```ignore
+//! wasm-pack build lurk-wasm --target web
+//! ```
+//! or `--target nodejs` for node.
+use anyhow::{bail, Context, Result};
+use halo2curves::bn256::Fr;
+use lurk::{
+    eval::lang::Coproc,
+    field::LurkField,
+    lem::{eval::evaluate_simple, pointers::Ptr, store::Store},
+    verifier::{self, PublicIO, VerifyingKey},
+};
+use wasm_bindgen::prelude::*;
+
+/// The outcome of `evaluate`
+#[wasm_bindgen]
+pub struct Evaluation {
+    result: String,
+    iterations: usize,
+    status: String,
+}
+
+#[wasm_bindgen]
+impl Evaluation {
+    /// The printed result of the evaluation
+    #[wasm_bindgen(getter)]
+    pub fn result(&self) -> String {
+        self.result.clone()
+    }
+
+    /// The number of iterations the evaluation took
+    #[wasm_bindgen(getter)]
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// `"terminal"` if the evaluation finished with a result, `"error"` if it finished with an error and
+    /// `"incomplete"` if it ran out of iterations
+    #[wasm_bindgen(getter)]
+    pub fn status(&self) -> String {
+        self.status.clone()
+    }
+}
+
+/// The content hash of an expression, as returned by `hash`
+#[wasm_bindgen]
+pub struct ContentHash {
+    tag: String,
+    value: String,
+}
+
+#[wasm_bindgen]
+impl ContentHash {
+    /// The hex-encoded field element of the expression's tag
+    #[wasm_bindgen(getter)]
+    pub fn tag(&self) -> String {
+        self.tag.clone()
+    }
+
+    /// The hex-encoded digest of the expression's value
+    #[wasm_bindgen(getter)]
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+}
+
+fn js_error(e: anyhow::Error) -> JsError {
+    JsError::new(&format!("{e:#}"))
+}
+
+fn read_expr(store: &Store<Fr>, src: &str) -> Result<Ptr> {
+    store
+        .read_with_default_state(src)
+        .with_context(|| format!("couldn't read `{src}`"))
+}
+
+fn evaluate_aux(src: &str, limit: usize) -> Result<Evaluation> {
+    let store = Store::<Fr>::default();
+    let expr = read_expr(&store, src)?;
+    let (output, iterations, _) = evaluate_simple::<Fr, Coproc<Fr>>(None, expr, &store, limit)?;
+    let status = if output[2] == store.cont_terminal() {
+        "terminal"
+    } else if output[2] == store.cont_error() {
+        "error"
+    } else {
+        "incomplete"
+    };
+    Ok(Evaluation {
+        result: output[0].fmt_to_string_simple(&store),
+        iterations,
+        status: status.into(),
+    })
+}
+
+/// Evaluates the Lurk program `src` for at most `limit` iterations
+#[wasm_bindgen]
+pub fn evaluate(src: &str, limit: usize) -> Result<Evaluation, JsError> {
+    evaluate_aux(src, limit).map_err(js_error)
+}
+
+fn read_aux(src: &str) -> Result<String> {
+    let store = Store::<Fr>::default();
+    Ok(read_expr(&store, src)?.fmt_to_string_simple(&store))
+}
+
+/// Reads the expression `src`, returning its canonical printed form
+#[wasm_bindgen]
+pub fn read(src: &str) -> Result<String, JsError> {
+    read_aux(src).map_err(js_error)
+}
+
+fn hash_aux(src: &str) -> Result<ContentHash> {
+    let store = Store::<Fr>::default();
+    let expr = read_expr(&store, src)?;
+    let z_ptr = store.hash_ptr(&expr);
+    Ok(ContentHash {
+        tag: z_ptr.tag_field().hex_digits(),
+        value: z_ptr.value().hex_digits(),
+    })
+}
+
+/// Reads the expression `src`, returning its content hash, tag and digest, as committed to by proofs
+#[wasm_bindgen]
+pub fn hash(src: &str) -> Result<ContentHash, JsError> {
+    hash_aux(src).map_err(js_error)
+}
+
+fn parse_elements(elements: &[String]) -> Result<Vec<Fr>> {
+    elements
+        .iter()
+        .map(|hex| Fr::from_hex_digits(hex).with_context(|| format!("invalid field element {hex}")))
+        .collect()
+}
+
+fn verify_aux(vk: &[u8], proof: &[u8], z0: &[String], zi: &[String]) -> Result<bool> {
+    let vk: VerifyingKey<Fr> = bincode::deserialize(vk).context("invalid verifying key")?;
+    let public_io = PublicIO {
+        z0: parse_elements(z0)?,
+        zi: parse_elements(zi)?,
+    };
+    if public_io.z0.len() != public_io.zi.len() {
+        bail!("the public inputs and outputs must have as many elements");
+    }
+    Ok(verifier::verify(proof, &public_io, &vk)?)
+}
+
+/// Verifies `proof`, a compressed Nova proof in the wire format, with `vk`, a bincode-encoded verifying key. `z0` and
+/// `zi` are the public inputs and outputs of the proof, as hex-encoded field elements. Returns `false` if the proof is
+/// well-formed but doesn't prove them.
+#[wasm_bindgen]
+pub fn verify(vk: &[u8], proof: &[u8], z0: Vec<String>, zi: Vec<String>) -> Result<bool, JsError> {
+    verify_aux(vk, proof, &z0, &zi).map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let evaluation = evaluate_aux("(+ 1 2)", 100).unwrap();
+        assert_eq!("3", evaluation.result);
+        assert_eq!("terminal", evaluation.status);
+        assert!(evaluation.iterations > 0);
+
+        assert_eq!("error", evaluate_aux("(car 1)", 100).unwrap().status);
+        let looping = "(letrec ((f (lambda (x) (f x)))) (f 0))";
+        assert_eq!("incomplete", evaluate_aux(looping, 100).unwrap().status);
+        assert!(evaluate_aux("(+ 1", 100).is_err());
+    }
+
+    #[test]
+    fn test_read_and_hash() {
+        assert_eq!(
+            read_aux("(1 . (2 . nil))").unwrap(),
+            read_aux("(1 2)").unwrap()
+        );
+        assert_eq!(
+            hash_aux("(1 . (2 . nil))").unwrap().value,
+            hash_aux("(1 2)").unwrap().value
+        );
+        assert_ne!(
+            hash_aux("(1 2)").unwrap().value,
+            hash_aux("(2 1)").unwrap().value
+        );
+
+        let store = Store::<Fr>::default();
+        let expr = store.read_with_default_state("(1 2)").unwrap();
+        let z_ptr = store.hash_ptr(&expr);
+        let hash = hash_aux("(1 2)").unwrap();
+        assert_eq!(
+            [z_ptr.tag_field(), *z_ptr.value()],
+            parse_elements(&[hash.tag, hash.value]).unwrap()[..]
+        );
+        // A number and a char with the same digest differ by their tag only
+        let (num, char) = (hash_aux("65").unwrap(), hash_aux("'A'").unwrap());
+        assert_eq!(num.value, char.value);
+        assert_ne!(num.tag, char.tag);
+        assert!(parse_elements(&["not hex".into()]).is_err());
+    }
+}
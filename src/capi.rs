@@ -0,0 +1,451 @@
+//! A C interface to the evaluator and the Nova prover, for embedding them in services written in other languages.
+//!
+//! Stores, expressions and provers are opaque handles, created and freed by the functions below. An expression is
+//! only meaningful along with the store it was read into. Proofs, and the verifying keys that check them, are byte
+//! buffers owned by the caller once returned, which must release them with `lurk_buffer_free`. A proof buffer bundles
+//! the compressed proof, in the [`crate::proof::wire`] format, with its public inputs and outputs. Verifiers check
+//! those against the evaluation they expect before checking the proof with a verifying key.
+//!
+//! Functions returning a handle return `NULL` on failure, and functions returning an `int32_t` return a negative
+//! number. In both cases `lurk_last_error` describes what went wrong. Panics are caught and reported the same way.
+//! Everything is over the BN256 scalar field, with the default `Lang`.
+//!
+//! The declarations are in `include/lurk.h`. The library is built, with the `capi` feature, as
+//! ```ignore
+//! cargo rustc --release --lib --features capi --crate-type cdylib
+//! ```
+//! or `--crate-type staticlib` for a static one.
+
+use anyhow::{anyhow, bail, Context, Result};
+use halo2curves::bn256::Fr;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Arc;
+
+use crate::{
+    eval::lang::{Coproc, Lang},
+    lem::{eval::evaluate_simple, pointers::Ptr, store::Store},
+    proof::{
+        nova::{public_params, NovaProver, PublicParams},
+        Prover, RecursiveSNARKTrait,
+    },
+    verifier::{self, PublicIO, VerifyingKey},
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Runs `f`, recording its error or panic as the last error and returning `failure` instead of its result
+fn ffi_call<T>(failure: T, f: impl FnOnce() -> Result<T>) -> T {
+    let result = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let msg = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        Err(anyhow!("panic: {msg}"))
+    });
+    result.unwrap_or_else(|e| {
+        let msg = format!("{e:#}").replace('\0', " ");
+        LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(msg).unwrap_or_default());
+        failure
+    })
+}
+
+/// # Safety
+/// `ptr` must be `NULL` or a valid reference to a `T`
+unsafe fn handle<'a, T>(ptr: *const T, name: &str) -> Result<&'a T> {
+    ptr.as_ref().with_context(|| format!("null {name}"))
+}
+
+/// # Safety
+/// `data` must be `NULL` or point to `len` readable bytes
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8]> {
+    if data.is_null() {
+        bail!("null buffer");
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+pub struct LurkStore(Store<Fr>);
+
+pub struct LurkExpr(Ptr);
+
+pub struct LurkProver {
+    rc: usize,
+    lang: Arc<Lang<Fr, Coproc<Fr>>>,
+    pp: PublicParams<Fr>,
+}
+
+/// Bytes owned by the caller, to be released with `lurk_buffer_free`
+#[repr(C)]
+pub struct LurkBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl LurkBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()).cast();
+        Self { data, len }
+    }
+}
+
+/// What a proof buffer holds
+#[derive(Serialize, Deserialize)]
+struct ProofBundle {
+    proof: Vec<u8>,
+    public_io: PublicIO<Fr>,
+}
+
+/// The description of the last failure on the calling thread, or an empty string. The pointer is valid until the next
+/// failure on the thread.
+#[no_mangle]
+pub extern "C" fn lurk_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn lurk_store_new() -> *mut LurkStore {
+    Box::into_raw(Box::new(LurkStore(Store::default())))
+}
+
+/// # Safety
+/// `store` must be `NULL` or a handle returned by `lurk_store_new`, not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn lurk_store_free(store: *mut LurkStore) {
+    if !store.is_null() {
+        drop(Box::from_raw(store));
+    }
+}
+
+/// Reads the NUL-terminated source `src` into `store`
+///
+/// # Safety
+/// `store` must be a live store handle and `src` a NUL-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn lurk_read(store: *const LurkStore, src: *const c_char) -> *mut LurkExpr {
+    ffi_call(ptr::null_mut(), || {
+        let store = &handle(store, "store")?.0;
+        if src.is_null() {
+            bail!("null source");
+        }
+        let src = CStr::from_ptr(src)
+            .to_str()
+            .context("the source isn't UTF-8")?;
+        let expr = store.read_with_default_state(src)?;
+        Ok(Box::into_raw(Box::new(LurkExpr(expr))))
+    })
+}
+
+/// # Safety
+/// `expr` must be `NULL` or a handle returned by this library, not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn lurk_expr_free(expr: *mut LurkExpr) {
+    if !expr.is_null() {
+        drop(Box::from_raw(expr));
+    }
+}
+
+/// Evaluates `expr` for at most `limit` iterations, returning the result. Evaluations that end with an error or run out
+/// of iterations fail.
+///
+/// # Safety
+/// `store` must be a live store handle and `expr` a live expression handle of that store
+#[no_mangle]
+pub unsafe extern "C" fn lurk_eval(
+    store: *const LurkStore,
+    expr: *const LurkExpr,
+    limit: usize,
+) -> *mut LurkExpr {
+    ffi_call(ptr::null_mut(), || {
+        let store = &handle(store, "store")?.0;
+        let expr = handle(expr, "expression")?.0;
+        let (output, iterations, _) = evaluate_simple::<Fr, Coproc<Fr>>(None, expr, store, limit)?;
+        if output[2] == store.cont_error() {
+            bail!(
+                "evaluation error on {}",
+                output[0].fmt_to_string_simple(store)
+            );
+        }
+        if output[2] != store.cont_terminal() {
+            bail!("evaluation incomplete after {iterations} iterations");
+        }
+        Ok(Box::into_raw(Box::new(LurkExpr(output[0]))))
+    })
+}
+
+/// Prints `expr`, returning a NUL-terminated string to be released with `lurk_string_free`
+///
+/// # Safety
+/// `store` must be a live store handle and `expr` a live expression handle of that store
+#[no_mangle]
+pub unsafe extern "C" fn lurk_expr_print(
+    store: *const LurkStore,
+    expr: *const LurkExpr,
+) -> *mut c_char {
+    ffi_call(ptr::null_mut(), || {
+        let store = &handle(store, "store")?.0;
+        let expr = handle(expr, "expression")?;
+        let printed = expr.0.fmt_to_string_simple(store).replace('\0', " ");
+        Ok(CString::new(printed)?.into_raw())
+    })
+}
+
+/// # Safety
+/// `s` must be `NULL` or a string returned by this library, not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn lurk_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Creates a prover of reduction count `rc`, generating its public parameters, which can take a while
+#[no_mangle]
+pub extern "C" fn lurk_prover_new(rc: usize) -> *mut LurkProver {
+    ffi_call(ptr::null_mut(), || {
+        if rc == 0 {
+            bail!("the reduction count must be positive");
+        }
+        let lang = Arc::new(Lang::new());
+        let pp = public_params(rc, lang.clone());
+        Ok(Box::into_raw(Box::new(LurkProver { rc, lang, pp })))
+    })
+}
+
+/// # Safety
+/// `prover` must be `NULL` or a handle returned by `lurk_prover_new`, not used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn lurk_prover_free(prover: *mut LurkProver) {
+    if !prover.is_null() {
+        drop(Box::from_raw(prover));
+    }
+}
+
+/// Writes the verifying key of the proofs of `prover` to `out`. Returns 0 on success.
+///
+/// # Safety
+/// `prover` must be a live prover handle and `out` must be valid for writes
+#[no_mangle]
+pub unsafe extern "C" fn lurk_prover_verifying_key(
+    prover: *const LurkProver,
+    out: *mut LurkBuffer,
+) -> i32 {
+    ffi_call(-1, || {
+        let prover = handle(prover, "prover")?;
+        if out.is_null() {
+            bail!("null output buffer");
+        }
+        let vk = VerifyingKey::new(&prover.pp, prover.rc);
+        out.write(LurkBuffer::new(bincode::serialize(&vk)?));
+        Ok(0)
+    })
+}
+
+/// Evaluates `expr` for at most `limit` iterations and writes a compressed proof of the evaluation to `out`. Returns 0
+/// on success.
+///
+/// # Safety
+/// `prover` and `store` must be live handles, `expr` a live expression handle of `store`, and `out` must be valid for
+/// writes
+#[no_mangle]
+pub unsafe extern "C" fn lurk_prove(
+    prover: *const LurkProver,
+    store: *const LurkStore,
+    expr: *const LurkExpr,
+    limit: usize,
+    out: *mut LurkBuffer,
+) -> i32 {
+    ffi_call(-1, || {
+        let prover = handle(prover, "prover")?;
+        let store = &handle(store, "store")?.0;
+        let expr = handle(expr, "expression")?.0;
+        if out.is_null() {
+            bail!("null output buffer");
+        }
+        let nova_prover = NovaProver::new(prover.rc, prover.lang.clone());
+        let (proof, z0, zi, _) = nova_prover.evaluate_and_prove(
+            &prover.pp,
+            expr,
+            store.intern_empty_env(),
+            store,
+            limit,
+        )?;
+        let proof = proof.compress(&prover.pp)?;
+        let bundle = ProofBundle {
+            proof: proof.to_bytes(&prover.pp, prover.rc)?,
+            public_io: PublicIO { z0, zi },
+        };
+        out.write(LurkBuffer::new(bincode::serialize(&bundle)?));
+        Ok(0)
+    })
+}
+
+/// Verifies, with a verifying key written by `lurk_prover_verifying_key`, that a proof written by `lurk_prove` shows
+/// that `expr` evaluates to `result`. The public inputs and outputs bundled with the proof are only trusted once they
+/// match these. Returns 1 if the proof is valid, 0 if it's well-formed but invalid or proves another evaluation, and -1
+/// on failure.
+///
+/// # Safety
+/// `vk` must point to `vk_len` readable bytes and `proof` to `proof_len` readable bytes, `store` must be a live store
+/// handle and `expr` and `result` live expression handles of that store
+#[no_mangle]
+pub unsafe extern "C" fn lurk_verify(
+    vk: *const u8,
+    vk_len: usize,
+    proof: *const u8,
+    proof_len: usize,
+    store: *const LurkStore,
+    expr: *const LurkExpr,
+    result: *const LurkExpr,
+) -> i32 {
+    ffi_call(-1, || {
+        let store = &handle(store, "store")?.0;
+        let expr = handle(expr, "expression")?.0;
+        let result = handle(result, "result")?.0;
+        let vk: VerifyingKey<Fr> =
+            bincode::deserialize(bytes(vk, vk_len)?).context("invalid verifying key")?;
+        let bundle: ProofBundle =
+            bincode::deserialize(bytes(proof, proof_len)?).context("invalid proof")?;
+        if !proves_evaluation(store, expr, result, &bundle.public_io) {
+            return Ok(0);
+        }
+        let valid = verifier::verify(&bundle.proof, &bundle.public_io, &vk)?;
+        Ok(i32::from(valid))
+    })
+}
+
+/// Whether `public_io` is the IO of an evaluation of `expr`, as by `lurk_prove`, that terminates with `result`
+fn proves_evaluation(store: &Store<Fr>, expr: Ptr, result: Ptr, public_io: &PublicIO<Fr>) -> bool {
+    let z0 = store.to_scalar_vector(&[expr, store.intern_empty_env(), store.cont_outermost()]);
+    // The output environment isn't part of the claim.
+    let output = store.to_scalar_vector(&[result, store.cont_terminal()]);
+    public_io.z0 == z0
+        && public_io.zi.len() == 6
+        && public_io.zi[..2] == output[..2]
+        && public_io.zi[4..] == output[2..]
+}
+
+/// # Safety
+/// `buffer` must have been written by this library and not freed before
+#[no_mangle]
+pub unsafe extern "C" fn lurk_buffer_free(buffer: LurkBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(lurk_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_read_eval_print() {
+        unsafe {
+            let store = lurk_store_new();
+            let src = CString::new("(+ 1 2)").unwrap();
+            let expr = lurk_read(store, src.as_ptr());
+            let result = lurk_eval(store, expr, 100);
+            assert!(!result.is_null());
+            let printed = lurk_expr_print(store, result);
+            assert_eq!("3", CStr::from_ptr(printed).to_str().unwrap());
+            lurk_string_free(printed);
+
+            let bad = CString::new("(car 1)").unwrap();
+            let bad = lurk_read(store, bad.as_ptr());
+            assert!(lurk_eval(store, bad, 100).is_null());
+            assert!(last_error().starts_with("evaluation error"));
+
+            let unreadable = CString::new("(+ 1").unwrap();
+            assert!(lurk_read(store, unreadable.as_ptr()).is_null());
+            assert!(lurk_read(ptr::null(), src.as_ptr()).is_null());
+            assert_eq!("null store", last_error());
+
+            for expr in [expr, result, bad] {
+                lurk_expr_free(expr);
+            }
+            lurk_store_free(store);
+        }
+    }
+
+    #[test]
+    fn test_prove_verify() {
+        unsafe {
+            let prover = lurk_prover_new(3);
+            let store = lurk_store_new();
+            let src = CString::new("(+ 1 2)").unwrap();
+            let expr = lurk_read(store, src.as_ptr());
+            let mut proof = LurkBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            let mut vk = LurkBuffer {
+                data: ptr::null_mut(),
+                len: 0,
+            };
+            assert_eq!(0, lurk_prove(prover, store, expr, 100, &mut proof));
+            assert_eq!(0, lurk_prover_verifying_key(prover, &mut vk));
+            let (three, four) = (CString::new("3").unwrap(), CString::new("4").unwrap());
+            let three = lurk_read(store, three.as_ptr());
+            let four = lurk_read(store, four.as_ptr());
+            let verify = |proof: &[u8], result| {
+                lurk_verify(
+                    vk.data,
+                    vk.len,
+                    proof.as_ptr(),
+                    proof.len(),
+                    store,
+                    expr,
+                    result,
+                )
+            };
+            let bytes = std::slice::from_raw_parts(proof.data, proof.len);
+            assert_eq!(1, verify(bytes, three));
+            // the proof doesn't show that the expression evaluates to 4
+            assert_eq!(0, verify(bytes, four));
+
+            // a proof whose bundled IO claims another result
+            let mut bundle: ProofBundle = bincode::deserialize(bytes).unwrap();
+            bundle.public_io.zi[0] += Fr::from(1);
+            let forged = bincode::serialize(&bundle).unwrap();
+            assert_eq!(0, verify(&forged, three));
+            assert_eq!(-1, verify(&[], three));
+            assert_eq!(
+                -1,
+                lurk_verify(
+                    vk.data,
+                    vk.len,
+                    proof.data,
+                    proof.len,
+                    ptr::null(),
+                    expr,
+                    three
+                )
+            );
+            assert_eq!("null store", last_error());
+            lurk_expr_free(three);
+            lurk_expr_free(four);
+
+            lurk_buffer_free(proof);
+            lurk_buffer_free(vk);
+            lurk_expr_free(expr);
+            lurk_store_free(store);
+            lurk_prover_free(prover);
+        }
+    }
+}
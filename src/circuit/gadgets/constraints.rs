@@ -178,6 +178,52 @@ pub(crate) fn implies_pack<F: LurkField, CS: ConstraintSystem<F>>(
     cs.enforce(|| "pack", diff, premise_lc, zero);
 }
 
+/// Allocates the `n` least significant bits of `num` and enforces that they are its complete decomposition, so
+/// `0 <= num < 2^n`.
+pub(crate) fn decompose_le<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    num: &AllocatedNum<F>,
+    n: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let values = num
+        .get_value()
+        .map(|v| v.to_le_bits().into_iter().take(n).collect::<Vec<_>>());
+    let bits = (0..n)
+        .map(|i| {
+            let bit = AllocatedBit::alloc(
+                &mut cs.namespace(|| format!("b.{i}")),
+                values.as_ref().map(|v| v[i]),
+            )?;
+            Ok(Boolean::Is(bit))
+        })
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+    implies_pack(
+        &mut cs.namespace(|| "decomposition"),
+        &Boolean::Constant(true),
+        &bits,
+        num,
+    );
+    Ok(bits)
+}
+
+/// Allocates `value` and enforces it to be equal to `lc`.
+pub(crate) fn alloc_lc<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    value: Option<F>,
+    lc: LinearCombination<F>,
+) -> Result<AllocatedNum<F>, SynthesisError> {
+    let num = AllocatedNum::alloc(cs.namespace(|| "lc"), || {
+        value.ok_or(SynthesisError::AssignmentMissing)
+    })?;
+    cs.enforce(
+        || "lc check",
+        |_| lc,
+        |l| l + CS::one(),
+        |l| l + num.get_variable(),
+    );
+    Ok(num)
+}
+
 /// Adds a constraint to CS, enforcing a difference relationship between the allocated numbers a, b, and difference.
 ///
 /// a - b = difference
@@ -808,15 +854,6 @@ pub(crate) fn or<CS: ConstraintSystem<F>, F: PrimeField>(
     )?))
 }
 
-#[allow(dead_code)]
-pub(crate) fn must_be_simple_bit(x: &Boolean) -> AllocatedBit {
-    match x {
-        Boolean::Constant(_) => panic!("Expected a non-constant Boolean."),
-        Boolean::Is(b) => b.clone(),
-        Boolean::Not(_) => panic!("Expected a non-negated Boolean."),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,12 +1,36 @@
-use bellpepper_core::{boolean::Boolean, num::AllocatedNum, ConstraintSystem, SynthesisError};
+//! Gadgets for Lurk data: allocating constants, checking tags, and constructing and deconstructing pointers such as
+//! tuples, conses, lists, strings, environments and bytes.
+
+use bellpepper::gadgets::multipack::pack_bits;
+use bellpepper_core::{
+    boolean::{AllocatedBit, Boolean},
+    num::AllocatedNum,
+    ConstraintSystem, SynthesisError,
+};
 use neptune::{
     circuit2::poseidon_hash_allocated as poseidon_hash,
     circuit2_witness::poseidon_hash_allocated_witness,
     poseidon::{Arity, PoseidonConstants},
 };
 
-use crate::field::LurkField;
-use crate::tag::{ContTag, ExprTag, Op1, Op2, Tag};
+use crate::{
+    field::LurkField,
+    lem::{
+        circuit::GlobalAllocator,
+        pointers::{Ptr, ZPtr},
+        store::{expect_ptrs, Store, BYTES_CHUNK_SIZE},
+        tag,
+    },
+    tag::{ContTag, ExprTag, Op1, Op2, Tag},
+};
+
+use super::{
+    constraints::{
+        alloc_equal, alloc_equal_const, boolean_to_num, decompose_le, implies_equal,
+        implies_equal_const, or, pick,
+    },
+    pointer::AllocatedPtr,
+};
 
 pub(crate) fn hash_poseidon<CS: ConstraintSystem<F>, F: LurkField, A: Arity<F>>(
     mut cs: CS,
@@ -84,3 +108,1183 @@ impl Op2 {
         )
     }
 }
+
+/// Constructs an `AllocatedPtr` compound by two others
+pub(crate) fn construct_tuple2<F: LurkField, CS: ConstraintSystem<F>, T: Tag>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    tag: &T,
+    a: &AllocatedPtr<F>,
+    b: &AllocatedPtr<F>,
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    let tag = g.alloc_tag_cloned(cs, tag);
+
+    let hash = hash_poseidon(
+        cs,
+        vec![
+            a.tag().clone(),
+            a.hash().clone(),
+            b.tag().clone(),
+            b.hash().clone(),
+        ],
+        store.poseidon_cache.constants.c4(),
+    )?;
+
+    Ok(AllocatedPtr::from_parts(tag, hash))
+}
+
+/// Constructs an `AllocatedPtr` compound by three others
+pub(crate) fn construct_tuple3<F: LurkField, CS: ConstraintSystem<F>, T: Tag>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    tag: &T,
+    a: &AllocatedPtr<F>,
+    b: &AllocatedPtr<F>,
+    c: &AllocatedPtr<F>,
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    let tag = g.alloc_tag_cloned(cs, tag);
+
+    let hash = hash_poseidon(
+        cs,
+        vec![
+            a.tag().clone(),
+            a.hash().clone(),
+            b.tag().clone(),
+            b.hash().clone(),
+            c.tag().clone(),
+            c.hash().clone(),
+        ],
+        store.poseidon_cache.constants.c6(),
+    )?;
+
+    Ok(AllocatedPtr::from_parts(tag, hash))
+}
+
+/// Constructs an `AllocatedPtr` compound by four others
+pub(crate) fn construct_tuple4<F: LurkField, CS: ConstraintSystem<F>, T: Tag>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    tag: &T,
+    a: &AllocatedPtr<F>,
+    b: &AllocatedPtr<F>,
+    c: &AllocatedPtr<F>,
+    d: &AllocatedPtr<F>,
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    let tag = g.alloc_tag_cloned(cs, tag);
+
+    let hash = hash_poseidon(
+        cs,
+        vec![
+            a.tag().clone(),
+            a.hash().clone(),
+            b.tag().clone(),
+            b.hash().clone(),
+            c.tag().clone(),
+            c.hash().clone(),
+            d.tag().clone(),
+            d.hash().clone(),
+        ],
+        store.poseidon_cache.constants.c8(),
+    )?;
+
+    Ok(AllocatedPtr::from_parts(tag, hash))
+}
+
+/// Constructs a `Cons` pointer
+#[inline]
+pub(crate) fn construct_cons<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    car: &AllocatedPtr<F>,
+    cdr: &AllocatedPtr<F>,
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    construct_tuple2(cs, g, store, &ExprTag::Cons, car, cdr)
+}
+
+/// Constructs a cons-list with the provided `elts`. The terminating value defaults
+/// to `nil` when `last` is `None`
+pub(crate) fn construct_list<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    elts: &[&AllocatedPtr<F>],
+    last: Option<AllocatedPtr<F>>,
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    let init = match last {
+        Some(last) => last,
+        None => g.alloc_ptr(cs, &store.intern_nil(), store),
+    };
+    elts.iter()
+        .rev()
+        .enumerate()
+        .try_fold(init, |acc, (i, ptr)| {
+            construct_cons(
+                &mut cs.namespace(|| format!("cons {i}")),
+                g,
+                store,
+                ptr,
+                &acc,
+            )
+        })
+}
+
+/// Allocates a `Boolean` that's true iff `ptr` has the tag `tag`
+pub(crate) fn alloc_is_tag<F: LurkField, CS: ConstraintSystem<F>, T: Tag>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    ptr: &AllocatedPtr<F>,
+    tag: &T,
+) -> Result<Boolean, SynthesisError> {
+    let tag = g.alloc_tag(cs, tag);
+    alloc_equal(cs, ptr.tag(), tag)
+}
+
+/// Enforces that `ptr` has the tag `tag` when `premise` is true
+pub(crate) fn implies_tag<F: LurkField, CS: ConstraintSystem<F>, T: Tag>(
+    cs: &mut CS,
+    premise: &Boolean,
+    ptr: &AllocatedPtr<F>,
+    tag: &T,
+) {
+    implies_equal_const(cs, premise, ptr.tag(), tag.to_field())
+}
+
+/// Deconstructs `env`, assumed to be a composition of a symbol hash, a value `Ptr`, and a next `Env` hash.
+///
+/// # Panics
+/// Panics if the store can't deconstruct the env hash.
+pub(crate) fn deconstruct_env<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    env: &AllocatedNum<F>,
+) -> Result<(AllocatedNum<F>, AllocatedPtr<F>, AllocatedNum<F>), SynthesisError> {
+    let env_zptr = ZPtr::from_parts(tag::Tag::Expr(ExprTag::Env), env.get_value().unwrap());
+    let env_ptr = s.to_ptr(&env_zptr);
+
+    let (a, b, c, d) = {
+        if let Some([v, val, new_env]) = s.pop_binding(env_ptr) {
+            let v_zptr = s.hash_ptr(&v);
+            let val_zptr = s.hash_ptr(&val);
+            let new_env_zptr = s.hash_ptr(&new_env);
+            (
+                *v_zptr.value(),
+                val_zptr.tag().to_field::<F>(),
+                *val_zptr.value(),
+                *new_env_zptr.value(),
+            )
+        } else {
+            (F::ZERO, F::ZERO, F::ZERO, F::ZERO)
+        }
+    };
+
+    let key_sym_hash = AllocatedNum::alloc_infallible(&mut cs.namespace(|| "key_sym_hash"), || a);
+    let val_tag = AllocatedNum::alloc_infallible(&mut cs.namespace(|| "val_tag"), || b);
+    let val_hash = AllocatedNum::alloc_infallible(&mut cs.namespace(|| "val_hash"), || c);
+    let new_env_hash = AllocatedNum::alloc_infallible(&mut cs.namespace(|| "new_env_hash"), || d);
+
+    let hash = hash_poseidon(
+        &mut cs.namespace(|| "hash"),
+        vec![
+            key_sym_hash.clone(),
+            val_tag.clone(),
+            val_hash.clone(),
+            new_env_hash.clone(),
+        ],
+        s.poseidon_cache.constants.c4(),
+    )?;
+
+    let val = AllocatedPtr::from_parts(val_tag, val_hash);
+
+    implies_equal(&mut cs.namespace(|| "hash equality"), not_dummy, env, &hash);
+
+    Ok((key_sym_hash, val, new_env_hash))
+}
+
+/// Retrieves the `Ptr` that corresponds to `a_ptr` by using the `Store` as the
+/// hint provider
+fn get_ptr<F: LurkField>(a_ptr: &AllocatedPtr<F>, store: &Store<F>) -> Result<Ptr, SynthesisError> {
+    let z_ptr = ZPtr::from_parts(
+        Tag::from_field(
+            &a_ptr
+                .tag()
+                .get_value()
+                .ok_or_else(|| SynthesisError::AssignmentMissing)?,
+        )
+        .ok_or_else(|| SynthesisError::Unsatisfiable)?,
+        a_ptr
+            .hash()
+            .get_value()
+            .ok_or_else(|| SynthesisError::AssignmentMissing)?,
+    );
+    Ok(store.to_ptr(&z_ptr))
+}
+
+/// Deconstructs `tuple`, assumed to be a composition of two others.
+///
+/// # Panics
+/// Panics if the store can't deconstruct the tuple pointer
+pub(crate) fn deconstruct_tuple2<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    store: &Store<F>,
+    not_dummy: &Boolean,
+    tuple: &AllocatedPtr<F>,
+) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>), SynthesisError> {
+    let (a, b) = if not_dummy.get_value() == Some(true) {
+        let idx = get_ptr(tuple, store)?.get_index2().expect("invalid Ptr");
+        let [a, b] = &expect_ptrs!(store, 2, idx);
+        (store.hash_ptr(a), store.hash_ptr(b))
+    } else {
+        (ZPtr::dummy(), ZPtr::dummy())
+    };
+
+    let a = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "a"), || a);
+    let b = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "b"), || b);
+
+    let hash = hash_poseidon(
+        &mut cs.namespace(|| "hash"),
+        vec![
+            a.tag().clone(),
+            a.hash().clone(),
+            b.tag().clone(),
+            b.hash().clone(),
+        ],
+        store.poseidon_cache.constants.c4(),
+    )?;
+
+    implies_equal(
+        &mut cs.namespace(|| "hash equality"),
+        not_dummy,
+        tuple.hash(),
+        &hash,
+    );
+
+    Ok((a, b))
+}
+
+/// Deconstructs `tuple`, assumed to be a composition of four others.
+///
+/// # Panics
+/// Panics if the store can't deconstruct the tuple pointer
+pub(crate) fn deconstruct_tuple4<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    store: &Store<F>,
+    not_dummy: &Boolean,
+    tuple: &AllocatedPtr<F>,
+) -> Result<
+    (
+        AllocatedPtr<F>,
+        AllocatedPtr<F>,
+        AllocatedPtr<F>,
+        AllocatedPtr<F>,
+    ),
+    SynthesisError,
+> {
+    let (a, b, c, d) = if not_dummy.get_value() == Some(true) {
+        let idx = get_ptr(tuple, store)?.get_index4().expect("invalid Ptr");
+        let [a, b, c, d] = &expect_ptrs!(store, 4, idx);
+        (
+            store.hash_ptr(a),
+            store.hash_ptr(b),
+            store.hash_ptr(c),
+            store.hash_ptr(d),
+        )
+    } else {
+        (ZPtr::dummy(), ZPtr::dummy(), ZPtr::dummy(), ZPtr::dummy())
+    };
+
+    let a = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "a"), || a);
+    let b = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "b"), || b);
+    let c = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "c"), || c);
+    let d = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "d"), || d);
+
+    let hash = hash_poseidon(
+        &mut cs.namespace(|| "hash"),
+        vec![
+            a.tag().clone(),
+            a.hash().clone(),
+            b.tag().clone(),
+            b.hash().clone(),
+            c.tag().clone(),
+            c.hash().clone(),
+            d.tag().clone(),
+            d.hash().clone(),
+        ],
+        store.poseidon_cache.constants.c8(),
+    )?;
+
+    implies_equal(
+        &mut cs.namespace(|| "hash equality"),
+        not_dummy,
+        tuple.hash(),
+        &hash,
+    );
+
+    Ok((a, b, c, d))
+}
+
+/// Constructs a `Bytes` pointer from the bits of its bytes, each byte given
+/// least significant bit first. As in `Store::intern_bytes`, the bytes are
+/// packed into `Num`s in chunks of `BYTES_CHUNK_SIZE`
+///
+/// # Panics
+/// Panics if the number of bits isn't a multiple of 8
+pub fn construct_bytes<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    bits: &[Boolean],
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    assert_eq!(bits.len() % 8, 0, "bits must make whole bytes");
+    let num_tag = g.alloc_tag_cloned(cs, &ExprTag::Num);
+
+    let chunks = bits
+        .chunks(8 * BYTES_CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let chunk = pack_bits(cs.namespace(|| format!("chunk {i}")), chunk)?;
+            Ok(AllocatedPtr::from_parts(num_tag.clone(), chunk))
+        })
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+    let chunks = construct_list(
+        &mut cs.namespace(|| "chunks"),
+        g,
+        store,
+        &chunks.iter().collect::<Vec<_>>(),
+        None,
+    )?;
+
+    let len = g.alloc_const_cloned(cs, F::from_u64((bits.len() / 8) as u64));
+    let len = AllocatedPtr::from_parts(num_tag, len);
+
+    construct_tuple2(
+        &mut cs.namespace(|| "bytes"),
+        g,
+        store,
+        &ExprTag::Bytes,
+        &len,
+        &chunks,
+    )
+}
+
+/// Deconstructs `bytes`, assumed to be a `Bytes` pointer with `len` bytes, into
+/// the bits of its bytes, each byte least significant bit first.
+///
+/// # Panics
+/// Panics if the store can't fetch the bytes or if there aren't `len` of them
+pub fn deconstruct_bytes<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    not_dummy: &Boolean,
+    bytes: &AllocatedPtr<F>,
+    len: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let values = if not_dummy.get_value() == Some(true) {
+        let values = store
+            .fetch_bytes(&get_ptr(bytes, store)?)
+            .expect("invalid Bytes pointer");
+        assert_eq!(values.len(), len, "unexpected number of bytes");
+        values
+    } else {
+        vec![0; len]
+    };
+
+    let mut bits = Vec::with_capacity(8 * len);
+    for (i, byte) in values.iter().enumerate() {
+        for j in 0..8 {
+            let bit = AllocatedBit::alloc(
+                cs.namespace(|| format!("byte {i} bit {j}")),
+                Some((byte >> j) & 1 == 1),
+            )?;
+            bits.push(Boolean::Is(bit));
+        }
+    }
+
+    let constructed = construct_bytes(&mut cs.namespace(|| "construct"), g, store, &bits)?;
+    implies_tag(
+        &mut cs.namespace(|| "tag equality"),
+        not_dummy,
+        bytes,
+        &ExprTag::Bytes,
+    );
+    implies_equal(
+        &mut cs.namespace(|| "hash equality"),
+        not_dummy,
+        bytes.hash(),
+        constructed.hash(),
+    );
+
+    Ok(bits)
+}
+
+/// The bytes of `ptr` if it's a `Bytes`, or a string whose characters are all below 256
+pub(crate) fn fetch_bytes_or_string<F: LurkField>(s: &Store<F>, ptr: &Ptr) -> Option<Vec<u8>> {
+    if let Some(bytes) = s.fetch_bytes(ptr) {
+        return Some(bytes);
+    }
+    s.fetch_string(ptr)?
+        .chars()
+        .map(|c| u8::try_from(c).ok())
+        .collect()
+}
+
+/// The bits of `input`, each byte least significant bit first, along with whether it's a `Bytes` of `len` bytes, as
+/// told by its length header. The bits are zeros unless it is.
+pub fn deconstruct_bytes_of_len<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    input: &AllocatedPtr<F>,
+    len: usize,
+) -> Result<(Vec<Boolean>, Boolean), SynthesisError> {
+    let is_bytes = alloc_is_tag(&mut cs.namespace(|| "is bytes"), g, input, &ExprTag::Bytes)?;
+    let bind_header = Boolean::and(&mut cs.namespace(|| "bind header"), &is_bytes, not_dummy)?;
+    let (header, _chunks) =
+        deconstruct_tuple2(&mut cs.namespace(|| "header"), s, &bind_header, input)?;
+    let expected = g.alloc_ptr(cs, &s.num_u64(len as u64), s);
+    let len_ok = header.alloc_equal(&mut cs.namespace(|| "len ok"), &expected)?;
+    let ok = Boolean::and(&mut cs.namespace(|| "ok"), &is_bytes, &len_ok)?;
+
+    let bind = Boolean::and(&mut cs.namespace(|| "bind"), &ok, not_dummy)?;
+    let bits = deconstruct_bytes(&mut cs.namespace(|| "bytes"), g, s, &bind, input, len)?;
+    // unlike the chars of strings, the bits are only bound to their witness when `bind` is true
+    let bits = bits
+        .iter()
+        .enumerate()
+        .map(|(i, bit)| Boolean::and(&mut cs.namespace(|| format!("bit {i}")), bit, &bind))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((bits, ok))
+}
+
+/// The bytes of each of `args`, or the first of them that isn't a `Bytes` of its length in `lens`. This is how
+/// coprocessors whose arguments are fixed-length byte strings, such as signature verifiers, evaluate them.
+pub(crate) fn fetch_bytes_args<F: LurkField, const N: usize>(
+    s: &Store<F>,
+    args: &[Ptr],
+    lens: [usize; N],
+) -> Result<[Vec<u8>; N], Ptr> {
+    let mut bytes = Vec::with_capacity(N);
+    for (ptr, len) in args.iter().zip(lens) {
+        bytes.push(
+            s.fetch_bytes(ptr)
+                .filter(|bytes| bytes.len() == len)
+                .ok_or(*ptr)?,
+        );
+    }
+    Ok(bytes.try_into().expect("one argument per length"))
+}
+
+/// The bits of each of `args`, as given by `deconstruct_bytes_of_len` for its length in `lens`, along with whether
+/// each of them is a `Bytes` of that length. This is the circuit of `fetch_bytes_args`.
+pub(crate) fn deconstruct_bytes_args<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    args: &[AllocatedPtr<F>],
+    lens: &[usize],
+) -> Result<(Vec<Vec<Boolean>>, Vec<Boolean>), SynthesisError> {
+    let mut bits = Vec::with_capacity(args.len());
+    let mut oks = Vec::with_capacity(args.len());
+    for (i, (arg, len)) in args.iter().zip(lens).enumerate() {
+        // the bits are zeros unless `arg` is a `Bytes` of `len` bytes
+        let (arg_bits, arg_ok) = deconstruct_bytes_of_len(
+            &mut cs.namespace(|| format!("arg {i} bytes")),
+            g,
+            s,
+            not_dummy,
+            arg,
+            *len,
+        )?;
+        bits.push(arg_bits);
+        oks.push(arg_ok);
+    }
+    Ok((bits, oks))
+}
+
+/// The result and continuation of a coprocessor given its arguments `args`, whether each of them is a `Bytes` of the
+/// expected length as returned by `deconstruct_bytes_args`, and its result `res` when they all are: `res` and `cont`
+/// if they all are, or else the first of them that isn't and the error continuation.
+pub(crate) fn pick_bytes_args_result<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    args: &[AllocatedPtr<F>],
+    oks: &[Boolean],
+    res: AllocatedPtr<F>,
+    cont: &AllocatedPtr<F>,
+) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>), SynthesisError> {
+    let mut types_ok = Boolean::Constant(true);
+    for (i, ok) in oks.iter().enumerate() {
+        types_ok = Boolean::and(cs.namespace(|| format!("types {i}")), &types_ok, ok)?;
+    }
+    let mut res = res;
+    for (i, (arg, ok)) in args.iter().zip(oks).enumerate().rev() {
+        res = AllocatedPtr::pick(cs.namespace(|| format!("result or arg {i}")), ok, &res, arg)?;
+    }
+    let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+    let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &types_ok, cont, &cont_err)?;
+    Ok((res, cont))
+}
+
+/// The bits of the codes of the characters of `input`, each least significant bit first, along with whether it's a
+/// string of `len` characters below 256. The bits are those of the characters' low bytes when it's any string.
+fn deconstruct_narrow_string<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    input: &AllocatedPtr<F>,
+    len: usize,
+) -> Result<(Vec<Boolean>, Boolean), SynthesisError> {
+    let is_str = alloc_is_tag(&mut cs.namespace(|| "is str"), g, input, &ExprTag::Str)?;
+    let bind = Boolean::and(&mut cs.namespace(|| "bind"), &is_str, not_dummy)?;
+    let (chars, rest, length) =
+        chain_car_cdr(&mut cs.namespace(|| "chars"), g, s, &bind, input, len)?;
+    let empty_str = g.alloc_ptr(cs, &s.intern_string(""), s);
+    let no_more = rest.alloc_equal(&mut cs.namespace(|| "no more chars"), &empty_str)?;
+    let full = alloc_equal_const(
+        &mut cs.namespace(|| "length"),
+        &length,
+        F::from_u64(len as u64),
+    )?;
+    let mut ok = Boolean::and(&mut cs.namespace(|| "len ok"), &no_more, &full)?;
+    ok = Boolean::and(&mut cs.namespace(|| "is str and len ok"), &is_str, &ok)?;
+
+    let zero = g.alloc_const(cs, F::ZERO);
+    let mut bits = Vec::with_capacity(8 * len);
+    for (i, char) in chars.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("char {i}"));
+        // shorter strings have `nil`s past their end
+        let is_char = alloc_is_tag(&mut cs.namespace(|| "is char"), g, char, &ExprTag::Char)?;
+        let code = pick(cs.namespace(|| "code"), &is_char, char.hash(), zero)?;
+        let code_bits = decompose_le(&mut cs.namespace(|| "code bits"), &code, 32)?;
+        let mut wide = Boolean::Constant(false);
+        for (j, bit) in code_bits[8..].iter().enumerate() {
+            wide = or(cs.namespace(|| format!("wide {j}")), &wide, bit)?;
+        }
+        ok = Boolean::and(&mut cs.namespace(|| "narrow"), &ok, &wide.not())?;
+        bits.extend_from_slice(&code_bits[..8]);
+    }
+    Ok((bits, ok))
+}
+
+/// The bits of the `len` bytes of `input`, each byte least significant bit first, along with whether `input` is a
+/// `Bytes` of `len` bytes or a string of `len` characters below 256, whose bytes are the codes of its characters.
+///
+/// Other inputs aren't deconstructed, so that callers can return an error for them as `fetch_bytes_or_string` does.
+pub fn deconstruct_bytes_or_string<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    input: &AllocatedPtr<F>,
+    len: usize,
+) -> Result<(Vec<Boolean>, Boolean), SynthesisError> {
+    let (bytes_bits, bytes_ok) =
+        deconstruct_bytes_of_len(&mut cs.namespace(|| "bytes"), g, s, not_dummy, input, len)?;
+    let (chars_bits, chars_ok) =
+        deconstruct_narrow_string(&mut cs.namespace(|| "string"), g, s, not_dummy, input, len)?;
+
+    let mut bits = Vec::with_capacity(8 * len);
+    for (i, (byte_bit, char_bit)) in bytes_bits.iter().zip(&chars_bits).enumerate() {
+        // zero unless `input` is a string
+        let char_bit = Boolean::and(
+            &mut cs.namespace(|| format!("char bit {i}")),
+            char_bit,
+            &chars_ok,
+        )?;
+        bits.push(or(
+            cs.namespace(|| format!("bit {i}")),
+            byte_bit,
+            &char_bit,
+        )?);
+    }
+
+    let ok = or(cs.namespace(|| "bytes or str"), &bytes_ok, &chars_ok)?;
+    Ok((bits, ok))
+}
+
+/// Deconstructs `data` with `car_cdr` semantics.
+///
+/// # Panics
+/// Panics if the store can't deconstruct `data` with `car_cdr`
+pub(crate) fn car_cdr<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    not_dummy: &Boolean,
+    data: &AllocatedPtr<F>,
+) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, Boolean), SynthesisError> {
+    let (car, cdr) = if not_dummy.get_value() == Some(true) {
+        let (car, cdr) = store.car_cdr(&get_ptr(data, store)?).expect("invalid Ptr");
+        (store.hash_ptr(&car), store.hash_ptr(&cdr))
+    } else {
+        (ZPtr::dummy(), ZPtr::dummy())
+    };
+
+    let nil = g.alloc_ptr(cs, &store.intern_nil(), store);
+    let empty_str = g.alloc_ptr(cs, &store.intern_string(""), store);
+
+    let car = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "car"), || car);
+    let cdr = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "cdr"), || cdr);
+
+    let data_is_nil = data.alloc_equal(&mut cs.namespace(|| "data is nil"), &nil)?;
+
+    let data_is_empty_str =
+        data.alloc_equal(&mut cs.namespace(|| "data is empty str"), &empty_str)?;
+
+    {
+        // when data is nil, we enforce that car and cdr are both nil
+        let not_dummy_and_data_is_nil = Boolean::and(
+            &mut cs.namespace(|| "not dummy and data is nil"),
+            not_dummy,
+            &data_is_nil,
+        )?;
+
+        car.implies_ptr_equal(
+            &mut cs.namespace(|| "data is nil implies car is nil"),
+            &not_dummy_and_data_is_nil,
+            &nil,
+        );
+        cdr.implies_ptr_equal(
+            &mut cs.namespace(|| "data is nil implies cdr is nil"),
+            &not_dummy_and_data_is_nil,
+            &nil,
+        );
+    }
+
+    {
+        // when data is the empty string, we enforce that car is nil and cdr is
+        // the empty string
+        let not_dummy_and_data_is_empty_str = Boolean::and(
+            &mut cs.namespace(|| "not dummy and data is empty str"),
+            not_dummy,
+            &data_is_empty_str,
+        )?;
+
+        car.implies_ptr_equal(
+            &mut cs.namespace(|| "data is empty str implies car is nil"),
+            &not_dummy_and_data_is_empty_str,
+            &nil,
+        );
+        cdr.implies_ptr_equal(
+            &mut cs.namespace(|| "data is empty str implies cdr is empty str"),
+            &not_dummy_and_data_is_empty_str,
+            &empty_str,
+        );
+    }
+
+    // data is not empty: it's not nil and it's not the empty string
+    let data_is_not_empty = Boolean::and(
+        &mut cs.namespace(|| "data is not empty"),
+        &data_is_nil.not(),
+        &data_is_empty_str.not(),
+    )?;
+
+    {
+        // when data is not empty, we enforce hash equality
+        let not_dumy_and_data_is_not_empty = Boolean::and(
+            &mut cs.namespace(|| "not dummy and data is not empty"),
+            not_dummy,
+            &data_is_not_empty,
+        )?;
+
+        let hash = hash_poseidon(
+            &mut cs.namespace(|| "hash"),
+            vec![
+                car.tag().clone(),
+                car.hash().clone(),
+                cdr.tag().clone(),
+                cdr.hash().clone(),
+            ],
+            store.poseidon_cache.constants.c4(),
+        )?;
+
+        implies_equal(
+            &mut cs.namespace(|| "data is not empty implies hash equality"),
+            &not_dumy_and_data_is_not_empty,
+            data.hash(),
+            &hash,
+        );
+    }
+
+    Ok((car, cdr, data_is_not_empty))
+}
+
+/// Chains `car_cdr` calls `n` times, returning the accumulated `car`s, the final
+/// `cdr` and the (explored) actual length (`<= n`) of the cons-like `data`. For
+/// example, calling `chain_car_cdr` on "ab" with `n = 4` should return the full
+/// actual length `2` of such string. But if `n` is set to `1`, it will return
+/// `1` as the (explored) length.
+///
+/// It can be used to deconstruct cons-lists into their elements or strings into
+/// their characters.
+///
+/// # Panics
+/// Panics if any of the reached elements can't be deconstructed with `car_cdr`
+///
+/// ```
+/// # use bellpepper_core::{boolean::Boolean, test_cs::TestConstraintSystem, ConstraintSystem};
+/// # use pasta_curves::Fq;
+///
+/// use lurk::{
+/// #    circuit::gadgets::pointer::AllocatedPtr,
+/// #    field::LurkField,
+/// #    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+///     coprocessor::gadgets::{a_ptr_as_z_ptr, chain_car_cdr},
+/// };
+///
+/// # let mut cs = TestConstraintSystem::new();
+/// # let g = GlobalAllocator::default();
+/// let store = Store::<Fq>::default();
+/// let nil = store.intern_nil();
+/// let z_nil = store.hash_ptr(&nil);
+/// let empty_str = store.intern_string("");
+/// let z_empty_str = store.hash_ptr(&empty_str);
+/// let not_dummy = Boolean::Constant(true);
+///
+/// let ab = store.intern_string("ab");
+/// let z_ab = store.hash_ptr(&ab);
+/// let a = store.char('a');
+/// let b = store.char('b');
+/// let z_a = store.hash_ptr(&a);
+/// let z_b = store.hash_ptr(&b);
+/// let a_ab = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "ab"), || z_ab);
+/// let (cars, cdr, length) = chain_car_cdr(
+///     &mut cs.namespace(|| "chain_car_cdr on ab"),
+///     &g,
+///     &store,
+///     &not_dummy,
+///     &a_ab,
+///     4,
+/// )
+/// .unwrap();
+/// assert_eq!(cars.len(), 4);
+/// assert_eq!(a_ptr_as_z_ptr(&cars[0]), Some(z_a));
+/// assert_eq!(a_ptr_as_z_ptr(&cars[1]), Some(z_b));
+/// assert_eq!(a_ptr_as_z_ptr(&cars[2]), Some(z_nil));
+/// assert_eq!(a_ptr_as_z_ptr(&cars[3]), Some(z_nil));
+/// assert_eq!(a_ptr_as_z_ptr(&cdr), Some(z_empty_str));
+/// assert_eq!(length.get_value(), Some(Fq::from_u64(2)));
+/// ```
+pub fn chain_car_cdr<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    store: &Store<F>,
+    not_dummy: &Boolean,
+    data: &AllocatedPtr<F>,
+    n: usize,
+) -> Result<(Vec<AllocatedPtr<F>>, AllocatedPtr<F>, AllocatedNum<F>), SynthesisError> {
+    let mut cars = Vec::with_capacity(n);
+    let mut cdr = data.clone();
+    let mut length = g.alloc_const_cloned(cs, F::ZERO);
+    for i in 0..n {
+        let (car, new_cdr, not_empty) = car_cdr(
+            &mut cs.namespace(|| format!("car_cdr {i}")),
+            g,
+            store,
+            not_dummy,
+            &cdr,
+        )?;
+        cars.push(car);
+        cdr = new_cdr;
+        let not_empty_num = boolean_to_num(
+            &mut cs.namespace(|| format!("not_empty_num {i}")),
+            &not_empty,
+        )?;
+        length = length.add(&mut cs.namespace(|| format!("length {i}")), &not_empty_num)?;
+    }
+    Ok((cars, cdr, length))
+}
+
+#[inline]
+pub fn a_ptr_as_z_ptr<T: Tag, F: LurkField>(
+    a: &AllocatedPtr<F>,
+) -> Option<crate::z_ptr::ZPtr<T, F>> {
+    a.tag()
+        .get_value()
+        .and_then(|t| Tag::from_field(&t))
+        .and_then(|tag| {
+            a.hash()
+                .get_value()
+                .map(|hash| crate::z_ptr::ZPtr::from_parts(tag, hash))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use bellpepper::util_cs::witness_cs::WitnessCS;
+    use bellpepper_core::{boolean::Boolean, test_cs::TestConstraintSystem, ConstraintSystem};
+    use halo2curves::bn256::Fr as Fq;
+
+    use crate::{
+        circuit::gadgets::pointer::AllocatedPtr,
+        field::LurkField,
+        lem::{
+            circuit::GlobalAllocator,
+            store::{intern_ptrs, Store},
+        },
+        tag::ExprTag,
+    };
+
+    use super::{
+        a_ptr_as_z_ptr, alloc_is_tag, car_cdr, chain_car_cdr, construct_bytes, construct_list,
+        construct_tuple2, construct_tuple3, construct_tuple4, deconstruct_bytes,
+        deconstruct_bytes_or_string, deconstruct_tuple2, deconstruct_tuple4, implies_tag,
+    };
+
+    #[test]
+    fn test_construct_tuples() {
+        let mut cs = WitnessCS::new();
+        let g = GlobalAllocator::default();
+        let store = Store::<Fq>::default();
+        let nil = store.intern_nil();
+        let nil_tag = nil.tag();
+        let a_nil = g.alloc_ptr(&mut cs, &nil, &store);
+
+        let nil2 = construct_tuple2(
+            &mut cs.namespace(|| "nil2"),
+            &g,
+            &store,
+            nil_tag,
+            &a_nil,
+            &a_nil,
+        )
+        .unwrap();
+        let nil2_ptr = intern_ptrs!(store, *nil_tag, nil, nil);
+        let z_nil2_ptr = store.hash_ptr(&nil2_ptr);
+        assert_eq!(a_ptr_as_z_ptr(&nil2), Some(z_nil2_ptr));
+
+        let nil3 = construct_tuple3(
+            &mut cs.namespace(|| "nil3"),
+            &g,
+            &store,
+            nil_tag,
+            &a_nil,
+            &a_nil,
+            &a_nil,
+        )
+        .unwrap();
+        let nil3_ptr = intern_ptrs!(store, *nil_tag, nil, nil, nil);
+        let z_nil3_ptr = store.hash_ptr(&nil3_ptr);
+        assert_eq!(a_ptr_as_z_ptr(&nil3), Some(z_nil3_ptr));
+
+        let nil4 = construct_tuple4(
+            &mut cs.namespace(|| "nil4"),
+            &g,
+            &store,
+            nil_tag,
+            &a_nil,
+            &a_nil,
+            &a_nil,
+            &a_nil,
+        )
+        .unwrap();
+        let nil4_ptr = intern_ptrs!(store, *nil_tag, nil, nil, nil, nil);
+        let z_nil4_ptr = store.hash_ptr(&nil4_ptr);
+        assert_eq!(a_ptr_as_z_ptr(&nil4), Some(z_nil4_ptr));
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut cs = TestConstraintSystem::<Fq>::new();
+        let g = GlobalAllocator::default();
+        let store = Store::<Fq>::default();
+        let string = g.alloc_ptr(&mut cs, &store.intern_string("abc"), &store);
+
+        let is_str =
+            alloc_is_tag(&mut cs.namespace(|| "is str"), &g, &string, &ExprTag::Str).unwrap();
+        let is_sym =
+            alloc_is_tag(&mut cs.namespace(|| "is sym"), &g, &string, &ExprTag::Sym).unwrap();
+        assert_eq!(is_str.get_value(), Some(true));
+        assert_eq!(is_sym.get_value(), Some(false));
+        implies_tag(
+            &mut cs.namespace(|| "str tag"),
+            &Boolean::Constant(true),
+            &string,
+            &ExprTag::Str,
+        );
+        assert!(cs.is_satisfied());
+        implies_tag(
+            &mut cs.namespace(|| "sym tag"),
+            &Boolean::Constant(true),
+            &string,
+            &ExprTag::Sym,
+        );
+        assert!(!cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_construct_list() {
+        let mut cs = WitnessCS::new();
+        let g = GlobalAllocator::default();
+        let store = Store::<Fq>::default();
+        let one = store.num_u64(1);
+        let a_one = g.alloc_ptr(&mut cs, &one, &store);
+
+        // proper list
+        let a_list = construct_list(&mut cs, &g, &store, &[&a_one, &a_one], None).unwrap();
+        let z_list = store.hash_ptr(&store.list(vec![one, one]));
+        assert_eq!(a_ptr_as_z_ptr(&a_list), Some(z_list));
+
+        // improper list
+        let a_list =
+            construct_list(&mut cs, &g, &store, &[&a_one, &a_one], Some(a_one.clone())).unwrap();
+        let z_list = store.hash_ptr(&store.improper_list(vec![one, one], one));
+        assert_eq!(a_ptr_as_z_ptr(&a_list), Some(z_list));
+    }
+
+    #[test]
+    fn deconstruct_tuples() {
+        let mut cs = TestConstraintSystem::new();
+        let store = Store::<Fq>::default();
+        let nil = store.intern_nil();
+        let z_nil = store.hash_ptr(&nil);
+        let nil_tag = *nil.tag();
+        let not_dummy = Boolean::Constant(true);
+
+        let tuple2 = intern_ptrs!(store, nil_tag, nil, nil);
+        let z_tuple2 = store.hash_ptr(&tuple2);
+        let a_tuple2 = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "tuple2"), || z_tuple2);
+        let (a, b) = deconstruct_tuple2(
+            &mut cs.namespace(|| "deconstruct tuple2"),
+            &store,
+            &not_dummy,
+            &a_tuple2,
+        )
+        .unwrap();
+        assert_eq!(a_ptr_as_z_ptr(&a), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&b), Some(z_nil));
+
+        let tuple4 = intern_ptrs!(store, nil_tag, nil, nil, nil, nil);
+        let z_tuple4 = store.hash_ptr(&tuple4);
+        let a_tuple4 = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "tuple4"), || z_tuple4);
+        let (a, b, c, d) = deconstruct_tuple4(
+            &mut cs.namespace(|| "deconstruct tuple4"),
+            &store,
+            &not_dummy,
+            &a_tuple4,
+        )
+        .unwrap();
+        assert_eq!(a_ptr_as_z_ptr(&a), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&b), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&c), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&d), Some(z_nil));
+
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_car_cdr() {
+        let mut cs = TestConstraintSystem::new();
+        let g = GlobalAllocator::default();
+        let store = Store::<Fq>::default();
+        let nil = store.intern_nil();
+        let z_nil = store.hash_ptr(&nil);
+        let empty_str = store.intern_string("");
+        let z_empty_str = store.hash_ptr(&empty_str);
+        let not_dummy = Boolean::Constant(true);
+
+        // nil
+        let a_nil = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "nil"), || z_nil);
+        let (car, cdr, not_empty) = car_cdr(
+            &mut cs.namespace(|| "car_cdr of nil"),
+            &g,
+            &store,
+            &not_dummy,
+            &a_nil,
+        )
+        .unwrap();
+        assert_eq!(a_ptr_as_z_ptr(&car), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&cdr), Some(z_nil));
+        assert_eq!(not_empty.get_value(), Some(false));
+
+        // cons
+        let one = store.num_u64(1);
+        let z_one = store.hash_ptr(&one);
+        let cons = store.cons(one, one);
+        let z_cons = store.hash_ptr(&cons);
+        let a_cons = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "cons"), || z_cons);
+        let (car, cdr, not_empty) = car_cdr(
+            &mut cs.namespace(|| "car_cdr of cons"),
+            &g,
+            &store,
+            &not_dummy,
+            &a_cons,
+        )
+        .unwrap();
+        assert_eq!(a_ptr_as_z_ptr(&car), Some(z_one));
+        assert_eq!(a_ptr_as_z_ptr(&cdr), Some(z_one));
+        assert_eq!(not_empty.get_value(), Some(true));
+
+        // empty string
+        let a_empty_str =
+            AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "empty str"), || z_empty_str);
+        let (car, cdr, not_empty) = car_cdr(
+            &mut cs.namespace(|| "car_cdr of empty str"),
+            &g,
+            &store,
+            &not_dummy,
+            &a_empty_str,
+        )
+        .unwrap();
+        assert_eq!(a_ptr_as_z_ptr(&car), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&cdr), Some(z_empty_str));
+        assert_eq!(not_empty.get_value(), Some(false));
+
+        // non-empty string
+        let abc = store.intern_string("abc");
+        let bc = store.intern_string("bc");
+        let a = store.char('a');
+        let z_abc = store.hash_ptr(&abc);
+        let z_bc = store.hash_ptr(&bc);
+        let z_a = store.hash_ptr(&a);
+        let a_abc = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "abc"), || z_abc);
+        let (car, cdr, not_empty) = car_cdr(
+            &mut cs.namespace(|| "car_cdr of abc"),
+            &g,
+            &store,
+            &not_dummy,
+            &a_abc,
+        )
+        .unwrap();
+        assert_eq!(a_ptr_as_z_ptr(&car), Some(z_a));
+        assert_eq!(a_ptr_as_z_ptr(&cdr), Some(z_bc));
+        assert_eq!(not_empty.get_value(), Some(true));
+
+        assert!(cs.is_satisfied());
+    }
+
+    #[test]
+    fn test_chain_car_cdr() {
+        let mut cs = TestConstraintSystem::new();
+        let g = GlobalAllocator::default();
+        let store = Store::<Fq>::default();
+        let nil = store.intern_nil();
+        let z_nil = store.hash_ptr(&nil);
+        let empty_str = store.intern_string("");
+        let z_empty_str = store.hash_ptr(&empty_str);
+        let not_dummy = Boolean::Constant(true);
+
+        // string
+        let ab = store.intern_string("ab");
+        let z_ab = store.hash_ptr(&ab);
+        let a = store.char('a');
+        let b = store.char('b');
+        let z_a = store.hash_ptr(&a);
+        let z_b = store.hash_ptr(&b);
+        let a_ab = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "ab"), || z_ab);
+        let (cars, cdr, length) = chain_car_cdr(
+            &mut cs.namespace(|| "chain_car_cdr on ab"),
+            &g,
+            &store,
+            &not_dummy,
+            &a_ab,
+            4,
+        )
+        .unwrap();
+        assert_eq!(cars.len(), 4);
+        assert_eq!(a_ptr_as_z_ptr(&cars[0]), Some(z_a));
+        assert_eq!(a_ptr_as_z_ptr(&cars[1]), Some(z_b));
+        assert_eq!(a_ptr_as_z_ptr(&cars[2]), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&cars[3]), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&cdr), Some(z_empty_str));
+        assert_eq!(length.get_value(), Some(Fq::from_u64(2)));
+
+        // list
+        let list = store.list(vec![ab, ab]);
+        let z_list = store.hash_ptr(&list);
+        let a_list = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "list"), || z_list);
+        let (cars, cdr, length) = chain_car_cdr(
+            &mut cs.namespace(|| "chain_car_cdr on list"),
+            &g,
+            &store,
+            &not_dummy,
+            &a_list,
+            4,
+        )
+        .unwrap();
+        assert_eq!(cars.len(), 4);
+        assert_eq!(a_ptr_as_z_ptr(&cars[0]), Some(z_ab));
+        assert_eq!(a_ptr_as_z_ptr(&cars[1]), Some(z_ab));
+        assert_eq!(a_ptr_as_z_ptr(&cars[2]), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&cars[3]), Some(z_nil));
+        assert_eq!(a_ptr_as_z_ptr(&cdr), Some(z_nil));
+        assert_eq!(length.get_value(), Some(Fq::from_u64(2)));
+    }
+
+    #[test]
+    fn test_bytes() {
+        let store = Store::<Fq>::default();
+        let not_dummy = Boolean::Constant(true);
+        for len in [0, 1, 31, 32, 100] {
+            let bytes = (0..len).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+            let ptr = store.intern_bytes(&bytes);
+            let z_ptr = store.hash_ptr(&ptr);
+
+            let mut cs = TestConstraintSystem::<Fq>::new();
+            let g = GlobalAllocator::default();
+            let a_ptr = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "bytes"), || z_ptr);
+            let bits = deconstruct_bytes(
+                &mut cs.namespace(|| "deconstruct"),
+                &g,
+                &store,
+                &not_dummy,
+                &a_ptr,
+                len,
+            )
+            .unwrap();
+            assert_eq!(8 * len, bits.len());
+            let constructed =
+                construct_bytes(&mut cs.namespace(|| "construct"), &g, &store, &bits).unwrap();
+            assert_eq!(a_ptr_as_z_ptr(&constructed), Some(z_ptr));
+            assert!(cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_bytes_or_string() {
+        let store = Store::<Fq>::default();
+        let inputs = [
+            (store.intern_bytes(b"abc"), Some(b"abc")),
+            (store.intern_string("abc"), Some(b"abc")),
+            (store.intern_bytes(b"ab"), None),
+            (store.intern_bytes(b"abcd"), None),
+            (store.intern_string("ab"), None),
+            (store.intern_string("abcd"), None),
+            (store.intern_string("ab\u{100}"), None),
+            (store.num_u64(3), None),
+            (store.intern_nil(), None),
+        ];
+        for (input, expected) in inputs {
+            let mut cs = TestConstraintSystem::<Fq>::new();
+            let g = GlobalAllocator::default();
+            let z_input = store.hash_ptr(&input);
+            let a_input = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "input"), || z_input);
+            let (bits, ok) = deconstruct_bytes_or_string(
+                &mut cs.namespace(|| "deconstruct"),
+                &g,
+                &store,
+                &Boolean::Constant(true),
+                &a_input,
+                3,
+            )
+            .unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(ok.get_value(), Some(expected.is_some()));
+            if let Some(expected) = expected {
+                let constructed =
+                    construct_bytes(&mut cs.namespace(|| "construct"), &g, &store, &bits).unwrap();
+                let z_expected = store.hash_ptr(&store.intern_bytes(expected));
+                assert_eq!(a_ptr_as_z_ptr(&constructed), Some(z_expected));
+            }
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! Gadgets for arithmetic modulo integers that don't fit in the native field, such as the fields of secp256k1.
+//!
+//! An `EmulatedNum` is a non-negative integer held as range-checked 64-bit limbs, least significant first: four by
+//! default, so below `2^256`, though `EmulatedNum::alloc_limbs` allocates wider ones. Values aren't kept reduced: most
+//! gadgets only constrain their results to be congruent to the expected value, and `EmulatedNum::reduce` additionally
+//! constrains a value to be below the modulus when a canonical one is needed, e.g. for comparisons.
+//!
+//! Congruences are stated as a `Poly`, an integer polynomial of degree at most 2 in emulated numbers, and checked by
+//! having the prover supply its quotient `q` by the modulus `m`, or checked to be zero when the modulus is itself an
+//! emulated number and its quotient is one of the products. The identity `poly + k m = q m`, where the constant
+//! `k` makes the left-hand side non-negative, is checked column by column, from the least significant limb up, with
+//! signed carries. Columns and carries stay below `2^150` in absolute value, so every column identity holds over the
+//! integers iff it holds in the native field.
+
+use bellpepper::gadgets::multipack::pack_bits;
+use bellpepper_core::{
+    boolean::Boolean, num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError,
+    Variable,
+};
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+use crate::field::LurkField;
+
+use super::constraints::{alloc_equal, alloc_is_zero, decompose_le, mul, pick};
+
+const LIMB_BITS: usize = 64;
+const NUM_LIMBS: usize = 4;
+
+/// The field element of `n`, which must be smaller than the field's modulus in absolute value
+fn to_field<F: LurkField>(n: &BigInt) -> F {
+    let (sign, bytes) = n.to_bytes_le();
+    let mut repr = F::ZERO.to_bytes();
+    repr[..bytes.len()].copy_from_slice(&bytes);
+    let f = F::from_bytes(&repr).expect("integer too large for the field");
+    if sign == Sign::Minus {
+        -f
+    } else {
+        f
+    }
+}
+
+/// The `i`th 64-bit limb of `n`
+fn limb(n: &BigUint, i: usize) -> u64 {
+    n.iter_u64_digits().nth(i).unwrap_or(0)
+}
+
+fn num_limbs(n: &BigUint) -> usize {
+    (n.bits() as usize).div_ceil(LIMB_BITS).max(1)
+}
+
+/// A non-negative integer, as limbs of 64 bits.
+#[derive(Clone)]
+pub(crate) struct EmulatedNum<F: LurkField> {
+    limbs: Vec<AllocatedNum<F>>,
+    value: Option<BigUint>,
+}
+
+impl<F: LurkField> EmulatedNum<F> {
+    /// Allocates `value` in `n` range-checked limbs, returning it along with its little-endian bits.
+    pub(crate) fn alloc_limbs<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        value: Option<BigUint>,
+        n: usize,
+    ) -> Result<(Self, Vec<Boolean>), SynthesisError> {
+        let mut limbs = Vec::with_capacity(n);
+        let mut bits = Vec::with_capacity(n * LIMB_BITS);
+        for i in 0..n {
+            let limb = AllocatedNum::alloc(cs.namespace(|| format!("limb {i}")), || {
+                let value = value.as_ref().ok_or(SynthesisError::AssignmentMissing)?;
+                Ok(F::from_u64(limb(value, i)))
+            })?;
+            bits.extend(decompose_le(
+                &mut cs.namespace(|| format!("limb {i} bits")),
+                &limb,
+                LIMB_BITS,
+            )?);
+            limbs.push(limb);
+        }
+        Ok((Self { limbs, value }, bits))
+    }
+
+    /// Allocates `value`, returning it along with its 256 little-endian bits.
+    pub(crate) fn alloc_with_bits<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        value: Option<BigUint>,
+    ) -> Result<(Self, Vec<Boolean>), SynthesisError> {
+        Self::alloc_limbs(cs, value, NUM_LIMBS)
+    }
+
+    pub(crate) fn alloc<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        value: Option<BigUint>,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self::alloc_with_bits(cs, value)?.0)
+    }
+
+    /// The number whose 256 little-endian bits are `bits`.
+    pub(crate) fn from_bits<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        bits: &[Boolean],
+    ) -> Result<Self, SynthesisError> {
+        assert_eq!(bits.len(), NUM_LIMBS * LIMB_BITS);
+        let limbs = bits
+            .chunks(LIMB_BITS)
+            .enumerate()
+            .map(|(i, bits)| pack_bits(cs.namespace(|| format!("limb {i}")), bits))
+            .collect::<Result<Vec<_>, _>>()?;
+        let value = bits.iter().rev().try_fold(BigUint::zero(), |acc, bit| {
+            Some((acc << 1) + u32::from(bit.get_value()?))
+        });
+        Ok(Self { limbs, value })
+    }
+
+    /// Allocates the constant `value`.
+    pub(crate) fn constant<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        value: &BigUint,
+    ) -> Result<Self, SynthesisError> {
+        Self::constant_limbs(cs, value, NUM_LIMBS)
+    }
+
+    /// Allocates the constant `value` in `n` limbs.
+    pub(crate) fn constant_limbs<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        value: &BigUint,
+        n: usize,
+    ) -> Result<Self, SynthesisError> {
+        assert!(value.bits() as usize <= n * LIMB_BITS);
+        let limbs = (0..n)
+            .map(|i| {
+                let c = F::from_u64(limb(value, i));
+                let limb =
+                    AllocatedNum::alloc_infallible(cs.namespace(|| format!("limb {i}")), || c);
+                cs.enforce(
+                    || format!("limb {i} is constant"),
+                    |lc| lc + limb.get_variable(),
+                    |lc| lc + CS::one(),
+                    |lc| lc + (c, CS::one()),
+                );
+                limb
+            })
+            .collect();
+        Ok(Self {
+            limbs,
+            value: Some(value.clone()),
+        })
+    }
+
+    pub(crate) fn value(&self) -> Option<&BigUint> {
+        self.value.as_ref()
+    }
+
+    pub(crate) fn limbs(&self) -> &[AllocatedNum<F>] {
+        &self.limbs
+    }
+
+    /// A bound on the value, from the number of limbs
+    fn bound(&self) -> BigInt {
+        BigInt::one() << (self.limbs.len() * LIMB_BITS)
+    }
+
+    /// `a` if `condition` is true, and `b` otherwise, which must have as many limbs.
+    pub(crate) fn pick<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        condition: &Boolean,
+        a: &Self,
+        b: &Self,
+    ) -> Result<Self, SynthesisError> {
+        assert_eq!(a.limbs.len(), b.limbs.len());
+        let limbs = a
+            .limbs
+            .iter()
+            .zip(&b.limbs)
+            .enumerate()
+            .map(|(i, (a, b))| pick(cs.namespace(|| format!("limb {i}")), condition, a, b))
+            .collect::<Result<_, _>>()?;
+        let value =
+            condition
+                .get_value()
+                .and_then(|c| if c { a.value.clone() } else { b.value.clone() });
+        Ok(Self { limbs, value })
+    }
+
+    /// Whether `self` and `other` are the same integer. Only meaningful for reduced values.
+    pub(crate) fn alloc_equal<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        let mut equal = Boolean::Constant(true);
+        for (i, (a, b)) in self.limbs.iter().zip(&other.limbs).enumerate() {
+            let limb_equal = alloc_equal(cs.namespace(|| format!("limb {i} equal")), a, b)?;
+            equal = Boolean::and(
+                cs.namespace(|| format!("limbs {i} equal")),
+                &equal,
+                &limb_equal,
+            )?;
+        }
+        Ok(equal)
+    }
+
+    /// Whether `self` is zero. Only meaningful for reduced values.
+    pub(crate) fn alloc_is_zero<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<Boolean, SynthesisError> {
+        let mut zero = Boolean::Constant(true);
+        for (i, limb) in self.limbs.iter().enumerate() {
+            let limb_zero = alloc_is_zero(cs.namespace(|| format!("limb {i} zero")), limb)?;
+            zero = Boolean::and(
+                cs.namespace(|| format!("limbs {i} zero")),
+                &zero,
+                &limb_zero,
+            )?;
+        }
+        Ok(zero)
+    }
+
+    /// The value in `[0, m)` congruent to `self` modulo `m`.
+    pub(crate) fn reduce<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        m: &BigUint,
+    ) -> Result<Self, SynthesisError> {
+        Poly::new().term(1, self).alloc_reduced(cs, m)
+    }
+
+    /// Enforces `self < m`.
+    fn enforce_lt<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        m: &BigUint,
+    ) -> Result<(), SynthesisError> {
+        // `self + d = m - 1` for some `d >= 0`
+        let m_1 = m - 1u32;
+        let d_value = self.value.as_ref().map(|v| {
+            if *v <= m_1 {
+                &m_1 - v
+            } else {
+                // unsatisfiable
+                BigUint::zero()
+            }
+        });
+        let d = Self::alloc(&mut cs.namespace(|| "difference"), d_value)?;
+        Poly::new()
+            .term(1, self)
+            .term(1, &d)
+            .constant(-BigInt::from(m_1))
+            .enforce(&mut cs.namespace(|| "sum"), None)
+    }
+}
+
+/// The columns of an identity between integers given as limbs, as linear combinations along with their values
+struct Columns<F: LurkField> {
+    lcs: Vec<LinearCombination<F>>,
+    values: Vec<Option<BigInt>>,
+}
+
+impl<F: LurkField> Default for Columns<F> {
+    fn default() -> Self {
+        Self {
+            lcs: vec![],
+            values: vec![],
+        }
+    }
+}
+
+impl<F: LurkField> Columns<F> {
+    /// Adds `coeff var`, whose value is `value`, to the `k`th column
+    fn add(&mut self, k: usize, coeff: F, var: Variable, value: Option<BigInt>) {
+        if self.lcs.len() <= k {
+            self.lcs.resize(k + 1, LinearCombination::zero());
+            self.values.resize(k + 1, Some(BigInt::zero()));
+        }
+        let lc = std::mem::replace(&mut self.lcs[k], LinearCombination::zero());
+        self.lcs[k] = lc + (coeff, var);
+        self.values[k] = self.values[k].take().zip(value).map(|(a, b)| a + b);
+    }
+}
+
+/// An integer polynomial `Σ c a b + Σ c a + k` in emulated numbers, with small coefficients.
+#[derive(Clone)]
+pub(crate) struct Poly<'a, F: LurkField> {
+    products: Vec<(i64, &'a EmulatedNum<F>, &'a EmulatedNum<F>)>,
+    terms: Vec<(i64, &'a EmulatedNum<F>)>,
+    constant: BigInt,
+}
+
+impl<'a, F: LurkField> Poly<'a, F> {
+    pub(crate) fn new() -> Self {
+        Self {
+            products: vec![],
+            terms: vec![],
+            constant: BigInt::zero(),
+        }
+    }
+
+    /// Adds `c a b`
+    pub(crate) fn product(mut self, c: i64, a: &'a EmulatedNum<F>, b: &'a EmulatedNum<F>) -> Self {
+        self.products.push((c, a, b));
+        self
+    }
+
+    /// Adds `c a`
+    pub(crate) fn term(mut self, c: i64, a: &'a EmulatedNum<F>) -> Self {
+        self.terms.push((c, a));
+        self
+    }
+
+    /// Adds `k`
+    pub(crate) fn constant(mut self, k: BigInt) -> Self {
+        self.constant += k;
+        self
+    }
+
+    fn value(&self) -> Option<BigInt> {
+        let mut value = self.constant.clone();
+        for (c, a, b) in &self.products {
+            value += BigInt::from(*c) * BigInt::from(a.value()? * b.value()?);
+        }
+        for (c, a) in &self.terms {
+            value += BigInt::from(*c) * BigInt::from(a.value()?.clone());
+        }
+        Some(value)
+    }
+
+    /// Enforces `self = 0`.
+    pub(crate) fn enforce_zero<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<(), SynthesisError> {
+        self.enforce(cs, None)
+    }
+
+    /// Enforces `self ≡ 0 (mod m)`.
+    pub(crate) fn enforce_zero_mod<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        m: &BigUint,
+    ) -> Result<(), SynthesisError> {
+        self.enforce(cs, Some(m))
+    }
+
+    /// Allocates a value congruent to `self` modulo `m`, which is only reduced if the prover is honest.
+    pub(crate) fn alloc_congruent<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        m: &BigUint,
+    ) -> Result<EmulatedNum<F>, SynthesisError> {
+        let value = self.value().map(|v| {
+            v.mod_floor(&BigInt::from(m.clone()))
+                .to_biguint()
+                .expect("non-negative")
+        });
+        let res = EmulatedNum::alloc(&mut cs.namespace(|| "result"), value)?;
+        self.clone()
+            .term(-1, &res)
+            .enforce(&mut cs.namespace(|| "congruence"), Some(m))?;
+        Ok(res)
+    }
+
+    /// Allocates the value in `[0, m)` congruent to `self` modulo `m`.
+    pub(crate) fn alloc_reduced<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        m: &BigUint,
+    ) -> Result<EmulatedNum<F>, SynthesisError> {
+        let res = self.alloc_congruent(cs, m)?;
+        res.enforce_lt(&mut cs.namespace(|| "reduced"), m)?;
+        Ok(res)
+    }
+
+    /// Enforces `self = q m` for some `q >= 0` supplied by the prover, or `self = 0` without a modulus.
+    fn enforce<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        m: Option<&BigUint>,
+    ) -> Result<(), SynthesisError> {
+        let limb_bound = BigInt::one() << LIMB_BITS;
+        // bounds on the negative and positive parts of the polynomial
+        let (mut neg, mut pos) = (BigInt::zero(), BigInt::zero());
+        let mut add_bound = |c: i64, bound: BigInt| {
+            if c < 0 {
+                neg += BigInt::from(-c) * bound;
+            } else {
+                pos += BigInt::from(c) * bound;
+            }
+        };
+        for (c, a, b) in &self.products {
+            add_bound(*c, a.bound() * b.bound());
+        }
+        for (c, a) in &self.terms {
+            add_bound(*c, a.bound());
+        }
+        if self.constant.is_negative() {
+            neg -= &self.constant;
+        } else {
+            pos += &self.constant;
+        }
+
+        let (constant, quotient) = match m {
+            Some(m) => {
+                let m_int = BigInt::from(m.clone());
+                // adding `k m` makes the polynomial non-negative, hence its quotient too
+                let shift = (neg + &m_int - 1) / &m_int * &m_int;
+                let q_bound = ((pos + &shift) / &m_int)
+                    .to_biguint()
+                    .expect("non-negative");
+                let q_value = self
+                    .value()
+                    .and_then(|v| ((v + &shift).div_floor(&m_int)).to_biguint());
+                let (q, _) = EmulatedNum::alloc_limbs(
+                    &mut cs.namespace(|| "quotient"),
+                    q_value,
+                    num_limbs(&q_bound),
+                )?;
+                (&self.constant + shift, Some((q, m)))
+            }
+            None => (self.constant.clone(), None),
+        };
+
+        let mut columns = Columns::default();
+        let mut column_bound = BigInt::zero();
+        for (n, (c, a, b)) in self.products.iter().enumerate() {
+            let coeff = to_field::<F>(&BigInt::from(*c));
+            for (i, a_limb) in a.limbs.iter().enumerate() {
+                for (j, b_limb) in b.limbs.iter().enumerate() {
+                    let product = mul(
+                        cs.namespace(|| format!("product {n} limbs {i} {j}")),
+                        a_limb,
+                        b_limb,
+                    )?;
+                    let value = a
+                        .value()
+                        .zip(b.value())
+                        .map(|(a, b)| BigInt::from(*c) * limb(a, i) * limb(b, j));
+                    columns.add(i + j, coeff, product.get_variable(), value);
+                }
+            }
+            let terms = a.limbs.len().min(b.limbs.len());
+            column_bound += BigInt::from(c.unsigned_abs()) * terms * &limb_bound * &limb_bound;
+        }
+        for (c, a) in &self.terms {
+            let coeff = to_field::<F>(&BigInt::from(*c));
+            for (i, a_limb) in a.limbs.iter().enumerate() {
+                let value = a.value().map(|a| BigInt::from(*c) * limb(a, i));
+                columns.add(i, coeff, a_limb.get_variable(), value);
+            }
+            column_bound += BigInt::from(c.unsigned_abs()) * &limb_bound;
+        }
+        let constant_abs = constant.magnitude();
+        for i in 0..num_limbs(constant_abs) {
+            let limb = BigInt::from_biguint(constant.sign(), limb(constant_abs, i).into());
+            columns.add(i, to_field(&limb), CS::one(), Some(limb));
+        }
+        column_bound += &limb_bound;
+        if let Some((q, m)) = &quotient {
+            for (i, q_limb) in q.limbs.iter().enumerate() {
+                for j in 0..num_limbs(m) {
+                    let m_limb = BigInt::from(limb(m, j));
+                    let value = q.value().map(|q| -&m_limb * limb(q, i));
+                    columns.add(i + j, -to_field::<F>(&m_limb), q_limb.get_variable(), value);
+                }
+            }
+            let terms = q.limbs.len().min(num_limbs(m));
+            column_bound += BigInt::from(terms) * &limb_bound * &limb_bound;
+        }
+
+        // `|carry| <= (column_bound + |carry|) / 2^64`, so carries are below `2^carry_bits` in absolute value
+        let carry_bits = column_bound.bits() as usize + 1 - LIMB_BITS;
+        assert!(carry_bits + LIMB_BITS + 2 < F::CAPACITY as usize);
+        let carry_shift = BigInt::one() << carry_bits;
+        let (mut carry_lc, mut carry_value) = (LinearCombination::zero(), Some(BigInt::zero()));
+        let last = columns.lcs.len() - 1;
+        for (k, (lc, value)) in columns.lcs.into_iter().zip(columns.values).enumerate() {
+            let sum_lc = lc + &carry_lc;
+            let sum_value = value.zip(carry_value).map(|(a, b)| a + b);
+            if k == last {
+                cs.enforce(
+                    || format!("column {k}"),
+                    |_| sum_lc,
+                    |lc| lc + CS::one(),
+                    |lc| lc,
+                );
+                break;
+            }
+            // `sum = 2^64 carry`, with `carry + 2^carry_bits` range-checked
+            carry_value = sum_value.map(|v| v >> LIMB_BITS);
+            let shifted = AllocatedNum::alloc(cs.namespace(|| format!("carry {k}")), || {
+                let value = carry_value
+                    .as_ref()
+                    .ok_or(SynthesisError::AssignmentMissing)?;
+                Ok(to_field(&(value + &carry_shift)))
+            })?;
+            decompose_le(
+                &mut cs.namespace(|| format!("carry {k} range")),
+                &shifted,
+                carry_bits + 1,
+            )?;
+            carry_lc = LinearCombination::zero() + shifted.get_variable()
+                - (to_field(&carry_shift), CS::one());
+            let two_64 = to_field::<F>(&limb_bound);
+            cs.enforce(
+                || format!("column {k}"),
+                |_| {
+                    sum_lc - (two_64, shifted.get_variable())
+                        + (two_64 * to_field::<F>(&carry_shift), CS::one())
+                },
+                |lc| lc + CS::one(),
+                |lc| lc,
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr;
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    fn modulus() -> BigUint {
+        // secp256k1's base field
+        BigUint::parse_bytes(
+            b"fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f",
+            16,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_congruences() {
+        let rng = &mut XorShiftRng::seed_from_u64(0);
+        let m = modulus();
+        for _ in 0..10 {
+            let cs = &mut TestConstraintSystem::<Fr>::new();
+            let [a_value, b_value, c_value] =
+                [(); 3].map(|_| BigUint::from_bytes_le(&rng.gen::<[u8; 32]>()));
+            let a = EmulatedNum::alloc(&mut cs.namespace(|| "a"), Some(a_value.clone())).unwrap();
+            let b = EmulatedNum::alloc(&mut cs.namespace(|| "b"), Some(b_value.clone())).unwrap();
+            let c = EmulatedNum::alloc(&mut cs.namespace(|| "c"), Some(c_value.clone())).unwrap();
+            // `a b - 3 c^2 + 5 a - 7`
+            let res = Poly::new()
+                .product(1, &a, &b)
+                .product(-3, &c, &c)
+                .term(5, &a)
+                .constant(BigInt::from(-7))
+                .alloc_reduced(&mut cs.namespace(|| "poly"), &m)
+                .unwrap();
+            let expected = (BigInt::from(&a_value * &b_value)
+                - 3 * BigInt::from(&c_value * &c_value)
+                + 5 * BigInt::from(a_value.clone())
+                - 7)
+            .mod_floor(&BigInt::from(m.clone()));
+            assert_eq!(expected.to_biguint().as_ref(), res.value());
+            assert!(cs.is_satisfied());
+
+            let reduced_a = a.reduce(&mut cs.namespace(|| "reduce a"), &m).unwrap();
+            assert_eq!(Some(&(&a_value % &m)), reduced_a.value());
+            assert!(cs.is_satisfied());
+        }
+    }
+
+    #[test]
+    fn test_wrong_results() {
+        let m = modulus();
+        let cs = &mut TestConstraintSystem::<Fr>::new();
+        let a = EmulatedNum::alloc(&mut cs.namespace(|| "a"), Some(m.clone() - 1u32)).unwrap();
+        let b = EmulatedNum::alloc(&mut cs.namespace(|| "b"), Some(BigUint::from(2u32))).unwrap();
+        // `(m - 1) 2 ≡ m - 2`, so claiming `m - 3` must fail
+        let wrong =
+            EmulatedNum::alloc(&mut cs.namespace(|| "wrong"), Some(m.clone() - 3u32)).unwrap();
+        Poly::new()
+            .product(1, &a, &b)
+            .term(-1, &wrong)
+            .enforce_zero_mod(&mut cs.namespace(|| "congruence"), &m)
+            .unwrap();
+        assert!(!cs.is_satisfied());
+
+        // `m + 1` is congruent to 1 but isn't reduced
+        let cs = &mut TestConstraintSystem::<Fr>::new();
+        let big = EmulatedNum::alloc(&mut cs.namespace(|| "big"), Some(m.clone() + 1u32)).unwrap();
+        big.enforce_lt(&mut cs.namespace(|| "lt"), &m).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+}
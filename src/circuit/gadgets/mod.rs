@@ -5,5 +5,8 @@ pub(crate) mod case;
 pub mod circom;
 pub mod constraints;
 pub(crate) mod data;
+pub(crate) mod emulated;
 pub(crate) mod hashes;
 pub mod pointer;
+pub(crate) mod signed;
+pub mod unsigned;
@@ -0,0 +1,210 @@
+//! Gadgets for signed 64-bit integers.
+//!
+//! An `i64` is represented in the circuit by the field element of its two's-complement bits, i.e. by an integer in
+//! `[0, 2^64)` whose bit 63 is the sign. Arithmetic wraps around like Rust's `wrapping_*` operations: the exact
+//! result is computed in the field, decomposed into bits and truncated to the lowest 64.
+
+use bellpepper_core::{
+    boolean::Boolean, num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError,
+};
+
+use crate::field::LurkField;
+
+use super::constraints::{alloc_lc, decompose_le, implies_pack, mul};
+
+/// Allocates the number whose little-endian bits are `bits`.
+pub(super) fn pack<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    bits: &[Boolean],
+) -> Result<AllocatedNum<F>, SynthesisError> {
+    let num = AllocatedNum::alloc(cs.namespace(|| "pack"), || {
+        bits.iter().rev().try_fold(F::ZERO, |acc, b| {
+            let b = b.get_value().ok_or(SynthesisError::AssignmentMissing)?;
+            Ok(acc.double() + if b { F::ONE } else { F::ZERO })
+        })
+    })?;
+    implies_pack(
+        &mut cs.namespace(|| "pack check"),
+        &Boolean::Constant(true),
+        bits,
+        &num,
+    );
+    Ok(num)
+}
+
+#[inline]
+pub(super) fn two_64<F: LurkField>() -> F {
+    F::from_u64(u64::MAX) + F::ONE
+}
+
+/// A range-checked `i64`, together with its two's-complement bits.
+#[derive(Clone)]
+pub(crate) struct AllocatedI64<F: LurkField> {
+    num: AllocatedNum<F>,
+    bits: Vec<Boolean>,
+}
+
+impl<F: LurkField> AllocatedI64<F> {
+    /// Enforces `num` to be the two's-complement representation of an `i64`.
+    pub(crate) fn from_num<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        num: AllocatedNum<F>,
+    ) -> Result<Self, SynthesisError> {
+        let bits = decompose_le(&mut cs.namespace(|| "range check"), &num, 64)?;
+        Ok(Self { num, bits })
+    }
+
+    fn from_low_bits<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        mut bits: Vec<Boolean>,
+    ) -> Result<Self, SynthesisError> {
+        bits.truncate(64);
+        let num = pack(cs, &bits)?;
+        Ok(Self { num, bits })
+    }
+
+    pub(crate) fn num(&self) -> &AllocatedNum<F> {
+        &self.num
+    }
+
+    /// True iff the integer is negative.
+    pub(crate) fn sign(&self) -> &Boolean {
+        &self.bits[63]
+    }
+
+    fn sign_value(&self) -> Option<F> {
+        self.sign()
+            .get_value()
+            .map(|b| if b { F::ONE } else { F::ZERO })
+    }
+
+    /// `self + other`, wrapping on overflow.
+    pub(crate) fn add<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let sum = self.num.add(&mut cs.namespace(|| "sum"), &other.num)?;
+        let bits = decompose_le(&mut cs.namespace(|| "sum bits"), &sum, 65)?;
+        Self::from_low_bits(&mut cs.namespace(|| "wrapped sum"), bits)
+    }
+
+    /// `self - other`, wrapping on overflow.
+    pub(crate) fn sub<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        // `self - other + 2^64` is in `(0, 2^65)` and is congruent to the result modulo `2^64`
+        let value = self
+            .num
+            .get_value()
+            .zip(other.num.get_value())
+            .map(|(a, b)| a - b + two_64::<F>());
+        let lc = LinearCombination::zero() + self.num.get_variable() - other.num.get_variable()
+            + (two_64(), CS::one());
+        let diff = alloc_lc(&mut cs.namespace(|| "difference"), value, lc)?;
+        let bits = decompose_le(&mut cs.namespace(|| "difference bits"), &diff, 65)?;
+        Self::from_low_bits(&mut cs.namespace(|| "wrapped difference"), bits)
+    }
+
+    /// `self * other`, wrapping on overflow.
+    pub(crate) fn mul<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        // the product of the unsigned representations is below `2^128`, so it doesn't overflow the field
+        let product = mul(&mut cs.namespace(|| "product"), &self.num, &other.num)?;
+        let bits = decompose_le(&mut cs.namespace(|| "product bits"), &product, 128)?;
+        Self::from_low_bits(&mut cs.namespace(|| "wrapped product"), bits)
+    }
+
+    /// Whether `self < other` as signed integers.
+    pub(crate) fn lt<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        // Flipping the sign bits maps the signed order onto the unsigned one. With `a' = a + 2^63 (1 - 2 s_a)` and
+        // likewise for `b'`, the difference `a' - b' + 2^64 = a - b + 2^64 (1 + s_b - s_a)` lies in `[1, 2^65)`,
+        // and its bit 64 is unset iff `a' < b'`.
+        let two_64 = two_64::<F>();
+        let value = self
+            .num
+            .get_value()
+            .zip(other.num.get_value())
+            .zip(self.sign_value().zip(other.sign_value()))
+            .map(|((a, b), (s_a, s_b))| a - b + two_64 * (F::ONE + s_b - s_a));
+        let lc = LinearCombination::zero() + self.num.get_variable() - other.num.get_variable()
+            + (two_64, CS::one());
+        let lc = other.sign().lc(CS::one(), two_64) + &lc;
+        let lc = lc - &self.sign().lc(CS::one(), two_64);
+        let diff = alloc_lc(&mut cs.namespace(|| "shifted difference"), value, lc)?;
+        let bits = decompose_le(&mut cs.namespace(|| "shifted difference bits"), &diff, 65)?;
+        Ok(bits[64].not())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr;
+    use proptest::prelude::*;
+
+    fn alloc_i64(cs: &mut TestConstraintSystem<Fr>, name: &str, x: i64) -> AllocatedI64<Fr> {
+        let num = AllocatedNum::alloc_infallible(cs.namespace(|| format!("{name} num")), || {
+            Fr::from_u64(x as u64)
+        });
+        AllocatedI64::from_num(&mut cs.namespace(|| name.to_string()), num).unwrap()
+    }
+
+    fn value(x: &AllocatedI64<Fr>) -> i64 {
+        x.num().get_value().unwrap().to_u64().unwrap() as i64
+    }
+
+    fn check_ops(a: i64, b: i64) {
+        let cs = &mut TestConstraintSystem::<Fr>::new();
+        let a_i64 = alloc_i64(cs, "a", a);
+        let b_i64 = alloc_i64(cs, "b", b);
+        assert_eq!(a < 0, a_i64.sign().get_value().unwrap());
+
+        let sum = a_i64.add(&mut cs.namespace(|| "add"), &b_i64).unwrap();
+        let diff = a_i64.sub(&mut cs.namespace(|| "sub"), &b_i64).unwrap();
+        let product = a_i64.mul(&mut cs.namespace(|| "mul"), &b_i64).unwrap();
+        let lt = a_i64.lt(&mut cs.namespace(|| "lt"), &b_i64).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(a.wrapping_add(b), value(&sum));
+        assert_eq!(a.wrapping_sub(b), value(&diff));
+        assert_eq!(a.wrapping_mul(b), value(&product));
+        assert_eq!(a < b, lt.get_value().unwrap());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_i64_ops(a in any::<i64>(), b in any::<i64>()) {
+            check_ops(a, b);
+        }
+    }
+
+    #[test]
+    fn test_i64_edge_cases() {
+        let edges = [i64::MIN, i64::MIN + 1, -1, 0, 1, i64::MAX - 1, i64::MAX];
+        for a in edges {
+            for b in edges {
+                check_ops(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_i64_range_check() {
+        let cs = &mut TestConstraintSystem::<Fr>::new();
+        let num = AllocatedNum::alloc_infallible(cs.namespace(|| "2^64"), two_64::<Fr>);
+        AllocatedI64::from_num(&mut cs.namespace(|| "i64"), num).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+}
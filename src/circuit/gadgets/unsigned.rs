@@ -0,0 +1,291 @@
+//! Gadgets for unsigned 64-bit integers.
+//!
+//! A `u64` is represented in the circuit by the field element of its value, range-checked through its bits. Unlike the
+//! signed gadgets, operations don't silently wrap: they also return the carry, borrow or high half that Rust's
+//! `overflowing_*` and widening operations would.
+//!
+//! They're meant for the circuits of coprocessors and queries, which is why they're public.
+
+use bellpepper_core::{
+    boolean::Boolean, num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError,
+};
+
+use crate::field::LurkField;
+
+use super::{
+    constraints::{
+        alloc_equal, alloc_is_zero, alloc_lc, decompose_le, enforce_implication,
+        implies_equal_const, mul,
+    },
+    signed::{pack, two_64},
+};
+
+/// A range-checked `u64`, together with its little-endian bits.
+#[derive(Clone)]
+pub struct AllocatedU64<F: LurkField> {
+    num: AllocatedNum<F>,
+    bits: Vec<Boolean>,
+}
+
+impl<F: LurkField> AllocatedU64<F> {
+    /// Enforces `num` to be a `u64`.
+    pub fn from_num<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        num: AllocatedNum<F>,
+    ) -> Result<Self, SynthesisError> {
+        let bits = decompose_le(&mut cs.namespace(|| "range check"), &num, 64)?;
+        Ok(Self { num, bits })
+    }
+
+    /// Allocates the `u64` `value`, enforcing its range.
+    pub fn alloc<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        value: Option<u64>,
+    ) -> Result<Self, SynthesisError> {
+        let num = AllocatedNum::alloc(cs.namespace(|| "num"), || {
+            value
+                .map(F::from_u64)
+                .ok_or(SynthesisError::AssignmentMissing)
+        })?;
+        Self::from_num(cs, num)
+    }
+
+    fn from_bits<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        bits: Vec<Boolean>,
+    ) -> Result<Self, SynthesisError> {
+        debug_assert_eq!(bits.len(), 64);
+        let num = pack(cs, &bits)?;
+        Ok(Self { num, bits })
+    }
+
+    pub fn num(&self) -> &AllocatedNum<F> {
+        &self.num
+    }
+
+    pub fn bits(&self) -> &[Boolean] {
+        &self.bits
+    }
+
+    pub fn get_value(&self) -> Option<u64> {
+        self.num.get_value().and_then(|f| f.to_u64())
+    }
+
+    /// `self + other` along with whether it overflowed, like `u64::overflowing_add`.
+    pub fn add_with_carry<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<(Self, Boolean), SynthesisError> {
+        let sum = self.num.add(&mut cs.namespace(|| "sum"), &other.num)?;
+        let mut bits = decompose_le(&mut cs.namespace(|| "sum bits"), &sum, 65)?;
+        let carry = bits.pop().expect("65 bits");
+        let sum = Self::from_bits(&mut cs.namespace(|| "wrapped sum"), bits)?;
+        Ok((sum, carry))
+    }
+
+    /// `self - other` along with whether it underflowed, like `u64::overflowing_sub`.
+    pub fn sub_with_borrow<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<(Self, Boolean), SynthesisError> {
+        // `self - other + 2^64` is in `(0, 2^65)`, and its bit 64 is unset iff `self < other`
+        let value = self
+            .num
+            .get_value()
+            .zip(other.num.get_value())
+            .map(|(a, b)| a - b + two_64::<F>());
+        let lc = LinearCombination::zero() + self.num.get_variable() - other.num.get_variable()
+            + (two_64(), CS::one());
+        let diff = alloc_lc(&mut cs.namespace(|| "difference"), value, lc)?;
+        let mut bits = decompose_le(&mut cs.namespace(|| "difference bits"), &diff, 65)?;
+        let borrow = bits.pop().expect("65 bits").not();
+        let diff = Self::from_bits(&mut cs.namespace(|| "wrapped difference"), bits)?;
+        Ok((diff, borrow))
+    }
+
+    /// The low and high halves of the 128-bit product `self * other`.
+    pub fn mul_wide<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<(Self, Self), SynthesisError> {
+        // the product is below `2^128`, so it doesn't overflow the field
+        let product = mul(&mut cs.namespace(|| "product"), &self.num, &other.num)?;
+        let mut bits = decompose_le(&mut cs.namespace(|| "product bits"), &product, 128)?;
+        let high = bits.split_off(64);
+        let low = Self::from_bits(&mut cs.namespace(|| "low"), bits)?;
+        let high = Self::from_bits(&mut cs.namespace(|| "high"), high)?;
+        Ok((low, high))
+    }
+
+    /// The quotient and remainder of `self / other`. Dividing by zero results in a quotient of zero and a remainder
+    /// of `self`, so callers that must reject it should check `other.is_zero` themselves.
+    pub fn div_rem<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<(Self, Self), SynthesisError> {
+        let (q, r) = match (self.get_value(), other.get_value()) {
+            (Some(a), Some(0)) => (Some(0), Some(a)),
+            (Some(a), Some(b)) => (Some(a / b), Some(a % b)),
+            _ => (None, None),
+        };
+        let q = Self::alloc(&mut cs.namespace(|| "quotient"), q)?;
+        let r = Self::alloc(&mut cs.namespace(|| "remainder"), r)?;
+
+        // `q * b + r` is below `2^128`, so this equation holds over the integers
+        cs.enforce(
+            || "q * b = a - r",
+            |lc| lc + q.num.get_variable(),
+            |lc| lc + other.num.get_variable(),
+            |lc| lc + self.num.get_variable() - r.num.get_variable(),
+        );
+
+        let divisor_is_zero = other.is_zero(&mut cs.namespace(|| "divisor is zero"))?;
+        implies_equal_const(
+            &mut cs.namespace(|| "zero quotient"),
+            &divisor_is_zero,
+            &q.num,
+            F::ZERO,
+        );
+        let r_lt_b = r.lt(&mut cs.namespace(|| "r < b"), other)?;
+        enforce_implication(
+            cs.namespace(|| "nonzero divisor implies r < b"),
+            &divisor_is_zero.not(),
+            &r_lt_b,
+        );
+
+        Ok((q, r))
+    }
+
+    /// Whether `self` is zero.
+    pub fn is_zero<CS: ConstraintSystem<F>>(&self, cs: &mut CS) -> Result<Boolean, SynthesisError> {
+        alloc_is_zero(cs, &self.num)
+    }
+
+    /// Whether `self == other`.
+    pub fn eq<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        alloc_equal(cs, &self.num, &other.num)
+    }
+
+    /// Whether `self < other`.
+    pub fn lt<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        let (_, borrow) = self.sub_with_borrow(cs, other)?;
+        Ok(borrow)
+    }
+
+    /// Whether `self <= other`.
+    pub fn le<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        Ok(other.lt(cs, self)?.not())
+    }
+
+    /// Whether `self > other`.
+    pub fn gt<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        other.lt(cs, self)
+    }
+
+    /// Whether `self >= other`.
+    pub fn ge<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Boolean, SynthesisError> {
+        Ok(self.lt(cs, other)?.not())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr;
+    use proptest::prelude::*;
+
+    fn alloc_u64(cs: &mut TestConstraintSystem<Fr>, name: &str, x: u64) -> AllocatedU64<Fr> {
+        AllocatedU64::alloc(&mut cs.namespace(|| name.to_string()), Some(x)).unwrap()
+    }
+
+    fn check_ops(a: u64, b: u64) {
+        let cs = &mut TestConstraintSystem::<Fr>::new();
+        let a_u64 = alloc_u64(cs, "a", a);
+        let b_u64 = alloc_u64(cs, "b", b);
+
+        let (sum, carry) = a_u64
+            .add_with_carry(&mut cs.namespace(|| "add"), &b_u64)
+            .unwrap();
+        let (diff, borrow) = a_u64
+            .sub_with_borrow(&mut cs.namespace(|| "sub"), &b_u64)
+            .unwrap();
+        let (low, high) = a_u64.mul_wide(&mut cs.namespace(|| "mul"), &b_u64).unwrap();
+        let (q, r) = a_u64.div_rem(&mut cs.namespace(|| "div"), &b_u64).unwrap();
+        let eq = a_u64.eq(&mut cs.namespace(|| "eq"), &b_u64).unwrap();
+        let lt = a_u64.lt(&mut cs.namespace(|| "lt"), &b_u64).unwrap();
+        let le = a_u64.le(&mut cs.namespace(|| "le"), &b_u64).unwrap();
+        let gt = a_u64.gt(&mut cs.namespace(|| "gt"), &b_u64).unwrap();
+        let ge = a_u64.ge(&mut cs.namespace(|| "ge"), &b_u64).unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(
+            a.overflowing_add(b),
+            (sum.get_value().unwrap(), carry.get_value().unwrap())
+        );
+        assert_eq!(
+            a.overflowing_sub(b),
+            (diff.get_value().unwrap(), borrow.get_value().unwrap())
+        );
+        let product = u128::from(a) * u128::from(b);
+        assert_eq!(product as u64, low.get_value().unwrap());
+        assert_eq!((product >> 64) as u64, high.get_value().unwrap());
+        assert_eq!(a.checked_div(b).unwrap_or(0), q.get_value().unwrap());
+        assert_eq!(a.checked_rem(b).unwrap_or(a), r.get_value().unwrap());
+        assert_eq!(a == b, eq.get_value().unwrap());
+        assert_eq!(a < b, lt.get_value().unwrap());
+        assert_eq!(a <= b, le.get_value().unwrap());
+        assert_eq!(a > b, gt.get_value().unwrap());
+        assert_eq!(a >= b, ge.get_value().unwrap());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_u64_ops(a in any::<u64>(), b in any::<u64>()) {
+            check_ops(a, b);
+        }
+    }
+
+    #[test]
+    fn test_u64_edge_cases() {
+        let edges = [0, 1, 2, u64::MAX / 2, u64::MAX - 1, u64::MAX];
+        for a in edges {
+            for b in edges {
+                check_ops(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_u64_range_check() {
+        let cs = &mut TestConstraintSystem::<Fr>::new();
+        let num = AllocatedNum::alloc_infallible(cs.namespace(|| "2^64"), two_64::<Fr>);
+        AllocatedU64::from_num(&mut cs.namespace(|| "u64"), num).unwrap();
+        assert!(!cs.is_satisfied());
+    }
+}
@@ -1,9 +1,9 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 use crate::{
-    field::LurkField,
+    field::{LanguageField, LurkField},
     lem::{
         pointers::{Ptr, ZPtr},
         store::Store,
@@ -57,3 +57,106 @@ impl<F: LurkField + Serialize> Commitment<F> {
         dump(self, &commitment_path(hash_str))
     }
 }
+
+/// A portable opening hint: the data needed to open a commitment, with field
+/// elements as hex strings and the payload as Lurk source. Commitments made by
+/// other systems, or by earlier versions of this crate, can be imported from it
+/// as long as the payload can be read back, which excludes functions.
+///
+/// **Warning**: holds the secret.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Opening {
+    pub(crate) field: LanguageField,
+    pub(crate) hash: String,
+    pub(crate) secret: String,
+    pub(crate) payload: String,
+}
+
+impl<F: LurkField> Commitment<F> {
+    /// Interns the payload in `store` and prints it, checking that it reads back
+    /// to the same commitment
+    pub(crate) fn to_opening(&self, store: &Store<F>) -> Result<Opening> {
+        let (secret, z_payload) = self.open()?;
+        let payload = self
+            .z_store
+            .populate_store(z_payload, store, &mut Default::default())?;
+        let payload = payload.fmt_to_string_simple(store);
+        let read_back = store
+            .read_with_default_state(&payload)
+            .with_context(|| "the payload can't be read back")?;
+        if store.hide_and_return_z_payload(*secret, read_back).0 != self.hash {
+            bail!("The payload doesn't read back to the same commitment")
+        }
+        Ok(Opening {
+            field: F::FIELD,
+            hash: format!("0x{}", self.hash.hex_digits()),
+            secret: format!("0x{}", secret.hex_digits()),
+            payload,
+        })
+    }
+
+    /// Reads the payload of `opening` into `store` and checks that it's hidden
+    /// by the claimed hash
+    pub(crate) fn from_opening(opening: &Opening, store: &Store<F>) -> Result<Self> {
+        if opening.field != F::FIELD {
+            bail!(
+                "Field mismatch: the opening is over {}, not {}",
+                opening.field,
+                F::FIELD
+            )
+        }
+        let hash = F::from_hex_digits(&opening.hash)
+            .ok_or_else(|| anyhow!("Invalid hash: {}", opening.hash))?;
+        let secret = F::from_hex_digits(&opening.secret)
+            .ok_or_else(|| anyhow!("Invalid secret: {}", opening.secret))?;
+        let payload = store
+            .read_with_default_state(&opening.payload)
+            .with_context(|| "reading the payload")?;
+        let commitment = Self::new(Some(secret), payload, store);
+        if commitment.hash != hash {
+            bail!(
+                "The opening hides 0x{}, not {}",
+                commitment.hash.hex_digits(),
+                opening.hash
+            )
+        }
+        Ok(commitment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr;
+
+    use super::{Commitment, Opening};
+    use crate::{field::LurkField, lem::store::Store};
+
+    #[test]
+    fn test_opening_roundtrip() {
+        let store = Store::<Fr>::default();
+        let payload = store.read_with_default_state("(1 \"two\" three)").unwrap();
+        let salt = [7; 32];
+        let commitment = Commitment::new(Some(Fr::from_le_bytes_reduced(&salt)), payload, &store);
+        let opening = commitment.to_opening(&store).unwrap();
+        let json = serde_json::to_string(&opening).unwrap();
+
+        let other_store = Store::<Fr>::default();
+        let opening: Opening = serde_json::from_str(&json).unwrap();
+        let imported = Commitment::<Fr>::from_opening(&opening, &other_store).unwrap();
+        assert_eq!(imported.hash, commitment.hash);
+        let comm = other_store.hide_with_salt(
+            &salt,
+            other_store
+                .read_with_default_state(&opening.payload)
+                .unwrap(),
+        );
+        assert_eq!(other_store.hash_ptr(&comm).value(), &commitment.hash);
+
+        // a tampered opening is rejected
+        let tampered = Opening {
+            payload: "(1 \"two\" four)".into(),
+            ..opening
+        };
+        assert!(Commitment::<Fr>::from_opening(&tampered, &other_store).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{bail, Result};
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    coroutine::memoset::{CompressedProof, CoroutineProof, CoroutinePublicInputs},
+    lem::store::Store,
+    proof::nova::CurveCycleEquipped,
+};
+
+use super::{
+    field_data::{de, HasFieldModulus},
+    repl::QueryScope,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub(crate) enum CoroutineProofWrapper<F: CurveCycleEquipped> {
+    Recursive(CoroutineProof<F>),
+    Compressed(CompressedProof<F>),
+}
+
+/// A coroutine proof of the queries of a memoset scope, as written by `!(prove-queries ...)` and checked by
+/// `lurk verify <proof-file> --public-inputs <file>`
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub(crate) struct CoroutineProofFile<F: CurveCycleEquipped> {
+    /// The query type, a built-in one or the list of query definitions, as accepted by `QueryScope::new`
+    pub(crate) query: String,
+    /// The key of the public parameters in the `FoldingParamsCache`
+    pub(crate) params_key: String,
+    pub(crate) proof: CoroutineProofWrapper<F>,
+}
+
+impl<F: CurveCycleEquipped> HasFieldModulus for CoroutineProofFile<F> {
+    fn field_modulus() -> String {
+        F::MODULUS.to_owned()
+    }
+}
+
+/// The outcome of the verification of a `CoroutineProofFile`, printed as JSON
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Verdict {
+    pub(crate) verified: bool,
+    /// "recursive" or "compressed"
+    kind: Option<&'static str>,
+    query: Option<String>,
+    /// Whether the IO of the proof agrees with the claimed public inputs
+    public_inputs_match: Option<bool>,
+    error: Option<String>,
+}
+
+impl Verdict {
+    fn failed(e: anyhow::Error) -> Self {
+        Self {
+            error: Some(format!("{e:#}")),
+            ..Self::default()
+        }
+    }
+}
+
+impl<F: CurveCycleEquipped> CoroutineProofFile<F> {
+    fn z0_zi(&self) -> (&[F], &[F]) {
+        match &self.proof {
+            CoroutineProofWrapper::Recursive(proof) => (proof.z0(), proof.zi()),
+            CoroutineProofWrapper::Compressed(proof) => (proof.z0(), proof.zi()),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match &self.proof {
+            CoroutineProofWrapper::Recursive(_) => "recursive",
+            CoroutineProofWrapper::Compressed(_) => "compressed",
+        }
+    }
+
+    /// Verifies the proof at `proof_path` against the public inputs at `public_inputs_path`, encoded as in
+    /// `CoroutinePublicInputs::to_bytes`. Failures to read the files or to get the public parameters are reported in
+    /// the verdict rather than returned.
+    pub(crate) fn verify_file(
+        proof_path: &Utf8PathBuf,
+        public_inputs_path: &Utf8PathBuf,
+    ) -> Verdict {
+        let read = || -> Result<_> {
+            Ok((
+                std::fs::read(proof_path)?,
+                std::fs::read(public_inputs_path)?,
+            ))
+        };
+        match read() {
+            Ok((proof, public_inputs)) => Self::verify_bytes(&proof, &public_inputs),
+            Err(e) => Verdict::failed(e),
+        }
+    }
+
+    /// Like `verify_file`, with the contents of the files
+    pub(crate) fn verify_bytes(proof: &[u8], public_inputs: &[u8]) -> Verdict {
+        let mut verdict = Verdict::default();
+        if let Err(e) = Self::verify_bytes_aux(proof, public_inputs, &mut verdict) {
+            verdict.verified = false;
+            verdict.error = Some(format!("{e:#}"));
+        }
+        verdict
+    }
+
+    fn verify_bytes_aux(proof: &[u8], public_inputs: &[u8], verdict: &mut Verdict) -> Result<()> {
+        let file: Self = de(proof)?;
+        verdict.kind = Some(file.kind());
+        verdict.query = Some(file.query.clone());
+
+        let store = Store::<F>::default();
+        let public_inputs = CoroutinePublicInputs::from_bytes(public_inputs)?;
+        let (z0, zi) = file.z0_zi();
+        let public_inputs_match =
+            public_inputs.check_toplevel_acc(&store) && public_inputs.matches_io(&store, z0, zi);
+        verdict.public_inputs_match = Some(public_inputs_match);
+        if !public_inputs_match {
+            return Ok(());
+        }
+
+        let (params_key, pp) = QueryScope::new(&file.query)?.public_params(&store)?;
+        if params_key != file.params_key {
+            bail!(
+                "The proof was made with public parameters {}, but the current ones are {params_key}",
+                file.params_key
+            )
+        }
+        verdict.verified = match &file.proof {
+            CoroutineProofWrapper::Recursive(proof) => proof.verify(&pp, &store, &public_inputs)?,
+            CoroutineProofWrapper::Compressed(proof) => {
+                proof.verify(&pp, &store, &public_inputs)?
+            }
+        };
+        Ok(())
+    }
+}
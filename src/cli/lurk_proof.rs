@@ -1,5 +1,6 @@
 use abomonation::Abomonation;
 use anyhow::{bail, Result};
+use camino::Utf8PathBuf;
 use ff::PrimeField;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{collections::HashMap, sync::Arc};
@@ -8,16 +9,17 @@ use crate::{
     coprocessor::Coprocessor,
     eval::lang::Lang,
     field::LurkField,
-    lem::{pointers::ZPtr, store::Store},
+    lem::{pointers::ZPtr, store::Store, tag::Tag},
     proof::{
         nova::{self, CurveCycleEquipped, Dual, C1LEM},
-        supernova, RecursiveSNARKTrait,
+        proved_iterations, supernova, RecursiveSNARKTrait,
     },
     public_parameters::{
         instance::{Instance, Kind},
         public_params, supernova_public_params,
     },
     state::{initial_lurk_state, State},
+    tag::Tag as TagTrait,
 };
 
 use super::{
@@ -173,6 +175,83 @@ impl<
     pub(crate) fn is_cached(proof_key: &str) -> bool {
         load::<Self>(&proof_path(proof_key)).is_ok()
     }
+
+    /// Prints what can be learned from the proof file at `path` without verifying it. The public IO is shown as Lurk
+    /// data when the meta file of the proof sits next to it, and as opaque pointers otherwise.
+    pub(crate) fn inspect_file(path: &Utf8PathBuf) -> Result<()> {
+        let file_size = std::fs::metadata(path)?.len();
+        let lurk_proof = load::<Self>(path)?;
+
+        let (system, kind, num_steps) = match &lurk_proof.proof {
+            LurkProofWrapper::Nova(proof @ nova::Proof::Recursive(..)) => {
+                ("Nova", "recursive", Some(proof.num_steps()))
+            }
+            LurkProofWrapper::Nova(proof @ nova::Proof::Compressed(..)) => {
+                ("Nova", "compressed", Some(proof.num_steps()))
+            }
+            LurkProofWrapper::SuperNova(supernova::Proof::Recursive(..)) => {
+                ("SuperNova", "recursive", None)
+            }
+            LurkProofWrapper::SuperNova(supernova::Proof::Compressed(..)) => {
+                ("SuperNova", "compressed", None)
+            }
+        };
+        let digest =
+            nova::circuit_cache_key::<F, C>(lurk_proof.rc, Arc::new(lurk_proof.lang.clone()));
+        println!("Proof: {system} ({kind})");
+        println!("Curve: {}", F::FIELD);
+        println!("Circuit digest: 0x{}", digest.hex_digits());
+        println!("Reduction count: {}", lurk_proof.rc);
+        match num_steps {
+            Some(num_steps) => println!("Folded steps: {num_steps}"),
+            None => println!("Folded steps: not recorded in SuperNova proofs"),
+        }
+        match proved_iterations(&lurk_proof.public_inputs, &lurk_proof.public_outputs) {
+            Some(iterations) => println!("Iterations: {iterations}"),
+            None => println!("Iterations: not metered"),
+        }
+
+        let store = Store::default();
+        let state = initial_lurk_state();
+        let z_dag = load::<LurkProofMeta<F>>(&path.with_extension("meta"))
+            .ok()
+            .map(|meta| meta.z_dag);
+        let mut cache = HashMap::default();
+        let mut fmt_io = |io: &[F]| -> Vec<String> {
+            io.chunks(2)
+                .map(|chunk| {
+                    let Some(tag) = Tag::from_field(&chunk[0]) else {
+                        return format!("<invalid tag {}>", chunk[0].hex_digits());
+                    };
+                    let z_ptr = ZPtr::from_parts(tag, chunk[1]);
+                    let ptr = z_dag
+                        .as_ref()
+                        .and_then(|z_dag| z_dag.populate_store(&z_ptr, &store, &mut cache).ok())
+                        .unwrap_or_else(|| store.to_ptr(&z_ptr));
+                    ptr.fmt_to_string(&store, state)
+                })
+                .collect()
+        };
+        for (name, io) in [
+            ("Public inputs", &lurk_proof.public_inputs),
+            ("Public outputs", &lurk_proof.public_outputs),
+        ] {
+            println!("{name}:");
+            for (label, data) in ["Expr", "Env", "Cont", "Step"].iter().zip(fmt_io(io)) {
+                println!("  {label}: {data}");
+            }
+        }
+
+        let proof_size = bincode::serialized_size(&lurk_proof.proof)?;
+        let io_size =
+            bincode::serialized_size(&(&lurk_proof.public_inputs, &lurk_proof.public_outputs))?;
+        let lang_size = bincode::serialized_size(&lurk_proof.lang)?;
+        println!("Size: {file_size} bytes");
+        println!("  SNARK: {proof_size} bytes");
+        println!("  Public IO: {io_size} bytes");
+        println!("  Lang: {lang_size} bytes");
+        Ok(())
+    }
 }
 
 impl<
@@ -184,8 +263,23 @@ where
     F::Repr: Abomonation,
     <Dual<F> as PrimeField>::Repr: Abomonation,
 {
-    pub(crate) fn verify_proof(proof_key: &str) -> Result<()> {
+    /// Verifies the proof stored under `proof_key`, also checking that it proves at most `max_iterations` iterations
+    /// when that's given
+    pub(crate) fn verify_proof(proof_key: &str, max_iterations: Option<usize>) -> Result<()> {
         let lurk_proof = load::<Self>(&proof_path(proof_key))?;
+        if let Some(budget) = max_iterations {
+            let Some(proved) =
+                proved_iterations(&lurk_proof.public_inputs, &lurk_proof.public_outputs)
+            else {
+                bail!("Iteration budgets can only be checked for metered proofs")
+            };
+            if proved > budget {
+                println!(
+                    "✗ Proof \"{proof_key}\" proves {proved} iterations, over the budget of {budget}"
+                );
+                return Ok(());
+            }
+        }
         if lurk_proof.verify()? {
             println!("✓ Proof \"{proof_key}\" verified");
         } else {
@@ -194,7 +288,7 @@ where
         Ok(())
     }
 
-    fn verify(&self) -> Result<bool> {
+    pub(crate) fn verify(&self) -> Result<bool> {
         match &self.proof {
             LurkProofWrapper::Nova(proof) => {
                 tracing::info!("Loading public parameters");
@@ -2,11 +2,13 @@ mod backend;
 mod circom;
 mod commitment;
 mod config;
+mod coroutine_proof;
 mod field_data;
 mod lurk_proof;
 pub mod paths;
 mod repl;
-mod zstore;
+mod server;
+pub mod zstore;
 
 use anyhow::{bail, Context, Result};
 use camino::Utf8PathBuf;
@@ -19,6 +21,7 @@ use std::{
     fs::{self, read_dir},
     io::BufReader,
     path::PathBuf,
+    time::Duration,
 };
 
 use crate::{
@@ -32,8 +35,10 @@ use crate::{
 use crate::cli::{
     backend::Backend,
     config::cli_config,
+    coroutine_proof::CoroutineProofFile,
     paths::create_lurk_dirs,
     repl::{validate_non_zero, Repl},
+    server::{ProverServer, ServerConfig},
     zstore::ZStore,
 };
 
@@ -56,6 +61,12 @@ enum Command {
     Verify(VerifyArgs),
     /// Inspects a Lurk proof
     Inspect(InspectArgs),
+    /// Prints the parameters, public IO and size breakdown of a proof file, without verifying it
+    InspectProof(InspectProofArgs),
+    /// Loads a file and proves each of its evaluations and its queries, printing timings and sizes as JSON
+    Bench(BenchArgs),
+    /// Runs a proving service, answering JSON-RPC requests over HTTP
+    Serve(ServeArgs),
     /// Instantiates a new circom gadget to interface with bellpepper.
     ///
     /// See `lurk circom --help` for more details
@@ -90,6 +101,11 @@ struct LoadArgs {
     #[clap(long, value_parser)]
     limit: Option<usize>,
 
+    /// Flag to count the iterations of evaluations, exposing the count in the public output of proofs and to
+    /// programs as `(current-step)`
+    #[arg(long)]
+    metered: bool,
+
     /// Prover backend (defaults to "nova")
     #[clap(long, value_enum)]
     backend: Option<Backend>,
@@ -139,6 +155,9 @@ struct LoadCli {
     #[clap(long, value_parser)]
     limit: Option<usize>,
 
+    #[arg(long)]
+    metered: bool,
+
     #[clap(long, value_enum)]
     backend: Option<Backend>,
 
@@ -170,6 +189,7 @@ impl LoadArgs {
             config: self.config,
             rc: self.rc,
             limit: self.limit,
+            metered: self.metered,
             backend: self.backend,
             field: self.field,
             public_params_dir: self.public_params_dir,
@@ -203,6 +223,11 @@ struct ReplArgs {
     #[clap(long, value_parser)]
     limit: Option<usize>,
 
+    /// Flag to count the iterations of evaluations, exposing the count in the public output of proofs and to
+    /// programs as `(current-step)`
+    #[arg(long)]
+    metered: bool,
+
     /// Prover backend (defaults to "nova")
     #[clap(long, value_enum)]
     backend: Option<Backend>,
@@ -245,6 +270,9 @@ struct ReplCli {
     #[clap(long, value_parser)]
     limit: Option<usize>,
 
+    #[arg(long)]
+    metered: bool,
+
     #[clap(long, value_enum)]
     backend: Option<Backend>,
 
@@ -272,6 +300,7 @@ impl ReplArgs {
             config: self.config,
             rc: self.rc,
             limit: self.limit,
+            metered: self.metered,
             backend: self.backend,
             field: self.field,
             public_params_dir: self.public_params_dir,
@@ -306,7 +335,8 @@ macro_rules! new_repl {
     ( $cli: expr, $rc: expr, $limit: expr, $field: path, $backend: expr ) => {{
         let store = get_store(&$cli.zstore).with_context(|| "reading store from file")?;
         // TODO: pick a predefined `Lang` according to a CLI parameter
-        let lang = Lang::new();
+        let mut lang = Lang::new();
+        lang.set_metered($cli.metered);
         Repl::<$field, Coproc<$field>>::new(store, lang, $rc, $limit, $backend)
     }};
 }
@@ -418,10 +448,19 @@ impl LoadCli {
 
 #[derive(Args, Debug)]
 struct VerifyArgs {
-    /// Key of the proof to be verified
+    /// Key of the proof to be verified, or path to a coroutine proof file when `--public-inputs` is set
     #[clap(value_parser)]
     proof_key: String,
 
+    /// Path to the public inputs of a coroutine proof, as written by `!(prove-queries ...)`. The verdict is printed
+    /// as JSON
+    #[clap(long, value_parser)]
+    public_inputs: Option<Utf8PathBuf>,
+
+    /// Also check that the proof proves at most this many iterations (metered proofs only)
+    #[clap(long, value_parser)]
+    max_iterations: Option<usize>,
+
     /// Arithmetic field (defaults to "bn256")
     #[clap(long, value_enum)]
     field: Option<LanguageField>,
@@ -458,6 +497,211 @@ struct InspectArgs {
     proofs_dir: Option<Utf8PathBuf>,
 }
 
+#[derive(Args, Debug)]
+struct BenchArgs {
+    /// The file to be loaded. Each of its evaluations is benchmarked, and so are the queries it makes
+    #[clap(value_parser)]
+    lurk_file: Utf8PathBuf,
+
+    /// ZStore to be preloaded before the loading the file
+    #[clap(long, value_parser)]
+    zstore: Option<Utf8PathBuf>,
+
+    /// Config file, containing the lowest precedence parameters
+    #[clap(long, value_parser)]
+    config: Option<Utf8PathBuf>,
+
+    /// Reduction count used for proofs (defaults to 10)
+    #[clap(long, value_parser)]
+    rc: Option<usize>,
+
+    /// Iterations allowed (defaults to 100_000_000; rounded up to the next multiple of rc)
+    #[clap(long, value_parser)]
+    limit: Option<usize>,
+
+    /// Flag to count the iterations of evaluations, exposing the count in the public output of proofs and to
+    /// programs as `(current-step)`
+    #[arg(long)]
+    metered: bool,
+
+    /// Prover backend (defaults to "nova")
+    #[clap(long, value_enum)]
+    backend: Option<Backend>,
+
+    /// Arithmetic field (defaults to "bn256")
+    #[clap(long, value_enum)]
+    field: Option<LanguageField>,
+
+    /// Path to public parameters directory
+    #[clap(long, value_parser)]
+    public_params_dir: Option<Utf8PathBuf>,
+}
+
+impl BenchArgs {
+    fn run(&self) -> Result<()> {
+        macro_rules! bench {
+            ( $rc: expr, $limit: expr, $field: path, $backend: expr ) => {{
+                let mut repl = new_repl!(self, $rc, $limit, $field, $backend);
+                repl.record_bench_cases();
+                repl.load_file(&self.lurk_file, false)?;
+                repl.bench()
+            }};
+        }
+        macro_rules! map_insert {
+            ( $map:expr, $( $field:ident ),* ) => {
+                $(
+                    if let Some(val) = &self.$field {
+                       $map.insert(stringify!($field), val.to_string());
+                    }
+                )*
+            };
+        }
+        let mut cli_settings: HashMap<&str, String> = HashMap::new();
+        map_insert!(
+            &mut cli_settings,
+            public_params_dir,
+            backend,
+            field,
+            rc,
+            limit
+        );
+
+        let config = cli_config(self.config.as_ref(), Some(&cli_settings));
+
+        create_lurk_dirs()?;
+
+        let rc = config.rc;
+        let limit = config.limit;
+        let backend = &config.backend;
+        let field = &config.field;
+        validate_non_zero("rc", rc)?;
+        backend.validate_field(field)?;
+        match field {
+            LanguageField::BN256 => bench!(rc, limit, bn256::Fr, backend.clone()),
+            LanguageField::Pallas => bench!(rc, limit, pallas::Scalar, backend.clone()),
+            LanguageField::Grumpkin | LanguageField::Vesta => unreachable!(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    /// Address to listen on
+    #[clap(long, value_parser, default_value = "127.0.0.1:8383")]
+    addr: String,
+
+    /// Number of jobs proved at the same time
+    #[clap(long, value_parser, default_value_t = 1)]
+    workers: usize,
+
+    /// Number of jobs allowed to wait for a worker before new submissions are rejected
+    #[clap(long, value_parser, default_value_t = 64)]
+    max_pending: usize,
+
+    /// Seconds during which the outcome of a finished job can be fetched
+    #[clap(long, value_parser, default_value_t = 3600)]
+    job_ttl: u64,
+
+    /// Address to also serve the gRPC API on
+    #[cfg(feature = "grpc")]
+    #[clap(long, value_parser)]
+    grpc_addr: Option<String>,
+
+    /// Config file, containing the lowest precedence parameters
+    #[clap(long, value_parser)]
+    config: Option<Utf8PathBuf>,
+
+    /// Reduction count used for proofs (defaults to 10)
+    #[clap(long, value_parser)]
+    rc: Option<usize>,
+
+    /// Iterations allowed per job, whatever the config file sets (rounded up to the next multiple of rc)
+    #[clap(long, value_parser, default_value_t = DEFAULT_SERVER_LIMIT)]
+    limit: usize,
+
+    /// Prover backend (defaults to "nova")
+    #[clap(long, value_enum)]
+    backend: Option<Backend>,
+
+    /// Arithmetic field (defaults to "bn256")
+    #[clap(long, value_enum)]
+    field: Option<LanguageField>,
+
+    /// Path to public parameters directory
+    #[clap(long, value_parser)]
+    public_params_dir: Option<Utf8PathBuf>,
+
+    /// Path to proofs directory
+    #[clap(long, value_parser)]
+    proofs_dir: Option<Utf8PathBuf>,
+}
+
+/// The iteration limit of server jobs, far below the REPL's since anyone able to reach the server can submit them
+const DEFAULT_SERVER_LIMIT: usize = 1_000_000;
+
+impl ServeArgs {
+    fn run(&self) -> Result<()> {
+        macro_rules! map_insert {
+            ( $map:expr, $( $field:ident ),* ) => {
+                $(
+                    if let Some(val) = &self.$field {
+                       $map.insert(stringify!($field), val.to_string());
+                    }
+                )*
+            };
+        }
+        let mut cli_settings: HashMap<&str, String> = HashMap::new();
+        map_insert!(
+            &mut cli_settings,
+            public_params_dir,
+            proofs_dir,
+            backend,
+            field,
+            rc
+        );
+        cli_settings.insert("limit", self.limit.to_string());
+
+        let config = cli_config(self.config.as_ref(), Some(&cli_settings));
+
+        create_lurk_dirs()?;
+
+        let rc = config.rc;
+        validate_non_zero("rc", rc)?;
+        validate_non_zero("workers", self.workers)?;
+        let backend = &config.backend;
+        let field = &config.field;
+        backend.validate_field(field)?;
+        let server_config = ServerConfig {
+            rc,
+            limit: config.limit,
+            backend: backend.clone(),
+            workers: self.workers,
+            max_pending: self.max_pending,
+            job_ttl: Duration::from_secs(self.job_ttl),
+            #[cfg(feature = "grpc")]
+            grpc_addr: self.grpc_addr.clone(),
+        };
+        match field {
+            LanguageField::BN256 => ProverServer::<bn256::Fr>::new(server_config).serve(&self.addr),
+            LanguageField::Pallas => {
+                ProverServer::<pallas::Scalar>::new(server_config).serve(&self.addr)
+            }
+            LanguageField::Grumpkin | LanguageField::Vesta => unreachable!(),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct InspectProofArgs {
+    /// Path to the proof file
+    #[clap(value_parser)]
+    proof_file: Utf8PathBuf,
+
+    /// Arithmetic field (defaults to "bn256")
+    #[clap(long, value_enum)]
+    field: Option<LanguageField>,
+}
+
 /// To setup a new circom gadget `<NAME>`, place your circom files in a designated folder and
 /// create a file called `<NAME>.circom`. `<CIRCOM_FOLDER>/<NAME>.circom` is the input file
 /// for the `circom` binary; in this file you must declare your circom main component.
@@ -603,14 +847,40 @@ impl Cli {
                 }
                 cli_config(verify_args.config.as_ref(), Some(&cli_settings));
 
-                // TODO: pick a predefined `Lang` according to a CLI parameter
-                match verify_args.field.unwrap_or_default() {
-                    LanguageField::BN256 => {
-                        LurkProof::<_, Coproc<bn256::Fr>>::verify_proof(&verify_args.proof_key)
-                    }
-                    LanguageField::Pallas => {
-                        LurkProof::<_, Coproc<pallas::Scalar>>::verify_proof(&verify_args.proof_key)
+                let field = verify_args.field.unwrap_or_default();
+                if let Some(public_inputs) = &verify_args.public_inputs {
+                    // Coroutine proofs are always made with SuperNova
+                    Backend::SuperNova.validate_field(&field)?;
+                    let proof_path = Utf8PathBuf::from(&verify_args.proof_key);
+                    let verdict = match field {
+                        LanguageField::BN256 => {
+                            CoroutineProofFile::<bn256::Fr>::verify_file(&proof_path, public_inputs)
+                        }
+                        LanguageField::Pallas => CoroutineProofFile::<pallas::Scalar>::verify_file(
+                            &proof_path,
+                            public_inputs,
+                        ),
+                        _ => unreachable!(),
+                    };
+                    println!("{}", serde_json::to_string(&verdict)?);
+                    if !verdict.verified {
+                        bail!("Coroutine proof not verified")
                     }
+                    return Ok(());
+                }
+
+                // The backend is only known once the proof is loaded, but every backend supports the same fields
+                Backend::default().validate_field(&field)?;
+                // TODO: pick a predefined `Lang` according to a CLI parameter
+                match field {
+                    LanguageField::BN256 => LurkProof::<_, Coproc<bn256::Fr>>::verify_proof(
+                        &verify_args.proof_key,
+                        verify_args.max_iterations,
+                    ),
+                    LanguageField::Pallas => LurkProof::<_, Coproc<pallas::Scalar>>::verify_proof(
+                        &verify_args.proof_key,
+                        verify_args.max_iterations,
+                    ),
                     _ => unreachable!(),
                 }
             }
@@ -637,6 +907,21 @@ impl Cli {
                     _ => unreachable!(),
                 }
             }
+            Command::Bench(bench_args) => bench_args.run(),
+            Command::Serve(serve_args) => serve_args.run(),
+            Command::InspectProof(inspect_proof_args) => {
+                use crate::cli::lurk_proof::LurkProof;
+                let proof_file = &inspect_proof_args.proof_file;
+                match inspect_proof_args.field.unwrap_or_default() {
+                    LanguageField::BN256 => {
+                        LurkProof::<_, Coproc<bn256::Fr>>::inspect_file(proof_file)
+                    }
+                    LanguageField::Pallas => {
+                        LurkProof::<_, Coproc<pallas::Scalar>>::inspect_file(proof_file)
+                    }
+                    _ => unreachable!(),
+                }
+            }
             Command::Circom(circom_args) => {
                 use crate::cli::circom::create_circom_gadget;
                 if circom_args.name == "main" {
@@ -0,0 +1,344 @@
+use abomonation::Abomonation;
+use anyhow::{bail, ensure, Result};
+use ff::PrimeField;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cli::backend::Backend,
+    coprocessor::Coprocessor,
+    coroutine::memoset::{CoroutineProof, LogMemo, Query, Scope},
+    lem::{eval::evaluate_with_env, pointers::Ptr, store::Store},
+    proof::{
+        events::{CancellationToken, ProverEvents, StepInfo},
+        nova::{self, CurveCycleEquipped, Dual, C1LEM},
+        supernova::{self, FoldingConfig, PublicParams},
+        RecursiveSNARKTrait,
+    },
+    public_parameters::{
+        instance::{Instance, Kind},
+        public_params, supernova_public_params,
+    },
+};
+
+use super::{QueryScope, Repl};
+
+/// The folding of one step, as reported by `ProverEvents`
+#[derive(Serialize)]
+struct StepReport {
+    index: usize,
+    circuit_index: usize,
+    num_constraints: usize,
+    /// Witness synthesis time, measured before folding
+    synthesis_ms: f64,
+    /// Folding time, with the witness already synthesized
+    folding_ms: f64,
+}
+
+/// Timings and sizes of the evaluation and proof of one expression
+#[derive(Serialize)]
+struct CaseReport {
+    expr: String,
+    iterations: usize,
+    eval_ms: f64,
+    steps: Vec<StepReport>,
+    synthesis_ms: f64,
+    folding_ms: f64,
+    compression_ms: f64,
+    verification_ms: f64,
+    /// The size of the compressed proof, in bytes
+    proof_size: u64,
+}
+
+/// The chunk circuit of a query index
+#[derive(Serialize)]
+struct CircuitReport {
+    query_index: usize,
+    rc: usize,
+    /// How many chunks the queries need
+    chunks: usize,
+    num_constraints: usize,
+}
+
+/// The synthesis of one chunk of the queries
+#[derive(Serialize)]
+struct ChunkReport {
+    query_index: usize,
+    synthesis_ms: f64,
+}
+
+/// Timings and sizes of the proof of the queries made with `!(query ...)`
+#[derive(Serialize)]
+struct QueriesReport {
+    query_type: String,
+    /// Loading or generating the SuperNova public parameters of the query type
+    public_params_ms: f64,
+    circuits: Vec<CircuitReport>,
+    chunks: Vec<ChunkReport>,
+    synthesis_ms: f64,
+    /// Proving time less the synthesis time of the chunks, which folding repeats
+    folding_ms: f64,
+    compression_ms: f64,
+    verification_ms: f64,
+    /// The size of the compressed proof, in bytes
+    proof_size: u64,
+}
+
+/// Timings and sizes of the evaluations and queries of a file, printed as JSON by `lurk bench`
+#[derive(Serialize)]
+struct BenchReport {
+    backend: String,
+    field: String,
+    rc: usize,
+    /// Loading or generating the public parameters of the evaluations, if there are any
+    public_params_ms: Option<f64>,
+    cases: Vec<CaseReport>,
+    queries: Option<QueriesReport>,
+}
+
+#[derive(Default)]
+struct StepRecorder(Mutex<Vec<(StepInfo, Duration)>>);
+
+impl ProverEvents for StepRecorder {
+    fn step_finished(&self, step: &StepInfo, elapsed: Duration) {
+        self.0.lock().unwrap().push((step.clone(), elapsed));
+    }
+}
+
+#[inline]
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl<
+        F: CurveCycleEquipped + Serialize + DeserializeOwned,
+        C: Coprocessor<F> + Serialize + DeserializeOwned + 'static,
+    > Repl<F, C>
+where
+    F::Repr: Abomonation,
+    <Dual<F> as PrimeField>::Repr: Abomonation,
+{
+    /// Makes the REPL remember every evaluation it memoizes from now on, to be benchmarked by `bench`
+    pub(crate) fn record_bench_cases(&mut self) {
+        self.bench_cases = Some(Vec::new());
+    }
+
+    /// Synthesizes the witness of every step ahead of folding, so that folding can be timed apart. Returns the time
+    /// each synthesis took.
+    fn synthesize_steps(&self, steps: &mut [C1LEM<'_, F, C>]) -> Result<Vec<f64>> {
+        steps
+            .iter_mut()
+            .map(|step| {
+                let start = Instant::now();
+                step.cache_witness(&self.store)?;
+                Ok(millis(start.elapsed()))
+            })
+            .collect()
+    }
+
+    /// Evaluates and proves again every evaluation recorded since `record_bench_cases`, then proves the queries made
+    /// so far, timing every phase, and prints the report as JSON. Proofs are compressed and verified, but not
+    /// persisted. The query scope is emptied afterwards.
+    pub(crate) fn bench(&mut self) -> Result<()> {
+        let cases = self.bench_cases.clone().unwrap_or_default();
+        let queries = match self.query_scope.as_mut() {
+            Some(scope) if scope.stats(&self.store).toplevel_insertions > 0 => {
+                Some(scope.bench(&self.store)?)
+            }
+            _ => None,
+        };
+        if cases.is_empty() && queries.is_none() {
+            bail!("No evaluations nor queries to benchmark")
+        }
+        let cancel = CancellationToken::new();
+
+        macro_rules! bench_cases {
+            ($proof: ty, $folding_config: expr, $pp: expr) => {{
+                if cases.is_empty() {
+                    (None, Vec::new())
+                } else {
+                    let start = Instant::now();
+                    let pp = $pp?;
+                    let public_params_ms = millis(start.elapsed());
+                    let folding_config = Arc::new($folding_config);
+
+                    let mut reports = Vec::with_capacity(cases.len());
+                    for &(expr, env) in &cases {
+                        let start = Instant::now();
+                        let frames = evaluate_with_env::<F, C>(
+                            Some(self.lang_setup()),
+                            expr,
+                            env,
+                            &self.store,
+                            self.limit,
+                        )?;
+                        let eval_ms = millis(start.elapsed());
+                        let iterations = frames.len();
+
+                        self.store.hydrate_z_cache();
+                        let z0 = self.store.to_scalar_vector(&frames[0].input);
+                        let zi = self.store.to_scalar_vector(&frames[iterations - 1].output);
+                        let mut steps =
+                            C1LEM::<'_, F, C>::from_frames(&frames, &self.store, &folding_config);
+                        let synthesis = self.synthesize_steps(&mut steps)?;
+
+                        let recorder = StepRecorder::default();
+                        let start = Instant::now();
+                        let proof = <$proof>::prove_recursively_with_events(
+                            &pp,
+                            &z0,
+                            steps,
+                            &self.store,
+                            &recorder,
+                            &cancel,
+                        )?;
+                        let folding_ms = millis(start.elapsed());
+
+                        let start = Instant::now();
+                        let proof = proof.compress(&pp)?;
+                        let compression_ms = millis(start.elapsed());
+
+                        let start = Instant::now();
+                        ensure!(proof.verify(&pp, &z0, &zi)?, "Proof failed to verify");
+                        let verification_ms = millis(start.elapsed());
+
+                        let steps = recorder
+                            .0
+                            .into_inner()
+                            .unwrap()
+                            .into_iter()
+                            .zip(&synthesis)
+                            .map(|((step, elapsed), synthesis_ms)| StepReport {
+                                index: step.index,
+                                circuit_index: step.circuit_index,
+                                num_constraints: step.num_constraints,
+                                synthesis_ms: *synthesis_ms,
+                                folding_ms: millis(elapsed),
+                            })
+                            .collect();
+                        reports.push(CaseReport {
+                            expr: expr.fmt_to_string_simple(&self.store),
+                            iterations,
+                            eval_ms,
+                            steps,
+                            synthesis_ms: synthesis.iter().sum(),
+                            folding_ms,
+                            compression_ms,
+                            verification_ms,
+                            proof_size: bincode::serialized_size(&proof)?,
+                        });
+                    }
+                    (Some(public_params_ms), reports)
+                }
+            }};
+        }
+
+        let (public_params_ms, cases) = match self.backend {
+            Backend::Nova => bench_cases!(
+                nova::Proof<F, C1LEM<'_, F, C>>,
+                FoldingConfig::new_ivc(self.lang.clone(), self.rc),
+                public_params(&Instance::new(
+                    self.rc,
+                    self.lang.clone(),
+                    true,
+                    Kind::NovaPublicParams
+                ))
+            ),
+            Backend::SuperNova => bench_cases!(
+                supernova::Proof<F, C1LEM<'_, F, C>>,
+                FoldingConfig::new_nivc(self.lang.clone(), self.rc),
+                supernova_public_params(&Instance::new(
+                    self.rc,
+                    self.lang.clone(),
+                    true,
+                    Kind::SuperNovaAuxParams
+                ))
+            ),
+        };
+
+        let report = BenchReport {
+            backend: self.backend.to_string(),
+            field: F::FIELD.to_string(),
+            rc: self.rc,
+            public_params_ms,
+            cases,
+            queries,
+        };
+        println!("{}", serde_json::to_string(&report)?);
+        Ok(())
+    }
+}
+
+impl<F: CurveCycleEquipped> QueryScope<F> {
+    /// Proves the queries made so far with SuperNova, timing every phase, and empties the scope
+    fn bench(&mut self, s: &Store<F>) -> Result<QueriesReport> {
+        fn bench_aux<F: CurveCycleEquipped, Q: Query<F> + Send + Sync>(
+            scope: &mut Scope<Q, LogMemo<F>>,
+            s: &Store<F>,
+            pp: &PublicParams<F>,
+            report: &mut QueriesReport,
+        ) -> Result<()> {
+            let public_inputs = scope.public_inputs(s);
+            report.chunks = scope
+                .time_chunk_synthesis(s)?
+                .into_iter()
+                .map(|(query_index, elapsed)| ChunkReport {
+                    query_index,
+                    synthesis_ms: millis(elapsed),
+                })
+                .collect();
+            report.synthesis_ms = report.chunks.iter().map(|chunk| chunk.synthesis_ms).sum();
+
+            let start = Instant::now();
+            let proof = CoroutineProof::prove(scope, s, pp)?;
+            report.folding_ms = (millis(start.elapsed()) - report.synthesis_ms).max(0.0);
+
+            let start = Instant::now();
+            let proof = proof.compress(pp)?;
+            report.compression_ms = millis(start.elapsed());
+
+            let start = Instant::now();
+            ensure!(
+                proof.verify(pp, s, &public_inputs)?,
+                "Queries proof failed to verify"
+            );
+            report.verification_ms = millis(start.elapsed());
+            report.proof_size = bincode::serialized_size(&proof)?;
+            Ok(())
+        }
+
+        let circuits = self
+            .chunk_sizes(s)?
+            .into_iter()
+            .map(|size| CircuitReport {
+                query_index: size.index,
+                rc: size.rc,
+                chunks: size.chunks,
+                num_constraints: size.num_constraints,
+            })
+            .collect();
+        let start = Instant::now();
+        let (_, pp) = self.public_params(s)?;
+        let mut report = QueriesReport {
+            query_type: self.name().to_owned(),
+            public_params_ms: millis(start.elapsed()),
+            circuits,
+            chunks: Vec::new(),
+            synthesis_ms: 0.0,
+            folding_ms: 0.0,
+            compression_ms: 0.0,
+            verification_ms: 0.0,
+            proof_size: 0,
+        };
+        match self {
+            Self::Factorial(scope) => bench_aux(scope, s, &pp, &mut report)?,
+            Self::Lookup(scope) => bench_aux(scope, s, &pp, &mut report)?,
+            Self::User(scope) => bench_aux(scope, s, &pp, &mut report)?,
+        }
+        *self = self.empty();
+        Ok(report)
+    }
+}
@@ -0,0 +1,112 @@
+use abomonation::Abomonation;
+use anyhow::{bail, Result};
+use ff::PrimeField;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::BTreeMap, sync::Arc};
+
+use crate::{
+    cli::backend::Backend,
+    coprocessor::Coprocessor,
+    lem::{eval::evaluate_with_env, multiframe::MultiFrame, pointers::Ptr},
+    proof::{
+        nova::{CurveCycleEquipped, Dual},
+        supernova::FoldingConfig,
+    },
+};
+
+use super::{queries::ChunkSize, Repl};
+
+/// A rough folding throughput, used to turn constraint counts into proving time estimates. It only gives an order of
+/// magnitude: the actual throughput depends on the machine, on `LURK_PERF` and on how sparse the circuits are.
+const CONSTRAINTS_PER_SEC: f64 = 1_000_000.0;
+
+fn print_estimate(total_constraints: usize) {
+    let secs = total_constraints as f64 / CONSTRAINTS_PER_SEC;
+    println!("Total constraints: {total_constraints}");
+    println!(
+        "Estimated proving time: ~{secs:.1}s (at {CONSTRAINTS_PER_SEC:.0} constraints/s, compression excluded)"
+    );
+}
+
+impl<
+        F: CurveCycleEquipped + Serialize + DeserializeOwned,
+        C: Coprocessor<F> + Serialize + DeserializeOwned + 'static,
+    > Repl<F, C>
+where
+    F::Repr: Abomonation,
+    <Dual<F> as PrimeField>::Repr: Abomonation,
+{
+    /// Synthesizes, without proving, the circuits that would fold the evaluation of `expr` and prints their sizes
+    pub(super) fn print_constraint_count(&self, expr: Ptr) -> Result<()> {
+        let frames = evaluate_with_env::<F, C>(
+            Some(self.lang_setup()),
+            expr,
+            self.env,
+            &self.store,
+            self.limit,
+        )?;
+        let folding_config = Arc::new(match self.backend {
+            Backend::Nova => FoldingConfig::new_ivc(self.lang.clone(), self.rc),
+            Backend::SuperNova => FoldingConfig::new_nivc(self.lang.clone(), self.rc),
+        });
+        let steps = MultiFrame::<'_, F, C>::from_frames(&frames, &self.store, &folding_config);
+
+        // program counter -> (steps, constraints, aux)
+        let mut circuits: BTreeMap<usize, (usize, usize, usize)> = BTreeMap::new();
+        for step in &steps {
+            let pc = step.program_counter();
+            if let Some((count, ..)) = circuits.get_mut(&pc) {
+                *count += 1;
+            } else {
+                let (num_constraints, num_aux) = step.circuit_size();
+                circuits.insert(pc, (1, num_constraints, num_aux));
+            }
+        }
+
+        println!(
+            "Iterations: {}, folding steps: {} (backend: {}, rc: {})",
+            frames.len(),
+            steps.len(),
+            self.backend,
+            self.rc
+        );
+        let mut total_constraints = 0;
+        for (pc, (count, num_constraints, num_aux)) in circuits {
+            let circuit = if pc == 0 {
+                "Lurk step".to_owned()
+            } else {
+                format!("coprocessor {}", pc - 1)
+            };
+            println!(
+                "  {circuit}: {count} step(s) of {num_constraints} constraints, {num_aux} aux"
+            );
+            total_constraints += count * num_constraints;
+        }
+        print_estimate(total_constraints);
+        Ok(())
+    }
+
+    /// Prints the sizes of the chunk circuits that would prove the queries made in the current memoset scope
+    pub(super) fn print_query_constraint_count(&self) -> Result<()> {
+        let Some(scope) = self.query_scope.as_ref() else {
+            bail!("No query scope. Start one with `defquery` or pass an expression")
+        };
+        let mut total_constraints = 0;
+        println!("Query type: {}", scope.name());
+        for ChunkSize {
+            index,
+            rc,
+            chunks,
+            num_constraints,
+            num_aux,
+        } in scope.chunk_sizes(&self.store)?
+        {
+            println!(
+                "  Query index {index} (rc: {rc}): {chunks} chunk(s) of {num_constraints} constraints, {num_aux} aux"
+            );
+            total_constraints += chunks * num_constraints;
+        }
+        print_estimate(total_constraints);
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use anyhow::{bail, Result};
+use std::fmt::Display;
+
+use crate::{
+    coprocessor::Coprocessor,
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{
+        eval::{first_input, next_frame},
+        interpreter::Frame,
+        pointers::Ptr,
+        store::Store,
+        tag::Tag,
+        Func,
+    },
+    state::State,
+    tag::ExprTag,
+};
+
+/// A step-by-step walk over the frames of an evaluation, started with `!(step <expr>)`. Frames are evaluated as they're
+/// stepped to, so stepping through a long evaluation doesn't wait for it to finish.
+pub(super) struct Debugger {
+    /// The frames evaluated so far
+    frames: Vec<Frame>,
+    /// The index of the current frame
+    cursor: usize,
+    /// Whether the last frame evaluated ends the evaluation
+    ended: bool,
+    /// The most frames to evaluate
+    limit: usize,
+}
+
+impl Debugger {
+    /// Evaluates the first frame of `expr` in `env`
+    pub(super) fn new<F: LurkField, C: Coprocessor<F>>(
+        lang_setup: (&Func, &[Func], &Lang<F, C>),
+        expr: Ptr,
+        env: Ptr,
+        store: &Store<F>,
+        limit: usize,
+    ) -> Result<Self> {
+        if limit == 0 {
+            bail!("No frames to debug with a limit of 0")
+        }
+        let input = first_input(lang_setup.0, expr, env, store);
+        let (frame, ended) = next_frame(lang_setup, &input, store)?;
+        Ok(Self {
+            frames: vec![frame],
+            cursor: 0,
+            ended,
+            limit,
+        })
+    }
+
+    #[inline]
+    pub(super) fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    #[inline]
+    pub(super) fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    #[inline]
+    pub(super) fn current(&self) -> &Frame {
+        &self.frames[self.cursor]
+    }
+
+    /// Whether the current frame is the last one, because the evaluation ends there or the limit is reached
+    #[inline]
+    pub(super) fn is_done(&self) -> bool {
+        self.cursor + 1 == self.frames.len() && (self.ended || self.frames.len() == self.limit)
+    }
+
+    /// Moves to the next frame, evaluating it if needed. Returns `false` if the current frame is the last one
+    pub(super) fn step<F: LurkField, C: Coprocessor<F>>(
+        &mut self,
+        lang_setup: (&Func, &[Func], &Lang<F, C>),
+        store: &Store<F>,
+    ) -> Result<bool> {
+        if self.is_done() {
+            return Ok(false);
+        }
+        if self.cursor + 1 == self.frames.len() {
+            let input = &self.frames[self.cursor].output;
+            let (frame, ended) = next_frame(lang_setup, input, store)?;
+            self.frames.push(frame);
+            self.ended = ended;
+        }
+        self.cursor += 1;
+        Ok(true)
+    }
+
+    /// Moves to the next frame whose expression is the symbol `sym` or a call to it. Returns `false`, without moving,
+    /// if there's no such frame
+    pub(super) fn run_to<F: LurkField, C: Coprocessor<F>>(
+        &mut self,
+        lang_setup: (&Func, &[Func], &Lang<F, C>),
+        store: &Store<F>,
+        sym: &Ptr,
+    ) -> Result<bool> {
+        let hit = |frame: &Frame| {
+            if frame.pc != 0 {
+                return false;
+            }
+            let expr = &frame.input[0];
+            if expr == sym {
+                return true;
+            }
+            expr.tag() == &Tag::Expr(ExprTag::Cons)
+                && matches!(store.car_cdr(expr), Ok((head, _)) if &head == sym)
+        };
+        let start = self.cursor;
+        while self.step(lang_setup, store)? {
+            if hit(self.current()) {
+                return Ok(true);
+            }
+        }
+        self.cursor = start;
+        Ok(false)
+    }
+}
+
+/// The input of a frame, along with what it emitted. Coprocessor frames show their arguments instead of an expression
+pub(super) struct DisplayFrame<'a, F: LurkField> {
+    pub(super) index: usize,
+    pub(super) frame: &'a Frame,
+    pub(super) store: &'a Store<F>,
+    pub(super) state: &'a State,
+}
+
+impl<'a, F: LurkField> Display for DisplayFrame<'a, F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            index,
+            frame,
+            store,
+            state,
+        } = self;
+        let fmt = |ptr: &Ptr| ptr.fmt_to_string(store, state);
+        let n = frame.input.len();
+        if frame.pc == 0 {
+            writeln!(f, "Frame {index}")?;
+            writeln!(f, "  Expr: {}", fmt(&frame.input[0]))?;
+        } else {
+            writeln!(f, "Frame {index} (coprocessor {})", frame.pc - 1)?;
+            let args = frame.input[..n - 2].iter().map(fmt).collect::<Vec<_>>();
+            writeln!(f, "  Args: {}", args.join(" "))?;
+        }
+        write!(
+            f,
+            "  Env:  {}\n  Cont: {}",
+            fmt(&frame.input[n - 2]),
+            fmt(&frame.input[n - 1])
+        )?;
+        for ptr in &frame.emitted {
+            write!(f, "\n  Emtd: {}", fmt(ptr))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::Debugger;
+    use crate::{
+        eval::lang::{Coproc, Lang},
+        lem::{
+            eval::{eval_step, evaluate_with_env},
+            store::Store,
+        },
+    };
+
+    #[test]
+    fn test_stepping_matches_frames() {
+        let store = Store::<Fr>::default();
+        let lang = Lang::<Fr, Coproc<Fr>>::new();
+        let lang_setup = (eval_step(), &[][..], &lang);
+        let expr = store
+            .read_with_default_state("(letrec ((square (lambda (x) (* x x)))) (+ 1 (square 2)))")
+            .unwrap();
+        let env = store.intern_empty_env();
+        let frames =
+            evaluate_with_env::<Fr, Coproc<Fr>>(Some(lang_setup), expr, env, &store, 1000).unwrap();
+
+        let mut debugger = Debugger::new(lang_setup, expr, env, &store, 1000).unwrap();
+        // frames are only evaluated as they're stepped to
+        assert_eq!(debugger.frames().len(), 1);
+        while debugger.step(lang_setup, &store).unwrap() {}
+        assert!(debugger.is_done());
+        assert_eq!(debugger.cursor() + 1, frames.len());
+        for (stepped, frame) in debugger.frames().iter().zip(&frames) {
+            assert_eq!(stepped.input, frame.input);
+            assert_eq!(stepped.output, frame.output);
+        }
+        assert!(!debugger.step(lang_setup, &store).unwrap());
+
+        let square = store.read_with_default_state("square").unwrap();
+        let expected = frames
+            .iter()
+            .enumerate()
+            .filter(|(_, frame)| {
+                let expr = &frame.input[0];
+                expr == &square || matches!(store.car_cdr(expr), Ok((head, _)) if head == square)
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        let mut debugger = Debugger::new(lang_setup, expr, env, &store, 1000).unwrap();
+        let mut hits = vec![];
+        while debugger.run_to(lang_setup, &store, &square).unwrap() {
+            hits.push(debugger.cursor());
+        }
+        assert!(!hits.is_empty());
+        assert_eq!(hits, expected);
+        // the last miss leaves the cursor at the last hit
+        assert_eq!(Some(&debugger.cursor()), hits.last());
+
+        // the limit ends stepping early
+        let mut debugger = Debugger::new(lang_setup, expr, env, &store, 3).unwrap();
+        while debugger.step(lang_setup, &store).unwrap() {}
+        assert_eq!(debugger.frames().len(), 3);
+        assert!(Debugger::new(lang_setup, expr, env, &store, 0).is_err());
+    }
+}
@@ -32,9 +32,14 @@ use crate::{
         public_params, supernova_public_params,
     },
     tag::{ContTag, ExprTag},
+    Symbol,
 };
 
-use super::Repl;
+use super::{
+    debugger::{Debugger, DisplayFrame},
+    queries::{DisplayStats, QueryScope},
+    Repl,
+};
 
 pub(super) struct MetaCmd<F: LurkField, C: Coprocessor<F> + Serialize + DeserializeOwned> {
     name: &'static str,
@@ -278,6 +283,74 @@ where
         },
     };
 
+    const HIDE_SALTED: MetaCmd<F, C> = MetaCmd {
+        name: "hide-salted",
+        summary: "Return and persist the commitment of <expr> using a secret derived from a 32-byte salt.",
+        format: "!(hide-salted <string> <expr>)",
+        description: &[
+            "The salt is given as 64 hex digits. The secret is the salt, read as a little-endian integer,",
+            "  reduced modulo the order of the field, so commitments made elsewhere with the same salt",
+            "  and payload are equal.",
+        ],
+        example: &[
+            "!(hide-salted \"0x0707070707070707070707070707070707070707070707070707070707070707\" '(13 . 21))",
+        ],
+        run: |repl, args, _path| {
+            let (first, second) = repl.peek2(args)?;
+            let salt = repl.get_string(&first)?;
+            let salt: [u8; 32] = hex::decode(salt.strip_prefix("0x").unwrap_or(&salt))
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| anyhow!("Salt must be 64 hex digits. Got {salt}"))?;
+            let (second_io, ..) = repl
+                .eval_expr(second)
+                .with_context(|| "evaluating second arg")?;
+            repl.hide(F::from_le_bytes_reduced(&salt), second_io[0])
+        },
+    };
+
+    const EXPORT_OPENING: MetaCmd<F, C> = MetaCmd {
+        name: "export-opening",
+        summary: "Write the opening of a commitment to a JSON file.",
+        format: "!(export-opening <commitment> <string>)",
+        description: &[
+            "The file holds the field, the hash, the secret and the payload as Lurk source, so it can be",
+            "  read by other systems. Payloads that can't be read back, such as functions, are rejected.",
+            "**Warning**: the file holds the secret.",
+        ],
+        example: &[
+            "!(hide 12345 '(13 . 21))",
+            "!(export-opening 0x1884a703eea837ffae6ae99ec9af8e90d3fce7666c7953ffbe5eac7463ed1819 \"opening.json\")",
+        ],
+        run: |repl, args, _path| {
+            let (first, second) = repl.peek2(args)?;
+            let first = repl.store.list(vec![first]);
+            let hash = *repl.get_comm_hash(&first)?;
+            let path = get_path(repl, &second)?;
+            repl.export_opening(&hash, &path)?;
+            println!("Opening written to {path}");
+            Ok(())
+        },
+    };
+
+    const IMPORT_OPENING: MetaCmd<F, C> = MetaCmd {
+        name: "import-opening",
+        summary: "Check and persist a commitment from a JSON opening.",
+        format: "!(import-opening <string>)",
+        description: &[
+            "Reads a file in the format written by `export-opening`, possibly produced by other systems,",
+            "  and fails unless the secret and the payload hash to the claimed commitment.",
+        ],
+        example: &[
+            "!(import-opening \"opening.json\")",
+            "(open 0x1884a703eea837ffae6ae99ec9af8e90d3fce7666c7953ffbe5eac7463ed1819)",
+        ],
+        run: |repl, args, _path| {
+            let path = get_path(repl, &repl.peek1(args)?)?;
+            repl.import_opening(&path)
+        },
+    };
+
     const FETCH: MetaCmd<F, C> = MetaCmd {
         name: "fetch",
         summary: "Add data from a commitment to the repl store.",
@@ -377,6 +450,7 @@ where
             let proof_id = repl.get_string(&first)?;
             LurkProof::<_, C>::verify_proof(
                 &proof_id,
+                None,
             )
         }
     };
@@ -384,20 +458,55 @@ where
     const DEFPACKAGE: MetaCmd<F, C> = MetaCmd {
         name: "defpackage",
         summary: "Add a package to the state.",
-        format: "!(defpackage <string|symbol>)",
-        description: &[],
-        example: &["!(defpackage abc)"],
+        format: "!(defpackage <string|symbol> (:use <string|symbol> ...) (:export <symbol> ...))",
+        description: &[
+            "The options are optional. `:use` imports the symbols exported by other packages",
+            "and `:export` restricts the symbols made accessible to packages that use this one.",
+            "Fails if the package already exists.",
+        ],
+        example: &[
+            "!(defpackage abc)",
+            "!(defpackage app (:use .lurk) (:export factorial))",
+        ],
         run: |repl, args, _path| {
-            // TODO: handle args
-            let (name, _args) = repl.store.car_cdr(args)?;
-            let name = match name.tag() {
-                Tag::Expr(ExprTag::Str) => repl.state.borrow_mut().intern(repl.get_string(&name)?),
-                Tag::Expr(ExprTag::Sym) => repl.get_symbol(&name)?.into(),
-                _ => bail!("Package name must be a string or a symbol"),
+            let (name, options) = repl.store.car_cdr(args)?;
+            let name = repl.get_package_name(&name)?;
+            let mut package = Package::new(name.clone());
+            let Some((options, None)) = repl.store.fetch_list(&options) else {
+                bail!("Package options must be a list")
             };
+            for option in options {
+                let Some((option, None)) = repl.store.fetch_list(&option) else {
+                    bail!("Package options must be lists")
+                };
+                let Some((key, values)) = option.split_first() else {
+                    bail!("Empty package option")
+                };
+                let key = repl.get_symbol(key)?;
+                if key == Symbol::key(&["use"]) {
+                    for value in values {
+                        let used = repl.get_package_name(value)?;
+                        let state = repl.state.borrow();
+                        let Some(used) = state.get_package(&used) else {
+                            bail!("Package {used} not found")
+                        };
+                        package.use_package(used)?;
+                    }
+                } else if key == Symbol::key(&["export"]) {
+                    let names = values
+                        .iter()
+                        .map(|value| Ok(repl.get_symbol(value)?.name()?.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+                    for name in &names {
+                        package.intern(name.clone());
+                    }
+                    package.export(&names)?;
+                } else {
+                    bail!("Unknown package option {key}")
+                }
+            }
+            repl.state.borrow_mut().define_package(package)?;
             println!("{}", repl.state.borrow().fmt_to_string(&name));
-            let package = Package::new(name);
-            repl.state.borrow_mut().add_package(package);
             Ok(())
         },
     };
@@ -447,23 +556,8 @@ where
         ],
         run: |repl, args, _path| {
             let first = repl.peek1(args)?;
-            match first.tag() {
-                Tag::Expr(ExprTag::Str) => {
-                    let name = repl.get_string(&first)?;
-                    let package_name = repl.state.borrow_mut().intern(name);
-                    repl.state.borrow_mut().set_current_package(package_name)
-                }
-                Tag::Expr(ExprTag::Sym) => {
-                    let package_name = repl.get_symbol(&first)?;
-                    repl.state
-                        .borrow_mut()
-                        .set_current_package(package_name.into())
-                }
-                _ => bail!(
-                    "Expected string or symbol. Got {}",
-                    first.fmt_to_string(&repl.store, &repl.state.borrow())
-                ),
-            }
+            let package_name = repl.get_package_name(&first)?;
+            repl.state.borrow_mut().set_current_package(package_name)
         },
     };
 
@@ -816,6 +910,34 @@ where
         Ok((fun, backend, rc))
     }
 
+    /// Prints the current frame of the debugger, along with the result if it's the last one
+    fn print_current_frame(repl: &Repl<F, C>) -> Result<()> {
+        let Some(debugger) = &repl.debugger else {
+            bail!("Nothing to step through. Start with `!(step <expr>)`")
+        };
+        let state = repl.state.borrow();
+        let frame = debugger.current();
+        println!(
+            "{}",
+            DisplayFrame {
+                index: debugger.cursor(),
+                frame,
+                store: &repl.store,
+                state: &state,
+            }
+        );
+        if debugger.is_done() {
+            match frame.output[2].tag() {
+                Tag::Cont(ContTag::Terminal) => {
+                    println!("=> {}", frame.output[0].fmt_to_string(&repl.store, &state))
+                }
+                Tag::Cont(ContTag::Error) => println!("Evaluation encountered an error"),
+                _ => println!("Limit reached"),
+            }
+        }
+        Ok(())
+    }
+
     /// Returns a vector containing the elements of a list.
     ///
     /// # Errors
@@ -1078,7 +1200,334 @@ where
         },
     };
 
-    const CMDS: [MetaCmd<F, C>; 28] = [
+    const DEFQUERY: MetaCmd<F, C> = MetaCmd {
+        name: "defquery",
+        summary: "Start a memoset scope for a type of queries.",
+        format: "!(defquery <symbol> [(<symbol>) <expr>])",
+        description: &[
+            "The built-in query types are `factorial`, whose queries are `(factorial . <num>)`,",
+            "  and `lookup`, whose queries are `(.lurk.env.lookup <symbol> . <env>)` or, on association",
+            "  lists, `(.lurk.env.assoc <key> . <alist>)`.",
+            "With an argument and a body, defines the query type <symbol>, whose queries are",
+            "  `(<symbol> . <num>)`. The body is an expression over numbers, with number literals,",
+            "  the argument, `+`, `-`, `*`, `(if (= <expr> <expr>) <expr> <expr>)`, and queries",
+            "  `(<symbol> <expr>)` of this type or of the ones defined before it.",
+            "Queries made with `query` are memoized in the scope, along with their subqueries,",
+            "  until they're proved with `prove-queries`.",
+        ],
+        example: &[
+            "!(defquery factorial)",
+            "!(query '(factorial . 5))",
+            "!(defquery fib (n) (if (= n 0) 0 (if (= n 1) 1 (+ (fib (- n 1)) (fib (- n 2))))))",
+            "!(query '(fib . 10))",
+        ],
+        run: |repl, args, _path| {
+            let (first, rest) = repl.store.car_cdr(args)?;
+            if rest.is_nil() {
+                let name = repl.get_symbol(&first)?;
+                let name = name.path().last().map(String::as_str).unwrap_or_default();
+                repl.query_scope = Some(QueryScope::new(name)?);
+                println!("Query scope for {name}");
+            } else {
+                let (scope, name) = QueryScope::define(&repl.store, args)?;
+                repl.query_scope = Some(scope);
+                println!("Query scope for {name}");
+            }
+            Ok(())
+        },
+    };
+
+    const QUERY: MetaCmd<F, C> = MetaCmd {
+        name: "query",
+        summary: "Make a query in the current memoset scope.",
+        format: "!(query <expr>)",
+        description: &[
+            "<expr> is evaluated to the query, whose response is printed.",
+            "Responses are memoized, so repeated queries and shared subqueries are only evaluated once.",
+        ],
+        example: &[
+            "!(defquery factorial)",
+            "!(query '(factorial . 5))",
+            "!(query (cons 'factorial 6))",
+        ],
+        run: |repl, args, _path| {
+            let first = repl.peek1(args)?;
+            let (first_io, ..) = repl
+                .eval_expr(first)
+                .with_context(|| "evaluating query")?;
+            let Some(scope) = repl.query_scope.as_mut() else {
+                bail!("No query scope. Start one with `defquery`")
+            };
+            let response = scope
+                .query(&repl.store, first_io[0])
+                .map_err(|e| repl.source_map.annotate(e, &first))?;
+            println!("{}", response.fmt_to_string(&repl.store, &repl.state.borrow()));
+            Ok(())
+        },
+    };
+
+    const QUERY_STATS: MetaCmd<F, C> = MetaCmd {
+        name: "query-stats",
+        summary: "Print the memoization statistics of the current memoset scope.",
+        format: "!(query-stats)",
+        description: &[],
+        example: &[
+            "!(defquery factorial)",
+            "!(query '(factorial . 5))",
+            "!(query '(factorial . 3))",
+            "!(query-stats)",
+        ],
+        run: |repl, _args, _path| {
+            let Some(scope) = repl.query_scope.as_ref() else {
+                bail!("No query scope. Start one with `defquery`")
+            };
+            println!("{}", DisplayStats(&scope.stats(&repl.store)));
+            Ok(())
+        },
+    };
+
+    const PROVE_QUERIES: MetaCmd<F, C> = MetaCmd {
+        name: "prove-queries",
+        summary: "Prove the queries of the current memoset scope.",
+        format: "!(prove-queries [<string> <string>])",
+        description: &[
+            "Every query made since `defquery` or the last `prove-queries` is proved with a coroutine proof.",
+            "Without arguments, the proof is verified right away. Otherwise, it's compressed and written to the",
+            "  first path, and its public inputs to the second one, to be checked with `lurk verify`.",
+            "The scope is emptied afterwards.",
+        ],
+        example: &[
+            "!(defquery factorial)",
+            "!(query '(factorial . 5))",
+            "!(prove-queries \"fact.proof\" \"fact.inputs\")",
+        ],
+        run: |repl, args, _path| {
+            let paths = if args.is_nil() {
+                None
+            } else {
+                let (proof_path, inputs_path) = repl.peek2(args)?;
+                Some((get_path(repl, &proof_path)?, get_path(repl, &inputs_path)?))
+            };
+            let Some(scope) = repl.query_scope.as_mut() else {
+                bail!("No query scope. Start one with `defquery`")
+            };
+            println!("{}", DisplayStats(&scope.stats(&repl.store)));
+            match paths {
+                None => {
+                    if !scope.prove_and_verify(&repl.store)? {
+                        bail!("Queries proof failed to verify")
+                    }
+                    println!("Queries proved and verified");
+                }
+                Some((proof_path, inputs_path)) => {
+                    let (proof, public_inputs) = scope.prove(&repl.store, true)?;
+                    dump(proof, &proof_path)?;
+                    std::fs::write(&inputs_path, public_inputs.to_bytes())?;
+                    println!("Queries proof written to {proof_path}, public inputs to {inputs_path}");
+                }
+            }
+            Ok(())
+        },
+    };
+
+    const CONSTRAINT_COUNT: MetaCmd<F, C> = MetaCmd {
+        name: "constraint-count",
+        summary: "Print the sizes of the circuits that would prove an expression or the current queries.",
+        format: "!(constraint-count [<expr>])",
+        description: &[
+            "The circuits are synthesized but nothing is proved, so this is much cheaper than `prove`.",
+            "With an expression, evaluates it in the current env and reports the constraints and auxiliary",
+            "  variables of each distinct folding step circuit, for the current backend and rc.",
+            "Without arguments, does the same for the chunk circuits of the memoset scope started with",
+            "  `defquery`, without finalizing it.",
+            "The estimated proving time is a rough, machine-independent heuristic.",
+        ],
+        example: &[
+            "!(constraint-count (+ 1 2))",
+            "!(defquery factorial)",
+            "!(query '(factorial . 5))",
+            "!(constraint-count)",
+        ],
+        run: |repl, args, _path| {
+            if args.is_nil() {
+                repl.print_query_constraint_count()
+            } else {
+                let expr = repl.peek1(args)?;
+                repl.print_constraint_count(expr)
+            }
+        },
+    };
+
+    const STEP: MetaCmd<F, C> = MetaCmd {
+        name: "step",
+        summary: "Step through the evaluation of an expression.",
+        format: "!(step [<expr>])",
+        description: &[
+            "With an expression, evaluates its first frame in the current env and shows it.",
+            "Without arguments, evaluates and moves to the next frame of the expression given to the last `step`.",
+            "A frame shows the expression, env and continuation about to be reduced, as Lurk data.",
+        ],
+        example: &["!(step (+ 1 2))", "!(step)", "!(frames)"],
+        run: |repl, args, _path| {
+            if args.is_nil() {
+                let Some(mut debugger) = repl.debugger.take() else {
+                    bail!("Nothing to step through. Start with `!(step <expr>)`")
+                };
+                let stepped = debugger.step(repl.lang_setup(), &repl.store);
+                repl.debugger = Some(debugger);
+                if !stepped? {
+                    bail!("Already at the last frame")
+                }
+            } else {
+                let expr = repl.peek1(args)?;
+                repl.debugger = Some(Debugger::new(
+                    repl.lang_setup(),
+                    expr,
+                    repl.env,
+                    &repl.store,
+                    repl.limit,
+                )?);
+            }
+            Self::print_current_frame(repl)
+        },
+    };
+
+    const BREAK: MetaCmd<F, C> = MetaCmd {
+        name: "break",
+        summary: "Step until a symbol is evaluated.",
+        format: "!(break <symbol>)",
+        description: &[
+            "Moves to the next frame whose expression is <symbol> or a call to it, in the evaluation started",
+            "  with `step`.",
+        ],
+        example: &[
+            "!(def square (lambda (x) (* x x)))",
+            "!(step (+ 1 (square 2)))",
+            "!(break square)",
+        ],
+        run: |repl, args, _path| {
+            let sym = repl.peek1(args)?;
+            if !sym.is_sym() {
+                bail!(
+                    "Breakpoint must be a symbol. Got {}",
+                    sym.fmt_to_string(&repl.store, &repl.state.borrow())
+                )
+            }
+            let Some(mut debugger) = repl.debugger.take() else {
+                bail!("Nothing to step through. Start with `!(step <expr>)`")
+            };
+            let hit = debugger.run_to(repl.lang_setup(), &repl.store, &sym);
+            repl.debugger = Some(debugger);
+            if !hit? {
+                bail!(
+                    "{} isn't evaluated in the remaining frames",
+                    sym.fmt_to_string(&repl.store, &repl.state.borrow())
+                )
+            }
+            Self::print_current_frame(repl)
+        },
+    };
+
+    const FRAMES: MetaCmd<F, C> = MetaCmd {
+        name: "frames",
+        summary: "Show the frames of the evaluation started with `step`.",
+        format: "!(frames [<num>])",
+        description: &[
+            "Without arguments, lists the expression of every frame up to the current one.",
+            "With a frame index, shows the whole frame, which can be ahead of the current one if it was",
+            "  evaluated by a `break` that didn't find its symbol.",
+        ],
+        example: &["!(step (+ 1 2))", "!(step)", "!(frames)", "!(frames 0)"],
+        run: |repl, args, _path| {
+            let Some(debugger) = &repl.debugger else {
+                bail!("Nothing to step through. Start with `!(step <expr>)`")
+            };
+            let state = repl.state.borrow();
+            if args.is_nil() {
+                for (index, frame) in debugger.frames()[..=debugger.cursor()].iter().enumerate() {
+                    if frame.pc == 0 {
+                        println!(
+                            "{index}: {}",
+                            frame.input[0].fmt_to_string(&repl.store, &state)
+                        );
+                    } else {
+                        println!("{index}: <coprocessor {}>", frame.pc - 1);
+                    }
+                }
+                let remaining = debugger.frames().len() - debugger.cursor() - 1;
+                if remaining > 0 {
+                    println!("({remaining} frames ahead)");
+                }
+                return Ok(());
+            }
+            let arg = repl.peek1(args)?;
+            let (Tag::Expr(ExprTag::Num), RawPtr::Atom(idx)) = arg.parts() else {
+                bail!("Frame index must be a Num")
+            };
+            let index = repl
+                .store
+                .expect_f(*idx)
+                .to_u64()
+                .map(|u| u as usize)
+                .filter(|index| *index < debugger.frames().len());
+            let Some(index) = index else {
+                bail!(
+                    "Frame index must be smaller than {}",
+                    debugger.frames().len()
+                )
+            };
+            let frame = &debugger.frames()[index];
+            println!(
+                "{}",
+                DisplayFrame {
+                    index,
+                    frame,
+                    store: &repl.store,
+                    state: &state,
+                }
+            );
+            Ok(())
+        },
+    };
+
+    const SAVE_STORE: MetaCmd<F, C> = MetaCmd {
+        name: "save-store",
+        summary: "Write the store and the env to the file system.",
+        format: "!(save-store <string>)",
+        description: &[
+            "Everything interned so far is written, including commitment openings, so that the session",
+            "  can be resumed with `load-store`.",
+        ],
+        example: &[
+            "!(def x (commit 42))",
+            "!(save-store \"session.store\")",
+        ],
+        run: |repl, args, _path| {
+            let path = get_path(repl, &repl.peek1(args)?)?;
+            repl.save_store(&path)?;
+            println!("Store saved to {path}");
+            Ok(())
+        },
+    };
+
+    const LOAD_STORE: MetaCmd<F, C> = MetaCmd {
+        name: "load-store",
+        summary: "Read the store and the env from the file system.",
+        format: "!(load-store <string>)",
+        description: &[
+            "Replaces the store and the env with the ones written by `save-store`.",
+            "The last evaluation, the query scope and the `step` session are discarded.",
+        ],
+        example: &["!(load-store \"session.store\")", "(open x)"],
+        run: |repl, args, _path| {
+            let path = get_path(repl, &repl.peek1(args)?)?;
+            repl.load_store(&path)?;
+            println!("Store loaded from {path}");
+            Ok(())
+        },
+    };
+
+    const CMDS: [MetaCmd<F, C>; 41] = [
         MetaCmd::LOAD,
         MetaCmd::DEF,
         MetaCmd::DEFREC,
@@ -1088,6 +1537,9 @@ where
         MetaCmd::ASSERT_ERROR,
         MetaCmd::COMMIT,
         MetaCmd::HIDE,
+        MetaCmd::HIDE_SALTED,
+        MetaCmd::EXPORT_OPENING,
+        MetaCmd::IMPORT_OPENING,
         MetaCmd::FETCH,
         MetaCmd::OPEN,
         MetaCmd::CLEAR,
@@ -1107,6 +1559,16 @@ where
         MetaCmd::DEFPROTOCOL,
         MetaCmd::PROVE_PROTOCOL,
         MetaCmd::VERIFY_PROTOCOL,
+        MetaCmd::DEFQUERY,
+        MetaCmd::QUERY,
+        MetaCmd::QUERY_STATS,
+        MetaCmd::PROVE_QUERIES,
+        MetaCmd::STEP,
+        MetaCmd::BREAK,
+        MetaCmd::FRAMES,
+        MetaCmd::SAVE_STORE,
+        MetaCmd::LOAD_STORE,
+        MetaCmd::CONSTRAINT_COUNT,
     ];
 
     pub(super) fn cmds() -> std::collections::HashMap<&'static str, MetaCmd<F, C>> {
@@ -1,22 +1,28 @@
+mod bench;
+mod constraints;
+mod debugger;
 mod meta_cmd;
+mod queries;
+mod source_map;
 
 use abomonation::Abomonation;
 use anyhow::{anyhow, bail, Context, Result};
 use camino::{Utf8Path, Utf8PathBuf};
 use ff::PrimeField;
 use rustyline::{
+    completion::Completer,
     error::ReadlineError,
     history::DefaultHistory,
     validate::{MatchingBracketValidator, ValidationContext, ValidationResult, Validator},
-    Config, Editor,
+    CompletionType, Config, Editor,
 };
-use rustyline_derive::{Completer, Helper, Highlighter, Hinter};
-use serde::{de::DeserializeOwned, Serialize};
+use rustyline_derive::{Helper, Highlighter, Hinter};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     cell::{OnceCell, RefCell},
     collections::HashMap,
-    fs::read_to_string,
-    io::Write,
+    fs::{read_to_string, File},
+    io::{BufReader, BufWriter, Write},
     rc::Rc,
     sync::Arc,
 };
@@ -37,10 +43,11 @@ use crate::{
         tag::Tag,
         Func,
     },
+    package::SymbolRef,
     parser,
     proof::{
-        nova::{CurveCycleEquipped, Dual, NovaProver},
-        supernova::SuperNovaProver,
+        nova::{self, CurveCycleEquipped, Dual, NovaProver},
+        supernova::{self, SuperNovaProver},
         RecursiveSNARKTrait,
     },
     public_parameters::{
@@ -54,26 +61,71 @@ use crate::{
 
 use super::{
     backend::Backend,
-    commitment::Commitment,
+    commitment::{Commitment, Opening},
     field_data::load,
     lurk_proof::{LurkProof, LurkProofMeta, LurkProofWrapper},
     paths::{commitment_path, repl_history},
     zstore::ZDag,
 };
 
+use debugger::Debugger;
 use meta_cmd::MetaCmd;
+pub(crate) use queries::QueryScope;
+use source_map::{SourceCursor, SourceMap};
 
-#[derive(Completer, Helper, Highlighter, Hinter)]
-struct InputValidator {
+#[derive(Helper, Highlighter, Hinter)]
+struct InputHelper {
     brackets: MatchingBracketValidator,
+    state: Rc<RefCell<State>>,
+    meta_cmds: Vec<&'static str>,
 }
 
-impl Validator for InputValidator {
+impl Validator for InputHelper {
     fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
         self.brackets.validate(ctx)
     }
 }
 
+impl InputHelper {
+    /// Where the word before `pos` starts, and its completions: meta command names right after `!(`, and symbols
+    /// anywhere else
+    fn completions(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .char_indices()
+            .rfind(|(_, c)| c.is_whitespace() || matches!(c, '(' | ')' | '\''))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let prefix = &line[start..pos];
+        let candidates = if line[..start].ends_with("!(") {
+            let mut names = self
+                .meta_cmds
+                .iter()
+                .filter(|name| name.starts_with(prefix))
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>();
+            names.sort();
+            names
+        } else if prefix.is_empty() {
+            vec![]
+        } else {
+            self.state.borrow().completions(prefix)
+        };
+        (start, candidates)
+    }
+}
+
+impl Completer for InputHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.completions(line, pos))
+    }
+}
+
 #[allow(dead_code)]
 struct Evaluation {
     frames: Vec<Frame>,
@@ -102,6 +154,34 @@ pub(crate) struct Repl<F: LurkField, C: Coprocessor<F> + Serialize + Deserialize
     pwd_path: Utf8PathBuf,
     meta: HashMap<&'static str, MetaCmd<F, C>>,
     apply_fn: OnceCell<Ptr>,
+    query_scope: Option<QueryScope<F>>,
+    debugger: Option<Debugger>,
+    source_map: SourceMap,
+    /// The expressions and envs of the evaluations memoized so far, when recording them for `lurk bench`
+    bench_cases: Option<Vec<(Ptr, Ptr)>>,
+}
+
+/// The public parameters used by `Repl::prove_frames_with`, loaded on first use and kept in memory afterwards. They
+/// can be shared by `Repl`s with the same `Lang` and rc
+pub(crate) struct PublicParamsCache<F: CurveCycleEquipped> {
+    nova: once_cell::sync::OnceCell<nova::PublicParams<F>>,
+    supernova: once_cell::sync::OnceCell<supernova::PublicParams<F>>,
+}
+
+impl<F: CurveCycleEquipped> Default for PublicParamsCache<F> {
+    fn default() -> Self {
+        Self {
+            nova: Default::default(),
+            supernova: Default::default(),
+        }
+    }
+}
+
+/// The beginning of the files written by `!(save-store ...)`, followed by the image of the store
+#[derive(Serialize, Deserialize)]
+struct StoreSnapshotHeader {
+    field_modulus: String,
+    env: Ptr,
 }
 
 pub(crate) fn validate_non_zero(name: &str, x: usize) -> Result<()> {
@@ -160,6 +240,22 @@ impl<F: LurkField, C: Coprocessor<F> + Serialize + DeserializeOwned> Repl<F, C>
             )
         })
     }
+
+    /// Package names are symbols or strings, which are interned in the current
+    /// package
+    fn get_package_name(&self, ptr: &Ptr) -> Result<SymbolRef> {
+        match ptr.tag() {
+            Tag::Expr(ExprTag::Str) => {
+                let name = self.get_string(ptr)?;
+                Ok(self.state.borrow_mut().intern(name))
+            }
+            Tag::Expr(ExprTag::Sym) => Ok(self.get_symbol(ptr)?.into()),
+            _ => bail!(
+                "Expected string or symbol. Got {}",
+                ptr.fmt_to_string(&self.store, &self.state.borrow())
+            ),
+        }
+    }
 }
 
 impl<
@@ -206,6 +302,10 @@ where
             pwd_path,
             meta: MetaCmd::cmds(),
             apply_fn: OnceCell::new(),
+            query_scope: None,
+            debugger: None,
+            source_map: SourceMap::default(),
+            bench_cases: None,
         }
     }
 
@@ -296,6 +396,16 @@ where
 
     /// Proves a computation and returns the proof key
     pub(crate) fn prove_frames(&self, frames: &[Frame], iterations: usize) -> Result<String> {
+        self.prove_frames_with(frames, iterations, &PublicParamsCache::default())
+    }
+
+    /// Like `prove_frames`, but reading the public parameters from `params`, which keeps them for the next proofs
+    pub(crate) fn prove_frames_with(
+        &self,
+        frames: &[Frame],
+        iterations: usize,
+        params: &PublicParamsCache<F>,
+    ) -> Result<String> {
         info!("Hydrating the store");
         self.store.hydrate_z_cache();
 
@@ -338,10 +448,12 @@ where
             info!("Proof not cached");
             let (proof, public_inputs, public_outputs) = match self.backend {
                 Backend::Nova => {
-                    info!("Loading Nova public parameters");
-                    let instance =
-                        Instance::new(self.rc, self.lang.clone(), true, Kind::NovaPublicParams);
-                    let pp = public_params(&instance)?;
+                    let pp = params.nova.get_or_try_init(|| {
+                        info!("Loading Nova public parameters");
+                        let instance =
+                            Instance::new(self.rc, self.lang.clone(), true, Kind::NovaPublicParams);
+                        public_params(&instance)
+                    })?;
 
                     let prover = NovaProver::<_, C>::new(self.rc, self.lang.clone());
                     info!("Proving with NovaProver");
@@ -354,10 +466,16 @@ where
                     (LurkProofWrapper::Nova(proof), public_inputs, public_outputs)
                 }
                 Backend::SuperNova => {
-                    info!("Loading SuperNova public parameters");
-                    let instance =
-                        Instance::new(self.rc, self.lang.clone(), true, Kind::SuperNovaAuxParams);
-                    let pp = supernova_public_params(&instance)?;
+                    let pp = params.supernova.get_or_try_init(|| {
+                        info!("Loading SuperNova public parameters");
+                        let instance = Instance::new(
+                            self.rc,
+                            self.lang.clone(),
+                            true,
+                            Kind::SuperNovaAuxParams,
+                        );
+                        supernova_public_params(&instance)
+                    })?;
 
                     let prover = SuperNovaProver::<_, C>::new(self.rc, self.lang.clone());
                     info!("Proving with SuperNovaProver");
@@ -390,6 +508,30 @@ where
         Ok(proof_key)
     }
 
+    /// Reads the single expression in `source`, evaluates it in the current env and proves the evaluation, returning
+    /// the proof key. Meta commands are rejected
+    pub(crate) fn prove_expression(
+        &mut self,
+        source: &str,
+        params: &PublicParamsCache<F>,
+    ) -> Result<String> {
+        let (.., expr, is_meta) = self.store.read_maybe_meta(self.state.clone(), source)?;
+        if is_meta {
+            bail!("Meta commands can't be proved")
+        }
+        let (output, iterations) = self.eval_expr_and_memoize(expr)?;
+        if output[2].tag() != &Tag::Cont(ContTag::Terminal) {
+            bail!(
+                "Evaluation didn't terminate successfully after {}",
+                Self::pretty_iterations_display(iterations)
+            )
+        }
+        let Some(Evaluation { frames, iterations }) = &self.evaluation else {
+            unreachable!("the evaluation was just memoized")
+        };
+        self.prove_frames_with(frames, *iterations, params)
+    }
+
     /// Proves the last cached computation and returns the proof key
     pub(crate) fn prove_last_frames(&self) -> Result<String> {
         match self.evaluation.as_ref() {
@@ -398,6 +540,42 @@ where
         }
     }
 
+    /// Writes the whole store and the current env to `path`, for `Repl::load_store`
+    fn save_store(&self, path: &Utf8Path) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("creating {path}"))?;
+        let mut writer = BufWriter::new(file);
+        let header = StoreSnapshotHeader {
+            field_modulus: F::MODULUS.to_owned(),
+            env: self.env,
+        };
+        bincode::serialize_into(&mut writer, &header)?;
+        self.store.dump_into(writer)
+    }
+
+    /// Replaces the store and the env with the ones written by `Repl::save_store`. Everything else that points to the
+    /// previous store is discarded.
+    fn load_store(&mut self, path: &Utf8Path) -> Result<()> {
+        let file = File::open(path).with_context(|| format!("opening {path}"))?;
+        let mut reader = BufReader::new(file);
+        let header: StoreSnapshotHeader = bincode::deserialize_from(&mut reader)
+            .with_context(|| format!("reading the header of {path}"))?;
+        if header.field_modulus != F::MODULUS {
+            bail!("Field mismatch")
+        }
+        self.store =
+            Store::load_from(reader).with_context(|| format!("reading store from {path}"))?;
+        self.env = header.env;
+        self.evaluation = None;
+        self.apply_fn = OnceCell::new();
+        self.query_scope = None;
+        self.debugger = None;
+        self.source_map = SourceMap::default();
+        if let Some(cases) = &mut self.bench_cases {
+            cases.clear();
+        }
+        Ok(())
+    }
+
     fn hide(&mut self, secret: F, payload: Ptr) -> Result<()> {
         let commitment = Commitment::new(Some(secret), payload, &self.store);
         let hash_str = &commitment.hash.hex_digits();
@@ -430,6 +608,30 @@ where
         Ok(())
     }
 
+    /// Writes the opening of a persisted commitment as JSON to `path`
+    fn export_opening(&mut self, hash: &F, path: &Utf8Path) -> Result<()> {
+        let commitment: Commitment<F> = load(&commitment_path(&hash.hex_digits()))?;
+        if &commitment.hash != hash {
+            bail!("Hash mismatch. Corrupted commitment file.")
+        }
+        let opening = commitment.to_opening(&self.store)?;
+        std::fs::write(path, serde_json::to_string_pretty(&opening)?)
+            .with_context(|| format!("writing {path}"))?;
+        Ok(())
+    }
+
+    /// Checks and persists the commitment of the JSON opening in `path`, whose
+    /// data then becomes available
+    fn import_opening(&mut self, path: &Utf8Path) -> Result<()> {
+        let json = std::fs::read_to_string(path).with_context(|| format!("reading {path}"))?;
+        let opening: Opening = serde_json::from_str(&json)?;
+        let commitment = Commitment::from_opening(&opening, &self.store)?;
+        let hash_str = &commitment.hash.hex_digits();
+        commitment.persist()?;
+        println!("Hash: 0x{hash_str}");
+        Ok(())
+    }
+
     fn pretty_iterations_display(iterations: usize) -> String {
         if iterations != 1 {
             format!("{iterations} iterations")
@@ -451,7 +653,10 @@ where
             t => {
                 let iterations_display = Self::pretty_iterations_display(iterations);
                 if t == &Tag::Cont(ContTag::Error) {
-                    bail!("Evaluation encountered an error after {iterations_display}")
+                    bail!(
+                        "Evaluation encountered an error after {iterations_display}{}",
+                        self.error_location([&ptrs[0], &expr])
+                    )
                 } else {
                     bail!("Limit reached after {iterations_display}")
                 }
@@ -501,6 +706,9 @@ where
         };
 
         let output = last_frames.output.clone();
+        if let Some(cases) = &mut self.bench_cases {
+            cases.push((expr_ptr, self.env));
+        }
         self.evaluation = Some(Evaluation { frames, iterations });
         Ok((output, iterations))
     }
@@ -518,6 +726,14 @@ where
         Ok(self.store.expect_f(*hash_idx))
     }
 
+    /// Points at the source of the first expression in `exprs` that was read by the REPL, if any
+    fn error_location<'a>(&self, exprs: impl IntoIterator<Item = &'a Ptr>) -> String {
+        match self.source_map.locate(exprs) {
+            Some(span) => format!("\n  at {span}"),
+            None => String::new(),
+        }
+    }
+
     pub(crate) fn handle_non_meta(&mut self, expr_ptr: Ptr) -> Result<()> {
         let (output, iterations) = self.eval_expr_and_memoize(expr_ptr)?;
         let iterations_display = Self::pretty_iterations_display(iterations);
@@ -530,7 +746,16 @@ where
                 Ok(())
             }
             Tag::Cont(ContTag::Error) => {
-                bail!("Evaluation encountered an error after {iterations_display}")
+                let Some(Evaluation { frames, .. }) = &self.evaluation else {
+                    unreachable!("the evaluation was just memoized")
+                };
+                // the expression that failed or else the closest one before it that was read from source
+                let exprs = frames.iter().map(|frame| &frame.input[0]);
+                let location = match self.source_map.locate_eval(exprs.chain([&output[0]])) {
+                    Some(span) => format!("\n  at {span}"),
+                    None => String::new(),
+                };
+                bail!("Evaluation encountered an error after {iterations_display}{location}")
             }
             _ => bail!("Limit reached after {iterations_display}"),
         }
@@ -567,14 +792,22 @@ where
         )
     }
 
+    /// Reads the next form from `input` and handles it. `cursor` is where `input` starts, and is moved to where the
+    /// returned input does
     fn handle_form<'a>(
         &mut self,
         input: parser::Span<'a>,
         file_path: &Utf8Path,
+        cursor: &mut SourceCursor,
         demo: bool,
     ) -> Result<parser::Span<'a>> {
+        let mut form_spans = cursor.form_spans();
         let (syntax_start, mut new_input, ptr, is_meta) =
-            self.store.read_maybe_meta(self.state.clone(), &input)?;
+            self.store
+                .read_maybe_meta_with_pos(self.state.clone(), &input, &mut |ptr, pos| {
+                    form_spans.record(ptr, pos)
+                })?;
+        self.source_map.extend(form_spans);
         if demo {
             // adjustment to print the exclamation mark in the right place
             let syntax_start = syntax_start - usize::from(is_meta);
@@ -593,6 +826,7 @@ where
             // ENTER already prints a new line so we can remove it from the start of incoming input
             new_input = parser::Span::new(new_input.trim_start_matches('\n'));
         }
+        cursor.advance(&input[..input.len() - new_input.len()]);
         if is_meta {
             self.handle_meta(ptr, file_path)?;
         } else {
@@ -610,12 +844,13 @@ where
         }
 
         let mut input = parser::Span::new(&input);
+        let mut cursor = SourceCursor::new(Some(file_path));
         loop {
             let Some(file_dir) = file_path.parent() else {
                 bail!("Can't load parent of {}", file_path);
             };
 
-            match self.handle_form(input, file_dir, demo) {
+            match self.handle_form(input, file_dir, &mut cursor, demo) {
                 Ok(new_input) => input = new_input,
                 Err(e) => {
                     if let Some(parser::Error::NoInput) = e.downcast_ref::<parser::Error>() {
@@ -631,15 +866,18 @@ where
     pub(crate) fn start(&mut self) -> Result<()> {
         println!("Lurk REPL welcomes you.");
 
-        let mut editor: Editor<InputValidator, DefaultHistory> = Editor::with_config(
+        let mut editor: Editor<InputHelper, DefaultHistory> = Editor::with_config(
             Config::builder()
                 .color_mode(rustyline::ColorMode::Enabled)
+                .completion_type(CompletionType::List)
                 .auto_add_history(true)
                 .build(),
         )?;
 
-        editor.set_helper(Some(InputValidator {
+        editor.set_helper(Some(InputHelper {
             brackets: MatchingBracketValidator::new(),
+            state: self.state.clone(),
+            meta_cmds: self.meta.keys().copied().collect(),
         }));
 
         let history_path = &repl_history();
@@ -651,9 +889,17 @@ where
         loop {
             match editor.readline(&self.input_marker()) {
                 Ok(line) => {
-                    editor.save_history(history_path)?;
-                    match self.store.read_maybe_meta(self.state.clone(), &line) {
+                    // appending rather than overwriting keeps the lines of concurrent sessions
+                    editor.append_history(history_path)?;
+                    let mut form_spans = SourceCursor::new(None).form_spans();
+                    let read = self.store.read_maybe_meta_with_pos(
+                        self.state.clone(),
+                        &line,
+                        &mut |ptr, pos| form_spans.record(ptr, pos),
+                    );
+                    match read {
                         Ok((.., expr_ptr, is_meta)) => {
+                            self.source_map.extend(form_spans);
                             if is_meta {
                                 if let Err(e) = self.handle_meta(expr_ptr, &self.pwd_path.clone()) {
                                     eprintln!("!Error: {e}")
@@ -690,4 +936,24 @@ mod test {
         assert_eq!(pad(610, 10), 610);
         assert_eq!(pad(619, 20), 620);
     }
+
+    #[test]
+    fn test_completions() {
+        use crate::{cli::repl::InputHelper, state::State};
+        use rustyline::validate::MatchingBracketValidator;
+
+        let helper = InputHelper {
+            brackets: MatchingBracketValidator::new(),
+            state: State::init_lurk_state().rccell(),
+            meta_cmds: vec!["load", "lookup", "prove"],
+        };
+        let complete = |line: &str| helper.completions(line, line.len());
+        assert_eq!(complete("!(lo"), (2, vec!["load".into(), "lookup".into()]));
+        assert_eq!(complete("(ca").0, 1);
+        assert!(complete("(ca").1.contains(&"car".into()));
+        assert_eq!(complete("(foo "), (5, vec![]));
+        // multibyte whitespace before the word
+        assert_eq!(complete("(cons\u{3000}ca").0, "(cons\u{3000}".len());
+        assert!(complete("(cons\u{3000}ca").1.contains(&"car".into()));
+    }
 }
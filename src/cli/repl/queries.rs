@@ -0,0 +1,271 @@
+use anyhow::{bail, Result};
+use std::fmt::Display;
+
+use crate::{
+    cli::coroutine_proof::{CoroutineProofFile, CoroutineProofWrapper},
+    coroutine::memoset::{
+        demo::DemoQuery, ChunkShapeCache, CoroutineProof, CoroutinePublicInputs, EnvQuery,
+        FoldingParamsCache, LogMemo, Query, QueryDef, Scope, ScopeStats, SuperNovaBackend,
+        UserQuery,
+    },
+    field::LurkField,
+    lem::{pointers::Ptr, store::Store, tag::Tag},
+    proof::{nova::CurveCycleEquipped, supernova::PublicParams},
+    symbol::Symbol,
+    tag::ExprTag,
+};
+
+/// The memoset `Scope` of the queries made with `!(query ...)`, for the query type chosen with `!(defquery ...)`
+pub(crate) enum QueryScope<F: LurkField> {
+    Factorial(Scope<DemoQuery<F>, LogMemo<F>>),
+    Lookup(Scope<EnvQuery<F>, LogMemo<F>>),
+    /// The queries defined with `!(defquery <name> (<arg>) <body>)`
+    User(Scope<UserQuery<F>, LogMemo<F>>),
+}
+
+/// The names of the built-in query types, as accepted by `!(defquery ...)`
+pub(super) const QUERY_TYPES: [&str; 2] = ["factorial", "lookup"];
+
+impl<F: LurkField> QueryScope<F> {
+    /// An empty scope for the query type `spec`: the name of a built-in one, or a list of query definitions
+    /// `(<name> (<arg>) <body>)`, defined in order, as returned by `spec`
+    pub(crate) fn new(spec: &str) -> Result<Self> {
+        match spec {
+            "factorial" => Ok(Self::Factorial(Scope::default())),
+            "lookup" => Ok(Self::Lookup(Scope::default())),
+            _ => {
+                let s = Store::<F>::default();
+                match s
+                    .read_with_default_state(spec)
+                    .ok()
+                    .and_then(|defs| s.fetch_list(&defs))
+                {
+                    Some((defs, None)) if !defs.is_empty() => {
+                        for def in &defs {
+                            QueryDef::define(&s, def)?;
+                        }
+                        Ok(Self::User(Scope::default()))
+                    }
+                    _ => bail!(
+                        "Unknown query type {spec}. Expected one of: {}, or query definitions",
+                        QUERY_TYPES.join(", ")
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Compiles the query definition `(<name> (<arg>) <body>)` and returns an empty scope for it
+    pub(super) fn define(s: &Store<F>, def: &Ptr) -> Result<(Self, Symbol)> {
+        let def = QueryDef::define(s, def)?;
+        Ok((Self::User(Scope::default()), def.name().clone()))
+    }
+
+    pub(super) fn name(&self) -> &'static str {
+        match self {
+            Self::Factorial(_) => "factorial",
+            Self::Lookup(_) => "lookup",
+            Self::User(_) => "user",
+        }
+    }
+
+    /// What `new` takes to make an empty scope for the same query type. For queries defined in Lurk, that's every
+    /// definition made so far, since they all have circuits.
+    pub(crate) fn spec(&self) -> String {
+        match self {
+            Self::Factorial(_) | Self::Lookup(_) => self.name().to_owned(),
+            Self::User(_) => {
+                let defs = QueryDef::all();
+                let defs = defs.iter().map(|def| def.source()).collect::<Vec<_>>();
+                format!("({})", defs.join(" "))
+            }
+        }
+    }
+
+    /// An empty scope for the same query type
+    pub(super) fn empty(&self) -> Self {
+        match self {
+            Self::Factorial(_) => Self::Factorial(Scope::default()),
+            Self::Lookup(_) => Self::Lookup(Scope::default()),
+            Self::User(_) => Self::User(Scope::default()),
+        }
+    }
+
+    /// The response to the query `key`, which is memoized for the next queries
+    pub(crate) fn query(&mut self, s: &Store<F>, key: Ptr) -> Result<Ptr> {
+        fn query_aux<F: LurkField, Q: Query<F>>(
+            scope: &mut Scope<Q, LogMemo<F>>,
+            s: &Store<F>,
+            key: Ptr,
+        ) -> Result<Ptr> {
+            if Q::from_ptr(s, &key).is_none() {
+                bail!("Not a query of this type: {}", key.fmt_to_string_simple(s))
+            }
+            Ok(scope.query(s, key))
+        }
+        match self {
+            Self::Factorial(scope) => {
+                if !matches!(s.car_cdr(&key), Ok((_, n)) if n.tag() == &Tag::Expr(ExprTag::Num)) {
+                    bail!("Factorial queries have the form (factorial . <num>)")
+                }
+                query_aux(scope, s, key)
+            }
+            Self::Lookup(scope) => query_aux(scope, s, key),
+            Self::User(scope) => query_aux(scope, s, key),
+        }
+    }
+
+    pub(super) fn stats(&self, s: &Store<F>) -> ScopeStats {
+        match self {
+            Self::Factorial(scope) => scope.stats(s),
+            Self::Lookup(scope) => scope.stats(s),
+            Self::User(scope) => scope.stats(s),
+        }
+    }
+
+    /// The chunk circuit of every query index, along with how many chunks the queries made so far need. Shapes are
+    /// synthesized in a fresh scope, so this one can keep taking queries, and aren't persisted.
+    pub(super) fn chunk_sizes(&self, s: &Store<F>) -> Result<Vec<ChunkSize>> {
+        fn chunk_sizes_aux<F: LurkField, Q: Query<F>>(
+            scope: &Scope<Q, LogMemo<F>>,
+            s: &Store<F>,
+        ) -> Result<Vec<ChunkSize>> {
+            let stats = scope.stats(s);
+            let mut cache = ChunkShapeCache::in_memory();
+            let mut blank = Scope::<Q, LogMemo<F>>::default();
+            (0..Q::count())
+                .map(|index| {
+                    let rc = scope.rc_for_query(index);
+                    let shape = blank.chunk_shape(s, &mut cache, index, rc)?;
+                    Ok(ChunkSize {
+                        index,
+                        rc,
+                        chunks: stats.chunks.get(&index).copied().unwrap_or(0),
+                        num_constraints: shape.num_constraints(),
+                        num_aux: shape.num_aux,
+                    })
+                })
+                .collect()
+        }
+        match self {
+            Self::Factorial(scope) => chunk_sizes_aux(scope, s),
+            Self::Lookup(scope) => chunk_sizes_aux(scope, s),
+            Self::User(scope) => chunk_sizes_aux(scope, s),
+        }
+    }
+}
+
+/// The size of the chunk circuit of a query index
+pub(super) struct ChunkSize {
+    pub(super) index: usize,
+    pub(super) rc: usize,
+    /// How many chunks are needed to prove the queries with this index
+    pub(super) chunks: usize,
+    pub(super) num_constraints: usize,
+    pub(super) num_aux: usize,
+}
+
+impl<F: CurveCycleEquipped> QueryScope<F> {
+    /// The cache key and the SuperNova public parameters of this query type, read from the `FoldingParamsCache` when
+    /// possible
+    pub(crate) fn public_params(&self, s: &Store<F>) -> Result<(String, PublicParams<F>)> {
+        fn public_params_aux<F: CurveCycleEquipped, Q: Query<F> + Send + Sync>(
+            scope: &Scope<Q, LogMemo<F>>,
+            s: &Store<F>,
+        ) -> Result<(String, PublicParams<F>)> {
+            let cache = FoldingParamsCache::new(&FoldingParamsCache::default_dir())?;
+            let key = scope.folding_params_key::<SuperNovaBackend>(s)?;
+            let pp = scope.cached_folding_public_params(s, &SuperNovaBackend, &cache)?;
+            Ok((key, pp))
+        }
+        match self {
+            Self::Factorial(scope) => public_params_aux(scope, s),
+            Self::Lookup(scope) => public_params_aux(scope, s),
+            Self::User(scope) => public_params_aux(scope, s),
+        }
+    }
+
+    /// Proves every query made so far, compressing the proof if `compress` is set, and returns it with its public
+    /// inputs. The scope is emptied afterwards.
+    pub(super) fn prove(
+        &mut self,
+        s: &Store<F>,
+        compress: bool,
+    ) -> Result<(CoroutineProofFile<F>, CoroutinePublicInputs<F>)> {
+        let (params_key, pp) = self.public_params(s)?;
+        self.prove_with_params(s, compress, params_key, &pp)
+    }
+
+    /// Like `prove`, with public parameters previously returned by `public_params`
+    pub(crate) fn prove_with_params(
+        &mut self,
+        s: &Store<F>,
+        compress: bool,
+        params_key: String,
+        pp: &PublicParams<F>,
+    ) -> Result<(CoroutineProofFile<F>, CoroutinePublicInputs<F>)> {
+        fn prove_aux<F: CurveCycleEquipped, Q: Query<F> + Send + Sync>(
+            scope: &mut Scope<Q, LogMemo<F>>,
+            s: &Store<F>,
+            pp: &PublicParams<F>,
+        ) -> Result<(CoroutineProof<F>, CoroutinePublicInputs<F>)> {
+            let public_inputs = scope.public_inputs(s);
+            let proof = CoroutineProof::prove(scope, s, pp)?;
+            Ok((proof, public_inputs))
+        }
+        let (proof, public_inputs) = match self {
+            Self::Factorial(scope) => prove_aux(scope, s, pp)?,
+            Self::Lookup(scope) => prove_aux(scope, s, pp)?,
+            Self::User(scope) => prove_aux(scope, s, pp)?,
+        };
+        let proof = if compress {
+            CoroutineProofWrapper::Compressed(proof.compress(pp)?)
+        } else {
+            CoroutineProofWrapper::Recursive(proof)
+        };
+        let query = self.spec();
+        *self = self.empty();
+        let file = CoroutineProofFile {
+            query,
+            params_key,
+            proof,
+        };
+        Ok((file, public_inputs))
+    }
+
+    /// Proves every query made so far with SuperNova and verifies the proof. Since proving finalizes the transcript,
+    /// the scope is emptied afterwards.
+    pub(super) fn prove_and_verify(&mut self, s: &Store<F>) -> Result<bool> {
+        fn prove_aux<F: CurveCycleEquipped, Q: Query<F> + Send + Sync>(
+            scope: &mut Scope<Q, LogMemo<F>>,
+            s: &Store<F>,
+        ) -> Result<bool> {
+            let pp = scope.folding_public_params(s, &SuperNovaBackend)?;
+            let public_inputs = scope.public_inputs(s);
+            let proof = CoroutineProof::prove(scope, s, &pp)?;
+            proof.verify(&pp, s, &public_inputs)
+        }
+        let verified = match self {
+            Self::Factorial(scope) => prove_aux(scope, s)?,
+            Self::Lookup(scope) => prove_aux(scope, s)?,
+            Self::User(scope) => prove_aux(scope, s)?,
+        };
+        *self = self.empty();
+        Ok(verified)
+    }
+}
+
+/// Memoization statistics, one per line
+pub(super) struct DisplayStats<'a>(pub(super) &'a ScopeStats);
+
+impl<'a> Display for DisplayStats<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stats = self.0;
+        writeln!(f, "Top-level queries: {}", stats.toplevel_insertions)?;
+        writeln!(f, "Internal queries: {}", stats.internal_insertions)?;
+        let unique_keys: usize = stats.unique_keys.values().sum();
+        writeln!(f, "Unique queries: {unique_keys}")?;
+        writeln!(f, "Max multiplicity: {}", stats.max_multiplicity)?;
+        write!(f, "Chunks: {}", stats.total_chunks())
+    }
+}
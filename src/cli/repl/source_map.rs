@@ -0,0 +1,299 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Display,
+    rc::Rc,
+};
+
+use crate::{lem::pointers::Ptr, parser::position::Pos};
+
+/// Where an expression was read from. Lines and columns start at 1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SourceSpan {
+    /// `None` for input typed in the REPL
+    pub(crate) file: Option<Rc<Utf8PathBuf>>,
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+impl Display for SourceSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{file}:{}:{}", self.line, self.column),
+            None => write!(f, "{}:{}", self.line, self.column),
+        }
+    }
+}
+
+/// The source spans of the syntax nodes read by the REPL, so that errors can point at the offending source.
+///
+/// Expressions are hash-consed, so the same `Ptr` can be read from many nodes. Spans are kept per node of each form,
+/// and an expression is placed at a node of the latest form that has one. The forms kept hold at most `max_nodes`
+/// nodes altogether: the oldest are forgotten first.
+pub(crate) struct SourceMap {
+    forms: VecDeque<FormSpans>,
+    num_nodes: usize,
+    max_nodes: usize,
+}
+
+/// The number of syntax nodes a `SourceMap` keeps by default
+const MAX_NODES: usize = 1 << 16;
+
+impl Default for SourceMap {
+    fn default() -> Self {
+        Self {
+            forms: VecDeque::new(),
+            num_nodes: 0,
+            max_nodes: MAX_NODES,
+        }
+    }
+}
+
+/// The position reached while reading forms from some input
+pub(crate) struct SourceCursor {
+    file: Option<Rc<Utf8PathBuf>>,
+    line: usize,
+    column: usize,
+}
+
+impl SourceCursor {
+    pub(crate) fn new(file: Option<&Utf8Path>) -> Self {
+        Self {
+            file: file.map(|file| Rc::new(file.to_owned())),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Moves the cursor past `consumed`
+    pub(crate) fn advance(&mut self, consumed: &str) {
+        match consumed.rsplit_once('\n') {
+            Some((before, after)) => {
+                self.line += before.matches('\n').count() + 1;
+                self.column = after.chars().count() + 1;
+            }
+            None => self.column += consumed.chars().count(),
+        }
+    }
+
+    /// Collects the spans of a form read from where the cursor is
+    pub(crate) fn form_spans(&self) -> FormSpans {
+        FormSpans {
+            file: self.file.clone(),
+            line: self.line,
+            column: self.column,
+            nodes: Vec::new(),
+            nodes_of: HashMap::default(),
+        }
+    }
+}
+
+/// A syntax node of a form: what it reads as, and where it starts
+struct SyntaxNode {
+    ptr: Ptr,
+    offset: usize,
+    span: SourceSpan,
+}
+
+/// The syntax nodes of a form, as reported by `Store::read_maybe_meta_with_pos`
+pub(crate) struct FormSpans {
+    file: Option<Rc<Utf8PathBuf>>,
+    /// Where the input of the form starts, which positions are relative to
+    line: usize,
+    column: usize,
+    /// In source order, once the form is added to a `SourceMap`
+    nodes: Vec<SyntaxNode>,
+    /// The indices of the nodes of each expression, in source order
+    nodes_of: HashMap<Ptr, Vec<usize>>,
+}
+
+impl FormSpans {
+    pub(crate) fn record(&mut self, ptr: Ptr, pos: Pos) {
+        let Pos::Pos {
+            from_offset,
+            from_line,
+            from_column,
+            ..
+        } = pos
+        else {
+            return;
+        };
+        self.nodes.push(SyntaxNode {
+            ptr,
+            offset: from_offset,
+            span: SourceSpan {
+                file: self.file.clone(),
+                line: self.line + from_line - 1,
+                column: if from_line == 1 {
+                    self.column + from_column - 1
+                } else {
+                    from_column
+                },
+            },
+        });
+    }
+
+    /// Sorts the nodes, which are recorded children first, in source order and indexes them
+    fn index(&mut self) {
+        self.nodes.sort_by_key(|node| node.offset);
+        for (i, node) in self.nodes.iter().enumerate() {
+            self.nodes_of.entry(node.ptr).or_default().push(i);
+        }
+    }
+
+    /// The first node of `ptr` at or after the node `from`, or else its first node
+    fn place(&self, ptr: &Ptr, from: usize) -> Option<usize> {
+        let nodes = self.nodes_of.get(ptr)?;
+        let after = nodes.partition_point(|i| *i < from);
+        nodes.get(after).or(nodes.first()).copied()
+    }
+}
+
+impl SourceMap {
+    pub(crate) fn extend(&mut self, mut form_spans: FormSpans) {
+        if form_spans.nodes.is_empty() {
+            return;
+        }
+        form_spans.index();
+        self.num_nodes += form_spans.nodes.len();
+        self.forms.push_back(form_spans);
+        while self.num_nodes > self.max_nodes && self.forms.len() > 1 {
+            let oldest = self.forms.pop_front().expect("more than one form");
+            self.num_nodes -= oldest.nodes.len();
+        }
+    }
+
+    /// The latest form read with a node of `ptr`
+    fn form_of(&self, ptr: &Ptr) -> Option<&FormSpans> {
+        self.forms
+            .iter()
+            .rev()
+            .find(|form| form.nodes_of.contains_key(ptr))
+    }
+
+    /// The span of the first node of `ptr` in the latest form that has one
+    pub(crate) fn get(&self, ptr: &Ptr) -> Option<&SourceSpan> {
+        let form = self.form_of(ptr)?;
+        Some(&form.nodes[form.place(ptr, 0)?].span)
+    }
+
+    /// The span of the first expression in `exprs` that has one
+    pub(crate) fn locate<'a>(
+        &self,
+        exprs: impl IntoIterator<Item = &'a Ptr>,
+    ) -> Option<&SourceSpan> {
+        exprs.into_iter().find_map(|expr| self.get(expr))
+    }
+
+    /// Follows an evaluation through the nodes of the form it started from and returns the span of the last of
+    /// `exprs` that has a node there. `exprs` are the expressions evaluated, in order, starting with the form itself.
+    /// Evaluation mostly goes through a form in source order, so identical subexpressions are told apart by placing
+    /// each expression at its first node at or after the previous one placed.
+    pub(crate) fn locate_eval<'a>(
+        &self,
+        exprs: impl IntoIterator<Item = &'a Ptr>,
+    ) -> Option<&SourceSpan> {
+        let mut exprs = exprs.into_iter();
+        let first = exprs.next()?;
+        let form = self.form_of(first)?;
+        let mut placed = form.place(first, 0)?;
+        for expr in exprs {
+            if let Some(node) = form.place(expr, placed) {
+                placed = node;
+            }
+        }
+        Some(&form.nodes[placed].span)
+    }
+
+    /// Appends the span of `expr`, if any, to the message of `error`
+    pub(crate) fn annotate(&self, error: anyhow::Error, expr: &Ptr) -> anyhow::Error {
+        match self.get(expr) {
+            Some(span) => anyhow::anyhow!("{error}\n  at {span}"),
+            None => error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::{lem::store::Store, state::State};
+
+    /// Reads every form of `source` into `map`
+    fn read_forms(store: &Store<Fr>, map: &mut SourceMap, source: &str) {
+        let mut input = source;
+        let mut cursor = SourceCursor::new(Some(Utf8Path::new("test.lurk")));
+        while !input.trim().is_empty() {
+            let mut spans = cursor.form_spans();
+            let (_, rest, ..) = store
+                .read_maybe_meta_with_pos(
+                    State::init_lurk_state().rccell(),
+                    input,
+                    &mut |p, pos| spans.record(p, pos),
+                )
+                .unwrap();
+            map.extend(spans);
+            let rest = *rest.fragment();
+            cursor.advance(&input[..input.len() - rest.len()]);
+            input = rest;
+        }
+    }
+
+    #[test]
+    fn test_form_spans() {
+        let store = Store::<Fr>::default();
+        let mut map = SourceMap::default();
+        read_forms(&store, &mut map, "(+ 1 2)\n(car\n  (cons 'a nil))");
+
+        let span = |source| {
+            let ptr = store.read_with_default_state(source).unwrap();
+            map.get(&ptr).map(ToString::to_string)
+        };
+        assert_eq!(span("(+ 1 2)").as_deref(), Some("test.lurk:1:1"));
+        assert_eq!(span("2").as_deref(), Some("test.lurk:1:6"));
+        assert_eq!(span("(cons 'a nil)").as_deref(), Some("test.lurk:3:3"));
+        assert_eq!(span("'a").as_deref(), Some("test.lurk:3:9"));
+        assert_eq!(span("(+ 2 1)"), None);
+    }
+
+    #[test]
+    fn test_locate_eval() {
+        let store = Store::<Fr>::default();
+        let mut map = SourceMap::default();
+        read_forms(&store, &mut map, "(cons (car 1)\n      (car 1))");
+
+        let ptr = |source| store.read_with_default_state(source).unwrap();
+        let (form, car, one) = (ptr("(cons (car 1) (car 1))"), ptr("(car 1)"), ptr("1"));
+        let locate = |exprs: &[Ptr]| map.locate_eval(exprs).map(ToString::to_string);
+        // The same subexpression, evaluated a first and a second time
+        assert_eq!(locate(&[form, car]).as_deref(), Some("test.lurk:1:7"));
+        assert_eq!(
+            locate(&[form, car, one, car]).as_deref(),
+            Some("test.lurk:2:7")
+        );
+        // Expressions that weren't read are skipped
+        assert_eq!(
+            locate(&[form, car, ptr("(+ 2 1)")]).as_deref(),
+            Some("test.lurk:1:7")
+        );
+        assert_eq!(locate(&[ptr("(+ 2 1)"), car]), None);
+    }
+
+    #[test]
+    fn test_bounded() {
+        let store = Store::<Fr>::default();
+        // `(+ 1 2)` has four nodes
+        let mut map = SourceMap {
+            max_nodes: 8,
+            ..Default::default()
+        };
+        read_forms(&store, &mut map, "(+ 1 2)\n(+ 3 4)\n(+ 5 6)");
+        assert_eq!(2, map.forms.len());
+        assert_eq!(8, map.num_nodes);
+        let ptr = |source| store.read_with_default_state(source).unwrap();
+        assert!(map.get(&ptr("(+ 1 2)")).is_none());
+        assert!(map.get(&ptr("(+ 5 6)")).is_some());
+    }
+}
@@ -0,0 +1,251 @@
+use abomonation::Abomonation;
+use anyhow::{Context, Result};
+use ff::PrimeField;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    net::{SocketAddr, TcpListener},
+    sync::Arc,
+    thread,
+};
+use tokio_stream::{wrappers::TcpListenerStream, Iter};
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::warn;
+
+use crate::{
+    cli::{
+        coroutine_proof::CoroutineProofFile, field_data::de, lurk_proof::LurkProof,
+        paths::proof_meta_path,
+    },
+    eval::lang::Coproc,
+    field::LurkField,
+    proof::nova::{CurveCycleEquipped, Dual},
+};
+
+use super::{JobOutput, JobRequest, JobStatus, ProverServer};
+
+#[allow(unreachable_pub, clippy::all)]
+mod proto {
+    tonic::include_proto!("lurk.prover");
+}
+
+use proto::{
+    job_status::State,
+    prove_request,
+    prover_server::{Prover, ProverServer as ProverService},
+    Job, ProofChunk, ProveRequest, QueryBatch, StoreChunk, VerifyRequest, VerifyResponse,
+};
+
+/// Size of the chunks in which proofs and store subgraphs are streamed
+const CHUNK_SIZE: usize = 64 * 1024;
+
+type ChunkStream<T> = Iter<std::vec::IntoIter<Result<T, Status>>>;
+
+/// Splits `bytes` into messages made by `chunk`. There's always at least one message, so the first one can carry
+/// metadata
+fn chunks<T>(bytes: &[u8], mut chunk: impl FnMut(Vec<u8>) -> T) -> Vec<T> {
+    if bytes.is_empty() {
+        return vec![chunk(vec![])];
+    }
+    bytes
+        .chunks(CHUNK_SIZE)
+        .map(<[u8]>::to_vec)
+        .map(chunk)
+        .collect()
+}
+
+fn stream<T>(messages: Vec<T>) -> ChunkStream<T> {
+    tokio_stream::iter(messages.into_iter().map(Ok).collect::<Vec<_>>())
+}
+
+/// Serves the jobs of `server` on `addr` from a new thread. Fails if `addr` can't be bound.
+pub(super) fn spawn<F: CurveCycleEquipped + Serialize + DeserializeOwned>(
+    server: Arc<ProverServer<F>>,
+    addr: &str,
+) -> Result<()>
+where
+    F::Repr: Abomonation,
+    <Dual<F> as PrimeField>::Repr: Abomonation,
+{
+    let addr: SocketAddr = addr.parse()?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    // Bind before spawning, so that the caller learns about an address in use
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Binding the gRPC address {addr}"))?;
+    listener.set_nonblocking(true)?;
+    let listener = {
+        let _guard = runtime.enter();
+        tokio::net::TcpListener::from_std(listener)?
+    };
+    thread::spawn(move || {
+        let service = ProverService::new(GrpcProver(server));
+        let serve = Server::builder()
+            .add_service(service)
+            .serve_with_incoming(TcpListenerStream::new(listener));
+        if let Err(e) = runtime.block_on(serve) {
+            warn!("gRPC server stopped: {e}");
+        }
+    });
+    Ok(())
+}
+
+struct GrpcProver<F: CurveCycleEquipped>(Arc<ProverServer<F>>);
+
+impl<F: CurveCycleEquipped + Serialize + DeserializeOwned> GrpcProver<F>
+where
+    F::Repr: Abomonation,
+    <Dual<F> as PrimeField>::Repr: Abomonation,
+{
+    fn output(&self, job: &Job) -> Result<JobOutput, Status> {
+        self.0
+            .queue
+            .output(job.id)
+            .map_err(|e| Status::failed_precondition(e.message))
+    }
+}
+
+#[tonic::async_trait]
+impl<F: CurveCycleEquipped + Serialize + DeserializeOwned> Prover for GrpcProver<F>
+where
+    F::Repr: Abomonation,
+    <Dual<F> as PrimeField>::Repr: Abomonation,
+{
+    async fn prove(&self, request: Request<ProveRequest>) -> Result<Response<Job>, Status> {
+        let request = match request.into_inner().job {
+            Some(prove_request::Job::Program(program)) => JobRequest::Program { program },
+            Some(prove_request::Job::Queries(QueryBatch { query, keys })) => {
+                JobRequest::Queries { query, keys }
+            }
+            None => return Err(Status::invalid_argument("Nothing to prove")),
+        };
+        let id = self
+            .0
+            .queue
+            .submit(request)
+            .map_err(|e| Status::resource_exhausted(e.to_string()))?;
+        Ok(Response::new(Job { id }))
+    }
+
+    async fn get_status(
+        &self,
+        request: Request<Job>,
+    ) -> Result<Response<proto::JobStatus>, Status> {
+        let id = request.into_inner().id;
+        let Some(status) = self.0.queue.status(id) else {
+            return Err(Status::not_found(format!("Unknown job {id}")));
+        };
+        let mut reply = proto::JobStatus::default();
+        match status {
+            JobStatus::Queued { position } => {
+                reply.set_state(State::Queued);
+                reply.position = position as u64;
+            }
+            JobStatus::Proving => reply.set_state(State::Proving),
+            JobStatus::Done => reply.set_state(State::Done),
+            JobStatus::Failed { error } => {
+                reply.set_state(State::Failed);
+                reply.error = error;
+            }
+        }
+        Ok(Response::new(reply))
+    }
+
+    type FetchProofStream = ChunkStream<ProofChunk>;
+
+    async fn fetch_proof(
+        &self,
+        request: Request<Job>,
+    ) -> Result<Response<Self::FetchProofStream>, Status> {
+        let (proof_key, public_inputs, proof) = match self.output(request.get_ref())? {
+            JobOutput::Program { proof_key, proof } => (proof_key, vec![], proof),
+            JobOutput::Queries {
+                proof,
+                public_inputs,
+            } => (String::new(), public_inputs, proof),
+        };
+        let mut header = Some((proof_key, public_inputs));
+        let messages = chunks(&proof, |proof| {
+            let (proof_key, public_inputs) = header.take().unwrap_or_default();
+            ProofChunk {
+                proof_key,
+                public_inputs,
+                proof,
+            }
+        });
+        Ok(Response::new(stream(messages)))
+    }
+
+    async fn verify(
+        &self,
+        request: Request<VerifyRequest>,
+    ) -> Result<Response<VerifyResponse>, Status> {
+        let VerifyRequest {
+            proof,
+            public_inputs,
+        } = request.into_inner();
+        // verification loads public parameters, which may take a while
+        let reply = tokio::task::spawn_blocking(move || {
+            if public_inputs.is_empty() {
+                let result = de::<LurkProof<'static, F, Coproc<F>>>(&proof)
+                    .and_then(|proof| Ok((proof.verify()?, proof)));
+                match result {
+                    Ok((true, proof)) => {
+                        let hex = |io: &[F]| io.iter().map(LurkField::hex_digits).collect();
+                        VerifyResponse {
+                            verified: true,
+                            public_inputs: hex(&proof.public_inputs),
+                            public_outputs: hex(&proof.public_outputs),
+                            ..Default::default()
+                        }
+                    }
+                    Ok((false, _)) => VerifyResponse::default(),
+                    Err(e) => VerifyResponse {
+                        error: format!("{e:#}"),
+                        ..Default::default()
+                    },
+                }
+            } else {
+                // The public inputs come from the caller, and the verdict checks the proof against them
+                let verdict = CoroutineProofFile::<F>::verify_bytes(&proof, &public_inputs);
+                VerifyResponse {
+                    verified: verdict.verified,
+                    error: verdict.error.unwrap_or_default(),
+                    ..Default::default()
+                }
+            }
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(reply))
+    }
+
+    type FetchStoreSubgraphStream = ChunkStream<StoreChunk>;
+
+    async fn fetch_store_subgraph(
+        &self,
+        request: Request<Job>,
+    ) -> Result<Response<Self::FetchStoreSubgraphStream>, Status> {
+        let JobOutput::Program { proof_key, .. } = self.output(request.get_ref())? else {
+            return Err(Status::failed_precondition(
+                "Only program proofs have a store subgraph",
+            ));
+        };
+        let meta = std::fs::read(proof_meta_path(&proof_key))
+            .map_err(|e| Status::internal(format!("Reading the proof meta: {e}")))?;
+        Ok(Response::new(stream(chunks(&meta, |data| StoreChunk {
+            data,
+        }))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{chunks, CHUNK_SIZE};
+
+    #[test]
+    fn test_chunks() {
+        let bytes = vec![7; 2 * CHUNK_SIZE + 1];
+        let sizes = chunks(&bytes, |chunk| chunk.len());
+        assert_eq!(sizes, [CHUNK_SIZE, CHUNK_SIZE, 1]);
+        assert_eq!(chunks(&[], |chunk| chunk.len()), [0]);
+    }
+}
@@ -0,0 +1,669 @@
+#[cfg(feature = "grpc")]
+mod grpc;
+
+use abomonation::Abomonation;
+use anyhow::{anyhow, bail, Result};
+use ff::PrimeField;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::Read,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Method, Response, Server};
+use tracing::{info, warn};
+
+use crate::{
+    coroutine::memoset::CoroutinePublicInputs,
+    eval::lang::{Coproc, Lang},
+    lem::store::Store,
+    proof::{
+        nova::{CurveCycleEquipped, Dual},
+        supernova::PublicParams,
+    },
+};
+
+use super::{
+    backend::Backend,
+    coroutine_proof::CoroutineProofFile,
+    field_data::ser,
+    paths::proof_path,
+    repl::{PublicParamsCache, QueryScope, Repl},
+};
+
+/// What a client asks the server to prove
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum JobRequest {
+    /// A single Lurk expression, evaluated in the empty env
+    Program { program: String },
+    /// A batch of queries of the type `query`, a built-in one or a list of query definitions, as accepted by
+    /// `QueryScope::new`, proved together with a coroutine proof
+    Queries { query: String, keys: Vec<String> },
+}
+
+/// A finished proof, as returned by `get_proof`. Proofs are encoded like the files written by the CLI
+#[derive(Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum JobOutput {
+    Program {
+        /// The key under which the proof is also persisted in the proofs directory
+        proof_key: String,
+        #[serde(with = "hex")]
+        proof: Vec<u8>,
+    },
+    Queries {
+        #[serde(with = "hex")]
+        proof: Vec<u8>,
+        /// As encoded by `CoroutinePublicInputs::to_bytes`
+        #[serde(with = "hex")]
+        public_inputs: Vec<u8>,
+    },
+}
+
+enum JobState {
+    Queued,
+    Proving,
+    Done(JobOutput),
+    Failed(String),
+}
+
+/// The status of a job, as returned by `job_status`
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum JobStatus {
+    /// `position` jobs are waiting before this one
+    Queued {
+        position: usize,
+    },
+    Proving,
+    Done,
+    Failed {
+        error: String,
+    },
+}
+
+/// The number of finished jobs kept at most, however recent, so that their proofs don't pile up in memory
+const MAX_FINISHED_JOBS: usize = 1024;
+
+/// The largest request body accepted, in bytes
+const MAX_BODY_BYTES: u64 = 1 << 20;
+
+#[derive(Default)]
+struct Jobs {
+    next_id: u64,
+    pending: VecDeque<(u64, JobRequest)>,
+    states: HashMap<u64, JobState>,
+    /// The jobs done or failed, with when they finished, oldest first
+    finished: VecDeque<(u64, Instant)>,
+}
+
+impl Jobs {
+    /// Forgets the jobs that finished more than `ttl` ago, and the oldest ones beyond `MAX_FINISHED_JOBS`
+    fn evict(&mut self, ttl: Duration) {
+        let now = Instant::now();
+        while let Some(&(id, finished)) = self.finished.front() {
+            if self.finished.len() <= MAX_FINISHED_JOBS && now.duration_since(finished) < ttl {
+                break;
+            }
+            self.finished.pop_front();
+            self.states.remove(&id);
+        }
+    }
+}
+
+/// The jobs submitted to the server, shared between the thread answering requests and the proving workers
+struct JobQueue {
+    jobs: Mutex<Jobs>,
+    available: Condvar,
+    /// Maximum number of jobs waiting for a worker
+    max_pending: usize,
+    /// How long the outcome of a finished job is kept
+    ttl: Duration,
+}
+
+impl JobQueue {
+    fn new(max_pending: usize, ttl: Duration) -> Self {
+        Self {
+            jobs: Mutex::default(),
+            available: Condvar::new(),
+            max_pending,
+            ttl,
+        }
+    }
+
+    /// The jobs, once the expired ones are evicted
+    fn jobs(&self) -> std::sync::MutexGuard<'_, Jobs> {
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.evict(self.ttl);
+        jobs
+    }
+
+    fn submit(&self, request: JobRequest) -> Result<u64> {
+        let mut jobs = self.jobs();
+        if jobs.pending.len() >= self.max_pending {
+            bail!("The job queue is full ({} pending jobs)", self.max_pending)
+        }
+        let id = jobs.next_id;
+        jobs.next_id += 1;
+        jobs.pending.push_back((id, request));
+        jobs.states.insert(id, JobState::Queued);
+        self.available.notify_one();
+        Ok(id)
+    }
+
+    /// Blocks until there's a pending job, which is marked as being proved
+    fn take(&self) -> (u64, JobRequest) {
+        let mut jobs = self.jobs.lock().unwrap();
+        loop {
+            if let Some((id, request)) = jobs.pending.pop_front() {
+                jobs.states.insert(id, JobState::Proving);
+                return (id, request);
+            }
+            jobs = self.available.wait(jobs).unwrap();
+        }
+    }
+
+    fn finish(&self, id: u64, result: Result<JobOutput>) {
+        let state = match result {
+            Ok(output) => JobState::Done(output),
+            Err(e) => JobState::Failed(format!("{e:#}")),
+        };
+        let mut jobs = self.jobs();
+        jobs.states.insert(id, state);
+        jobs.finished.push_back((id, Instant::now()));
+        jobs.evict(self.ttl);
+    }
+
+    /// Runs the job `id` with `prove` and records its outcome. A panic fails the job rather than the worker.
+    fn run(&self, id: u64, prove: impl FnOnce() -> Result<JobOutput>) {
+        let result = panic::catch_unwind(AssertUnwindSafe(prove)).unwrap_or_else(|panic| {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic");
+            Err(anyhow!("Proving panicked: {message}"))
+        });
+        if let Err(e) = &result {
+            warn!("Job {id} failed: {e:#}");
+        }
+        self.finish(id, result);
+    }
+
+    fn status(&self, id: u64) -> Option<JobStatus> {
+        let jobs = self.jobs();
+        let status = match jobs.states.get(&id)? {
+            JobState::Queued => {
+                let position = jobs.pending.iter().position(|(i, _)| *i == id);
+                JobStatus::Queued {
+                    position: position.expect("queued jobs are pending"),
+                }
+            }
+            JobState::Proving => JobStatus::Proving,
+            JobState::Done(_) => JobStatus::Done,
+            JobState::Failed(error) => JobStatus::Failed {
+                error: error.clone(),
+            },
+        };
+        Some(status)
+    }
+
+    fn output(&self, id: u64) -> Result<JobOutput, RpcError> {
+        match self.jobs().states.get(&id) {
+            None => Err(RpcError::unknown_job(id)),
+            Some(JobState::Done(output)) => Ok(output.clone()),
+            Some(_) => Err(RpcError {
+                code: JOB_NOT_DONE,
+                message: format!("Job {id} has no proof"),
+            }),
+        }
+    }
+}
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const QUEUE_FULL: i64 = -32000;
+const UNKNOWN_JOB: i64 = -32001;
+const JOB_NOT_DONE: i64 = -32002;
+
+/// Reads a request body of at most `MAX_BODY_BYTES`
+fn read_body(reader: impl Read) -> Result<String> {
+    let mut body = String::new();
+    reader.take(MAX_BODY_BYTES + 1).read_to_string(&mut body)?;
+    if body.len() as u64 > MAX_BODY_BYTES {
+        bail!("The request body exceeds {MAX_BODY_BYTES} bytes")
+    }
+    Ok(body)
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(e: impl std::fmt::Display) -> Self {
+        Self {
+            code: INVALID_PARAMS,
+            message: format!("Invalid params: {e}"),
+        }
+    }
+
+    fn unknown_job(id: u64) -> Self {
+        Self {
+            code: UNKNOWN_JOB,
+            message: format!("Unknown job {id}"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    jsonrpc: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+    #[serde(default)]
+    id: Value,
+}
+
+#[derive(Deserialize)]
+struct JobParams {
+    job: u64,
+}
+
+/// The settings every job is proved with
+pub(crate) struct ServerConfig {
+    pub(crate) rc: usize,
+    pub(crate) limit: usize,
+    pub(crate) backend: Backend,
+    /// Number of jobs proved at the same time
+    pub(crate) workers: usize,
+    pub(crate) max_pending: usize,
+    /// How long the outcome of a finished job can be fetched
+    pub(crate) job_ttl: Duration,
+    /// Where to also serve the gRPC API
+    #[cfg(feature = "grpc")]
+    pub(crate) grpc_addr: Option<String>,
+}
+
+/// A proving service answering JSON-RPC 2.0 requests sent over HTTP POST. The methods are:
+///
+/// * `submit_program {"program": <expr>}` and `submit_queries {"query": <type>, "keys": [<key>, ...]}`, which return
+///   `{"job": <id>}`
+/// * `job_status {"job": <id>}`, which returns the status of the job: "queued", "proving", "done" or "failed"
+/// * `get_proof {"job": <id>}`, which returns the proof of a finished job
+/// * `server_info`, which returns the proving settings and the number of pending jobs
+///
+/// Finished jobs are forgotten after `job_ttl`, or sooner once more than `MAX_FINISHED_JOBS` have finished since. A
+/// job that panics fails without taking its worker down. Request bodies are limited to `MAX_BODY_BYTES`.
+///
+/// With the `grpc` feature, the same jobs can also be submitted and fetched with the `lurk.prover.Prover` gRPC
+/// service, defined in `proto/prover.proto`.
+///
+/// Public parameters are loaded once and shared by all the workers.
+pub(crate) struct ProverServer<F: CurveCycleEquipped> {
+    config: ServerConfig,
+    queue: JobQueue,
+    public_params: PublicParamsCache<F>,
+    /// query type -> parameters key and coroutine public parameters
+    query_params: Mutex<HashMap<String, Arc<(String, PublicParams<F>)>>>,
+}
+
+impl<F: CurveCycleEquipped + Serialize + DeserializeOwned> ProverServer<F>
+where
+    F::Repr: Abomonation,
+    <Dual<F> as PrimeField>::Repr: Abomonation,
+{
+    pub(crate) fn new(config: ServerConfig) -> Self {
+        let queue = JobQueue::new(config.max_pending, config.job_ttl);
+        Self {
+            config,
+            queue,
+            public_params: PublicParamsCache::default(),
+            query_params: Mutex::default(),
+        }
+    }
+
+    /// Starts the workers and answers requests sent to `addr` until the process is stopped
+    pub(crate) fn serve(self, addr: &str) -> Result<()> {
+        let server = Server::http(addr).map_err(|e| anyhow!("Couldn't listen on {addr}: {e}"))?;
+        let this = Arc::new(self);
+        for _ in 0..this.config.workers {
+            let this = this.clone();
+            thread::spawn(move || this.work());
+        }
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_addr) = &this.config.grpc_addr {
+            grpc::spawn(this.clone(), grpc_addr)?;
+            println!("Serving gRPC on {grpc_addr}");
+        }
+        println!(
+            "Serving proofs on {addr} (backend {}, field {}, rc {}, {} worker(s))",
+            this.config.backend,
+            F::FIELD,
+            this.config.rc,
+            this.config.workers
+        );
+        for mut request in server.incoming_requests() {
+            if request.method() != &Method::Post {
+                let response =
+                    Response::from_string("Expected a JSON-RPC POST request").with_status_code(405);
+                request.respond(response)?;
+                continue;
+            }
+            let reply = match read_body(request.as_reader()) {
+                Ok(body) => this.handle(&body),
+                Err(e) => Self::reply(
+                    Value::Null,
+                    Err(RpcError {
+                        code: INVALID_REQUEST,
+                        message: format!("{e:#}"),
+                    }),
+                ),
+            };
+            let content_type =
+                Header::from_bytes("Content-Type", "application/json").expect("valid header");
+            if let Err(e) = request.respond(Response::from_string(reply).with_header(content_type))
+            {
+                warn!("Couldn't respond: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    fn work(&self) {
+        loop {
+            let (id, request) = self.queue.take();
+            info!("Proving job {id}");
+            self.queue.run(id, || self.prove(request));
+        }
+    }
+
+    fn prove(&self, request: JobRequest) -> Result<JobOutput> {
+        match request {
+            JobRequest::Program { program } => {
+                let ServerConfig {
+                    rc, limit, backend, ..
+                } = &self.config;
+                let mut repl = Repl::<F, Coproc<F>>::new(
+                    Store::default(),
+                    Lang::new(),
+                    *rc,
+                    *limit,
+                    backend.clone(),
+                );
+                let proof_key = repl.prove_expression(&program, &self.public_params)?;
+                let proof = std::fs::read(proof_path(&proof_key))?;
+                Ok(JobOutput::Program { proof_key, proof })
+            }
+            JobRequest::Queries { query, keys } => {
+                let store = Store::<F>::default();
+                let mut scope = QueryScope::new(&query)?;
+                for key in &keys {
+                    let key = store.read_with_default_state(key)?;
+                    scope.query(&store, key)?;
+                }
+                let params = self.query_params(&scope, &store)?;
+                let (params_key, pp) = &*params;
+                let (file, public_inputs): (CoroutineProofFile<F>, CoroutinePublicInputs<F>) =
+                    scope.prove_with_params(&store, true, params_key.clone(), pp)?;
+                Ok(JobOutput::Queries {
+                    proof: ser(file)?,
+                    public_inputs: public_inputs.to_bytes(),
+                })
+            }
+        }
+    }
+
+    fn query_params(
+        &self,
+        scope: &QueryScope<F>,
+        store: &Store<F>,
+    ) -> Result<Arc<(String, PublicParams<F>)>> {
+        // keyed by the spec, which lists every query definition, since they all have circuits
+        let query = scope.spec();
+        // the lock is held while the parameters are loaded, so they're only loaded once
+        // a job panicking while loading them leaves nothing half-inserted
+        let mut query_params = self
+            .query_params
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if let Some(params) = query_params.get(&query) {
+            return Ok(params.clone());
+        }
+        let params = Arc::new(scope.public_params(store)?);
+        query_params.insert(query, params.clone());
+        Ok(params)
+    }
+
+    /// Answers the JSON-RPC request in `body`
+    fn handle(&self, body: &str) -> String {
+        let request: RpcRequest = match serde_json::from_str(body) {
+            Ok(request) => request,
+            Err(e) => {
+                return Self::reply(
+                    Value::Null,
+                    Err(RpcError {
+                        code: PARSE_ERROR,
+                        message: e.to_string(),
+                    }),
+                )
+            }
+        };
+        if request.jsonrpc != "2.0" {
+            return Self::reply(
+                request.id,
+                Err(RpcError {
+                    code: INVALID_REQUEST,
+                    message: "Only JSON-RPC 2.0 is supported".into(),
+                }),
+            );
+        }
+        let result = self.dispatch(&request.method, request.params);
+        Self::reply(request.id, result)
+    }
+
+    fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let job_params = || {
+            serde_json::from_value::<JobParams>(params.clone()).map_err(RpcError::invalid_params)
+        };
+        match method {
+            "submit_program" | "submit_queries" => {
+                let request: JobRequest =
+                    serde_json::from_value(params.clone()).map_err(RpcError::invalid_params)?;
+                let expected_program = method == "submit_program";
+                if matches!(request, JobRequest::Program { .. }) != expected_program {
+                    return Err(RpcError::invalid_params(format!(
+                        "{method} got the params of another method"
+                    )));
+                }
+                let id = self.queue.submit(request).map_err(|e| RpcError {
+                    code: QUEUE_FULL,
+                    message: e.to_string(),
+                })?;
+                Ok(json!({ "job": id }))
+            }
+            "job_status" => {
+                let JobParams { job } = job_params()?;
+                let status = self
+                    .queue
+                    .status(job)
+                    .ok_or_else(|| RpcError::unknown_job(job))?;
+                Ok(serde_json::to_value(status).expect("statuses serialize to JSON"))
+            }
+            "get_proof" => {
+                let JobParams { job } = job_params()?;
+                let output = self.queue.output(job)?;
+                Ok(serde_json::to_value(output).expect("proofs serialize to JSON"))
+            }
+            "server_info" => {
+                let pending = self.queue.jobs().pending.len();
+                Ok(json!({
+                    "backend": self.config.backend.to_string(),
+                    "field": F::FIELD.to_string(),
+                    "rc": self.config.rc,
+                    "workers": self.config.workers,
+                    "pending": pending,
+                    "max_pending": self.config.max_pending,
+                    "job_ttl_secs": self.config.job_ttl.as_secs(),
+                }))
+            }
+            _ => Err(RpcError {
+                code: METHOD_NOT_FOUND,
+                message: format!("Unknown method {method}"),
+            }),
+        }
+    }
+
+    fn reply(id: Value, result: Result<Value, RpcError>) -> String {
+        let reply = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Err(error) => json!({ "jsonrpc": "2.0", "error": error, "id": id }),
+        };
+        reply.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr;
+    use serde_json::{json, Value};
+
+    use std::{io::Cursor, time::Duration};
+
+    use super::{
+        read_body, JobOutput, JobQueue, JobRequest, JobStatus, ProverServer, ServerConfig,
+        INVALID_PARAMS, MAX_BODY_BYTES, MAX_FINISHED_JOBS, METHOD_NOT_FOUND, UNKNOWN_JOB,
+    };
+    use crate::cli::backend::Backend;
+
+    fn server(max_pending: usize) -> ProverServer<Fr> {
+        ProverServer::new(ServerConfig {
+            rc: 10,
+            limit: 1000,
+            backend: Backend::Nova,
+            workers: 1,
+            max_pending,
+            job_ttl: Duration::from_secs(60),
+            #[cfg(feature = "grpc")]
+            grpc_addr: None,
+        })
+    }
+
+    fn call(server: &ProverServer<Fr>, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 7 });
+        serde_json::from_str(&server.handle(&request.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_job_queue() {
+        // no workers are started, so jobs stay queued
+        let server = server(2);
+        let reply = call(&server, "submit_program", json!({ "program": "(+ 1 2)" }));
+        assert_eq!(reply["id"], 7);
+        assert_eq!(reply["result"]["job"], 0);
+        let reply = call(
+            &server,
+            "submit_queries",
+            json!({ "query": "factorial", "keys": ["(factorial . 3)"] }),
+        );
+        assert_eq!(reply["result"]["job"], 1);
+        let reply = call(&server, "submit_program", json!({ "program": "1" }));
+        assert!(reply["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("queue is full"));
+
+        let reply = call(&server, "job_status", json!({ "job": 1 }));
+        assert_eq!(
+            reply["result"],
+            json!({ "status": "queued", "position": 1 })
+        );
+        let reply = call(&server, "get_proof", json!({ "job": 0 }));
+        assert!(reply["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("no proof"));
+        let reply = call(&server, "job_status", json!({ "job": 5 }));
+        assert_eq!(reply["error"]["code"], UNKNOWN_JOB);
+        let reply = call(&server, "server_info", Value::Null);
+        assert_eq!(reply["result"]["pending"], 2);
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        let server = server(1);
+        let reply = call(&server, "prove", json!({}));
+        assert_eq!(reply["error"]["code"], METHOD_NOT_FOUND);
+        let reply = call(&server, "submit_queries", json!({ "program": "1" }));
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+        let reply = call(&server, "job_status", json!({ "job": "x" }));
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+        let reply: Value = serde_json::from_str(&server.handle("{")).unwrap();
+        assert_eq!(reply["error"]["code"], -32700);
+        assert_eq!(reply["id"], Value::Null);
+    }
+
+    fn program() -> JobRequest {
+        JobRequest::Program {
+            program: "1".into(),
+        }
+    }
+
+    #[test]
+    fn test_job_eviction() {
+        let output = || {
+            Ok(JobOutput::Queries {
+                proof: vec![],
+                public_inputs: vec![],
+            })
+        };
+        let queue = JobQueue::new(MAX_FINISHED_JOBS + 1, Duration::ZERO);
+        let id = queue.submit(program()).unwrap();
+        queue.take();
+        queue.finish(id, output());
+        assert_eq!(None, queue.status(id));
+
+        // only the latest finished jobs are kept
+        let queue = JobQueue::new(MAX_FINISHED_JOBS + 1, Duration::from_secs(60));
+        for _ in 0..=MAX_FINISHED_JOBS {
+            let id = queue.submit(program()).unwrap();
+            queue.take();
+            queue.finish(id, output());
+        }
+        assert_eq!(None, queue.status(0));
+        assert_eq!(Some(JobStatus::Done), queue.status(1));
+        assert_eq!(MAX_FINISHED_JOBS, queue.jobs().states.len());
+    }
+
+    #[test]
+    fn test_job_panic() {
+        let queue = JobQueue::new(1, Duration::from_secs(60));
+        let id = queue.submit(program()).unwrap();
+        queue.take();
+        queue.run(id, || panic!("boom"));
+        assert_eq!(
+            Some(JobStatus::Failed {
+                error: "Proving panicked: boom".into()
+            }),
+            queue.status(id)
+        );
+    }
+
+    #[test]
+    fn test_body_limit() {
+        let body = "x".repeat(MAX_BODY_BYTES as usize);
+        assert_eq!(body, read_body(Cursor::new(&body)).unwrap());
+        let err = read_body(Cursor::new(body + "x")).unwrap_err().to_string();
+        assert!(err.contains("exceeds"), "{err}");
+    }
+}
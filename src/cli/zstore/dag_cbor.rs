@@ -0,0 +1,438 @@
+//! DAG-CBOR encoding of Lurk data, for storage and addressing in IPLD-based systems.
+//!
+//! Every node of a `ZDag` becomes a block holding the map `{"tag": <u16>, "value": <bytes>, "children": [<link>...]}`,
+//! where `value` is the field element of the `ZPtr` as big-endian bytes and children are linked by CID. A commitment
+//! becomes a block holding `{"hash": <bytes>, "secret": <bytes>, "payload": <link>}`. Encodings are canonical: equal
+//! data always results in equal blocks, hence equal CIDs, and decoding rejects anything else.
+
+use anyhow::{bail, ensure, Context, Result};
+use base32ct::{Base32Unpadded, Encoding};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    field::LurkField,
+    lem::{
+        pointers::ZPtr,
+        tag::Tag,
+        z_dag::{ZDag, ZPtrType},
+    },
+    tag::ExprTag,
+};
+
+/// CIDv1 prefix: version 1, DAG-CBOR codec, SHA2-256 multihash of 32 bytes
+const CID_PREFIX: [u8; 4] = [0x01, 0x71, 0x12, 0x20];
+
+/// The CBOR tag for IPLD links
+const LINK_TAG: u64 = 42;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+
+/// The binary CIDv1 of a DAG-CBOR block
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cid([u8; 36]);
+
+impl Cid {
+    fn of(block: &[u8]) -> Self {
+        let mut cid = [0; 36];
+        cid[..4].copy_from_slice(&CID_PREFIX);
+        cid[4..].copy_from_slice(&Sha256::digest(block));
+        Self(cid)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let cid: [u8; 36] = bytes.try_into().context("CIDs must be 36 bytes long")?;
+        ensure!(
+            cid[..4] == CID_PREFIX,
+            "Only DAG-CBOR CIDs hashed with SHA2-256 are supported"
+        );
+        Ok(Self(cid))
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Multibase base32 (lowercase, unpadded), the default string form of CIDv1
+impl std::fmt::Display for Cid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "b{}", Base32Unpadded::encode_string(&self.0))
+    }
+}
+
+/// Lurk data as DAG-CBOR: the CID of the root block and every block reachable from it
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DagCbor {
+    pub root: Option<Cid>,
+    pub blocks: BTreeMap<Cid, Vec<u8>>,
+}
+
+impl DagCbor {
+    fn add(&mut self, block: Vec<u8>) -> Cid {
+        let cid = Cid::of(&block);
+        self.blocks.insert(cid, block);
+        cid
+    }
+
+    fn block(&self, cid: &Cid) -> Result<&[u8]> {
+        let block = self
+            .blocks
+            .get(cid)
+            .with_context(|| format!("Missing block {cid}"))?;
+        ensure!(&Cid::of(block) == cid, "Block {cid} doesn't match its CID");
+        Ok(block)
+    }
+
+    fn root(&self) -> Result<Cid> {
+        self.root.context("No root block")
+    }
+}
+
+fn write_header(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if n <= u64::from(u8::MAX) {
+        out.extend([major | 24, n as u8]);
+    } else if n <= u64::from(u16::MAX) {
+        out.push(major | 25);
+        out.extend((n as u16).to_be_bytes());
+    } else if n <= u64::from(u32::MAX) {
+        out.push(major | 26);
+        out.extend((n as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend(n.to_be_bytes());
+    }
+}
+
+fn write_text(out: &mut Vec<u8>, text: &str) {
+    write_header(out, MAJOR_TEXT, text.len() as u64);
+    out.extend(text.as_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_header(out, MAJOR_BYTES, bytes.len() as u64);
+    out.extend(bytes);
+}
+
+fn write_field<F: LurkField>(out: &mut Vec<u8>, f: &F) {
+    let mut bytes = f.to_bytes();
+    bytes.reverse();
+    write_bytes(out, &bytes);
+}
+
+fn write_link(out: &mut Vec<u8>, cid: &Cid) {
+    write_header(out, MAJOR_TAG, LINK_TAG);
+    // links are prefixed by the identity multibase
+    write_header(out, MAJOR_BYTES, 1 + cid.0.len() as u64);
+    out.push(0);
+    out.extend(cid.0);
+}
+
+/// A strict reader of the CBOR subset written above. Canonicity is checked by encoding what was read again.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        ensure!(self.bytes.len() >= n, "Unexpected end of block");
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn header(&mut self, major: u8) -> Result<u64> {
+        let first = self.take(1)?[0];
+        ensure!(first >> 5 == major, "Expected CBOR major type {major}");
+        let n = match first & 31 {
+            n @ 0..=23 => u64::from(n),
+            24 => u64::from(self.take(1)?[0]),
+            25 => u64::from(u16::from_be_bytes(self.take(2)?.try_into()?)),
+            26 => u64::from(u32::from_be_bytes(self.take(4)?.try_into()?)),
+            27 => u64::from_be_bytes(self.take(8)?.try_into()?),
+            _ => bail!("Indefinite lengths aren't allowed in DAG-CBOR"),
+        };
+        Ok(n)
+    }
+
+    fn key(&mut self, expected: &str) -> Result<()> {
+        let len = self.header(MAJOR_TEXT)?;
+        let key = self.take(usize::try_from(len)?)?;
+        ensure!(key == expected.as_bytes(), "Expected key {expected}");
+        Ok(())
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.header(MAJOR_BYTES)?;
+        self.take(usize::try_from(len)?)
+    }
+
+    fn field<F: LurkField>(&mut self) -> Result<F> {
+        let mut bytes = self.bytes()?.to_vec();
+        ensure!(
+            bytes.len() == F::ZERO.to_bytes().len(),
+            "Wrong field element size"
+        );
+        bytes.reverse();
+        F::from_bytes(&bytes).context("Non-canonical field element")
+    }
+
+    fn link(&mut self) -> Result<Cid> {
+        ensure!(self.header(MAJOR_TAG)? == LINK_TAG, "Expected a link");
+        let bytes = self.bytes()?;
+        ensure!(
+            bytes.first() == Some(&0),
+            "Links must use the identity multibase"
+        );
+        Cid::from_bytes(&bytes[1..])
+    }
+
+    fn finish(&self) -> Result<()> {
+        ensure!(self.bytes.is_empty(), "Trailing bytes in block");
+        Ok(())
+    }
+}
+
+fn encode_node<F: LurkField>(z_ptr: &ZPtr<F>, children: &[Cid]) -> Vec<u8> {
+    let mut out = vec![];
+    write_header(&mut out, MAJOR_MAP, 3);
+    write_text(&mut out, "tag");
+    write_header(&mut out, MAJOR_UINT, u64::from(u16::from(*z_ptr.tag())));
+    write_text(&mut out, "value");
+    write_field(&mut out, z_ptr.value());
+    write_text(&mut out, "children");
+    write_header(&mut out, MAJOR_ARRAY, children.len() as u64);
+    children.iter().for_each(|cid| write_link(&mut out, cid));
+    out
+}
+
+fn encode_commitment<F: LurkField>(hash: &F, secret: &F, payload: &Cid) -> Vec<u8> {
+    let mut out = vec![];
+    write_header(&mut out, MAJOR_MAP, 3);
+    write_text(&mut out, "hash");
+    write_field(&mut out, hash);
+    write_text(&mut out, "secret");
+    write_field(&mut out, secret);
+    write_text(&mut out, "payload");
+    write_link(&mut out, payload);
+    out
+}
+
+impl<F: LurkField> ZDag<F> {
+    fn encode_into(
+        &self,
+        z_ptr: &ZPtr<F>,
+        dag_cbor: &mut DagCbor,
+        cache: &mut HashMap<ZPtr<F>, Cid>,
+    ) -> Result<Cid> {
+        if let Some(cid) = cache.get(z_ptr) {
+            return Ok(*cid);
+        }
+        let Some(z_ptr_type) = self.get_type(z_ptr) else {
+            bail!("Couldn't find ZPtr on ZDag")
+        };
+        let children = z_ptr_type
+            .children()
+            .into_iter()
+            .map(|child| self.encode_into(child, dag_cbor, cache))
+            .collect::<Result<Vec<_>>>()?;
+        let cid = dag_cbor.add(encode_node(z_ptr, &children));
+        cache.insert(*z_ptr, cid);
+        Ok(cid)
+    }
+
+    /// Encodes the data reachable from `root` as DAG-CBOR blocks
+    pub fn to_dag_cbor(&self, root: &ZPtr<F>) -> Result<DagCbor> {
+        let mut dag_cbor = DagCbor::default();
+        dag_cbor.root = Some(self.encode_into(root, &mut dag_cbor, &mut HashMap::default())?);
+        Ok(dag_cbor)
+    }
+
+    fn decode_from(
+        &mut self,
+        cid: &Cid,
+        dag_cbor: &DagCbor,
+        cache: &mut HashMap<Cid, ZPtr<F>>,
+    ) -> Result<ZPtr<F>> {
+        if let Some(z_ptr) = cache.get(cid) {
+            return Ok(*z_ptr);
+        }
+        let block = dag_cbor.block(cid)?;
+        let mut reader = Reader { bytes: block };
+        ensure!(
+            reader.header(MAJOR_MAP)? == 3,
+            "Nodes are maps of 3 entries"
+        );
+        reader.key("tag")?;
+        let tag = Tag::try_from(u16::try_from(reader.header(MAJOR_UINT)?)?)?;
+        reader.key("value")?;
+        let z_ptr = ZPtr::from_parts(tag, reader.field()?);
+        reader.key("children")?;
+        let arity = reader.header(MAJOR_ARRAY)?;
+        let children = (0..arity)
+            .map(|_| reader.link())
+            .collect::<Result<Vec<_>>>()?;
+        reader.finish()?;
+        ensure!(
+            encode_node(&z_ptr, &children) == block,
+            "Block {cid} isn't canonical"
+        );
+
+        let children = children
+            .iter()
+            .map(|child| self.decode_from(child, dag_cbor, cache))
+            .collect::<Result<Vec<_>>>()?;
+        let z_ptr_type = match (children.as_slice(), tag) {
+            ([], _) => ZPtrType::Atom,
+            ([a, b], _) => ZPtrType::Tuple2(*a, *b),
+            ([a, b, c], Tag::Expr(ExprTag::Env)) => ZPtrType::Env(*a, *b, *c),
+            ([a, b, c], _) => ZPtrType::Tuple3(*a, *b, *c),
+            ([a, b, c, d], _) => ZPtrType::Tuple4(*a, *b, *c, *d),
+            _ => bail!("Nodes have at most 4 children"),
+        };
+        self.0.insert(z_ptr, z_ptr_type);
+        cache.insert(*cid, z_ptr);
+        Ok(z_ptr)
+    }
+
+    /// Decodes DAG-CBOR blocks written by `ZDag::to_dag_cbor`, returning the root and its dag. Blocks are checked
+    /// against their CIDs, but `ZPtr`s aren't hashed again: import the result in a `Store` and compare hashes for that.
+    pub fn from_dag_cbor(dag_cbor: &DagCbor) -> Result<(ZPtr<F>, Self)> {
+        let mut z_dag = Self::default();
+        let root = z_dag.decode_from(&dag_cbor.root()?, dag_cbor, &mut HashMap::default())?;
+        Ok((root, z_dag))
+    }
+
+    /// Encodes the commitment `hash`, which hides `payload` with `secret`, and the data it hides as DAG-CBOR blocks,
+    /// rooted at the commitment block
+    pub fn commitment_to_dag_cbor(&self, hash: F, secret: F, payload: &ZPtr<F>) -> Result<DagCbor> {
+        let mut dag_cbor = DagCbor::default();
+        let payload = self.encode_into(payload, &mut dag_cbor, &mut HashMap::default())?;
+        dag_cbor.root = Some(dag_cbor.add(encode_commitment(&hash, &secret, &payload)));
+        Ok(dag_cbor)
+    }
+
+    /// Decodes a commitment written by `ZDag::commitment_to_dag_cbor`, returning its hash, secret and payload, and the
+    /// dag of the payload. As with `from_dag_cbor`, the hash isn't checked: hide the imported payload with the secret
+    /// for that.
+    pub fn commitment_from_dag_cbor(dag_cbor: &DagCbor) -> Result<(F, F, ZPtr<F>, Self)> {
+        let root = dag_cbor.root()?;
+        let block = dag_cbor.block(&root)?;
+        let mut reader = Reader { bytes: block };
+        ensure!(
+            reader.header(MAJOR_MAP)? == 3,
+            "Commitments are maps of 3 entries"
+        );
+        reader.key("hash")?;
+        let hash = reader.field()?;
+        reader.key("secret")?;
+        let secret = reader.field()?;
+        reader.key("payload")?;
+        let payload = reader.link()?;
+        reader.finish()?;
+        ensure!(
+            encode_commitment(&hash, &secret, &payload) == block,
+            "Block {root} isn't canonical"
+        );
+
+        let mut z_dag = Self::default();
+        let payload = z_dag.decode_from(&payload, dag_cbor, &mut HashMap::default())?;
+        Ok((hash, secret, payload, z_dag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr as Bn;
+
+    use super::{Cid, ZDag};
+    use crate::{field::LurkField, lem::store::Store};
+
+    #[test]
+    fn test_dag_cbor_roundtrip() {
+        let store = Store::<Bn>::default();
+        let expr = store
+            .read_with_default_state("(letrec ((f (lambda (x) (cons x \"f\")))) (f 1))")
+            .unwrap();
+        let env = store.push_binding(
+            store.intern_lurk_symbol("y"),
+            store.num_u64(2),
+            store.intern_empty_env(),
+        );
+        let data = store.list(vec![expr, env, expr]);
+        let z_data = store.hash_ptr(&data);
+        let z_dag = ZDag::extract(&store, &[z_data]);
+
+        let dag_cbor = z_dag.to_dag_cbor(&z_data).unwrap();
+        // shared data is encoded once
+        assert_eq!(dag_cbor.blocks.len(), z_dag.len());
+        // and the encoding is deterministic
+        let other_store = Store::<Bn>::default();
+        let other = other_store
+            .read_with_default_state("(letrec ((f (lambda (x) (cons x \"f\")))) (f 1))")
+            .unwrap();
+        let z_other = other_store.hash_ptr(&other);
+        let other_cbor = ZDag::extract(&other_store, &[z_other])
+            .to_dag_cbor(&z_other)
+            .unwrap();
+        assert!(dag_cbor.blocks.contains_key(&other_cbor.root.unwrap()));
+
+        let (root, decoded) = ZDag::<Bn>::from_dag_cbor(&dag_cbor).unwrap();
+        assert_eq!(root, z_data);
+        let imported = decoded.import(&root, &other_store).unwrap();
+        assert_eq!(other_store.hash_ptr(&imported), z_data);
+
+        // tampered blocks are rejected
+        let mut tampered = dag_cbor;
+        let block = tampered.blocks.values_mut().next().unwrap();
+        *block.last_mut().unwrap() ^= 1;
+        assert!(ZDag::<Bn>::from_dag_cbor(&tampered).is_err());
+    }
+
+    #[test]
+    fn test_commitment_dag_cbor() {
+        let store = Store::<Bn>::default();
+        let payload = store.read_with_default_state("(13 . 21)").unwrap();
+        let secret = Bn::from_u64(12345);
+        let (hash, z_payload) = store.hide_and_return_z_payload(secret, payload);
+        let z_dag = ZDag::extract(&store, &[z_payload]);
+
+        let dag_cbor = z_dag
+            .commitment_to_dag_cbor(hash, secret, &z_payload)
+            .unwrap();
+        assert!(dag_cbor.root.unwrap().to_string().starts_with("bafyrei"));
+        let (decoded_hash, decoded_secret, decoded_payload, decoded) =
+            ZDag::<Bn>::commitment_from_dag_cbor(&dag_cbor).unwrap();
+        assert_eq!(decoded_hash, hash);
+        assert_eq!(decoded_secret, secret);
+        assert_eq!(decoded_payload, z_payload);
+        let other_store = Store::<Bn>::default();
+        let imported = decoded.import(&z_payload, &other_store).unwrap();
+        assert_eq!(
+            other_store.hide_and_return_z_payload(secret, imported).0,
+            hash
+        );
+        // the payload must be in the dag
+        assert!(ZDag::<Bn>::default()
+            .commitment_to_dag_cbor(hash, secret, &z_payload)
+            .is_err());
+    }
+
+    #[test]
+    fn test_cid_display() {
+        // the CID of the empty DAG-CBOR map, `{}`
+        assert_eq!(
+            Cid::of(&[0xa0]).to_string(),
+            "bafyreigbtj4x7ip5legnfznufuopl4sg4knzc2cof6duas4b3q2fy6swua"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{
+    field::{FWrap, LurkField},
+    lem::{
+        pointers::{Ptr, ZPtr},
+        store::Store,
+    },
+};
+
+use super::field_data::HasFieldModulus;
+
+pub use crate::lem::z_dag::{ZDag, ZDagManifest};
+
+pub mod dag_cbor;
+
+/// A `ZStore` is a stable IO format for `Store`, without index-based references
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct ZStore<F: LurkField> {
+    z_dag: ZDag<F>,
+    comms: BTreeMap<FWrap<F>, (F, ZPtr<F>)>,
+}
+
+impl<F: LurkField> HasFieldModulus for ZStore<F> {
+    fn field_modulus() -> String {
+        F::MODULUS.to_owned()
+    }
+}
+
+impl<F: LurkField> ZStore<F> {
+    #[inline]
+    pub(crate) fn add_comm(&mut self, hash: F, secret: F, payload: ZPtr<F>) {
+        self.comms.insert(FWrap(hash), (secret, payload));
+    }
+
+    #[inline]
+    pub(crate) fn open(&self, hash: F) -> Option<&(F, ZPtr<F>)> {
+        self.comms.get(&FWrap(hash))
+    }
+
+    pub(crate) fn to_store(&self) -> Result<Store<F>> {
+        let store = Store::default();
+        let mut cache = HashMap::default();
+        for z_ptr in self.z_dag.0.keys() {
+            self.populate_store(z_ptr, &store, &mut cache)?;
+        }
+        for (hash, (secret, z_payload)) in &self.comms {
+            let payload = self.populate_store(z_payload, &store, &mut cache)?;
+            store.add_comm(hash.0, *secret, payload);
+        }
+        Ok(store)
+    }
+
+    #[inline]
+    pub(crate) fn populate_with(
+        &mut self,
+        ptr: &Ptr,
+        store: &Store<F>,
+        cache: &mut HashMap<Ptr, ZPtr<F>>,
+    ) -> ZPtr<F> {
+        self.z_dag.populate_with(ptr, store, cache)
+    }
+
+    pub(crate) fn populate_store(
+        &self,
+        z_ptr: &ZPtr<F>,
+        store: &Store<F>,
+        cache: &mut HashMap<ZPtr<F>, Ptr>,
+    ) -> Result<Ptr> {
+        self.z_dag.populate_store(z_ptr, store, cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr as Bn;
+    use rand::{rngs::StdRng, Rng};
+    use rand_core::SeedableRng;
+    use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+    use std::collections::HashMap;
+
+    use crate::{
+        field::LurkField,
+        lem::{
+            pointers::Ptr,
+            store::{intern_ptrs, Store},
+            tag::Tag,
+        },
+        tag::{ContTag, ExprTag, Op1, Op2},
+    };
+
+    use super::ZStore;
+
+    /// helper function that interns random data into a store
+    fn rng_interner(rng: &mut StdRng, max_depth: usize, store: &Store<Bn>) -> Ptr {
+        let rnd = rng.gen::<u64>();
+        let tag = match rnd % 4 {
+            0 => Tag::Expr(ExprTag::try_from((rnd % 11) as u16).unwrap()),
+            1 => Tag::Cont(ContTag::try_from((rnd % 17) as u16 + 4096).unwrap()),
+            2 => Tag::Op1(Op1::try_from((rnd % 12) as u16 + 8192).unwrap()),
+            3 => Tag::Op2(Op2::try_from((rnd % 16) as u16 + 12288).unwrap()),
+            _ => unreachable!(),
+        };
+        if max_depth == 0 {
+            store.intern_atom(tag, Bn::from_u64(rnd))
+        } else {
+            match rnd % 4 {
+                0 => store.intern_atom(tag, Bn::from_u64(rnd)),
+                1 => intern_ptrs!(
+                    store,
+                    tag,
+                    rng_interner(rng, max_depth - 1, store),
+                    rng_interner(rng, max_depth - 1, store)
+                ),
+                2 => intern_ptrs!(
+                    store,
+                    tag,
+                    rng_interner(rng, max_depth - 1, store),
+                    rng_interner(rng, max_depth - 1, store),
+                    rng_interner(rng, max_depth - 1, store)
+                ),
+                3 => intern_ptrs!(
+                    store,
+                    tag,
+                    rng_interner(rng, max_depth - 1, store),
+                    rng_interner(rng, max_depth - 1, store),
+                    rng_interner(rng, max_depth - 1, store),
+                    rng_interner(rng, max_depth - 1, store)
+                ),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_z_store_roundtrip() {
+        const NUM_TESTS: u64 = 192;
+        const MAX_DEPTH: usize = 10;
+
+        (0..NUM_TESTS).into_par_iter().for_each(|seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let store1 = Store::default();
+            let ptr1 = rng_interner(&mut rng, MAX_DEPTH, &store1);
+
+            let mut z_store = ZStore::default();
+            let mut cache_into = HashMap::default();
+            let z_ptr = z_store.populate_with(&ptr1, &store1, &mut cache_into);
+
+            let mut cache_from = HashMap::default();
+            let store2 = Store::default();
+            let ptr2 = z_store
+                .populate_store(&z_ptr, &store2, &mut cache_from)
+                .unwrap();
+
+            assert_eq!(store1.hash_ptr(&ptr1), store2.hash_ptr(&ptr2))
+        });
+    }
+}
@@ -0,0 +1,543 @@
+//! ChaCha20-Poly1305 authenticated decryption, as specified by RFC 8439.
+//!
+//! `.lurk.aead.chacha20poly1305-open-<aad-len>-<len>` takes a key of 32 bytes, a nonce of 12 bytes, `aad-len` bytes
+//! of additional data and a sealed message of `len` bytes followed by its 16-byte tag, all as `Bytes`. It returns the
+//! `len` bytes of the plaintext if the tag authenticates the message and the additional data under the key, and nil
+//! otherwise, so that a program can prove properties of the plaintext of a ciphertext whose key is only known through
+//! a commitment. Failing authentication is a regular result, which can be proved.
+//!
+//! Arguments that aren't `Bytes` of the expected length are returned along with an error continuation.
+//!
+//! AES-GCM isn't supported: its S-boxes and the multiplications in `GF(2^128)` of GHASH cost far more constraints
+//! than the additions, rotations and xors of ChaCha20 and the prime field arithmetic of Poly1305.
+
+use bellpepper::gadgets::{multieq::MultiEq, uint32::UInt32};
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use num_bigint::BigUint;
+use num_traits::{One, Zero};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        data::{construct_bytes, deconstruct_bytes_args, fetch_bytes_args, pick_bytes_args_result},
+        emulated::{EmulatedNum, Poly},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+const BLOCK_LEN: usize = 64;
+
+/// "expand 32-byte k"
+const SIGMA: [u32; 4] = [0x6170_7865, 0x3320_646E, 0x7962_2D32, 0x6B20_6574];
+
+/// The words mixed by each quarter round of a double round: the columns, then the diagonals
+const QUARTER_ROUNDS: [[usize; 4]; 8] = [
+    [0, 4, 8, 12],
+    [1, 5, 9, 13],
+    [2, 6, 10, 14],
+    [3, 7, 11, 15],
+    [0, 5, 10, 15],
+    [1, 6, 11, 12],
+    [2, 7, 8, 13],
+    [3, 4, 9, 14],
+];
+
+/// The bytes of the Poly1305 key that are masked by clamping `r`, and their masks
+const CLAMPS: [(usize, u8); 7] = [
+    (3, 0x0F),
+    (7, 0x0F),
+    (11, 0x0F),
+    (15, 0x0F),
+    (4, 0xFC),
+    (8, 0xFC),
+    (12, 0xFC),
+];
+
+fn le_words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks(4)
+        .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// The 64 bytes of keystream of the ChaCha20 block `counter`
+fn chacha20_block(key: &[u8], counter: u32, nonce: &[u8]) -> Vec<u8> {
+    let mut state = SIGMA.to_vec();
+    state.extend(le_words(key));
+    state.push(counter);
+    state.extend(le_words(nonce));
+
+    let mut x = state.clone();
+    for _ in 0..10 {
+        for &[a, b, c, d] in &QUARTER_ROUNDS {
+            x[a] = x[a].wrapping_add(x[b]);
+            x[d] = (x[d] ^ x[a]).rotate_left(16);
+            x[c] = x[c].wrapping_add(x[d]);
+            x[b] = (x[b] ^ x[c]).rotate_left(12);
+            x[a] = x[a].wrapping_add(x[b]);
+            x[d] = (x[d] ^ x[a]).rotate_left(8);
+            x[c] = x[c].wrapping_add(x[d]);
+            x[b] = (x[b] ^ x[c]).rotate_left(7);
+        }
+    }
+    x.iter()
+        .zip(&state)
+        .flat_map(|(x, s)| x.wrapping_add(*s).to_le_bytes())
+        .collect()
+}
+
+/// `data` xored with the keystream starting at block 1, the first block after the one of the Poly1305 key
+fn chacha20_xor(key: &[u8], nonce: &[u8], data: &[u8]) -> Vec<u8> {
+    data.chunks(BLOCK_LEN)
+        .enumerate()
+        .flat_map(|(i, chunk)| {
+            let keystream = chacha20_block(key, 1 + i as u32, nonce);
+            chunk
+                .iter()
+                .zip(keystream)
+                .map(|(b, k)| b ^ k)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// 2^130 - 5
+fn poly1305_prime() -> BigUint {
+    (BigUint::one() << 130) - 5u32
+}
+
+/// The Poly1305 tag of `message` under the one-time key `key`
+fn poly1305(key: &[u8], message: &[u8]) -> [u8; TAG_LEN] {
+    let mut r = key[..16].to_vec();
+    for (i, mask) in CLAMPS {
+        r[i] &= mask;
+    }
+    let r = BigUint::from_bytes_le(&r);
+    let s = BigUint::from_bytes_le(&key[16..32]);
+    let p = poly1305_prime();
+
+    let mut h = BigUint::zero();
+    for chunk in message.chunks(16) {
+        let n = BigUint::from_bytes_le(chunk) + (BigUint::one() << (8 * chunk.len()));
+        h = (h + n) * &r % &p;
+    }
+    let mut tag = [0; TAG_LEN];
+    let bytes = ((h + s) % (BigUint::one() << 128)).to_bytes_le();
+    tag[..bytes.len()].copy_from_slice(&bytes);
+    tag
+}
+
+/// The input of Poly1305 in the AEAD construction: the additional data and the ciphertext, each padded with zeros
+/// to a multiple of 16 bytes, followed by their lengths as little-endian `u64`s
+fn mac_data(aad: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    let pad = |len: usize| (16 - len % 16) % 16;
+    let mut data = aad.to_vec();
+    data.resize(aad.len() + pad(aad.len()), 0);
+    data.extend(ciphertext);
+    data.resize(data.len() + pad(ciphertext.len()), 0);
+    data.extend((aad.len() as u64).to_le_bytes());
+    data.extend((ciphertext.len() as u64).to_le_bytes());
+    data
+}
+
+/// Encrypts `plaintext` and returns it followed by the tag authenticating it along with `aad`
+///
+/// # Panics
+/// Panics if the key or the nonce have the wrong length
+pub fn seal(key: &[u8], nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+    assert_eq!(key.len(), KEY_LEN);
+    assert_eq!(nonce.len(), NONCE_LEN);
+    let mut sealed = chacha20_xor(key, nonce, plaintext);
+    let mac_key = chacha20_block(key, 0, nonce);
+    let tag = poly1305(&mac_key[..32], &mac_data(aad, &sealed));
+    sealed.extend(tag);
+    sealed
+}
+
+/// Decrypts a message sealed by `seal`, unless its tag doesn't authenticate it
+///
+/// # Panics
+/// Panics if the key or the nonce have the wrong length, or if `sealed` is shorter than a tag
+pub fn open(key: &[u8], nonce: &[u8], aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    assert_eq!(key.len(), KEY_LEN);
+    assert_eq!(nonce.len(), NONCE_LEN);
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - TAG_LEN);
+    let mac_key = chacha20_block(key, 0, nonce);
+    (poly1305(&mac_key[..32], &mac_data(aad, ciphertext)) == tag)
+        .then(|| chacha20_xor(key, nonce, ciphertext))
+}
+
+/// The ChaCha20 quarter round over the words `abcd` of `x`
+fn quarter_round<
+    F: LurkField,
+    CS: ConstraintSystem<F>,
+    M: ConstraintSystem<F, Root = MultiEq<F, CS>>,
+>(
+    mut cs: M,
+    x: &mut [UInt32],
+    [a, b, c, d]: [usize; 4],
+) -> Result<(), SynthesisError> {
+    // `rotr(32 - n)` rotates left by `n`
+    x[a] = UInt32::addmany(cs.namespace(|| "a + b (1)"), &[x[a].clone(), x[b].clone()])?;
+    x[d] = x[d].xor(cs.namespace(|| "d ^ a (1)"), &x[a])?.rotr(16);
+    x[c] = UInt32::addmany(cs.namespace(|| "c + d (1)"), &[x[c].clone(), x[d].clone()])?;
+    x[b] = x[b].xor(cs.namespace(|| "b ^ c (1)"), &x[c])?.rotr(20);
+    x[a] = UInt32::addmany(cs.namespace(|| "a + b (2)"), &[x[a].clone(), x[b].clone()])?;
+    x[d] = x[d].xor(cs.namespace(|| "d ^ a (2)"), &x[a])?.rotr(24);
+    x[c] = UInt32::addmany(cs.namespace(|| "c + d (2)"), &[x[c].clone(), x[d].clone()])?;
+    x[b] = x[b].xor(cs.namespace(|| "b ^ c (2)"), &x[c])?.rotr(25);
+    Ok(())
+}
+
+/// The 512 bits of keystream of the ChaCha20 block `counter`, each byte least significant bit first
+fn synthesize_block<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: CS,
+    key: &[Boolean],
+    counter: u32,
+    nonce: &[Boolean],
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let mut cs = MultiEq::new(cs);
+    let mut state: Vec<_> = SIGMA.iter().map(|w| UInt32::constant(*w)).collect();
+    state.extend(key.chunks(32).map(UInt32::from_bits));
+    state.push(UInt32::constant(counter));
+    state.extend(nonce.chunks(32).map(UInt32::from_bits));
+
+    let mut x = state.clone();
+    for i in 0..10 {
+        let mut cs = cs.namespace(|| format!("double round {i}"));
+        for (j, abcd) in QUARTER_ROUNDS.iter().enumerate() {
+            quarter_round(cs.namespace(|| format!("quarter round {j}")), &mut x, *abcd)?;
+        }
+    }
+    let mut bits = Vec::with_capacity(8 * BLOCK_LEN);
+    for (i, (x, s)) in x.into_iter().zip(state).enumerate() {
+        let word = UInt32::addmany(cs.namespace(|| format!("x{i} + s{i}")), &[x, s])?;
+        bits.extend(word.into_bits());
+    }
+    Ok(bits)
+}
+
+/// The bits of `data` xored with the keystream starting at block 1
+fn synthesize_xor<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    key: &[Boolean],
+    nonce: &[Boolean],
+    data: &[Boolean],
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, chunk) in data.chunks(8 * BLOCK_LEN).enumerate() {
+        let mut cs = cs.namespace(|| format!("block {}", i + 1));
+        let keystream = synthesize_block(cs.namespace(|| "keystream"), key, 1 + i as u32, nonce)?;
+        for (j, (bit, k)) in chunk.iter().zip(keystream).enumerate() {
+            out.push(Boolean::xor(cs.namespace(|| format!("bit {j}")), bit, &k)?);
+        }
+    }
+    Ok(out)
+}
+
+/// The integer of at most 256 little-endian bits
+fn emulated_from_bits<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    bits: &[Boolean],
+) -> Result<EmulatedNum<F>, SynthesisError> {
+    let mut bits = bits.to_vec();
+    bits.resize(256, Boolean::Constant(false));
+    EmulatedNum::from_bits(cs, &bits)
+}
+
+/// Whether `tag` is the Poly1305 tag of the whole blocks of `message` under the one-time key `key`, all as bits
+fn synthesize_authenticate<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    key: &[Boolean],
+    message: &[Boolean],
+    tag: &[Boolean],
+) -> Result<Boolean, SynthesisError> {
+    let mut r_bits = key[..128].to_vec();
+    for (i, mask) in CLAMPS {
+        for j in 0..8 {
+            if mask & (1 << j) == 0 {
+                r_bits[8 * i + j] = Boolean::Constant(false);
+            }
+        }
+    }
+    let r = emulated_from_bits(&mut cs.namespace(|| "r"), &r_bits)?;
+    let s = emulated_from_bits(&mut cs.namespace(|| "s"), &key[128..256])?;
+    let p = poly1305_prime();
+
+    let mut h = EmulatedNum::constant(&mut cs.namespace(|| "h0"), &BigUint::zero())?;
+    for (i, chunk) in message.chunks(128).enumerate() {
+        let mut cs = cs.namespace(|| format!("block {i}"));
+        let mut bits = chunk.to_vec();
+        bits.push(Boolean::Constant(true));
+        let n = emulated_from_bits(&mut cs.namespace(|| "n"), &bits)?;
+        h = Poly::new()
+            .product(1, &h, &r)
+            .product(1, &n, &r)
+            .alloc_congruent(&mut cs.namespace(|| "(h + n) r"), &p)?;
+    }
+    let h = h.reduce(&mut cs.namespace(|| "h"), &p)?;
+    let computed = Poly::new()
+        .term(1, &h)
+        .term(1, &s)
+        .alloc_reduced(&mut cs.namespace(|| "h + s"), &(BigUint::one() << 128))?;
+    let tag = emulated_from_bits(&mut cs.namespace(|| "tag"), tag)?;
+    computed.alloc_equal(&mut cs.namespace(|| "tag matches"), &tag)
+}
+
+/// The bits of the plaintext of `sealed` along with whether its tag authenticates it
+fn synthesize_open<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    key: &[Boolean],
+    nonce: &[Boolean],
+    aad: &[Boolean],
+    sealed: &[Boolean],
+) -> Result<(Vec<Boolean>, Boolean), SynthesisError> {
+    let (ciphertext, tag) = sealed.split_at(sealed.len() - 8 * TAG_LEN);
+    let mac_key = synthesize_block(cs.namespace(|| "block 0"), key, 0, nonce)?;
+
+    let pad = |bits: &mut Vec<Boolean>| {
+        let len = bits.len().div_ceil(128) * 128;
+        bits.resize(len, Boolean::Constant(false));
+    };
+    let mut mac_data = aad.to_vec();
+    pad(&mut mac_data);
+    mac_data.extend_from_slice(ciphertext);
+    pad(&mut mac_data);
+    for len in [aad.len() / 8, ciphertext.len() / 8] {
+        let len = len as u64;
+        mac_data.extend((0..64).map(|i| Boolean::Constant((len >> i) & 1 == 1)));
+    }
+    let authentic = synthesize_authenticate(
+        &mut cs.namespace(|| "poly1305"),
+        &mac_key[..256],
+        &mac_data,
+        tag,
+    )?;
+    let plaintext = synthesize_xor(&mut cs.namespace(|| "chacha20"), key, nonce, ciphertext)?;
+    Ok((plaintext, authentic))
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChaChaPolyCoprocessor<F: LurkField> {
+    aad_len: usize,
+    len: usize,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> ChaChaPolyCoprocessor<F> {
+    pub fn new(aad_len: usize, len: usize) -> Self {
+        Self {
+            aad_len,
+            len,
+            _p: Default::default(),
+        }
+    }
+
+    /// The name of the coprocessor in the `.lurk.aead` package
+    pub fn name(&self) -> String {
+        format!("chacha20poly1305-open-{}-{}", self.aad_len, self.len)
+    }
+
+    fn arg_lens(&self) -> [usize; 4] {
+        [KEY_LEN, NONCE_LEN, self.aad_len, self.len + TAG_LEN]
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for ChaChaPolyCoprocessor<F> {
+    fn arity(&self) -> usize {
+        4
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (bits, arg_oks) = deconstruct_bytes_args(cs, g, s, not_dummy, args, &self.arg_lens())?;
+        let (plaintext, authentic) = synthesize_open(
+            &mut cs.namespace(|| "open"),
+            &bits[0],
+            &bits[1],
+            &bits[2],
+            &bits[3],
+        )?;
+        let plaintext = construct_bytes(&mut cs.namespace(|| "plaintext"), g, s, &plaintext)?;
+
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let res = AllocatedPtr::pick(
+            cs.namespace(|| "plaintext or nil"),
+            &authentic,
+            &plaintext,
+            &nil,
+        )?;
+        let (res, cont) = pick_bytes_args_result(cs, g, s, args, &arg_oks, res, cont)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for ChaChaPolyCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        4
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match fetch_bytes_args(s, args, self.arg_lens()) {
+            Ok([key, nonce, aad, sealed]) => {
+                let res = match open(&key, &nonce, &aad, &sealed) {
+                    Some(plaintext) => s.intern_bytes(&plaintext),
+                    None => s.intern_nil(),
+                };
+                vec![res, *env, *cont]
+            }
+            Err(arg) => vec![arg, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum AeadCoproc<F: LurkField> {
+    ChaChaPoly(ChaChaPolyCoprocessor<F>),
+}
+
+/// Add `.lurk.aead.chacha20poly1305-open-<aad-len>-<len>` to a `Lang` for each of the `(aad_len, len)` in `lens`
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, AeadCoproc<F>>,
+    lens: &[(usize, usize)],
+) {
+    let package_name: Symbol = ".lurk.aead".into();
+    let mut package = Package::new(package_name.clone().into());
+    for &(aad_len, len) in lens {
+        let coproc = ChaChaPolyCoprocessor::new(aad_len, len);
+        let name = coproc.name();
+        lang.add_coprocessor(package_name.direct_child(&name), coproc);
+        package.intern(name);
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    fn check(s: &Store<Fr>, coproc: &ChaChaPolyCoprocessor<Fr>, args: &[Ptr]) -> Vec<Ptr> {
+        check_coprocessor(s, coproc, args, &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_rfc8439_vector() {
+        // RFC 8439, section 2.8.2
+        let key: Vec<u8> = (0x80..0xa0).collect();
+        let nonce = hex::decode("070000004041424344454647").unwrap();
+        let aad = hex::decode("50515253c0c1c2c3c4c5c6c7").unwrap();
+        let plaintext =
+            b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the \
+            future, sunscreen would be it.";
+        let sealed = seal(&key, &nonce, &aad, plaintext);
+        assert_eq!(
+            "d31a8d34648e60db7b86afbc53ef7ec2",
+            hex::encode(&sealed[..16])
+        );
+        assert_eq!(
+            "1ae10b594f09e26a7e902ecbd0600691",
+            hex::encode(&sealed[plaintext.len()..])
+        );
+        assert_eq!(Some(plaintext.to_vec()), open(&key, &nonce, &aad, &sealed));
+        assert_eq!(None, open(&key, &nonce, &aad[1..], &sealed));
+    }
+
+    #[test]
+    fn test_open() {
+        let s = &Store::<Fr>::default();
+        let key = [7; KEY_LEN];
+        let nonce = [1; NONCE_LEN];
+        let aad = b"header";
+        let plaintext = b"attack at dawn";
+        let mut sealed = seal(&key, &nonce, aad, plaintext);
+        let coproc = ChaChaPolyCoprocessor::new(aad.len(), plaintext.len());
+
+        let args = |sealed: &[u8]| {
+            [
+                s.intern_bytes(&key),
+                s.intern_bytes(&nonce),
+                s.intern_bytes(aad),
+                s.intern_bytes(sealed),
+            ]
+        };
+        let output = check(s, &coproc, &args(&sealed));
+        assert_eq!(s.intern_bytes(plaintext), output[0]);
+        assert_eq!(s.cont_outermost(), output[2]);
+
+        // forgeries are rejected in the circuit too
+        sealed[0] ^= 1;
+        let output = check(s, &coproc, &args(&sealed));
+        assert_eq!(s.intern_nil(), output[0]);
+        assert_eq!(s.cont_outermost(), output[2]);
+    }
+
+    #[test]
+    fn test_open_errors() {
+        let s = &Store::<Fr>::default();
+        let coproc = ChaChaPolyCoprocessor::new(0, 1);
+        let num = s.num_u64(3);
+        let args = [
+            s.intern_bytes(&[0; KEY_LEN]),
+            num,
+            s.intern_bytes(&[]),
+            s.intern_bytes(&[0; 1 + TAG_LEN]),
+        ];
+        let output = check(s, &coproc, &args);
+        assert_eq!(vec![num, s.intern_empty_env(), s.cont_error()], output);
+
+        // so is a key of the wrong length
+        let short_key = s.intern_bytes(&[0; 16]);
+        let args = [
+            short_key,
+            s.intern_bytes(&[0; NONCE_LEN]),
+            s.intern_bytes(&[]),
+            s.intern_bytes(&[0; 1 + TAG_LEN]),
+        ];
+        let output = check(s, &coproc, &args);
+        assert_eq!(
+            vec![short_key, s.intern_empty_env(), s.cont_error()],
+            output
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! Association lists: proper lists of `(key . value)` conses, compared by key.
+//!
+//! `.lurk.alist.assoc` takes a key and an association list and returns its first entry for the key, or nil.
+//! `alist-insert` takes a key, a value and an association list and returns the list with the value of the first entry
+//! for the key replaced, or with a new entry in front if there's none. `alist-remove` takes a key and an association
+//! list and returns the list without its first entry for the key. Keys can be any expression.
+//!
+//! Hashmaps are association lists whose keys are unique, which `alist-insert` and `alist-remove` preserve. `hashmap`
+//! takes an association list and returns the hashmap of its bindings, dropping the entries shadowed by an earlier one
+//! for the same key, and `hashmap-get` takes a key and a hashmap and returns the value bound to the key, or nil. Each
+//! operation walks the association list once, so it takes time linear in the list's length, and `n` successive
+//! insertions into a growing list take quadratic time overall.
+//!
+//! The circuits unroll the association list up to a bound, `max_len`, that each coprocessor is created with. If the
+//! last argument isn't an association list of at most `max_len` entries, it's returned along with an error
+//! continuation.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::or,
+        data::{alloc_is_tag, car_cdr, construct_cons},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    tag::ExprTag,
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlistOp {
+    Assoc,
+    Insert,
+    Remove,
+    Hashmap,
+    Get,
+}
+
+impl AlistOp {
+    const ALL: [AlistOp; 5] = [
+        Self::Assoc,
+        Self::Insert,
+        Self::Remove,
+        Self::Hashmap,
+        Self::Get,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Assoc => "assoc",
+            Self::Insert => "alist-insert",
+            Self::Remove => "alist-remove",
+            Self::Hashmap => "hashmap",
+            Self::Get => "hashmap-get",
+        }
+    }
+
+    /// The number of arguments, the last of which is always the association list
+    fn arity(&self) -> usize {
+        match self {
+            Self::Hashmap => 1,
+            Self::Assoc | Self::Remove | Self::Get => 2,
+            Self::Insert => 3,
+        }
+    }
+}
+
+/// The unrolled entries of an association list
+struct AllocatedAlist<F: LurkField> {
+    /// The first `max_len` entries, padded with nil
+    entries: Vec<AllocatedPtr<F>>,
+    keys: Vec<AllocatedPtr<F>>,
+    vals: Vec<AllocatedPtr<F>>,
+    /// Whether each of `entries` is an entry of the list
+    present: Vec<Boolean>,
+    /// Whether the list is an association list of at most `max_len` entries
+    valid: Boolean,
+}
+
+impl<F: LurkField> AllocatedAlist<F> {
+    fn alloc<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        alist: &AllocatedPtr<F>,
+        max_len: usize,
+    ) -> Result<Self, SynthesisError> {
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let mut entries = Vec::with_capacity(max_len);
+        let mut keys = Vec::with_capacity(max_len);
+        let mut vals = Vec::with_capacity(max_len);
+        let mut present = Vec::with_capacity(max_len);
+        let mut valid = Boolean::Constant(true);
+        let mut rest = alist.clone();
+        for j in 0..max_len {
+            let cs = &mut cs.namespace(|| format!("entry {j}"));
+            let is_cons = alloc_is_tag(&mut cs.namespace(|| "is cons"), g, &rest, &ExprTag::Cons)?;
+            let is_nil = rest.alloc_equal(&mut cs.namespace(|| "is nil"), &nil)?;
+            let is_list = or(cs.namespace(|| "is list"), &is_cons, &is_nil)?;
+            valid = Boolean::and(cs.namespace(|| "list ok"), &valid, &is_list)?;
+
+            // `car_cdr` can only look up conses and nil
+            let bind = Boolean::and(cs.namespace(|| "bind"), not_dummy, &valid)?;
+            let (entry, cdr, not_empty) =
+                car_cdr(&mut cs.namespace(|| "entry"), g, s, &bind, &rest)?;
+            let entry_is_cons = alloc_is_tag(
+                &mut cs.namespace(|| "entry is cons"),
+                g,
+                &entry,
+                &ExprTag::Cons,
+            )?;
+            let bad_entry = Boolean::and(
+                cs.namespace(|| "bad entry"),
+                &not_empty,
+                &entry_is_cons.not(),
+            )?;
+            valid = Boolean::and(cs.namespace(|| "entry ok"), &valid, &bad_entry.not())?;
+
+            let bind = Boolean::and(cs.namespace(|| "bind entry"), &bind, &entry_is_cons)?;
+            let (key, val, _) = car_cdr(&mut cs.namespace(|| "key"), g, s, &bind, &entry)?;
+            entries.push(entry);
+            keys.push(key);
+            vals.push(val);
+            present.push(not_empty);
+            rest = cdr;
+        }
+        let ends = rest.alloc_equal(&mut cs.namespace(|| "ends"), &nil)?;
+        let valid = Boolean::and(cs.namespace(|| "valid"), &valid, &ends)?;
+        Ok(Self {
+            entries,
+            keys,
+            vals,
+            present,
+            valid,
+        })
+    }
+
+    /// Flags for whether each entry is the first one for `key`, and whether there's one
+    fn find<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        key: &AllocatedPtr<F>,
+    ) -> Result<(Vec<Boolean>, Boolean), SynthesisError> {
+        let mut found = Boolean::Constant(false);
+        let mut first = Vec::with_capacity(self.keys.len());
+        for (j, (k, present)) in self.keys.iter().zip(&self.present).enumerate() {
+            let cs = &mut cs.namespace(|| format!("find {j}"));
+            let same_key = k.alloc_equal(&mut cs.namespace(|| "same key"), key)?;
+            let matches = Boolean::and(cs.namespace(|| "matches"), &same_key, present)?;
+            first.push(Boolean::and(
+                cs.namespace(|| "first"),
+                &matches,
+                &found.not(),
+            )?);
+            found = or(cs.namespace(|| "found"), &found, &matches)?;
+        }
+        Ok((first, found))
+    }
+
+    /// Flags for whether each entry is shadowed by an earlier one for the same key
+    fn shadowed<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<Vec<Boolean>, SynthesisError> {
+        let mut shadowed = Vec::with_capacity(self.keys.len());
+        for (j, (key, present)) in self.keys.iter().zip(&self.present).enumerate() {
+            let cs = &mut cs.namespace(|| format!("shadowed {j}"));
+            let mut flag = Boolean::Constant(false);
+            for (i, earlier) in self.keys[..j].iter().enumerate() {
+                let same_key =
+                    key.alloc_equal(&mut cs.namespace(|| format!("same key {i}")), earlier)?;
+                flag = or(
+                    cs.namespace(|| format!("shadowed by {i}")),
+                    &flag,
+                    &same_key,
+                )?;
+            }
+            // absent entries are padding, which `rebuild` skips anyway
+            shadowed.push(Boolean::and(cs.namespace(|| "present"), &flag, present)?);
+        }
+        Ok(shadowed)
+    }
+
+    /// Rebuilds the list with each entry either kept, replaced by `replace` or dropped, as flagged
+    fn rebuild<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        edit: Edit<'_, F>,
+        flags: &[Boolean],
+    ) -> Result<AllocatedPtr<F>, SynthesisError> {
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let mut acc = nil.clone();
+        for (j, ((entry, present), flag)) in self
+            .entries
+            .iter()
+            .zip(&self.present)
+            .zip(flags)
+            .enumerate()
+            .rev()
+        {
+            let cs = &mut cs.namespace(|| format!("rebuild {j}"));
+            let edited = match edit {
+                Edit::Replace(new_entry) => {
+                    let elt = AllocatedPtr::pick(cs.namespace(|| "elt"), flag, new_entry, entry)?;
+                    construct_cons(&mut cs.namespace(|| "cons"), g, s, &elt, &acc)?
+                }
+                Edit::Drop => {
+                    let kept = construct_cons(&mut cs.namespace(|| "cons"), g, s, entry, &acc)?;
+                    AllocatedPtr::pick(cs.namespace(|| "drop"), flag, &acc, &kept)?
+                }
+            };
+            acc = AllocatedPtr::pick(cs.namespace(|| "acc"), present, &edited, &nil)?;
+        }
+        Ok(acc)
+    }
+}
+
+/// What `AllocatedAlist::rebuild` does to the flagged entries
+#[derive(Clone, Copy)]
+enum Edit<'a, F: LurkField> {
+    Replace(&'a AllocatedPtr<F>),
+    Drop,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlistCoprocessor<F: LurkField> {
+    op: AlistOp,
+    max_len: usize,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> AlistCoprocessor<F> {
+    /// A coprocessor for association lists of up to `max_len` entries
+    pub fn new(op: AlistOp, max_len: usize) -> Self {
+        Self {
+            op,
+            max_len,
+            _p: Default::default(),
+        }
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for AlistCoprocessor<F> {
+    fn arity(&self) -> usize {
+        self.op.arity()
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (key, alist) = (&args[0], &args[args.len() - 1]);
+        let unrolled = AllocatedAlist::alloc(
+            &mut cs.namespace(|| "alist"),
+            g,
+            s,
+            not_dummy,
+            alist,
+            self.max_len,
+        )?;
+        let (first, found) = match self.op {
+            // the only argument is the list
+            AlistOp::Hashmap => (vec![], Boolean::Constant(false)),
+            _ => unrolled.find(&mut cs.namespace(|| "find"), key)?,
+        };
+
+        let res = {
+            let cs = &mut cs.namespace(|| self.op.name());
+            match self.op {
+                AlistOp::Assoc => {
+                    let mut res = g.alloc_ptr(cs, &s.intern_nil(), s);
+                    for (j, (flag, entry)) in first.iter().zip(&unrolled.entries).enumerate() {
+                        res = AllocatedPtr::pick(
+                            cs.namespace(|| format!("entry {j}")),
+                            flag,
+                            entry,
+                            &res,
+                        )?;
+                    }
+                    res
+                }
+                AlistOp::Insert => {
+                    let new_entry =
+                        construct_cons(&mut cs.namespace(|| "new entry"), g, s, key, &args[1])?;
+                    let replaced = unrolled.rebuild(
+                        &mut cs.namespace(|| "replace"),
+                        g,
+                        s,
+                        Edit::Replace(&new_entry),
+                        &first,
+                    )?;
+                    let added =
+                        construct_cons(&mut cs.namespace(|| "add"), g, s, &new_entry, alist)?;
+                    AllocatedPtr::pick(
+                        cs.namespace(|| "replaced or added"),
+                        &found,
+                        &replaced,
+                        &added,
+                    )?
+                }
+                AlistOp::Remove => {
+                    unrolled.rebuild(&mut cs.namespace(|| "remove"), g, s, Edit::Drop, &first)?
+                }
+                AlistOp::Hashmap => {
+                    let shadowed = unrolled.shadowed(&mut cs.namespace(|| "shadowed"))?;
+                    unrolled.rebuild(&mut cs.namespace(|| "dedup"), g, s, Edit::Drop, &shadowed)?
+                }
+                AlistOp::Get => {
+                    let mut res = g.alloc_ptr(cs, &s.intern_nil(), s);
+                    for (j, (flag, val)) in first.iter().zip(&unrolled.vals).enumerate() {
+                        res = AllocatedPtr::pick(
+                            cs.namespace(|| format!("value {j}")),
+                            flag,
+                            val,
+                            &res,
+                        )?;
+                    }
+                    res
+                }
+            }
+        };
+
+        let res = AllocatedPtr::pick(
+            cs.namespace(|| "result or alist"),
+            &unrolled.valid,
+            &res,
+            alist,
+        )?;
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(
+            cs.namespace(|| "result cont"),
+            &unrolled.valid,
+            cont,
+            &cont_err,
+        )?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for AlistCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        self.op.arity()
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        let alist = &args[args.len() - 1];
+        let fits = s
+            .fetch_alist(alist)
+            .is_some_and(|entries| entries.len() <= self.max_len);
+        let res = fits
+            .then(|| match self.op {
+                AlistOp::Assoc => s.assoc(&args[0], alist),
+                AlistOp::Insert => s.alist_insert(args[0], args[1], alist),
+                AlistOp::Remove => s.alist_remove(&args[0], alist),
+                AlistOp::Hashmap => s.hashmap(alist),
+                AlistOp::Get => s.hashmap_get(&args[0], alist),
+            })
+            .flatten();
+        match res {
+            Some(res) => vec![res, *env, *cont],
+            None => vec![*alist, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum AlistCoproc<F: LurkField> {
+    Alist(AlistCoprocessor<F>),
+}
+
+/// Add the association list operations to a `Lang` as `.lurk.alist.assoc`, `.lurk.alist.alist-insert`, etc., for lists
+/// of up to `max_len` entries
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, AlistCoproc<F>>,
+    max_len: usize,
+) {
+    let package_name: Symbol = ".lurk.alist".into();
+    let mut package = Package::new(package_name.clone().into());
+    for op in AlistOp::ALL {
+        lang.add_coprocessor(
+            package_name.direct_child(op.name()),
+            AlistCoprocessor::new(op, max_len),
+        );
+        package.intern(op.name());
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    const MAX_LEN: usize = 3;
+
+    fn check(s: &Store<Fr>, op: AlistOp, args: &[Ptr]) -> Vec<Ptr> {
+        let coproc = AlistCoprocessor::new(op, MAX_LEN);
+        check_coprocessor(s, &coproc, args, &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_alist_ops() {
+        let s = &Store::<Fr>::default();
+        let read = |src: &str| s.read_with_default_state(src).unwrap();
+        let ok = |op, args: &[Ptr], res: &str| {
+            let output = check(s, op, args);
+            assert_eq!(read(res), output[0]);
+            assert_eq!(s.cont_outermost(), output[2]);
+        };
+        let alist = read("((a . 1) (b . 2) (a . 3))");
+
+        ok(AlistOp::Assoc, &[read("a"), alist], "(a . 1)");
+        ok(AlistOp::Assoc, &[read("b"), alist], "(b . 2)");
+        ok(AlistOp::Assoc, &[read("c"), alist], "nil");
+        ok(AlistOp::Assoc, &[read("a"), read("nil")], "nil");
+        ok(
+            AlistOp::Assoc,
+            &[read("(1 2)"), read("(((1 2) . x))")],
+            "((1 2) . x)",
+        );
+
+        ok(
+            AlistOp::Insert,
+            &[read("a"), read("4"), alist],
+            "((a . 4) (b . 2) (a . 3))",
+        );
+        ok(
+            AlistOp::Insert,
+            &[read("b"), read("4"), alist],
+            "((a . 1) (b . 4) (a . 3))",
+        );
+        ok(
+            AlistOp::Insert,
+            &[read("c"), read("4"), alist],
+            "((c . 4) (a . 1) (b . 2) (a . 3))",
+        );
+        ok(
+            AlistOp::Insert,
+            &[read("a"), read("1"), read("nil")],
+            "((a . 1))",
+        );
+
+        ok(AlistOp::Remove, &[read("a"), alist], "((b . 2) (a . 3))");
+        ok(AlistOp::Remove, &[read("b"), alist], "((a . 1) (a . 3))");
+        ok(
+            AlistOp::Remove,
+            &[read("c"), alist],
+            "((a . 1) (b . 2) (a . 3))",
+        );
+        ok(AlistOp::Remove, &[read("a"), read("((a . 1))")], "nil");
+    }
+
+    #[test]
+    fn test_hashmap_ops() {
+        let s = &Store::<Fr>::default();
+        let read = |src: &str| s.read_with_default_state(src).unwrap();
+        let ok = |op, args: &[Ptr], res: &str| {
+            let output = check(s, op, args);
+            assert_eq!(read(res), output[0]);
+            assert_eq!(s.cont_outermost(), output[2]);
+        };
+
+        ok(
+            AlistOp::Hashmap,
+            &[read("((a . 1) (b . 2) (a . 3))")],
+            "((a . 1) (b . 2))",
+        );
+        ok(
+            AlistOp::Hashmap,
+            &[read("((a . 1) (a . 2) (a . 3))")],
+            "((a . 1))",
+        );
+        ok(
+            AlistOp::Hashmap,
+            &[read("((a . 1) (b . 2) (c . 3))")],
+            "((a . 1) (b . 2) (c . 3))",
+        );
+        ok(AlistOp::Hashmap, &[read("nil")], "nil");
+
+        let map = read("((a . 1) (b . nil))");
+        ok(AlistOp::Get, &[read("a"), map], "1");
+        ok(AlistOp::Get, &[read("b"), map], "nil");
+        ok(AlistOp::Get, &[read("c"), map], "nil");
+
+        // inserting and removing keep the keys of a hashmap unique, sharing the rest of the list
+        let map = s.hashmap(&read("((a . 1) (b . 2) (a . 3))")).unwrap();
+        let map = s.alist_insert(read("b"), read("4"), &map).unwrap();
+        assert_eq!(read("((a . 1) (b . 4))"), map);
+        let map = s.alist_remove(&read("a"), &map).unwrap();
+        assert_eq!(read("((b . 4))"), map);
+        assert_eq!(Some(read("4")), s.hashmap_get(&read("b"), &map));
+        assert_eq!(Some(map), s.hashmap(&map));
+    }
+
+    #[test]
+    fn test_alist_errors() {
+        let s = &Store::<Fr>::default();
+        let read = |src: &str| s.read_with_default_state(src).unwrap();
+        for alist in [
+            "((a . 1) (b . 2) (c . 3) (d . 4))",
+            "((a . 1) . (b . 2))",
+            "((a . 1) b)",
+            "(a)",
+            "42",
+            "\"ab\"",
+        ] {
+            let alist = read(alist);
+            for op in AlistOp::ALL {
+                let args = match op {
+                    AlistOp::Insert => vec![read("a"), read("1"), alist],
+                    AlistOp::Hashmap => vec![alist],
+                    _ => vec![read("a"), alist],
+                };
+                let output = check(s, op, &args);
+                assert_eq!(alist, output[0]);
+                assert_eq!(s.cont_error(), output[2]);
+            }
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! Batched coprocessor calls.
+//!
+//! A `BatchCoprocessor` takes a list of calls to another coprocessor, each a list of its arguments, and returns the
+//! list of their results, so that up to `size` calls share one reduction and the constants its circuit allocates.
+//! Only `batchable` coprocessors can be batched: they must leave the environment alone and only return the error
+//! continuation, along with the offending value, when a call fails. The first failing call fails the batch the same
+//! way.
+//!
+//! Arguments that aren't lists return themselves along with an error continuation, and so do improper lists, lists of
+//! more than `size` calls and lists of calls that aren't lists of the right number of arguments.
+//!
+//! `Lang::set_batch_size` has the evaluator call a batched version of every batchable coprocessor, named `name.batch`.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+use crate::{
+    circuit::gadgets::{
+        constraints::or,
+        data::{alloc_is_tag, car_cdr, construct_cons},
+        pointer::AllocatedPtr,
+    },
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store, tag::Tag},
+    tag::ExprTag,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchCoprocessor<F: LurkField, C> {
+    inner: C,
+    size: usize,
+    pub(crate) _p: PhantomData<F>,
+}
+
+impl<F: LurkField, C: Coprocessor<F>> BatchCoprocessor<F, C> {
+    /// Batches up to `size` calls to `inner`.
+    ///
+    /// # Panics
+    /// Panics if `inner` isn't `batchable`
+    pub fn new(inner: C, size: usize) -> Self {
+        assert!(inner.batchable(), "coprocessor isn't batchable");
+        Self {
+            inner,
+            size,
+            _p: Default::default(),
+        }
+    }
+
+    /// The arguments of each call, if `calls` is a list of at most `size` calls with the right number of arguments
+    fn fetch_calls(&self, s: &Store<F>, calls: &Ptr) -> Option<Vec<Vec<Ptr>>> {
+        let (calls, None) = s.fetch_list(calls)? else {
+            return None;
+        };
+        if calls.len() > self.size {
+            return None;
+        }
+        calls
+            .iter()
+            .map(|call| match s.fetch_list(call)? {
+                (args, None) if args.len() == self.inner.arity() => Some(args),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl<F: LurkField, C: Coprocessor<F>> CoCircuit<F> for BatchCoprocessor<F, C> {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let [calls] = args else {
+            return Err(SynthesisError::Unsatisfiable);
+        };
+        let is_cons = alloc_is_tag(&mut cs.namespace(|| "is cons"), g, calls, &ExprTag::Cons)?;
+        let is_nil = alloc_is_tag(&mut cs.namespace(|| "is nil"), g, calls, &ExprTag::Nil)?;
+        let type_ok = or(cs.namespace(|| "is list"), &is_cons, &is_nil)?;
+        let bind = Boolean::and(cs.namespace(|| "bind"), &type_ok, not_dummy)?;
+
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let mut results = Vec::with_capacity(self.size);
+        // whether the calls seen so far are well-formed
+        let mut shape_ok = Boolean::Constant(true);
+        let mut cell = calls.clone();
+        for i in 0..self.size {
+            let mut cs = cs.namespace(|| format!("call {i}"));
+            // cells are only deconstructed while they're conses, after which `cell` keeps the end of the list
+            let cell_is_cons = alloc_is_tag(
+                &mut cs.namespace(|| "cell is cons"),
+                g,
+                &cell,
+                &ExprTag::Cons,
+            )?;
+            let active = Boolean::and(cs.namespace(|| "active"), &cell_is_cons, &bind)?;
+            let (call, next, _) = car_cdr(&mut cs.namespace(|| "car_cdr"), g, s, &active, &cell)?;
+            cell = AllocatedPtr::pick(cs.namespace(|| "next cell"), &active, &next, &cell)?;
+
+            let mut call_args = Vec::with_capacity(self.inner.arity());
+            let mut arg_cell = call;
+            let mut call_ok = Boolean::Constant(true);
+            for j in 0..self.inner.arity() {
+                let arg_is_cons = alloc_is_tag(
+                    &mut cs.namespace(|| format!("arg {j} is cons")),
+                    g,
+                    &arg_cell,
+                    &ExprTag::Cons,
+                )?;
+                call_ok = Boolean::and(
+                    cs.namespace(|| format!("arg {j} ok")),
+                    &call_ok,
+                    &arg_is_cons,
+                )?;
+                let arg_active = Boolean::and(
+                    cs.namespace(|| format!("arg {j} active")),
+                    &active,
+                    &call_ok,
+                )?;
+                let (arg, next, _) = car_cdr(
+                    &mut cs.namespace(|| format!("arg {j}")),
+                    g,
+                    s,
+                    &arg_active,
+                    &arg_cell,
+                )?;
+                call_args.push(arg);
+                arg_cell = AllocatedPtr::pick(
+                    cs.namespace(|| format!("arg {j} next")),
+                    &arg_active,
+                    &next,
+                    &arg_cell,
+                )?;
+            }
+            let no_more_args = alloc_is_tag(
+                &mut cs.namespace(|| "no more args"),
+                g,
+                &arg_cell,
+                &ExprTag::Nil,
+            )?;
+            let call_ok = Boolean::and(cs.namespace(|| "call ok"), &call_ok, &no_more_args)?;
+            // inactive calls don't matter
+            let bad_call = Boolean::and(cs.namespace(|| "bad call"), &active, &call_ok.not())?;
+            shape_ok = Boolean::and(cs.namespace(|| "shape ok"), &shape_ok, &bad_call.not())?;
+
+            let run = Boolean::and(cs.namespace(|| "run"), &active, &call_ok)?;
+            let output = self.inner.synthesize(
+                &mut cs.namespace(|| "inner"),
+                g,
+                s,
+                &run,
+                &call_args,
+                env,
+                cont,
+            )?;
+            let failed = output[2].alloc_equal(&mut cs.namespace(|| "failed"), &cont_err)?;
+            let failed = Boolean::and(cs.namespace(|| "active failure"), &failed, &run)?;
+            results.push((output[0].clone(), run, failed));
+        }
+        // longer lists and improper ones don't end with `nil` after `size` calls
+        let no_more_calls = alloc_is_tag(
+            &mut cs.namespace(|| "no more calls"),
+            g,
+            &cell,
+            &ExprTag::Nil,
+        )?;
+        let shape_ok = Boolean::and(cs.namespace(|| "all calls ok"), &shape_ok, &no_more_calls)?;
+        let type_ok = Boolean::and(cs.namespace(|| "type and shape ok"), &type_ok, &shape_ok)?;
+
+        // the list of results and the result of the first failing call, if any
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let mut list = nil.clone();
+        let mut error = nil;
+        let mut any_failed = Boolean::Constant(false);
+        for (i, (result, active, failed)) in results.iter().enumerate().rev() {
+            let mut cs = cs.namespace(|| format!("result {i}"));
+            let cons = construct_cons(&mut cs.namespace(|| "cons"), g, s, result, &list)?;
+            list = AllocatedPtr::pick(cs.namespace(|| "list"), active, &cons, &list)?;
+            error = AllocatedPtr::pick(cs.namespace(|| "error"), failed, result, &error)?;
+            any_failed = or(cs.namespace(|| "any failed"), &any_failed, failed)?;
+        }
+
+        let res = AllocatedPtr::pick(cs.namespace(|| "list or error"), &any_failed, &error, &list)?;
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or calls"), &type_ok, &res, calls)?;
+        let ok = Boolean::and(cs.namespace(|| "ok"), &type_ok, &any_failed.not())?;
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &ok, cont, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField, C: Coprocessor<F>> Coprocessor<F> for BatchCoprocessor<F, C> {
+    fn eval_arity(&self) -> usize {
+        1
+    }
+
+    fn has_circuit(&self) -> bool {
+        self.inner.has_circuit()
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        let calls = &args[0];
+        if !matches!(calls.tag(), Tag::Expr(ExprTag::Cons | ExprTag::Nil)) {
+            return vec![*calls, *env, s.cont_error()];
+        }
+        let Some(calls) = self.fetch_calls(s, calls) else {
+            return vec![*calls, *env, s.cont_error()];
+        };
+        let mut results = Vec::with_capacity(calls.len());
+        for call_args in calls {
+            let output = self.inner.evaluate(s, &call_args, env, cont);
+            if output[2] == s.cont_error() {
+                return vec![output[0], *env, output[2]];
+            }
+            results.push(output[0]);
+        }
+        vec![s.list(results), *env, *cont]
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::{check_coprocessor, DumbCoprocessor};
+
+    fn check(
+        s: &Store<Fr>,
+        coproc: &BatchCoprocessor<Fr, DumbCoprocessor<Fr>>,
+        calls: Ptr,
+    ) -> Vec<Ptr> {
+        check_coprocessor(s, coproc, &[calls], &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_batch() {
+        let s = &Store::<Fr>::default();
+        let coproc = BatchCoprocessor::new(DumbCoprocessor::new(), 3);
+        let num = |n: u64| s.num(Fr::from(n));
+        let call = |a: u64, b: u64| s.list(vec![num(a), num(b)]);
+
+        let output = check(s, &coproc, s.list(vec![call(2, 1), call(3, 4)]));
+        assert_eq!(s.list(vec![num(5), num(13)]), output[0]);
+        assert_eq!(s.cont_outermost(), output[2]);
+
+        let output = check(s, &coproc, s.list(vec![call(1, 1), call(1, 2), call(1, 3)]));
+        assert_eq!(s.list(vec![num(2), num(3), num(4)]), output[0]);
+
+        let output = check(s, &coproc, s.intern_nil());
+        assert_eq!(s.intern_nil(), output[0]);
+        assert_eq!(s.cont_outermost(), output[2]);
+    }
+
+    #[test]
+    fn test_batch_errors() {
+        let s = &Store::<Fr>::default();
+        let coproc = BatchCoprocessor::new(DumbCoprocessor::new(), 2);
+        let num = |n: u64| s.num(Fr::from(n));
+        let nil = s.intern_nil();
+
+        // the first failing call fails the batch
+        let calls = s.list(vec![
+            s.list(vec![num(1), num(2)]),
+            s.list(vec![num(1), nil]),
+        ]);
+        let output = check(s, &coproc, calls);
+        assert_eq!(vec![nil, s.intern_empty_env(), s.cont_error()], output);
+
+        // so does a batch that isn't a list
+        let output = check(s, &coproc, num(1));
+        assert_eq!(vec![num(1), s.intern_empty_env(), s.cont_error()], output);
+
+        // and so do malformed lists
+        let call = s.list(vec![num(1), num(2)]);
+        for calls in [
+            s.list(vec![call, call, call]),
+            s.list(vec![s.list(vec![num(1)])]),
+            s.list(vec![s.list(vec![num(1), num(2), num(3)])]),
+            s.list(vec![s.improper_list(vec![num(1)], num(2))]),
+            s.list(vec![call, num(1)]),
+            s.improper_list(vec![call], num(1)),
+        ] {
+            let output = check(s, &coproc, calls);
+            assert_eq!(vec![calls, s.intern_empty_env(), s.cont_error()], output);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+//! Arithmetic over natural numbers too large for the native field, such as the ones of RSA signatures.
+//!
+//! Naturals are represented as in `Store::intern_biguint`: the little-endian list of their `U64` limbs, without
+//! trailing zero limbs, so that zero is `nil`. Each coprocessor handles naturals of up to `limbs` limbs, that is,
+//! below `2^(64 limbs)`:
+//!
+//! - `.lurk.bignum.add-<bits>` and `.lurk.bignum.mul-<bits>` return the sum and the product of two naturals;
+//! - `.lurk.bignum.mod-exp-<bits>-<exp bits>` returns `base^exp mod m` for `(base exp m)`, where the exponent has up
+//!   to `exp bits` bits. RSA verification with `e = 65537` only needs a 64-bit exponent, for instance.
+//!
+//! In the circuits, every natural is decomposed into range-checked limbs and operations are checked limb by limb, as
+//! in `circuit::gadgets::emulated`. Reductions modulo `m` are checked with a quotient supplied by the prover, along
+//! with the remainder being below `m`.
+//!
+//! Arguments that aren't naturals of the right width return themselves along with an error continuation, and so do
+//! zero moduli. Sums and products that don't fit return the first argument with an error continuation. The circuits
+//! tell naturals apart by walking their lists, so that every error can be proved.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use num_bigint::{BigInt, BigUint};
+use num_integer::Integer;
+use num_traits::{One, Zero};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::{alloc_is_zero, implies_equal, pick},
+        data::{alloc_is_tag, car_cdr, construct_cons},
+        emulated::{EmulatedNum, Poly},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    tag::ExprTag,
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+const LIMB_BITS: usize = 64;
+
+fn fits(n: &BigUint, limbs: usize) -> bool {
+    n.bits() as usize <= limbs * LIMB_BITS
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BignumOp {
+    Add,
+    Mul,
+    /// Modular exponentiation, with exponents of up to `exp_limbs` limbs
+    ModExp {
+        exp_limbs: usize,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BignumCoprocessor<F: LurkField> {
+    op: BignumOp,
+    limbs: usize,
+    _p: PhantomData<F>,
+}
+
+/// The list of limbs of `n`, as in `Store::intern_biguint`
+fn construct_natural<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    n: &EmulatedNum<F>,
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    let u64_tag = g.alloc_tag_cloned(cs, &ExprTag::U64);
+    let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+    let mut list = nil.clone();
+    // whether every limb so far, from the most significant one, is zero
+    let mut is_nil = Boolean::Constant(true);
+    for (i, limb) in n.limbs().iter().enumerate().rev() {
+        let mut cs = cs.namespace(|| format!("limb {i}"));
+        let limb_is_zero = alloc_is_zero(cs.namespace(|| "is zero"), limb)?;
+        is_nil = Boolean::and(cs.namespace(|| "is nil"), &is_nil, &limb_is_zero)?;
+        let limb = AllocatedPtr::from_parts(u64_tag.clone(), limb.clone());
+        let cons = construct_cons(&mut cs.namespace(|| "cons"), g, s, &limb, &list)?;
+        list = AllocatedPtr::pick(cs.namespace(|| "list"), &is_nil, &nil, &cons)?;
+    }
+    Ok(list)
+}
+
+/// Allocates the natural `ptr` points to in `limbs` limbs, along with its little-endian bits and whether `ptr` is
+/// indeed a natural of up to `limbs` limbs, as `Store::fetch_biguint` would tell. The natural is only meaningful when
+/// it is.
+fn alloc_natural<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    ptr: &AllocatedPtr<F>,
+    limbs: usize,
+) -> Result<(EmulatedNum<F>, Vec<Boolean>, Boolean), SynthesisError> {
+    let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+    let zero = g.alloc_const(cs, F::ZERO);
+    // walks the first `limbs` cells, staying on the first one that isn't a cons
+    let mut cell = ptr.clone();
+    let mut shape_ok = Boolean::Constant(true);
+    let mut cell_limbs = Vec::with_capacity(limbs);
+    for i in 0..limbs {
+        let mut cs = cs.namespace(|| format!("cell {i}"));
+        let is_cons = alloc_is_tag(&mut cs.namespace(|| "is cons"), g, &cell, &ExprTag::Cons)?;
+        let active = Boolean::and(cs.namespace(|| "active"), &is_cons, not_dummy)?;
+        let (limb, next, _) = car_cdr(&mut cs.namespace(|| "car_cdr"), g, s, &active, &cell)?;
+        let is_u64 = alloc_is_tag(&mut cs.namespace(|| "is u64"), g, &limb, &ExprTag::U64)?;
+        let bad_limb = Boolean::and(cs.namespace(|| "bad limb"), &is_cons, &is_u64.not())?;
+        shape_ok = Boolean::and(cs.namespace(|| "limbs ok"), &shape_ok, &bad_limb.not())?;
+        cell_limbs.push(pick(cs.namespace(|| "limb"), &is_cons, limb.hash(), zero)?);
+        cell = AllocatedPtr::pick(cs.namespace(|| "next cell"), &is_cons, &next, &cell)?;
+    }
+    let ends = cell.alloc_equal(&mut cs.namespace(|| "ends in nil"), &nil)?;
+    shape_ok = Boolean::and(cs.namespace(|| "shape ok"), &shape_ok, &ends)?;
+
+    let value = match shape_ok.get_value() {
+        Some(true) => cell_limbs
+            .iter()
+            .rev()
+            .try_fold(BigUint::zero(), |acc, limb| {
+                Some((acc << LIMB_BITS) + limb.get_value()?.to_u64().unwrap_or(0))
+            }),
+        Some(false) => Some(BigUint::zero()),
+        None => None,
+    };
+    let (n, bits) = EmulatedNum::alloc_limbs(&mut cs.namespace(|| "limbs"), value, limbs)?;
+    let bind = Boolean::and(cs.namespace(|| "bind"), &shape_ok, not_dummy)?;
+    for (i, (limb, cell_limb)) in n.limbs().iter().zip(&cell_limbs).enumerate() {
+        implies_equal(
+            &mut cs.namespace(|| format!("limb {i}")),
+            &bind,
+            limb,
+            cell_limb,
+        );
+    }
+    // lists with trailing zero limbs aren't naturals, and the list of `n` has none
+    let list = construct_natural(&mut cs.namespace(|| "list"), g, s, &n)?;
+    let canonical = list.alloc_equal(&mut cs.namespace(|| "canonical"), ptr)?;
+    let ok = Boolean::and(cs.namespace(|| "ok"), &shape_ok, &canonical)?;
+    Ok((n, bits, ok))
+}
+
+/// Enforces `r < m`, for a variable `m`
+fn enforce_lt<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    r: &EmulatedNum<F>,
+    m: &EmulatedNum<F>,
+) -> Result<(), SynthesisError> {
+    // `r + d = m - 1` for some `d >= 0`
+    let d_value = r.value().zip(m.value()).map(|(r, m)| {
+        if r < m {
+            m - r - 1u32
+        } else {
+            // unsatisfiable
+            BigUint::zero()
+        }
+    });
+    let (d, _) =
+        EmulatedNum::alloc_limbs(&mut cs.namespace(|| "difference"), d_value, m.limbs().len())?;
+    Poly::new()
+        .term(1, m)
+        .term(-1, r)
+        .term(-1, &d)
+        .constant(BigInt::from(-1))
+        .enforce_zero(&mut cs.namespace(|| "sum"))
+}
+
+/// The remainder of `a b` by `m`, or of `a` without `b`, for a positive `m` with as many limbs as `a` and `b`
+fn alloc_mod<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    a: &EmulatedNum<F>,
+    b: Option<&EmulatedNum<F>>,
+    m: &EmulatedNum<F>,
+) -> Result<EmulatedNum<F>, SynthesisError> {
+    let limbs = m.limbs().len();
+    let value = match b {
+        Some(b) => a.value().zip(b.value()).map(|(a, b)| a * b),
+        None => a.value().cloned(),
+    };
+    let (q_value, r_value) = value.zip(m.value()).map(|(v, m)| v.div_rem(m)).unzip();
+    let (q, _) = EmulatedNum::alloc_limbs(&mut cs.namespace(|| "quotient"), q_value, limbs)?;
+    let (r, _) = EmulatedNum::alloc_limbs(&mut cs.namespace(|| "remainder"), r_value, limbs)?;
+    let poly = match b {
+        Some(b) => Poly::new().product(1, a, b),
+        None => Poly::new().term(1, a),
+    };
+    poly.product(-1, &q, m)
+        .term(-1, &r)
+        .enforce_zero(&mut cs.namespace(|| "division"))?;
+    enforce_lt(&mut cs.namespace(|| "reduced"), &r, m)?;
+    Ok(r)
+}
+
+impl<F: LurkField> BignumCoprocessor<F> {
+    pub fn new(op: BignumOp, limbs: usize) -> Self {
+        Self {
+            op,
+            limbs,
+            _p: Default::default(),
+        }
+    }
+
+    /// The name of the coprocessor in the `.lurk.bignum` package
+    pub fn name(&self) -> String {
+        let bits = self.limbs * LIMB_BITS;
+        match self.op {
+            BignumOp::Add => format!("add-{bits}"),
+            BignumOp::Mul => format!("mul-{bits}"),
+            BignumOp::ModExp { exp_limbs } => format!("mod-exp-{bits}-{}", exp_limbs * LIMB_BITS),
+        }
+    }
+
+    /// The result of the operation, or the offending argument
+    fn apply(&self, s: &Store<F>, args: &[Ptr]) -> Result<Ptr, Ptr> {
+        let natural =
+            |ptr: &Ptr, limbs| s.fetch_biguint(ptr).filter(|n| fits(n, limbs)).ok_or(*ptr);
+        let res = match self.op {
+            BignumOp::Add | BignumOp::Mul => {
+                let a = natural(&args[0], self.limbs)?;
+                let b = natural(&args[1], self.limbs)?;
+                let res = if self.op == BignumOp::Add {
+                    a + b
+                } else {
+                    a * b
+                };
+                if !fits(&res, self.limbs) {
+                    return Err(args[0]);
+                }
+                res
+            }
+            BignumOp::ModExp { exp_limbs } => {
+                let base = natural(&args[0], self.limbs)?;
+                let exp = natural(&args[1], exp_limbs)?;
+                let m = natural(&args[2], self.limbs)?;
+                if m.is_zero() {
+                    return Err(args[2]);
+                }
+                base.modpow(&exp, &m)
+            }
+        };
+        Ok(s.intern_biguint(&res))
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for BignumCoprocessor<F> {
+    fn arity(&self) -> usize {
+        match self.op {
+            BignumOp::Add | BignumOp::Mul => 2,
+            BignumOp::ModExp { .. } => 3,
+        }
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let limbs = self.limbs;
+        let (res, err, ok) = match (self.op, args) {
+            (BignumOp::Add | BignumOp::Mul, [a_ptr, b_ptr]) => {
+                let (a, _, a_ok) =
+                    alloc_natural(&mut cs.namespace(|| "a"), g, s, not_dummy, a_ptr, limbs)?;
+                let (b, _, b_ok) =
+                    alloc_natural(&mut cs.namespace(|| "b"), g, s, not_dummy, b_ptr, limbs)?;
+                let (poly, value, width) = if self.op == BignumOp::Add {
+                    let value = a.value().zip(b.value()).map(|(a, b)| a + b);
+                    (Poly::new().term(1, &a).term(1, &b), value, limbs + 1)
+                } else {
+                    let value = a.value().zip(b.value()).map(|(a, b)| a * b);
+                    (Poly::new().product(1, &a, &b), value, 2 * limbs)
+                };
+                // wide enough for any result, which fits iff its limbs past the `limbs`th are zero
+                let (res, _) =
+                    EmulatedNum::alloc_limbs(&mut cs.namespace(|| "result"), value, width)?;
+                poly.term(-1, &res)
+                    .enforce_zero(&mut cs.namespace(|| "result identity"))?;
+                let mut fits = Boolean::Constant(true);
+                for (i, limb) in res.limbs()[limbs..].iter().enumerate() {
+                    let mut cs = cs.namespace(|| format!("high limb {i}"));
+                    let limb_is_zero = alloc_is_zero(cs.namespace(|| "is zero"), limb)?;
+                    fits = Boolean::and(cs.namespace(|| "fits"), &fits, &limb_is_zero)?;
+                }
+                let res = construct_natural(&mut cs.namespace(|| "result list"), g, s, &res)?;
+
+                let args_ok = Boolean::and(cs.namespace(|| "args ok"), &a_ok, &b_ok)?;
+                let ok = Boolean::and(cs.namespace(|| "ok"), &args_ok, &fits)?;
+                // `b` if it's the first bad argument, and `a` otherwise, overflows included
+                let err = AllocatedPtr::pick(cs.namespace(|| "bad argument"), &a_ok, b_ptr, a_ptr)?;
+                let err = AllocatedPtr::pick(cs.namespace(|| "error"), &args_ok, a_ptr, &err)?;
+                (res, err, ok)
+            }
+            (BignumOp::ModExp { exp_limbs }, [base_ptr, exp_ptr, m_ptr]) => {
+                let (base, _, base_ok) = alloc_natural(
+                    &mut cs.namespace(|| "base"),
+                    g,
+                    s,
+                    not_dummy,
+                    base_ptr,
+                    limbs,
+                )?;
+                let (_, exp_bits, exp_ok) = alloc_natural(
+                    &mut cs.namespace(|| "exp"),
+                    g,
+                    s,
+                    not_dummy,
+                    exp_ptr,
+                    exp_limbs,
+                )?;
+                let (m, _, m_ok) =
+                    alloc_natural(&mut cs.namespace(|| "m"), g, s, not_dummy, m_ptr, limbs)?;
+
+                // computing modulo 1 instead of 0 keeps the identities satisfiable
+                let m_is_zero = m.alloc_is_zero(&mut cs.namespace(|| "m is zero"))?;
+                let one = EmulatedNum::constant_limbs(
+                    &mut cs.namespace(|| "one"),
+                    &BigUint::one(),
+                    limbs,
+                )?;
+                let m = EmulatedNum::pick(&mut cs.namespace(|| "modulus"), &m_is_zero, &one, &m)?;
+
+                let base = alloc_mod(&mut cs.namespace(|| "base mod m"), &base, None, &m)?;
+                let mut acc = alloc_mod(&mut cs.namespace(|| "one mod m"), &one, None, &m)?;
+                // square and multiply, from the most significant bit down
+                for (i, bit) in exp_bits.iter().enumerate().rev() {
+                    let mut cs = cs.namespace(|| format!("exp bit {i}"));
+                    let square = alloc_mod(&mut cs.namespace(|| "square"), &acc, Some(&acc), &m)?;
+                    let product =
+                        alloc_mod(&mut cs.namespace(|| "product"), &square, Some(&base), &m)?;
+                    acc = EmulatedNum::pick(&mut cs.namespace(|| "acc"), bit, &product, &square)?;
+                }
+                let res = construct_natural(&mut cs.namespace(|| "result list"), g, s, &acc)?;
+
+                let ok = Boolean::and(cs.namespace(|| "base and exp ok"), &base_ok, &exp_ok)?;
+                let ok = Boolean::and(cs.namespace(|| "args ok"), &ok, &m_ok)?;
+                let ok = Boolean::and(cs.namespace(|| "ok"), &ok, &m_is_zero.not())?;
+                // the first bad argument, or `m` if it's zero
+                let err = AllocatedPtr::pick(cs.namespace(|| "exp or m"), &exp_ok, m_ptr, exp_ptr)?;
+                let err = AllocatedPtr::pick(cs.namespace(|| "error"), &base_ok, &err, base_ptr)?;
+                (res, err, ok)
+            }
+            _ => return Err(SynthesisError::Unsatisfiable),
+        };
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or error"), &ok, &res, &err)?;
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &ok, cont, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for BignumCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        self.arity()
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match self.apply(s, args) {
+            Ok(res) => vec![res, *env, *cont],
+            Err(arg) => vec![arg, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum BignumCoproc<F: LurkField> {
+    Bignum(BignumCoprocessor<F>),
+}
+
+/// Add the operations over naturals of up to `limbs` limbs to a `Lang`, as `.lurk.bignum.add-<bits>`,
+/// `.lurk.bignum.mul-<bits>` and `.lurk.bignum.mod-exp-<bits>-<exp bits>`, with exponents of up to `exp_limbs` limbs
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, BignumCoproc<F>>,
+    limbs: usize,
+    exp_limbs: usize,
+) {
+    let package_name: Symbol = ".lurk.bignum".into();
+    let mut package = Package::new(package_name.clone().into());
+    for op in [BignumOp::Add, BignumOp::Mul, BignumOp::ModExp { exp_limbs }] {
+        let coproc = BignumCoprocessor::new(op, limbs);
+        let name = coproc.name();
+        lang.add_coprocessor(package_name.direct_child(&name), coproc);
+        package.intern(name);
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+    use rand::{Rng, SeedableRng};
+    use rand_xorshift::XorShiftRng;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    fn check(s: &Store<Fr>, coproc: &BignumCoprocessor<Fr>, args: &[Ptr]) -> Vec<Ptr> {
+        check_coprocessor(s, coproc, args, &s.cont_outermost())
+    }
+
+    fn random(rng: &mut XorShiftRng, limbs: usize) -> BigUint {
+        BigUint::from_slice(&(0..2 * limbs).map(|_| rng.gen()).collect::<Vec<u32>>())
+    }
+
+    #[test]
+    fn test_add_mul() {
+        let s = &Store::<Fr>::default();
+        let rng = &mut XorShiftRng::seed_from_u64(0);
+        let add = BignumCoprocessor::new(BignumOp::Add, 3);
+        let mul = BignumCoprocessor::new(BignumOp::Mul, 3);
+        for _ in 0..5 {
+            let (a, b) = (random(rng, 2), random(rng, 1));
+            let args = [s.intern_biguint(&a), s.intern_biguint(&b)];
+            assert_eq!(s.intern_biguint(&(&a + &b)), check(s, &add, &args)[0]);
+            assert_eq!(s.intern_biguint(&(&a * &b)), check(s, &mul, &args)[0]);
+        }
+        let zero = s.intern_biguint(&BigUint::zero());
+        assert_eq!(zero, check(s, &add, &[zero, zero])[0]);
+        let a = s.intern_biguint(&BigUint::from(u64::MAX));
+        assert_eq!(zero, check(s, &mul, &[a, zero])[0]);
+    }
+
+    #[test]
+    fn test_mod_exp() {
+        let s = &Store::<Fr>::default();
+        let rng = &mut XorShiftRng::seed_from_u64(1);
+        let mod_exp = BignumCoprocessor::new(BignumOp::ModExp { exp_limbs: 1 }, 2);
+        // a toy RSA signature, with `e = 65537`
+        let e = BigUint::from(65537u32);
+        for _ in 0..2 {
+            let (signature, m) = (random(rng, 2), random(rng, 2) | BigUint::one());
+            let message = signature.modpow(&e, &m);
+            let args = [signature, e.clone(), m].map(|n| s.intern_biguint(&n));
+            assert_eq!(s.intern_biguint(&message), check(s, &mod_exp, &args)[0]);
+        }
+        let one = BigUint::one();
+        let args = [BigUint::from(5u32), BigUint::zero(), one].map(|n| s.intern_biguint(&n));
+        assert_eq!(
+            s.intern_biguint(&BigUint::zero()),
+            check(s, &mod_exp, &args)[0]
+        );
+    }
+
+    #[test]
+    fn test_bignum_errors() {
+        let s = &Store::<Fr>::default();
+        let env = s.intern_empty_env();
+        let err = s.cont_error();
+
+        let mod_exp = BignumCoprocessor::new(BignumOp::ModExp { exp_limbs: 1 }, 1);
+        let zero = s.intern_biguint(&BigUint::zero());
+        let two = s.intern_biguint(&BigUint::from(2u32));
+        assert_eq!(vec![zero, env, err], check(s, &mod_exp, &[two, two, zero]));
+
+        let add = BignumCoprocessor::new(BignumOp::Add, 1);
+        let mul = BignumCoprocessor::new(BignumOp::Mul, 1);
+        let max = s.intern_biguint(&BigUint::from(u64::MAX));
+        assert_eq!(vec![max, env, err], check(s, &add, &[max, two]));
+        assert_eq!(vec![two, env, err], check(s, &mul, &[two, max]));
+
+        let num = s.num_u64(2);
+        let wide = s.intern_biguint(&(BigUint::one() << 64));
+        // a trailing zero limb, a limb that isn't a `U64` and an improper list
+        let trailing_zero = s.list(vec![s.u64(2), s.u64(0)]);
+        let not_u64 = s.list(vec![num]);
+        let improper = s.cons(s.u64(2), s.u64(2));
+        for arg in [num, wide, trailing_zero, not_u64, improper] {
+            assert_eq!(vec![arg, env, err], check(s, &add, &[two, arg]));
+            assert_eq!(vec![arg, env, err], check(s, &add, &[arg, two]));
+            assert_eq!(vec![arg, env, err], check(s, &mod_exp, &[two, arg, two]));
+            assert_eq!(vec![arg, env, err], check(s, &mod_exp, &[arg, max, arg]));
+        }
+    }
+}
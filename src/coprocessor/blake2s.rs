@@ -0,0 +1,659 @@
+//! BLAKE2s-256 hashing over bytes, for interoperability with content addressing schemes that use it.
+//!
+//! `.lurk.blake2s.bytes-<len>` hashes `len` bytes at once, given as a `Bytes` or as a string of characters below 256
+//! as in `.lurk.sha256`. Larger inputs can be hashed incrementally instead, carrying the hash state from a chunk to
+//! the next:
+//!
+//! - `.lurk.blake2s.init` returns the state of an empty hash;
+//! - `.lurk.blake2s.update-<len>` absorbs a chunk of `len` bytes, a positive multiple of the 64 bytes of a block;
+//! - `.lurk.blake2s.finalize-<len>` absorbs the last `len` bytes, at least one, and returns the digest.
+//!
+//! The state is a `Bytes` of 40 bytes: the chaining value, as 8 little-endian words, followed by the number of bytes
+//! absorbed so far, as a little-endian `u64`. Since BLAKE2s flags the last block, only a non-empty last chunk can be
+//! absorbed by `finalize`, and the empty input is only hashed by `bytes-0`. A message hashes the same in both modes.
+//!
+//! Type errors return the offending argument along with an error continuation. So do states and chunks of the right
+//! type but of the wrong length.
+
+use bellpepper::gadgets::{blake2s::blake2s, multieq::MultiEq, uint32::UInt32};
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, LinearCombination, SynthesisError};
+use lurk_macros::Coproc;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::{add_to_lc, alloc_lc, decompose_le},
+        data::{
+            construct_bytes, deconstruct_bytes_of_len, deconstruct_bytes_or_string,
+            fetch_bytes_or_string,
+        },
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+const BLOCK_LEN: usize = 64;
+
+/// The number of bytes of an incremental hash state
+pub const STATE_LEN: usize = 40;
+
+const IV: [u32; 8] = [
+    0x6A09_E667,
+    0xBB67_AE85,
+    0x3C6E_F372,
+    0xA54F_F53A,
+    0x510E_527F,
+    0x9B05_688C,
+    0x1F83_D9AB,
+    0x5BE0_CD19,
+];
+
+const SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// The words mixed by each `G` of a round: the columns, then the diagonals
+const MIX: [[usize; 4]; 8] = [
+    [0, 4, 8, 12],
+    [1, 5, 9, 13],
+    [2, 6, 10, 14],
+    [3, 7, 11, 15],
+    [0, 5, 10, 15],
+    [1, 6, 11, 12],
+    [2, 7, 8, 13],
+    [3, 4, 9, 14],
+];
+
+/// The chaining value and byte counter of an unkeyed BLAKE2s-256 hash
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Blake2sState {
+    h: [u32; 8],
+    t: u64,
+}
+
+impl Default for Blake2sState {
+    fn default() -> Self {
+        let mut h = IV;
+        // digest length 32, no key, fanout and depth 1
+        h[0] ^= 0x0101_0020;
+        Self { h, t: 0 }
+    }
+}
+
+impl Blake2sState {
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != STATE_LEN {
+            return None;
+        }
+        let mut h = [0; 8];
+        for (word, chunk) in h.iter_mut().zip(bytes.chunks(4)) {
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        let t = u64::from_le_bytes(bytes[32..].try_into().unwrap());
+        Some(Self { h, t })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.h.iter().flat_map(|w| w.to_le_bytes()).collect();
+        bytes.extend(self.t.to_le_bytes());
+        bytes
+    }
+
+    /// Compresses a block of up to 64 bytes, padded with zeros
+    fn compress(&mut self, block: &[u8], last: bool) {
+        self.t = self.t.wrapping_add(block.len() as u64);
+        let mut m = [0u32; 16];
+        for (word, chunk) in m.iter_mut().zip(block.chunks(4)) {
+            let mut bytes = [0; 4];
+            bytes[..chunk.len()].copy_from_slice(chunk);
+            *word = u32::from_le_bytes(bytes);
+        }
+
+        let mut v = [0u32; 16];
+        v[..8].copy_from_slice(&self.h);
+        v[8..].copy_from_slice(&IV);
+        v[12] ^= self.t as u32;
+        v[13] ^= (self.t >> 32) as u32;
+        if last {
+            v[14] = !v[14];
+        }
+        for s in &SIGMA {
+            for (j, &[a, b, c, d]) in MIX.iter().enumerate() {
+                let (x, y) = (m[s[2 * j]], m[s[2 * j + 1]]);
+                v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+                v[d] = (v[d] ^ v[a]).rotate_right(16);
+                v[c] = v[c].wrapping_add(v[d]);
+                v[b] = (v[b] ^ v[c]).rotate_right(12);
+                v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+                v[d] = (v[d] ^ v[a]).rotate_right(8);
+                v[c] = v[c].wrapping_add(v[d]);
+                v[b] = (v[b] ^ v[c]).rotate_right(7);
+            }
+        }
+        for (i, h) in self.h.iter_mut().enumerate() {
+            *h ^= v[i] ^ v[i + 8];
+        }
+    }
+
+    /// Absorbs whole blocks, none of which is the last one
+    ///
+    /// # Panics
+    /// Panics if `data` isn't made of whole blocks
+    pub fn update(&mut self, data: &[u8]) {
+        assert_eq!(data.len() % BLOCK_LEN, 0, "data must make whole blocks");
+        for block in data.chunks(BLOCK_LEN) {
+            self.compress(block, false);
+        }
+    }
+
+    /// Absorbs the rest of the input and returns the digest
+    pub fn finalize(mut self, data: &[u8]) -> [u8; 32] {
+        let split = last_block_start(data.len());
+        self.update(&data[..split]);
+        self.compress(&data[split..], true);
+        let mut digest = [0; 32];
+        digest.copy_from_slice(&self.to_bytes()[..32]);
+        digest
+    }
+}
+
+/// Where the last block of an input of `len` bytes starts. The empty input has an empty last block.
+fn last_block_start(len: usize) -> usize {
+    len.saturating_sub(1) / BLOCK_LEN * BLOCK_LEN
+}
+
+/// The BLAKE2s-256 digest of `data`
+pub fn blake2s_digest(data: &[u8]) -> [u8; 32] {
+    Blake2sState::default().finalize(data)
+}
+
+/// The mixing function `G` over the words `abcd` of `v`
+fn mix<F: LurkField, CS: ConstraintSystem<F>, M: ConstraintSystem<F, Root = MultiEq<F, CS>>>(
+    mut cs: M,
+    v: &mut [UInt32],
+    [a, b, c, d]: [usize; 4],
+    x: &UInt32,
+    y: &UInt32,
+) -> Result<(), SynthesisError> {
+    v[a] = UInt32::addmany(
+        cs.namespace(|| "a + b + x"),
+        &[v[a].clone(), v[b].clone(), x.clone()],
+    )?;
+    v[d] = v[d].xor(cs.namespace(|| "d ^ a (1)"), &v[a])?.rotr(16);
+    v[c] = UInt32::addmany(cs.namespace(|| "c + d (1)"), &[v[c].clone(), v[d].clone()])?;
+    v[b] = v[b].xor(cs.namespace(|| "b ^ c (1)"), &v[c])?.rotr(12);
+    v[a] = UInt32::addmany(
+        cs.namespace(|| "a + b + y"),
+        &[v[a].clone(), v[b].clone(), y.clone()],
+    )?;
+    v[d] = v[d].xor(cs.namespace(|| "d ^ a (2)"), &v[a])?.rotr(8);
+    v[c] = UInt32::addmany(cs.namespace(|| "c + d (2)"), &[v[c].clone(), v[d].clone()])?;
+    v[b] = v[b].xor(cs.namespace(|| "b ^ c (2)"), &v[c])?.rotr(7);
+    Ok(())
+}
+
+/// Compresses the 512 bits of `block` into `h`, given the 64 bits of the counter `t`, least significant first
+fn synthesize_compress<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: CS,
+    h: &mut [UInt32],
+    block: &[Boolean],
+    t: &[Boolean],
+    last: bool,
+) -> Result<(), SynthesisError> {
+    let mut cs = MultiEq::new(cs);
+    let m: Vec<_> = block.chunks(32).map(UInt32::from_bits).collect();
+    let mut v: Vec<_> = h
+        .iter()
+        .cloned()
+        .chain(IV.iter().map(|w| UInt32::constant(*w)))
+        .collect();
+    v[12] = v[12].xor(cs.namespace(|| "t0"), &UInt32::from_bits(&t[..32]))?;
+    v[13] = v[13].xor(cs.namespace(|| "t1"), &UInt32::from_bits(&t[32..]))?;
+    if last {
+        v[14] = v[14].xor(cs.namespace(|| "f0"), &UInt32::constant(u32::MAX))?;
+    }
+    for (i, s) in SIGMA.iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("round {i}"));
+        for (j, abcd) in MIX.iter().enumerate() {
+            let (x, y) = (&m[s[2 * j]], &m[s[2 * j + 1]]);
+            mix(cs.namespace(|| format!("G {j}")), &mut v, *abcd, x, y)?;
+        }
+    }
+    for i in 0..8 {
+        let hv = h[i].xor(cs.namespace(|| format!("h{i} ^ v{i}")), &v[i])?;
+        h[i] = hv.xor(cs.namespace(|| format!("h{i} ^ v{}", i + 8)), &v[i + 8])?;
+    }
+    Ok(())
+}
+
+/// The 64 bits of `t + n` modulo `2^64`, for the 64 bits of `t`
+fn synthesize_add_counter<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    t: &[Boolean],
+    n: u64,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let mut lc = LinearCombination::zero() + (F::from_u64(n), CS::one());
+    let mut value = Some(F::from_u64(n));
+    let mut coeff = F::ONE;
+    for bit in t {
+        lc = add_to_lc::<F, CS>(bit, lc, coeff);
+        value = value
+            .zip(bit.get_value())
+            .map(|(v, b)| if b { v + coeff } else { v });
+        coeff = coeff.double();
+    }
+    let sum = alloc_lc(&mut cs.namespace(|| "sum"), value, lc)?;
+    let mut bits = decompose_le(&mut cs.namespace(|| "sum bits"), &sum, 65)?;
+    bits.truncate(64);
+    Ok(bits)
+}
+
+/// Absorbs the bytes of `bits` into the state `(h, t)`, ending with the last block if `last`, and returns the new
+/// counter
+fn absorb_blocks<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    h: &mut [UInt32],
+    t: &[Boolean],
+    bits: &[Boolean],
+    last: bool,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    let len = bits.len() / 8;
+    let split = if last { last_block_start(len) } else { len };
+    let mut blocks: Vec<_> = bits[..8 * split]
+        .chunks(8 * BLOCK_LEN)
+        .map(|block| (block, false))
+        .collect();
+    if last {
+        blocks.push((&bits[8 * split..], true));
+    }
+
+    let mut absorbed = 0;
+    let mut counter = t.to_vec();
+    for (i, (block, last)) in blocks.into_iter().enumerate() {
+        let mut cs = cs.namespace(|| format!("block {i}"));
+        absorbed += block.len() / 8;
+        counter = synthesize_add_counter(&mut cs.namespace(|| "t"), t, absorbed as u64)?;
+        let mut padded = block.to_vec();
+        padded.resize(8 * BLOCK_LEN, Boolean::constant(false));
+        synthesize_compress(cs.namespace(|| "compress"), h, &padded, &counter, last)?;
+    }
+    Ok(counter)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Blake2sOp {
+    /// Hashes `len` bytes at once
+    Bytes(usize),
+    Init,
+    /// Absorbs `len` bytes, a positive multiple of 64
+    Update(usize),
+    /// Absorbs the last `len` bytes, at least one, and returns the digest
+    Finalize(usize),
+}
+
+impl Blake2sOp {
+    fn name(&self) -> String {
+        match self {
+            Self::Bytes(len) => format!("bytes-{len}"),
+            Self::Init => "init".into(),
+            Self::Update(len) => format!("update-{len}"),
+            Self::Finalize(len) => format!("finalize-{len}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Blake2sCoprocessor<F: LurkField> {
+    op: Blake2sOp,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> Blake2sCoprocessor<F> {
+    /// # Panics
+    /// Panics if the length of an `Update` isn't a positive multiple of 64, or if the length of a `Finalize` is zero
+    pub fn new(op: Blake2sOp) -> Self {
+        match op {
+            Blake2sOp::Update(len) => assert!(
+                len > 0 && len % BLOCK_LEN == 0,
+                "updates must absorb whole blocks"
+            ),
+            Blake2sOp::Finalize(len) => assert!(len > 0, "the last chunk can't be empty"),
+            Blake2sOp::Bytes(_) | Blake2sOp::Init => (),
+        }
+        Self {
+            op,
+            _p: Default::default(),
+        }
+    }
+
+    /// The name of the coprocessor in the `.lurk.blake2s` package
+    pub fn name(&self) -> String {
+        self.op.name()
+    }
+
+    /// The result of the operation, or the offending argument
+    fn apply(&self, s: &Store<F>, args: &[Ptr]) -> Result<Ptr, Ptr> {
+        let chunk = |ptr: &Ptr, len| {
+            fetch_bytes_or_string(s, ptr)
+                .filter(|bytes| bytes.len() == len)
+                .ok_or(*ptr)
+        };
+        let state = |ptr: &Ptr| {
+            s.fetch_bytes(ptr)
+                .and_then(|bytes| Blake2sState::from_bytes(&bytes))
+                .ok_or(*ptr)
+        };
+        match self.op {
+            Blake2sOp::Bytes(len) => Ok(s.intern_bytes(&blake2s_digest(&chunk(&args[0], len)?))),
+            Blake2sOp::Init => Ok(s.intern_bytes(&Blake2sState::default().to_bytes())),
+            Blake2sOp::Update(len) => {
+                let mut state = state(&args[0])?;
+                state.update(&chunk(&args[1], len)?);
+                Ok(s.intern_bytes(&state.to_bytes()))
+            }
+            Blake2sOp::Finalize(len) => {
+                let state = state(&args[0])?;
+                Ok(s.intern_bytes(&state.finalize(&chunk(&args[1], len)?)))
+            }
+        }
+    }
+
+    /// The digest of the bytes of `input`, along with whether it's a `Bytes` or a string of `len` bytes
+    fn synthesize_bytes<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        input: &AllocatedPtr<F>,
+        len: usize,
+    ) -> Result<(AllocatedPtr<F>, Boolean), SynthesisError> {
+        let (bits, ok) = deconstruct_bytes_or_string(
+            &mut cs.namespace(|| "input"),
+            g,
+            s,
+            not_dummy,
+            input,
+            len,
+        )?;
+        // a zero personalization is the same as none
+        let digest = blake2s(cs.namespace(|| "blake2s"), &bits, &[0; 8])?;
+        let digest = construct_bytes(&mut cs.namespace(|| "digest"), g, s, &digest)?;
+        Ok((digest, ok))
+    }
+
+    /// The new state, or the digest if `last`, along with whether `state` is a `Bytes` of `STATE_LEN` bytes and
+    /// whether `chunk` is a `Bytes` or a string of `len` bytes
+    #[allow(clippy::too_many_arguments)]
+    fn synthesize_absorb<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        state: &AllocatedPtr<F>,
+        chunk: &AllocatedPtr<F>,
+        len: usize,
+        last: bool,
+    ) -> Result<(AllocatedPtr<F>, Boolean, Boolean), SynthesisError> {
+        let (state_bits, state_ok) = deconstruct_bytes_of_len(
+            &mut cs.namespace(|| "state"),
+            g,
+            s,
+            not_dummy,
+            state,
+            STATE_LEN,
+        )?;
+        let bind_state = Boolean::and(&mut cs.namespace(|| "bind state"), &state_ok, not_dummy)?;
+        // the chunk is irrelevant to the result if the state is ill-typed
+        let (chunk_bits, chunk_ok) = deconstruct_bytes_or_string(
+            &mut cs.namespace(|| "chunk"),
+            g,
+            s,
+            &bind_state,
+            chunk,
+            len,
+        )?;
+
+        let mut h: Vec<_> = state_bits[..256]
+            .chunks(32)
+            .map(UInt32::from_bits)
+            .collect();
+        let t = absorb_blocks(
+            &mut cs.namespace(|| "absorb"),
+            &mut h,
+            &state_bits[256..],
+            &chunk_bits,
+            last,
+        )?;
+        let mut out_bits: Vec<_> = h.iter().flat_map(UInt32::into_bits).collect();
+        if !last {
+            out_bits.extend(t);
+        }
+        let out = construct_bytes(&mut cs.namespace(|| "output"), g, s, &out_bits)?;
+        Ok((out, state_ok, chunk_ok))
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for Blake2sCoprocessor<F> {
+    fn arity(&self) -> usize {
+        match self.op {
+            Blake2sOp::Bytes(_) => 1,
+            Blake2sOp::Init => 0,
+            Blake2sOp::Update(_) | Blake2sOp::Finalize(_) => 2,
+        }
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (res, type_ok) = match (self.op, args) {
+            (Blake2sOp::Init, []) => {
+                let state = s.intern_bytes(&Blake2sState::default().to_bytes());
+                return Ok(vec![g.alloc_ptr(cs, &state, s), env.clone(), cont.clone()]);
+            }
+            (Blake2sOp::Bytes(len), [input]) => {
+                let (digest, type_ok) = Self::synthesize_bytes(cs, g, s, not_dummy, input, len)?;
+                let res = AllocatedPtr::pick(
+                    cs.namespace(|| "digest or input"),
+                    &type_ok,
+                    &digest,
+                    input,
+                )?;
+                (res, type_ok)
+            }
+            (Blake2sOp::Update(len) | Blake2sOp::Finalize(len), [state, chunk]) => {
+                let last = matches!(self.op, Blake2sOp::Finalize(_));
+                let (out, state_ok, chunk_ok) =
+                    Self::synthesize_absorb(cs, g, s, not_dummy, state, chunk, len, last)?;
+                let res =
+                    AllocatedPtr::pick(cs.namespace(|| "output or chunk"), &chunk_ok, &out, chunk)?;
+                let res =
+                    AllocatedPtr::pick(cs.namespace(|| "result or state"), &state_ok, &res, state)?;
+                let type_ok = Boolean::and(cs.namespace(|| "type ok"), &state_ok, &chunk_ok)?;
+                (res, type_ok)
+            }
+            _ => return Err(SynthesisError::Unsatisfiable),
+        };
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &type_ok, cont, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for Blake2sCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        self.arity()
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match self.apply(s, args) {
+            Ok(res) => vec![res, *env, *cont],
+            Err(arg) => vec![arg, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum Blake2sCoproc<F: LurkField> {
+    Blake2s(Blake2sCoprocessor<F>),
+}
+
+/// Add the BLAKE2s operations to a `Lang`: `.lurk.blake2s.init`, and `.lurk.blake2s.bytes-<len>` and
+/// `.lurk.blake2s.finalize-<len>` for each of `lens`, as well as `.lurk.blake2s.update-<len>` for the ones that are
+/// multiples of 64. `finalize-0` is skipped.
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, Blake2sCoproc<F>>,
+    lens: &[usize],
+) {
+    let package_name: Symbol = ".lurk.blake2s".into();
+    let mut package = Package::new(package_name.clone().into());
+    let mut ops = vec![Blake2sOp::Init];
+    for &len in lens {
+        ops.push(Blake2sOp::Bytes(len));
+        if len > 0 {
+            ops.push(Blake2sOp::Finalize(len));
+            if len % BLOCK_LEN == 0 {
+                ops.push(Blake2sOp::Update(len));
+            }
+        }
+    }
+    for op in ops {
+        let coproc = Blake2sCoprocessor::new(op);
+        let name = coproc.name();
+        lang.add_coprocessor(package_name.direct_child(&name), coproc);
+        package.intern(name);
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    fn check(s: &Store<Fr>, op: Blake2sOp, args: &[Ptr]) -> Vec<Ptr> {
+        check_coprocessor(s, &Blake2sCoprocessor::new(op), args, &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_blake2s_digest() {
+        // RFC 7693, appendix B
+        assert_eq!(
+            "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982",
+            hex::encode(blake2s_digest(b"abc"))
+        );
+        assert_eq!(
+            "69217a3079908094e11121d042354a7c1f55b6482ca1a51e1b250dfd1ed0eef9",
+            hex::encode(blake2s_digest(b""))
+        );
+    }
+
+    #[test]
+    fn test_blake2s_bytes() {
+        let s = &Store::<Fr>::default();
+        let inputs: [&[u8]; 3] = [b"", b"abc", &[0xff; 70]];
+        for input in inputs {
+            let digest = s.intern_bytes(&blake2s_digest(input));
+            let output = check(s, Blake2sOp::Bytes(input.len()), &[s.intern_bytes(input)]);
+            assert_eq!(digest, output[0]);
+        }
+        let digest = s.intern_bytes(&blake2s_digest(b"abc"));
+        assert_eq!(
+            digest,
+            check(s, Blake2sOp::Bytes(3), &[s.intern_string("abc")])[0]
+        );
+    }
+
+    #[test]
+    fn test_blake2s_incremental() {
+        let s = &Store::<Fr>::default();
+        let message: Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let init = check(s, Blake2sOp::Init, &[])[0];
+        let state = check(
+            s,
+            Blake2sOp::Update(128),
+            &[init, s.intern_bytes(&message[..128])],
+        )[0];
+        let digest = check(
+            s,
+            Blake2sOp::Finalize(72),
+            &[state, s.intern_bytes(&message[128..])],
+        )[0];
+        assert_eq!(s.intern_bytes(&blake2s_digest(&message)), digest);
+
+        // a last chunk of whole blocks
+        let digest = check(
+            s,
+            Blake2sOp::Finalize(128),
+            &[init, s.intern_bytes(&message[..128])],
+        )[0];
+        assert_eq!(s.intern_bytes(&blake2s_digest(&message[..128])), digest);
+    }
+
+    #[test]
+    fn test_blake2s_errors() {
+        let s = &Store::<Fr>::default();
+        let num = s.num_u64(3);
+        let chunk = s.intern_bytes(&[0; 64]);
+        let init = s.intern_bytes(&Blake2sState::default().to_bytes());
+        for (args, offending) in [([num, chunk], num), ([init, num], num), ([num, num], num)] {
+            let output = check(s, Blake2sOp::Update(64), &args);
+            assert_eq!(
+                vec![offending, s.intern_empty_env(), s.cont_error()],
+                output
+            );
+        }
+
+        // so do states and chunks of the wrong length
+        let short_state = s.intern_bytes(&[0; 32]);
+        let short_chunk = s.intern_string("abc");
+        for (args, offending) in [
+            ([short_state, chunk], short_state),
+            ([init, short_chunk], short_chunk),
+        ] {
+            let output = check(s, Blake2sOp::Finalize(64), &args);
+            assert_eq!(
+                vec![offending, s.intern_empty_env(), s.cont_error()],
+                output
+            );
+        }
+    }
+}
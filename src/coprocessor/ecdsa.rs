@@ -0,0 +1,587 @@
+//! ECDSA signature verification over secp256k1.
+//!
+//! `.lurk.secp256k1.verify` takes a public key, a message hash and a signature, all as `Bytes`, and returns `t` if the
+//! signature is valid and `nil` otherwise. Public keys are the 64 bytes of their big-endian coordinates `x || y`, as
+//! in Ethereum, hashes are 32 bytes, e.g. from `.lurk.sha256.bytes-<len>`, and signatures are the 64 bytes of their
+//! big-endian components `r || s`. Malformed public keys and out-of-range signature components make the signature
+//! invalid. Arguments that aren't `Bytes` of the expected length return the first offending argument along with an
+//! error continuation.
+//!
+//! The circuit emulates secp256k1's fields with `EmulatedNum`s. It computes `u1 G + u2 Q` with a double-and-add
+//! ladder started from a fixed point `OFFSET` whose discrete logarithm is unknown, so that no intermediate point is
+//! the point at infinity, and subtracts `2^256 OFFSET` at the end. Additions enforce their operands to have distinct
+//! abscissas, so a prover can't exploit the exceptional cases of the affine formulas; an honest prover only hits them
+//! with negligible probability.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use num_bigint::{BigInt, BigUint};
+use num_traits::{One, Zero};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        data::{deconstruct_bytes_args, fetch_bytes_args, pick_bytes_args_result},
+        emulated::{EmulatedNum, Poly},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+const PUBLIC_KEY_LEN: usize = 64;
+const HASH_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+/// The lengths of the public key, hash and signature arguments
+const ARG_LENS: [usize; 3] = [PUBLIC_KEY_LEN, HASH_LEN, SIGNATURE_LEN];
+
+fn hex(s: &str) -> BigUint {
+    BigUint::parse_bytes(s.as_bytes(), 16).expect("valid hex")
+}
+
+/// The order of secp256k1's base field
+pub(super) static P: Lazy<BigUint> =
+    Lazy::new(|| hex("fffffffffffffffffffffffffffffffffffffffffffffffffffffffefffffc2f"));
+
+/// The order of secp256k1's group
+pub(super) static N: Lazy<BigUint> =
+    Lazy::new(|| hex("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"));
+
+pub(super) static G: Lazy<Point> = Lazy::new(|| Point {
+    x: hex("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+    y: hex("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"),
+});
+
+/// The starting point of the circuit's ladder: the first point whose abscissa is at least the SHA-256 digest of
+/// `lurk secp256k1 offset`, with the square root of `x^3 + 7` as ordinate
+pub(super) static OFFSET: Lazy<Point> = Lazy::new(|| {
+    let mut x = BigUint::from_bytes_be(&Sha256::digest(b"lurk secp256k1 offset")) % &*P;
+    loop {
+        let rhs = (x.modpow(&BigUint::from(3u32), &P) + 7u32) % &*P;
+        // `P = 3 mod 4`
+        let y = rhs.modpow(&((&*P + 1u32) >> 2), &P);
+        if (&y * &y) % &*P == rhs {
+            return Point { x, y };
+        }
+        x += 1u32;
+    }
+});
+
+/// `-2^256 OFFSET`, which takes the ladder's result back to `u1 G + u2 Q`
+pub(super) static OFFSET_CORRECTION: Lazy<Point> = Lazy::new(|| {
+    let mut point = OFFSET.clone();
+    for _ in 0..256 {
+        point = point.double();
+    }
+    point.neg()
+});
+
+pub(super) fn inv(a: &BigUint, m: &BigUint) -> BigUint {
+    a.modpow(&(m - 2u32), m)
+}
+
+pub(super) fn sub_mod(a: &BigUint, b: &BigUint, m: &BigUint) -> BigUint {
+    (a + m - b % m) % m
+}
+
+/// A point of secp256k1 other than the point at infinity, in affine coordinates
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct Point {
+    pub(super) x: BigUint,
+    pub(super) y: BigUint,
+}
+
+impl Point {
+    pub(super) fn from_coordinates(x: BigUint, y: BigUint) -> Option<Self> {
+        let p = &*P;
+        let on_curve = (&y * &y) % p == (x.modpow(&BigUint::from(3u32), p) + 7u32) % p;
+        (x < *p && y < *p && on_curve).then_some(Self { x, y })
+    }
+
+    pub(super) fn neg(&self) -> Self {
+        Self {
+            x: self.x.clone(),
+            y: sub_mod(&BigUint::zero(), &self.y, &P),
+        }
+    }
+
+    pub(super) fn double(&self) -> Self {
+        // secp256k1 has no point of order 2, so `y` isn't zero
+        let p = &*P;
+        let lambda = (3u32 * &self.x * &self.x * inv(&(2u32 * &self.y), p)) % p;
+        self.with_slope(&lambda, &self.x)
+    }
+
+    /// `self + other`, or `None` for the point at infinity
+    pub(super) fn add(&self, other: &Self) -> Option<Self> {
+        let p = &*P;
+        if self.x == other.x {
+            return (self.y == other.y).then(|| self.double());
+        }
+        let lambda = sub_mod(&other.y, &self.y, p) * inv(&sub_mod(&other.x, &self.x, p), p) % p;
+        Some(self.with_slope(&lambda, &other.x))
+    }
+
+    /// The third point on the line of slope `lambda` through `self` and the point of abscissa `other_x`, negated
+    fn with_slope(&self, lambda: &BigUint, other_x: &BigUint) -> Self {
+        let p = &*P;
+        let x = sub_mod(&(lambda * lambda), &(&self.x + other_x), p);
+        let y = sub_mod(&(lambda * sub_mod(&self.x, &x, p)), &self.y, p);
+        Self { x, y }
+    }
+
+    /// `k self`, or `None` for the point at infinity
+    pub(super) fn mul(&self, k: &BigUint) -> Option<Self> {
+        let mut acc: Option<Self> = None;
+        for i in (0..k.bits()).rev() {
+            acc = acc.map(|acc| acc.double());
+            if k.bit(i) {
+                acc = match acc {
+                    None => Some(self.clone()),
+                    Some(acc) => acc.add(self),
+                };
+            }
+        }
+        acc
+    }
+}
+
+/// Whether `signature` is a valid ECDSA signature of `hash` for `public_key`, in the formats described in the module
+/// documentation.
+///
+/// # Panics
+/// Panics if the arguments don't have the expected lengths
+pub fn verify(public_key: &[u8], hash: &[u8], signature: &[u8]) -> bool {
+    assert_eq!(public_key.len(), PUBLIC_KEY_LEN);
+    assert_eq!(hash.len(), HASH_LEN);
+    assert_eq!(signature.len(), SIGNATURE_LEN);
+    let n = &*N;
+    let Some(q) = Point::from_coordinates(
+        BigUint::from_bytes_be(&public_key[..32]),
+        BigUint::from_bytes_be(&public_key[32..]),
+    ) else {
+        return false;
+    };
+    let z = BigUint::from_bytes_be(hash);
+    let r = BigUint::from_bytes_be(&signature[..32]);
+    let s = BigUint::from_bytes_be(&signature[32..]);
+    if r.is_zero() || s.is_zero() || r >= *n || s >= *n {
+        return false;
+    }
+    let w = inv(&s, n);
+    let u1 = z * &w % n;
+    let u2 = &r * &w % n;
+    let sum = match (G.mul(&u1), q.mul(&u2)) {
+        (Some(a), Some(b)) => a.add(&b),
+        (a, b) => a.or(b),
+    };
+    sum.is_some_and(|sum| sum.x % n == r)
+}
+
+/// A point of secp256k1 in the circuit, with emulated coordinates
+#[derive(Clone)]
+pub(super) struct AllocatedPoint<F: LurkField> {
+    pub(super) x: EmulatedNum<F>,
+    pub(super) y: EmulatedNum<F>,
+}
+
+impl<F: LurkField> AllocatedPoint<F> {
+    pub(super) fn constant<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        point: &Point,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self {
+            x: EmulatedNum::constant(&mut cs.namespace(|| "x"), &point.x)?,
+            y: EmulatedNum::constant(&mut cs.namespace(|| "y"), &point.y)?,
+        })
+    }
+
+    pub(super) fn value(&self) -> Option<Point> {
+        let p = &*P;
+        Some(Point {
+            x: self.x.value()? % p,
+            y: self.y.value()? % p,
+        })
+    }
+
+    pub(super) fn pick<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        condition: &Boolean,
+        a: &Self,
+        b: &Self,
+    ) -> Result<Self, SynthesisError> {
+        Ok(Self {
+            x: EmulatedNum::pick(&mut cs.namespace(|| "x"), condition, &a.x, &b.x)?,
+            y: EmulatedNum::pick(&mut cs.namespace(|| "y"), condition, &a.y, &b.y)?,
+        })
+    }
+
+    /// The point of slope `lambda` with `self` and the point of abscissa `other_x`, as in `Point::with_slope`
+    fn with_slope<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        lambda: &EmulatedNum<F>,
+        other_x: &EmulatedNum<F>,
+    ) -> Result<Self, SynthesisError> {
+        let p = &*P;
+        let x = Poly::new()
+            .product(1, lambda, lambda)
+            .term(-1, &self.x)
+            .term(-1, other_x)
+            .alloc_congruent(&mut cs.namespace(|| "x"), p)?;
+        let y = Poly::new()
+            .product(1, lambda, &self.x)
+            .product(-1, lambda, &x)
+            .term(-1, &self.y)
+            .alloc_congruent(&mut cs.namespace(|| "y"), p)?;
+        Ok(Self { x, y })
+    }
+
+    pub(super) fn double<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<Self, SynthesisError> {
+        let p = &*P;
+        let lambda_value = self
+            .value()
+            .map(|point| (3u32 * &point.x * &point.x * inv(&(2u32 * &point.y), p)) % p);
+        let lambda = EmulatedNum::alloc(&mut cs.namespace(|| "lambda"), lambda_value)?;
+        // `2 lambda y = 3 x^2`
+        Poly::new()
+            .product(2, &lambda, &self.y)
+            .product(-3, &self.x, &self.x)
+            .enforce_zero_mod(&mut cs.namespace(|| "slope"), p)?;
+        self.with_slope(cs, &lambda, &self.x)
+    }
+
+    /// `self + other`, enforcing `self` and `other` to have distinct abscissas
+    pub(super) fn add<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let p = &*P;
+        let values = self.value().zip(other.value());
+        let dx_inv = values
+            .as_ref()
+            .map(|(a, b)| inv(&sub_mod(&b.x, &a.x, p), p));
+        let lambda_value = values
+            .as_ref()
+            .zip(dx_inv.as_ref())
+            .map(|((a, b), dx_inv)| sub_mod(&b.y, &a.y, p) * dx_inv % p);
+        let dx_inv = EmulatedNum::alloc(&mut cs.namespace(|| "dx inverse"), dx_inv)?;
+        Poly::new()
+            .product(1, &dx_inv, &other.x)
+            .product(-1, &dx_inv, &self.x)
+            .constant(-BigInt::one())
+            .enforce_zero_mod(&mut cs.namespace(|| "distinct abscissas"), p)?;
+        let lambda = EmulatedNum::alloc(&mut cs.namespace(|| "lambda"), lambda_value)?;
+        // `lambda (x2 - x1) = y2 - y1`
+        Poly::new()
+            .product(1, &lambda, &other.x)
+            .product(-1, &lambda, &self.x)
+            .term(-1, &other.y)
+            .term(1, &self.y)
+            .enforce_zero_mod(&mut cs.namespace(|| "slope"), p)?;
+        self.with_slope(cs, &lambda, &other.x)
+    }
+
+    /// `self + other` if `condition` is true, and `self` otherwise
+    pub(super) fn add_if<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        condition: &Boolean,
+        other: &Self,
+    ) -> Result<Self, SynthesisError> {
+        let sum = self.add(&mut cs.namespace(|| "sum"), other)?;
+        Self::pick(&mut cs.namespace(|| "pick"), condition, &sum, self)
+    }
+}
+
+/// The little-endian bits of a big-endian integer given as the bits of its bytes, each least significant bit first
+pub(super) fn le_bits(bytes_bits: &[Boolean]) -> Vec<Boolean> {
+    bytes_bits.chunks(8).rev().flatten().cloned().collect()
+}
+
+/// Whether a signature is valid, as in `verify`, from the bits of its arguments as returned by `deconstruct_bytes_args`
+fn synthesize_verify<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    public_key: &[Boolean],
+    hash: &[Boolean],
+    signature: &[Boolean],
+) -> Result<Boolean, SynthesisError> {
+    let (p, n) = (&*P, &*N);
+    let mut from_bits = |name: &str, bits: &[Boolean]| {
+        EmulatedNum::from_bits(&mut cs.namespace(|| name.to_string()), &le_bits(bits))
+    };
+    let qx = from_bits("qx", &public_key[..256])?;
+    let qy = from_bits("qy", &public_key[256..])?;
+    let z = from_bits("z", hash)?;
+    let r = from_bits("r", &signature[..256])?;
+    let s = from_bits("s", &signature[256..])?;
+
+    // the public key must be a point of the curve, and `r` and `s` must be in `[1, n)`
+    let is_reduced = |cs: &mut CS, name: &str, a: &EmulatedNum<F>, m: &BigUint| {
+        let mut cs = cs.namespace(|| name.to_string());
+        let reduced = a.reduce(&mut cs.namespace(|| "reduce"), m)?;
+        let is_reduced = a.alloc_equal(&mut cs.namespace(|| "is reduced"), &reduced)?;
+        Ok::<_, SynthesisError>((reduced, is_reduced))
+    };
+    let (qx, qx_ok) = is_reduced(cs, "qx reduced", &qx, p)?;
+    let (qy, qy_ok) = is_reduced(cs, "qy reduced", &qy, p)?;
+    let (r, r_reduced) = is_reduced(cs, "r reduced", &r, n)?;
+    let (s, s_reduced) = is_reduced(cs, "s reduced", &s, n)?;
+    let r_zero = r.alloc_is_zero(&mut cs.namespace(|| "r is zero"))?;
+    let s_zero = s.alloc_is_zero(&mut cs.namespace(|| "s is zero"))?;
+    let qx_squared = Poly::new()
+        .product(1, &qx, &qx)
+        .alloc_congruent(&mut cs.namespace(|| "qx squared"), p)?;
+    let curve_equation = Poly::new()
+        .product(1, &qy, &qy)
+        .product(-1, &qx_squared, &qx)
+        .constant(BigInt::from(-7))
+        .alloc_reduced(&mut cs.namespace(|| "curve equation"), p)?;
+    let on_curve = curve_equation.alloc_is_zero(&mut cs.namespace(|| "on curve"))?;
+    let mut valid = Boolean::Constant(true);
+    for (i, condition) in [
+        qx_ok,
+        qy_ok,
+        on_curve,
+        r_reduced,
+        s_reduced,
+        r_zero.not(),
+        s_zero.not(),
+    ]
+    .iter()
+    .enumerate()
+    {
+        valid = Boolean::and(cs.namespace(|| format!("valid {i}")), &valid, condition)?;
+    }
+
+    // invalid inputs are replaced by valid ones, which keeps the computation below satisfiable
+    let g = AllocatedPoint::constant(&mut cs.namespace(|| "G"), &G)?;
+    let q = AllocatedPoint::pick(
+        &mut cs.namespace(|| "Q"),
+        &valid,
+        &AllocatedPoint { x: qx, y: qy },
+        &g,
+    )?;
+    let one = EmulatedNum::constant(&mut cs.namespace(|| "one"), &BigUint::one())?;
+    let r = EmulatedNum::pick(&mut cs.namespace(|| "valid r"), &valid, &r, &one)?;
+    let s = EmulatedNum::pick(&mut cs.namespace(|| "valid s"), &valid, &s, &one)?;
+
+    let w = EmulatedNum::alloc(&mut cs.namespace(|| "w"), s.value().map(|s| inv(s, n)))?;
+    Poly::new()
+        .product(1, &s, &w)
+        .constant(-BigInt::one())
+        .enforce_zero_mod(&mut cs.namespace(|| "s w = 1"), n)?;
+    let mut scalar = |name: &str, a: &EmulatedNum<F>| {
+        let mut cs = cs.namespace(|| name.to_string());
+        let value = a.value().zip(w.value()).map(|(a, w)| a * w % n);
+        let (u, bits) = EmulatedNum::alloc_with_bits(&mut cs.namespace(|| "alloc"), value)?;
+        Poly::new()
+            .product(1, a, &w)
+            .term(-1, &u)
+            .enforce_zero_mod(&mut cs.namespace(|| "congruence"), n)?;
+        Ok::<_, SynthesisError>(bits)
+    };
+    let u1 = scalar("u1", &z)?;
+    let u2 = scalar("u2", &r)?;
+
+    let mut acc = AllocatedPoint::constant(&mut cs.namespace(|| "offset"), &OFFSET)?;
+    for i in (0..256).rev() {
+        let mut cs = cs.namespace(|| format!("ladder {i}"));
+        acc = acc.double(&mut cs.namespace(|| "double"))?;
+        acc = acc.add_if(&mut cs.namespace(|| "add G"), &u1[i], &g)?;
+        acc = acc.add_if(&mut cs.namespace(|| "add Q"), &u2[i], &q)?;
+    }
+
+    // the sum is the point at infinity iff the ladder ended at `2^256 OFFSET`, in which case `G` is added instead
+    let correction =
+        AllocatedPoint::constant(&mut cs.namespace(|| "correction"), &OFFSET_CORRECTION)?;
+    let acc_x = acc.x.reduce(&mut cs.namespace(|| "ladder x"), p)?;
+    let at_infinity = acc_x.alloc_equal(&mut cs.namespace(|| "at infinity"), &correction.x)?;
+    let acc = AllocatedPoint::pick(&mut cs.namespace(|| "finite"), &at_infinity, &g, &acc)?;
+    let sum = acc.add(&mut cs.namespace(|| "sum"), &correction)?;
+
+    let sum_x = sum.x.reduce(&mut cs.namespace(|| "sum x"), p)?;
+    let sum_x = sum_x.reduce(&mut cs.namespace(|| "sum x mod n"), n)?;
+    let matches = sum_x.alloc_equal(&mut cs.namespace(|| "matches"), &r)?;
+    let matches = Boolean::and(
+        cs.namespace(|| "finite match"),
+        &matches,
+        &at_infinity.not(),
+    )?;
+    Boolean::and(cs.namespace(|| "verified"), &valid, &matches)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EcdsaCoprocessor<F: LurkField> {
+    pub(crate) _p: PhantomData<F>,
+}
+
+impl<F: LurkField> EcdsaCoprocessor<F> {
+    pub fn new() -> Self {
+        Self {
+            _p: Default::default(),
+        }
+    }
+}
+
+impl<F: LurkField> Default for EcdsaCoprocessor<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for EcdsaCoprocessor<F> {
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (bits, arg_oks) = deconstruct_bytes_args(cs, g, s, not_dummy, args, &ARG_LENS)?;
+        let verified =
+            synthesize_verify(&mut cs.namespace(|| "verify"), &bits[0], &bits[1], &bits[2])?;
+
+        let t = g.alloc_ptr(cs, &s.intern_t(), s);
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let res = AllocatedPtr::pick(cs.namespace(|| "t or nil"), &verified, &t, &nil)?;
+        let (res, cont) = pick_bytes_args_result(cs, g, s, args, &arg_oks, res, cont)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for EcdsaCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        3
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match fetch_bytes_args(s, args, ARG_LENS) {
+            Ok([public_key, hash, signature]) => {
+                let res = if verify(&public_key, &hash, &signature) {
+                    s.intern_t()
+                } else {
+                    s.intern_nil()
+                };
+                vec![res, *env, *cont]
+            }
+            Err(arg) => vec![arg, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum EcdsaCoproc<F: LurkField> {
+    Ecdsa(EcdsaCoprocessor<F>),
+}
+
+/// Add ECDSA verification to a `Lang` as `.lurk.secp256k1.verify`
+pub fn install<F: LurkField>(state: &Rc<RefCell<State>>, lang: &mut Lang<F, EcdsaCoproc<F>>) {
+    let package_name: Symbol = ".lurk.secp256k1".into();
+    let mut package = Package::new(package_name.clone().into());
+    lang.add_coprocessor(package_name.direct_child("verify"), EcdsaCoprocessor::new());
+    package.intern("verify");
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    // A signature made with the private key `0x1234567890abcdef...` repeated, over the SHA-256 digest of `lurk`
+    const PUBLIC_KEY: &str = "bb50e2d89a4ed70663d080659fe0ad4b9bc3e06c17a227433966cb59ceee020d\
+                              ecddbf6e00192011648d13b1c00af770c0c1bb609d4d3a5c98a43772e0e18ef4";
+    const HASH: &str = "796affbb84b427cc07b8174fdffbe7e8752b198a851b9fb90df95d77b3c0b701";
+    const SIGNATURE: &str = "97855f402631f09e602e5ccadc219503f07cdd4c73b2215b5418f52a7fdbfcd9\
+                             735ab28568a4d4d8cb8afd0e4dc7ea2ce79b091ad2466261303df57bb363e035";
+
+    fn vector() -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let decode = |s| ::hex::decode(s).unwrap();
+        (decode(PUBLIC_KEY), decode(HASH), decode(SIGNATURE))
+    }
+
+    #[test]
+    fn test_verify() {
+        let (public_key, hash, signature) = vector();
+        assert_eq!(hash, Sha256::digest(b"lurk").to_vec());
+        assert!(verify(&public_key, &hash, &signature));
+
+        let mut wrong_hash = hash.clone();
+        wrong_hash[0] ^= 1;
+        assert!(!verify(&public_key, &wrong_hash, &signature));
+        let mut wrong_key = public_key.clone();
+        wrong_key[63] ^= 1;
+        assert!(!verify(&wrong_key, &hash, &signature));
+        // `s` and `n - s` are both valid
+        let s = BigUint::from_bytes_be(&signature[32..]);
+        let mut malleated = signature[..32].to_vec();
+        malleated.extend((&*N - s).to_bytes_be());
+        assert!(verify(&public_key, &hash, &malleated));
+        assert!(!verify(&public_key, &hash, &[0; 64]));
+    }
+
+    #[test]
+    fn test_curve() {
+        assert!(Point::from_coordinates(G.x.clone(), G.y.clone()).is_some());
+        assert!(Point::from_coordinates(OFFSET.x.clone(), OFFSET.y.clone()).is_some());
+        assert_eq!(None, G.mul(&N));
+        assert_eq!(Some(G.double()), G.add(&G));
+        assert_eq!(None, G.add(&G.neg()));
+    }
+
+    #[test]
+    #[ignore] // Skip expensive tests in CI for now. Do run these locally, please.
+    fn test_verify_circuit() {
+        let s = &Store::<Fr>::default();
+        let (public_key, hash, signature) = vector();
+        let mut wrong_signature = signature.clone();
+        wrong_signature[40] ^= 1;
+        let coproc = EcdsaCoprocessor::new();
+        let cont = s.cont_outermost();
+        for (signature, expected) in [(signature, s.intern_t()), (wrong_signature, s.intern_nil())]
+        {
+            let args =
+                [public_key.as_slice(), &hash, &signature].map(|bytes| s.intern_bytes(bytes));
+            let output = check_coprocessor(s, &coproc, &args, &cont);
+            assert_eq!(vec![expected, s.intern_empty_env(), cont], output);
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! Errors that Lurk programs can throw and catch.
+//!
+//! `.lurk.error.throw` takes a tag and a payload and throws the error `(tag . payload)`. `.lurk.error.catch` takes a
+//! function of no arguments, the body, and a function of one argument, the handler. It calls the body and returns its
+//! result, unless the body throws, in which case it returns the result of calling the handler with the error instead.
+//! The handler is called with the continuation of the `catch`, so errors it throws propagate to enclosing `catch`es.
+//!
+//! A `catch` pushes a continuation frame that, when the body returns, passes its result through, and `throw` looks for
+//! the nearest such frame in its continuation. Neither changes the evaluator, so the proof of an evaluation that
+//! catches an error shows the handler being called from the frame it was caught at. Errors raised by the evaluator
+//! itself, such as taking the `car` of a number, aren't thrown and can't be caught.
+//!
+//! `throw`'s circuit unrolls the continuation up to a bound, `max_depth`, that the coprocessors are created with. An
+//! error that isn't caught within `max_depth` frames is returned along with an error continuation, as is the body if
+//! `catch` isn't given two functions.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::or,
+        data::{
+            alloc_is_tag, construct_cons, construct_list, construct_tuple4, deconstruct_tuple4,
+        },
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{
+        circuit::GlobalAllocator,
+        pointers::Ptr,
+        store::{fetch_ptrs, intern_ptrs, Store},
+        trace::parent_position,
+        Tag,
+    },
+    package::Package,
+    state::{lurk_sym, State},
+    tag::{ContTag, ExprTag},
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorOp {
+    Throw,
+    Catch,
+}
+
+impl ErrorOp {
+    const ALL: [ErrorOp; 2] = [Self::Throw, Self::Catch];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Throw => "throw",
+            Self::Catch => "catch",
+        }
+    }
+}
+
+/// The tags of the continuations that have a parent, which `throw` walks through
+const FRAME_TAGS: [ContTag; 12] = [
+    ContTag::Emit,
+    ContTag::Unop,
+    ContTag::Call,
+    ContTag::Binop2,
+    ContTag::If,
+    ContTag::Call2,
+    ContTag::Let,
+    ContTag::LetRec,
+    ContTag::Binop,
+    ContTag::Cproc,
+    ContTag::Match,
+    ContTag::MatchPattern,
+];
+
+/// The identity function, whose application marks the continuation frames pushed by `catch`. Its last slot is `nil`
+/// rather than the dummy of the functions the evaluator makes, so Lurk code can't forge a frame by applying a function.
+fn catch_marker<F: LurkField>(s: &Store<F>) -> Ptr {
+    let caught = s.intern_symbol(&lurk_sym("error").direct_child("caught"));
+    intern_ptrs!(
+        s,
+        Tag::Expr(ExprTag::Fun),
+        s.list(vec![caught]),
+        caught,
+        s.intern_empty_env(),
+        s.intern_nil()
+    )
+}
+
+/// The continuation frame pushed by `catch`, which applies the marker to the result of the body and holds the handler
+/// where the environment of the arguments would be, as there are none
+fn catch_frame<F: LurkField>(s: &Store<F>, handler: Ptr, cont: Ptr) -> Ptr {
+    intern_ptrs!(
+        s,
+        Tag::Cont(ContTag::Call2),
+        catch_marker(s),
+        s.intern_nil(),
+        handler,
+        cont
+    )
+}
+
+/// The handler and the continuation of the nearest `catch` in `cont`, looking at up to `max_depth` frames
+fn find_catch<F: LurkField>(s: &Store<F>, cont: &Ptr, max_depth: usize) -> Option<(Ptr, Ptr)> {
+    let (marker, nil) = (catch_marker(s), s.intern_nil());
+    let mut cont = *cont;
+    for _ in 0..max_depth {
+        let Tag::Cont(tag) = cont.tag() else {
+            return None;
+        };
+        let position = parent_position(*tag)?;
+        let ptrs = cont.get_index4().and_then(|idx| fetch_ptrs!(s, 4, idx))?;
+        if *tag == ContTag::Call2 && ptrs[0] == marker && ptrs[1] == nil {
+            return Some((ptrs[2], ptrs[3]));
+        }
+        cont = ptrs[position];
+    }
+    None
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ErrorCoprocessor<F: LurkField> {
+    op: ErrorOp,
+    max_depth: usize,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> ErrorCoprocessor<F> {
+    /// A coprocessor whose `throw` looks for a `catch` in up to `max_depth` continuation frames
+    pub fn new(op: ErrorOp, max_depth: usize) -> Self {
+        Self {
+            op,
+            max_depth,
+            _p: Default::default(),
+        }
+    }
+
+    fn synthesize_throw<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let marker = g.alloc_ptr(cs, &catch_marker(s), s);
+        let error = construct_cons(&mut cs.namespace(|| "error"), g, s, &args[0], &args[1])?;
+
+        let mut frame = cont.clone();
+        // whether `frame` is still to be looked at
+        let mut active = Boolean::Constant(true);
+        let mut found = Boolean::Constant(false);
+        let mut handler = nil.clone();
+        let mut catch_cont = nil.clone();
+        for j in 0..self.max_depth {
+            let cs = &mut cs.namespace(|| format!("frame {j}"));
+            let mut parent_at = [(); 4].map(|_| Boolean::Constant(false));
+            let mut is_call2 = Boolean::Constant(false);
+            for tag in FRAME_TAGS {
+                let is_tag =
+                    alloc_is_tag(&mut cs.namespace(|| format!("is {tag:?}")), g, &frame, &tag)?;
+                let position = parent_position(tag).expect("frames have a parent");
+                parent_at[position] = or(
+                    cs.namespace(|| format!("parent of {tag:?}")),
+                    &parent_at[position],
+                    &is_tag,
+                )?;
+                if tag == ContTag::Call2 {
+                    is_call2 = is_tag;
+                }
+            }
+            let mut has_parent = Boolean::Constant(false);
+            for (i, at) in parent_at.iter().enumerate() {
+                has_parent = or(cs.namespace(|| format!("has parent {i}")), &has_parent, at)?;
+            }
+
+            // only continuations with a parent can be deconstructed
+            let walks = Boolean::and(cs.namespace(|| "walks"), &active, &has_parent)?;
+            let bind = Boolean::and(cs.namespace(|| "bind"), not_dummy, &walks)?;
+            let (a, b, c, d) = deconstruct_tuple4(&mut cs.namespace(|| "ptrs"), s, &bind, &frame)?;
+
+            let is_marker = a.alloc_equal(&mut cs.namespace(|| "is marker"), &marker)?;
+            let no_args = b.alloc_equal(&mut cs.namespace(|| "no args"), &nil)?;
+            let is_catch =
+                Boolean::and(cs.namespace(|| "marker and no args"), &is_marker, &no_args)?;
+            let is_catch = Boolean::and(cs.namespace(|| "marker call"), &is_catch, &is_call2)?;
+            let is_catch = Boolean::and(cs.namespace(|| "is catch"), &is_catch, &active)?;
+            handler = AllocatedPtr::pick(cs.namespace(|| "handler"), &is_catch, &c, &handler)?;
+            catch_cont =
+                AllocatedPtr::pick(cs.namespace(|| "catch cont"), &is_catch, &d, &catch_cont)?;
+            found = or(cs.namespace(|| "found"), &found, &is_catch)?;
+            active = Boolean::and(cs.namespace(|| "active"), &walks, &is_catch.not())?;
+
+            let parent = AllocatedPtr::pick(cs.namespace(|| "parent 2"), &parent_at[2], &c, &d)?;
+            let parent =
+                AllocatedPtr::pick(cs.namespace(|| "parent 1"), &parent_at[1], &b, &parent)?;
+            frame = AllocatedPtr::pick(cs.namespace(|| "parent 0"), &parent_at[0], &a, &parent)?;
+        }
+
+        // the handler is called with the quoted error
+        let quote = g.alloc_ptr(cs, &s.intern_lurk_symbol("quote"), s);
+        let quoted = construct_list(
+            &mut cs.namespace(|| "quoted"),
+            g,
+            s,
+            &[&quote, &error],
+            None,
+        )?;
+        let call_args = construct_list(&mut cs.namespace(|| "call args"), g, s, &[&quoted], None)?;
+        let dummy = g.alloc_ptr(cs, &s.dummy(), s);
+        let call = construct_tuple4(
+            &mut cs.namespace(|| "call"),
+            g,
+            s,
+            &ContTag::Call,
+            &call_args,
+            env,
+            &catch_cont,
+            &dummy,
+        )?;
+
+        let res = AllocatedPtr::pick(
+            cs.namespace(|| "handler or error"),
+            &found,
+            &handler,
+            &error,
+        )?;
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &found, &call, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+
+    fn synthesize_catch<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (body, handler) = (&args[0], &args[1]);
+        let body_is_fun =
+            alloc_is_tag(&mut cs.namespace(|| "body is fun"), g, body, &ExprTag::Fun)?;
+        let handler_is_fun = alloc_is_tag(
+            &mut cs.namespace(|| "handler is fun"),
+            g,
+            handler,
+            &ExprTag::Fun,
+        )?;
+        let valid = Boolean::and(cs.namespace(|| "valid"), &body_is_fun, &handler_is_fun)?;
+
+        let marker = g.alloc_ptr(cs, &catch_marker(s), s);
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let dummy = g.alloc_ptr(cs, &s.dummy(), s);
+        let frame = construct_tuple4(
+            &mut cs.namespace(|| "catch frame"),
+            g,
+            s,
+            &ContTag::Call2,
+            &marker,
+            &nil,
+            handler,
+            cont,
+        )?;
+        // the body is called with no arguments
+        let call = construct_tuple4(
+            &mut cs.namespace(|| "call"),
+            g,
+            s,
+            &ContTag::Call,
+            &nil,
+            env,
+            &frame,
+            &dummy,
+        )?;
+
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &valid, &call, &cont_err)?;
+        Ok(vec![body.clone(), env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for ErrorCoprocessor<F> {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        match self.op {
+            ErrorOp::Throw => self.synthesize_throw(cs, g, s, not_dummy, args, env, cont),
+            ErrorOp::Catch => self.synthesize_catch(cs, g, s, args, env, cont),
+        }
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for ErrorCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        2
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match self.op {
+            ErrorOp::Throw => {
+                let error = s.cons(args[0], args[1]);
+                match find_catch(s, cont, self.max_depth) {
+                    Some((handler, catch_cont)) => {
+                        let quoted = s.list(vec![s.intern_lurk_symbol("quote"), error]);
+                        let call = intern_ptrs!(
+                            s,
+                            Tag::Cont(ContTag::Call),
+                            s.list(vec![quoted]),
+                            *env,
+                            catch_cont,
+                            s.dummy()
+                        );
+                        vec![handler, *env, call]
+                    }
+                    None => vec![error, *env, s.cont_error()],
+                }
+            }
+            ErrorOp::Catch => {
+                let (body, handler) = (args[0], args[1]);
+                let fun = Tag::Expr(ExprTag::Fun);
+                if *body.tag() != fun || *handler.tag() != fun {
+                    return vec![body, *env, s.cont_error()];
+                }
+                let call = intern_ptrs!(
+                    s,
+                    Tag::Cont(ContTag::Call),
+                    s.intern_nil(),
+                    *env,
+                    catch_frame(s, handler, *cont),
+                    s.dummy()
+                );
+                vec![body, *env, call]
+            }
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum ErrorCoproc<F: LurkField> {
+    Error(ErrorCoprocessor<F>),
+}
+
+/// Add `.lurk.error.throw` and `.lurk.error.catch` to a `Lang`, with `throw` looking for a `catch` in up to
+/// `max_depth` continuation frames
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, ErrorCoproc<F>>,
+    max_depth: usize,
+) {
+    let package_name: Symbol = ".lurk.error".into();
+    let mut package = Package::new(package_name.clone().into());
+    for op in ErrorOp::ALL {
+        lang.add_coprocessor(
+            package_name.direct_child(op.name()),
+            ErrorCoprocessor::new(op, max_depth),
+        );
+        package.intern(op.name());
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+    use crate::lem::eval::{
+        evaluate_simple, make_cprocs_funcs_from_lang, make_eval_step_from_config, EvalConfig,
+    };
+
+    const MAX_DEPTH: usize = 8;
+
+    fn check(s: &Store<Fr>, op: ErrorOp, args: &[Ptr], cont: &Ptr) -> Vec<Ptr> {
+        check_coprocessor(s, &ErrorCoprocessor::new(op, MAX_DEPTH), args, cont)
+    }
+
+    #[test]
+    fn test_throw_finds_the_nearest_catch() {
+        let s = &Store::<Fr>::default();
+        let read = |src: &str| s.read_with_default_state(src).unwrap();
+        let emit = |parent| {
+            intern_ptrs!(
+                s,
+                Tag::Cont(ContTag::Emit),
+                parent,
+                s.intern_nil(),
+                s.dummy(),
+                s.dummy()
+            )
+        };
+        let (outer, inner) = (read("(lambda (e) 1)"), read("(lambda (e) 2)"));
+        let outer_frame = catch_frame(s, outer, s.cont_outermost());
+        let inner_frame = catch_frame(s, inner, emit(outer_frame));
+        let args = [read("oops"), read("42")];
+
+        let output = check(s, ErrorOp::Throw, &args, &emit(emit(inner_frame)));
+        assert_eq!(inner, output[0]);
+        let output = check(s, ErrorOp::Throw, &args, &emit(outer_frame));
+        assert_eq!(outer, output[0]);
+
+        // too deep to be found, or not there at all
+        let mut deep = inner_frame;
+        for _ in 0..MAX_DEPTH {
+            deep = emit(deep);
+        }
+        for cont in [deep, emit(s.cont_outermost()), s.cont_outermost()] {
+            let output = check(s, ErrorOp::Throw, &args, &cont);
+            assert_eq!(read("(oops . 42)"), output[0]);
+            assert_eq!(s.cont_error(), output[2]);
+        }
+    }
+
+    #[test]
+    fn test_catch_pushes_a_frame() {
+        let s = &Store::<Fr>::default();
+        let read = |src: &str| s.read_with_default_state(src).unwrap();
+        let (body, handler) = (read("(lambda () 1)"), read("(lambda (e) 2)"));
+        let output = check(s, ErrorOp::Catch, &[body, handler], &s.cont_outermost());
+        assert_eq!(body, output[0]);
+        let call = output[2];
+        assert_eq!(&Tag::Cont(ContTag::Call), call.tag());
+        let [_, _, frame, _] = fetch_ptrs!(s, 4, call.get_index4().unwrap()).unwrap();
+        assert_eq!(catch_frame(s, handler, s.cont_outermost()), frame);
+
+        for args in [[read("1"), handler], [body, read("nil")]] {
+            let output = check(s, ErrorOp::Catch, &args, &s.cont_outermost());
+            assert_eq!(s.cont_error(), output[2]);
+        }
+    }
+
+    #[test]
+    fn test_throw_catch_eval() {
+        let s = &Store::<Fr>::default();
+        let state = State::init_lurk_state().rccell();
+        let mut lang = Lang::<Fr, ErrorCoproc<Fr>>::new();
+        install(&state, &mut lang, MAX_DEPTH);
+        let lurk_step = make_eval_step_from_config(&EvalConfig::new_ivc(&lang));
+        let cprocs = make_cprocs_funcs_from_lang(&lang);
+        let eval = |src: &str| {
+            let expr = s.read(state.clone(), src).unwrap();
+            let (output, ..) =
+                evaluate_simple(Some((&lurk_step, &cprocs, &lang)), expr, s, 100000).unwrap();
+            output
+        };
+        let read = |src: &str| s.read(state.clone(), src).unwrap();
+        let ok = |src: &str, expected: &str| {
+            let output = eval(src);
+            assert_eq!(read(expected), output[0], "{src}");
+            assert_eq!(s.cont_terminal(), output[2], "{src}");
+        };
+
+        ok(
+            "(.lurk.error.catch (lambda () (+ 1 2)) (lambda (e) e))",
+            "3",
+        );
+        ok(
+            "(.lurk.error.catch (lambda () (+ 1 (.lurk.error.throw 'oops 42))) (lambda (e) e))",
+            "(oops . 42)",
+        );
+        // thrown from within a function
+        ok(
+            "(letrec ((f (lambda (n) (if (= n 0) (.lurk.error.throw 'done n) (f (- n 1))))))
+               (.lurk.error.catch (lambda () (f 3)) (lambda (e) (car e))))",
+            "done",
+        );
+        // the nearest catch handles the error, and its handler can throw to the next one
+        ok(
+            "(.lurk.error.catch
+               (lambda ()
+                 (.lurk.error.catch (lambda () (.lurk.error.throw 'inner 1))
+                                    (lambda (e) (.lurk.error.throw 'outer (cdr e)))))
+               (lambda (e) e))",
+            "(outer . 1)",
+        );
+        // the result of a catch that returns normally can be used like any other
+        ok(
+            "(+ 1 (.lurk.error.catch (lambda () 2) (lambda (e) 0)))",
+            "3",
+        );
+
+        let output = eval("(.lurk.error.throw 'oops 42)");
+        assert_eq!(read("(oops . 42)"), output[0]);
+        assert_eq!(s.cont_error(), output[2]);
+
+        // applying a function like the marker doesn't catch anything
+        let output = eval(
+            "((lambda (.lurk.error.caught) .lurk.error.caught) (.lurk.error.throw 'oops (lambda (e) e)))",
+        );
+        assert_eq!(s.cont_error(), output[2]);
+    }
+
+    #[test]
+    fn test_not_batchable() {
+        // `throw` and `catch` continue with `Call` continuations, which batches would drop
+        for op in ErrorOp::ALL {
+            assert!(!ErrorCoprocessor::<Fr>::new(op, MAX_DEPTH).batchable());
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Signed 64-bit integer arithmetic and comparisons, over the `I64` expression type.
+//!
+//! Each operation takes two `I64` arguments. Arithmetic wraps around on overflow and comparisons return `t` or `nil`.
+//! If an argument isn't an `I64`, the first offending argument is returned along with an error continuation.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::{alloc_equal, pick},
+        data::alloc_is_tag,
+        pointer::AllocatedPtr,
+        signed::AllocatedI64,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    tag::{ExprTag, Tag as XTag},
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum I64Op {
+    Add,
+    Sub,
+    Mul,
+    Lt,
+    Eq,
+}
+
+impl I64Op {
+    const ALL: [I64Op; 5] = [Self::Add, Self::Sub, Self::Mul, Self::Lt, Self::Eq];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::Mul => "mul",
+            Self::Lt => "lt",
+            Self::Eq => "eq",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct I64Coprocessor<F: LurkField> {
+    op: I64Op,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> I64Coprocessor<F> {
+    pub fn new(op: I64Op) -> Self {
+        Self {
+            op,
+            _p: Default::default(),
+        }
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for I64Coprocessor<F> {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (a, b) = (&args[0], &args[1]);
+        let zero = g.alloc_const(cs, F::ZERO);
+
+        let a_is_i64 = alloc_is_tag(&mut cs.namespace(|| "fst is i64"), g, a, &ExprTag::I64)?;
+        let b_is_i64 = alloc_is_tag(&mut cs.namespace(|| "snd is i64"), g, b, &ExprTag::I64)?;
+        let types_are_correct = Boolean::and(
+            &mut cs.namespace(|| "types are correct"),
+            &a_is_i64,
+            &b_is_i64,
+        )?;
+
+        // Only range check values that are known to be `I64`s, and zero otherwise
+        let check = Boolean::and(
+            &mut cs.namespace(|| "check values"),
+            &types_are_correct,
+            not_dummy,
+        )?;
+        let a_num = pick(cs.namespace(|| "fst value"), &check, a.hash(), zero)?;
+        let b_num = pick(cs.namespace(|| "snd value"), &check, b.hash(), zero)?;
+        let a_i64 = AllocatedI64::from_num(&mut cs.namespace(|| "fst i64"), a_num)?;
+        let b_i64 = AllocatedI64::from_num(&mut cs.namespace(|| "snd i64"), b_num)?;
+
+        let mut cs = cs.namespace(|| self.op.name());
+        let res = match self.op {
+            I64Op::Add | I64Op::Sub | I64Op::Mul => {
+                let res = match self.op {
+                    I64Op::Add => a_i64.add(&mut cs, &b_i64)?,
+                    I64Op::Sub => a_i64.sub(&mut cs, &b_i64)?,
+                    _ => a_i64.mul(&mut cs, &b_i64)?,
+                };
+                AllocatedPtr::alloc_tag(&mut cs, ExprTag::I64.to_field(), res.num().clone())?
+            }
+            I64Op::Lt | I64Op::Eq => {
+                let res = match self.op {
+                    I64Op::Lt => a_i64.lt(&mut cs, &b_i64)?,
+                    _ => alloc_equal(&mut cs, a_i64.num(), b_i64.num())?,
+                };
+                let t = g.alloc_ptr(&mut cs, &s.intern_t(), s);
+                let nil = g.alloc_ptr(&mut cs, &s.intern_nil(), s);
+                AllocatedPtr::pick(cs.namespace(|| "bool"), &res, &t, &nil)?
+            }
+        };
+
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or snd"), &b_is_i64, &res, b)?;
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or fst"), &a_is_i64, &res, a)?;
+
+        let cont_err = g.alloc_ptr(&mut cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(
+            cs.namespace(|| "result cont"),
+            &types_are_correct,
+            cont,
+            &cont_err,
+        )?;
+
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for I64Coprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        2
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        let Some(a) = s.fetch_i64(&args[0]) else {
+            return vec![args[0], *env, s.cont_error()];
+        };
+        let Some(b) = s.fetch_i64(&args[1]) else {
+            return vec![args[1], *env, s.cont_error()];
+        };
+        let lurk_bool = |b| if b { s.intern_t() } else { s.intern_nil() };
+        let res = match self.op {
+            I64Op::Add => s.i64(a.wrapping_add(b)),
+            I64Op::Sub => s.i64(a.wrapping_sub(b)),
+            I64Op::Mul => s.i64(a.wrapping_mul(b)),
+            I64Op::Lt => lurk_bool(a < b),
+            I64Op::Eq => lurk_bool(a == b),
+        };
+        vec![res, *env, *cont]
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum I64Coproc<F: LurkField> {
+    I64(I64Coprocessor<F>),
+}
+
+/// Add the `I64` operations to a `Lang` as `.lurk.i64.add`, `.lurk.i64.lt`, etc.
+pub fn install<F: LurkField>(state: &Rc<RefCell<State>>, lang: &mut Lang<F, I64Coproc<F>>) {
+    let package_name: Symbol = ".lurk.i64".into();
+    let mut package = Package::new(package_name.clone().into());
+    for op in I64Op::ALL {
+        lang.add_coprocessor(
+            package_name.direct_child(op.name()),
+            I64Coprocessor::new(op),
+        );
+        package.intern(op.name());
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    fn check(s: &Store<Fr>, op: I64Op, a: Ptr, b: Ptr) {
+        check_coprocessor(s, &I64Coprocessor::new(op), &[a, b], &s.cont_outermost());
+    }
+
+    #[test]
+    fn test_i64_ops() {
+        let s = &Store::<Fr>::default();
+        let values = [i64::MIN, -7, -1, 0, 3, i64::MAX];
+        for op in I64Op::ALL {
+            for a in values {
+                for b in values {
+                    check(s, op, s.i64(a), s.i64(b));
+                }
+            }
+        }
+
+        let coproc = I64Coprocessor::new(I64Op::Add);
+        let env = s.intern_empty_env();
+        let cont = s.cont_outermost();
+        assert_eq!(
+            vec![s.i64(i64::MIN), env, cont],
+            coproc.evaluate(s, &[s.i64(i64::MAX), s.i64(1)], &env, &cont)
+        );
+        let lt = I64Coprocessor::new(I64Op::Lt);
+        assert_eq!(
+            vec![s.intern_t(), env, cont],
+            lt.evaluate(s, &[s.i64(-1), s.i64(0)], &env, &cont)
+        );
+    }
+
+    #[test]
+    fn test_i64_type_errors() {
+        let s = &Store::<Fr>::default();
+        let num = s.num_u64(1);
+        let u64 = s.u64(u64::MAX);
+        let one = s.i64(1);
+        for op in I64Op::ALL {
+            check(s, op, num, one);
+            check(s, op, one, u64);
+            check(s, op, num, u64);
+        }
+
+        let coproc = I64Coprocessor::new(I64Op::Mul);
+        let env = s.intern_empty_env();
+        let cont = s.cont_outermost();
+        assert_eq!(
+            vec![u64, env, s.cont_error()],
+            coproc.evaluate(s, &[one, u64], &env, &cont)
+        );
+    }
+}
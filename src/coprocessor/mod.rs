@@ -8,9 +8,23 @@ use crate::{
     lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
 };
 
+pub mod aead;
+pub mod alist;
+pub mod batch;
+pub mod bignum;
+pub mod blake2s;
 pub mod circom;
+pub mod ecdsa;
+pub mod error;
 pub mod gadgets;
+pub mod int;
+pub mod poseidon;
+pub mod rand;
+pub mod ratio;
+pub mod registry;
+pub mod schnorr;
 pub mod sha256;
+pub mod string;
 pub mod trie;
 
 /// `Coprocessor` is a trait that represents a generalized interface for coprocessors.
@@ -34,6 +48,12 @@ pub trait Coprocessor<F: LurkField>: Clone + Debug + Sync + Send + CoCircuit<F>
         false
     }
 
+    /// Returns true if calls can be batched with a `BatchCoprocessor`: `evaluate` and `synthesize` must return the
+    /// environment they're given, along with either their continuation or the error one.
+    fn batchable(&self) -> bool {
+        false
+    }
+
     /// Function for internal plumbing. Reimplementing is not recommended
     fn evaluate_internal(&self, s: &Store<F>, ptrs: &[Ptr]) -> Vec<Ptr> {
         let arity = self.arity();
@@ -108,7 +128,7 @@ pub trait CoCircuit<F: LurkField>: Send + Sync + Clone {
 
 #[cfg(test)]
 pub(crate) mod test {
-    use bellpepper_core::num::AllocatedNum;
+    use bellpepper_core::{num::AllocatedNum, test_cs::TestConstraintSystem};
     use serde::{Deserialize, Serialize};
 
     use super::*;
@@ -117,6 +137,50 @@ pub(crate) mod test {
     use crate::tag::{ExprTag, Tag};
     use std::marker::PhantomData;
 
+    /// Evaluates `coproc` on `args` with the empty env and `cont`, and checks that its circuit is satisfied by the same
+    /// pointers and outputs the same ones. Returns the output of the evaluation.
+    pub(crate) fn check_coprocessor<F: LurkField, C: Coprocessor<F>>(
+        s: &Store<F>,
+        coproc: &C,
+        args: &[Ptr],
+        cont: &Ptr,
+    ) -> Vec<Ptr> {
+        let env = s.intern_empty_env();
+        let expected = coproc.evaluate(s, args, &env, cont);
+
+        let cs = &mut TestConstraintSystem::<F>::new();
+        let g = GlobalAllocator::default();
+        let alloc = |cs: &mut TestConstraintSystem<F>, name: &str, ptr: &Ptr| {
+            let z_ptr = s.hash_ptr(ptr);
+            AllocatedPtr::alloc_infallible(&mut cs.namespace(|| name.to_string()), || z_ptr)
+        };
+        let a_args = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| alloc(cs, &format!("arg {i}"), arg))
+            .collect::<Vec<_>>();
+        let a_env = alloc(cs, "env", &env);
+        let a_cont = alloc(cs, "cont", cont);
+        let output = coproc
+            .synthesize(
+                cs,
+                &g,
+                s,
+                &Boolean::Constant(true),
+                &a_args,
+                &a_env,
+                &a_cont,
+            )
+            .unwrap();
+
+        assert!(cs.is_satisfied());
+        assert_eq!(expected.len(), output.len());
+        for (expected, output) in expected.iter().zip(output) {
+            assert_eq!(Some(s.hash_ptr(expected)), output.get_value());
+        }
+        expected
+    }
+
     /// A dumb Coprocessor for testing.
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub(crate) struct DumbCoprocessor<F> {
@@ -208,6 +272,10 @@ pub(crate) mod test {
             true
         }
 
+        fn batchable(&self) -> bool {
+            true
+        }
+
         fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
             let (LEMTag::Expr(ExprTag::Num), RawPtr::Atom(a)) = args[0].parts() else {
                 return vec![args[0], *env, s.cont_error()];
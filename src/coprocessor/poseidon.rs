@@ -0,0 +1,589 @@
+//! Poseidon sponges.
+//!
+//! `.lurk.poseidon.sponge-<pattern>` runs neptune's SAFE sponge, of rate 4, over its arguments. The pattern is a
+//! sequence of absorptions and squeezes, e.g. `a2-s1-a3-s2` absorbs two elements, squeezes one, absorbs three more
+//! and squeezes two, so that a transcript can derive challenges between its messages. The coprocessor takes as many
+//! `Num`s as it absorbs, in order, and returns the list of the `Num`s it squeezes. As in SAFE, the pattern is hashed
+//! into the sponge's initial state, so that different patterns never share outputs. Arguments that aren't `Num`s
+//! return the first of them along with an error continuation.
+//!
+//! An `l<n>` in the pattern absorbs a single argument, a list of at most `n` `Num`s, as its length followed by its
+//! elements padded with zeros. Messages of any length up to `n` thus share one coprocessor, and so one circuit.
+//! Arguments that aren't such lists are returned along with an error continuation.
+//!
+//! `.lurk.poseidon.duplex-<pattern>` is the duplex version of a sponge: it takes the state of a previous call as its
+//! first argument, absorbs it before the pattern and squeezes the next state after it, returning `(state . squeezed)`.
+//! A program can thus keep absorbing messages and squeezing challenges over as many calls as it needs, each call
+//! depending on all the previous ones. The first call can start from any `Num`, such as `0` or a domain separator.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use generic_array::typenum::U4;
+use lurk_macros::Coproc;
+use neptune::{
+    circuit2::Elt,
+    poseidon::PoseidonConstants,
+    sponge::{
+        api::{IOPattern, SpongeAPI, SpongeOp as IOOp},
+        circuit::SpongeCircuit,
+        vanilla::{Mode, Sponge, SpongeTrait},
+    },
+    Strength,
+};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::{alloc_lc, or, pick, popcount_lc},
+        data::{alloc_is_tag, car_cdr, construct_cons, construct_list},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{
+        circuit::GlobalAllocator,
+        pointers::{Ptr, RawPtr},
+        store::Store,
+        tag::Tag,
+    },
+    package::Package,
+    state::State,
+    tag::ExprTag,
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpongeOp {
+    /// Absorbs as many `Num` arguments
+    Absorb(u32),
+    /// Absorbs one argument, a list of at most as many `Num`s, as its length followed by its zero-padded elements
+    AbsorbList(u32),
+    /// Squeezes as many elements
+    Squeeze(u32),
+}
+
+impl SpongeOp {
+    fn io_op(self) -> IOOp {
+        match self {
+            Self::Absorb(n) => IOOp::Absorb(n),
+            Self::AbsorbList(n) => IOOp::Absorb(n + 1),
+            Self::Squeeze(n) => IOOp::Squeeze(n),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpongeCoprocessor<F: LurkField> {
+    pattern: Vec<SpongeOp>,
+    #[serde(default)]
+    duplex: bool,
+    #[serde(skip)]
+    constants: OnceCell<PoseidonConstants<F, U4>>,
+}
+
+impl<F: LurkField> SpongeCoprocessor<F> {
+    /// # Panics
+    /// Panics if the pattern is empty or has an operation on no elements
+    pub fn new(pattern: Vec<SpongeOp>) -> Self {
+        assert!(!pattern.is_empty(), "empty sponge pattern");
+        assert!(
+            pattern.iter().all(|op| !matches!(
+                op,
+                SpongeOp::Absorb(0) | SpongeOp::AbsorbList(0) | SpongeOp::Squeeze(0)
+            )),
+            "sponge operations must have elements"
+        );
+        Self {
+            pattern,
+            duplex: false,
+            constants: OnceCell::new(),
+        }
+    }
+
+    /// The duplex version of the sponge of `pattern`, which absorbs the state of a previous call before the pattern
+    /// and squeezes the next state after it
+    ///
+    /// # Panics
+    /// Panics if the pattern is empty or has an operation on no elements
+    pub fn duplex(pattern: Vec<SpongeOp>) -> Self {
+        Self {
+            duplex: true,
+            ..Self::new(pattern)
+        }
+    }
+
+    /// The name of the coprocessor in the `.lurk.poseidon` package
+    pub fn name(&self) -> String {
+        let ops = self
+            .pattern
+            .iter()
+            .map(|op| match op {
+                SpongeOp::Absorb(n) => format!("a{n}"),
+                SpongeOp::AbsorbList(n) => format!("l{n}"),
+                SpongeOp::Squeeze(n) => format!("s{n}"),
+            })
+            .collect::<Vec<_>>();
+        let kind = if self.duplex { "duplex" } else { "sponge" };
+        format!("{kind}-{}", ops.join("-"))
+    }
+
+    fn constants(&self) -> &PoseidonConstants<F, U4> {
+        self.constants
+            .get_or_init(|| Sponge::<F, U4>::api_constants(Strength::Standard))
+    }
+
+    /// The operations of the underlying sponge, including the absorption and squeeze of the duplex state
+    fn io_ops(&self) -> Vec<IOOp> {
+        let mut ops = Vec::with_capacity(self.pattern.len() + 2);
+        if self.duplex {
+            ops.push(IOOp::Absorb(1));
+        }
+        ops.extend(self.pattern.iter().map(|op| op.io_op()));
+        if self.duplex {
+            ops.push(IOOp::Squeeze(1));
+        }
+        ops
+    }
+
+    /// The arguments of the coprocessor: `None` for a `Num` and `Some(n)` for a list of at most `n` `Num`s
+    fn args(&self) -> Vec<Option<u32>> {
+        let mut args = vec![];
+        if self.duplex {
+            args.push(None);
+        }
+        for op in &self.pattern {
+            match op {
+                SpongeOp::Absorb(n) => args.extend((0..*n).map(|_| None)),
+                SpongeOp::AbsorbList(n) => args.push(Some(*n)),
+                SpongeOp::Squeeze(_) => (),
+            }
+        }
+        args
+    }
+
+    /// The number of elements the underlying sponge absorbs
+    pub fn absorbed(&self) -> usize {
+        self.io_ops()
+            .iter()
+            .map(|op| match op {
+                IOOp::Absorb(n) => *n as usize,
+                IOOp::Squeeze(_) => 0,
+            })
+            .sum()
+    }
+
+    /// The squeezed elements, given the absorbed ones. Lists are absorbed as their length followed by their
+    /// zero-padded elements, and the last squeezed element of a duplex sponge is its next state.
+    pub fn sponge(&self, input: &[F]) -> Vec<F> {
+        assert_eq!(input.len(), self.absorbed());
+        let ops = self.io_ops();
+        let mut sponge = Sponge::new_with_constants(self.constants(), Mode::Simplex);
+        let acc = &mut ();
+        SpongeAPI::start(&mut sponge, IOPattern(ops.clone()), None, acc);
+        let mut input = input;
+        let mut output = vec![];
+        for op in &ops {
+            match op {
+                IOOp::Absorb(n) => {
+                    let (absorbed, rest) = input.split_at(*n as usize);
+                    SpongeAPI::absorb(&mut sponge, *n, absorbed, acc);
+                    input = rest;
+                }
+                IOOp::Squeeze(n) => output.extend(SpongeAPI::squeeze(&mut sponge, *n, acc)),
+            }
+        }
+        SpongeAPI::finish(&mut sponge, acc).expect("sponge pattern followed");
+        output
+    }
+
+    /// In-circuit counterpart of `sponge`
+    pub(crate) fn synthesize_sponge<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        input: &[Elt<F>],
+    ) -> Result<Vec<Elt<F>>, SynthesisError> {
+        let ops = self.io_ops();
+        let acc = cs;
+        let mut sponge = SpongeCircuit::new_with_constants(self.constants(), Mode::Simplex);
+        SpongeAPI::start(&mut sponge, IOPattern(ops.clone()), None, acc);
+        let mut input = input;
+        let mut output = vec![];
+        for op in &ops {
+            match op {
+                IOOp::Absorb(n) => {
+                    let (absorbed, rest) = input.split_at(*n as usize);
+                    SpongeAPI::absorb(&mut sponge, *n, absorbed, acc);
+                    input = rest;
+                }
+                IOOp::Squeeze(n) => output.extend(SpongeAPI::squeeze(&mut sponge, *n, acc)),
+            }
+        }
+        SpongeAPI::finish(&mut sponge, acc).map_err(|_| SynthesisError::Unsatisfiable)?;
+        Ok(output)
+    }
+
+    /// The elements absorbed for `args`, or the first argument of the wrong type
+    fn absorbed_elements(&self, s: &Store<F>, args: &[Ptr]) -> Result<Vec<F>, Ptr> {
+        let num = |arg: &Ptr| match arg.parts() {
+            (Tag::Expr(ExprTag::Num), RawPtr::Atom(idx)) => Some(*s.expect_f(*idx)),
+            _ => None,
+        };
+        let mut input = Vec::with_capacity(self.absorbed());
+        for (max, arg) in self.args().into_iter().zip(args) {
+            match max {
+                None => input.push(num(arg).ok_or(*arg)?),
+                Some(max) => {
+                    let max = max as usize;
+                    let elts = match s.fetch_list(arg) {
+                        Some((elts, None)) if elts.len() <= max => elts,
+                        _ => return Err(*arg),
+                    };
+                    input.push(F::from_u64(elts.len() as u64));
+                    for elt in &elts {
+                        input.push(num(elt).ok_or(*arg)?);
+                    }
+                    input.resize(input.len() + max - elts.len(), F::ZERO);
+                }
+            }
+        }
+        Ok(input)
+    }
+
+    /// Absorbs `arg`, a list of at most `max` `Num`s, into `input` as its length followed by its zero-padded elements.
+    /// Returns whether `arg` is such a list.
+    fn synthesize_list<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        arg: &AllocatedPtr<F>,
+        max: u32,
+        input: &mut Vec<Elt<F>>,
+    ) -> Result<Boolean, SynthesisError> {
+        let is_cons = alloc_is_tag(&mut cs.namespace(|| "is cons"), g, arg, &ExprTag::Cons)?;
+        let is_nil = alloc_is_tag(&mut cs.namespace(|| "is nil"), g, arg, &ExprTag::Nil)?;
+        let type_ok = or(cs.namespace(|| "is list"), &is_cons, &is_nil)?;
+        let bind = Boolean::and(cs.namespace(|| "bind"), &type_ok, not_dummy)?;
+
+        let zero = g.alloc_const_cloned(cs, F::ZERO);
+        let mut actives = Vec::with_capacity(max as usize);
+        let mut elts = Vec::with_capacity(max as usize);
+        // whether the elements seen so far are `Num`s
+        let mut elts_ok = Boolean::Constant(true);
+        let mut cell = arg.clone();
+        for i in 0..max {
+            let mut cs = cs.namespace(|| format!("element {i}"));
+            // cells are only deconstructed while they're conses, after which `cell` keeps the end of the list
+            let cell_is_cons = alloc_is_tag(
+                &mut cs.namespace(|| "cell is cons"),
+                g,
+                &cell,
+                &ExprTag::Cons,
+            )?;
+            let active = Boolean::and(cs.namespace(|| "active"), &cell_is_cons, &bind)?;
+            let (elt, next, _) = car_cdr(&mut cs.namespace(|| "car_cdr"), g, s, &active, &cell)?;
+            cell = AllocatedPtr::pick(cs.namespace(|| "next cell"), &active, &next, &cell)?;
+
+            let elt_is_num = alloc_is_tag(
+                &mut cs.namespace(|| "element is num"),
+                g,
+                &elt,
+                &ExprTag::Num,
+            )?;
+            let bad_elt = Boolean::and(cs.namespace(|| "bad element"), &active, &elt_is_num.not())?;
+            elts_ok = Boolean::and(cs.namespace(|| "elements ok"), &elts_ok, &bad_elt.not())?;
+            elts.push(pick(
+                cs.namespace(|| "padded element"),
+                &active,
+                elt.hash(),
+                &zero,
+            )?);
+            actives.push(active);
+        }
+        // longer lists and improper ones don't end with `nil` after `max` elements
+        let no_more_elts = alloc_is_tag(
+            &mut cs.namespace(|| "no more elements"),
+            g,
+            &cell,
+            &ExprTag::Nil,
+        )?;
+
+        let len = actives
+            .iter()
+            .map(|active| active.get_value().map(u64::from))
+            .sum::<Option<u64>>()
+            .map(F::from_u64);
+        let len = alloc_lc(
+            &mut cs.namespace(|| "length"),
+            len,
+            popcount_lc::<F, CS>(&actives),
+        )?;
+        input.push(Elt::Allocated(len));
+        input.extend(elts.into_iter().map(Elt::Allocated));
+
+        let list_ok = Boolean::and(cs.namespace(|| "list ok"), &type_ok, &elts_ok)?;
+        Boolean::and(cs.namespace(|| "list ends"), &list_ok, &no_more_elts)
+    }
+
+    /// The result of the coprocessor given the squeezed elements
+    fn output(&self, s: &Store<F>, squeezed: Vec<F>) -> Ptr {
+        let mut squeezed = squeezed.into_iter().map(|f| s.num(f)).collect::<Vec<_>>();
+        if self.duplex {
+            let state = squeezed.pop().expect("duplex sponges squeeze their state");
+            s.cons(state, s.list(squeezed))
+        } else {
+            s.list(squeezed)
+        }
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for SpongeCoprocessor<F> {
+    fn arity(&self) -> usize {
+        self.args().len()
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let mut input = Vec::with_capacity(self.absorbed());
+        let mut arg_oks = Vec::with_capacity(args.len());
+        let mut types_ok = Boolean::Constant(true);
+        for (i, (max, arg)) in self.args().into_iter().zip(args).enumerate() {
+            let arg_ok = match max {
+                None => {
+                    input.push(Elt::Allocated(arg.hash().clone()));
+                    alloc_is_tag(
+                        &mut cs.namespace(|| format!("arg {i} type")),
+                        g,
+                        arg,
+                        &ExprTag::Num,
+                    )?
+                }
+                Some(max) => Self::synthesize_list(
+                    &mut cs.namespace(|| format!("arg {i} list")),
+                    g,
+                    s,
+                    not_dummy,
+                    arg,
+                    max,
+                    &mut input,
+                )?,
+            };
+            types_ok = Boolean::and(cs.namespace(|| format!("types {i}")), &types_ok, &arg_ok)?;
+            arg_oks.push(arg_ok);
+        }
+
+        let output = self.synthesize_sponge(&mut cs.namespace(|| "sponge"), &input)?;
+        let num_tag = g.alloc_tag_cloned(cs, &ExprTag::Num);
+        let mut output = output
+            .iter()
+            .enumerate()
+            .map(|(i, elt)| {
+                let num =
+                    elt.ensure_allocated(&mut cs.namespace(|| format!("output {i}")), true)?;
+                Ok(AllocatedPtr::from_parts(num_tag.clone(), num))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+        let state = if self.duplex { output.pop() } else { None };
+        let output = construct_list(
+            &mut cs.namespace(|| "output list"),
+            g,
+            s,
+            &output.iter().collect::<Vec<_>>(),
+            None,
+        )?;
+        let output = match state {
+            Some(state) => construct_cons(
+                &mut cs.namespace(|| "state and output"),
+                g,
+                s,
+                &state,
+                &output,
+            )?,
+            None => output,
+        };
+
+        let mut res = output;
+        for (i, (arg, arg_ok)) in args.iter().zip(&arg_oks).enumerate().rev() {
+            res = AllocatedPtr::pick(
+                cs.namespace(|| format!("result or arg {i}")),
+                arg_ok,
+                &res,
+                arg,
+            )?;
+        }
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &types_ok, cont, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for SpongeCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        self.arity()
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match self.absorbed_elements(s, args) {
+            Ok(input) => vec![self.output(s, self.sponge(&input)), *env, *cont],
+            Err(arg) => vec![arg, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum PoseidonCoproc<F: LurkField> {
+    Sponge(SpongeCoprocessor<F>),
+}
+
+/// Add each of the `sponges` to a `Lang`, as `.lurk.poseidon.sponge-<pattern>` or `.lurk.poseidon.duplex-<pattern>`
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, PoseidonCoproc<F>>,
+    sponges: Vec<SpongeCoprocessor<F>>,
+) {
+    let package_name: Symbol = ".lurk.poseidon".into();
+    let mut package = Package::new(package_name.clone().into());
+    for coproc in sponges {
+        let name = coproc.name();
+        lang.add_coprocessor(package_name.direct_child(&name), coproc);
+        package.intern(name);
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    fn check(s: &Store<Fr>, coproc: &SpongeCoprocessor<Fr>, args: &[Ptr]) -> Vec<Ptr> {
+        check_coprocessor(s, coproc, args, &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_sponge() {
+        let s = &Store::<Fr>::default();
+        let nums = (1..=5u64).map(|n| s.num(Fr::from(n))).collect::<Vec<_>>();
+
+        let interleaved = SpongeCoprocessor::new(vec![
+            SpongeOp::Absorb(2),
+            SpongeOp::Squeeze(1),
+            SpongeOp::Absorb(3),
+            SpongeOp::Squeeze(2),
+        ]);
+        assert_eq!("sponge-a2-s1-a3-s2", interleaved.name());
+        assert_eq!(5, interleaved.arity());
+        let output = check(s, &interleaved, &nums);
+        let (squeezed, None) = s.fetch_list(&output[0]).unwrap() else {
+            panic!("improper list")
+        };
+        assert_eq!(3, squeezed.len());
+        assert_eq!(s.cont_outermost(), output[2]);
+
+        // the pattern separates the domains of sponges absorbing the same elements
+        let simplex = SpongeCoprocessor::new(vec![SpongeOp::Absorb(5), SpongeOp::Squeeze(3)]);
+        assert_ne!(output[0], check(s, &simplex, &nums)[0]);
+        let inputs = (1..=5u64).map(Fr::from).collect::<Vec<_>>();
+        assert_eq!(interleaved.sponge(&inputs), interleaved.sponge(&inputs));
+        assert_ne!(
+            interleaved.sponge(&inputs)[..1],
+            simplex.sponge(&inputs)[..1]
+        );
+    }
+
+    #[test]
+    fn test_sponge_type_error() {
+        let s = &Store::<Fr>::default();
+        let coproc = SpongeCoprocessor::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(1)]);
+        let args = [s.num(Fr::from(1)), s.intern_nil()];
+        let output = check(s, &coproc, &args);
+        assert_eq!(vec![args[1], s.intern_empty_env(), s.cont_error()], output);
+    }
+
+    #[test]
+    fn test_sponge_list() {
+        let s = &Store::<Fr>::default();
+        let coproc = SpongeCoprocessor::new(vec![SpongeOp::AbsorbList(3), SpongeOp::Squeeze(1)]);
+        assert_eq!("sponge-l3-s1", coproc.name());
+        assert_eq!(1, coproc.arity());
+        assert_eq!(4, coproc.absorbed());
+
+        let list = |n: u64| s.list((1..=n).map(|i| s.num(Fr::from(i))).collect());
+        let outputs = (0..=3)
+            .map(|n| check(s, &coproc, &[list(n)])[0])
+            .collect::<Vec<_>>();
+        // the length is absorbed, so lists padded with zeros don't collide with shorter ones
+        let padded = s.list(vec![s.num(Fr::from(1)), s.num(Fr::from(0))]);
+        assert_ne!(outputs[1], check(s, &coproc, &[padded])[0]);
+        let expected = coproc.sponge(&[2u64, 1, 2, 0].map(Fr::from));
+        assert_eq!(s.list(vec![s.num(expected[0])]), outputs[2]);
+
+        // lists that are too long, improper or of other values are errors
+        let bad = [
+            list(4),
+            s.cons(s.num(Fr::from(1)), s.num(Fr::from(2))),
+            s.list(vec![s.intern_nil()]),
+            s.num(Fr::from(1)),
+        ];
+        for arg in bad {
+            let output = check(s, &coproc, &[arg]);
+            assert_eq!(vec![arg, s.intern_empty_env(), s.cont_error()], output);
+        }
+    }
+
+    #[test]
+    fn test_duplex() {
+        let s = &Store::<Fr>::default();
+        let coproc = SpongeCoprocessor::duplex(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(2)]);
+        assert_eq!("duplex-a1-s2", coproc.name());
+        assert_eq!(2, coproc.arity());
+
+        let call = |state: Ptr, msg: u64| {
+            let output = check(s, &coproc, &[state, s.num(Fr::from(msg))]);
+            let (state, squeezed) = s.car_cdr(&output[0]).unwrap();
+            let (squeezed, None) = s.fetch_list(&squeezed).unwrap() else {
+                panic!("improper list")
+            };
+            assert_eq!(2, squeezed.len());
+            (state, squeezed)
+        };
+        let (state, first) = call(s.num(Fr::from(0)), 1);
+        let (_, second) = call(state, 1);
+        // the same message squeezes other challenges once absorbed into the state
+        assert_ne!(first, second);
+        let (other_state, _) = call(s.num(Fr::from(0)), 2);
+        assert_ne!(state, other_state);
+
+        // duplex sponges are separated from the simplex ones absorbing the same elements
+        let simplex = SpongeCoprocessor::new(vec![SpongeOp::Absorb(2), SpongeOp::Squeeze(3)]);
+        let inputs = [0u64, 1].map(Fr::from);
+        assert_ne!(coproc.sponge(&inputs), simplex.sponge(&inputs));
+    }
+}
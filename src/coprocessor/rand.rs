@@ -0,0 +1,203 @@
+//! Deterministic pseudo-random numbers.
+//!
+//! `.lurk.rand.rand` takes a seed, any expression, and a `Num` index `n`, and returns the `n`th `Num` of the stream the
+//! seed determines: the element squeezed by a Poseidon sponge of pattern `a3-s1` absorbing the seed's tag, the seed's
+//! hash and `n`. The stream is reproducible and its elements are provable, so a program seeded with a commitment, e.g.
+//! `(rand (commit secret) 0)`, samples values that can't be chosen after the commitment is published and that anyone
+//! can recompute once it's opened. Values in a range are sampled by converting to `u64` and taking the remainder.
+//!
+//! An index that isn't a `Num` is returned along with an error continuation.
+
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use neptune::circuit2::Elt;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{data::alloc_is_tag, pointer::AllocatedPtr},
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{
+        circuit::GlobalAllocator,
+        pointers::{Ptr, RawPtr},
+        store::Store,
+        tag::Tag,
+    },
+    package::Package,
+    state::State,
+    tag::ExprTag,
+    Symbol,
+};
+
+use super::{
+    poseidon::{SpongeCoprocessor, SpongeOp},
+    CoCircuit, Coprocessor,
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RandCoprocessor<F: LurkField> {
+    sponge: SpongeCoprocessor<F>,
+}
+
+impl<F: LurkField> Default for RandCoprocessor<F> {
+    fn default() -> Self {
+        Self {
+            sponge: SpongeCoprocessor::new(vec![SpongeOp::Absorb(3), SpongeOp::Squeeze(1)]),
+        }
+    }
+}
+
+impl<F: LurkField> RandCoprocessor<F> {
+    /// The `n`th element of the stream of `seed`
+    pub fn rand(&self, s: &Store<F>, seed: &Ptr, n: F) -> F {
+        let seed = s.hash_ptr(seed);
+        self.sponge.sponge(&[seed.tag_field(), *seed.value(), n])[0]
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for RandCoprocessor<F> {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        _not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (seed, n) = (&args[0], &args[1]);
+        let n_is_num = alloc_is_tag(&mut cs.namespace(|| "n is num"), g, n, &ExprTag::Num)?;
+
+        let input = [seed.tag(), seed.hash(), n.hash()].map(|x| Elt::Allocated(x.clone()));
+        let output = self
+            .sponge
+            .synthesize_sponge(&mut cs.namespace(|| "sponge"), &input)?;
+        let num = output[0].ensure_allocated(&mut cs.namespace(|| "output"), true)?;
+        let num = AllocatedPtr::from_parts(g.alloc_tag_cloned(cs, &ExprTag::Num), num);
+
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or n"), &n_is_num, &num, n)?;
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &n_is_num, cont, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for RandCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        2
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        let (seed, n) = (&args[0], &args[1]);
+        let (Tag::Expr(ExprTag::Num), RawPtr::Atom(idx)) = n.parts() else {
+            return vec![*n, *env, s.cont_error()];
+        };
+        vec![s.num(self.rand(s, seed, *s.expect_f(*idx))), *env, *cont]
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum RandCoproc<F: LurkField> {
+    Rand(RandCoprocessor<F>),
+}
+
+/// Add `.lurk.rand.rand` to a `Lang`
+pub fn install<F: LurkField>(state: &Rc<RefCell<State>>, lang: &mut Lang<F, RandCoproc<F>>) {
+    let package_name: Symbol = ".lurk.rand".into();
+    let mut package = Package::new(package_name.clone().into());
+    lang.add_coprocessor(
+        package_name.direct_child("rand"),
+        RandCoprocessor::default(),
+    );
+    package.intern("rand".to_string());
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+    use crate::lem::eval::{
+        evaluate_simple, make_cprocs_funcs_from_lang, make_eval_step_from_config, EvalConfig,
+    };
+
+    fn check(s: &Store<Fr>, args: &[Ptr]) -> Vec<Ptr> {
+        check_coprocessor(s, &RandCoprocessor::default(), args, &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_rand() {
+        let s = &Store::<Fr>::default();
+        let seed = s.commit(s.num_u64(42));
+        let [zero, one] = [0, 1].map(|n| s.num_u64(n));
+
+        let r0 = check(s, &[seed, zero]);
+        assert_eq!(s.cont_outermost(), r0[2]);
+        assert_eq!(r0, check(s, &[seed, zero]));
+        assert_ne!(r0[0], check(s, &[seed, one])[0]);
+        assert_ne!(r0[0], check(s, &[s.commit(s.num_u64(43)), zero])[0]);
+        // the seed's tag is absorbed, so a commitment and a number of the same hash seed different streams
+        let num_seed = s.num(*s.hash_ptr(&seed).value());
+        assert_ne!(r0[0], check(s, &[num_seed, zero])[0]);
+
+        // the element squeezed by the sponge absorbing the seed's tag, the seed's hash and `n`
+        let z_seed = s.hash_ptr(&seed);
+        let sponge = SpongeCoprocessor::new(vec![SpongeOp::Absorb(3), SpongeOp::Squeeze(1)]);
+        let squeezed = sponge.sponge(&[z_seed.tag_field(), *z_seed.value(), Fr::from(0)]);
+        assert_eq!(s.num(squeezed[0]), r0[0]);
+    }
+
+    #[test]
+    fn test_rand_type_error() {
+        let s = &Store::<Fr>::default();
+        let args = [s.num_u64(1), s.intern_nil()];
+        let output = check(s, &args);
+        assert_eq!(vec![args[1], s.intern_empty_env(), s.cont_error()], output);
+    }
+
+    #[test]
+    fn test_rand_eval() {
+        let s = &Store::<Fr>::default();
+        let state = State::init_lurk_state().rccell();
+        let mut lang = Lang::<Fr, RandCoproc<Fr>>::new();
+        install(&state, &mut lang);
+        let lurk_step = make_eval_step_from_config(&EvalConfig::new_ivc(&lang));
+        let cprocs = make_cprocs_funcs_from_lang(&lang);
+        let eval = |src: &str| {
+            let expr = s.read(state.clone(), src).unwrap();
+            let (output, ..) =
+                evaluate_simple(Some((&lurk_step, &cprocs, &lang)), expr, s, 100000).unwrap();
+            assert_eq!(s.cont_terminal(), output[2], "{src}");
+            output[0]
+        };
+
+        let seed = s.commit(s.num_u64(42));
+        let expected = RandCoprocessor::default().rand(s, &seed, Fr::from(3));
+        assert_eq!(s.num(expected), eval("(.lurk.rand.rand (commit 42) 3)"));
+        // sampling a die roll
+        let roll = eval("(% (u64 (.lurk.rand.rand (commit 42) 3)) 6u64)");
+        assert_eq!(s.u64(expected.to_u64_unchecked() % 6), roll);
+    }
+}
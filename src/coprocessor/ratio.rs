@@ -0,0 +1,637 @@
+//! Exact rational arithmetic over the `Ratio` expression type.
+//!
+//! `.lurk.ratio.new` builds the ratio `n/d` out of two `I64`s. The remaining operations take two ratios: `add`,
+//! `sub`, `mul` and `div` return ratios, whereas `lt` and `eq` return `t` or `nil`. Type errors return the first
+//! offending argument along with an error continuation, and so does a zero denominator or divisor.
+//!
+//! Evaluation is exact for numerators and denominators of any size. The circuits only support ratios whose
+//! numerator and denominator magnitudes are below `2^64`: under that bound, every identity checked below involves
+//! integers far smaller than the field's modulus, so it holds in the field iff it holds over the integers. Proving
+//! an operation over larger ratios, or whose result is larger, is unsatisfiable.
+
+use bellpepper_core::{
+    boolean::{AllocatedBit, Boolean},
+    num::AllocatedNum,
+    ConstraintSystem, LinearCombination, SynthesisError,
+};
+use lurk_macros::Coproc;
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::{
+            alloc_equal, alloc_is_zero, alloc_lc, boolean_to_num, decompose_le,
+            enforce_implication, enforce_implication_lc_zero, implies_equal, implies_equal_const,
+            mul, pick,
+        },
+        data::{alloc_is_tag, construct_cons, construct_tuple3},
+        pointer::AllocatedPtr,
+        signed::AllocatedI64,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    tag::ExprTag,
+    Ratio, Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RatioOp {
+    New,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Eq,
+}
+
+impl RatioOp {
+    const ALL: [RatioOp; 7] = [
+        Self::New,
+        Self::Add,
+        Self::Sub,
+        Self::Mul,
+        Self::Div,
+        Self::Lt,
+        Self::Eq,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::New => "new",
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::Mul => "mul",
+            Self::Div => "div",
+            Self::Lt => "lt",
+            Self::Eq => "eq",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RatioCoprocessor<F: LurkField> {
+    op: RatioOp,
+    _p: PhantomData<F>,
+}
+
+/// The sign, numerator magnitude and denominator of a ratio that fits the circuits
+type SmallParts = (bool, u64, u64);
+
+fn small_parts(r: &Ratio) -> Option<SmallParts> {
+    let (negative, numer, denom) = r.to_parts();
+    Some((negative, numer.to_u64()?, denom.to_u64()?))
+}
+
+/// Witnesses `(x, y)` such that `x * numer - y * denom = 1`, with `1 <= x <= denom`, if `numer` and `denom` are
+/// nonzero and coprime. Returns `(0, 0)` otherwise.
+fn bezout(numer: u64, denom: u64) -> (u64, u64) {
+    if numer == 0 || denom == 0 {
+        return (0, 0);
+    }
+    // extended Euclid, tracking the coefficients of `numer` modulo `denom`
+    let (mut r0, mut r1) = (i128::from(denom), i128::from(numer % denom));
+    let (mut t0, mut t1) = (0i128, 1i128);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (t0, t1) = (t1, t0 - q * t1);
+    }
+    if r0 != 1 {
+        return (0, 0);
+    }
+    let x = match t0.rem_euclid(i128::from(denom)) as u64 {
+        0 => denom,
+        x => x,
+    };
+    let y = (u128::from(x) * u128::from(numer) - 1) / u128::from(denom);
+    (x, y as u64)
+}
+
+/// The witness behind an allocated pointer, if it has a value
+fn witness_ptr<F: LurkField>(s: &Store<F>, ptr: &AllocatedPtr<F>) -> Option<Ptr> {
+    Some(s.to_ptr(&ptr.get_value()?))
+}
+
+/// The list of limbs of `n < 2^64`, as in `Store::intern_biguint`: `nil` if `n` is zero and `(n)` otherwise
+fn alloc_limbs<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    n: &AllocatedNum<F>,
+    n_is_zero: &Boolean,
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    let u64_tag = g.alloc_tag_cloned(cs, &ExprTag::U64);
+    let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+    let limb = AllocatedPtr::from_parts(u64_tag, n.clone());
+    let list = construct_cons(&mut cs.namespace(|| "cons"), g, s, &limb, &nil)?;
+    AllocatedPtr::pick(cs.namespace(|| "limbs"), n_is_zero, &nil, &list)
+}
+
+/// The integer represented by an `AllocatedI64`, as a field element
+fn signed_i64<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    i: &AllocatedI64<F>,
+) -> Result<AllocatedNum<F>, SynthesisError> {
+    let two_64 = F::from_u64(u64::MAX) + F::ONE;
+    let value = i
+        .num()
+        .get_value()
+        .zip(i.sign().get_value())
+        .map(|(n, negative)| if negative { n - two_64 } else { n });
+    let lc = LinearCombination::zero() + i.num().get_variable() - &i.sign().lc(CS::one(), two_64);
+    alloc_lc(cs, value, lc)
+}
+
+/// A ratio with range-checked parts, along with the pointer it's interned as
+struct AllocatedRatio<F: LurkField> {
+    numer: AllocatedNum<F>,
+    numer_is_zero: Boolean,
+    denom: AllocatedNum<F>,
+    denom_is_zero: Boolean,
+    sign: AllocatedNum<F>,
+    /// The numerator with its sign, i.e. `numer * (1 - 2 * sign)`
+    signed: AllocatedNum<F>,
+    ptr: AllocatedPtr<F>,
+}
+
+impl<F: LurkField> AllocatedRatio<F> {
+    fn alloc<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        parts: Option<SmallParts>,
+    ) -> Result<Self, SynthesisError> {
+        let (negative, numer, denom) = parts.unwrap_or_default();
+        let sign = AllocatedBit::alloc(cs.namespace(|| "sign bit"), Some(negative))?;
+        let sign = boolean_to_num(cs.namespace(|| "sign"), &Boolean::Is(sign))?;
+        let numer = AllocatedNum::alloc_infallible(cs.namespace(|| "numer"), || F::from_u64(numer));
+        let denom = AllocatedNum::alloc_infallible(cs.namespace(|| "denom"), || F::from_u64(denom));
+        decompose_le(&mut cs.namespace(|| "numer range"), &numer, 64)?;
+        decompose_le(&mut cs.namespace(|| "denom range"), &denom, 64)?;
+
+        let sign_numer = mul(cs.namespace(|| "sign * numer"), &sign, &numer)?;
+        let signed = alloc_lc(
+            &mut cs.namespace(|| "signed numer"),
+            numer
+                .get_value()
+                .zip(sign_numer.get_value())
+                .map(|(n, sn)| n - sn.double()),
+            LinearCombination::zero() + numer.get_variable()
+                - (F::from_u64(2), sign_numer.get_variable()),
+        )?;
+
+        let numer_is_zero = alloc_is_zero(cs.namespace(|| "numer is zero"), &numer)?;
+        let denom_is_zero = alloc_is_zero(cs.namespace(|| "denom is zero"), &denom)?;
+        let u64_tag = g.alloc_tag_cloned(cs, &ExprTag::U64);
+        let sign_ptr = AllocatedPtr::from_parts(u64_tag, sign.clone());
+        let numer_ptr = alloc_limbs(
+            &mut cs.namespace(|| "numer limbs"),
+            g,
+            s,
+            &numer,
+            &numer_is_zero,
+        )?;
+        let denom_ptr = alloc_limbs(
+            &mut cs.namespace(|| "denom limbs"),
+            g,
+            s,
+            &denom,
+            &denom_is_zero,
+        )?;
+        let ptr = construct_tuple3(
+            &mut cs.namespace(|| "ratio"),
+            g,
+            s,
+            &ExprTag::Ratio,
+            &sign_ptr,
+            &numer_ptr,
+            &denom_ptr,
+        )?;
+
+        Ok(Self {
+            numer,
+            numer_is_zero,
+            denom,
+            denom_is_zero,
+            sign,
+            signed,
+            ptr,
+        })
+    }
+
+    /// Enforces, if `premise` holds, that the ratio is normalized like a `Ratio`: the denominator is nonzero and
+    /// coprime to the numerator, and zero is `0/1` with a positive sign.
+    fn implies_normalized<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        premise: &Boolean,
+    ) -> Result<(), SynthesisError> {
+        enforce_implication(
+            cs.namespace(|| "nonzero denom"),
+            premise,
+            &self.denom_is_zero.not(),
+        );
+
+        let is_zero = Boolean::and(
+            &mut cs.namespace(|| "is zero"),
+            premise,
+            &self.numer_is_zero,
+        )?;
+        implies_equal_const(
+            &mut cs.namespace(|| "zero denom"),
+            &is_zero,
+            &self.denom,
+            F::ONE,
+        );
+        implies_equal_const(
+            &mut cs.namespace(|| "zero sign"),
+            &is_zero,
+            &self.sign,
+            F::ZERO,
+        );
+
+        // a nonzero numerator is coprime to the denominator iff `x * numer - y * denom = 1` for some integers, which
+        // can be chosen below `2^64` so that the equation can't wrap around the field
+        let is_nonzero = Boolean::and(
+            &mut cs.namespace(|| "is nonzero"),
+            premise,
+            &self.numer_is_zero.not(),
+        )?;
+        let (x, y) = self
+            .numer
+            .get_value()
+            .zip(self.denom.get_value())
+            .and_then(|(n, d)| Some(bezout(n.to_u64()?, d.to_u64()?)))
+            .unwrap_or_default();
+        let x = AllocatedNum::alloc_infallible(cs.namespace(|| "x"), || F::from_u64(x));
+        let y = AllocatedNum::alloc_infallible(cs.namespace(|| "y"), || F::from_u64(y));
+        decompose_le(&mut cs.namespace(|| "x range"), &x, 64)?;
+        decompose_le(&mut cs.namespace(|| "y range"), &y, 64)?;
+        let x_numer = mul(cs.namespace(|| "x * numer"), &x, &self.numer)?;
+        let y_denom = mul(cs.namespace(|| "y * denom"), &y, &self.denom)?;
+        enforce_implication_lc_zero(cs.namespace(|| "coprime"), &is_nonzero, |lc| {
+            lc + x_numer.get_variable() - y_denom.get_variable() - CS::one()
+        });
+        Ok(())
+    }
+}
+
+impl<F: LurkField> RatioCoprocessor<F> {
+    pub fn new(op: RatioOp) -> Self {
+        Self {
+            op,
+            _p: Default::default(),
+        }
+    }
+
+    /// The result of the operation, or the offending argument on errors
+    fn apply(&self, s: &Store<F>, args: &[Ptr]) -> Result<Ptr, Ptr> {
+        let (a, b) = (&args[0], &args[1]);
+        if self.op == RatioOp::New {
+            let n = s.fetch_i64(a).ok_or(*a)?;
+            let d = s.fetch_i64(b).ok_or(*b)?;
+            let r = Ratio::new(n.into(), d.into()).ok_or(*b)?;
+            return Ok(s.intern_ratio(&r));
+        }
+        let x = s.fetch_ratio(a).ok_or(*a)?;
+        let y = s.fetch_ratio(b).ok_or(*b)?;
+        let lurk_bool = |b| if b { s.intern_t() } else { s.intern_nil() };
+        Ok(match self.op {
+            RatioOp::Add => s.intern_ratio(&x.add(&y)),
+            RatioOp::Sub => s.intern_ratio(&x.sub(&y)),
+            RatioOp::Mul => s.intern_ratio(&x.mul(&y)),
+            RatioOp::Div => s.intern_ratio(&x.div(&y).ok_or(*b)?),
+            RatioOp::Lt => lurk_bool(x < y),
+            RatioOp::Eq => lurk_bool(x == y),
+            RatioOp::New => unreachable!(),
+        })
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for RatioCoprocessor<F> {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (a, b) = (&args[0], &args[1]);
+        let arg_tag = if self.op == RatioOp::New {
+            ExprTag::I64
+        } else {
+            ExprTag::Ratio
+        };
+        let a_ok = alloc_is_tag(&mut cs.namespace(|| "fst type"), g, a, &arg_tag)?;
+        let b_ok = alloc_is_tag(&mut cs.namespace(|| "snd type"), g, b, &arg_tag)?;
+        let types_are_correct =
+            Boolean::and(&mut cs.namespace(|| "types are correct"), &a_ok, &b_ok)?;
+        // the arguments are only bound to their witnesses when they have the right types
+        let bind = Boolean::and(&mut cs.namespace(|| "bind"), &types_are_correct, not_dummy)?;
+
+        // the native result drives the witnesses, which are zeros whenever they aren't bound
+        let native = match (witness_ptr(s, a), witness_ptr(s, b), bind.get_value()) {
+            (Some(a), Some(b), Some(true)) => self.apply(s, &[a, b]).ok(),
+            _ => None,
+        };
+
+        let mut cs = cs.namespace(|| self.op.name());
+        let (res, zero_divisor) = if self.op == RatioOp::New {
+            let zero = g.alloc_const(&mut cs, F::ZERO);
+            let x_num = pick(cs.namespace(|| "fst value"), &bind, a.hash(), zero)?;
+            let y_num = pick(cs.namespace(|| "snd value"), &bind, b.hash(), zero)?;
+            let x = AllocatedI64::from_num(&mut cs.namespace(|| "fst i64"), x_num)?;
+            let y = AllocatedI64::from_num(&mut cs.namespace(|| "snd i64"), y_num)?;
+            let y_is_zero = alloc_is_zero(cs.namespace(|| "snd is zero"), y.num())?;
+            let valid = Boolean::and(&mut cs.namespace(|| "valid"), &bind, &y_is_zero.not())?;
+
+            let c = AllocatedRatio::alloc(
+                &mut cs.namespace(|| "result"),
+                g,
+                s,
+                native.and_then(|ptr| small_parts(&s.fetch_ratio(&ptr)?)),
+            )?;
+            c.implies_normalized(&mut cs.namespace(|| "normalized"), &valid)?;
+
+            // `c = x / y`, with `x` and `y` as signed integers
+            let x = signed_i64(&mut cs.namespace(|| "signed fst"), &x)?;
+            let y = signed_i64(&mut cs.namespace(|| "signed snd"), &y)?;
+            let lhs = mul(cs.namespace(|| "lhs"), &c.signed, &y)?;
+            let rhs = mul(cs.namespace(|| "rhs"), &x, &c.denom)?;
+            implies_equal(&mut cs.namespace(|| "quotient"), &valid, &lhs, &rhs);
+            (c.ptr, y_is_zero)
+        } else {
+            let parts = |ptr: &AllocatedPtr<F>| {
+                bind.get_value()
+                    .filter(|b| *b)
+                    .and_then(|_| small_parts(&s.fetch_ratio(&witness_ptr(s, ptr)?)?))
+            };
+            let x = AllocatedRatio::alloc(&mut cs.namespace(|| "fst ratio"), g, s, parts(a))?;
+            let y = AllocatedRatio::alloc(&mut cs.namespace(|| "snd ratio"), g, s, parts(b))?;
+            implies_equal(
+                &mut cs.namespace(|| "fst hash"),
+                &bind,
+                x.ptr.hash(),
+                a.hash(),
+            );
+            implies_equal(
+                &mut cs.namespace(|| "snd hash"),
+                &bind,
+                y.ptr.hash(),
+                b.hash(),
+            );
+            // the identities below and the equality of hashes assume normalized arguments
+            x.implies_normalized(&mut cs.namespace(|| "fst normalized"), &bind)?;
+            y.implies_normalized(&mut cs.namespace(|| "snd normalized"), &bind)?;
+
+            let zero_divisor = if self.op == RatioOp::Div {
+                y.numer_is_zero.clone()
+            } else {
+                Boolean::Constant(false)
+            };
+            let valid = Boolean::and(&mut cs.namespace(|| "valid"), &bind, &zero_divisor.not())?;
+
+            let res = match self.op {
+                RatioOp::Lt | RatioOp::Eq => {
+                    let res = if self.op == RatioOp::Lt {
+                        // `x < y` iff `y.signed * x.denom - x.signed * y.denom - 1 >= 0`, and shifting that
+                        // difference by `2^129` keeps it in `[0, 2^130)`
+                        let t1 = mul(cs.namespace(|| "snd * fst denom"), &y.signed, &x.denom)?;
+                        let t2 = mul(cs.namespace(|| "fst * snd denom"), &x.signed, &y.denom)?;
+                        let two_64 = F::from_u64(u64::MAX) + F::ONE;
+                        let shift = two_64 * two_64 * F::from_u64(2) - F::ONE;
+                        let value = t1
+                            .get_value()
+                            .zip(t2.get_value())
+                            .map(|(t1, t2)| t1 - t2 + shift);
+                        let lc = LinearCombination::zero() + t1.get_variable() - t2.get_variable()
+                            + (shift, CS::one());
+                        let diff = alloc_lc(&mut cs.namespace(|| "shifted difference"), value, lc)?;
+                        let bits = decompose_le(
+                            &mut cs.namespace(|| "shifted difference bits"),
+                            &diff,
+                            130,
+                        )?;
+                        bits[129].clone()
+                    } else {
+                        // normalized ratios are equal iff their hashes are
+                        alloc_equal(&mut cs.namespace(|| "equal"), a.hash(), b.hash())?
+                    };
+                    let t = g.alloc_ptr(&mut cs, &s.intern_t(), s);
+                    let nil = g.alloc_ptr(&mut cs, &s.intern_nil(), s);
+                    AllocatedPtr::pick(cs.namespace(|| "bool"), &res, &t, &nil)?
+                }
+                _ => {
+                    let c = AllocatedRatio::alloc(
+                        &mut cs.namespace(|| "result"),
+                        g,
+                        s,
+                        native.and_then(|ptr| small_parts(&s.fetch_ratio(&ptr)?)),
+                    )?;
+                    c.implies_normalized(&mut cs.namespace(|| "normalized"), &valid)?;
+
+                    // cross-multiplied identities, e.g. `c.signed * x.denom * y.denom =
+                    // (x.signed * y.denom + y.signed * x.denom) * c.denom` for `c = x + y`
+                    let (lhs, rhs) = match self.op {
+                        RatioOp::Add | RatioOp::Sub => {
+                            let dd = mul(cs.namespace(|| "denoms"), &x.denom, &y.denom)?;
+                            let lhs = mul(cs.namespace(|| "lhs"), &c.signed, &dd)?;
+                            let t1 = mul(cs.namespace(|| "fst * snd denom"), &x.signed, &y.denom)?;
+                            let t2 = mul(cs.namespace(|| "snd * fst denom"), &y.signed, &x.denom)?;
+                            let t = if self.op == RatioOp::Add {
+                                t1.add(&mut cs.namespace(|| "numer"), &t2)?
+                            } else {
+                                let value = t1.get_value().zip(t2.get_value()).map(|(a, b)| a - b);
+                                let lc = LinearCombination::zero() + t1.get_variable()
+                                    - t2.get_variable();
+                                alloc_lc(&mut cs.namespace(|| "numer"), value, lc)?
+                            };
+                            (lhs, mul(cs.namespace(|| "rhs"), &t, &c.denom)?)
+                        }
+                        RatioOp::Mul => {
+                            let dd = mul(cs.namespace(|| "denoms"), &x.denom, &y.denom)?;
+                            let lhs = mul(cs.namespace(|| "lhs"), &c.signed, &dd)?;
+                            let t = mul(cs.namespace(|| "numers"), &x.signed, &y.signed)?;
+                            (lhs, mul(cs.namespace(|| "rhs"), &t, &c.denom)?)
+                        }
+                        _ => {
+                            let t =
+                                mul(cs.namespace(|| "result * fst denom"), &c.signed, &x.denom)?;
+                            let lhs = mul(cs.namespace(|| "lhs"), &t, &y.signed)?;
+                            let t = mul(cs.namespace(|| "fst * snd denom"), &x.signed, &y.denom)?;
+                            (lhs, mul(cs.namespace(|| "rhs"), &t, &c.denom)?)
+                        }
+                    };
+                    implies_equal(&mut cs.namespace(|| "identity"), &valid, &lhs, &rhs);
+                    c.ptr
+                }
+            };
+            (res, zero_divisor)
+        };
+
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or divisor"), &zero_divisor, b, &res)?;
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or snd"), &b_ok, &res, b)?;
+        let res = AllocatedPtr::pick(cs.namespace(|| "result or fst"), &a_ok, &res, a)?;
+
+        let succeeded = Boolean::and(
+            &mut cs.namespace(|| "succeeded"),
+            &types_are_correct,
+            &zero_divisor.not(),
+        )?;
+        let cont_err = g.alloc_ptr(&mut cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &succeeded, cont, &cont_err)?;
+
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for RatioCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        2
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match self.apply(s, args) {
+            Ok(res) => vec![res, *env, *cont],
+            Err(arg) => vec![arg, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum RatioCoproc<F: LurkField> {
+    Ratio(RatioCoprocessor<F>),
+}
+
+/// Add the `Ratio` operations to a `Lang` as `.lurk.ratio.new`, `.lurk.ratio.add`, etc.
+pub fn install<F: LurkField>(state: &Rc<RefCell<State>>, lang: &mut Lang<F, RatioCoproc<F>>) {
+    let package_name: Symbol = ".lurk.ratio".into();
+    let mut package = Package::new(package_name.clone().into());
+    for op in RatioOp::ALL {
+        lang.add_coprocessor(
+            package_name.direct_child(op.name()),
+            RatioCoprocessor::new(op),
+        );
+        package.intern(op.name());
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+    use num_bigint::BigInt;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    fn check(s: &Store<Fr>, op: RatioOp, a: Ptr, b: Ptr) {
+        check_coprocessor(s, &RatioCoprocessor::new(op), &[a, b], &s.cont_outermost());
+    }
+
+    fn ratio(s: &Store<Fr>, n: i64, d: i64) -> Ptr {
+        s.intern_ratio(&Ratio::new(n.into(), d.into()).unwrap())
+    }
+
+    #[test]
+    fn test_bezout() {
+        for (numer, denom) in [(1, 1), (5, 1), (3, 7), (7, 3), (u64::MAX, u64::MAX - 1)] {
+            let (x, y) = bezout(numer, denom);
+            assert!(1 <= x && x <= denom);
+            assert_eq!(
+                1,
+                i128::from(x) * i128::from(numer) - i128::from(y) * i128::from(denom)
+            );
+        }
+        assert_eq!((0, 0), bezout(2, 4));
+    }
+
+    #[test]
+    fn test_ratio_ops() {
+        let s = &Store::<Fr>::default();
+        let values = [
+            ratio(s, 0, 1),
+            ratio(s, 1, 1),
+            ratio(s, -1, 3),
+            ratio(s, 5, 2),
+            ratio(s, i32::MIN.into(), i32::MAX.into()),
+        ];
+        for op in RatioOp::ALL.into_iter().filter(|op| *op != RatioOp::New) {
+            for a in values {
+                for b in values {
+                    check(s, op, a, b);
+                }
+            }
+        }
+        let ints = [i64::MIN, -6, -1, 0, 3, i64::MAX];
+        for a in ints {
+            for b in ints {
+                check(s, RatioOp::New, s.i64(a), s.i64(b));
+            }
+        }
+
+        let coproc = RatioCoprocessor::new(RatioOp::New);
+        let env = s.intern_empty_env();
+        let cont = s.cont_outermost();
+        assert_eq!(
+            vec![ratio(s, -1, 2), env, cont],
+            coproc.evaluate(s, &[s.i64(3), s.i64(-6)], &env, &cont)
+        );
+        let div = RatioCoprocessor::new(RatioOp::Div);
+        let zero = ratio(s, 0, 1);
+        assert_eq!(
+            vec![zero, env, s.cont_error()],
+            div.evaluate(s, &[ratio(s, 1, 2), zero], &env, &cont)
+        );
+    }
+
+    #[test]
+    fn test_ratio_type_errors() {
+        let s = &Store::<Fr>::default();
+        let num = s.num_u64(1);
+        let one = ratio(s, 1, 1);
+        for op in RatioOp::ALL {
+            check(s, op, num, one);
+            check(s, op, one, num);
+            check(s, op, num, num);
+        }
+    }
+
+    #[test]
+    fn test_ratio_no_wraparound() {
+        let s = &Store::<Fr>::default();
+        let big = s.intern_ratio(&Ratio::from_integer(BigInt::from(u64::MAX)));
+        let coproc = RatioCoprocessor::new(RatioOp::Mul);
+        let env = s.intern_empty_env();
+        let cont = s.cont_outermost();
+        let res = coproc.evaluate(s, &[big, big], &env, &cont)[0];
+        expect_test::expect!["<RATIO 340282366920938463426481119284349108225/1>"]
+            .assert_eq(&res.fmt_to_string_simple(s));
+    }
+}
@@ -0,0 +1,510 @@
+//! Coprocessors registered at runtime.
+//!
+//! `Coprocessor`s are usually closed into an enum, such as the ones derived with `Coproc`, which fixes the set of
+//! coprocessors a crate can use when it's compiled. A `CoprocessorRegistry` instead holds `DynCoprocessor` trait
+//! objects, so that plugins can extend the language without a new enum. Its `Lang` orders them by symbol, whatever the
+//! order of registration, so that every application registering the same coprocessors assigns them the same indices,
+//! hence the same NIVC circuits.
+//!
+//! `CoCircuit::synthesize` is generic over the constraint system, which trait objects can't be. A `DynCoprocessor`
+//! synthesizes into a `DynConstraintSystem` instead: a `ConstraintSystem` forwarding to whichever one the circuit is
+//! being synthesized into, so that the usual gadgets apply.
+
+use bellpepper::util_cs::witness_cs::WitnessCS;
+use bellpepper_core::{
+    boolean::Boolean, ConstraintSystem, LinearCombination, SynthesisError, Variable,
+};
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+use thiserror::Error;
+
+use crate::{
+    circuit::gadgets::pointer::AllocatedPtr,
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+/// The object-safe subset of `ConstraintSystem` a `DynConstraintSystem` forwards to
+trait ErasedCS<F: LurkField>: Send {
+    fn alloc(
+        &mut self,
+        name: Box<dyn FnOnce() -> String + '_>,
+        value: Box<dyn FnOnce() -> Result<F, SynthesisError> + '_>,
+    ) -> Result<Variable, SynthesisError>;
+
+    fn alloc_input(
+        &mut self,
+        name: Box<dyn FnOnce() -> String + '_>,
+        value: Box<dyn FnOnce() -> Result<F, SynthesisError> + '_>,
+    ) -> Result<Variable, SynthesisError>;
+
+    fn enforce(
+        &mut self,
+        name: Box<dyn FnOnce() -> String + '_>,
+        a: LinearCombination<F>,
+        b: LinearCombination<F>,
+        c: LinearCombination<F>,
+    );
+
+    fn push_namespace(&mut self, name: Box<dyn FnOnce() -> String + '_>);
+
+    fn pop_namespace(&mut self);
+
+    fn is_witness_generator(&self) -> bool;
+
+    fn extend_inputs(&mut self, new_inputs: &[F]);
+
+    fn extend_aux(&mut self, new_aux: &[F]);
+
+    fn inputs_slice(&self) -> &[F];
+
+    fn aux_slice(&self) -> &[F];
+}
+
+impl<F: LurkField, CS: ConstraintSystem<F>> ErasedCS<F> for CS {
+    fn alloc(
+        &mut self,
+        name: Box<dyn FnOnce() -> String + '_>,
+        value: Box<dyn FnOnce() -> Result<F, SynthesisError> + '_>,
+    ) -> Result<Variable, SynthesisError> {
+        ConstraintSystem::alloc(self, name, value)
+    }
+
+    fn alloc_input(
+        &mut self,
+        name: Box<dyn FnOnce() -> String + '_>,
+        value: Box<dyn FnOnce() -> Result<F, SynthesisError> + '_>,
+    ) -> Result<Variable, SynthesisError> {
+        ConstraintSystem::alloc_input(self, name, value)
+    }
+
+    fn enforce(
+        &mut self,
+        name: Box<dyn FnOnce() -> String + '_>,
+        a: LinearCombination<F>,
+        b: LinearCombination<F>,
+        c: LinearCombination<F>,
+    ) {
+        ConstraintSystem::enforce(self, name, |_| a, |_| b, |_| c)
+    }
+
+    fn push_namespace(&mut self, name: Box<dyn FnOnce() -> String + '_>) {
+        // only roots can push namespaces
+        self.get_root().push_namespace(name)
+    }
+
+    fn pop_namespace(&mut self) {
+        self.get_root().pop_namespace()
+    }
+
+    fn is_witness_generator(&self) -> bool {
+        ConstraintSystem::is_witness_generator(self)
+    }
+
+    fn extend_inputs(&mut self, new_inputs: &[F]) {
+        ConstraintSystem::extend_inputs(self, new_inputs)
+    }
+
+    fn extend_aux(&mut self, new_aux: &[F]) {
+        ConstraintSystem::extend_aux(self, new_aux)
+    }
+
+    fn inputs_slice(&self) -> &[F] {
+        ConstraintSystem::inputs_slice(self)
+    }
+
+    fn aux_slice(&self) -> &[F] {
+        ConstraintSystem::aux_slice(self)
+    }
+}
+
+/// A `ConstraintSystem` forwarding to another one, whose type is erased. `DynConstraintSystem::new` forwards to a new
+/// `WitnessCS`, which only records the witness.
+pub struct DynConstraintSystem<'a, F: LurkField>(Erased<'a, F>);
+
+enum Erased<'a, F: LurkField> {
+    Borrowed(&'a mut dyn ErasedCS<F>),
+    Owned(Box<WitnessCS<F>>),
+}
+
+impl<'a, F: LurkField> DynConstraintSystem<'a, F> {
+    fn inner(&self) -> &dyn ErasedCS<F> {
+        match &self.0 {
+            Erased::Borrowed(cs) => &**cs,
+            Erased::Owned(cs) => &**cs,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn ErasedCS<F> {
+        match &mut self.0 {
+            Erased::Borrowed(cs) => &mut **cs,
+            Erased::Owned(cs) => &mut **cs,
+        }
+    }
+}
+
+impl<'a, F: LurkField> ConstraintSystem<F> for DynConstraintSystem<'a, F> {
+    type Root = Self;
+
+    fn new() -> Self {
+        Self(Erased::Owned(Box::new(WitnessCS::new())))
+    }
+
+    fn alloc<Fo, A, AR>(&mut self, annotation: A, f: Fo) -> Result<Variable, SynthesisError>
+    where
+        Fo: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.inner_mut()
+            .alloc(Box::new(|| annotation().into()), Box::new(f))
+    }
+
+    fn alloc_input<Fo, A, AR>(&mut self, annotation: A, f: Fo) -> Result<Variable, SynthesisError>
+    where
+        Fo: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.inner_mut()
+            .alloc_input(Box::new(|| annotation().into()), Box::new(f))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LB: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LC: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+    {
+        self.inner_mut().enforce(
+            Box::new(|| annotation().into()),
+            a(LinearCombination::zero()),
+            b(LinearCombination::zero()),
+            c(LinearCombination::zero()),
+        )
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.inner_mut()
+            .push_namespace(Box::new(|| name_fn().into()))
+    }
+
+    fn pop_namespace(&mut self) {
+        self.inner_mut().pop_namespace()
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn is_witness_generator(&self) -> bool {
+        self.inner().is_witness_generator()
+    }
+
+    fn extend_inputs(&mut self, new_inputs: &[F]) {
+        self.inner_mut().extend_inputs(new_inputs)
+    }
+
+    fn extend_aux(&mut self, new_aux: &[F]) {
+        self.inner_mut().extend_aux(new_aux)
+    }
+
+    fn inputs_slice(&self) -> &[F] {
+        self.inner().inputs_slice()
+    }
+
+    fn aux_slice(&self) -> &[F] {
+        self.inner().aux_slice()
+    }
+}
+
+/// An object-safe counterpart of `Coprocessor` and `CoCircuit`, for coprocessors registered at runtime. The methods
+/// mirror theirs, except for synthesizing into a `DynConstraintSystem`. Coprocessors with circuits implement
+/// `synthesize` or `synthesize_simple`, whose default is unsatisfiable since the others are never synthesized.
+pub trait DynCoprocessor<F: LurkField>: Debug + Send + Sync {
+    fn arity(&self) -> usize;
+
+    fn has_circuit(&self) -> bool {
+        false
+    }
+
+    fn batchable(&self) -> bool {
+        false
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        vec![self.evaluate_simple(s, args), *env, *cont]
+    }
+
+    fn evaluate_simple(&self, s: &Store<F>, args: &[Ptr]) -> Ptr;
+
+    #[allow(clippy::too_many_arguments)]
+    fn synthesize(
+        &self,
+        cs: &mut DynConstraintSystem<'_, F>,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        Ok(vec![
+            self.synthesize_simple(cs, g, s, not_dummy, args)?,
+            env.clone(),
+            cont.clone(),
+        ])
+    }
+
+    fn synthesize_simple(
+        &self,
+        _cs: &mut DynConstraintSystem<'_, F>,
+        _g: &GlobalAllocator<F>,
+        _s: &Store<F>,
+        _not_dummy: &Boolean,
+        _args: &[AllocatedPtr<F>],
+    ) -> Result<AllocatedPtr<F>, SynthesisError> {
+        Err(SynthesisError::Unsatisfiable)
+    }
+}
+
+/// A registered `DynCoprocessor`, as a `Coprocessor`
+#[derive(Clone, Debug)]
+pub struct DynCoproc<F: LurkField>(Arc<dyn DynCoprocessor<F>>);
+
+impl<F: LurkField> DynCoproc<F> {
+    pub fn new(coproc: Arc<dyn DynCoprocessor<F>>) -> Self {
+        Self(coproc)
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for DynCoproc<F> {
+    fn arity(&self) -> usize {
+        self.0.arity()
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let cs = &mut DynConstraintSystem(Erased::Borrowed(cs));
+        self.0.synthesize(cs, g, s, not_dummy, args, env, cont)
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for DynCoproc<F> {
+    fn eval_arity(&self) -> usize {
+        self.0.arity()
+    }
+
+    fn has_circuit(&self) -> bool {
+        self.0.has_circuit()
+    }
+
+    fn batchable(&self) -> bool {
+        self.0.batchable()
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        self.0.evaluate(s, args, env, cont)
+    }
+
+    fn evaluate_simple(&self, s: &Store<F>, args: &[Ptr]) -> Ptr {
+        self.0.evaluate_simple(s, args)
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    #[error("A coprocessor is already registered as {0}")]
+    AlreadyRegistered(Symbol),
+}
+
+/// Coprocessors registered at runtime, by symbol
+#[derive(Clone, Debug)]
+pub struct CoprocessorRegistry<F: LurkField> {
+    /// Keyed by the printed symbols, which sort them deterministically
+    coprocessors: BTreeMap<String, (Symbol, DynCoproc<F>)>,
+}
+
+impl<F: LurkField> Default for CoprocessorRegistry<F> {
+    fn default() -> Self {
+        Self {
+            coprocessors: BTreeMap::new(),
+        }
+    }
+}
+
+impl<F: LurkField> CoprocessorRegistry<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `coproc` as `name`, which must not be taken yet
+    pub fn register<S: Into<Symbol>>(
+        &mut self,
+        name: S,
+        coproc: Arc<dyn DynCoprocessor<F>>,
+    ) -> Result<(), RegistryError> {
+        let name = name.into();
+        let key = name.to_string();
+        if self.coprocessors.contains_key(&key) {
+            return Err(RegistryError::AlreadyRegistered(name));
+        }
+        self.coprocessors
+            .insert(key, (name, DynCoproc::new(coproc)));
+        Ok(())
+    }
+
+    pub fn lookup(&self, name: &Symbol) -> Option<&DynCoproc<F>> {
+        self.coprocessors.get(&name.to_string()).map(|(_, c)| c)
+    }
+
+    /// The index of the coprocessor registered as `name` in the `Lang` of the registry
+    pub fn index_of(&self, name: &Symbol) -> Option<usize> {
+        self.coprocessors
+            .keys()
+            .position(|key| *key == name.to_string())
+    }
+
+    pub fn len(&self) -> usize {
+        self.coprocessors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.coprocessors.is_empty()
+    }
+
+    /// A `Lang` with the registered coprocessors, ordered by symbol
+    pub fn lang(&self) -> Lang<F, DynCoproc<F>> {
+        let mut lang = Lang::new();
+        for (name, coproc) in self.coprocessors.values() {
+            lang.add_coprocessor(name.clone(), coproc.clone());
+        }
+        lang
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::{
+        circuit::gadgets::constraints::mul,
+        coprocessor::test::check_coprocessor,
+        lem::eval::{evaluate_simple, make_eval_step_from_config, EvalConfig},
+        tag::{ExprTag, Tag},
+    };
+
+    /// Squares a `Num`, as a plugin would
+    #[derive(Debug)]
+    struct Square;
+
+    impl DynCoprocessor<Fr> for Square {
+        fn arity(&self) -> usize {
+            1
+        }
+
+        fn has_circuit(&self) -> bool {
+            true
+        }
+
+        fn evaluate_simple(&self, s: &Store<Fr>, args: &[Ptr]) -> Ptr {
+            let n = *s.expect_f(args[0].get_atom().unwrap());
+            s.num(n * n)
+        }
+
+        fn synthesize_simple(
+            &self,
+            cs: &mut DynConstraintSystem<'_, Fr>,
+            _g: &GlobalAllocator<Fr>,
+            _s: &Store<Fr>,
+            _not_dummy: &Boolean,
+            args: &[AllocatedPtr<Fr>],
+        ) -> Result<AllocatedPtr<Fr>, SynthesisError> {
+            let square = mul(
+                &mut cs.namespace(|| "square"),
+                args[0].hash(),
+                args[0].hash(),
+            )?;
+            AllocatedPtr::alloc_tag(&mut cs.namespace(|| "tag"), ExprTag::Num.to_field(), square)
+        }
+    }
+
+    fn registry(names: &[&str]) -> CoprocessorRegistry<Fr> {
+        let mut registry = CoprocessorRegistry::new();
+        for name in names {
+            let name = Symbol::sym_from_vec(vec!["plugin".into(), (*name).into()]);
+            registry.register(name, Arc::new(Square)).unwrap();
+        }
+        registry
+    }
+
+    #[test]
+    fn test_consistent_indices() {
+        let (r1, r2) = (registry(&["b", "a", "c"]), registry(&["c", "b", "a"]));
+        assert_eq!(r1.lang().key(), r2.lang().key());
+        let b = Symbol::sym_from_vec(vec!["plugin".into(), "b".into()]);
+        assert_eq!(Some(1), r1.index_of(&b));
+        assert_eq!(r1.index_of(&b), r1.lang().get_index_by_symbol(&b));
+
+        let mut r1 = r1;
+        assert_eq!(
+            Err(RegistryError::AlreadyRegistered(b.clone())),
+            r1.register(b, Arc::new(Square))
+        );
+    }
+
+    #[test]
+    fn test_dyn_coprocessor() {
+        let s = &Store::<Fr>::default();
+        let registry = registry(&["square"]);
+        let lang = registry.lang();
+        let name = Symbol::sym_from_vec(vec!["plugin".into(), "square".into()]);
+        let coproc = registry.lookup(&name).unwrap();
+
+        let output = check_coprocessor(s, &coproc, &[s.num_u64(3)], &s.cont_outermost());
+        assert_eq!(s.num_u64(9), output[0]);
+
+        // evaluated from its symbol
+        let func = make_eval_step_from_config(&EvalConfig::new_ivc(&lang));
+        let expr = s.read_with_default_state("(.plugin.square 3)").unwrap();
+        let (output, ..) = evaluate_simple(Some((&func, &[], &lang)), expr, s, 10).unwrap();
+        assert_eq!(s.num_u64(9), output[0]);
+    }
+
+    #[test]
+    fn test_dyn_constraint_system() {
+        let s = &Store::<Fr>::default();
+        let g = &GlobalAllocator::default();
+        let cs = &mut DynConstraintSystem::new();
+        assert!(cs.is_witness_generator());
+        let arg = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "arg"), || {
+            s.hash_ptr(&s.num_u64(3))
+        });
+        let square = Square
+            .synthesize_simple(cs, g, s, &Boolean::Constant(true), &[arg])
+            .unwrap();
+        assert_eq!(Some(Fr::from(9)), square.hash().get_value());
+        cs.extend_aux(&[Fr::from(1)]);
+        // the argument's tag and hash, then the square and its tag
+        assert_eq!(&[Fr::from(3), Fr::from(9)], &cs.aux_slice()[1..3]);
+        assert_eq!(Some(&Fr::from(1)), cs.aux_slice().last());
+    }
+}
@@ -0,0 +1,444 @@
+//! BIP-340 Schnorr signature verification over secp256k1.
+//!
+//! `.lurk.bip340.verify` takes a public key, a message and a signature, all as `Bytes`, and returns `t` if the
+//! signature is valid and `nil` otherwise. Public keys are the 32 bytes of an x-only key, as in Taproot outputs,
+//! messages are 32 bytes and signatures are the 64 bytes of `r || s`. Keys that aren't the abscissa of a point and
+//! out-of-range signature components make the signature invalid. Arguments that aren't `Bytes` of the expected length
+//! return the first offending argument along with an error continuation.
+//!
+//! The circuit lifts the key to the point with an even ordinate, computes the tagged challenge hash with the SHA-256
+//! gadget and checks `s G - e P` with the same offset ladder as the ECDSA coprocessor. Lifting is witnessed by a
+//! square root of either `x^3 + 7` or its opposite: since `-1` isn't a square modulo `p`, exactly one of them has
+//! one, so a prover can't claim a valid key is off the curve.
+
+use bellpepper::gadgets::sha256::sha256;
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use lurk_macros::Coproc;
+use num_bigint::{BigInt, BigUint};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::{decompose_le, or},
+        data::{deconstruct_bytes_args, fetch_bytes_args, pick_bytes_args_result},
+        emulated::{EmulatedNum, Poly},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    Symbol,
+};
+
+use super::{
+    ecdsa::{le_bits, sub_mod, AllocatedPoint, Point, G, N, OFFSET, OFFSET_CORRECTION, P},
+    sha256::flip_bytes,
+    CoCircuit, Coprocessor,
+};
+
+const PUBLIC_KEY_LEN: usize = 32;
+const MESSAGE_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+/// The lengths of the public key, message and signature arguments
+const ARG_LENS: [usize; 3] = [PUBLIC_KEY_LEN, MESSAGE_LEN, SIGNATURE_LEN];
+
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+/// `SHA256(SHA256(tag) || SHA256(tag) || data)`, the tagged hash of BIP-340
+fn tagged_hash(tag: &[u8], data: &[&[u8]]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag);
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    for data in data {
+        hasher.update(data);
+    }
+    hasher.finalize().into()
+}
+
+/// A square root modulo `P` of `a`, if there's one
+fn sqrt(a: &BigUint) -> Option<BigUint> {
+    let p = &*P;
+    // `P = 3 mod 4`
+    let root = a.modpow(&((p + 1u32) >> 2), p);
+    ((&root * &root) % p == a % p).then_some(root)
+}
+
+/// The ordinate of the key's point, as a square root of `x^3 + 7`, or a square root of `-(x^3 + 7)` if there's no
+/// such point. The former is the even one, as in BIP-340's `lift_x`.
+fn lift_x_witness(x: &BigUint) -> (BigUint, bool) {
+    let p = &*P;
+    let rhs = (x.modpow(&BigUint::from(3u32), p) + 7u32) % p;
+    match sqrt(&rhs) {
+        Some(y) if y.bit(0) => (p - y, true),
+        Some(y) => (y, true),
+        None => (
+            sqrt(&sub_mod(&BigUint::default(), &rhs, p)).expect("-1 isn't a square"),
+            false,
+        ),
+    }
+}
+
+/// The point with abscissa `x` and an even ordinate, if there's one
+fn lift_x(x: BigUint) -> Option<Point> {
+    if x >= *P {
+        return None;
+    }
+    let (y, on_curve) = lift_x_witness(&x);
+    on_curve.then_some(Point { x, y })
+}
+
+/// Whether `signature` is a valid BIP-340 signature of `message` for `public_key`, in the formats described in the
+/// module documentation.
+///
+/// # Panics
+/// Panics if the arguments don't have the expected lengths
+pub fn verify(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    assert_eq!(public_key.len(), PUBLIC_KEY_LEN);
+    assert_eq!(message.len(), MESSAGE_LEN);
+    assert_eq!(signature.len(), SIGNATURE_LEN);
+    let Some(pk) = lift_x(BigUint::from_bytes_be(public_key)) else {
+        return false;
+    };
+    let r = BigUint::from_bytes_be(&signature[..32]);
+    let s = BigUint::from_bytes_be(&signature[32..]);
+    if r >= *P || s >= *N {
+        return false;
+    }
+    let e = BigUint::from_bytes_be(&tagged_hash(
+        CHALLENGE_TAG,
+        &[&signature[..32], public_key, message],
+    )) % &*N;
+    let sum = match (G.mul(&s), pk.mul(&e).map(|point| point.neg())) {
+        (Some(a), Some(b)) => a.add(&b),
+        (a, b) => a.or(b),
+    };
+    sum.is_some_and(|sum| !sum.y.bit(0) && sum.x == r)
+}
+
+/// The bits of `bytes`, each byte most significant bit first, as the SHA-256 gadget takes them
+fn constant_bits(bytes: &[u8]) -> impl Iterator<Item = Boolean> + '_ {
+    bytes.iter().flat_map(|byte| {
+        (0..8)
+            .rev()
+            .map(move |i| Boolean::constant((byte >> i) & 1 == 1))
+    })
+}
+
+/// Whether a signature is valid, as in `verify`, from the bits of its arguments as returned by `deconstruct_bytes_args`
+fn synthesize_verify<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    public_key: &[Boolean],
+    message: &[Boolean],
+    signature: &[Boolean],
+) -> Result<Boolean, SynthesisError> {
+    let (p, n) = (&*P, &*N);
+    let mut from_bits = |name: &str, bits: &[Boolean]| {
+        EmulatedNum::from_bits(&mut cs.namespace(|| name.to_string()), &le_bits(bits))
+    };
+    let px = from_bits("px", public_key)?;
+    let r = from_bits("r", &signature[..256])?;
+    let s = from_bits("s", &signature[256..])?;
+
+    // the key must be below `p`, and so must `r`, while `s` must be below `n`
+    let is_reduced = |cs: &mut CS, name: &str, a: &EmulatedNum<F>, m: &BigUint| {
+        let mut cs = cs.namespace(|| name.to_string());
+        let reduced = a.reduce(&mut cs.namespace(|| "reduce"), m)?;
+        let is_reduced = a.alloc_equal(&mut cs.namespace(|| "is reduced"), &reduced)?;
+        Ok::<_, SynthesisError>((reduced, is_reduced))
+    };
+    let (px, px_ok) = is_reduced(cs, "px reduced", &px, p)?;
+    let (r, r_ok) = is_reduced(cs, "r reduced", &r, p)?;
+    let (_, s_ok) = is_reduced(cs, "s reduced", &s, n)?;
+
+    // `lift_x`: `py` is canonical and even, and squares to either `px^3 + 7` or its opposite
+    let (py, py_bits) = EmulatedNum::alloc_with_bits(
+        &mut cs.namespace(|| "py"),
+        px.value().map(|px| lift_x_witness(px).0),
+    )?;
+    let (_, py_ok) = is_reduced(cs, "py reduced", &py, p)?;
+    Boolean::enforce_equal(
+        cs.namespace(|| "py canonical"),
+        &py_ok,
+        &Boolean::constant(true),
+    )?;
+    Boolean::enforce_equal(
+        cs.namespace(|| "py even"),
+        &py_bits[0],
+        &Boolean::constant(false),
+    )?;
+    let px_squared = Poly::new()
+        .product(1, &px, &px)
+        .alloc_congruent(&mut cs.namespace(|| "px squared"), p)?;
+    let on_curve = Poly::new()
+        .product(1, &py, &py)
+        .product(-1, &px_squared, &px)
+        .constant(BigInt::from(-7))
+        .alloc_reduced(&mut cs.namespace(|| "curve equation"), p)?
+        .alloc_is_zero(&mut cs.namespace(|| "on curve"))?;
+    let off_curve = Poly::new()
+        .product(1, &py, &py)
+        .product(1, &px_squared, &px)
+        .constant(BigInt::from(7))
+        .alloc_reduced(&mut cs.namespace(|| "opposite curve equation"), p)?
+        .alloc_is_zero(&mut cs.namespace(|| "off curve"))?;
+    let lifted = or(cs.namespace(|| "lifted"), &on_curve, &off_curve)?;
+    Boolean::enforce_equal(
+        cs.namespace(|| "square root"),
+        &lifted,
+        &Boolean::constant(true),
+    )?;
+
+    let mut valid = Boolean::Constant(true);
+    for (i, condition) in [px_ok, on_curve, r_ok, s_ok].iter().enumerate() {
+        valid = Boolean::and(cs.namespace(|| format!("valid {i}")), &valid, condition)?;
+    }
+
+    // `e = tagged_hash(CHALLENGE_TAG, r || px || message)`, whose multiples of the key don't depend on its reduction
+    // modulo `n`
+    let tag_hash = Sha256::digest(CHALLENGE_TAG);
+    let mut challenge_input = constant_bits(&tag_hash)
+        .chain(constant_bits(&tag_hash))
+        .collect::<Vec<_>>();
+    challenge_input.extend(flip_bytes(&signature[..256]));
+    challenge_input.extend(flip_bytes(public_key));
+    challenge_input.extend(flip_bytes(message));
+    let e = sha256(cs.namespace(|| "challenge"), &challenge_input)?;
+    let e = le_bits(&flip_bytes(&e));
+    let s = le_bits(&signature[256..]);
+
+    // an invalid key is replaced by a valid one, which keeps the computation below satisfiable
+    let neg_py = Poly::new()
+        .term(-1, &py)
+        .alloc_congruent(&mut cs.namespace(|| "-py"), p)?;
+    let g = AllocatedPoint::constant(&mut cs.namespace(|| "G"), &G)?;
+    let neg_pk = AllocatedPoint::pick(
+        &mut cs.namespace(|| "-P"),
+        &valid,
+        &AllocatedPoint { x: px, y: neg_py },
+        &g,
+    )?;
+
+    let mut acc = AllocatedPoint::constant(&mut cs.namespace(|| "offset"), &OFFSET)?;
+    for i in (0..256).rev() {
+        let mut cs = cs.namespace(|| format!("ladder {i}"));
+        acc = acc.double(&mut cs.namespace(|| "double"))?;
+        acc = acc.add_if(&mut cs.namespace(|| "add G"), &s[i], &g)?;
+        acc = acc.add_if(&mut cs.namespace(|| "add -P"), &e[i], &neg_pk)?;
+    }
+
+    // the sum is the point at infinity iff the ladder ended at `2^256 OFFSET`, in which case `G` is added instead
+    let correction =
+        AllocatedPoint::constant(&mut cs.namespace(|| "correction"), &OFFSET_CORRECTION)?;
+    let acc_x = acc.x.reduce(&mut cs.namespace(|| "ladder x"), p)?;
+    let at_infinity = acc_x.alloc_equal(&mut cs.namespace(|| "at infinity"), &correction.x)?;
+    let acc = AllocatedPoint::pick(&mut cs.namespace(|| "finite"), &at_infinity, &g, &acc)?;
+    let sum = acc.add(&mut cs.namespace(|| "sum"), &correction)?;
+
+    let sum_x = sum.x.reduce(&mut cs.namespace(|| "sum x"), p)?;
+    let sum_y = sum.y.reduce(&mut cs.namespace(|| "sum y"), p)?;
+    let sum_y_bits = decompose_le(&mut cs.namespace(|| "sum y bits"), &sum_y.limbs()[0], 64)?;
+    let matches = sum_x.alloc_equal(&mut cs.namespace(|| "matches"), &r)?;
+    let mut verified = valid;
+    for (i, condition) in [matches, at_infinity.not(), sum_y_bits[0].not()]
+        .iter()
+        .enumerate()
+    {
+        verified = Boolean::and(
+            cs.namespace(|| format!("verified {i}")),
+            &verified,
+            condition,
+        )?;
+    }
+    Ok(verified)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchnorrCoprocessor<F: LurkField> {
+    pub(crate) _p: PhantomData<F>,
+}
+
+impl<F: LurkField> SchnorrCoprocessor<F> {
+    pub fn new() -> Self {
+        Self {
+            _p: Default::default(),
+        }
+    }
+}
+
+impl<F: LurkField> Default for SchnorrCoprocessor<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for SchnorrCoprocessor<F> {
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let (bits, arg_oks) = deconstruct_bytes_args(cs, g, s, not_dummy, args, &ARG_LENS)?;
+        let verified =
+            synthesize_verify(&mut cs.namespace(|| "verify"), &bits[0], &bits[1], &bits[2])?;
+
+        let t = g.alloc_ptr(cs, &s.intern_t(), s);
+        let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+        let res = AllocatedPtr::pick(cs.namespace(|| "t or nil"), &verified, &t, &nil)?;
+        let (res, cont) = pick_bytes_args_result(cs, g, s, args, &arg_oks, res, cont)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for SchnorrCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        3
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        match fetch_bytes_args(s, args, ARG_LENS) {
+            Ok([public_key, message, signature]) => {
+                let res = if verify(&public_key, &message, &signature) {
+                    s.intern_t()
+                } else {
+                    s.intern_nil()
+                };
+                vec![res, *env, *cont]
+            }
+            Err(arg) => vec![arg, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum SchnorrCoproc<F: LurkField> {
+    Schnorr(SchnorrCoprocessor<F>),
+}
+
+/// Add BIP-340 verification to a `Lang` as `.lurk.bip340.verify`
+pub fn install<F: LurkField>(state: &Rc<RefCell<State>>, lang: &mut Lang<F, SchnorrCoproc<F>>) {
+    let package_name: Symbol = ".lurk.bip340".into();
+    let mut package = Package::new(package_name.clone().into());
+    lang.add_coprocessor(
+        package_name.direct_child("verify"),
+        SchnorrCoprocessor::new(),
+    );
+    package.intern("verify");
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    // The first two test vectors of BIP-340
+    const VECTORS: [(&str, &str, &str); 2] = [
+        (
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215\
+             25f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+        ),
+        (
+            "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+            "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+            "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de3341\
+             8906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+        ),
+    ];
+
+    // Not the abscissa of a point of secp256k1, from BIP-340's test vectors
+    const OFF_CURVE_KEY: &str = "eefdea4cdb677750a420fee807eacf21eb9898ae79b9768766e4faa04a2d4a34";
+
+    fn vector(i: usize) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let decode = |s| ::hex::decode(s).unwrap();
+        let (public_key, message, signature) = VECTORS[i];
+        (decode(public_key), decode(message), decode(signature))
+    }
+
+    #[test]
+    fn test_lift_x() {
+        let point = lift_x(G.x.clone()).unwrap();
+        assert!(!point.y.bit(0));
+        assert!(Point::from_coordinates(point.x, point.y).is_some());
+        assert!(lift_x(BigUint::from_bytes_be(
+            &::hex::decode(OFF_CURVE_KEY).unwrap()
+        ))
+        .is_none());
+        assert!(lift_x(P.clone()).is_none());
+    }
+
+    #[test]
+    fn test_verify() {
+        for i in 0..VECTORS.len() {
+            let (public_key, message, signature) = vector(i);
+            assert!(verify(&public_key, &message, &signature));
+
+            let mut wrong_message = message.clone();
+            wrong_message[0] ^= 1;
+            assert!(!verify(&public_key, &wrong_message, &signature));
+            let mut wrong_signature = signature.clone();
+            wrong_signature[63] ^= 1;
+            assert!(!verify(&public_key, &message, &wrong_signature));
+            let off_curve_key = ::hex::decode(OFF_CURVE_KEY).unwrap();
+            assert!(!verify(&off_curve_key, &message, &signature));
+            // `r` must be below `p`
+            let mut large_r = signature.clone();
+            large_r[..32].copy_from_slice(&[0xff; 32]);
+            assert!(!verify(&public_key, &message, &large_r));
+        }
+    }
+
+    #[test]
+    #[ignore] // Skip expensive tests in CI for now. Do run these locally, please.
+    fn test_verify_circuit() {
+        let s = &Store::<Fr>::default();
+        let (public_key, message, signature) = vector(1);
+        let mut wrong_signature = signature.clone();
+        wrong_signature[40] ^= 1;
+        let off_curve_key = ::hex::decode(OFF_CURVE_KEY).unwrap();
+        let coproc = SchnorrCoprocessor::new();
+        let cont = s.cont_outermost();
+        for (public_key, signature, expected) in [
+            (&public_key, &signature, s.intern_t()),
+            (&public_key, &wrong_signature, s.intern_nil()),
+            (&off_curve_key, &signature, s.intern_nil()),
+        ] {
+            let args =
+                [public_key.as_slice(), &message, signature].map(|bytes| s.intern_bytes(bytes));
+            let output = check_coprocessor(s, &coproc, &args, &cont);
+            assert_eq!(vec![expected, s.intern_empty_env(), cont], output);
+        }
+    }
+}
@@ -3,15 +3,24 @@ use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
 use lurk_macros::Coproc;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::rc::Rc;
 
 use crate::{
     self as lurk,
-    circuit::gadgets::pointer::AllocatedPtr,
+    circuit::gadgets::{
+        data::{construct_bytes, deconstruct_bytes_or_string, fetch_bytes_or_string},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
     field::LurkField,
-    lem::{pointers::Ptr, store::Store},
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
     tag::{ExprTag, Tag},
     z_ptr::ZPtr,
+    Symbol,
 };
 
 use super::{CoCircuit, Coprocessor};
@@ -145,7 +154,189 @@ fn discard_bits<Scalar: LurkField>(bytes: &mut [u8]) {
     }
 }
 
+/// Reverses the bits of each byte, since the SHA-256 gadget takes and returns bytes most significant bit first
+pub(crate) fn flip_bytes(bits: &[Boolean]) -> Vec<Boolean> {
+    bits.chunks(8)
+        .flat_map(|byte| byte.iter().rev().cloned())
+        .collect()
+}
+
+/// The SHA-256 digest of `input`, as a `Bytes` of 32 bytes, along with whether `input` is a `Bytes` of `len` bytes or a
+/// string of `len` characters below 256, as checked by `deconstruct_bytes_or_string`.
+pub(crate) fn synthesize_sha256_bytes<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    input: &AllocatedPtr<F>,
+    len: usize,
+) -> Result<(AllocatedPtr<F>, Boolean), SynthesisError> {
+    let (bits, ok) = deconstruct_bytes_or_string(cs, g, s, not_dummy, input, len)?;
+    let digest = sha256(cs.namespace(|| "sha256"), &flip_bytes(&bits))?;
+    let digest = construct_bytes(&mut cs.namespace(|| "digest"), g, s, &flip_bytes(&digest))?;
+    Ok((digest, ok))
+}
+
+/// SHA-256 over `len` bytes, given as a `Bytes` or as a string of characters below 256, such as the ones read from
+/// `b""` or `""` literals. The digest is returned whole, as a `Bytes` of 32 bytes, so that it can be compared with
+/// digests computed outside of Lurk.
+///
+/// Other arguments, including ones of the wrong length or with wider characters, return themselves along with an
+/// error continuation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sha256BytesCoprocessor<F: LurkField> {
+    len: usize,
+    pub(crate) _p: PhantomData<F>,
+}
+
+impl<F: LurkField> Sha256BytesCoprocessor<F> {
+    pub fn new(len: usize) -> Self {
+        Self {
+            len,
+            _p: Default::default(),
+        }
+    }
+
+    /// The name of the coprocessor in the `.lurk.sha256` package
+    pub fn name(&self) -> String {
+        format!("bytes-{}", self.len)
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for Sha256BytesCoprocessor<F> {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let [input] = args else {
+            return Err(SynthesisError::Unsatisfiable);
+        };
+        let (digest, ok) = synthesize_sha256_bytes(
+            &mut cs.namespace(|| "sha256"),
+            g,
+            s,
+            not_dummy,
+            input,
+            self.len,
+        )?;
+        let res = AllocatedPtr::pick(cs.namespace(|| "digest or input"), &ok, &digest, input)?;
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &ok, cont, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for Sha256BytesCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        1
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        let input = args[0];
+        match fetch_bytes_or_string(s, &input).filter(|bytes| bytes.len() == self.len) {
+            Some(bytes) => vec![s.intern_bytes(&Sha256::digest(bytes)), *env, *cont],
+            None => vec![input, *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
 #[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
 pub enum Sha256Coproc<F: LurkField> {
     SC(Sha256Coprocessor<F>),
+    Bytes(Sha256BytesCoprocessor<F>),
+}
+
+/// Add a `Sha256BytesCoprocessor` for each of `lens` to a `Lang`, as `.lurk.sha256.bytes-<len>`
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, Sha256Coproc<F>>,
+    lens: &[usize],
+) {
+    let package_name: Symbol = ".lurk.sha256".into();
+    let mut package = Package::new(package_name.clone().into());
+    for len in lens {
+        let coproc = Sha256BytesCoprocessor::new(*len);
+        let name = coproc.name();
+        lang.add_coprocessor(package_name.direct_child(&name), coproc);
+        package.intern(name);
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    fn check(s: &Store<Fr>, len: usize, input: Ptr) -> Vec<Ptr> {
+        let coproc = Sha256BytesCoprocessor::new(len);
+        check_coprocessor(s, &coproc, &[input], &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_sha256_bytes() {
+        let s = &Store::<Fr>::default();
+        let cont = s.cont_outermost();
+        let inputs: [&[u8]; 3] = [b"", b"abc", &[0xff; 40]];
+        for input in inputs {
+            let digest = s.intern_bytes(&Sha256::digest(input));
+            let output = check(s, input.len(), s.intern_bytes(input));
+            assert_eq!(vec![digest, s.intern_empty_env(), cont], output);
+        }
+
+        // strings hash like the bytes of their characters
+        let digest = s.intern_bytes(&Sha256::digest(b"abc"));
+        assert_eq!(digest, check(s, 3, s.intern_string("abc"))[0]);
+        let latin1 = s.intern_string("\u{e9}t\u{e9}");
+        assert_eq!(
+            s.intern_bytes(&Sha256::digest([0xe9, b't', 0xe9])),
+            check(s, 3, latin1)[0]
+        );
+    }
+
+    #[test]
+    fn test_sha256_bytes_errors() {
+        let s = &Store::<Fr>::default();
+        let num = s.num_u64(3);
+        assert_eq!(
+            vec![num, s.intern_empty_env(), s.cont_error()],
+            check(s, 3, num)
+        );
+
+        // so do inputs of the right type but of the wrong length or with wider characters
+        for input in [
+            s.intern_bytes(b"abc"),
+            s.intern_string("abc"),
+            s.intern_string("abc\u{100}"),
+        ] {
+            assert_eq!(
+                vec![input, s.intern_empty_env(), s.cont_error()],
+                check(s, 4, input)
+            );
+        }
+    }
 }
@@ -0,0 +1,693 @@
+//! String operations over the `Str`, `Char` and `U64` expression types.
+//!
+//! `.lurk.string.length` returns the number of characters of a string as a `U64`, `char-at` the `Char` at a `U64`
+//! index, `substring` the characters from a start index up to an end index, `concat` the concatenation of two strings
+//! and `parse-integer` the `I64` written in decimal by a string, with an optional leading `-` and at most 18 digits.
+//!
+//! The circuits unroll the characters of the first argument up to a bound, `max_len`, that each coprocessor is
+//! created with. If an argument has the wrong type, the first offending argument is returned along with an error
+//! continuation. The first argument is returned along with an error continuation if it's longer than `max_len`, if an
+//! index is out of range or if it isn't an integer.
+
+use bellpepper_core::{
+    boolean::Boolean, num::AllocatedNum, ConstraintSystem, LinearCombination, SynthesisError,
+};
+use lurk_macros::Coproc;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::{
+    self as lurk,
+    circuit::gadgets::{
+        constraints::{alloc_equal_const, alloc_is_zero, alloc_lc, or, pick},
+        data::{alloc_is_tag, car_cdr, construct_tuple2},
+        pointer::AllocatedPtr,
+    },
+    eval::lang::Lang,
+    field::LurkField,
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
+    package::Package,
+    state::State,
+    tag::{ExprTag, Tag},
+    Symbol,
+};
+
+use super::{CoCircuit, Coprocessor};
+
+/// The most digits `parse-integer` accepts, so that every result fits in an `I64`
+const MAX_DIGITS: usize = 18;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StrOp {
+    Length,
+    CharAt,
+    Substring,
+    Concat,
+    ParseInteger,
+}
+
+impl StrOp {
+    const ALL: [StrOp; 5] = [
+        Self::Length,
+        Self::CharAt,
+        Self::Substring,
+        Self::Concat,
+        Self::ParseInteger,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Length => "length",
+            Self::CharAt => "char-at",
+            Self::Substring => "substring",
+            Self::Concat => "concat",
+            Self::ParseInteger => "parse-integer",
+        }
+    }
+
+    /// The types of the arguments, the first of which is always the string whose characters are unrolled
+    fn arg_tags(&self) -> &'static [ExprTag] {
+        match self {
+            Self::Length | Self::ParseInteger => &[ExprTag::Str],
+            Self::CharAt => &[ExprTag::Str, ExprTag::U64],
+            Self::Substring => &[ExprTag::Str, ExprTag::U64, ExprTag::U64],
+            Self::Concat => &[ExprTag::Str, ExprTag::Str],
+        }
+    }
+}
+
+fn parse_integer(string: &str) -> Option<i64> {
+    let (negative, digits) = match string.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, string),
+    };
+    if digits.is_empty() || digits.len() > MAX_DIGITS || !digits.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let value: i64 = digits.parse().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// The unrolled characters of a string
+struct AllocatedChars<F: LurkField> {
+    /// The first `max_len` characters, padded with nil
+    chars: Vec<AllocatedPtr<F>>,
+    /// Whether each of `chars` is a character of the string
+    present: Vec<Boolean>,
+    /// The string and what's left of it after each of `chars`
+    suffixes: Vec<AllocatedPtr<F>>,
+    /// Whether the string has at most `max_len` characters
+    fits: Boolean,
+}
+
+/// Flags for whether `index` equals each of `0..n`
+fn index_flags<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    index: &AllocatedNum<F>,
+    n: usize,
+) -> Result<Vec<Boolean>, SynthesisError> {
+    (0..n)
+        .map(|k| {
+            alloc_equal_const(
+                cs.namespace(|| format!("index is {k}")),
+                index,
+                F::from_u64(k as u64),
+            )
+        })
+        .collect()
+}
+
+impl<F: LurkField> AllocatedChars<F> {
+    /// Unrolls `string`, which must be a `Str` if `bind` is true
+    fn alloc<CS: ConstraintSystem<F>>(
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        bind: &Boolean,
+        string: &AllocatedPtr<F>,
+        max_len: usize,
+    ) -> Result<Self, SynthesisError> {
+        let mut chars = Vec::with_capacity(max_len);
+        let mut present = Vec::with_capacity(max_len);
+        let mut suffixes = Vec::with_capacity(max_len + 1);
+        suffixes.push(string.clone());
+        for j in 0..max_len {
+            let (car, cdr, not_empty) = car_cdr(
+                &mut cs.namespace(|| format!("char {j}")),
+                g,
+                s,
+                bind,
+                &suffixes[j],
+            )?;
+            chars.push(car);
+            present.push(not_empty);
+            suffixes.push(cdr);
+        }
+        let empty = g.alloc_ptr(cs, &s.intern_string(""), s);
+        let fits = suffixes[max_len].alloc_equal(&mut cs.namespace(|| "fits"), &empty)?;
+        Ok(Self {
+            chars,
+            present,
+            suffixes,
+            fits,
+        })
+    }
+
+    /// Whether the string has at least `k` characters
+    fn has_at_least(&self, k: usize) -> Boolean {
+        match k {
+            0 => Boolean::Constant(true),
+            k => self.present[k - 1].clone(),
+        }
+    }
+
+    /// Whether the index flagged by `flags` is at most the length of the string
+    fn within<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        flags: &[Boolean],
+    ) -> Result<Boolean, SynthesisError> {
+        let mut within = Boolean::Constant(false);
+        for (k, flag) in flags.iter().enumerate() {
+            let reached = Boolean::and(
+                cs.namespace(|| format!("reaches {k}")),
+                flag,
+                &self.has_at_least(k),
+            )?;
+            within = or(cs.namespace(|| format!("within {k}")), &within, &reached)?;
+        }
+        Ok(within)
+    }
+
+    fn length<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+    ) -> Result<AllocatedNum<F>, SynthesisError> {
+        let value = self.present.iter().try_fold(F::ZERO, |acc, present| {
+            present
+                .get_value()
+                .map(|present| if present { acc + F::ONE } else { acc })
+        });
+        let lc = self
+            .present
+            .iter()
+            .fold(LinearCombination::zero(), |lc, present| {
+                lc + &present.lc(CS::one(), F::ONE)
+            });
+        alloc_lc(cs, value, lc)
+    }
+
+    /// The character at `index`, or nil if it's out of range
+    fn char_at<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        index: &AllocatedNum<F>,
+    ) -> Result<AllocatedPtr<F>, SynthesisError> {
+        let flags = index_flags(&mut cs.namespace(|| "index"), index, self.chars.len())?;
+        let mut res = g.alloc_ptr(cs, &s.intern_nil(), s);
+        for (j, (flag, c)) in flags.iter().zip(&self.chars).enumerate() {
+            res = AllocatedPtr::pick(cs.namespace(|| format!("char {j}")), flag, c, &res)?;
+        }
+        Ok(res)
+    }
+
+    /// The rest of the string after `start` characters, and whether `start` is in range
+    fn suffix<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        start: &AllocatedNum<F>,
+    ) -> Result<(AllocatedPtr<F>, Boolean), SynthesisError> {
+        let flags = index_flags(&mut cs.namespace(|| "start"), start, self.suffixes.len())?;
+        let mut res = g.alloc_ptr(cs, &s.intern_string(""), s);
+        for (k, (flag, suffix)) in flags.iter().zip(&self.suffixes).enumerate() {
+            res = AllocatedPtr::pick(cs.namespace(|| format!("suffix {k}")), flag, suffix, &res)?;
+        }
+        let in_range = self.within(&mut cs.namespace(|| "start in range"), &flags)?;
+        Ok((res, in_range))
+    }
+
+    /// Flags for whether each character comes before the `n`th, and whether `n` is in range
+    fn prefix<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        n: &AllocatedNum<F>,
+    ) -> Result<(Vec<Boolean>, Boolean), SynthesisError> {
+        let flags = index_flags(&mut cs.namespace(|| "n"), n, self.chars.len() + 1)?;
+        let mut reached = Boolean::Constant(false);
+        let mut taken = Vec::with_capacity(self.chars.len());
+        for (j, flag) in flags[..self.chars.len()].iter().enumerate() {
+            reached = or(cs.namespace(|| format!("reached {j}")), &reached, flag)?;
+            taken.push(reached.not());
+        }
+        let in_range = self.within(&mut cs.namespace(|| "n in range"), &flags)?;
+        Ok((taken, in_range))
+    }
+
+    /// Prepends the characters flagged by `taken`, which must be a prefix of the string, to `tail`
+    fn prepend_to<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        taken: &[Boolean],
+        tail: &AllocatedPtr<F>,
+    ) -> Result<AllocatedPtr<F>, SynthesisError> {
+        let mut res = tail.clone();
+        for (j, (take, c)) in taken.iter().zip(&self.chars).enumerate().rev() {
+            let cs = &mut cs.namespace(|| format!("prepend {j}"));
+            let cons = construct_tuple2(
+                &mut cs.namespace(|| "strcons"),
+                g,
+                s,
+                &ExprTag::Str,
+                c,
+                &res,
+            )?;
+            res = AllocatedPtr::pick(cs.namespace(|| "pick"), take, &cons, &res)?;
+        }
+        Ok(res)
+    }
+
+    /// The `I64` value of the string, as its two's complement, and whether it's an integer of at most `MAX_DIGITS`
+    /// digits
+    fn parse_integer<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+    ) -> Result<(AllocatedNum<F>, Boolean), SynthesisError> {
+        let code = |c: char| F::from_u64(c as u64);
+        let is_minus =
+            alloc_equal_const(cs.namespace(|| "minus"), self.chars[0].hash(), code('-'))?;
+        let is_minus = Boolean::and(cs.namespace(|| "is minus"), &is_minus, &self.present[0])?;
+
+        let mut value = g.alloc_const_cloned(cs, F::ZERO);
+        let mut digits_ok = Boolean::Constant(true);
+        let mut has_digit = Boolean::Constant(false);
+        // with the sign, a valid integer has at most `MAX_DIGITS + 1` characters
+        for (j, c) in self.chars.iter().take(MAX_DIGITS + 1).enumerate() {
+            let cs = &mut cs.namespace(|| format!("digit {j}"));
+            let used = if j == 0 {
+                Boolean::and(cs.namespace(|| "used"), &self.present[0], &is_minus.not())?
+            } else {
+                self.present[j].clone()
+            };
+            let mut is_digit = Boolean::Constant(false);
+            for d in 0..10 {
+                let is_d = alloc_equal_const(
+                    cs.namespace(|| format!("is {d}")),
+                    c.hash(),
+                    code('0') + F::from_u64(d),
+                )?;
+                is_digit = or(cs.namespace(|| format!("is digit {d}")), &is_digit, &is_d)?;
+            }
+            let bad = Boolean::and(cs.namespace(|| "bad"), &used, &is_digit.not())?;
+            digits_ok = Boolean::and(cs.namespace(|| "digits ok"), &digits_ok, &bad.not())?;
+            has_digit = or(cs.namespace(|| "has digit"), &has_digit, &used)?;
+
+            let ten = F::from_u64(10);
+            let shifted = alloc_lc(
+                &mut cs.namespace(|| "shifted"),
+                value
+                    .get_value()
+                    .zip(c.hash().get_value())
+                    .map(|(value, c)| value * ten + c - code('0')),
+                LinearCombination::zero() + (ten, value.get_variable()) + c.hash().get_variable()
+                    - (code('0'), CS::one()),
+            )?;
+            value = pick(cs.namespace(|| "value"), &used, &shifted, &value)?;
+        }
+
+        // too many digits, whether or not there's a sign
+        let mut too_long = Boolean::Constant(false);
+        if let Some(present) = self.present.get(MAX_DIGITS) {
+            too_long = Boolean::and(cs.namespace(|| "too many digits"), present, &is_minus.not())?;
+        }
+        if let Some(present) = self.present.get(MAX_DIGITS + 1) {
+            too_long = or(cs.namespace(|| "too long"), &too_long, present)?;
+        }
+        let ok = Boolean::and(
+            cs.namespace(|| "digits ok and some"),
+            &digits_ok,
+            &has_digit,
+        )?;
+        let ok = Boolean::and(cs.namespace(|| "parsed"), &ok, &too_long.not())?;
+
+        // -0 is 0
+        let is_zero = alloc_is_zero(cs.namespace(|| "is zero"), &value)?;
+        let negate = Boolean::and(cs.namespace(|| "negate"), &is_minus, &is_zero.not())?;
+        let two_64 = F::from_u64(u64::MAX) + F::ONE;
+        let negated = alloc_lc(
+            &mut cs.namespace(|| "negated"),
+            value.get_value().map(|value| two_64 - value),
+            LinearCombination::zero() + (two_64, CS::one()) - value.get_variable(),
+        )?;
+        let value = pick(cs.namespace(|| "signed value"), &negate, &negated, &value)?;
+        Ok((value, ok))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StrCoprocessor<F: LurkField> {
+    op: StrOp,
+    max_len: usize,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> StrCoprocessor<F> {
+    /// A coprocessor for strings of up to `max_len` characters, which must be positive
+    pub fn new(op: StrOp, max_len: usize) -> Self {
+        assert!(max_len > 0, "max_len must be positive");
+        Self {
+            op,
+            max_len,
+            _p: Default::default(),
+        }
+    }
+
+    /// The result for `string`, or `None` if it's too long or the operation fails on it and `args`
+    fn apply(&self, s: &Store<F>, string: &str, args: &[Ptr]) -> Option<Ptr> {
+        let chars = string.chars().collect::<Vec<_>>();
+        if chars.len() > self.max_len {
+            return None;
+        }
+        let index = |ptr: &Ptr| usize::try_from(s.fetch_u64(ptr)?).ok();
+        match self.op {
+            StrOp::Length => Some(s.u64(chars.len() as u64)),
+            StrOp::CharAt => chars.get(index(&args[0])?).map(|c| s.char(*c)),
+            StrOp::Substring => {
+                let sub = chars.get(index(&args[0])?..index(&args[1])?)?;
+                Some(s.intern_string(&sub.iter().collect::<String>()))
+            }
+            StrOp::Concat => {
+                let tail = s.fetch_string(&args[0])?;
+                Some(s.intern_string(&format!("{string}{tail}")))
+            }
+            StrOp::ParseInteger => parse_integer(string).map(|i| s.i64(i)),
+        }
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for StrCoprocessor<F> {
+    fn arity(&self) -> usize {
+        self.op.arg_tags().len()
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let mut types_ok = Boolean::Constant(true);
+        let mut arg_oks = Vec::with_capacity(args.len());
+        for (i, (arg, tag)) in args.iter().zip(self.op.arg_tags()).enumerate() {
+            let arg_ok = alloc_is_tag(&mut cs.namespace(|| format!("arg {i} type")), g, arg, tag)?;
+            types_ok = Boolean::and(cs.namespace(|| format!("types {i}")), &types_ok, &arg_ok)?;
+            arg_oks.push(arg_ok);
+        }
+
+        // `car_cdr` can only look up the characters of a `Str`
+        let bind = Boolean::and(cs.namespace(|| "bind"), &arg_oks[0], not_dummy)?;
+        let string = AllocatedChars::alloc(
+            &mut cs.namespace(|| "string"),
+            g,
+            s,
+            &bind,
+            &args[0],
+            self.max_len,
+        )?;
+
+        let (res, op_ok) = {
+            let cs = &mut cs.namespace(|| self.op.name());
+            match self.op {
+                StrOp::Length => {
+                    let length = string.length(&mut cs.namespace(|| "length"))?;
+                    let res = AllocatedPtr::alloc_tag(cs, ExprTag::U64.to_field(), length)?;
+                    (res, Boolean::Constant(true))
+                }
+                StrOp::CharAt => {
+                    let res = string.char_at(cs, g, s, args[1].hash())?;
+                    let in_range =
+                        alloc_is_tag(&mut cs.namespace(|| "in range"), g, &res, &ExprTag::Char)?;
+                    (res, in_range)
+                }
+                StrOp::Substring => {
+                    let (start, end) = (args[1].hash(), args[2].hash());
+                    let (suffix, start_ok) =
+                        string.suffix(&mut cs.namespace(|| "suffix"), g, s, start)?;
+                    let suffix = AllocatedChars::alloc(
+                        &mut cs.namespace(|| "suffix chars"),
+                        g,
+                        s,
+                        &bind,
+                        &suffix,
+                        self.max_len,
+                    )?;
+                    let n = alloc_lc(
+                        &mut cs.namespace(|| "n"),
+                        end.get_value()
+                            .zip(start.get_value())
+                            .map(|(end, start)| end - start),
+                        LinearCombination::zero() + end.get_variable() - start.get_variable(),
+                    )?;
+                    let (taken, n_ok) = suffix.prefix(&mut cs.namespace(|| "prefix"), &n)?;
+                    let empty = g.alloc_ptr(cs, &s.intern_string(""), s);
+                    let res = suffix.prepend_to(
+                        &mut cs.namespace(|| "substring"),
+                        g,
+                        s,
+                        &taken,
+                        &empty,
+                    )?;
+                    let ok = Boolean::and(cs.namespace(|| "range ok"), &start_ok, &n_ok)?;
+                    (res, ok)
+                }
+                StrOp::Concat => {
+                    let res = string.prepend_to(
+                        &mut cs.namespace(|| "concat"),
+                        g,
+                        s,
+                        &string.present,
+                        &args[1],
+                    )?;
+                    (res, Boolean::Constant(true))
+                }
+                StrOp::ParseInteger => {
+                    let (value, ok) = string.parse_integer(&mut cs.namespace(|| "parse"), g)?;
+                    let res = AllocatedPtr::alloc_tag(cs, ExprTag::I64.to_field(), value)?;
+                    (res, ok)
+                }
+            }
+        };
+
+        let ok = Boolean::and(cs.namespace(|| "fits and op ok"), &string.fits, &op_ok)?;
+        let ok = Boolean::and(cs.namespace(|| "ok"), &types_ok, &ok)?;
+        let mut res = AllocatedPtr::pick(cs.namespace(|| "result or fst"), &ok, &res, &args[0])?;
+        for (i, (arg, arg_ok)) in args.iter().zip(&arg_oks).enumerate().rev() {
+            res = AllocatedPtr::pick(
+                cs.namespace(|| format!("result or arg {i}")),
+                arg_ok,
+                &res,
+                arg,
+            )?;
+        }
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &ok, cont, &cont_err)?;
+        Ok(vec![res, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField> Coprocessor<F> for StrCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        self.op.arg_tags().len()
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn batchable(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        if let Some(arg) = args
+            .iter()
+            .zip(self.op.arg_tags())
+            .find_map(|(arg, tag)| (*arg.tag() != Tag::Expr(*tag)).then_some(arg))
+        {
+            return vec![*arg, *env, s.cont_error()];
+        }
+        let string = s.fetch_string(&args[0]).expect("missing string");
+        match self.apply(s, &string, &args[1..]) {
+            Some(res) => vec![res, *env, *cont],
+            None => vec![args[0], *env, s.cont_error()],
+        }
+    }
+
+    fn evaluate_simple(&self, _s: &Store<F>, _args: &[Ptr]) -> Ptr {
+        unreachable!()
+    }
+}
+
+#[derive(Clone, Debug, Coproc, Serialize, Deserialize)]
+pub enum StrCoproc<F: LurkField> {
+    Str(StrCoprocessor<F>),
+}
+
+/// Add the string operations to a `Lang` as `.lurk.string.length`, `.lurk.string.substring`, etc., for strings of up
+/// to `max_len` characters
+pub fn install<F: LurkField>(
+    state: &Rc<RefCell<State>>,
+    lang: &mut Lang<F, StrCoproc<F>>,
+    max_len: usize,
+) {
+    let package_name: Symbol = ".lurk.string".into();
+    let mut package = Package::new(package_name.clone().into());
+    for op in StrOp::ALL {
+        lang.add_coprocessor(
+            package_name.direct_child(op.name()),
+            StrCoprocessor::new(op, max_len),
+        );
+        package.intern(op.name());
+    }
+    state.borrow_mut().add_package(package);
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::coprocessor::test::check_coprocessor;
+
+    const MAX_LEN: usize = 5;
+
+    fn check(s: &Store<Fr>, coproc: &StrCoprocessor<Fr>, args: &[Ptr]) -> Vec<Ptr> {
+        check_coprocessor(s, coproc, args, &s.cont_outermost())
+    }
+
+    #[test]
+    fn test_str_ops() {
+        let s = &Store::<Fr>::default();
+        let str = |string: &str| s.intern_string(string);
+        let ok = |op, args: &[Ptr], res: Ptr| {
+            let output = check(s, &StrCoprocessor::new(op, MAX_LEN), args);
+            assert_eq!(vec![res, s.intern_empty_env(), s.cont_outermost()], output);
+        };
+
+        for (string, length) in [("", 0), ("a", 1), ("héllo", 5)] {
+            ok(StrOp::Length, &[str(string)], s.u64(length));
+        }
+        ok(StrOp::CharAt, &[str("héllo"), s.u64(1)], s.char('é'));
+        ok(StrOp::CharAt, &[str("héllo"), s.u64(4)], s.char('o'));
+        ok(
+            StrOp::Substring,
+            &[str("héllo"), s.u64(1), s.u64(3)],
+            str("él"),
+        );
+        ok(
+            StrOp::Substring,
+            &[str("héllo"), s.u64(0), s.u64(5)],
+            str("héllo"),
+        );
+        ok(
+            StrOp::Substring,
+            &[str("héllo"), s.u64(5), s.u64(5)],
+            str(""),
+        );
+        ok(StrOp::Substring, &[str(""), s.u64(0), s.u64(0)], str(""));
+        ok(StrOp::Concat, &[str("ab"), str("cd")], str("abcd"));
+        ok(StrOp::Concat, &[str(""), str("cd")], str("cd"));
+        ok(StrOp::Concat, &[str("hello"), str("")], str("hello"));
+        for (string, i) in [
+            ("0", 0),
+            ("42", 42),
+            ("-42", -42),
+            ("-0", 0),
+            ("00107", 107),
+        ] {
+            ok(StrOp::ParseInteger, &[str(string)], s.i64(i));
+        }
+    }
+
+    #[test]
+    fn test_str_errors() {
+        let s = &Store::<Fr>::default();
+        let str = |string: &str| s.intern_string(string);
+        let err = |op, args: &[Ptr], res: Ptr| {
+            let output = check(s, &StrCoprocessor::new(op, MAX_LEN), args);
+            assert_eq!(vec![res, s.intern_empty_env(), s.cont_error()], output);
+        };
+
+        // wrong types, in argument order
+        err(StrOp::Length, &[s.u64(1)], s.u64(1));
+        err(StrOp::CharAt, &[str("ab"), s.char('a')], s.char('a'));
+        err(
+            StrOp::Substring,
+            &[s.num_u64(1), s.u64(0), str("x")],
+            s.num_u64(1),
+        );
+        err(StrOp::Substring, &[str("ab"), s.u64(0), str("x")], str("x"));
+        err(StrOp::Concat, &[str("ab"), s.char('c')], s.char('c'));
+
+        // too long
+        err(StrOp::Length, &[str("abcdef")], str("abcdef"));
+        err(StrOp::Concat, &[str("abcdef"), str("")], str("abcdef"));
+
+        // out of range
+        err(StrOp::CharAt, &[str("ab"), s.u64(2)], str("ab"));
+        err(
+            StrOp::CharAt,
+            &[str("hello"), s.u64(u64::MAX)],
+            str("hello"),
+        );
+        err(
+            StrOp::Substring,
+            &[str("hello"), s.u64(3), s.u64(2)],
+            str("hello"),
+        );
+        err(
+            StrOp::Substring,
+            &[str("hello"), s.u64(3), s.u64(6)],
+            str("hello"),
+        );
+        err(
+            StrOp::Substring,
+            &[str("ab"), s.u64(3), s.u64(3)],
+            str("ab"),
+        );
+
+        for string in ["", "-", "--1", "1-", "+1", "1a", " 1"] {
+            err(StrOp::ParseInteger, &[str(string)], str(string));
+        }
+    }
+
+    #[test]
+    fn test_parse_integer_digits() {
+        let s = &Store::<Fr>::default();
+        let coproc = StrCoprocessor::new(StrOp::ParseInteger, MAX_DIGITS + 2);
+        let parse = |string: &str| check(s, &coproc, &[s.intern_string(string)])[0];
+
+        let max = "9".repeat(MAX_DIGITS);
+        assert_eq!(s.i64(max.parse().unwrap()), parse(&max));
+        assert_eq!(
+            s.i64(-max.parse::<i64>().unwrap()),
+            parse(&format!("-{max}"))
+        );
+        for too_many in [format!("1{max}"), format!("-1{max}")] {
+            assert_eq!(s.intern_string(&too_many), parse(&too_many));
+        }
+    }
+}
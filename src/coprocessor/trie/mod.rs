@@ -55,6 +55,7 @@ pub enum TrieCoproc<F: LurkField> {
     New(NewCoprocessor<F>),
     Lookup(LookupCoprocessor<F>),
     Insert(InsertCoprocessor<F>),
+    Delete(DeleteCoprocessor<F>),
 }
 
 #[derive(Clone, Debug, Serialize, Default, Deserialize)]
@@ -240,7 +241,7 @@ fn synthesize_insert_aux<F: LurkField, CS: ConstraintSystem<F>>(
     cs: &mut CS,
     root_ptr: &AllocatedPtr<F>,
     key_ptr: &AllocatedPtr<F>,
-    val_ptr: &AllocatedPtr<F>,
+    new_val: &AllocatedNum<F>,
     not_dummy: &Boolean,
     poseidon_cache: &PoseidonCache<F>,
     inverse_poseidon_cache: &InversePoseidonCache<F>,
@@ -249,7 +250,6 @@ fn synthesize_insert_aux<F: LurkField, CS: ConstraintSystem<F>>(
     let supplied_root_value = root_ptr.hash();
     let root_value = supplied_root_value.get_value();
     let key_val = key_ptr.hash();
-    let new_val = val_ptr.hash();
     let trie: StandardTrie<'_, F> = if not_dummy.get_value() == Some(true) {
         Trie::new_with_root(
             poseidon_cache,
@@ -303,7 +303,68 @@ impl<F: LurkField> CoCircuit<F> for InsertCoprocessor<F> {
             cs,
             root_ptr,
             key_ptr,
-            val_ptr,
+            val_ptr.hash(),
+            not_dummy,
+            &s.poseidon_cache,
+            &s.inverse_poseidon_cache,
+        )?;
+
+        let num_tag = g.alloc_tag(cs, &ExprTag::Num);
+        Ok(AllocatedPtr::from_parts(num_tag.clone(), new_root_val))
+    }
+}
+
+/// Removes a key from a trie by inserting the empty element in its place. Since the empty subtrees are canonical, the
+/// resulting root is the same as if the key had never been inserted.
+#[derive(Clone, Debug, Serialize, Default, Deserialize)]
+pub struct DeleteCoprocessor<F> {
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> Coprocessor<F> for DeleteCoprocessor<F> {
+    fn eval_arity(&self) -> usize {
+        2
+    }
+
+    fn evaluate_simple(&self, s: &Store<F>, args: &[Ptr]) -> Ptr {
+        let root_ptr = &args[0];
+        let key_ptr = &args[1];
+        let root_scalar = *s.hash_ptr(root_ptr).value();
+        let key_scalar = *s.hash_ptr(key_ptr).value();
+        let mut trie: StandardTrie<'_, F> =
+            Trie::new_with_root(&s.poseidon_cache, &s.inverse_poseidon_cache, root_scalar);
+        trie.delete(key_scalar).unwrap();
+
+        s.num(trie.root)
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+}
+
+impl<F: LurkField> CoCircuit<F> for DeleteCoprocessor<F> {
+    fn arity(&self) -> usize {
+        2
+    }
+
+    fn synthesize_simple<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &lurk::lem::circuit::GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+    ) -> Result<AllocatedPtr<F>, SynthesisError> {
+        let root_ptr = &args[0];
+        let key_ptr = &args[1];
+
+        let empty = g.alloc_const_cloned(cs, StandardTrie::<'_, F>::empty_element());
+        let new_root_val = synthesize_insert_aux(
+            cs,
+            root_ptr,
+            key_ptr,
+            &empty,
             not_dummy,
             &s.poseidon_cache,
             &s.inverse_poseidon_cache,
@@ -320,10 +381,11 @@ pub fn install<F: LurkField>(state: &Rc<RefCell<State>>, lang: &mut Lang<F, Trie
     lang.add_coprocessor(".lurk.trie.new", NewCoprocessor::default());
     lang.add_coprocessor(".lurk.trie.lookup", LookupCoprocessor::default());
     lang.add_coprocessor(".lurk.trie.insert", InsertCoprocessor::default());
+    lang.add_coprocessor(".lurk.trie.delete", DeleteCoprocessor::default());
 
     let trie_package_name: Symbol = ".lurk.trie".into();
     let mut package = Package::new(trie_package_name.into());
-    for name in ["new", "lookup", "insert"].into_iter() {
+    for name in ["new", "lookup", "insert", "delete"].into_iter() {
         package.intern(name);
     }
     state.borrow_mut().add_package(package);
@@ -761,6 +823,23 @@ impl<'a, F: LurkField, const ARITY: usize, const HEIGHT: usize> Trie<'a, F, ARIT
         Ok(inserted)
     }
 
+    /// Removes `key`, returning whether it was present.
+    pub fn delete(&mut self, key: F) -> Result<bool, Error<F>> {
+        let (_delete_proof, deleted) = self.prove_delete(key)?;
+
+        Ok(deleted)
+    }
+
+    /// A deletion is proved as the insertion of the empty element, and verified with `InsertProof::verify` for a
+    /// `new_value` of zero.
+    pub fn prove_delete(
+        &mut self,
+        key: F,
+    ) -> Result<(InsertProof<F, ARITY, HEIGHT>, bool), Error<F>> {
+        let path = Self::path(key);
+        self.insert_at_path(&path, Self::empty_element())
+    }
+
     pub fn prove_insert(
         &mut self,
         key: F,
@@ -1196,4 +1275,37 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_delete() {
+        let mut t3: Trie<'_, Fr, 8, 3> =
+            Trie::new_with_capacity(poseidon_cache(), inverse_poseidon_cache(), 512);
+        let empty_root = t3.root;
+        let key = Fr::from_u64(500);
+        let val = Fr::from_u64(123);
+        let key2 = Fr::from_u64(127);
+        let val2 = Fr::from_u64(987);
+
+        assert!(!t3.delete(key).unwrap());
+        assert_eq!(empty_root, t3.root);
+
+        t3.insert(key, val).unwrap();
+        let root_with_key = t3.root;
+        t3.insert(key2, val2).unwrap();
+
+        let old_root = t3.root;
+        let (delete_proof, deleted) = t3.prove_delete(key2).unwrap();
+        assert!(deleted);
+        assert_eq!(root_with_key, t3.root);
+        assert_eq!(None, t3.lookup(key2).unwrap());
+        assert_eq!(Some(val), t3.lookup(key).unwrap());
+
+        let fresh_p = PoseidonCache::<Fr>::default();
+        let verified =
+            delete_proof.verify(old_root, t3.root, key2, Some(val2), Fr::zero(), &fresh_p);
+        assert!(verified);
+
+        assert!(t3.delete(key).unwrap());
+        assert_eq!(empty_root, t3.root);
+    }
 }
@@ -0,0 +1,299 @@
+//! An audit log of the Fiat-Shamir transcript.
+//!
+//! The challenge `r` is the hash of the transcript built natively when it's finalized, while the circuits rebuild the
+//! transcript item by item, chunk after chunk. When the two disagree, the circuit only reports that
+//! `r_matches_transcript` is unsatisfied. A `TranscriptAudit` records every item finalizing the transcript hashed, in
+//! order, with the transcript after each one, so that `r` can be re-derived step by step and compared against the
+//! items the chunks are replayed with and the transcripts they actually started from and ended with.
+
+use anyhow::{bail, Result};
+use std::fmt;
+
+use super::{ChunkWitness, LogMemo, MemoSet, Query, Scope, Transcript, TranscriptScheme};
+use crate::field::LurkField;
+use crate::lem::{pointers::Ptr, store::Store, tag::Tag};
+use crate::tag::ExprTag;
+use crate::z_ptr::ZPtr;
+
+/// What an item of the transcript records
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TranscriptItemKind {
+    /// `(key . value)`, or `((key . value) . multiplicity)` with top-level dedup
+    ToplevelInsertion,
+    /// The list of every top-level `(key . value)`, with public top-level queries
+    ToplevelCommitment,
+    /// `(key . value)` of a subquery, when internal insertions are transcribed
+    Insertion,
+    /// `((key . value) . count)`
+    Removal,
+}
+
+impl fmt::Display for TranscriptItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ToplevelInsertion => write!(f, "top-level insertion"),
+            Self::ToplevelCommitment => write!(f, "top-level commitment"),
+            Self::Insertion => write!(f, "insertion"),
+            Self::Removal => write!(f, "removal"),
+        }
+    }
+}
+
+/// An item `Scope::finalize_transcript` added to the transcript, kept unhashed until it's audited
+#[derive(Clone, Copy, Debug)]
+pub(super) struct TranscriptItem {
+    pub(super) kind: TranscriptItemKind,
+    pub(super) item: Ptr,
+    pub(super) count: Option<usize>,
+    /// The transcript once the item was added
+    pub(super) transcript: Ptr,
+}
+
+/// An item added to the transcript
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscriptAuditEntry<F: LurkField> {
+    pub kind: TranscriptItemKind,
+    /// `(key . value)`, or the list of them of a top-level commitment
+    pub kv: ZPtr<Tag, F>,
+    /// The count recorded with `kv`, for removals and deduplicated top-level insertions
+    pub count: Option<usize>,
+    /// The transcript once the item was added
+    pub transcript: ZPtr<Tag, F>,
+}
+
+/// Every item added to the transcript, in order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscriptAudit<F: LurkField> {
+    pub entries: Vec<TranscriptAuditEntry<F>>,
+    /// The challenge the memoset was finalized with
+    pub r: F,
+    /// How the items were hashed into the transcript
+    pub scheme: TranscriptScheme,
+}
+
+impl<F: LurkField> TranscriptAudit<F> {
+    pub(super) fn record(
+        &mut self,
+        s: &Store<F>,
+        kind: TranscriptItemKind,
+        kv: Ptr,
+        count: Option<usize>,
+        transcript: &Transcript<F>,
+    ) {
+        self.entries.push(TranscriptAuditEntry {
+            kind,
+            kv: s.hash_ptr(&kv),
+            count,
+            transcript: s.hash_ptr(&transcript.acc),
+        });
+    }
+
+    fn describe(&self, s: &Store<F>, position: usize) -> String {
+        match position.checked_sub(1) {
+            None => "before the first item".into(),
+            Some(i) => {
+                let entry = &self.entries[i];
+                let kv = s.to_ptr(&entry.kv).fmt_to_string_simple(s);
+                match entry.count {
+                    Some(count) => format!("after item {i}, the {} {kv} x{count}", entry.kind),
+                    None => format!("after item {i}, the {} {kv}", entry.kind),
+                }
+            }
+        }
+    }
+
+    /// Re-derives the transcript one item at a time, failing at the first entry whose recorded transcript doesn't
+    /// follow from the previous one, or if the last doesn't hash to `r`. Returns `r` otherwise.
+    pub fn rederive(&self, s: &Store<F>) -> Result<F> {
+        let mut transcript = Transcript::new(s);
+        for (i, entry) in self.entries.iter().enumerate() {
+            transcript.add_kv(s, self.scheme, s.to_ptr(&entry.kv), entry.count);
+            if s.hash_ptr(&transcript.acc) != entry.transcript {
+                bail!(
+                    "The transcript doesn't follow from {}",
+                    self.describe(s, i + 1)
+                );
+            }
+        }
+        let hash = *s.hash_ptr(&transcript.acc).value();
+        if hash != self.r {
+            bail!(
+                "The transcript hashes to {}, not to r = {}",
+                hash.hex_digits(),
+                self.r.hex_digits()
+            );
+        }
+        Ok(hash)
+    }
+
+    /// Checks that `replayed` adds the same items as this audit, in the same order. Fails naming the first that
+    /// differs.
+    pub fn check_replay(&self, s: &Store<F>, replayed: &Self) -> Result<()> {
+        for (i, (entry, other)) in self.entries.iter().zip(&replayed.entries).enumerate() {
+            if entry != other {
+                let kv = s.to_ptr(&other.kv).fmt_to_string_simple(s);
+                bail!(
+                    "The replay adds the {} {kv} {}",
+                    other.kind,
+                    self.describe(s, i)
+                );
+            }
+        }
+        if self.entries.len() != replayed.entries.len() {
+            bail!(
+                "The replay adds {} items, but finalizing the transcript added {}",
+                replayed.entries.len(),
+                self.entries.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// How many items the transcript had when it was `transcript`, if it ever was
+    pub fn position(&self, transcript: &ZPtr<Tag, F>) -> Option<usize> {
+        if *transcript.tag() == Tag::Expr(ExprTag::Nil) {
+            return Some(0);
+        }
+        self.entries
+            .iter()
+            .position(|entry| entry.transcript == *transcript)
+            .map(|i| i + 1)
+    }
+
+    /// Checks that `chunks` start and end with the transcripts of the audit, one after the other, and that the last
+    /// ends with the whole transcript. Fails naming the first chunk that doesn't, and the last item it agrees with.
+    pub fn check_chunks(&self, s: &Store<F>, chunks: &[ChunkWitness<F>]) -> Result<()> {
+        let mut expected = None;
+        for chunk in chunks {
+            let name = format!(
+                "Chunk {} of query index {}",
+                chunk.chunk_index, chunk.query_index
+            );
+            let Some(start) = self.position(&chunk.z_in.transcript) else {
+                bail!("{name} starts from a transcript the native one never reaches");
+            };
+            if let Some(expected) = expected.filter(|expected| *expected != start) {
+                bail!(
+                    "{name} starts {}, but the previous chunk ended {}",
+                    self.describe(s, start),
+                    self.describe(s, expected)
+                );
+            }
+            let Some(end) = self
+                .position(&chunk.z_out.transcript)
+                .filter(|end| *end >= start)
+            else {
+                bail!(
+                    "{name} ends with a transcript the native one doesn't reach after it starts {}",
+                    self.describe(s, start)
+                );
+            };
+            expected = Some(end);
+        }
+        if let Some(end) = expected.filter(|end| *end != self.entries.len()) {
+            bail!(
+                "The last chunk ends {}, before the end of the transcript",
+                self.describe(s, end)
+            );
+        }
+        Ok(())
+    }
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    fn empty_audit(&self) -> TranscriptAudit<F> {
+        TranscriptAudit {
+            entries: Vec::new(),
+            r: *self.memoset.r().expect("transcript not finalized"),
+            scheme: self.transcript_scheme,
+        }
+    }
+
+    /// Records the items finalizing the transcript hashed, finalizing it if necessary. A scope restored from a snapshot
+    /// doesn't carry them, so they're rebuilt the way `finalize_transcript` built them.
+    pub fn transcript_audit(&mut self, s: &Store<F>) -> TranscriptAudit<F> {
+        self.ensure_transcript_finalized(s);
+        let mut audit = self.empty_audit();
+        let rebuilt;
+        let items = match &self.transcript_items {
+            Some(items) => items,
+            None => {
+                let mut items = Vec::new();
+                self.build_transcript(s, &mut items);
+                rebuilt = items;
+                &rebuilt
+            }
+        };
+        audit.entries = items
+            .iter()
+            .map(|item| TranscriptAuditEntry {
+                kind: item.kind,
+                kv: s.hash_ptr(&item.item),
+                count: item.count,
+                transcript: s.hash_ptr(&item.transcript),
+            })
+            .collect();
+        audit
+    }
+
+    /// Records the items the chunks add to the transcript when it's replayed to schedule them, which should be those
+    /// of `transcript_audit`.
+    pub fn replay_audit(&mut self, s: &Store<F>) -> TranscriptAudit<F> {
+        self.ensure_transcript_finalized(s);
+        let mut audit = self.empty_audit();
+        self.replay_with(s, Some(&mut audit));
+        audit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::demo::DemoQuery;
+
+    #[test]
+    fn test_transcript_audit() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(true, 2);
+        scope.query(s, s.read_with_default_state("(factorial . 3)").unwrap());
+        scope.query(s, s.read_with_default_state("(factorial . 2)").unwrap());
+
+        let audit = scope.transcript_audit(s);
+        let count = |kind| audit.entries.iter().filter(|e| e.kind == kind).count();
+        assert_eq!(2, count(TranscriptItemKind::ToplevelInsertion));
+        // Factorial 3, 2 and 1 each insert their subquery, and the four of them are removed.
+        assert_eq!(3, count(TranscriptItemKind::Insertion));
+        assert_eq!(4, count(TranscriptItemKind::Removal));
+        assert_eq!(audit.r, audit.rederive(s).unwrap());
+        audit.check_replay(s, &scope.replay_audit(s)).unwrap();
+
+        let mut chunks = scope.synthesize_chunks_parallel(s).unwrap();
+        audit.check_chunks(s, &chunks).unwrap();
+
+        // Finalizing and replaying disagree on a count
+        let mut diverging = scope.clone();
+        let items = diverging.transcript_items.as_mut().unwrap();
+        items[5].count = items[5].count.map(|count| count + 1);
+        let err = diverging
+            .transcript_audit(s)
+            .check_replay(s, &scope.replay_audit(s))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("after item 4"), "{err}");
+
+        let mut tampered = audit.clone();
+        tampered.entries[4].kv = s.hash_ptr(&s.num_u64(7));
+        let err = tampered.rederive(s).unwrap_err().to_string();
+        assert!(err.contains("after item 4"), "{err}");
+
+        // A chunk that skips an item of the transcript
+        chunks[1].z_out.transcript = audit.entries[audit.entries.len() - 2].transcript;
+        let err = audit.check_chunks(s, &chunks).unwrap_err().to_string();
+        assert!(err.contains("before the end of the transcript"), "{err}");
+        chunks.pop();
+        let err = audit.check_chunks(s, &chunks).unwrap_err().to_string();
+        assert!(err.contains("before the end of the transcript"), "{err}");
+    }
+}
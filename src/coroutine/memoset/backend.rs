@@ -0,0 +1,508 @@
+//! Folding the chunks of a `Scope` with interchangeable recursive SNARKs.
+//!
+//! Each chunk is a `ChunkCircuit`, a step circuit over the 12 field elements of the coroutine IO
+//! `[c, e, k, memoset_acc, transcript, r]` (a tag and a hash per pointer), whose circuit index is the chunk's query
+//! index. A `FoldingBackend` turns a sequence of such steps into a proof, so the `Scope` doesn't depend on any
+//! particular proving system. `NovaBackend` folds with Nova, which needs every step to have the same circuit and hence
+//! a single query type, `SuperNovaBackend` folds with SuperNova and `MockBackend` only checks that every step is
+//! satisfied, which is handy for tests.
+
+use ::nova::{
+    supernova::{NonUniformCircuit, RecursiveSNARK as SuperNovaSNARK},
+    traits::{
+        circuit::{StepCircuit, TrivialCircuit},
+        snark::{BatchedRelaxedR1CSSNARKTrait, RelaxedR1CSSNARKTrait},
+        Dual as DualEng,
+    },
+    RecursiveSNARK,
+};
+use anyhow::{bail, Context, Result};
+use bellpepper_core::{
+    num::AllocatedNum, test_cs::TestConstraintSystem, ConstraintSystem, SynthesisError,
+};
+use ff::Field;
+
+use super::{io::CoroutineIO, query::Query, ChunkSpec, CoroutinePublicInputs, LogMemo, Scope};
+use crate::field::LurkField;
+use crate::lem::{store::Store, tag::Tag};
+use crate::proof::nova::{CurveCycleEquipped, Dual, E1};
+use crate::proof::{nova, supernova};
+use crate::telemetry;
+use crate::z_ptr::ZPtr;
+
+/// The number of field elements in the IO of a chunk
+pub(super) const CHUNK_ARITY: usize = 2 * CoroutineIO::<()>::ARITY;
+
+/// A chunk of a `Scope` as a folding step.
+#[derive(Clone)]
+pub struct ChunkCircuit<'a, F: LurkField, Q> {
+    scope: &'a Scope<Q, LogMemo<F>>,
+    store: &'a Store<F>,
+    spec: ChunkSpec<F>,
+    /// The query index of the following chunk, which is the next program counter for SuperNova
+    next_query_index: usize,
+}
+
+impl<'a, F: LurkField, Q: Query<F> + Send + Sync> StepCircuit<F> for ChunkCircuit<'a, F, Q> {
+    fn arity(&self) -> usize {
+        CHUNK_ARITY
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        z: &[AllocatedNum<F>],
+    ) -> Result<Vec<AllocatedNum<F>>, SynthesisError> {
+        let z = CoroutineIO::from_nums(z);
+        let z_out = self
+            .scope
+            .synthesize_chunk_io(cs, self.store, &self.spec, &z)?;
+        Ok(z_out.into_nums())
+    }
+}
+
+impl<'a, F: LurkField, Q: Query<F> + Send + Sync> ::nova::supernova::StepCircuit<F>
+    for ChunkCircuit<'a, F, Q>
+{
+    fn arity(&self) -> usize {
+        CHUNK_ARITY
+    }
+
+    fn circuit_index(&self) -> usize {
+        self.spec.query_index
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        _pc: Option<&AllocatedNum<F>>,
+        z: &[AllocatedNum<F>],
+    ) -> Result<(Option<AllocatedNum<F>>, Vec<AllocatedNum<F>>), SynthesisError> {
+        let next_pc = AllocatedNum::alloc_infallible(&mut cs.namespace(|| "next_pc"), || {
+            F::from_u64(self.next_query_index as u64)
+        });
+        let z_out = <Self as StepCircuit<F>>::synthesize(self, cs, z)?;
+        Ok((Some(next_pc), z_out))
+    }
+}
+
+impl<'a, F: CurveCycleEquipped, Q: Query<F> + Send + Sync> NonUniformCircuit<E1<F>>
+    for ChunkCircuit<'a, F, Q>
+{
+    type C1 = Self;
+    type C2 = supernova::C2<F>;
+
+    fn num_circuits(&self) -> usize {
+        Q::count()
+    }
+
+    fn primary_circuit(&self, circuit_index: usize) -> Self {
+        self.scope.blank_chunk_circuit(self.store, circuit_index)
+    }
+
+    fn secondary_circuit(&self) -> Self::C2 {
+        Default::default()
+    }
+
+    fn initial_circuit_index(&self) -> usize {
+        self.spec.query_index
+    }
+}
+
+/// A recursive SNARK able to fold the chunks of a `Scope`.
+pub trait FoldingBackend<F: LurkField> {
+    /// A name identifying the backend, which is part of the key of cached public parameters
+    const ID: &'static str;
+
+    type PublicParams;
+    type Proof;
+
+    /// Sets up the public parameters, given a blank circuit for each query index
+    fn public_params<Q: Query<F> + Send + Sync>(
+        &self,
+        blank_circuits: &[ChunkCircuit<'_, F, Q>],
+    ) -> Result<Self::PublicParams>;
+
+    /// Folds `steps`, in order, starting from the IO `z0`
+    fn prove<Q: Query<F> + Send + Sync>(
+        &self,
+        pp: &Self::PublicParams,
+        steps: &[ChunkCircuit<'_, F, Q>],
+        z0: &[F],
+    ) -> Result<Self::Proof>;
+
+    /// Checks that `proof` folds steps from `z0` to `zi`
+    fn verify(
+        &self,
+        pp: &Self::PublicParams,
+        proof: &Self::Proof,
+        z0: &[F],
+        zi: &[F],
+    ) -> Result<bool>;
+
+    /// Like `verify`, but also checks that `z0` and `zi` are the IO of a completely proved `Scope` with the public
+    /// inputs `inputs`: the first chunk starts from the accumulator and transcript of its top-level queries, the
+    /// memoset ends empty and the transcript hashes to the challenge `r` used throughout.
+    fn verify_scope(
+        &self,
+        pp: &Self::PublicParams,
+        proof: &Self::Proof,
+        s: &Store<F>,
+        inputs: &CoroutinePublicInputs<F>,
+        z0: &[F],
+        zi: &[F],
+    ) -> Result<bool> {
+        Ok(inputs.check_toplevel_acc(s)
+            && inputs.matches_io(s, z0, zi)
+            && is_complete_scope_io(z0, zi)?
+            && self.verify(pp, proof, z0, zi)?)
+    }
+}
+
+/// Whether `z0` and `zi` are the IO of a completely proved `Scope`
+pub(crate) fn is_complete_scope_io<F: LurkField>(z0: &[F], zi: &[F]) -> Result<bool> {
+    let z0 = CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(z0)?;
+    let zi = CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(zi)?;
+    let r = *zi.r.value();
+    Ok(*zi.memoset_acc.value() == F::ZERO && *zi.transcript.value() == r && *z0.r.value() == r)
+}
+
+impl<F: LurkField, Q: Query<F> + Send + Sync> Scope<Q, LogMemo<F>> {
+    /// A chunk circuit without keys, which has the shape of every chunk for `query_index`
+    pub(super) fn blank_chunk_circuit<'a>(
+        &'a self,
+        s: &'a Store<F>,
+        query_index: usize,
+    ) -> ChunkCircuit<'a, F, Q> {
+        ChunkCircuit {
+            scope: self,
+            store: s,
+            spec: ChunkSpec {
+                query_index,
+                chunk_index: 0,
+                keys: vec![],
+                rc: self.rc_for_query(query_index),
+                acc: F::ZERO,
+                transcript: s.intern_nil(),
+            },
+            next_query_index: query_index,
+        }
+    }
+
+    /// Sets up the public parameters of `backend` for the chunks of this scope. They only depend on the query type
+    /// and on the number of queries proved per chunk, so they can be reused across scopes.
+    pub fn folding_public_params<B: FoldingBackend<F>>(
+        &self,
+        s: &Store<F>,
+        backend: &B,
+    ) -> Result<B::PublicParams> {
+        let blank_circuits = (0..Q::count())
+            .map(|query_index| self.blank_chunk_circuit(s, query_index))
+            .collect::<Vec<_>>();
+        backend.public_params(&blank_circuits)
+    }
+
+    /// Proves every chunk of this scope with `backend`, finalizing the transcript if necessary. Returns the proof with
+    /// the IO of the first and the last chunks, as expected by `FoldingBackend::verify_scope`.
+    pub fn prove_with<B: FoldingBackend<F>>(
+        &mut self,
+        s: &Store<F>,
+        backend: &B,
+        pp: &B::PublicParams,
+    ) -> Result<(B::Proof, Vec<F>, Vec<F>)> {
+        self.ensure_transcript_finalized(s);
+        let r = *self.memoset.r().expect("transcript not finalized");
+        let (specs, final_acc) = self.replay(s);
+        let Some(first) = specs.first() else {
+            bail!("No queries to prove");
+        };
+        let z0 =
+            CoroutineIO::chunk(s, first.acc, s.hash_ptr(&first.transcript), r).to_field_elements();
+        let transcript = self
+            .memoset
+            .transcript
+            .get()
+            .expect("transcript not finalized");
+        let zi =
+            CoroutineIO::chunk(s, final_acc, s.hash_ptr(&transcript.acc), r).to_field_elements();
+
+        let scope: &Self = self;
+        let next_query_indices = specs
+            .iter()
+            .skip(1)
+            .map(|spec| spec.query_index)
+            .chain([specs[specs.len() - 1].query_index])
+            .collect::<Vec<_>>();
+        let steps = specs
+            .into_iter()
+            .zip(next_query_indices)
+            .map(|(spec, next_query_index)| ChunkCircuit {
+                scope,
+                store: s,
+                spec,
+                next_query_index,
+            })
+            .collect::<Vec<_>>();
+
+        let proof = backend.prove(pp, &steps, &z0)?;
+        Ok((proof, z0, zi))
+    }
+}
+
+/// A backend that synthesizes each step in a test constraint system and checks that it's satisfied, without proving
+/// anything. Its proof is just the IO before the first step and after the last one.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MockBackend;
+
+impl<F: LurkField> FoldingBackend<F> for MockBackend {
+    const ID: &'static str = "mock";
+
+    type PublicParams = ();
+    type Proof = (Vec<F>, Vec<F>);
+
+    fn public_params<Q: Query<F> + Send + Sync>(
+        &self,
+        _blank_circuits: &[ChunkCircuit<'_, F, Q>],
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn prove<Q: Query<F> + Send + Sync>(
+        &self,
+        _pp: &(),
+        steps: &[ChunkCircuit<'_, F, Q>],
+        z0: &[F],
+    ) -> Result<(Vec<F>, Vec<F>)> {
+        let mut z = z0.to_vec();
+        for (i, step) in steps.iter().enumerate() {
+            let mut cs = TestConstraintSystem::<F>::new();
+            let z_in = z
+                .iter()
+                .enumerate()
+                .map(|(j, x)| {
+                    AllocatedNum::alloc_infallible(cs.namespace(|| format!("z{j}")), || *x)
+                })
+                .collect::<Vec<_>>();
+            let z_out = StepCircuit::synthesize(step, &mut cs, &z_in)?;
+            if let Some(unsatisfied) = cs.which_is_unsatisfied() {
+                bail!("Step {i} is unsatisfied at {unsatisfied}");
+            }
+            z = z_out
+                .iter()
+                .map(|x| x.get_value().context("Missing output value"))
+                .collect::<Result<_>>()?;
+        }
+        Ok((z0.to_vec(), z))
+    }
+
+    fn verify(&self, _pp: &(), proof: &Self::Proof, z0: &[F], zi: &[F]) -> Result<bool> {
+        Ok(proof.0 == z0 && proof.1 == zi)
+    }
+}
+
+/// Folds chunks with Nova. Only scopes with a single query type are supported.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NovaBackend;
+
+pub struct NovaChunksProof<F: CurveCycleEquipped> {
+    snark: RecursiveSNARK<E1<F>>,
+    num_steps: usize,
+}
+
+impl<F: CurveCycleEquipped> FoldingBackend<F> for NovaBackend {
+    const ID: &'static str = "nova";
+
+    type PublicParams = nova::PublicParams<F>;
+    type Proof = NovaChunksProof<F>;
+
+    fn public_params<Q: Query<F> + Send + Sync>(
+        &self,
+        blank_circuits: &[ChunkCircuit<'_, F, Q>],
+    ) -> Result<Self::PublicParams> {
+        let [circuit] = blank_circuits else {
+            bail!("Nova can't fold more than one query type");
+        };
+        let commitment_size_hint1 = <nova::SS1<F> as RelaxedR1CSSNARKTrait<E1<F>>>::ck_floor();
+        let commitment_size_hint2 =
+            <nova::SS2<F> as RelaxedR1CSSNARKTrait<DualEng<E1<F>>>>::ck_floor();
+        let pp = ::nova::PublicParams::setup(
+            circuit,
+            &TrivialCircuit::default(),
+            &*commitment_size_hint1,
+            &*commitment_size_hint2,
+        );
+        Ok(pp.into())
+    }
+
+    fn prove<Q: Query<F> + Send + Sync>(
+        &self,
+        pp: &Self::PublicParams,
+        steps: &[ChunkCircuit<'_, F, Q>],
+        z0: &[F],
+    ) -> Result<Self::Proof> {
+        let secondary_circuit = TrivialCircuit::default();
+        let z0_secondary = [Dual::<F>::ZERO];
+        let mut snark: Option<RecursiveSNARK<E1<F>>> = None;
+        for (i, step) in steps.iter().enumerate() {
+            let mut recursive_snark = match snark.take() {
+                Some(recursive_snark) => recursive_snark,
+                None => RecursiveSNARK::new(&pp.pp, step, &secondary_circuit, z0, &z0_secondary)?,
+            };
+            telemetry::fold_step(i, 0, || {
+                recursive_snark.prove_step(&pp.pp, step, &secondary_circuit)
+            })?;
+            snark = Some(recursive_snark);
+        }
+        Ok(NovaChunksProof {
+            snark: snark.context("No steps to prove")?,
+            num_steps: steps.len(),
+        })
+    }
+
+    fn verify(
+        &self,
+        pp: &Self::PublicParams,
+        proof: &Self::Proof,
+        z0: &[F],
+        zi: &[F],
+    ) -> Result<bool> {
+        let (zi_verified, _) =
+            proof
+                .snark
+                .verify(&pp.pp, proof.num_steps, z0, &[Dual::<F>::ZERO])?;
+        Ok(zi_verified == zi)
+    }
+}
+
+/// Folds chunks with SuperNova, with a circuit per query type.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SuperNovaBackend;
+
+impl<F: CurveCycleEquipped> FoldingBackend<F> for SuperNovaBackend {
+    const ID: &'static str = "supernova";
+
+    type PublicParams = supernova::PublicParams<F>;
+    type Proof = SuperNovaSNARK<E1<F>>;
+
+    fn public_params<Q: Query<F> + Send + Sync>(
+        &self,
+        blank_circuits: &[ChunkCircuit<'_, F, Q>],
+    ) -> Result<Self::PublicParams> {
+        let Some(circuit) = blank_circuits.first() else {
+            bail!("No query types to fold");
+        };
+        let commitment_size_hint1 =
+            <supernova::SS1<F> as BatchedRelaxedR1CSSNARKTrait<E1<F>>>::ck_floor();
+        let commitment_size_hint2 =
+            <supernova::SS2<F> as RelaxedR1CSSNARKTrait<DualEng<E1<F>>>>::ck_floor();
+        let pp = supernova::SuperNovaPublicParams::<F>::setup(
+            circuit,
+            &*commitment_size_hint1,
+            &*commitment_size_hint2,
+        );
+        Ok(pp.into())
+    }
+
+    fn prove<Q: Query<F> + Send + Sync>(
+        &self,
+        pp: &Self::PublicParams,
+        steps: &[ChunkCircuit<'_, F, Q>],
+        z0: &[F],
+    ) -> Result<Self::Proof> {
+        let z0_secondary = [Dual::<F>::ZERO];
+        let mut snark: Option<SuperNovaSNARK<E1<F>>> = None;
+        for (i, step) in steps.iter().enumerate() {
+            let secondary_circuit = step.secondary_circuit();
+            let mut recursive_snark = match snark.take() {
+                Some(recursive_snark) => recursive_snark,
+                None => {
+                    SuperNovaSNARK::new(&pp.pp, step, step, &secondary_circuit, z0, &z0_secondary)?
+                }
+            };
+            telemetry::fold_step(i, step.circuit_index(), || {
+                recursive_snark.prove_step(&pp.pp, step, &secondary_circuit)
+            })?;
+            snark = Some(recursive_snark);
+        }
+        snark.context("No steps to prove")
+    }
+
+    fn verify(
+        &self,
+        pp: &Self::PublicParams,
+        proof: &Self::Proof,
+        z0: &[F],
+        zi: &[F],
+    ) -> Result<bool> {
+        let (zi_verified, _) = proof.verify(&pp.pp, z0, &[Dual::<F>::ZERO])?;
+        Ok(zi_verified == zi)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr;
+    use pasta_curves::pallas;
+
+    use super::*;
+    use crate::coroutine::memoset::demo::DemoQuery;
+
+    fn check_backend<F: CurveCycleEquipped, B: FoldingBackend<F>>(backend: B) {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 3);
+        scope.query(s, s.read_with_default_state("(factorial . 4)").unwrap());
+        scope.query(s, s.read_with_default_state("(factorial . 3)").unwrap());
+
+        let pp = scope.folding_public_params(s, &backend).unwrap();
+        let (proof, z0, zi) = scope.prove_with(s, &backend, &pp).unwrap();
+        let inputs = scope.public_inputs(s);
+        assert!(backend
+            .verify_scope(&pp, &proof, s, &inputs, &z0, &zi)
+            .unwrap());
+
+        // the memoset isn't empty
+        let mut zi_bad = zi.clone();
+        zi_bad[7] = F::ONE;
+        assert!(!backend
+            .verify_scope(&pp, &proof, s, &inputs, &z0, &zi_bad)
+            .unwrap());
+        // a different starting point
+        let mut z0_bad = z0.clone();
+        z0_bad[7] += F::ONE;
+        assert!(!backend
+            .verify_scope(&pp, &proof, s, &inputs, &z0_bad, &zi)
+            .unwrap_or(false));
+        // an initial accumulator that doesn't insert the claimed top-level values
+        let mut forged = inputs.clone();
+        forged.toplevel[0].1 = s.hash_ptr(&s.num_u64(25));
+        assert!(!backend
+            .verify_scope(&pp, &proof, s, &forged, &z0, &zi)
+            .unwrap());
+        forged.initial_acc = z0[7];
+        assert!(!backend
+            .verify_scope(&pp, &proof, s, &forged, &z0, &zi)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_mock_backend() {
+        let s = &Store::<Fr>::default();
+        let mut scope: Scope<DemoQuery<Fr>, LogMemo<Fr>> = Scope::default();
+        assert!(scope.prove_with(s, &MockBackend, &()).is_err());
+
+        check_backend::<Fr, _>(MockBackend);
+        check_backend::<pallas::Scalar, _>(MockBackend);
+    }
+
+    #[test]
+    fn test_nova_backend() {
+        check_backend::<Fr, _>(NovaBackend);
+        check_backend::<pallas::Scalar, _>(NovaBackend);
+    }
+
+    #[test]
+    fn test_supernova_backend() {
+        check_backend::<Fr, _>(SuperNovaBackend);
+        check_backend::<pallas::Scalar, _>(SuperNovaBackend);
+    }
+}
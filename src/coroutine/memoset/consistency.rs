@@ -0,0 +1,337 @@
+//! Native checks of the bookkeeping of a `Scope`, to run before synthesis.
+//!
+//! An inconsistent `Scope` -- a dependency without a memoized value, a query of an index the circuits don't have, a
+//! memoset whose counts don't match the insertions the chunks will make -- would otherwise only show up as an
+//! unsatisfied constraint somewhere in a chunk, or as an accumulator that doesn't end at zero. `Scope::verify_native`
+//! reports each of these in terms of the queries involved instead.
+
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+use super::{LogMemo, MemoSet, Query, Scope, Transcript};
+use crate::field::LurkField;
+use crate::lem::{pointers::Ptr, store::Store};
+
+/// An inconsistency found by `Scope::verify_native`. Queries are printed as Lurk expressions.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Error)]
+pub enum ConsistencyIssue {
+    #[error("{key} is not a valid query")]
+    InvalidQuery { key: String },
+    #[error("{key} has index {index}, but there are only {count} query indices")]
+    IndexOutOfRange {
+        key: String,
+        index: usize,
+        count: usize,
+    },
+    #[error("{key} was inserted but has no memoized value")]
+    MissingValue { key: String },
+    #[error("{kv} is in the memoset, but {key} is memoized with another value")]
+    ValueMismatch { kv: String, key: String },
+    #[error("{kv} is in the memoset, but isn't a key-value pair: {error}")]
+    MalformedKv { kv: String, error: String },
+    #[error(
+        "{kv} is counted {counted} times in the memoset, but the chunks insert it {inserted} times"
+    )]
+    UnbalancedCount {
+        kv: String,
+        counted: usize,
+        inserted: usize,
+    },
+    #[error("{key} depends on [{recorded}], but evaluating it makes the subqueries [{evaluated}]")]
+    DependencyOrder {
+        key: String,
+        recorded: String,
+        evaluated: String,
+    },
+    #[error("{key} of index {index} is not proved by any chunk of that index")]
+    Unscheduled { key: String, index: usize },
+    #[error("the memoset accumulator ends the transcript at {acc} rather than zero")]
+    NonZeroAccumulator { acc: String },
+}
+
+/// What `Scope::verify_native` found
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsistencyReport {
+    /// The number of memoized queries checked
+    pub queries: usize,
+    /// The number of insertions into the memoset the chunks will make, top-level ones included
+    pub insertions: usize,
+    /// Whether the finalized transcript was replayed too
+    pub replayed: bool,
+    /// The inconsistencies found, sorted
+    pub issues: Vec<ConsistencyIssue>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} queries, {} insertions: ",
+            self.queries, self.insertions
+        )?;
+        if self.is_consistent() {
+            return write!(f, "consistent");
+        }
+        write!(f, "{} issues", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n  {issue}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// Checks natively that the bookkeeping of this scope can be proved:
+    /// - every memoized query, and every dependency, is a valid query with an index below `Q::count()`;
+    /// - every internal insertion and every dependency has a memoized value, which the memoset agrees with;
+    /// - the dependencies of every query are the subqueries its evaluation makes, in the order the chunk of its index
+    ///   inserts them;
+    /// - the memoset counts each key-value pair as many times as the chunks insert it, so that their removals balance;
+    /// - once the transcript is finalized, every query that isn't deferred is proved by a chunk of its index, and
+    ///   replaying the transcript brings the memoset accumulator back to zero, but for the deferred queries.
+    ///
+    /// This doesn't finalize the transcript, so it can be called at any point during evaluation.
+    pub fn verify_native(&self, s: &Store<F>) -> ConsistencyReport {
+        let show = |ptr: &Ptr| ptr.fmt_to_string_simple(s);
+        let mut issues = Vec::new();
+
+        let check_query = |key: &Ptr, issues: &mut Vec<ConsistencyIssue>| {
+            let Some(query) = Q::from_ptr(s, key) else {
+                issues.push(ConsistencyIssue::InvalidQuery { key: show(key) });
+                return None;
+            };
+            let index = query.index();
+            if index >= Q::count() {
+                issues.push(ConsistencyIssue::IndexOutOfRange {
+                    key: show(key),
+                    index,
+                    count: Q::count(),
+                });
+                return None;
+            }
+            Some(index)
+        };
+
+        let mut indices = HashMap::with_capacity(self.queries.len());
+        for key in self.queries.keys() {
+            if let Some(index) = check_query(key, &mut issues) {
+                indices.insert(*key, index);
+            }
+        }
+
+        for key in &self.internal_insertions {
+            if !self.queries.contains_key(key) {
+                issues.push(ConsistencyIssue::MissingValue { key: show(key) });
+            }
+        }
+
+        // The chunks insert each dependency of each query they prove, and the top-level insertions are made up front.
+        let mut inserted: HashMap<Ptr, usize> = HashMap::new();
+        for kv in &self.toplevel_insertions {
+            *inserted.entry(*kv).or_insert(0) += self.toplevel_multiplicity(kv);
+        }
+        for (key, dependencies) in &self.dependencies {
+            if !self.queries.contains_key(key) {
+                issues.push(ConsistencyIssue::MissingValue { key: show(key) });
+            }
+            if self.deferred.contains(key) {
+                // The scope proving it inserts its subqueries.
+                continue;
+            }
+            for dependency in dependencies {
+                let k = dependency.to_ptr(s);
+                check_query(&k, &mut issues);
+                match self.queries.get(&k) {
+                    Some(v) => *inserted.entry(Transcript::make_kv(s, k, *v)).or_insert(0) += 1,
+                    None => issues.push(ConsistencyIssue::MissingValue { key: show(&k) }),
+                }
+            }
+        }
+
+        // Re-evaluating each query against the memoized values makes its subqueries again, without going any deeper.
+        let mut scratch = Self {
+            queries: self.queries.clone(),
+            ..Self::default()
+        };
+        for key in indices.keys() {
+            if self.deferred.contains(key) {
+                continue;
+            }
+            let query = Q::from_ptr(s, key).expect("checked above");
+            scratch.dependencies.clear();
+            query.eval(s, &mut scratch);
+            let show_all = |dependencies: Option<&Vec<Q>>| {
+                dependencies
+                    .into_iter()
+                    .flatten()
+                    .map(|q| show(&q.to_ptr(s)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            let recorded = show_all(self.dependencies.get(key));
+            let evaluated = show_all(scratch.dependencies.get(key));
+            if recorded != evaluated {
+                issues.push(ConsistencyIssue::DependencyOrder {
+                    key: show(key),
+                    recorded,
+                    evaluated,
+                });
+            }
+        }
+
+        for (kv, counted) in self.memoset.multiset.iter() {
+            let (key, value) = match s.try_car_cdr(kv) {
+                Ok(key_value) => key_value,
+                Err(error) => {
+                    issues.push(ConsistencyIssue::MalformedKv {
+                        kv: show(kv),
+                        error: error.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if self.queries.get(&key) != Some(&value) {
+                issues.push(ConsistencyIssue::ValueMismatch {
+                    kv: show(kv),
+                    key: show(&key),
+                });
+            }
+            let inserted = inserted.get(kv).copied().unwrap_or(0);
+            if inserted != counted {
+                issues.push(ConsistencyIssue::UnbalancedCount {
+                    kv: show(kv),
+                    counted,
+                    inserted,
+                });
+            }
+        }
+        for (kv, inserted) in &inserted {
+            if self.memoset.count(kv) == 0 {
+                issues.push(ConsistencyIssue::UnbalancedCount {
+                    kv: show(kv),
+                    counted: 0,
+                    inserted: *inserted,
+                });
+            }
+        }
+
+        // Replaying a transcript with missing values would panic, so it's only done for otherwise consistent scopes.
+        let replayed = self.memoset.is_finalized() && issues.is_empty();
+        if replayed {
+            for (key, index) in &indices {
+                if self.deferred.contains(key) {
+                    continue;
+                }
+                let scheduled = self
+                    .unique_inserted_keys
+                    .get(index)
+                    .is_some_and(|keys| keys.contains(key));
+                if !scheduled {
+                    issues.push(ConsistencyIssue::Unscheduled {
+                        key: show(key),
+                        index: *index,
+                    });
+                }
+            }
+            // Deferred queries are left in the memoset.
+            let (_, acc) = self.replay(s);
+            let acc = acc - self.deferred_acc(s);
+            if acc != F::ZERO {
+                issues.push(ConsistencyIssue::NonZeroAccumulator {
+                    acc: acc.hex_digits(),
+                });
+            }
+        }
+
+        issues.sort();
+        ConsistencyReport {
+            queries: self.queries.len(),
+            insertions: inserted.values().sum(),
+            replayed,
+            issues,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::demo::DemoQuery;
+
+    #[test]
+    fn test_verify_native() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::default();
+        let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+        scope.query(s, fact_4);
+        scope.query(s, s.read_with_default_state("(factorial . 2)").unwrap());
+
+        let report = scope.verify_native(s);
+        assert!(report.is_consistent(), "{report}");
+        assert!(!report.replayed);
+        assert_eq!(5, report.queries);
+        // Two top-level insertions, and one for each of the four recursive calls.
+        assert_eq!(6, report.insertions);
+
+        scope.finalize_transcript(s);
+        let report = scope.verify_native(s);
+        assert!(report.is_consistent(), "{report}");
+        assert!(report.replayed);
+
+        // A query made twice but counted once more
+        let mut extra = scope.clone();
+        let kv = Transcript::make_kv(s, fact_4, s.num_u64(24));
+        extra.memoset.add(kv);
+        assert_eq!(
+            vec![ConsistencyIssue::UnbalancedCount {
+                kv: kv.fmt_to_string_simple(s),
+                counted: 2,
+                inserted: 1,
+            }],
+            extra.verify_native(s).issues
+        );
+
+        // Dependencies moved between queries: the insertions still balance, but the chunks would prove the wrong
+        // subqueries.
+        let mut reordered = scope.clone();
+        let fact_3 = s.read_with_default_state("(factorial . 3)").unwrap();
+        let deps_4 = reordered.dependencies.remove(&fact_4).unwrap();
+        let deps_3 = reordered.dependencies.insert(fact_3, deps_4).unwrap();
+        reordered.dependencies.insert(fact_4, deps_3);
+        let show = |ptr: Ptr| ptr.fmt_to_string_simple(s);
+        let fact_2 = s.read_with_default_state("(factorial . 2)").unwrap();
+        assert_eq!(
+            vec![
+                ConsistencyIssue::DependencyOrder {
+                    key: show(fact_3),
+                    recorded: show(fact_3),
+                    evaluated: show(fact_2),
+                },
+                ConsistencyIssue::DependencyOrder {
+                    key: show(fact_4),
+                    recorded: show(fact_2),
+                    evaluated: show(fact_3),
+                },
+            ],
+            reordered.verify_native(s).issues
+        );
+
+        // A dependency whose value was lost
+        let mut missing = scope;
+        let fact_0 = s.read_with_default_state("(factorial . 0)").unwrap();
+        missing.queries.remove(&fact_0);
+        let issues = missing.verify_native(s).issues;
+        assert!(issues.contains(&ConsistencyIssue::MissingValue {
+            key: fact_0.fmt_to_string_simple(s)
+        }));
+    }
+}
@@ -0,0 +1,212 @@
+use bellpepper_core::{
+    boolean::{AllocatedBit, Boolean},
+    ConstraintSystem, SynthesisError,
+};
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use super::{query::Query, LogMemo, Scope};
+use crate::circuit::gadgets::{data::construct_cons, pointer::AllocatedPtr};
+use crate::coprocessor::{CoCircuit, Coprocessor};
+use crate::field::LurkField;
+use crate::lem::circuit::GlobalAllocator;
+use crate::lem::{pointers::Ptr, store::Store};
+
+/// A coprocessor that lets Lurk code make queries to a `Scope`.
+///
+/// Applied to a query form, such as `(factorial . 4)`, it evaluates to the query's response. The query is made with
+/// `Scope::query`, so it's recorded as a top-level insertion, in the same transcript the coroutine circuits discharge
+/// when the `Scope` is proved. Forms that aren't queries evaluate to an error.
+///
+/// The circuit returns the same expression, environment and continuation as evaluation. It supplies the response as a
+/// hint and builds the key-value pair `(form . response)`, which is exactly the top-level insertion the proof of the
+/// `Scope` discharges: the correctness of the response is established by that proof, which must hence be made after
+/// evaluation, with the same scope shared by every clone of this coprocessor, and verified along with the proof of the
+/// evaluation.
+#[derive(Clone, Debug)]
+pub struct QueryCoprocessor<F: LurkField, Q> {
+    scope: Arc<Mutex<Scope<Q, LogMemo<F>>>>,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField, Q> QueryCoprocessor<F, Q> {
+    pub fn new(scope: Scope<Q, LogMemo<F>>) -> Self {
+        Self {
+            scope: Arc::new(Mutex::new(scope)),
+            _p: PhantomData,
+        }
+    }
+
+    /// The scope shared by the clones of this coprocessor, to be proved once evaluation is done
+    pub fn scope(&self) -> MutexGuard<'_, Scope<Q, LogMemo<F>>> {
+        self.scope.lock().expect("poisoned scope")
+    }
+}
+
+impl<F: LurkField, Q: Query<F> + Debug + Send> CoCircuit<F> for QueryCoprocessor<F, Q> {
+    fn arity(&self) -> usize {
+        1
+    }
+
+    fn synthesize<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        _not_dummy: &Boolean,
+        args: &[AllocatedPtr<F>],
+        env: &AllocatedPtr<F>,
+        cont: &AllocatedPtr<F>,
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        let [form] = args else {
+            return Err(SynthesisError::Unsatisfiable);
+        };
+        let form_ptr = form.get_value().map(|form| s.to_ptr(&form));
+        let response = form_ptr.and_then(|form| self.scope().queries.get(&form).copied());
+        let is_query = AllocatedBit::alloc(
+            cs.namespace(|| "is_query"),
+            form_ptr.map(|form| Q::from_ptr(s, &form).is_some()),
+        )?;
+        let response = AllocatedPtr::alloc(&mut cs.namespace(|| "response"), || {
+            let response = response.unwrap_or_else(|| s.intern_nil());
+            Ok(s.hash_ptr(&response))
+        })?;
+        // The top-level insertion of the query, which the proof of the scope removes
+        construct_cons(&mut cs.namespace(|| "kv"), g, s, form, &response)?;
+
+        let is_query = Boolean::Is(is_query);
+        let result = AllocatedPtr::pick(
+            cs.namespace(|| "response or form"),
+            &is_query,
+            &response,
+            form,
+        )?;
+        let cont_err = g.alloc_ptr(cs, &s.cont_error(), s);
+        let cont = AllocatedPtr::pick(cs.namespace(|| "result cont"), &is_query, cont, &cont_err)?;
+        Ok(vec![result, env.clone(), cont])
+    }
+}
+
+impl<F: LurkField, Q: Query<F> + Debug + Send> Coprocessor<F> for QueryCoprocessor<F, Q> {
+    fn eval_arity(&self) -> usize {
+        1
+    }
+
+    fn has_circuit(&self) -> bool {
+        true
+    }
+
+    fn evaluate(&self, s: &Store<F>, args: &[Ptr], env: &Ptr, cont: &Ptr) -> Vec<Ptr> {
+        let form = args[0];
+        if Q::from_ptr(s, &form).is_none() {
+            return vec![form, *env, s.cont_error()];
+        }
+        vec![self.evaluate_simple(s, args), *env, *cont]
+    }
+
+    fn evaluate_simple(&self, s: &Store<F>, args: &[Ptr]) -> Ptr {
+        self.scope().query(s, args[0])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::demo::DemoQuery;
+    use crate::eval::lang::Lang;
+    use crate::lem::eval::{evaluate_simple, make_eval_step_from_config, EvalConfig};
+    use crate::state::user_sym;
+
+    #[test]
+    fn test_query_from_lurk() {
+        let s = &Store::<F>::default();
+        let coproc = QueryCoprocessor::<F, DemoQuery<F>>::new(Scope::default());
+        let lang = Lang::new_with_bindings(vec![(user_sym("query"), coproc.clone())]);
+
+        let expr = s
+            .read_with_default_state("(+ (query (cons 'factorial 4)) (query '(factorial . 3)))")
+            .unwrap();
+        let lurk_step = make_eval_step_from_config(&EvalConfig::new_ivc(&lang));
+        let (output, ..) = evaluate_simple(Some((&lurk_step, &[], &lang)), expr, s, 100).unwrap();
+        assert_eq!(s.num_u64(30), output[0]);
+
+        let (output, ..) = evaluate_simple(
+            Some((&lurk_step, &[], &lang)),
+            s.read_with_default_state("(query 'factorial)").unwrap(),
+            s,
+            100,
+        )
+        .unwrap();
+        assert_eq!(s.cont_error(), output[2]);
+
+        let mut scope = coproc.scope();
+        assert_eq!(2, scope.toplevel_insertions.len());
+        assert_eq!(5, scope.queries.len());
+
+        let cs = &mut TestConstraintSystem::new();
+        let g = &mut GlobalAllocator::default();
+        scope.synthesize(cs, g, s).unwrap();
+        assert!(cs.is_satisfied());
+    }
+
+    /// Evaluates `form` with `evaluator` and synthesizes the call with `synthesizer`, returning whether the circuit is
+    /// satisfied and agrees with evaluation
+    fn check(
+        s: &Store<F>,
+        evaluator: &QueryCoprocessor<F, DemoQuery<F>>,
+        synthesizer: &QueryCoprocessor<F, DemoQuery<F>>,
+        form: Ptr,
+    ) -> bool {
+        let env = s.intern_empty_env();
+        let cont = s.cont_outermost();
+        let expected = evaluator.evaluate(s, &[form], &env, &cont);
+
+        let cs = &mut TestConstraintSystem::<F>::new();
+        let g = GlobalAllocator::default();
+        let alloc = |cs: &mut TestConstraintSystem<F>, name: &str, ptr: &Ptr| {
+            let z_ptr = s.hash_ptr(ptr);
+            AllocatedPtr::alloc_infallible(&mut cs.namespace(|| name.to_string()), || z_ptr)
+        };
+        let a_form = alloc(cs, "form", &form);
+        let a_env = alloc(cs, "env", &env);
+        let a_cont = alloc(cs, "cont", &cont);
+        let output = synthesizer
+            .synthesize(
+                cs,
+                &g,
+                s,
+                &Boolean::Constant(true),
+                &[a_form],
+                &a_env,
+                &a_cont,
+            )
+            .unwrap();
+        cs.is_satisfied()
+            && expected
+                .iter()
+                .zip(output)
+                .all(|(expected, output)| Some(s.hash_ptr(expected)) == output.get_value())
+    }
+
+    #[test]
+    fn test_query_circuit() {
+        let s = &Store::<F>::default();
+        let coproc = QueryCoprocessor::<F, DemoQuery<F>>::new(Scope::default());
+        let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+        assert!(check(s, &coproc, &coproc, fact_4));
+        // Forms that aren't queries lead to the error continuation in both.
+        let not_query = s.read_with_default_state("factorial").unwrap();
+        assert!(check(s, &coproc, &coproc, not_query));
+
+        // A circuit supplying another response disagrees with evaluation.
+        let forged = QueryCoprocessor::<F, DemoQuery<F>>::new(Scope::default());
+        forged.scope().queries.insert(fact_4, s.num_u64(25));
+        assert!(!check(s, &coproc, &forged, fact_4));
+    }
+}
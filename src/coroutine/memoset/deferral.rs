@@ -0,0 +1,268 @@
+//! Deferral of queries from one `Scope` to another.
+//!
+//! A large workload can be split between provers by letting a scope rely on the results of some queries without
+//! proving them. `Scope::defer` leaves a query to another scope: the chunks of this one still insert it into the
+//! memoset wherever it's used, but none of them removes it. The proof then ends with the memoset accumulator at the sum
+//! of the elements of the deferred key-value pairs, rather than at zero. Deferring requires a scope that transcribes
+//! its internal insertions, so that the challenge `r` is derived from a transcript recording every insertion, deferred
+//! ones included. The accumulator then binds the proof to the `Obligations` the scope exports, which verifiers check
+//! with `Obligations::matches_io`.
+//!
+//! Another scope discharges the obligations by proving the deferred queries as top-level queries. A verifier accepts
+//! the pair once that scope's proof is complete and its public inputs list every obligation, which
+//! `Obligations::is_discharged_by` checks.
+
+use anyhow::{bail, Result};
+
+use super::{CoroutineIO, CoroutinePublicInputs, LogMemo, MemoSet, Query, Scope, Transcript};
+use crate::field::LurkField;
+use crate::lem::{pointers::Ptr, store::Store, tag::Tag};
+use crate::z_ptr::ZPtr;
+
+/// The queries a scope deferred, as it relied on them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Obligations<F: LurkField> {
+    /// Each deferred query with its value and the number of times the deferring scope inserted it
+    pub claims: Vec<(ZPtr<Tag, F>, ZPtr<Tag, F>, usize)>,
+    /// A commitment to `claims`, in order, by which provers can refer to the obligations. `Obligations::matches_io`
+    /// rejects obligations whose commitment doesn't match their claims.
+    pub commitment: F,
+}
+
+impl<F: LurkField> Obligations<F> {
+    fn new(s: &Store<F>, claims: Vec<(ZPtr<Tag, F>, ZPtr<Tag, F>, usize)>) -> Self {
+        let commitment = Self::commit(s, &claims);
+        Self { claims, commitment }
+    }
+
+    fn commit(s: &Store<F>, claims: &[(ZPtr<Tag, F>, ZPtr<Tag, F>, usize)]) -> F {
+        let items = claims
+            .iter()
+            .map(|(key, value, count)| {
+                let kv = Transcript::make_kv(s, s.to_ptr(key), s.to_ptr(value));
+                Transcript::make_kv_count(s, kv, *count)
+            })
+            .collect();
+        *s.hash_ptr(&s.list(items)).value()
+    }
+
+    /// Whether `commitment` is the commitment to `claims`
+    pub fn is_consistent(&self, s: &Store<F>) -> bool {
+        Self::commit(s, &self.claims) == self.commitment
+    }
+
+    /// The memoset accumulator the claims leave under the challenge `r`
+    fn acc(&self, s: &Store<F>, r: F) -> Option<F> {
+        let mut acc = F::ZERO;
+        for (key, value, count) in &self.claims {
+            let kv = Transcript::make_kv(s, s.to_ptr(key), s.to_ptr(value));
+            let element: Option<F> = (r + s.hash_ptr(&kv).value()).invert().into();
+            acc += element? * F::from_u64(*count as u64);
+        }
+        Some(acc)
+    }
+
+    /// Whether `z0` and `zi` are the IO of a proof of a scope that proved everything but these obligations. Like
+    /// `is_complete_scope_io`, this doesn't verify the proof itself.
+    pub fn matches_io(&self, s: &Store<F>, z0: &[F], zi: &[F]) -> Result<bool> {
+        let z0 = CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(z0)?;
+        let zi = CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(zi)?;
+        let r = *zi.r.value();
+        Ok(self.is_consistent(s)
+            && *zi.transcript.value() == r
+            && *z0.r.value() == r
+            && self.acc(s, r) == Some(*zi.memoset_acc.value()))
+    }
+
+    /// Whether the scope with public inputs `inputs` proved every claim. Its proof must still be verified, and checked
+    /// to match `inputs`.
+    pub fn is_discharged_by(&self, inputs: &CoroutinePublicInputs<F>) -> bool {
+        inputs.final_acc == F::ZERO
+            && self
+                .claims
+                .iter()
+                .all(|(key, value, _)| inputs.toplevel.contains(&(*key, *value, 1)))
+    }
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// Leaves the proof of `key`, which must have been queried, to another scope. This scope's chunks keep inserting
+    /// it wherever they use it, but don't prove it, and hence don't insert its own subqueries either. Such a scope is
+    /// proved chunk by chunk, as by `prove_with`: `synthesize` still requires the memoset to end empty.
+    ///
+    /// # Panics
+    /// Panics if the scope doesn't transcribe its internal insertions, if the transcript is already finalized, if
+    /// `key` wasn't queried or if it returns several values
+    pub fn defer(&mut self, s: &Store<F>, key: Ptr) {
+        // Otherwise `r` wouldn't depend on the deferred values, and neither would the obligations the proof binds.
+        assert!(
+            self.transcribe_internal_insertions,
+            "only scopes that transcribe internal insertions can defer queries"
+        );
+        assert!(
+            !self.memoset.is_finalized(),
+            "queries must be deferred before the transcript is finalized"
+        );
+        assert!(
+            self.queries.contains_key(&key),
+            "only queries that were made can be deferred"
+        );
+        // Claims carry a single value, which must be the query's only memoset entry.
+        assert!(
+            Q::from_ptr(s, &key).map_or(true, |query| query.arity() == 1),
+            "queries with several values can't be deferred"
+        );
+        if !self.deferred.insert(key) {
+            return;
+        }
+        let subquery_kvs = self
+            .dependencies
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .map(|dependency| {
+                let k = dependency.to_ptr(s);
+                Transcript::make_kv(s, k, self.queries[&k])
+            })
+            .collect::<Vec<_>>();
+        for kv in &subquery_kvs {
+            self.memoset.multiset.remove(kv);
+        }
+    }
+
+    /// The keys, values and key-value pairs of the deferred queries this scope inserts, with how many times it does
+    fn deferred_kvs(&self, s: &Store<F>) -> Vec<(Ptr, Ptr, Ptr, usize)> {
+        self.deferred
+            .iter()
+            .filter_map(|key| {
+                let value = self.queries[key];
+                let kv = Transcript::make_kv(s, *key, value);
+                let count = self.memoset.count(&kv);
+                (count > 0).then_some((*key, value, kv, count))
+            })
+            .collect()
+    }
+
+    /// The queries this scope deferred and relies on, to be discharged by another scope. A deferred query that is only
+    /// a subquery of other deferred queries isn't one of them.
+    pub fn obligations(&self, s: &Store<F>) -> Obligations<F> {
+        let claims = self
+            .deferred_kvs(s)
+            .into_iter()
+            .map(|(key, value, _, count)| (s.hash_ptr(&key), s.hash_ptr(&value), count))
+            .collect();
+        Obligations::new(s, claims)
+    }
+
+    /// The memoset accumulator the deferred queries leave once every chunk has been proved
+    pub(super) fn deferred_acc(&self, s: &Store<F>) -> F {
+        self.deferred_kvs(s)
+            .iter()
+            .map(|(_, _, kv, count)| {
+                self.memoset
+                    .map_to_element(*s.hash_ptr(kv).value())
+                    .expect("transcript not finalized")
+                    * F::from_u64(*count as u64)
+            })
+            .fold(F::ZERO, |acc, element| acc + element)
+    }
+
+    /// Makes the claims of `obligations` top-level queries of this scope, so that its proof discharges them. Fails if
+    /// a claimed value isn't the value of its query.
+    pub fn discharge(&mut self, s: &Store<F>, obligations: &Obligations<F>) -> Result<()> {
+        for (key, value, _) in &obligations.claims {
+            let key = s.to_ptr(key);
+            let response = self.query(s, key);
+            if s.hash_ptr(&response) != *value {
+                bail!(
+                    "{} was claimed to be {}, but is {}",
+                    key.fmt_to_string_simple(s),
+                    s.to_ptr(value).fmt_to_string_simple(s),
+                    response.fmt_to_string_simple(s)
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::{demo::DemoQuery, FoldingBackend, MockBackend};
+
+    #[test]
+    fn test_deferral() {
+        let s = &Store::<F>::default();
+        let fact = |n: u64| {
+            s.read_with_default_state(&format!("(factorial . {n})"))
+                .unwrap()
+        };
+
+        // Alice proves factorial 5 down to 4, and from 2 on, leaving 3 to Bob.
+        let mut alice: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(true, 2);
+        alice.query(s, fact(5));
+        alice.defer(s, fact(3));
+        let obligations = alice.obligations(s);
+        assert_eq!(
+            vec![(s.hash_ptr(&fact(3)), s.hash_ptr(&s.num_u64(6)), 1)],
+            obligations.claims
+        );
+        assert!(obligations.is_consistent(s));
+
+        // Factorial 5, 4, 2, 1 and 0, two per chunk
+        assert_eq!(3, alice.synthesize_chunks_parallel(s).unwrap().len());
+        let (proof, z0, zi) = alice.prove_with(s, &MockBackend, &()).unwrap();
+        let inputs = alice.public_inputs(s);
+        assert!(!MockBackend
+            .verify_scope(&(), &proof, s, &inputs, &z0, &zi)
+            .unwrap());
+        assert!(MockBackend.verify(&(), &proof, &z0, &zi).unwrap());
+        assert!(obligations.matches_io(s, &z0, &zi).unwrap());
+        let report = alice.verify_native(s);
+        assert!(report.is_consistent(), "{report}");
+
+        let mut bob: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        bob.discharge(s, &obligations).unwrap();
+        let (proof, bob_z0, bob_zi) = bob.prove_with(s, &MockBackend, &()).unwrap();
+        assert!(MockBackend
+            .verify_scope(&(), &proof, s, &bob.public_inputs(s), &bob_z0, &bob_zi)
+            .unwrap());
+        let inputs = bob.public_inputs(s);
+        assert!(obligations.is_discharged_by(&inputs));
+
+        // Other claims neither match Alice's proof nor can be discharged, even with a matching commitment.
+        let mut wrong = obligations.clone();
+        wrong.claims[0].1 = s.hash_ptr(&s.num_u64(7));
+        assert!(!wrong.is_consistent(s));
+        assert!(!wrong.matches_io(s, &z0, &zi).unwrap());
+        let wrong = Obligations::new(s, wrong.claims);
+        assert!(wrong.is_consistent(s));
+        assert!(!wrong.matches_io(s, &z0, &zi).unwrap());
+        assert!(!wrong.is_discharged_by(&inputs));
+        assert!(Scope::<DemoQuery<F>, LogMemo<F>>::default()
+            .discharge(s, &wrong)
+            .is_err());
+        // Neither do fewer uses of the deferred query.
+        let mut fewer = obligations.clone();
+        fewer.claims[0].2 = 2;
+        let fewer = Obligations::new(s, fewer.claims);
+        assert!(!fewer.matches_io(s, &z0, &zi).unwrap());
+        // A tampered commitment is rejected too.
+        let mut tampered = obligations;
+        tampered.commitment += F::from_u64(1);
+        assert!(!tampered.matches_io(s, &z0, &zi).unwrap());
+    }
+
+    #[test]
+    #[should_panic = "only scopes that transcribe internal insertions can defer queries"]
+    fn test_deferral_requires_transcribed_insertions() {
+        let s = &Store::<F>::default();
+        let fact_5 = s.read_with_default_state("(factorial . 5)").unwrap();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        scope.query(s, fact_5);
+        scope.defer(s, fact_5);
+    }
+}
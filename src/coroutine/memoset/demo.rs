@@ -1,7 +1,7 @@
 use bellpepper_core::{num::AllocatedNum, ConstraintSystem, SynthesisError};
 
 use super::{
-    query::{CircuitQuery, Query, RecursiveQuery},
+    query::{versioned_query_body, versioned_symbol, CircuitQuery, Query, RecursiveQuery},
     CircuitScope, CircuitTranscript, LogMemo, LogMemoCircuit, Scope,
 };
 use crate::circuit::gadgets::constraints::alloc_is_zero;
@@ -12,15 +12,28 @@ use crate::lem::{pointers::Ptr, store::Store};
 use crate::symbol::Symbol;
 use crate::tag::{ExprTag, Tag};
 
+/// The version of `DemoQuery`, which is bumped when its semantics change
+const VERSION: u32 = 0;
+
+fn factorial_name() -> Symbol {
+    Symbol::sym(&["lurk", "user", "factorial"])
+}
+
+fn factorial_symbol() -> Symbol {
+    versioned_symbol(&factorial_name(), VERSION)
+}
+
+/// `(factorial . n)`, whose response is `n!`, computed by querying `(factorial . n-1)`
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
-pub(crate) enum DemoQuery<F> {
+pub enum DemoQuery<F> {
     Factorial(Ptr),
     Phantom(F),
 }
 
+/// The circuit of `DemoQuery`
 #[derive(Debug, Clone)]
-pub(crate) enum DemoCircuitQuery<F: LurkField> {
+pub enum DemoCircuitQuery<F: LurkField> {
     Factorial(AllocatedPtr<F>),
 }
 
@@ -49,21 +62,13 @@ impl<F: LurkField> Query<F> for DemoQuery<F> {
 
     fn symbol(&self) -> Symbol {
         match self {
-            Self::Factorial(_) => Symbol::sym(&["lurk", "user", "factorial"]),
+            Self::Factorial(_) => factorial_symbol(),
             _ => unreachable!(),
         }
     }
 
     fn from_ptr(s: &Store<F>, ptr: &Ptr) -> Option<Self> {
-        let (head, body) = s.car_cdr(ptr).expect("query should be cons");
-        let sym = s.fetch_sym(&head).expect("head should be sym");
-
-        if sym == Symbol::sym(&["lurk", "user", "factorial"]) {
-            let num = body;
-            Some(Self::Factorial(num))
-        } else {
-            None
-        }
+        versioned_query_body(s, ptr, &factorial_name(), VERSION).map(Self::Factorial)
     }
 
     fn to_ptr(&self, s: &Store<F>) -> Ptr {
@@ -106,12 +111,10 @@ impl<F: LurkField> Query<F> for DemoQuery<F> {
 }
 
 impl<F: LurkField> RecursiveQuery<F> for DemoCircuitQuery<F> {
-    // It would be nice if this could be passed to `CircuitQuery::recurse` as an optional closure, rather than be a
-    // trait method. That would allow more generality. The types get complicated, though. For generality, we should
-    // support a context object that can be initialized once in `synthesize_eval` and be passed through for use here.
     fn post_recursion<CS: ConstraintSystem<F>>(
         &self,
         cs: &mut CS,
+        _ctx: &Self::Ctx,
         subquery_result: AllocatedPtr<F>,
     ) -> Result<AllocatedPtr<F>, SynthesisError> {
         match self {
@@ -132,6 +135,9 @@ impl<F: LurkField> RecursiveQuery<F> for DemoCircuitQuery<F> {
 }
 
 impl<F: LurkField> CircuitQuery<F> for DemoCircuitQuery<F> {
+    // Factorial needs nothing beyond `n`, which it already holds.
+    type Ctx = ();
+
     fn synthesize_eval<CS: ConstraintSystem<F>>(
         &self,
         cs: &mut CS,
@@ -179,6 +185,7 @@ impl<F: LurkField> CircuitQuery<F> for DemoCircuitQuery<F> {
                     g,
                     store,
                     scope,
+                    &(),
                     &new_num,
                     &n_is_zero.not(),
                     (&base_case, acc, transcript),
@@ -197,7 +204,7 @@ impl<F: LurkField> CircuitQuery<F> for DemoCircuitQuery<F> {
 
     fn symbol(&self) -> Symbol {
         match self {
-            Self::Factorial(_) => Symbol::sym(&["lurk", "user", "factorial"]),
+            Self::Factorial(_) => factorial_symbol(),
         }
     }
 }
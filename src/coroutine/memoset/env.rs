@@ -1,28 +1,54 @@
-use bellpepper_core::{num::AllocatedNum, ConstraintSystem, SynthesisError};
+use bellpepper_core::{boolean::Boolean, num::AllocatedNum, ConstraintSystem, SynthesisError};
 
 use super::{
-    query::{CircuitQuery, Query, RecursiveQuery},
+    query::{
+        none, some, synthesize_option, synthesize_values, versioned_query_body, versioned_symbol,
+        CircuitQuery, Query, RecursiveQuery,
+    },
     CircuitScope, CircuitTranscript, LogMemo, LogMemoCircuit, Scope,
 };
 use crate::circuit::gadgets::constraints::{alloc_equal, alloc_is_zero};
+use crate::circuit::gadgets::data::{alloc_is_tag, car_cdr, construct_cons, deconstruct_env};
 use crate::circuit::gadgets::pointer::AllocatedPtr;
-use crate::coprocessor::gadgets::{construct_cons, deconstruct_env};
 use crate::field::LurkField;
 use crate::lem::circuit::GlobalAllocator;
-use crate::lem::{pointers::Ptr, store::Store};
+use crate::lem::{pointers::Ptr, store::Store, tag::Tag};
 use crate::symbol::Symbol;
 use crate::tag::ExprTag;
 
-#[allow(dead_code)]
+/// The version of `EnvQuery`, which is bumped when the semantics of any of its queries change
+const VERSION: u32 = 0;
+
+fn env_name(op: &str) -> Symbol {
+    Symbol::sym(&["lurk", "env", op])
+}
+
+fn env_symbol(op: &str) -> Symbol {
+    versioned_symbol(&env_name(op), VERSION)
+}
+
+/// Queries about environments and association lists, each proved by recursing one binding or entry at a time
 #[derive(Debug, Clone)]
-pub(crate) enum EnvQuery<F> {
+pub enum EnvQuery<F> {
+    /// `Lookup(var, env)` is the value of `var` in `env`, if bound, as an option
     Lookup(Ptr, Ptr),
+    /// `Diff(n, old, new)` is `t` if `new` is `old` with exactly `n` bindings pushed onto it, and `nil` otherwise. An
+    /// update pushes a binding that shadows the old one, so this covers both inserts and updates. Proving it costs one
+    /// binding per step, however deep the lookups into `old` are. It's `nil` if `n` isn't a number.
+    Diff(Ptr, Ptr, Ptr),
+    /// `Assoc(key, alist)` is the first entry of the association list `alist` for `key`, or `nil` if there's none, as
+    /// returned by `assoc`. Proving it costs one entry per step. The search ends at the first element of `alist` that
+    /// isn't an entry, so that anything can be looked up in.
+    Assoc(Ptr, Ptr),
     Phantom(F),
 }
 
+/// The circuit counterpart of `EnvQuery`
 #[derive(Debug, Clone)]
-pub(crate) enum EnvCircuitQuery<F: LurkField> {
+pub enum EnvCircuitQuery<F: LurkField> {
     Lookup(AllocatedNum<F>, AllocatedNum<F>),
+    Diff(AllocatedPtr<F>, AllocatedNum<F>, AllocatedNum<F>),
+    Assoc(AllocatedPtr<F>, AllocatedPtr<F>),
 }
 
 impl<F: LurkField> Query<F> for EnvQuery<F> {
@@ -33,14 +59,45 @@ impl<F: LurkField> Query<F> for EnvQuery<F> {
             Self::Lookup(var, env) => {
                 if let Some([v, val, new_env]) = s.pop_binding(*env) {
                     if s.ptr_eq(var, &v) {
-                        let t = s.intern_t();
-                        s.cons(val, t)
+                        some(s, val)
                     } else {
                         self.recursive_eval(scope, s, Self::Lookup(*var, new_env))
                     }
                 } else {
-                    let nil = s.intern_nil();
-                    s.cons(nil, nil)
+                    none(s)
+                }
+            }
+            Self::Diff(n, old, new) => {
+                if *n.tag() != Tag::Expr(ExprTag::Num) {
+                    return s.intern_nil();
+                }
+                let n = *s.hash_ptr(n).value();
+                if n == F::ZERO {
+                    if s.ptr_eq(old, new) {
+                        s.intern_t()
+                    } else {
+                        s.intern_nil()
+                    }
+                } else if let Some([_, _, rest]) = s.pop_binding(*new) {
+                    self.recursive_eval(scope, s, Self::Diff(s.num(n - F::ONE), *old, rest))
+                } else {
+                    s.intern_nil()
+                }
+            }
+            Self::Assoc(key, alist) => {
+                let cons = Tag::Expr(ExprTag::Cons);
+                if *alist.tag() != cons {
+                    return s.intern_nil();
+                }
+                let (entry, rest) = s.car_cdr(alist).expect("alist is a cons");
+                if *entry.tag() != cons {
+                    return s.intern_nil();
+                }
+                let (k, _) = s.car_cdr(&entry).expect("entry is a cons");
+                if s.ptr_eq(key, &k) {
+                    entry
+                } else {
+                    self.recursive_eval(scope, s, Self::Assoc(*key, rest))
                 }
             }
             _ => unreachable!(),
@@ -49,18 +106,25 @@ impl<F: LurkField> Query<F> for EnvQuery<F> {
 
     fn symbol(&self) -> Symbol {
         match self {
-            Self::Lookup(_, _) => Symbol::sym(&["lurk", "env", "lookup"]),
+            Self::Lookup(_, _) => env_symbol("lookup"),
+            Self::Diff(_, _, _) => env_symbol("diff"),
+            Self::Assoc(_, _) => env_symbol("assoc"),
             _ => unreachable!(),
         }
     }
 
     fn from_ptr(s: &Store<F>, ptr: &Ptr) -> Option<Self> {
-        let (head, body) = s.car_cdr(ptr).expect("query should be cons");
-        let sym = s.fetch_sym(&head).expect("head should be sym");
-
-        if sym == Symbol::sym(&["lurk", "env", "lookup"]) {
-            let (var, env) = s.car_cdr(&body).expect("query body should be cons");
+        let body = |op| versioned_query_body(s, ptr, &env_name(op), VERSION);
+        if let Some(body) = body("lookup") {
+            let (var, env) = s.try_car_cdr(&body).ok()?;
             Some(Self::Lookup(var, env))
+        } else if let Some(body) = body("diff") {
+            let (n, envs) = s.try_car_cdr(&body).ok()?;
+            let (old, new) = s.try_car_cdr(&envs).ok()?;
+            Some(Self::Diff(n, old, new))
+        } else if let Some(body) = body("assoc") {
+            let (key, alist) = s.try_car_cdr(&body).ok()?;
+            Some(Self::Assoc(key, alist))
         } else {
             None
         }
@@ -76,6 +140,15 @@ impl<F: LurkField> Query<F> for EnvQuery<F> {
                 let args = s.cons(*var, *env);
                 s.cons(lookup, args)
             }
+            Self::Diff(n, old, new) => {
+                let diff = s.intern_symbol(&self.symbol());
+                let args = s.cons(*n, s.cons(*old, *new));
+                s.cons(diff, args)
+            }
+            Self::Assoc(key, alist) => {
+                let assoc = s.intern_symbol(&self.symbol());
+                s.cons(assoc, s.cons(*key, *alist))
+            }
             _ => unreachable!(),
         }
     }
@@ -91,6 +164,21 @@ impl<F: LurkField> Query<F> for EnvQuery<F> {
                     AllocatedNum::alloc_infallible(&mut env_cs, || *s.hash_ptr(env).value());
                 Self::CQ::Lookup(allocated_var, allocated_env)
             }
+            EnvQuery::Diff(n, old, new) => {
+                let n = AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "n"), || s.hash_ptr(n));
+                let mut alloc = |name: &'static str, ptr: &Ptr| {
+                    AllocatedNum::alloc_infallible(&mut cs.namespace(|| name), || {
+                        *s.hash_ptr(ptr).value()
+                    })
+                };
+                Self::CQ::Diff(n, alloc("old", old), alloc("new", new))
+            }
+            EnvQuery::Assoc(key, alist) => {
+                let mut alloc = |name: &'static str, ptr: &Ptr| {
+                    AllocatedPtr::alloc_infallible(&mut cs.namespace(|| name), || s.hash_ptr(ptr))
+                };
+                Self::CQ::Assoc(alloc("key", key), alloc("alist", alist))
+            }
             _ => unreachable!(),
         }
     }
@@ -98,6 +186,8 @@ impl<F: LurkField> Query<F> for EnvQuery<F> {
     fn dummy_from_index(s: &Store<F>, index: usize) -> Self {
         match index {
             0 => Self::Lookup(s.num(0.into()), s.num(0.into())),
+            1 => Self::Diff(s.num(0.into()), s.intern_empty_env(), s.intern_empty_env()),
+            2 => Self::Assoc(s.intern_nil(), s.intern_nil()),
             _ => unreachable!(),
         }
     }
@@ -105,18 +195,30 @@ impl<F: LurkField> Query<F> for EnvQuery<F> {
     fn index(&self) -> usize {
         match self {
             Self::Lookup(_, _) => 0,
+            Self::Diff(_, _, _) => 1,
+            Self::Assoc(_, _) => 2,
             _ => unreachable!(),
         }
     }
 
     fn count() -> usize {
-        1
+        3
+    }
+
+    /// A lookup returns the value of `var`, if bound, and whether it is, encoded as `some` or `none`.
+    fn arity(&self) -> usize {
+        match self {
+            Self::Lookup(_, _) => 2,
+            _ => 1,
+        }
     }
 }
 
 impl<F: LurkField> RecursiveQuery<F> for EnvCircuitQuery<F> {}
 
 impl<F: LurkField> CircuitQuery<F> for EnvCircuitQuery<F> {
+    type Ctx = ();
+
     fn synthesize_eval<CS: ConstraintSystem<F>>(
         &self,
         cs: &mut CS,
@@ -127,64 +229,145 @@ impl<F: LurkField> CircuitQuery<F> for EnvCircuitQuery<F> {
         transcript: &CircuitTranscript<F>,
     ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
         match self {
-            Self::Lookup(var, env) => {
-                let nil = store.intern_nil();
-                let t = store.intern_t();
-                let allocated_nil = g.alloc_ptr(cs, &nil, store);
-                let allocated_t = g.alloc_ptr(cs, &t, store);
-                let sym_tag = g.alloc_tag(&mut cs.namespace(|| "sym_tag"), &ExprTag::Sym);
+            Self::Lookup(..) => {
+                // The scope records a lookup's two values as an option.
+                let (values, acc, transcript) =
+                    self.synthesize_eval_values(cs, g, store, scope, acc, transcript)?;
+                let value = synthesize_values(&mut cs.namespace(|| "value"), g, store, &values)?;
+                Ok((value, acc, transcript))
+            }
+            Self::Diff(n, old, new) => {
+                let num_tag = g.alloc_tag(&mut cs.namespace(|| "num_tag"), &ExprTag::Num);
                 let env_tag = g.alloc_tag(&mut cs.namespace(|| "env_tag"), &ExprTag::Env);
+                let t = g.alloc_ptr(&mut cs.namespace(|| "t"), &store.intern_t(), store);
+                let nil = g.alloc_ptr(&mut cs.namespace(|| "nil"), &store.intern_nil(), store);
+
+                let n_is_num = alloc_is_tag(&mut cs.namespace(|| "n_is_num"), g, n, &ExprTag::Num)?;
+                let n_is_zero = alloc_is_zero(&mut cs.namespace(|| "n_is_zero"), n.hash())?;
+                let new_is_empty = alloc_is_zero(&mut cs.namespace(|| "new_is_empty"), new)?;
+                let new_is_old = alloc_equal(&mut cs.namespace(|| "new_is_old"), new, old)?;
+                // Out of bindings before `n` reaches zero, `new` can't be `old` with `n` more. Nor can it be with an
+                // `n` that isn't a number.
+                let is_immediate = or!(cs, &n_is_zero, &new_is_empty, &n_is_num.not())?;
+
+                let n_is_zero_num =
+                    Boolean::and(&mut cs.namespace(|| "n_is_zero_num"), &n_is_num, &n_is_zero)?;
+                let is_diff =
+                    Boolean::and(&mut cs.namespace(|| "is_diff"), &n_is_zero_num, &new_is_old)?;
+                let immediate_result = AllocatedPtr::pick(
+                    &mut cs.namespace(|| "immediate_result"),
+                    &is_diff,
+                    &t,
+                    &nil,
+                )?;
 
-                let env_is_empty = alloc_is_zero(&mut cs.namespace(|| "env_is_empty"), env)?;
-
-                let (next_var, next_val, new_env) = deconstruct_env(
+                let (_, _, rest) = deconstruct_env(
                     &mut cs.namespace(|| "deconstruct_env"),
                     store,
-                    &env_is_empty.not(),
-                    env,
+                    &new_is_empty.not(),
+                    new,
                 )?;
 
-                let var_matches = alloc_equal(&mut cs.namespace(|| "var_matches"), var, &next_var)?;
-                let is_immediate = or!(cs, &var_matches, &env_is_empty)?;
-
-                let immediate_val = AllocatedPtr::pick(
-                    &mut cs.namespace(|| "immediate_val"),
-                    &var_matches,
-                    &next_val,
-                    &allocated_nil,
-                )?;
-
-                let immediate_bound = AllocatedPtr::pick(
-                    &mut cs.namespace(|| "immediate_bound"),
-                    &var_matches,
-                    &allocated_t,
-                    &allocated_nil,
+                let n = n.hash();
+                let new_n = AllocatedNum::alloc(&mut cs.namespace(|| "new_n"), || {
+                    n.get_value()
+                        .map(|n| n - F::ONE)
+                        .ok_or(SynthesisError::AssignmentMissing)
+                })?;
+
+                // new_n * 1 = n - 1
+                cs.enforce(
+                    || "enforce_new_n",
+                    |lc| lc + new_n.get_variable(),
+                    |lc| lc + CS::one(),
+                    |lc| lc + n.get_variable() - CS::one(),
+                );
+
+                let new_n_alloc = AllocatedPtr::from_parts(num_tag.clone(), new_n);
+                let old_alloc = AllocatedPtr::from_parts(env_tag.clone(), old.clone());
+                let rest_alloc = AllocatedPtr::from_parts(env_tag.clone(), rest);
+
+                let envs = construct_cons(
+                    &mut cs.namespace(|| "envs"),
+                    g,
+                    store,
+                    &old_alloc,
+                    &rest_alloc,
                 )?;
-
-                let immediate_result = construct_cons(
-                    &mut cs.namespace(|| "immediate_result"),
+                let recursive_args = construct_cons(
+                    &mut cs.namespace(|| "recursive_args"),
                     g,
                     store,
-                    &immediate_val,
-                    &immediate_bound,
+                    &new_n_alloc,
+                    &envs,
                 )?;
 
-                let new_env_alloc = AllocatedPtr::from_parts(env_tag.clone(), new_env);
-                let var_alloc = AllocatedPtr::from_parts(sym_tag.clone(), var.clone());
+                self.recurse(
+                    cs,
+                    g,
+                    store,
+                    scope,
+                    &(),
+                    &recursive_args,
+                    &is_immediate.not(),
+                    (&immediate_result, acc, transcript),
+                )
+            }
+            Self::Assoc(key, alist) => {
+                let nil = g.alloc_ptr(&mut cs.namespace(|| "nil"), &store.intern_nil(), store);
 
-                let recursive_args = construct_cons(
-                    &mut cs.namespace(|| "recursive_args"),
+                let alist_is_cons = alloc_is_tag(
+                    &mut cs.namespace(|| "alist_is_cons"),
+                    g,
+                    alist,
+                    &ExprTag::Cons,
+                )?;
+                let (entry, rest, _) = car_cdr(
+                    &mut cs.namespace(|| "pop_entry"),
                     g,
                     store,
-                    &var_alloc,
-                    &new_env_alloc,
+                    &alist_is_cons,
+                    alist,
+                )?;
+                let entry_is_cons = alloc_is_tag(
+                    &mut cs.namespace(|| "entry_is_cons"),
+                    g,
+                    &entry,
+                    &ExprTag::Cons,
+                )?;
+                let has_entry = Boolean::and(
+                    &mut cs.namespace(|| "has_entry"),
+                    &alist_is_cons,
+                    &entry_is_cons,
+                )?;
+                let (k, _, _) = car_cdr(
+                    &mut cs.namespace(|| "entry_key"),
+                    g,
+                    store,
+                    &has_entry,
+                    &entry,
                 )?;
 
+                let key_matches = k.alloc_equal(&mut cs.namespace(|| "key_matches"), key)?;
+                let found = Boolean::and(&mut cs.namespace(|| "found"), &has_entry, &key_matches)?;
+                // Out of entries, `key` isn't in `alist`.
+                let is_immediate = or!(cs, &found, &has_entry.not())?;
+                let immediate_result = AllocatedPtr::pick(
+                    &mut cs.namespace(|| "immediate_result"),
+                    &found,
+                    &entry,
+                    &nil,
+                )?;
+
+                let recursive_args =
+                    construct_cons(&mut cs.namespace(|| "recursive_args"), g, store, key, &rest)?;
+
                 self.recurse(
                     cs,
                     g,
                     store,
                     scope,
+                    &(),
                     &recursive_args,
                     &is_immediate.not(),
                     (&immediate_result, acc, transcript),
@@ -193,6 +376,66 @@ impl<F: LurkField> CircuitQuery<F> for EnvCircuitQuery<F> {
         }
     }
 
+    fn synthesize_eval_values<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        store: &Store<F>,
+        scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        acc: &AllocatedPtr<F>,
+        transcript: &CircuitTranscript<F>,
+    ) -> Result<(Vec<AllocatedPtr<F>>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        let Self::Lookup(var, env) = self else {
+            let (value, acc, transcript) =
+                self.synthesize_eval(cs, g, store, scope, acc, transcript)?;
+            return Ok((vec![value], acc, transcript));
+        };
+        let sym_tag = g.alloc_tag(&mut cs.namespace(|| "sym_tag"), &ExprTag::Sym);
+        let env_tag = g.alloc_tag(&mut cs.namespace(|| "env_tag"), &ExprTag::Env);
+
+        let env_is_empty = alloc_is_zero(&mut cs.namespace(|| "env_is_empty"), env)?;
+
+        let (next_var, next_val, new_env) = deconstruct_env(
+            &mut cs.namespace(|| "deconstruct_env"),
+            store,
+            &env_is_empty.not(),
+            env,
+        )?;
+
+        let var_matches = alloc_equal(&mut cs.namespace(|| "var_matches"), var, &next_var)?;
+        let is_immediate = or!(cs, &var_matches, &env_is_empty)?;
+
+        // An unbound `var` is a provable result: `none`.
+        let immediate_values = synthesize_option(
+            &mut cs.namespace(|| "immediate_values"),
+            g,
+            store,
+            &var_matches,
+            &next_val,
+        )?;
+
+        let new_env_alloc = AllocatedPtr::from_parts(env_tag.clone(), new_env);
+        let var_alloc = AllocatedPtr::from_parts(sym_tag.clone(), var.clone());
+
+        let recursive_args = construct_cons(
+            &mut cs.namespace(|| "recursive_args"),
+            g,
+            store,
+            &var_alloc,
+            &new_env_alloc,
+        )?;
+
+        self.recurse_values(
+            cs,
+            g,
+            store,
+            scope,
+            &recursive_args,
+            &is_immediate.not(),
+            (&immediate_values[..], acc, transcript),
+        )
+    }
+
     fn from_ptr<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, ptr: &Ptr) -> Option<Self> {
         let query = EnvQuery::from_ptr(s, ptr);
         if let Some(q) = query {
@@ -208,6 +451,7 @@ impl<F: LurkField> CircuitQuery<F> for EnvCircuitQuery<F> {
                         });
                     Some(Self::Lookup(allocated_var, allocated_env))
                 }
+                q @ (EnvQuery::Diff(..) | EnvQuery::Assoc(..)) => Some(q.to_circuit(cs, s)),
                 _ => unreachable!(),
             }
         } else {
@@ -221,7 +465,9 @@ impl<F: LurkField> CircuitQuery<F> for EnvCircuitQuery<F> {
 
     fn symbol(&self) -> Symbol {
         match self {
-            Self::Lookup(_, _) => Symbol::sym(&["lurk", "env", "lookup"]),
+            Self::Lookup(_, _) => env_symbol("lookup"),
+            Self::Diff(_, _, _) => env_symbol("diff"),
+            Self::Assoc(_, _) => env_symbol("assoc"),
         }
     }
 }
@@ -230,6 +476,8 @@ impl<F: LurkField> CircuitQuery<F> for EnvCircuitQuery<F> {
 mod test {
     use super::*;
 
+    use crate::coroutine::memoset::query::fetch_option;
+    use crate::coroutine::memoset::{FoldingBackend, MockBackend};
     use crate::state::State;
     use crate::sym;
 
@@ -280,6 +528,164 @@ mod test {
         test(c, b_env, None);
         test(c, c_env, Some(three));
         test(c, a2_env, Some(three));
+
+        // Absence is a result like any other.
+        let absent = EnvQuery::Lookup(c, a_env).eval(&s, &mut scope);
+        assert_eq!(Some(None), fetch_option(&s, &absent));
+        let present = EnvQuery::Lookup(c, c_env).eval(&s, &mut scope);
+        assert_eq!(Some(Some(three)), fetch_option(&s, &present));
+
+        // Lookups agree with `assoc` on the bindings of the env as an association list.
+        for env in [empty, a_env, b_env, c_env, a2_env] {
+            let alist = s.env_alist(&env).unwrap();
+            for var in [a, b, c] {
+                let result = EnvQuery::Lookup(var, env).eval(&s, &mut scope);
+                let entry = s.assoc(&var, &alist).unwrap();
+                let expected = match fetch_option(&s, &result).unwrap() {
+                    Some(val) => s.cons(var, val),
+                    None => nil,
+                };
+                assert!(s.ptr_eq(&expected, &entry));
+            }
+        }
+        assert_eq!(
+            Some(vec![(a, four), (c, three), (b, two)]),
+            s.fetch_alist(&s.env_alist(&a2_env).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_lookup_entries() {
+        let s = &Store::<F>::default();
+        let a = s.intern_symbol(&sym!("a"));
+        let b = s.intern_symbol(&sym!("b"));
+        let empty = s.intern_empty_env();
+        let a_env = s.push_binding(a, s.num_u64(1), empty);
+        let b_env = s.push_binding(b, s.num_u64(2), a_env);
+
+        let scope = |dedup| {
+            let mut scope: Scope<EnvQuery<F>, LogMemo<F>> = Scope::new(true, 1);
+            if dedup {
+                scope = scope.with_toplevel_dedup();
+            }
+            // Each lookup passes both of its values to the one it recurses from as separate entries.
+            for (var, env) in [(a, b_env), (b, empty), (a, b_env)] {
+                scope.query(s, EnvQuery::Lookup(var, env).to_ptr(s));
+            }
+            scope
+        };
+        for dedup in [false, true] {
+            let cs = &mut TestConstraintSystem::new();
+            let g = &mut GlobalAllocator::default();
+            scope(dedup).synthesize(cs, g, s).unwrap();
+            assert!(cs.is_satisfied());
+
+            let mut scope = scope(dedup);
+            let (proof, z0, zi) = scope.prove_with(s, &MockBackend, &()).unwrap();
+            let inputs = scope.public_inputs(s);
+            assert!(inputs.toplevel.iter().all(|(_, _, arity)| *arity == 2));
+            assert!(MockBackend
+                .verify_scope(&(), &proof, s, &inputs, &z0, &zi)
+                .unwrap());
+
+            // Verifiers rebuild the entries of the top-level lookups from their arity.
+            let mut wrong_arity = inputs.clone();
+            wrong_arity.toplevel[0].2 = 1;
+            assert!(!wrong_arity.check_toplevel_acc(s));
+        }
+    }
+
+    #[test]
+    fn test_env_diff() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<EnvQuery<F>, LogMemo<F>> = Scope::default();
+        let a = s.intern_symbol(&sym!("a"));
+        let b = s.intern_symbol(&sym!("b"));
+
+        let empty = s.intern_empty_env();
+        let a_env = s.push_binding(a, s.num_u64(1), empty);
+        let b_env = s.push_binding(b, s.num_u64(2), a_env);
+        // An update of `a`
+        let a2_env = s.push_binding(a, s.num_u64(3), b_env);
+
+        let mut test = |n, old, new, expected: bool| {
+            let result = EnvQuery::Diff(s.num_u64(n), old, new).eval(s, &mut scope);
+            assert_eq!(expected, s.ptr_eq(&result, &s.intern_t()));
+        };
+
+        test(0, a_env, a_env, true);
+        test(1, a_env, b_env, true);
+        test(2, a_env, a2_env, true);
+        test(3, empty, a2_env, true);
+        test(1, a_env, a2_env, false);
+        test(3, a_env, a2_env, false);
+        test(1, b_env, a_env, false);
+        test(0, a_env, b_env, false);
+
+        // A count that isn't a number, even one hashing to zero
+        let not_num = s.char('\0');
+        let result = EnvQuery::Diff(not_num, a_env, a_env).eval(s, &mut scope);
+        assert!(s.ptr_eq(&result, &s.intern_nil()));
+
+        let query = EnvQuery::Diff(s.num_u64(2), a_env, a2_env).to_ptr(s);
+        assert!(matches!(
+            EnvQuery::from_ptr(s, &query),
+            Some(EnvQuery::Diff(n, old, new))
+                if s.ptr_eq(&n, &s.num_u64(2)) && s.ptr_eq(&old, &a_env) && s.ptr_eq(&new, &a2_env)
+        ));
+
+        // Each binding costs the same, so the constraints grow linearly with the size of the diff.
+        let constraints = |n, old, new| {
+            let mut scope: Scope<EnvQuery<F>, LogMemo<F>> = Scope::new(true, 1);
+            scope.query(s, EnvQuery::Diff(n, old, new).to_ptr(s));
+            scope.finalize_transcript(s);
+
+            let cs = &mut TestConstraintSystem::new();
+            let g = &mut GlobalAllocator::default();
+            scope.synthesize(cs, g, s).unwrap();
+            assert!(cs.is_satisfied());
+            cs.num_constraints()
+        };
+        let one = constraints(s.num_u64(1), b_env, a2_env);
+        let two = constraints(s.num_u64(2), a_env, a2_env);
+        let three = constraints(s.num_u64(3), empty, a2_env);
+        assert_eq!(two - one, three - two);
+        constraints(s.num_u64(2), b_env, a2_env);
+        constraints(not_num, a_env, a_env);
+    }
+
+    #[test]
+    fn test_env_assoc() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<EnvQuery<F>, LogMemo<F>> = Scope::default();
+        let read = |src: &str| s.read_with_default_state(src).unwrap();
+        let alist = read("((a . 1) (b . 2) (a . 3))");
+
+        // Assoc queries agree with `assoc`, and end the search at anything that isn't an entry.
+        for key in ["a", "b", "c"] {
+            let key = read(key);
+            let result = EnvQuery::Assoc(key, alist).eval(s, &mut scope);
+            assert_eq!(s.assoc(&key, &alist), Some(result));
+        }
+        for alist in ["nil", "42", "((a . 1) . 2)", "(b (a . 1))"] {
+            let result = EnvQuery::Assoc(read("a"), read(alist)).eval(s, &mut scope);
+            assert_eq!(s.intern_nil(), result);
+        }
+
+        let query = EnvQuery::Assoc(read("b"), alist).to_ptr(s);
+        assert!(matches!(
+            EnvQuery::from_ptr(s, &query),
+            Some(EnvQuery::Assoc(key, list)) if key == read("b") && list == alist
+        ));
+
+        let mut scope: Scope<EnvQuery<F>, LogMemo<F>> = Scope::new(true, 1);
+        for (key, alist) in [("c", alist), ("a", read("(b (a . 1))"))] {
+            scope.query(s, EnvQuery::Assoc(read(key), alist).to_ptr(s));
+        }
+        let cs = &mut TestConstraintSystem::new();
+        let g = &mut GlobalAllocator::default();
+        scope.synthesize(cs, g, s).unwrap();
+        assert!(cs.is_satisfied());
     }
 
     #[test]
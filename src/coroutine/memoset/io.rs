@@ -0,0 +1,164 @@
+//! The IO of coroutine circuits.
+//!
+//! A `CoroutineCircuit` receives the pointers of a `CoroutineIO` from the previous chunk and passes them on to the
+//! next. Folding backends see them flattened into field elements, the tag and then the hash of each pointer, in the
+//! order of the fields. New pointers, such as a program counter or extra accumulators, are added as fields here, and
+//! call sites refer to the IO by field rather than by position.
+
+use anyhow::{bail, Context, Result};
+use bellpepper_core::num::AllocatedNum;
+use serde::{Deserialize, Serialize};
+
+use crate::circuit::gadgets::pointer::AllocatedPtr;
+use crate::field::LurkField;
+use crate::lem::{store::Store, tag::Tag};
+use crate::tag::{ExprTag, Tag as XTag};
+use crate::z_ptr::ZPtr;
+
+/// The pointers a chunk starts from or ends with: `T` is `AllocatedPtr` in circuits and `ZPtr` for their values.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoroutineIO<T> {
+    pub c: T,
+    pub e: T,
+    pub k: T,
+    /// The memoset accumulator
+    pub memoset_acc: T,
+    /// The transcript so far
+    pub transcript: T,
+    /// The challenge the memoset maps its elements with
+    pub r: T,
+}
+
+impl<T> CoroutineIO<T> {
+    /// The number of pointers
+    pub const ARITY: usize = 6;
+
+    /// The IO from its pointers, in field order
+    ///
+    /// # Panics
+    /// Panics if there aren't `ARITY` pointers
+    pub fn from_vec(z: Vec<T>) -> Self {
+        let len = z.len();
+        let Ok([c, e, k, memoset_acc, transcript, r]) = <[T; 6]>::try_from(z) else {
+            panic!(
+                "expected {} pointers of coroutine IO, got {len}",
+                Self::ARITY
+            );
+        };
+        Self {
+            c,
+            e,
+            k,
+            memoset_acc,
+            transcript,
+            r,
+        }
+    }
+
+    /// The pointers, in field order
+    pub fn into_vec(self) -> Vec<T> {
+        vec![
+            self.c,
+            self.e,
+            self.k,
+            self.memoset_acc,
+            self.transcript,
+            self.r,
+        ]
+    }
+}
+
+impl<F: LurkField> CoroutineIO<AllocatedPtr<F>> {
+    /// The IO from the folded field elements, a tag and a hash per pointer
+    pub fn from_nums(z: &[AllocatedNum<F>]) -> Self {
+        assert_eq!(2 * Self::ARITY, z.len());
+        Self::from_vec(
+            z.chunks(2)
+                .map(|parts| AllocatedPtr::from_parts(parts[0].clone(), parts[1].clone()))
+                .collect(),
+        )
+    }
+
+    /// The folded field elements, a tag and a hash per pointer
+    pub fn into_nums(self) -> Vec<AllocatedNum<F>> {
+        self.into_vec()
+            .into_iter()
+            .flat_map(|ptr| [ptr.tag().clone(), ptr.hash().clone()])
+            .collect()
+    }
+
+    /// The values of the pointers, if known
+    pub fn get_value(&self) -> Option<CoroutineIO<ZPtr<Tag, F>>> {
+        let values = self
+            .clone()
+            .into_vec()
+            .iter()
+            .map(|ptr| ptr.get_value::<Tag>())
+            .collect::<Option<Vec<_>>>()?;
+        Some(CoroutineIO::from_vec(values))
+    }
+}
+
+impl<F: LurkField> CoroutineIO<ZPtr<Tag, F>> {
+    /// The IO of a chunk with memoset accumulator `acc`, transcript `transcript` and challenge `r`. Chunks don't use
+    /// `c`, `e` and `k`, which are left `nil`.
+    pub fn chunk(s: &Store<F>, acc: F, transcript: ZPtr<Tag, F>, r: F) -> Self {
+        let nil = s.hash_ptr(&s.intern_nil());
+        let num = |f| ZPtr::from_parts(Tag::Expr(ExprTag::Num), f);
+        Self {
+            c: nil,
+            e: nil,
+            k: nil,
+            memoset_acc: num(acc),
+            transcript,
+            r: num(r),
+        }
+    }
+
+    /// The folded field elements, a tag and a hash per pointer
+    pub fn to_field_elements(&self) -> Vec<F> {
+        self.clone()
+            .into_vec()
+            .iter()
+            .flat_map(|z_ptr| [z_ptr.tag_field(), *z_ptr.value()])
+            .collect()
+    }
+
+    /// The IO from the folded field elements, failing on a wrong number of elements or an unknown tag
+    pub fn from_field_elements(z: &[F]) -> Result<Self> {
+        if z.len() != 2 * Self::ARITY {
+            bail!("Expected {} field elements of IO", 2 * Self::ARITY);
+        }
+        let z_ptrs = z
+            .chunks(2)
+            .map(|parts| {
+                let tag = Tag::from_field(&parts[0]).context("Unknown tag in IO")?;
+                Ok(ZPtr::from_parts(tag, parts[1]))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::from_vec(z_ptrs))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+
+    #[test]
+    fn test_field_elements_roundtrip() {
+        let s = &Store::<F>::default();
+        let transcript = s.hash_ptr(&s.list(vec![s.num_u64(1)]));
+        let io = CoroutineIO::chunk(s, F::from(3), transcript, F::from(5));
+        let elements = io.to_field_elements();
+        assert_eq!(2 * CoroutineIO::<()>::ARITY, elements.len());
+        assert_eq!(F::from(3), elements[7]);
+        assert_eq!(io, CoroutineIO::from_field_elements(&elements).unwrap());
+
+        assert!(CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(&elements[1..]).is_err());
+        let mut unknown_tag = elements;
+        unknown_tag[0] = F::from(u64::MAX);
+        assert!(CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(&unknown_tag).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+//! Merging of independent `Scope`s into one.
+//!
+//! Scopes made separately, for instance by different users, can be merged before proving so that a single proof, and
+//! hence a single verification, covers all of them. This doesn't combine existing proofs: the merged scope is proved
+//! from scratch. Since queries are deterministic, replaying each scope's top-level queries into a fresh scope rebuilds
+//! all the bookkeeping the merged proof needs, and subqueries shared by several scopes are only proved once.
+//!
+//! The public inputs of the merged scope list the top-level queries of every original scope, so each party checks its
+//! own queries were included with `CoroutinePublicInputs::contains_toplevel`, once the merged proof is verified against
+//! those inputs.
+
+use anyhow::{bail, Result};
+
+use super::{query::Query, LogMemo, Scope};
+use crate::field::LurkField;
+use crate::lem::{pointers::Ptr, store::Store};
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// The top-level queries of this scope, each repeated as many times as it was made
+    fn toplevel_queries(&self, s: &Store<F>) -> Result<Vec<Ptr>> {
+        let mut queries = Vec::with_capacity(self.toplevel_insertions.len());
+        for kv in &self.toplevel_insertions {
+            let (key, _) = s.try_car_cdr(kv)?;
+            queries.extend(std::iter::repeat(key).take(self.toplevel_multiplicity(kv)));
+        }
+        Ok(queries)
+    }
+
+    /// Merges `scopes` into a new scope whose proof covers all their top-level queries. The scopes must have been
+    /// made with the same store and configuration.
+    pub fn merge(s: &Store<F>, scopes: &[Self]) -> Result<Self> {
+        let Some(first) = scopes.first() else {
+            bail!("No scopes to merge");
+        };
+        if scopes.iter().any(|scope| {
+            scope.transcribe_internal_insertions != first.transcribe_internal_insertions
+                || scope.dedup_toplevel_insertions != first.dedup_toplevel_insertions
+                || scope.transcribe_toplevel_insertions != first.transcribe_toplevel_insertions
+                || scope.transcript_scheme != first.transcript_scheme
+                || scope.default_rc != first.default_rc
+        }) {
+            bail!("Can't merge scopes with different configurations");
+        }
+        // Only top-level queries are replayed, so deferrals would be lost.
+        if scopes.iter().any(|scope| !scope.deferred.is_empty()) {
+            bail!("Can't merge scopes with deferred queries");
+        }
+
+        let mut merged = Self::new(first.transcribe_internal_insertions, first.default_rc);
+        merged.dedup_toplevel_insertions = first.dedup_toplevel_insertions;
+        merged.transcribe_toplevel_insertions = first.transcribe_toplevel_insertions;
+        merged.transcript_scheme = first.transcript_scheme;
+        for scope in scopes {
+            for query in scope.toplevel_queries(s)? {
+                merged.query(s, query);
+            }
+        }
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::{demo::DemoQuery, FoldingBackend, MockBackend};
+
+    #[test]
+    fn test_merge() {
+        let s = &Store::<F>::default();
+        let fact = |n: u64| {
+            s.read_with_default_state(&format!("(factorial . {n})"))
+                .unwrap()
+        };
+
+        let mut alice: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        alice.query(s, fact(4));
+        alice.query(s, fact(4));
+        let mut bob: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        bob.query(s, fact(3));
+        bob.query(s, fact(6));
+
+        let mut merged = Scope::merge(s, &[alice.clone(), bob.clone()]).unwrap();
+        assert_eq!(4, merged.toplevel_insertions.len());
+        // factorial 0 through 6
+        assert_eq!(7, merged.queries.len());
+
+        let (proof, z0, zi) = merged.prove_with(s, &MockBackend, &()).unwrap();
+        let inputs = merged.public_inputs(s);
+        assert!(MockBackend
+            .verify_scope(&(), &proof, s, &inputs, &z0, &zi)
+            .unwrap());
+        // Alice's query, made twice, and Bob's are all part of the verified inputs.
+        assert!(inputs.contains_toplevel(&alice.public_inputs(s)));
+        assert!(inputs.contains_toplevel(&bob.public_inputs(s)));
+        let mut carol: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        carol.query(s, fact(5));
+        assert!(!inputs.contains_toplevel(&carol.public_inputs(s)));
+
+        let other: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(true, 2);
+        assert!(Scope::merge(s, &[alice, other]).is_err());
+    }
+}
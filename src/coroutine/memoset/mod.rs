@@ -27,19 +27,27 @@
 //! results computed 'naturally' during evaluation. We then separate and sort in an order matching that which the NIVC
 //! prover will follow when provably maintaining the multiset accumulator and Fiat-Shamir transcript in the circuit.
 
+use anyhow::{bail, Context, Result};
+use camino::Utf8Path;
 use itertools::Itertools;
-use std::collections::HashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
 use std::marker::PhantomData;
+use std::time::{Duration, Instant};
 
+use bellpepper::util_cs::witness_cs::WitnessCS;
 use bellpepper_core::{boolean::Boolean, num::AllocatedNum, ConstraintSystem, SynthesisError};
 use indexmap::IndexSet;
 use once_cell::sync::OnceCell;
+use rayon::prelude::*;
 
 use crate::circuit::gadgets::{
     constraints::{enforce_equal, enforce_equal_zero, invert, sub},
+    data::construct_cons,
     pointer::AllocatedPtr,
 };
-use crate::coprocessor::gadgets::construct_cons; // FIXME: Move to common location.
 use crate::field::LurkField;
 use crate::lem::circuit::GlobalAllocator;
 use crate::lem::tag::Tag;
@@ -47,13 +55,66 @@ use crate::lem::{pointers::Ptr, store::Store};
 use crate::tag::{ExprTag, Tag as XTag};
 use crate::z_ptr::ZPtr;
 
+use audit::TranscriptItem;
+pub use audit::{TranscriptAudit, TranscriptAuditEntry, TranscriptItemKind};
+pub use backend::{
+    ChunkCircuit, FoldingBackend, MockBackend, NovaBackend, NovaChunksProof, SuperNovaBackend,
+};
+pub use consistency::{ConsistencyIssue, ConsistencyReport};
+pub use coproc::QueryCoprocessor;
+pub use deferral::Obligations;
+#[cfg(feature = "examples")]
+pub use demo::{DemoCircuitQuery, DemoQuery};
+pub use env::{EnvCircuitQuery, EnvQuery};
+pub use io::CoroutineIO;
 use multiset::MultiSet;
-pub use query::{CircuitQuery, Query};
-
-mod demo;
+pub use open::{OpenCircuitQuery, OpenQuery};
+pub use params::FoldingParamsCache;
+pub use proof::{CompressedProof, CoroutineProof};
+pub use public_inputs::{CoroutinePublicInputs, ToplevelTranscription};
+pub use query::{
+    check_query_version, compound_key, compound_key_args, synthesize_compound_key_args,
+    versioned_query_body, versioned_symbol, CircuitQuery, Query, QueryVersionError, RecursiveQuery,
+};
+pub use reproducible::{ChunkEntry, ProofManifest};
+pub use scheme::TranscriptScheme;
+pub use sha256::{Sha256CircuitQuery, Sha256Query};
+pub use shape::{ChunkShape, ChunkShapeCache, ShapeKey};
+#[cfg(feature = "memoset-serde")]
+pub use snapshot::{LogMemoData, ScopeData, Snapshot, TranscriptData};
+pub use table::QueryRow;
+#[cfg(feature = "arrow")]
+pub use table::{query_table_schema, to_record_batches, write_parquet};
+pub use user::{QueryDef, UserCircuitQuery, UserQuery};
+pub use witness::{CachedChunkWitness, ChunkWitnessCache};
+
+mod audit;
+mod backend;
+mod consistency;
+mod coproc;
+mod deferral;
+// Its items are only public with the `examples` feature.
+#[cfg_attr(not(feature = "examples"), allow(unreachable_pub))]
+pub(crate) mod demo;
 mod env;
+mod io;
+mod merge;
 mod multiset;
+mod open;
+mod params;
+mod proof;
+mod public_inputs;
 mod query;
+mod reproducible;
+mod scheme;
+mod sha256;
+mod shape;
+#[cfg(feature = "memoset-serde")]
+mod snapshot;
+mod table;
+pub mod testing;
+mod user;
+mod witness;
 
 #[derive(Clone, Debug)]
 pub struct Transcript<F> {
@@ -83,19 +144,48 @@ impl<F: LurkField> Transcript<F> {
         s.cons(kv, count_num)
     }
 
+    /// The items recording the top-level insertions `kvs`, each with the number of times it was queried, along with
+    /// the count to transcribe them with
+    fn toplevel_items(
+        s: &Store<F>,
+        transcription: ToplevelTranscription,
+        kvs: &[(Ptr, usize)],
+    ) -> Vec<(Ptr, Option<usize>)> {
+        match transcription {
+            ToplevelTranscription::Each => kvs
+                .iter()
+                .flat_map(|(kv, multiplicity)| std::iter::repeat((*kv, None)).take(*multiplicity))
+                .collect(),
+            ToplevelTranscription::Dedup => kvs
+                .iter()
+                .map(|(kv, multiplicity)| (*kv, Some(*multiplicity)))
+                .collect(),
+            ToplevelTranscription::Committed => {
+                let mut commitment = Self::new(s);
+                for (kv, multiplicity) in kvs {
+                    for _ in 0..*multiplicity {
+                        commitment.add(s, *kv);
+                    }
+                }
+                vec![(commitment.acc, None)]
+            }
+        }
+    }
+
     /// Since the transcript is just a content-addressed Lurk list, its randomness is the hash value of the associated
-    /// top-level `Cons`. This function sanity-checks the type and extracts that field element.
+    /// top-level `Cons`, or of the `Num` a packed transcript ends with. This function sanity-checks the type and
+    /// extracts that field element.
     fn r(&self, s: &Store<F>) -> F {
+        // The transcript is a long list, so hash it bottom-up and in parallel
+        s.hydrate_ptrs(&[self.acc]);
         let z_ptr = s.hash_ptr(&self.acc);
-        assert_eq!(Tag::Expr(ExprTag::Cons), *z_ptr.tag());
+        assert!(matches!(
+            z_ptr.tag(),
+            Tag::Expr(ExprTag::Cons | ExprTag::Num)
+        ));
         *z_ptr.value()
     }
 
-    #[allow(dead_code)]
-    fn dbg(&self, s: &Store<F>) {
-        tracing::debug!("transcript: {}", self.acc.fmt_to_string_simple(s));
-    }
-
     #[allow(dead_code)]
     fn fmt_to_string_simple(&self, s: &Store<F>) -> String {
         self.acc.fmt_to_string_simple(s)
@@ -169,13 +259,6 @@ impl<F: LurkField> CircuitTranscript<F> {
     fn r(&self) -> &AllocatedNum<F> {
         self.acc.hash()
     }
-
-    #[allow(dead_code)]
-    fn dbg(&self, s: &Store<F>) {
-        let z = self.acc.get_value::<Tag>().unwrap();
-        let transcript = s.to_ptr(&z);
-        tracing::debug!("transcript: {}", transcript.fmt_to_string_simple(s));
-    }
 }
 
 #[derive(Clone, Debug)]
@@ -188,13 +271,25 @@ pub struct Scope<Q, M> {
     queries: HashMap<Ptr, Ptr>,
     /// k => ordered subqueries
     dependencies: HashMap<Ptr, Vec<Q>>,
-    /// kv pairs
+    /// kv pairs, made by `Transcript::make_kv` or checked by `from_snapshot`, so taking them apart can't fail
     toplevel_insertions: Vec<Ptr>,
     /// internally-inserted keys
     internal_insertions: Vec<Ptr>,
     /// unique keys: query-index -> [key]
     unique_inserted_keys: HashMap<usize, Vec<Ptr>>,
+    /// top-level kv pair => number of times it was queried
+    toplevel_multiplicities: HashMap<Ptr, usize>,
     transcribe_internal_insertions: bool,
+    /// When set, repeated top-level queries are inserted once, with multiplicity.
+    dedup_toplevel_insertions: bool,
+    /// When unset, top-level insertions are left out of the transcript and bound by the public inputs instead.
+    transcribe_toplevel_insertions: bool,
+    /// Keys whose proofs are left to another scope
+    deferred: IndexSet<Ptr>,
+    /// How items are hashed into the transcript
+    transcript_scheme: TranscriptScheme,
+    /// The items `finalize_transcript` hashed into the transcript, in order, once it has been finalized here
+    transcript_items: Option<Vec<TranscriptItem>>,
     // This may become an explicit map or something allowing more fine-grained control.
     default_rc: usize,
 }
@@ -217,10 +312,151 @@ impl<F: LurkField, Q> Scope<Q, LogMemo<F>> {
             toplevel_insertions: Default::default(),
             internal_insertions: Default::default(),
             unique_inserted_keys: Default::default(),
+            toplevel_multiplicities: Default::default(),
             transcribe_internal_insertions,
+            dedup_toplevel_insertions: false,
+            transcribe_toplevel_insertions: true,
+            deferred: Default::default(),
+            transcript_scheme: Default::default(),
+            transcript_items: None,
             default_rc,
         }
     }
+
+    /// Records each distinct top-level query once, however many times it is made, so the circuit inserts it once
+    /// rather than once per use. The transcript then records a top-level insertion as `((key . value) . multiplicity)`
+    /// (the same shape as a removal) and the memoset accumulator gains `multiplicity` copies of the element at once.
+    pub fn with_toplevel_dedup(mut self) -> Self {
+        assert!(
+            self.toplevel_insertions.is_empty(),
+            "dedup mode must be chosen before querying"
+        );
+        assert!(
+            self.transcribe_toplevel_insertions,
+            "dedup mode can't be combined with public top-level queries"
+        );
+        self.dedup_toplevel_insertions = true;
+        self
+    }
+
+    /// Replaces the top-level insertions of the transcript with a single item committing to all of them, for verifiers
+    /// that know the top-level queries and their values. The challenge still depends on the values, so they can't be
+    /// picked once it's known. The transcript is shorter by one item per top-level query but one.
+    pub fn with_public_toplevel(mut self) -> Self {
+        assert!(
+            self.toplevel_insertions.is_empty(),
+            "public top-level mode must be chosen before querying"
+        );
+        // The commitment lists every use of a query already.
+        assert!(
+            !self.dedup_toplevel_insertions,
+            "public top-level mode can't be combined with dedup mode"
+        );
+        self.transcribe_toplevel_insertions = false;
+        self
+    }
+
+    /// Hashes each transcript item with `scheme`, as described in `TranscriptScheme`.
+    pub fn with_transcript_scheme(mut self, scheme: TranscriptScheme) -> Self {
+        assert!(
+            !self.memoset.is_finalized(),
+            "the transcript scheme must be chosen before the transcript is finalized"
+        );
+        self.transcript_scheme = scheme;
+        self
+    }
+}
+
+/// Summary of the bookkeeping held by a `Scope`, meant to help size `rc` and padding before committing to synthesis.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScopeStats {
+    /// Number of top-level insertions (queries made directly through `Scope::query`), counting repeated queries even
+    /// when top-level insertions are deduplicated.
+    pub toplevel_insertions: usize,
+    /// Number of internal insertions (subqueries made while evaluating other queries).
+    pub internal_insertions: usize,
+    /// query-index -> number of unique keys
+    pub unique_keys: BTreeMap<usize, usize>,
+    /// Largest number of times any single kv pair was inserted into the memoset.
+    pub max_multiplicity: usize,
+    /// fan-out -> number of keys with that many direct dependencies
+    pub dependency_fan_out: BTreeMap<usize, usize>,
+    /// query-index -> number of chunks needed to prove all unique keys with that index's `rc`
+    pub chunks: BTreeMap<usize, usize>,
+}
+
+impl ScopeStats {
+    /// Total number of chunks across all query indices.
+    pub fn total_chunks(&self) -> usize {
+        self.chunks.values().sum()
+    }
+
+    /// Total number of padding (dummy) queries that will be synthesized, given the `rc` used for each query index.
+    pub fn padding(&self, rc_for_query: impl Fn(usize) -> usize) -> usize {
+        self.chunks
+            .iter()
+            .map(|(index, chunks)| {
+                let unique = self.unique_keys.get(index).copied().unwrap_or(0);
+                chunks * rc_for_query(*index) - unique
+            })
+            .sum()
+    }
+}
+
+/// The witness for a single chunk of a `Scope`, synthesized independently of every other chunk. `z_in` and `z_out`
+/// are the chunk's `CoroutineCircuit` IO.
+pub struct ChunkWitness<F: LurkField> {
+    pub query_index: usize,
+    pub chunk_index: usize,
+    pub z_in: CoroutineIO<ZPtr<Tag, F>>,
+    pub z_out: CoroutineIO<ZPtr<Tag, F>>,
+    pub witness: WitnessCS<F>,
+}
+
+/// The IO state left by the last completed chunk, persisted after each chunk so that proving a `Scope` can resume from
+/// there rather than start over.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkCheckpoint<F: LurkField> {
+    /// Number of chunks, in proving order, already completed.
+    pub chunks_completed: usize,
+    pub acc: F,
+    pub transcript: ZPtr<Tag, F>,
+    pub r: F,
+}
+
+impl<F: LurkField + Serialize + DeserializeOwned> ChunkCheckpoint<F> {
+    /// Writes the checkpoint to a temporary file first and then renames it over `path`, so a crash mid-write never
+    /// leaves a corrupt checkpoint behind.
+    pub fn write(&self, path: &Utf8Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        {
+            let file = File::create(&tmp).with_context(|| format!("creating {tmp}"))?;
+            bincode::serialize_into(BufWriter::new(file), self)?;
+        }
+        std::fs::rename(&tmp, path).with_context(|| format!("renaming {tmp} to {path}"))?;
+        Ok(())
+    }
+
+    /// Reads the checkpoint at `path`, if there is one.
+    pub fn read(path: &Utf8Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let file = File::open(path).with_context(|| format!("opening {path}"))?;
+        Ok(Some(bincode::deserialize_from(BufReader::new(file))?))
+    }
+}
+
+/// Everything needed to synthesize one chunk without first synthesizing the chunks preceding it: the keys to prove and
+/// the natively-computed memoset accumulator and transcript on entry.
+#[derive(Clone, Debug)]
+struct ChunkSpec<F> {
+    query_index: usize,
+    chunk_index: usize,
+    keys: Vec<Ptr>,
+    rc: usize,
+    acc: F,
+    transcript: Ptr,
 }
 
 #[derive(Debug, Clone)]
@@ -232,6 +468,8 @@ pub struct CircuitScope<F: LurkField, CM> {
     transcript: CircuitTranscript<F>,
     acc: Option<AllocatedPtr<F>>,
     transcribe_internal_insertions: bool,
+    transcribe_toplevel_insertions: bool,
+    transcript_scheme: TranscriptScheme,
 }
 
 pub struct CoroutineCircuit<'a, F: LurkField, CM, Q> {
@@ -241,6 +479,7 @@ pub struct CoroutineCircuit<'a, F: LurkField, CM, Q> {
     query_index: usize,
     store: &'a Store<F>,
     transcribe_internal_insertions: bool,
+    transcript_scheme: TranscriptScheme,
     rc: usize,
     _p: PhantomData<Q>,
 }
@@ -264,6 +503,7 @@ impl<'a, F: LurkField, Q: Query<F>> CoroutineCircuit<'a, F, LogMemoCircuit<F>, Q
             query_index,
             store,
             transcribe_internal_insertions: scope.transcribe_internal_insertions,
+            transcript_scheme: scope.transcript_scheme,
             rc,
             _p: Default::default(),
         }
@@ -274,15 +514,10 @@ impl<'a, F: LurkField, Q: Query<F>> CoroutineCircuit<'a, F, LogMemoCircuit<F>, Q
     fn synthesize<CS: ConstraintSystem<F>>(
         &mut self,
         cs: &mut CS,
-        z: &[AllocatedPtr<F>],
-    ) -> Result<(Option<AllocatedNum<F>>, Vec<AllocatedPtr<F>>), SynthesisError> {
+        z: &CoroutineIO<AllocatedPtr<F>>,
+    ) -> Result<(Option<AllocatedNum<F>>, CoroutineIO<AllocatedPtr<F>>), SynthesisError> {
         let g = &mut GlobalAllocator::<F>::default();
 
-        assert_eq!(6, z.len());
-        let [c, e, k, memoset_acc, transcript, r] = z else {
-            unreachable!()
-        };
-
         let mut circuit_scope: CircuitScope<F, LogMemoCircuit<F>> = CircuitScope::from_queries(
             cs,
             g,
@@ -291,7 +526,8 @@ impl<'a, F: LurkField, Q: Query<F>> CoroutineCircuit<'a, F, LogMemoCircuit<F>, Q
             self.queries,
             self.transcribe_internal_insertions,
         );
-        circuit_scope.update_from_io(memoset_acc.clone(), transcript.clone(), r);
+        circuit_scope.transcript_scheme = self.transcript_scheme;
+        circuit_scope.update_from_io(z.memoset_acc.clone(), z.transcript.clone(), &z.r);
 
         for (i, key) in self
             .keys
@@ -313,7 +549,14 @@ impl<'a, F: LurkField, Q: Query<F>> CoroutineCircuit<'a, F, LogMemoCircuit<F>, Q
         let (memoset_acc, transcript, r_num) = circuit_scope.io();
         let r = AllocatedPtr::alloc_tag(&mut cs.namespace(|| "r"), ExprTag::Num.to_field(), r_num)?;
 
-        let z_out = vec![c.clone(), e.clone(), k.clone(), memoset_acc, transcript, r];
+        let z_out = CoroutineIO {
+            c: z.c.clone(),
+            e: z.e.clone(),
+            k: z.k.clone(),
+            memoset_acc,
+            transcript,
+            r,
+        };
 
         let next_pc = None; // FIXME.
         Ok((next_pc, z_out))
@@ -324,11 +567,66 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
     pub fn query(&mut self, s: &Store<F>, form: Ptr) -> Ptr {
         let (response, kv_ptr) = self.query_aux(s, form);
 
-        self.toplevel_insertions.push(kv_ptr);
+        let multiplicity = self.toplevel_multiplicities.entry(kv_ptr).or_insert(0);
+        *multiplicity += 1;
+        if !self.dedup_toplevel_insertions || *multiplicity == 1 {
+            self.toplevel_insertions.push(kv_ptr);
+        }
 
         response
     }
 
+    /// How many copies of the top-level `kv` a single entry of `toplevel_insertions` stands for.
+    fn toplevel_multiplicity(&self, kv: &Ptr) -> usize {
+        if self.dedup_toplevel_insertions {
+            self.toplevel_multiplicities[kv]
+        } else {
+            1
+        }
+    }
+
+    fn toplevel_transcription(&self) -> ToplevelTranscription {
+        if !self.transcribe_toplevel_insertions {
+            ToplevelTranscription::Committed
+        } else if self.dedup_toplevel_insertions {
+            ToplevelTranscription::Dedup
+        } else {
+            ToplevelTranscription::Each
+        }
+    }
+
+    /// What kind of transcript item the top-level insertions make
+    fn toplevel_item_kind(&self) -> TranscriptItemKind {
+        match self.toplevel_transcription() {
+            ToplevelTranscription::Committed => TranscriptItemKind::ToplevelCommitment,
+            _ => TranscriptItemKind::ToplevelInsertion,
+        }
+    }
+
+    /// What the transcript records for the top-level insertions, in order.
+    fn toplevel_transcript_items(&self, s: &Store<F>) -> Vec<(Ptr, Option<usize>)> {
+        let kvs = self
+            .toplevel_insertions
+            .iter()
+            .map(|kv| (Self::kv_item(s, kv), self.toplevel_multiplicity(kv)))
+            .collect::<Vec<_>>();
+        Transcript::toplevel_items(s, self.toplevel_transcription(), &kvs)
+    }
+
+    /// The memoset entries of `kv`: `kv` itself, or one per value for queries with several, as `query::entries`
+    fn kv_entries(s: &Store<F>, kv: &Ptr) -> Vec<Ptr> {
+        let (key, value) = s.try_car_cdr(kv).expect("kv should be cons");
+        match Q::from_ptr(s, &key).map_or(1, |query| query.arity()) {
+            1 => vec![*kv],
+            arity => query::value_entries(s, key, value, arity).expect("malformed values"),
+        }
+    }
+
+    /// What the transcript records for `kv`: its last memoset entry, which binds its key to all of its values
+    fn kv_item(s: &Store<F>, kv: &Ptr) -> Ptr {
+        *Self::kv_entries(s, kv).last().expect("at least one entry")
+    }
+
     fn query_recursively(&mut self, s: &Store<F>, parent: &Q, child: Q) -> Ptr {
         let form = child.to_ptr(s);
         self.internal_insertions.push(form);
@@ -359,9 +657,17 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
         (response, kv)
     }
 
+    #[tracing::instrument(skip_all, name = "memoset::finalize_transcript")]
     fn finalize_transcript(&mut self, s: &Store<F>) -> Transcript<F> {
-        let (transcript, insertions) = self.build_transcript(s);
+        let mut items = Vec::new();
+        let (transcript, insertions) = self.build_transcript(s, &mut items);
         self.memoset.finalize_transcript(s, transcript.clone());
+        self.transcript_items = Some(items);
+        tracing::debug!(
+            queries = self.queries.len(),
+            unique_keys = insertions.values().map(Vec::len).sum::<usize>(),
+            "transcript finalized"
+        );
         self.unique_inserted_keys = insertions;
         transcript
     }
@@ -372,16 +678,34 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
         }
     }
 
-    fn build_transcript(&self, s: &Store<F>) -> (Transcript<F>, HashMap<usize, Vec<Ptr>>) {
+    /// Builds the transcript, pushing each item added to it onto `items`
+    fn build_transcript(
+        &self,
+        s: &Store<F>,
+        items: &mut Vec<TranscriptItem>,
+    ) -> (Transcript<F>, HashMap<usize, Vec<Ptr>>) {
         let mut transcript = Transcript::new(s);
+        let mut add = |transcript: &mut Transcript<F>, kind, item, count| {
+            transcript.add_kv(s, self.transcript_scheme, item, count);
+            items.push(TranscriptItem {
+                kind,
+                item,
+                count,
+                transcript: transcript.acc,
+            });
+        };
 
         // k -> [kv]
         let mut insertions: HashMap<Ptr, IndexSet<Ptr>> = HashMap::new();
         let mut unique_keys: HashMap<usize, Vec<Ptr>> = Default::default();
 
         let mut insert = |kv: Ptr| {
-            let key = s.car_cdr(&kv).unwrap().0;
+            let key = s.try_car_cdr(&kv).expect("kv should be cons").0;
 
+            if self.deferred.contains(&key) {
+                // Another scope proves it.
+                return;
+            }
             if let Some(kvs) = insertions.get_mut(&key) {
                 kvs.insert(kv);
             } else {
@@ -408,8 +732,9 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
         for kv in internal_insertions_kv {
             insert(kv);
         }
-        for kv in self.toplevel_insertions.iter() {
-            transcript.add(s, *kv);
+        let toplevel_kind = self.toplevel_item_kind();
+        for (item, count) in self.toplevel_transcript_items(s) {
+            add(&mut transcript, toplevel_kind, item, count);
         }
 
         // Then add insertions and removals interleaved, sorted by query type. We interleave insertions and removals
@@ -431,23 +756,30 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
                             // because the proof must do so each time a query is used.
                             let kv = Transcript::make_kv(s, k, *v);
                             if self.transcribe_internal_insertions {
-                                transcript.add(s, kv)
+                                let item = Self::kv_item(s, &kv);
+                                add(&mut transcript, TranscriptItemKind::Insertion, item, None)
                             }
                         })
                     };
                     let count = self.memoset.count(kv);
-                    let kv_count = Transcript::make_kv_count(s, *kv, count);
 
                     // Add removal for the query identified by `key`. The queries being removed here were deduplicated
                     // above, so each is removed only once. However, we freely choose the multiplicity (`count`) of the
                     // removal to match the total number of insertions actually made (considering dependencies).
-                    transcript.add(s, kv_count);
+                    let item = Self::kv_item(s, kv);
+                    add(
+                        &mut transcript,
+                        TranscriptItemKind::Removal,
+                        item,
+                        Some(count),
+                    );
                 }
             }
         }
         (transcript, unique_keys)
     }
 
+    #[tracing::instrument(skip_all, name = "memoset::synthesize")]
     pub fn synthesize<CS: ConstraintSystem<F>>(
         &mut self,
         cs: &mut CS,
@@ -469,6 +801,8 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
             &self.queries,
             self.transcribe_internal_insertions,
         );
+        circuit_scope.transcribe_toplevel_insertions = self.transcribe_toplevel_insertions;
+        circuit_scope.transcript_scheme = self.transcript_scheme;
         circuit_scope.init(cs, g, s);
         {
             circuit_scope.synthesize_insert_toplevel_queries(self, cs, g, s)?;
@@ -481,22 +815,27 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
                     r_num,
                 )?;
                 let dummy = g.alloc_ptr(cs, &s.intern_nil(), s);
-                let mut z = vec![
-                    dummy.clone(),
-                    dummy.clone(),
-                    dummy.clone(),
+                let mut z = CoroutineIO {
+                    c: dummy.clone(),
+                    e: dummy.clone(),
+                    k: dummy,
                     memoset_acc,
                     transcript,
                     r,
-                ];
-                for (index, keys) in self.unique_inserted_keys.iter() {
+                };
+                // Chunks go in query index order, like in the transcript, rather than in the map's arbitrary order.
+                for index in 0..Q::count() {
+                    let Some(keys) = self.unique_inserted_keys.get(&index) else {
+                        continue;
+                    };
                     let cs = &mut cs.namespace(|| format!("query-index-{index}"));
 
-                    let rc = self.rc_for_query(*index);
+                    let rc = self.rc_for_query(index);
 
                     for (i, chunk) in keys.chunks(rc).enumerate() {
-                        // This namespace exists only because we are putting multiple 'chunks' into a single, larger circuit (as a stage in development).
-                        // It shouldn't exist, when instead we have only the single NIVC circuit repeated multiple times.
+                        // This namespace exists only because we are putting multiple 'chunks' into a single, larger
+                        // circuit (as a stage in development). It shouldn't exist, when instead we have only the
+                        // single NIVC circuit repeated multiple times.
                         let cs = &mut cs.namespace(|| format!("chunk-{i}"));
 
                         let mut circuit: CoroutineCircuit<'_, F, LogMemoCircuit<F>, Q> =
@@ -504,21 +843,17 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
                                 self,
                                 memoset_circuit.clone(),
                                 chunk.to_vec(),
-                                *index,
+                                index,
                                 s,
                                 rc,
                             );
 
                         let (_next_pc, z_out) = circuit.synthesize(cs, &z)?;
                         {
-                            let memoset_acc = &z_out[3];
-                            let transcript = &z_out[4];
-                            let r = &z_out[5];
-
                             circuit_scope.update_from_io(
-                                memoset_acc.clone(),
-                                transcript.clone(),
-                                r,
+                                z_out.memoset_acc.clone(),
+                                z_out.transcript.clone(),
+                                &z_out.r,
                             );
 
                             z = z_out;
@@ -533,9 +868,350 @@ impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
         Ok(())
     }
 
-    fn rc_for_query(&self, _index: usize) -> usize {
+    pub(crate) fn rc_for_query(&self, _index: usize) -> usize {
         self.default_rc
     }
+
+    /// Reports counts describing the queries made so far. This does not finalize the transcript, so it can be called
+    /// at any point during evaluation.
+    pub fn stats(&self, s: &Store<F>) -> ScopeStats {
+        let mut unique_keys = BTreeMap::new();
+        let mut dependency_fan_out = BTreeMap::new();
+
+        for key in self.queries.keys() {
+            let index = Q::from_ptr(s, key).expect("bad query").index();
+            *unique_keys.entry(index).or_insert(0) += 1;
+
+            let fan_out = self.dependencies.get(key).map_or(0, Vec::len);
+            *dependency_fan_out.entry(fan_out).or_insert(0) += 1;
+        }
+
+        let chunks = unique_keys
+            .iter()
+            .map(|(index, count)| (*index, count.div_ceil(self.rc_for_query(*index))))
+            .collect();
+
+        ScopeStats {
+            toplevel_insertions: self.toplevel_multiplicities.values().sum(),
+            internal_insertions: self.internal_insertions.len(),
+            unique_keys,
+            max_multiplicity: self.memoset.multiset.max_multiplicity(),
+            dependency_fan_out,
+            chunks,
+        }
+    }
+}
+
+impl<F: LurkField, Q: Query<F> + Send + Sync> Scope<Q, LogMemo<F>> {
+    /// Synthesizes the witness of every chunk in parallel, each into its own `WitnessCS`, rather than serially into a
+    /// single constraint system as `synthesize` does. The IO each chunk starts from is computed natively beforehand, so
+    /// chunks don't depend on one another's synthesis. The results are then checked to chain together: each chunk's
+    /// `z_out` must be the next chunk's `z_in`, and the last must leave only the deferred queries in the memoset and a
+    /// transcript hashing to `r`.
+    ///
+    /// Chunks are returned in the order they must be folded, which matches the order of the transcript. Top-level
+    /// insertions are not part of any chunk; the returned `z_in` of the first chunk already accounts for them.
+    pub fn synthesize_chunks_parallel(
+        &mut self,
+        s: &Store<F>,
+    ) -> Result<Vec<ChunkWitness<F>>, SynthesisError> {
+        self.ensure_transcript_finalized(s);
+        let r = *self.memoset.r().expect("transcript not finalized");
+
+        let scope: &Self = self;
+        let chunks = scope
+            .chunk_specs(s)
+            .into_par_iter()
+            .map(|spec| {
+                let mut witness = WitnessCS::new();
+                let (z_in, z_out) = scope.synthesize_chunk(&mut witness, s, &spec)?;
+                Ok(ChunkWitness {
+                    query_index: spec.query_index,
+                    chunk_index: spec.chunk_index,
+                    z_in,
+                    z_out,
+                    witness,
+                })
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        check_chunks_chain(&chunks, r, scope.deferred_acc(s))?;
+        Ok(chunks)
+    }
+
+    /// Synthesizes the witness of every chunk in turn, in folding order, and returns each chunk's query index with how
+    /// long its synthesis took. The witnesses are discarded: this is for benchmarks, which time folding apart.
+    pub fn time_chunk_synthesis(
+        &mut self,
+        s: &Store<F>,
+    ) -> Result<Vec<(usize, Duration)>, SynthesisError> {
+        self.ensure_transcript_finalized(s);
+        self.chunk_specs(s)
+            .iter()
+            .map(|spec| {
+                let start = Instant::now();
+                self.synthesize_chunk(&mut WitnessCS::new(), s, spec)?;
+                Ok((spec.query_index, start.elapsed()))
+            })
+            .collect()
+    }
+}
+
+/// Checks that each chunk's `z_out` is the next chunk's `z_in`, and that the last chunk leaves the memoset accumulator
+/// at `final_acc` and a transcript hashing to `r`.
+fn check_chunks_chain<F: LurkField>(
+    chunks: &[ChunkWitness<F>],
+    r: F,
+    final_acc: F,
+) -> Result<(), SynthesisError> {
+    for (prev, next) in chunks.iter().tuple_windows() {
+        if prev.z_out != next.z_in {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+    }
+    if let Some(last) = chunks.last() {
+        let (acc, transcript) = (&last.z_out.memoset_acc, &last.z_out.transcript);
+        if *acc.value() != final_acc || *transcript.value() != r {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+    }
+    Ok(())
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// Synthesizes chunks one at a time, in proving order, handing each chunk's witness to `on_chunk` (which would fold
+    /// it) and then persisting a `ChunkCheckpoint` at `checkpoint`. If a checkpoint already exists there, the chunks it
+    /// records as completed are skipped, after checking that it belongs to this `Scope`. Returns the final checkpoint.
+    pub fn synthesize_chunks_resumable(
+        &mut self,
+        s: &Store<F>,
+        checkpoint: &Utf8Path,
+        mut on_chunk: impl FnMut(ChunkWitness<F>) -> Result<()>,
+    ) -> Result<ChunkCheckpoint<F>>
+    where
+        F: Serialize + DeserializeOwned,
+    {
+        self.ensure_transcript_finalized(s);
+        let r = *self.memoset.r().expect("transcript not finalized");
+        let specs = self.chunk_specs(s);
+
+        let mut state = match ChunkCheckpoint::read(checkpoint)? {
+            Some(state) => {
+                if state.r != r || state.chunks_completed > specs.len() {
+                    bail!("checkpoint at {checkpoint} was not made for this scope");
+                }
+                state
+            }
+            None => {
+                let (acc, transcript) = specs.first().map_or_else(
+                    || (F::ZERO, s.hash_ptr(&s.intern_nil())),
+                    |spec| (spec.acc, s.hash_ptr(&spec.transcript)),
+                );
+                ChunkCheckpoint {
+                    chunks_completed: 0,
+                    acc,
+                    transcript,
+                    r,
+                }
+            }
+        };
+
+        for spec in &specs[state.chunks_completed..] {
+            if spec.acc != state.acc || s.hash_ptr(&spec.transcript) != state.transcript {
+                bail!(
+                    "checkpoint at {checkpoint} does not match chunk {}",
+                    state.chunks_completed
+                );
+            }
+
+            let mut witness = WitnessCS::new();
+            let (z_in, z_out) = self.synthesize_chunk(&mut witness, s, spec)?;
+            state = ChunkCheckpoint {
+                chunks_completed: state.chunks_completed + 1,
+                acc: *z_out.memoset_acc.value(),
+                transcript: z_out.transcript,
+                r,
+            };
+
+            on_chunk(ChunkWitness {
+                query_index: spec.query_index,
+                chunk_index: spec.chunk_index,
+                z_in,
+                z_out,
+                witness,
+            })?;
+            state.write(checkpoint)?;
+        }
+
+        Ok(state)
+    }
+
+    /// Replays the transcript natively, recording the memoset accumulator and transcript at the start of each chunk.
+    fn chunk_specs(&self, s: &Store<F>) -> Vec<ChunkSpec<F>> {
+        self.replay(s).0
+    }
+
+    /// Like `chunk_specs`, but also returns the memoset accumulator once every chunk has been proved.
+    fn replay(&self, s: &Store<F>) -> (Vec<ChunkSpec<F>>, F) {
+        self.replay_with(s, None)
+    }
+
+    /// Like `replay`, recording every item added to the transcript into `audit`, if any.
+    fn replay_with(
+        &self,
+        s: &Store<F>,
+        mut audit: Option<&mut TranscriptAudit<F>>,
+    ) -> (Vec<ChunkSpec<F>>, F) {
+        let mut add = |transcript: &mut Transcript<F>, kind, kv, count| {
+            transcript.add_kv(s, self.transcript_scheme, kv, count);
+            if let Some(audit) = audit.as_deref_mut() {
+                audit.record(s, kind, kv, count, transcript);
+            }
+        };
+        let element = |kv: &Ptr| {
+            Self::kv_entries(s, kv)
+                .iter()
+                .map(|entry| {
+                    self.memoset
+                        .map_to_element(*s.hash_ptr(entry).value())
+                        .expect("transcript not finalized")
+                })
+                .fold(F::ZERO, |acc, element| acc + element)
+        };
+
+        let mut acc = F::ZERO;
+        let mut transcript = Transcript::new(s);
+        for kv in &self.toplevel_insertions {
+            acc += element(kv) * F::from_u64(self.toplevel_multiplicity(kv) as u64);
+        }
+        let toplevel_kind = self.toplevel_item_kind();
+        for (item, count) in self.toplevel_transcript_items(s) {
+            add(&mut transcript, toplevel_kind, item, count);
+        }
+
+        let mut specs = Vec::new();
+        for index in 0..Q::count() {
+            let Some(keys) = self.unique_inserted_keys.get(&index) else {
+                continue;
+            };
+            let rc = self.rc_for_query(index);
+
+            for (chunk_index, chunk) in keys.chunks(rc).enumerate() {
+                specs.push(ChunkSpec {
+                    query_index: index,
+                    chunk_index,
+                    keys: chunk.to_vec(),
+                    rc,
+                    acc,
+                    transcript: transcript.acc,
+                });
+
+                for key in chunk {
+                    for dependency in self.dependencies.get(key).into_iter().flatten() {
+                        let k = dependency.to_ptr(s);
+                        let v = self
+                            .queries
+                            .get(&k)
+                            .expect("value missing for dependency key");
+                        let kv = Transcript::make_kv(s, k, *v);
+                        acc += element(&kv);
+                        if self.transcribe_internal_insertions {
+                            let item = Self::kv_item(s, &kv);
+                            add(&mut transcript, TranscriptItemKind::Insertion, item, None);
+                        }
+                    }
+
+                    let value = self.queries.get(key).expect("value missing for key");
+                    let kv = Transcript::make_kv(s, *key, *value);
+                    let count = self.memoset.count(&kv);
+                    acc -= element(&kv) * F::from_u64(count as u64);
+                    add(
+                        &mut transcript,
+                        TranscriptItemKind::Removal,
+                        Self::kv_item(s, &kv),
+                        Some(count),
+                    );
+                }
+            }
+        }
+        (specs, acc)
+    }
+
+    /// The values of the IO a chunk starts from
+    fn chunk_z_in(&self, s: &Store<F>, spec: &ChunkSpec<F>) -> CoroutineIO<ZPtr<Tag, F>> {
+        let r = *self.memoset.r().expect("transcript not finalized");
+        CoroutineIO::chunk(s, spec.acc, s.hash_ptr(&spec.transcript), r)
+    }
+
+    /// Synthesizes a single chunk into `cs`, starting from the IO described by `spec`. Returns the values of `z_in` and
+    /// `z_out`.
+    #[allow(clippy::type_complexity)]
+    fn synthesize_chunk<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        s: &Store<F>,
+        spec: &ChunkSpec<F>,
+    ) -> Result<(CoroutineIO<ZPtr<Tag, F>>, CoroutineIO<ZPtr<Tag, F>>), SynthesisError> {
+        let (z_in, z_out) = self.synthesize_chunk_allocated(cs, s, spec)?;
+        let value = |z: CoroutineIO<AllocatedPtr<F>>| {
+            z.get_value().ok_or(SynthesisError::AssignmentMissing)
+        };
+        Ok((value(z_in)?, value(z_out)?))
+    }
+
+    /// Like `synthesize_chunk`, but returns the allocated `z_in` and `z_out`.
+    #[allow(clippy::type_complexity)]
+    fn synthesize_chunk_allocated<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        s: &Store<F>,
+        spec: &ChunkSpec<F>,
+    ) -> Result<(CoroutineIO<AllocatedPtr<F>>, CoroutineIO<AllocatedPtr<F>>), SynthesisError> {
+        let z_in = self.chunk_z_in(s, spec);
+
+        let z = CoroutineIO::from_vec(
+            z_in.into_vec()
+                .iter()
+                .enumerate()
+                .map(|(i, z_ptr)| {
+                    AllocatedPtr::alloc_infallible(
+                        &mut cs.namespace(|| format!("z_in-{i}")),
+                        || *z_ptr,
+                    )
+                })
+                .collect(),
+        );
+
+        let z_out = self.synthesize_chunk_io(cs, s, spec, &z)?;
+        Ok((z, z_out))
+    }
+
+    /// Synthesizes the chunk described by `spec` from the allocated IO `z`, whose values may be unknown. Returns the
+    /// allocated `z_out`.
+    fn synthesize_chunk_io<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        s: &Store<F>,
+        spec: &ChunkSpec<F>,
+        z: &CoroutineIO<AllocatedPtr<F>>,
+    ) -> Result<CoroutineIO<AllocatedPtr<F>>, SynthesisError> {
+        // `CoroutineCircuit::synthesize` replaces `r` with the one carried in `z`.
+        let memoset = LogMemoCircuit {
+            multiset: self.memoset.multiset.clone(),
+            r: z.r.hash().clone(),
+        };
+
+        let mut circuit: CoroutineCircuit<'_, F, LogMemoCircuit<F>, Q> = CoroutineCircuit::new(
+            self,
+            memoset,
+            spec.keys.clone(),
+            spec.query_index,
+            s,
+            spec.rc,
+        );
+        let (_next_pc, z_out) = circuit.synthesize(cs, z)?;
+        Ok(z_out)
+    }
 }
 
 impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
@@ -547,6 +1223,12 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         queries: &HashMap<Ptr, Ptr>,
         transcribe_internal_insertions: bool,
     ) -> Self {
+        s.hydrate_ptrs(
+            &queries
+                .iter()
+                .flat_map(|(k, v)| [*k, *v])
+                .collect::<Vec<_>>(),
+        );
         let queries = queries
             .iter()
             .map(|(k, v)| (s.hash_ptr(k), s.hash_ptr(v)))
@@ -558,6 +1240,8 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
             transcript: CircuitTranscript::new(cs, g, s),
             acc: Default::default(),
             transcribe_internal_insertions,
+            transcribe_toplevel_insertions: true,
+            transcript_scheme: TranscriptScheme::Cons,
         }
     }
 
@@ -601,22 +1285,29 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         s: &Store<F>,
         acc: &AllocatedPtr<F>,
         transcript: &CircuitTranscript<F>,
-        key: &AllocatedPtr<F>,
-        value: &AllocatedPtr<F>,
+        entries: &[AllocatedPtr<F>],
         is_toplevel: bool,
     ) -> Result<(AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
-        let kv = CircuitTranscript::make_kv(&mut cs.namespace(|| "kv"), g, s, key, value)?;
-        let new_transcript = if is_toplevel || self.transcribe_internal_insertions {
-            transcript.add(&mut cs.namespace(|| "new_transcript"), g, s, &kv)?
+        let item = entries.last().expect("at least one entry");
+        let transcribe = if is_toplevel {
+            self.transcribe_toplevel_insertions
+        } else {
+            self.transcribe_internal_insertions
+        };
+        let new_transcript = if transcribe {
+            transcript.add_kv(cs, g, s, self.transcript_scheme, item, "new_transcript")?
         } else {
             transcript.clone()
         };
 
-        let acc_v = acc.hash();
-
-        let new_acc_v =
-            self.memoset
-                .synthesize_add(&mut cs.namespace(|| "new_acc_v"), acc_v, &kv)?;
+        let mut new_acc_v = acc.hash().clone();
+        for (i, entry) in entries.iter().enumerate() {
+            new_acc_v = self.memoset.synthesize_add(
+                &mut cs.namespace(|| format!("new_acc_v {i}")),
+                &new_acc_v,
+                entry,
+            )?;
+        }
 
         let new_acc = AllocatedPtr::alloc_tag(
             &mut cs.namespace(|| "new_acc"),
@@ -635,32 +1326,43 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         acc: &AllocatedPtr<F>,
         transcript: &CircuitTranscript<F>,
         key: &AllocatedPtr<F>,
-        value: &AllocatedPtr<F>,
+        values: &[AllocatedPtr<F>],
     ) -> Result<(AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
-        let kv = CircuitTranscript::make_kv(&mut cs.namespace(|| "kv"), g, s, key, value)?;
-        let zptr = kv.get_value().unwrap_or(s.hash_ptr(&s.intern_nil())); // dummy case: use nil
-        let raw_count = self.memoset.count(&s.to_ptr(&zptr)) as u64; // dummy case: count is meaningless
+        let entries = query::synthesize_entries(cs, g, s, key, values)?;
+        // The memoset counts the key-value pair the scope recorded, with its values encoded as `query::values`.
+        let kv = key.get_value().and_then(|key| {
+            let values = values
+                .iter()
+                .map(|value| value.get_value().map(|value| s.to_ptr(&value)))
+                .collect::<Option<Vec<_>>>()?;
+            Some(Transcript::make_kv(
+                s,
+                s.to_ptr(&key),
+                query::values(s, &values),
+            ))
+        });
+        let kv = kv.unwrap_or(s.intern_nil()); // dummy case: use nil
+        let raw_count = self.memoset.count(&kv) as u64; // dummy case: count is meaningless
 
-        let (kv_count, count) = CircuitTranscript::make_kv_count(
-            &mut cs.namespace(|| "kv_count"),
+        let (new_transcript, count) = transcript.add_kv_count(
+            cs,
             g,
             s,
-            &kv,
+            self.transcript_scheme,
+            entries.last().expect("at least one entry"),
             raw_count,
-        )?;
-        let new_transcript = transcript.add(
-            &mut cs.namespace(|| "new_removal_transcript"),
-            g,
-            s,
-            &kv_count,
+            "new_removal_transcript",
         )?;
 
-        let new_acc_v = self.memoset.synthesize_remove_n(
-            &mut cs.namespace(|| "new_acc_v"),
-            acc.hash(),
-            &kv,
-            &count,
-        )?;
+        let mut new_acc_v = acc.hash().clone();
+        for (i, entry) in entries.iter().enumerate() {
+            new_acc_v = self.memoset.synthesize_remove_n(
+                &mut cs.namespace(|| format!("new_acc_v {i}")),
+                &new_acc_v,
+                entry,
+                &count,
+            )?;
+        }
 
         let new_acc = AllocatedPtr::alloc_tag(
             &mut cs.namespace(|| "new_acc"),
@@ -676,33 +1378,9 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         enforce_equal_zero(cs, || "acc_is_zero", self.acc.clone().unwrap().hash());
     }
 
-    fn synthesize_query<CS: ConstraintSystem<F>>(
-        &mut self,
-        cs: &mut CS,
-        g: &GlobalAllocator<F>,
-        store: &Store<F>,
-        key: &AllocatedPtr<F>,
-        acc: &AllocatedPtr<F>,
-        transcript: &CircuitTranscript<F>,
-        not_dummy: &Boolean,
-    ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
-        self.synthesize_query_aux(cs, g, store, key, acc, transcript, not_dummy, true)
-    }
-
-    fn synthesize_internal_query<CS: ConstraintSystem<F>>(
-        &mut self,
-        cs: &mut CS,
-        g: &GlobalAllocator<F>,
-        store: &Store<F>,
-        key: &AllocatedPtr<F>,
-        acc: &AllocatedPtr<F>,
-        transcript: &CircuitTranscript<F>,
-        not_dummy: &Boolean,
-    ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
-        self.synthesize_query_aux(cs, g, store, key, acc, transcript, not_dummy, false)
-    }
-
-    fn synthesize_query_aux<CS: ConstraintSystem<F>>(
+    /// Synthesizes a query made from within another query's circuit: allocates the value recorded for `key`, and
+    /// inserts the key-value pair into the memoset. Returns the value with the updated accumulator and transcript.
+    pub fn synthesize_internal_query<CS: ConstraintSystem<F>>(
         &mut self,
         cs: &mut CS,
         g: &GlobalAllocator<F>,
@@ -711,7 +1389,6 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         acc: &AllocatedPtr<F>,
         transcript: &CircuitTranscript<F>,
         not_dummy: &Boolean, // TODO: use this more deeply?
-        is_toplevel: bool,
     ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
         let value = AllocatedPtr::alloc(&mut cs.namespace(|| "value"), || {
             Ok(if not_dummy.get_value() == Some(true) {
@@ -724,12 +1401,55 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
             })
         })?;
 
+        let entries = query::synthesize_entries(cs, g, store, key, std::slice::from_ref(&value))?;
         let (new_acc, new_insertion_transcript) =
-            self.synthesize_insert_query(cs, g, store, acc, transcript, key, &value, is_toplevel)?;
+            self.synthesize_insert_query(cs, g, store, acc, transcript, &entries, false)?;
 
         Ok((value, new_acc, new_insertion_transcript))
     }
 
+    /// Like `synthesize_internal_query`, for queries that return `arity` values. The values are allocated and inserted
+    /// as separate memoset entries, as `query::entries`, so callers get them without the encoding the scope records.
+    pub fn synthesize_internal_query_values<CS: ConstraintSystem<F>>(
+        &mut self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        store: &Store<F>,
+        key: &AllocatedPtr<F>,
+        acc: &AllocatedPtr<F>,
+        transcript: &CircuitTranscript<F>,
+        not_dummy: &Boolean,
+        arity: usize,
+    ) -> Result<(Vec<AllocatedPtr<F>>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        let values = if not_dummy.get_value() == Some(true) {
+            let value = key
+                .get_value()
+                .and_then(|k| self.queries.get(&k))
+                .ok_or(SynthesisError::AssignmentMissing)?;
+            let values = query::fetch_values(store, &store.to_ptr(value), arity)
+                .ok_or(SynthesisError::AssignmentMissing)?;
+            Some(values)
+        } else {
+            None
+        };
+        let values = (0..arity)
+            .map(|i| {
+                AllocatedPtr::alloc(&mut cs.namespace(|| format!("value {i}")), || {
+                    Ok(match &values {
+                        Some(values) => store.hash_ptr(&values[i]),
+                        // Dummy value that will not be used.
+                        None => store.hash_ptr(&store.intern_nil()),
+                    })
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let entries = query::synthesize_entries(cs, g, store, key, &values)?;
+        let (new_acc, new_insertion_transcript) =
+            self.synthesize_insert_query(cs, g, store, acc, transcript, &entries, false)?;
+
+        Ok((values, new_acc, new_insertion_transcript))
+    }
+
     fn synthesize_insert_toplevel_queries<CS: ConstraintSystem<F>, Q: Query<F>>(
         &mut self,
         scope: &mut Scope<Q, LogMemo<F>>,
@@ -737,8 +1457,39 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         g: &mut GlobalAllocator<F>,
         s: &Store<F>,
     ) -> Result<(), SynthesisError> {
+        // Without transcribed top-level insertions, the list of their kvs is transcribed instead, once complete.
+        let mut commitment =
+            (!self.transcribe_toplevel_insertions).then(|| CircuitTranscript::new(cs, g, s));
         for (i, kv) in scope.toplevel_insertions.iter().enumerate() {
-            self.synthesize_toplevel_query(cs, g, s, i, kv)?;
+            let multiplicity = scope
+                .dedup_toplevel_insertions
+                .then(|| scope.toplevel_multiplicity(kv));
+            let (key, value) = s
+                .try_car_cdr(kv)
+                .map_err(|_| SynthesisError::AssignmentMissing)?;
+            let arity = Q::from_ptr(s, &key).map_or(1, |query| query.arity());
+            let values =
+                query::fetch_values(s, &value, arity).ok_or(SynthesisError::AssignmentMissing)?;
+            self.synthesize_toplevel_query(
+                cs,
+                g,
+                s,
+                i,
+                &key,
+                &values,
+                multiplicity,
+                commitment.as_mut(),
+            )?;
+        }
+        if let Some(commitment) = commitment {
+            self.transcript = self.transcript.add_kv(
+                cs,
+                g,
+                s,
+                self.transcript_scheme,
+                &commitment.acc,
+                "toplevel_commitment",
+            )?;
         }
         Ok(())
     }
@@ -749,30 +1500,68 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         g: &mut GlobalAllocator<F>,
         s: &Store<F>,
         i: usize,
-        kv: &Ptr,
+        key: &Ptr,
+        values: &[Ptr],
+        multiplicity: Option<usize>,
+        commitment: Option<&mut CircuitTranscript<F>>,
     ) -> Result<(), SynthesisError> {
-        let (key, value) = s.car_cdr(kv).unwrap();
         let cs = &mut cs.namespace(|| format!("toplevel-{i}"));
-        let allocated_key = AllocatedPtr::alloc(&mut cs.namespace(|| "allocated_key"), || {
-            Ok(s.hash_ptr(&key))
-        })
-        .unwrap();
+        let allocated_key =
+            AllocatedPtr::alloc(
+                &mut cs.namespace(|| "allocated_key"),
+                || Ok(s.hash_ptr(key)),
+            )
+            .unwrap();
+        let allocated_values = values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                AllocatedPtr::alloc(&mut cs.namespace(|| format!("value {i}")), || {
+                    Ok(s.hash_ptr(value))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let entries = query::synthesize_entries(cs, g, s, &allocated_key, &allocated_values)?;
+        let item = entries.last().expect("at least one entry");
 
         let acc = self.acc.clone().unwrap();
         let insertion_transcript = self.transcript.clone();
 
-        let (val, new_acc, new_transcript) = self.synthesize_query(
-            cs,
-            g,
-            s,
-            &allocated_key,
-            &acc,
-            &insertion_transcript,
-            &Boolean::Constant(true),
-        )?;
+        if let Some(multiplicity) = multiplicity {
+            let (new_transcript, count) = insertion_transcript.add_kv_count(
+                cs,
+                g,
+                s,
+                self.transcript_scheme,
+                item,
+                multiplicity as u64,
+                "new_transcript",
+            )?;
+            let mut new_acc_v = acc.hash().clone();
+            for (i, entry) in entries.iter().enumerate() {
+                new_acc_v = self.memoset.synthesize_add_n(
+                    &mut cs.namespace(|| format!("new_acc_v {i}")),
+                    &new_acc_v,
+                    entry,
+                    &count,
+                )?;
+            }
+            let new_acc = AllocatedPtr::alloc_tag(
+                &mut cs.namespace(|| "new_acc"),
+                ExprTag::Num.to_field(),
+                new_acc_v,
+            )?;
+
+            self.acc = Some(new_acc);
+            self.transcript = new_transcript;
+            return Ok(());
+        }
+
+        let (new_acc, new_transcript) =
+            self.synthesize_insert_query(cs, g, s, &acc, &insertion_transcript, &entries, true)?;
 
-        if let Some(val_ptr) = val.get_value().map(|x| s.to_ptr(&x)) {
-            assert_eq!(value, val_ptr);
+        if let Some(commitment) = commitment {
+            *commitment = commitment.add(&mut cs.namespace(|| "commitment"), g, s, item)?;
         }
 
         self.acc = Some(new_acc);
@@ -828,12 +1617,12 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
         let acc = self.acc.clone().unwrap();
         let transcript = self.transcript.clone();
 
-        let (val, new_acc, new_transcript) = circuit_query
-            .synthesize_eval(&mut cs.namespace(|| "eval"), g, s, self, &acc, &transcript)
+        let (values, new_acc, new_transcript) = circuit_query
+            .synthesize_eval_values(&mut cs.namespace(|| "eval"), g, s, self, &acc, &transcript)
             .unwrap();
 
         let (new_acc, new_transcript) =
-            self.synthesize_remove(cs, g, s, &new_acc, &new_transcript, allocated_key, &val)?;
+            self.synthesize_remove(cs, g, s, &new_acc, &new_transcript, allocated_key, &values)?;
 
         // Prover can choose non-deterministically whether or not a given query is a dummy, to allow for padding.
         let final_acc = AllocatedPtr::pick(
@@ -854,11 +1643,6 @@ impl<F: LurkField> CircuitScope<F, LogMemoCircuit<F>> {
 
         Ok(())
     }
-
-    #[allow(dead_code)]
-    fn dbg_transcript(&self, s: &Store<F>) {
-        self.transcript.dbg(s);
-    }
 }
 
 pub trait CircuitMemoSet<F: LurkField>: Clone {
@@ -870,6 +1654,14 @@ pub trait CircuitMemoSet<F: LurkField>: Clone {
         count: &AllocatedNum<F>,
     ) -> Result<AllocatedNum<F>, SynthesisError>;
 
+    fn synthesize_add_n<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        acc: &AllocatedNum<F>,
+        kv: &AllocatedPtr<F>,
+        count: &AllocatedNum<F>,
+    ) -> Result<AllocatedNum<F>, SynthesisError>;
+
     fn allocated_r(&self) -> AllocatedNum<F>;
 
     // x is H(k,v) = hash part of (cons k v)
@@ -1024,6 +1816,19 @@ impl<F: LurkField> CircuitMemoSet<F> for LogMemoCircuit<F> {
         sub(&mut cs.namespace(|| "add to acc"), acc, &scaled)
     }
 
+    fn synthesize_add_n<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        acc: &AllocatedNum<F>,
+        kv: &AllocatedPtr<F>,
+        count: &AllocatedNum<F>,
+    ) -> Result<AllocatedNum<F>, SynthesisError> {
+        let kv_num = kv.hash().clone();
+        let element = self.synthesize_map_to_element(&mut cs.namespace(|| "element"), kv_num)?;
+        let scaled = element.mul(&mut cs.namespace(|| "scaled"), count)?;
+        acc.add(&mut cs.namespace(|| "add to acc"), &scaled)
+    }
+
     // x is H(k,v) = hash part of (cons k v)
     // 1 / r + x
     fn synthesize_map_to_element<CS: ConstraintSystem<F>>(
@@ -1055,7 +1860,7 @@ mod test {
 
     #[test]
     fn test_query_with_internal_insertion_transcript() {
-        test_query_aux(
+        test_query_aux::<F>(
             true,
             expect!["9430"],
             expect!["9463"],
@@ -1063,7 +1868,7 @@ mod test {
             expect!["10049"],
             1,
         );
-        test_query_aux(
+        test_query_aux::<F>(
             true,
             expect!["11174"],
             expect!["11213"],
@@ -1071,7 +1876,7 @@ mod test {
             expect!["11799"],
             3,
         );
-        test_query_aux(
+        test_query_aux::<F>(
             true,
             expect!["18216"],
             expect!["18279"],
@@ -1083,7 +1888,7 @@ mod test {
 
     #[test]
     fn test_query_without_internal_insertion_transcript() {
-        test_query_aux(
+        test_query_aux::<F>(
             false,
             expect!["7985"],
             expect!["8018"],
@@ -1091,7 +1896,7 @@ mod test {
             expect!["8604"],
             1,
         );
-        test_query_aux(
+        test_query_aux::<F>(
             false,
             expect!["9440"],
             expect!["9479"],
@@ -1099,7 +1904,7 @@ mod test {
             expect!["10065"],
             3,
         );
-        test_query_aux(
+        test_query_aux::<F>(
             false,
             expect!["15326"],
             expect!["15389"],
@@ -1109,7 +1914,290 @@ mod test {
         )
     }
 
-    fn test_query_aux(
+    #[test]
+    fn test_query_pasta() {
+        // Nothing in the coroutine circuits depends on the curve, so the counts are the same as over BN256.
+        test_query_aux::<pasta_curves::pallas::Scalar>(
+            true,
+            expect!["11174"],
+            expect!["11213"],
+            expect!["11756"],
+            expect!["11799"],
+            3,
+        );
+        test_query_aux::<pasta_curves::vesta::Scalar>(
+            false,
+            expect!["9440"],
+            expect!["9479"],
+            expect!["10022"],
+            expect!["10065"],
+            3,
+        );
+    }
+
+    #[test]
+    fn test_scope_stats() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 3);
+
+        let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+        let fact_3 = s.read_with_default_state("(factorial . 3)").unwrap();
+
+        scope.query(s, fact_4);
+        scope.query(s, fact_3);
+
+        let stats = scope.stats(s);
+
+        assert_eq!(2, stats.toplevel_insertions);
+        assert_eq!(4, stats.internal_insertions);
+        assert_eq!(BTreeMap::from([(0, 5)]), stats.unique_keys);
+        // (factorial . 3) is inserted once internally and once at the top level.
+        assert_eq!(2, stats.max_multiplicity);
+        // (factorial . 0) has no dependencies; every other key has exactly one.
+        assert_eq!(BTreeMap::from([(0, 1), (1, 4)]), stats.dependency_fan_out);
+        assert_eq!(BTreeMap::from([(0, 2)]), stats.chunks);
+        assert_eq!(2, stats.total_chunks());
+        assert_eq!(1, stats.padding(|_| 3));
+    }
+
+    #[test]
+    fn test_toplevel_dedup() {
+        let s = &Store::<F>::default();
+        let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+        let fact_3 = s.read_with_default_state("(factorial . 3)").unwrap();
+
+        let synthesize = |scope: &mut Scope<DemoQuery<F>, LogMemo<F>>| {
+            for query in [fact_4, fact_3, fact_4, fact_4] {
+                scope.query(s, query);
+            }
+            scope.finalize_transcript(s);
+            let cs = &mut TestConstraintSystem::new();
+            let g = &mut GlobalAllocator::default();
+            scope.synthesize(cs, g, s).unwrap();
+            assert!(cs.is_satisfied());
+            cs.num_constraints()
+        };
+
+        let mut plain: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 1);
+        let plain_constraints = synthesize(&mut plain);
+        assert_eq!(4, plain.toplevel_insertions.len());
+
+        let mut dedup: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 1).with_toplevel_dedup();
+        let dedup_constraints = synthesize(&mut dedup);
+        assert_eq!(2, dedup.toplevel_insertions.len());
+        assert!(dedup_constraints < plain_constraints);
+        assert_eq!(4, plain.stats(s).toplevel_insertions);
+        assert_eq!(4, dedup.stats(s).toplevel_insertions);
+
+        // Removals still account for every use.
+        let kv = Transcript::make_kv(s, fact_4, s.num_u64(24));
+        assert_eq!(3, dedup.memoset.count(&kv));
+        assert_eq!(F::ZERO, dedup.public_inputs(s).final_acc);
+        assert_eq!(
+            dedup.stats(s).total_chunks(),
+            dedup.synthesize_chunks_parallel(s).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_public_toplevel() {
+        let s = &Store::<F>::default();
+        let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+        let fact_3 = s.read_with_default_state("(factorial . 3)").unwrap();
+
+        let synthesize = |scope: &mut Scope<DemoQuery<F>, LogMemo<F>>| {
+            for query in [fact_4, fact_3] {
+                scope.query(s, query);
+            }
+            let transcript = scope.finalize_transcript(s);
+            let cs = &mut TestConstraintSystem::new();
+            let g = &mut GlobalAllocator::default();
+            scope.synthesize(cs, g, s).unwrap();
+            assert!(cs.is_satisfied());
+            let (items, _) = s.fetch_list(&transcript.acc).unwrap();
+            items.len()
+        };
+
+        let mut transcribed: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 1);
+        let transcribed_items = synthesize(&mut transcribed);
+        let mut public: Scope<DemoQuery<F>, LogMemo<F>> =
+            Scope::new(false, 1).with_public_toplevel();
+        let public_items = synthesize(&mut public);
+        // The two top-level insertions are replaced by their commitment, which the circuit builds.
+        assert_eq!(transcribed_items - 1, public_items);
+        assert_ne!(transcribed.memoset.r(), public.memoset.r());
+
+        assert_eq!(F::ZERO, public.public_inputs(s).final_acc);
+        assert_eq!(
+            public.stats(s).total_chunks(),
+            public.synthesize_chunks_parallel(s).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn test_synthesize_chunks_parallel() {
+        for transcribe_internal_insertions in [false, true] {
+            let s = &Store::<F>::default();
+            let mut scope: Scope<DemoQuery<F>, LogMemo<F>> =
+                Scope::new(transcribe_internal_insertions, 2);
+
+            let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+            let fact_3 = s.read_with_default_state("(factorial . 3)").unwrap();
+            scope.query(s, fact_4);
+            scope.query(s, fact_3);
+
+            let chunks = scope.synthesize_chunks_parallel(s).unwrap();
+            assert_eq!(scope.stats(s).total_chunks(), chunks.len());
+            assert_eq!(
+                vec![0, 1, 2],
+                chunks.iter().map(|c| c.chunk_index).collect::<Vec<_>>()
+            );
+
+            // Each chunk is independently satisfiable from its natively-computed input.
+            for spec in scope.chunk_specs(s) {
+                let cs = &mut TestConstraintSystem::<F>::new();
+                let (z_in, z_out) = scope.synthesize_chunk(cs, s, &spec).unwrap();
+                let chunk = &chunks[spec.chunk_index];
+                assert_eq!(chunk.z_in, z_in);
+                assert_eq!(chunk.z_out, z_out);
+                assert!(cs.is_satisfied());
+            }
+        }
+    }
+
+    #[test]
+    fn test_synthesize_chunks_resumable() {
+        let s = &Store::<F>::default();
+        let tmp_dir = tempfile::Builder::new().prefix("tmp").tempdir().unwrap();
+        let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+        let checkpoint = tmp_dir.join("checkpoint");
+
+        let new_scope = || {
+            let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 1);
+            scope.query(s, s.read_with_default_state("(factorial . 4)").unwrap());
+            scope
+        };
+
+        // Crash while handling the third chunk.
+        let mut seen = vec![];
+        let crashed = new_scope().synthesize_chunks_resumable(s, &checkpoint, |chunk| {
+            if seen.len() == 2 {
+                bail!("crash");
+            }
+            seen.push(chunk.chunk_index);
+            Ok(())
+        });
+        assert!(crashed.is_err());
+        assert_eq!(
+            Some(2),
+            ChunkCheckpoint::<F>::read(&checkpoint)
+                .unwrap()
+                .map(|c| c.chunks_completed)
+        );
+
+        // Resuming picks up at the third chunk.
+        let mut resumed = vec![];
+        let state = new_scope()
+            .synthesize_chunks_resumable(s, &checkpoint, |chunk| {
+                resumed.push(chunk.chunk_index);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(vec![2, 3, 4], resumed);
+        assert_eq!(5, state.chunks_completed);
+        assert_eq!(F::ZERO, state.acc);
+        assert_eq!(state.r, *state.transcript.value());
+
+        // A checkpoint from a different scope is rejected.
+        let mut other: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 1);
+        other.query(s, s.read_with_default_state("(factorial . 3)").unwrap());
+        assert!(other
+            .synthesize_chunks_resumable(s, &checkpoint, |_| Ok(()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_synthesize_internal_query_values() {
+        use ff::Field;
+
+        let s = &Store::<F>::default();
+        let key = s.read_with_default_state("(divmod 7 . 2)").unwrap();
+        let vals = [s.num_u64(3), s.num_u64(1)];
+        let queries = HashMap::from([(key, query::values(s, &vals))]);
+
+        let synthesize = |arity: Option<usize>| {
+            let cs = &mut TestConstraintSystem::<F>::new();
+            let g = &mut GlobalAllocator::default();
+            let memoset = LogMemoCircuit {
+                multiset: MultiSet::new(),
+                r: AllocatedNum::alloc_infallible(&mut cs.namespace(|| "r"), || F::from_u64(42)),
+            };
+            let mut scope = CircuitScope::from_queries(cs, g, s, memoset, &queries, true);
+            scope.init(cs, g, s);
+            let (acc, transcript) = (scope.acc.clone().unwrap(), scope.transcript.clone());
+            let allocated_key =
+                AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "key"), || s.hash_ptr(&key));
+            let not_dummy = Boolean::Constant(true);
+            let (values, acc, transcript) = match arity {
+                Some(arity) => scope
+                    .synthesize_internal_query_values(
+                        cs,
+                        g,
+                        s,
+                        &allocated_key,
+                        &acc,
+                        &transcript,
+                        &not_dummy,
+                        arity,
+                    )
+                    .unwrap(),
+                None => {
+                    let (value, acc, transcript) = scope
+                        .synthesize_internal_query(
+                            cs,
+                            g,
+                            s,
+                            &allocated_key,
+                            &acc,
+                            &transcript,
+                            &not_dummy,
+                        )
+                        .unwrap();
+                    (vec![value], acc, transcript)
+                }
+            };
+            assert!(cs.is_satisfied());
+            let values = values
+                .iter()
+                .map(|value| s.to_ptr(&value.get_value().unwrap()))
+                .collect::<Vec<_>>();
+            (
+                values,
+                acc.get_value::<Tag>(),
+                transcript.acc.get_value::<Tag>(),
+            )
+        };
+
+        let (values, acc, transcript) = synthesize(Some(2));
+        assert_eq!(vals.to_vec(), values);
+        // each value is a separate entry, and the last one, which binds them all, is transcribed
+        let entries = query::entries(s, key, &vals);
+        let r = F::from_u64(42);
+        let expected_acc = entries
+            .iter()
+            .map(|entry| (r + s.hash_ptr(entry).value()).invert().unwrap())
+            .fold(F::ZERO, |acc, element| acc + element);
+        assert_eq!(Some(expected_acc), acc.map(|acc| *acc.value()));
+        let mut expected_transcript = Transcript::new(s);
+        expected_transcript.add_kv(s, TranscriptScheme::Cons, entries[1], None);
+        assert_eq!(Some(s.hash_ptr(&expected_transcript.acc)), transcript);
+        // unlike an insertion of the encoded value
+        let (value, single_acc, _) = synthesize(None);
+        assert_eq!(vec![query::values(s, &vals)], value);
+        assert_ne!(single_acc, acc);
+    }
+
+    fn test_query_aux<F: LurkField>(
         transcribe_internal_insertions: bool,
         expected_constraints_simple: Expect,
         expected_aux_simple: Expect,
@@ -20,6 +20,16 @@ impl<T: Hash + Eq> MultiSet<T> {
         self.cardinality += 1;
     }
 
+    /// Removes one copy of `element`, which must be present.
+    pub(crate) fn remove(&mut self, element: &T) {
+        let count = self.map.get_mut(element).expect("element not in multiset");
+        *count -= 1;
+        if *count == 0 {
+            self.map.remove(element);
+        }
+        self.cardinality -= 1;
+    }
+
     pub(crate) fn get(&self, element: &T) -> Option<usize> {
         self.map.get(element).copied()
     }
@@ -33,6 +43,16 @@ impl<T: Hash + Eq> MultiSet<T> {
     pub(crate) fn len(&self) -> usize {
         self.map.len()
     }
+
+    /// The distinct elements with their multiplicities, in no particular order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&T, usize)> {
+        self.map.iter().map(|(element, count)| (element, *count))
+    }
+
+    /// The largest multiplicity of any element, or zero if the multiset is empty.
+    pub(crate) fn max_multiplicity(&self) -> usize {
+        self.map.values().copied().max().unwrap_or(0)
+    }
 }
 
 #[cfg(test)]
@@ -54,6 +74,13 @@ mod test {
             assert_eq!(c, m.cardinality());
             assert_eq!(Some(i), m.get(&i));
             assert_eq!(None, m.get(&(i + n)));
+            assert_eq!(i, m.max_multiplicity());
         }
+
+        m.remove(&1);
+        assert_eq!(None, m.get(&1));
+        m.remove(&2);
+        assert_eq!(Some(1), m.get(&2));
+        assert_eq!(c - 2, m.cardinality());
     }
 }
@@ -0,0 +1,188 @@
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem, SynthesisError};
+use std::marker::PhantomData;
+
+use super::{
+    query::{versioned_query_body, versioned_symbol, CircuitQuery, Query},
+    CircuitScope, CircuitTranscript, LogMemo, LogMemoCircuit, Scope,
+};
+use crate::circuit::gadgets::constraints::enforce_equal;
+use crate::circuit::gadgets::data::hash_poseidon;
+use crate::circuit::gadgets::pointer::AllocatedPtr;
+use crate::field::LurkField;
+use crate::lem::circuit::GlobalAllocator;
+use crate::lem::{
+    pointers::{Ptr, ZPtr},
+    store::Store,
+    tag::Tag,
+};
+use crate::symbol::Symbol;
+use crate::tag::ExprTag;
+
+/// The version of `OpenQuery`, which is bumped when its semantics change
+const VERSION: u32 = 0;
+
+fn open_name() -> Symbol {
+    Symbol::sym(&["lurk", "open"])
+}
+
+fn open_symbol() -> Symbol {
+    versioned_symbol(&open_name(), VERSION)
+}
+
+/// `(open . comm)`, where `comm` is a commitment the store can open. Its response is the committed payload, as with
+/// Lurk's `open`. The circuit proves the opening by hashing the secret and the payload back to `comm`, so a proof
+/// opening the same commitment many times only proves it once.
+#[derive(Debug, Clone)]
+pub struct OpenQuery<F> {
+    comm: Ptr,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField> OpenQuery<F> {
+    /// The query opening `comm`, if it's a commitment known to `s`
+    pub fn new(s: &Store<F>, comm: Ptr) -> Option<Self> {
+        if !comm.has_tag(&Tag::Expr(ExprTag::Comm)) {
+            return None;
+        }
+        s.open(*s.hash_ptr(&comm).value())?;
+        Some(Self {
+            comm,
+            _p: PhantomData,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenCircuitQuery<F: LurkField> {
+    comm: AllocatedPtr<F>,
+    secret: F,
+    payload: ZPtr<F>,
+}
+
+impl<F: LurkField> Query<F> for OpenQuery<F> {
+    type CQ = OpenCircuitQuery<F>;
+
+    fn eval(&self, s: &Store<F>, _scope: &mut Scope<Self, LogMemo<F>>) -> Ptr {
+        let (_, payload) = s
+            .open(*s.hash_ptr(&self.comm).value())
+            .expect("checked by `OpenQuery::new`");
+        *payload
+    }
+
+    fn symbol(&self) -> Symbol {
+        open_symbol()
+    }
+
+    fn from_ptr(s: &Store<F>, ptr: &Ptr) -> Option<Self> {
+        let comm = versioned_query_body(s, ptr, &open_name(), VERSION)?;
+        Self::new(s, comm)
+    }
+
+    fn to_ptr(&self, s: &Store<F>) -> Ptr {
+        s.cons(self.symbol_ptr(s), self.comm)
+    }
+
+    fn to_circuit<CS: ConstraintSystem<F>>(&self, cs: &mut CS, s: &Store<F>) -> Self::CQ {
+        let comm = s.hash_ptr(&self.comm);
+        let (secret, payload) = s.open(*comm.value()).expect("checked by `OpenQuery::new`");
+        OpenCircuitQuery {
+            comm: AllocatedPtr::alloc_infallible(cs, || comm),
+            secret: *secret,
+            payload: s.hash_ptr(payload),
+        }
+    }
+
+    fn dummy_from_index(s: &Store<F>, index: usize) -> Self {
+        assert_eq!(index, 0);
+        Self::new(s, s.commit(s.intern_nil())).unwrap()
+    }
+
+    fn index(&self) -> usize {
+        0
+    }
+
+    fn count() -> usize {
+        1
+    }
+}
+
+impl<F: LurkField> CircuitQuery<F> for OpenCircuitQuery<F> {
+    // Opening doesn't recurse.
+    type Ctx = ();
+
+    fn synthesize_eval<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        store: &Store<F>,
+        _scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        acc: &AllocatedPtr<F>,
+        transcript: &CircuitTranscript<F>,
+    ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        let secret = AllocatedNum::alloc_infallible(&mut cs.namespace(|| "secret"), || self.secret);
+        let payload =
+            AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "payload"), || self.payload);
+        let hash = hash_poseidon(
+            &mut cs.namespace(|| "hash"),
+            vec![secret, payload.tag().clone(), payload.hash().clone()],
+            store.poseidon_cache.constants.c3(),
+        )?;
+        enforce_equal(cs, || "comm hash", &hash, self.comm.hash());
+        let comm_tag = g.alloc_tag(cs, &ExprTag::Comm);
+        enforce_equal(cs, || "comm tag", comm_tag, self.comm.tag());
+        Ok((payload, acc.clone(), transcript.clone()))
+    }
+
+    fn from_ptr<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, ptr: &Ptr) -> Option<Self> {
+        OpenQuery::<F>::from_ptr(s, ptr).map(|q| q.to_circuit(cs, s))
+    }
+
+    fn dummy_from_index<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, index: usize) -> Self {
+        OpenQuery::<F>::dummy_from_index(s, index).to_circuit(cs, s)
+    }
+
+    fn symbol(&self) -> Symbol {
+        open_symbol()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::testing::QueryHarness;
+
+    #[test]
+    fn test_open_query() {
+        let s = &Store::<F>::default();
+        let payload = s.read_with_default_state("(1 2 3)").unwrap();
+        let hidden = s.hide(F::from(42), payload);
+        let committed = s.commit(s.num_u64(7));
+
+        let mut scope: Scope<OpenQuery<F>, LogMemo<F>> = Scope::default();
+        for (comm, expected) in [
+            (hidden, payload),
+            (committed, s.num_u64(7)),
+            (hidden, payload),
+        ] {
+            let query = OpenQuery::new(s, comm).unwrap().to_ptr(s);
+            assert_eq!(expected, scope.query(s, query));
+        }
+        // The repeated opening is proved once.
+        assert_eq!(2, scope.stats(s).unique_keys[&0]);
+
+        let unknown = s.cons(s.intern_symbol(&open_symbol()), s.comm(F::from(123)));
+        assert!(OpenQuery::<F>::from_ptr(s, &unknown).is_none());
+        let not_comm = s.cons(s.intern_symbol(&open_symbol()), s.num_u64(7));
+        assert!(OpenQuery::<F>::from_ptr(s, &not_comm).is_none());
+
+        let queries =
+            [hidden, committed, hidden].map(|comm| OpenQuery::new(s, comm).unwrap().to_ptr(s));
+        let report = QueryHarness::<F, OpenQuery<F>>::default()
+            .with_rc(2)
+            .check(s, &queries)
+            .unwrap();
+        assert_eq!(1, report.chunks);
+    }
+}
@@ -0,0 +1,227 @@
+//! A content-addressed disk cache for the public parameters of `FoldingBackend`s.
+//!
+//! Parameters are keyed by a hash of everything they depend on: the backend, the field, and the R1CS shape of the
+//! chunk circuit of each query index, which already reflects its `rc` and whether internal insertions are
+//! transcribed. Changing a query's circuit hence never picks stale parameters. Each file starts with a header holding
+//! the format version and the key, so that files written by an incompatible version are regenerated rather than
+//! misread.
+
+use anyhow::{Context, Result};
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem};
+use camino::{Utf8Path, Utf8PathBuf};
+use ff::Field;
+use nova::traits::circuit::StepCircuit;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use tracing::{info, warn};
+
+use super::{io::CoroutineIO, query::Query, shape::ShapeCS, FoldingBackend, LogMemo, Scope};
+use crate::field::LurkField;
+use crate::lem::store::Store;
+use crate::public_parameters::disk_cache::public_params_dir;
+
+/// Bump whenever the layout of cached parameters changes
+const FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    key: String,
+}
+
+/// A directory of public parameters, one file per key.
+pub struct FoldingParamsCache {
+    dir: Utf8PathBuf,
+}
+
+impl FoldingParamsCache {
+    pub fn new(dir: &Utf8Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {dir}"))?;
+        Ok(Self {
+            dir: dir.to_owned(),
+        })
+    }
+
+    /// The default location, next to the public parameters of the Lurk circuits.
+    pub fn default_dir() -> Utf8PathBuf {
+        public_params_dir().join("coroutine_params")
+    }
+
+    fn path(&self, key: &str) -> Utf8PathBuf {
+        self.dir.join(format!("{key}.params"))
+    }
+
+    /// Whether there are up-to-date parameters for `key`.
+    pub fn contains(&self, key: &str) -> bool {
+        matches!(self.read_header(key), Ok(Header { version, .. }) if version == FORMAT_VERSION)
+    }
+
+    fn read_header(&self, key: &str) -> Result<Header> {
+        let path = self.path(key);
+        let file = File::open(&path).with_context(|| format!("opening {path}"))?;
+        Ok(bincode::deserialize_from(BufReader::new(file))?)
+    }
+
+    /// Reads the parameters for `key`, if present and written by the current format version.
+    pub fn read<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let path = self.path(key);
+        let Ok(file) = File::open(&path) else {
+            return Ok(None);
+        };
+        let mut reader = BufReader::new(file);
+        let header: Header = bincode::deserialize_from(&mut reader)
+            .with_context(|| format!("reading the header of {path}"))?;
+        if header.version != FORMAT_VERSION || header.key != key {
+            warn!(
+                "ignoring {path}, written with format version {} for key {}",
+                header.version, header.key
+            );
+            return Ok(None);
+        }
+        let params = bincode::deserialize_from(&mut reader)
+            .with_context(|| format!("reading public parameters from {path}"))?;
+        Ok(Some(params))
+    }
+
+    /// Writes the parameters for `key` to a temporary file that is then renamed, so concurrent readers never see a
+    /// partially written file.
+    pub fn write<T: Serialize>(&self, key: &str, params: &T) -> Result<()> {
+        let path = self.path(key);
+        let tmp = self.dir.join(format!("{key}.{}.tmp", std::process::id()));
+        {
+            let file = File::create(&tmp).with_context(|| format!("creating {tmp}"))?;
+            let mut writer = BufWriter::new(file);
+            let header = Header {
+                version: FORMAT_VERSION,
+                key: key.to_owned(),
+            };
+            bincode::serialize_into(&mut writer, &header)?;
+            bincode::serialize_into(&mut writer, params)?;
+        }
+        std::fs::rename(&tmp, &path).with_context(|| format!("renaming {tmp} to {path}"))?;
+        Ok(())
+    }
+}
+
+impl<F: LurkField, Q: Query<F> + Send + Sync> Scope<Q, LogMemo<F>> {
+    /// The cache key of the public parameters of `B` for the chunks of this scope.
+    pub fn folding_params_key<B: FoldingBackend<F>>(&self, s: &Store<F>) -> Result<String> {
+        let nil = s.hash_ptr(&s.intern_nil());
+        let z_blank = CoroutineIO::chunk(s, F::ZERO, nil, F::ZERO).to_field_elements();
+
+        let mut hasher = Sha256::new();
+        hasher.update(B::ID.as_bytes());
+        hasher.update(F::FIELD.to_string().as_bytes());
+        for query_index in 0..Q::count() {
+            let circuit = self.blank_chunk_circuit(s, query_index);
+            let mut cs = ShapeCS::<F>::new();
+            let z = z_blank
+                .iter()
+                .enumerate()
+                .map(|(i, x)| {
+                    AllocatedNum::alloc_infallible(cs.namespace(|| format!("z{i}")), || *x)
+                })
+                .collect::<Vec<_>>();
+            StepCircuit::synthesize(&circuit, &mut cs, &z)?;
+            hasher.update(bincode::serialize(&cs.shape)?);
+        }
+        Ok(format!("{}-{}", B::ID, hex::encode(hasher.finalize())))
+    }
+
+    /// Like `folding_public_params`, but reads the parameters from `cache` when possible, and writes them to it
+    /// otherwise.
+    pub fn cached_folding_public_params<B: FoldingBackend<F>>(
+        &self,
+        s: &Store<F>,
+        backend: &B,
+        cache: &FoldingParamsCache,
+    ) -> Result<B::PublicParams>
+    where
+        B::PublicParams: Serialize + DeserializeOwned,
+    {
+        let key = self.folding_params_key::<B>(s)?;
+        if let Some(pp) = cache.read(&key)? {
+            info!("loaded public parameters {key}");
+            return Ok(pp);
+        }
+        info!("generating public parameters {key}");
+        let pp = self.folding_public_params(s, backend)?;
+        cache.write(&key, &pp)?;
+        Ok(pp)
+    }
+
+    /// Makes sure `cache` holds the parameters of `backend` for this scope, generating them if needed, so that they
+    /// can be computed ahead of proving. Returns their key.
+    pub fn pregenerate_folding_public_params<B: FoldingBackend<F>>(
+        &self,
+        s: &Store<F>,
+        backend: &B,
+        cache: &FoldingParamsCache,
+    ) -> Result<String>
+    where
+        B::PublicParams: Serialize,
+    {
+        let key = self.folding_params_key::<B>(s)?;
+        if !cache.contains(&key) {
+            info!("generating public parameters {key}");
+            cache.write(&key, &self.folding_public_params(s, backend)?)?;
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::{demo::DemoQuery, MockBackend, NovaBackend};
+
+    #[test]
+    fn test_folding_params_cache() {
+        let s = &Store::<F>::default();
+        let tmp_dir = tempfile::Builder::new().prefix("tmp").tempdir().unwrap();
+        let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+        let cache = FoldingParamsCache::new(tmp_dir).unwrap();
+
+        let scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        let key = scope.folding_params_key::<NovaBackend>(s).unwrap();
+        assert_eq!(key, scope.folding_params_key::<NovaBackend>(s).unwrap());
+        assert_ne!(key, scope.folding_params_key::<MockBackend>(s).unwrap());
+        // the shape depends on `rc`
+        let other: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 3);
+        assert_ne!(key, other.folding_params_key::<NovaBackend>(s).unwrap());
+
+        assert!(!cache.contains(&key));
+        assert_eq!(
+            key,
+            scope
+                .pregenerate_folding_public_params(s, &NovaBackend, &cache)
+                .unwrap()
+        );
+        assert!(cache.contains(&key));
+        let pp = scope
+            .cached_folding_public_params(s, &NovaBackend, &cache)
+            .unwrap();
+        assert_eq!(
+            scope
+                .folding_public_params(s, &NovaBackend)
+                .unwrap()
+                .pp
+                .digest(),
+            pp.pp.digest()
+        );
+
+        // parameters written by another format version are ignored
+        let header = Header {
+            version: FORMAT_VERSION + 1,
+            key: key.clone(),
+        };
+        let file = File::create(cache.path(&key)).unwrap();
+        bincode::serialize_into(file, &header).unwrap();
+        assert!(!cache.contains(&key));
+        assert!(cache.read::<()>(&key).unwrap().is_none());
+    }
+}
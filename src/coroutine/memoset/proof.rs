@@ -0,0 +1,142 @@
+use anyhow::Result;
+use ff::Field;
+use nova::supernova::{snark::CompressedSNARK, RecursiveSNARK};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    backend::is_complete_scope_io, query::Query, CoroutinePublicInputs, FoldingBackend, LogMemo,
+    Scope, SuperNovaBackend,
+};
+use crate::lem::store::Store;
+use crate::proof::nova::{CurveCycleEquipped, Dual, E1};
+use crate::proof::supernova::{PublicParams, SS1, SS2};
+
+/// A SuperNova proof of every chunk of a `Scope`, along with the IO it was proved for.
+///
+/// Its size grows with the circuits of the query types. Use `compress` to get a constant-size proof.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct CoroutineProof<F: CurveCycleEquipped> {
+    snark: RecursiveSNARK<E1<F>>,
+    z0: Vec<F>,
+    zi: Vec<F>,
+}
+
+/// A `CoroutineProof` compressed into a Spartan SNARK of the folded instances, whose size and verification time don't
+/// depend on the number of chunks.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct CompressedProof<F: CurveCycleEquipped> {
+    snark: CompressedSNARK<E1<F>, SS1<F>, SS2<F>>,
+    z0: Vec<F>,
+    zi: Vec<F>,
+}
+
+impl<F: CurveCycleEquipped> CoroutineProof<F> {
+    /// Proves every chunk of `scope`, with public parameters from `Scope::folding_public_params` for
+    /// `SuperNovaBackend`
+    pub fn prove<Q: Query<F> + Send + Sync>(
+        scope: &mut Scope<Q, LogMemo<F>>,
+        s: &Store<F>,
+        pp: &PublicParams<F>,
+    ) -> Result<Self> {
+        let (snark, z0, zi) = scope.prove_with(s, &SuperNovaBackend, pp)?;
+        Ok(Self { snark, z0, zi })
+    }
+
+    pub fn z0(&self) -> &[F] {
+        &self.z0
+    }
+
+    pub fn zi(&self) -> &[F] {
+        &self.zi
+    }
+
+    /// Verifies the proof for the public inputs `inputs`, which the verifier must get from a source it trusts rather
+    /// than from the prover
+    pub fn verify(
+        &self,
+        pp: &PublicParams<F>,
+        s: &Store<F>,
+        inputs: &CoroutinePublicInputs<F>,
+    ) -> Result<bool> {
+        SuperNovaBackend.verify_scope(pp, &self.snark, s, inputs, &self.z0, &self.zi)
+    }
+
+    /// Compresses the proof. The prover key is generated on first use and cached in `pp`.
+    pub fn compress(&self, pp: &PublicParams<F>) -> Result<CompressedProof<F>> {
+        let snark = CompressedSNARK::<_, SS1<F>, SS2<F>>::prove(&pp.pp, pp.pk(), &self.snark)?;
+        Ok(CompressedProof {
+            snark,
+            z0: self.z0.clone(),
+            zi: self.zi.clone(),
+        })
+    }
+}
+
+impl<F: CurveCycleEquipped> CompressedProof<F> {
+    pub fn z0(&self) -> &[F] {
+        &self.z0
+    }
+
+    pub fn zi(&self) -> &[F] {
+        &self.zi
+    }
+
+    /// Verifies the proof for the public inputs `inputs`, which doesn't require the `Scope` nor the `Store` it was
+    /// proved with
+    pub fn verify(
+        &self,
+        pp: &PublicParams<F>,
+        s: &Store<F>,
+        inputs: &CoroutinePublicInputs<F>,
+    ) -> Result<bool> {
+        if !inputs.check_toplevel_acc(s)
+            || !inputs.matches_io(s, &self.z0, &self.zi)
+            || !is_complete_scope_io(&self.z0, &self.zi)?
+        {
+            return Ok(false);
+        }
+        let (zi_verified, _) = self
+            .snark
+            .verify(&pp.pp, pp.vk(), &self.z0, &[Dual::<F>::ZERO])?;
+        Ok(zi_verified == self.zi)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::demo::DemoQuery;
+
+    #[test]
+    fn test_compress() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::default();
+        scope.query(s, s.read_with_default_state("(factorial . 5)").unwrap());
+
+        let pp = scope.folding_public_params(s, &SuperNovaBackend).unwrap();
+        let inputs = scope.public_inputs(s);
+        let proof = CoroutineProof::prove(&mut scope, s, &pp).unwrap();
+        assert!(proof.verify(&pp, s, &inputs).unwrap());
+
+        let compressed = proof.compress(&pp).unwrap();
+        assert!(compressed.verify(&pp, s, &inputs).unwrap());
+
+        let bytes = bincode::serialize(&compressed).unwrap();
+        let mut compressed: CompressedProof<F> = bincode::deserialize(&bytes).unwrap();
+        assert!(compressed.verify(&pp, s, &inputs).unwrap());
+
+        // Both proofs are only valid for the query they were made for.
+        let mut other: Scope<DemoQuery<F>, LogMemo<F>> = Scope::default();
+        other.query(s, s.read_with_default_state("(factorial . 4)").unwrap());
+        let other_inputs = other.public_inputs(s);
+        assert!(!proof.verify(&pp, s, &other_inputs).unwrap());
+        assert!(!compressed.verify(&pp, s, &other_inputs).unwrap());
+
+        compressed.zi[7] += F::ONE;
+        assert!(!compressed.verify(&pp, s, &inputs).unwrap());
+    }
+}
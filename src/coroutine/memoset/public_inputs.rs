@@ -0,0 +1,426 @@
+//! The public IO of a coroutine proof, with a stable encoding that external verifiers can rely on.
+//!
+//! Version 3 of the byte encoding is laid out as follows, where every field element is written as its canonical
+//! little-endian representation (`LurkField::to_bytes`) and every tag as the field element it corresponds to:
+//!
+//! ```text
+//! version: u8 (= 3)
+//! toplevel transcription: u8 (0 = each, 1 = dedup, 2 = committed)
+//! transcript scheme: u8 (0 = cons, 1 = packed)
+//! toplevel count: u64, little-endian
+//! initial_acc
+//! final_acc
+//! transcript tag, transcript hash
+//! r
+//! for each top-level query, in query order: key tag, key hash, value tag, value hash, arity
+//! ```
+//!
+//! `to_field_elements` returns the same sequence of field elements, with the bytes and the count as field elements.
+//! The arity is the number of values the query returns, encoded in its value as `query::values`, each of which is a
+//! separate memoset entry.
+//!
+//! The public inputs determine the IO a complete proof of the scope starts from: verifiers re-derive the transcript of
+//! the top-level queries and their memoset accumulator, rather than trusting the prover's.
+
+use anyhow::{anyhow, bail, ensure, Result};
+use indexmap::IndexMap;
+
+use super::{query, CoroutineIO, LogMemo, MemoSet, Query, Scope, Transcript, TranscriptScheme};
+use crate::field::LurkField;
+use crate::lem::{pointers::Ptr, store::Store, tag::Tag};
+use crate::tag::Tag as XTag;
+use crate::z_ptr::ZPtr;
+
+const VERSION: u8 = 3;
+
+/// How the top-level queries of a scope enter its transcript
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ToplevelTranscription {
+    /// Each top-level query is an item, `(key . value)`, or its last memoset entry if it returns several values
+    #[default]
+    Each,
+    /// Each distinct top-level query is an item, `((key . value) . multiplicity)`, as `Scope::with_toplevel_dedup`
+    Dedup,
+    /// A single item, the list of every `(key . value)`, last first, commits to them, as
+    /// `Scope::with_public_toplevel`
+    Committed,
+}
+
+impl ToplevelTranscription {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Each => 0,
+            Self::Dedup => 1,
+            Self::Committed => 2,
+        }
+    }
+
+    fn from_u8(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(Self::Each),
+            1 => Ok(Self::Dedup),
+            2 => Ok(Self::Committed),
+            _ => bail!("unknown top-level transcription {byte}"),
+        }
+    }
+}
+
+fn scheme_to_u8(scheme: TranscriptScheme) -> u8 {
+    match scheme {
+        TranscriptScheme::Cons => 0,
+        TranscriptScheme::Packed => 1,
+    }
+}
+
+fn scheme_from_u8(byte: u8) -> Result<TranscriptScheme> {
+    match byte {
+        0 => Ok(TranscriptScheme::Cons),
+        1 => Ok(TranscriptScheme::Packed),
+        _ => bail!("unknown transcript scheme {byte}"),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CoroutinePublicInputs<F: LurkField> {
+    pub toplevel_transcription: ToplevelTranscription,
+    pub scheme: TranscriptScheme,
+    /// Memoset accumulator after the top-level insertions, which is where the first chunk starts.
+    pub initial_acc: F,
+    /// Memoset accumulator after the last chunk. It is zero if every deferred query was proved.
+    pub final_acc: F,
+    /// The complete transcript.
+    pub transcript: ZPtr<Tag, F>,
+    /// The Fiat-Shamir challenge derived from `transcript`.
+    pub r: F,
+    /// The top-level queries, their values and how many values they return, once per time they were queried, in
+    /// query order.
+    pub toplevel: Vec<(ZPtr<Tag, F>, ZPtr<Tag, F>, usize)>,
+}
+
+impl<F: LurkField> CoroutinePublicInputs<F> {
+    pub fn to_field_elements(&self) -> Vec<F> {
+        let mut elts = Vec::with_capacity(8 + 5 * self.toplevel.len());
+        elts.extend([
+            F::from_u64(self.toplevel_transcription.to_u8().into()),
+            F::from_u64(scheme_to_u8(self.scheme).into()),
+            F::from_u64(self.toplevel.len() as u64),
+        ]);
+        elts.extend([
+            self.initial_acc,
+            self.final_acc,
+            self.transcript.tag_field(),
+            *self.transcript.value(),
+            self.r,
+        ]);
+        for (key, value, arity) in &self.toplevel {
+            elts.extend([
+                key.tag_field(),
+                *key.value(),
+                value.tag_field(),
+                *value.value(),
+                F::from_u64(*arity as u64),
+            ]);
+        }
+        elts
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            VERSION,
+            self.toplevel_transcription.to_u8(),
+            scheme_to_u8(self.scheme),
+        ];
+        bytes.extend((self.toplevel.len() as u64).to_le_bytes());
+        for f in &self.to_field_elements()[3..] {
+            bytes.extend(f.to_bytes());
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some((&version, rest)) = bytes.split_first() else {
+            bail!("empty public inputs");
+        };
+        ensure!(
+            version == VERSION,
+            "unsupported public inputs version {version}"
+        );
+        ensure!(rest.len() >= 10, "public inputs too short");
+        let toplevel_transcription = ToplevelTranscription::from_u8(rest[0])?;
+        let scheme = scheme_from_u8(rest[1])?;
+        let (count, rest) = rest[2..].split_at(8);
+        let count = u64::from_le_bytes(count.try_into().expect("8 bytes"));
+
+        let width = F::ZERO.to_bytes().len();
+        let expected = usize::try_from(count)
+            .ok()
+            .and_then(|count| count.checked_mul(5))
+            .and_then(|elts| elts.checked_add(5))
+            .and_then(|elts| elts.checked_mul(width))
+            .ok_or_else(|| anyhow!("too many top-level queries: {count}"))?;
+        ensure!(
+            rest.len() == expected,
+            "expected {expected} bytes of field elements, found {}",
+            rest.len()
+        );
+
+        let elts = rest
+            .chunks(width)
+            .map(|chunk| F::from_bytes(chunk).ok_or_else(|| anyhow!("invalid field element")))
+            .collect::<Result<Vec<_>>>()?;
+        let z_ptr = |tag: &F, hash: &F| -> Result<ZPtr<Tag, F>> {
+            let tag = Tag::from_field(tag).ok_or_else(|| anyhow!("invalid tag"))?;
+            Ok(ZPtr::from_parts(tag, *hash))
+        };
+
+        let toplevel = elts[5..]
+            .chunks(5)
+            .map(|kv| {
+                let arity = kv[4]
+                    .to_u64()
+                    .and_then(|arity| usize::try_from(arity).ok())
+                    .filter(|arity| *arity > 0)
+                    .ok_or_else(|| anyhow!("invalid arity"))?;
+                Ok((z_ptr(&kv[0], &kv[1])?, z_ptr(&kv[2], &kv[3])?, arity))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            toplevel_transcription,
+            scheme,
+            initial_acc: elts[0],
+            final_acc: elts[1],
+            transcript: z_ptr(&elts[2], &elts[3])?,
+            r: elts[4],
+            toplevel,
+        })
+    }
+
+    /// The memoset entries of each top-level query, as `query::entries`, or `None` if a value doesn't encode as many
+    /// values as its arity
+    fn toplevel_entries(&self, s: &Store<F>) -> Option<Vec<Vec<Ptr>>> {
+        self.toplevel
+            .iter()
+            .map(|(key, value, arity)| {
+                query::value_entries(s, s.to_ptr(key), s.to_ptr(value), *arity)
+            })
+            .collect()
+    }
+
+    /// The transcript items of the top-level queries, each with the number of times it was queried. With `Dedup`,
+    /// repeated queries are grouped by first occurrence, as `Scope::query` does.
+    fn toplevel_counts(&self, entries: &[Vec<Ptr>]) -> Vec<(Ptr, usize)> {
+        let items = entries
+            .iter()
+            .map(|entries| *entries.last().expect("at least one entry"));
+        if self.toplevel_transcription != ToplevelTranscription::Dedup {
+            return items.map(|item| (item, 1)).collect();
+        }
+        let mut grouped = IndexMap::new();
+        for item in items {
+            *grouped.entry(item).or_insert(0) += 1;
+        }
+        grouped.into_iter().collect()
+    }
+
+    /// The transcript of the top-level queries alone, which is where the first chunk starts, or `None` if a value
+    /// doesn't encode as many values as its arity
+    pub fn initial_transcript(&self, s: &Store<F>) -> Option<ZPtr<Tag, F>> {
+        let mut transcript = Transcript::new(s);
+        let counts = self.toplevel_counts(&self.toplevel_entries(s)?);
+        let items = Transcript::toplevel_items(s, self.toplevel_transcription, &counts);
+        for (item, count) in items {
+            transcript.add_kv(s, self.scheme, item, count);
+        }
+        Some(s.hash_ptr(&transcript.acc))
+    }
+
+    /// Whether `z0` and `zi`, the IO of a proof of a scope, agree with these public inputs. The top-level queries
+    /// aren't part of that IO: they are bound to it through the transcript the first chunk starts from, which must be
+    /// `initial_transcript`, and through `initial_acc`, which verifiers must also check with `check_toplevel_acc`.
+    pub fn matches_io(&self, s: &Store<F>, z0: &[F], zi: &[F]) -> bool {
+        let (Ok(z0), Ok(zi)) = (
+            CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(z0),
+            CoroutineIO::<ZPtr<Tag, F>>::from_field_elements(zi),
+        ) else {
+            return false;
+        };
+        *z0.memoset_acc.value() == self.initial_acc
+            && Some(z0.transcript) == self.initial_transcript(s)
+            && *z0.r.value() == self.r
+            && *zi.memoset_acc.value() == self.final_acc
+            && zi.transcript == self.transcript
+            && *zi.r.value() == self.r
+    }
+
+    /// Whether `initial_acc` is the memoset accumulator of the top-level queries and their values alone. Otherwise, a
+    /// prover could start from an accumulator that cancels out whatever it didn't prove.
+    pub fn check_toplevel_acc(&self, s: &Store<F>) -> bool {
+        let Some(entries) = self.toplevel_entries(s) else {
+            return false;
+        };
+        let mut acc = F::ZERO;
+        for entry in entries.iter().flatten() {
+            let element: Option<F> = (self.r + s.hash_ptr(entry).value()).invert().into();
+            let Some(element) = element else {
+                return false;
+            };
+            acc += element;
+        }
+        acc == self.initial_acc
+    }
+
+    /// Whether every top-level query of `other`, with its value, is also one of these, as many times. A party whose
+    /// scope was merged into another checks its queries were included this way.
+    pub fn contains_toplevel(&self, other: &Self) -> bool {
+        let mut remaining = self.toplevel.clone();
+        other.toplevel.iter().all(|kv| {
+            let found = remaining.iter().position(|x| x == kv);
+            found.map(|i| remaining.swap_remove(i)).is_some()
+        })
+    }
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// Collects the public IO of the proof of this scope, finalizing the transcript if necessary.
+    pub fn public_inputs(&mut self, s: &Store<F>) -> CoroutinePublicInputs<F> {
+        self.ensure_transcript_finalized(s);
+        let (specs, final_acc) = self.replay(s);
+        let transcript = self
+            .memoset
+            .transcript
+            .get()
+            .expect("transcript not finalized");
+
+        let toplevel = self
+            .toplevel_insertions
+            .iter()
+            .flat_map(|kv| {
+                let (key, value) = s.try_car_cdr(kv).expect("kv should be cons");
+                let arity = Q::from_ptr(s, &key).map_or(1, |query| query.arity());
+                std::iter::repeat((s.hash_ptr(&key), s.hash_ptr(&value), arity))
+                    .take(self.toplevel_multiplicity(kv))
+            })
+            .collect();
+
+        CoroutinePublicInputs {
+            toplevel_transcription: self.toplevel_transcription(),
+            scheme: self.transcript_scheme,
+            initial_acc: specs.first().map_or(F::ZERO, |spec| spec.acc),
+            final_acc,
+            transcript: s.hash_ptr(&transcript.acc),
+            r: *self.memoset.r().expect("transcript not finalized"),
+            toplevel,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coroutine::memoset::{demo::DemoQuery, MockBackend};
+    use ff::Field;
+    use halo2curves::bn256::Fr as F;
+
+    #[test]
+    fn test_public_inputs_roundtrip() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::default();
+        let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+        let fact_3 = s.read_with_default_state("(factorial . 3)").unwrap();
+        scope.query(s, fact_4);
+        scope.query(s, fact_3);
+
+        let public_inputs = scope.public_inputs(s);
+        assert_eq!(F::ZERO, public_inputs.final_acc);
+        assert_ne!(F::ZERO, public_inputs.initial_acc);
+        assert_eq!(public_inputs.r, *public_inputs.transcript.value());
+        assert_eq!(
+            vec![
+                (s.hash_ptr(&fact_4), s.hash_ptr(&s.num_u64(24)), 1),
+                (s.hash_ptr(&fact_3), s.hash_ptr(&s.num_u64(6)), 1),
+            ],
+            public_inputs.toplevel
+        );
+
+        let bytes = public_inputs.to_bytes();
+        assert_eq!(3 + 8 + 15 * 32, bytes.len());
+        assert_eq!(18, public_inputs.to_field_elements().len());
+        assert_eq!(
+            public_inputs,
+            CoroutinePublicInputs::from_bytes(&bytes).unwrap()
+        );
+
+        assert!(CoroutinePublicInputs::<F>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut wrong_version = bytes.clone();
+        wrong_version[0] = 1;
+        assert!(CoroutinePublicInputs::<F>::from_bytes(&wrong_version).is_err());
+        let mut wrong_scheme = bytes.clone();
+        wrong_scheme[2] = 2;
+        assert!(CoroutinePublicInputs::<F>::from_bytes(&wrong_scheme).is_err());
+        // a count whose size overflows
+        let mut huge_count = bytes;
+        huge_count[3..11].copy_from_slice(&(u64::MAX / 5).to_le_bytes());
+        assert!(CoroutinePublicInputs::<F>::from_bytes(&huge_count).is_err());
+    }
+
+    #[test]
+    fn test_public_inputs_match_io() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::default();
+        scope.query(s, s.read_with_default_state("(factorial . 3)").unwrap());
+
+        let public_inputs = scope.public_inputs(s);
+        let (_, mut z0, mut zi) = scope.prove_with(s, &MockBackend, &()).unwrap();
+        assert!(public_inputs.matches_io(s, &z0, &zi));
+        assert!(!public_inputs.matches_io(s, &zi, &z0));
+
+        zi[7] += F::ONE;
+        assert!(!public_inputs.matches_io(s, &z0, &zi));
+        zi[7] -= F::ONE;
+        // The first chunk must start from the transcript of the top-level queries.
+        z0[9] += F::ONE;
+        assert!(!public_inputs.matches_io(s, &z0, &zi));
+    }
+
+    #[test]
+    fn test_check_toplevel_acc() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::default().with_public_toplevel();
+        scope.query(s, s.read_with_default_state("(factorial . 4)").unwrap());
+        scope.query(s, s.read_with_default_state("(factorial . 3)").unwrap());
+
+        let mut public_inputs = scope.public_inputs(s);
+        assert_eq!(
+            ToplevelTranscription::Committed,
+            public_inputs.toplevel_transcription
+        );
+        assert!(public_inputs.check_toplevel_acc(s));
+        let (_, z0, zi) = scope.prove_with(s, &MockBackend, &()).unwrap();
+        assert!(public_inputs.matches_io(s, &z0, &zi));
+
+        // A verifier expecting another value rejects the proof, whose transcript commits to the actual ones.
+        public_inputs.toplevel[1].1 = s.hash_ptr(&s.num_u64(7));
+        assert!(!public_inputs.check_toplevel_acc(s));
+        assert!(!public_inputs.matches_io(s, &z0, &zi));
+    }
+
+    #[test]
+    fn test_check_dedup_toplevel_acc() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::default().with_toplevel_dedup();
+        let fact_3 = s.read_with_default_state("(factorial . 3)").unwrap();
+        let fact_4 = s.read_with_default_state("(factorial . 4)").unwrap();
+        for query in [fact_3, fact_4, fact_3] {
+            scope.query(s, query);
+        }
+
+        // Repeated queries are listed once per use, so that verifiers can re-derive the multiplicities.
+        let public_inputs = scope.public_inputs(s);
+        assert_eq!(3, public_inputs.toplevel.len());
+        assert!(public_inputs.check_toplevel_acc(s));
+        let (_, z0, zi) = scope.prove_with(s, &MockBackend, &()).unwrap();
+        assert!(public_inputs.matches_io(s, &z0, &zi));
+    }
+}
@@ -1,12 +1,19 @@
 use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use thiserror::Error;
+use tracing::warn;
 
-use super::{CircuitScope, CircuitTranscript, LogMemo, LogMemoCircuit, Scope};
+use super::{CircuitScope, CircuitTranscript, LogMemo, LogMemoCircuit, Scope, Transcript};
+use crate::circuit::gadgets::data::{construct_cons, construct_list};
 use crate::circuit::gadgets::pointer::AllocatedPtr;
-use crate::coprocessor::gadgets::construct_cons;
 use crate::field::LurkField;
 use crate::lem::circuit::GlobalAllocator;
-use crate::lem::{pointers::Ptr, store::Store};
+use crate::lem::{
+    pointers::{Ptr, ZPtr},
+    store::Store,
+    tag::Tag,
+};
 use crate::symbol::Symbol;
+use crate::tag::ExprTag;
 
 pub trait Query<F: LurkField>
 where
@@ -37,12 +44,22 @@ where
     fn index(&self) -> usize;
     /// How many types of query are provided?
     fn count() -> usize;
+
+    /// How many values the query returns. A query with several values returns them encoded with `values`, but passes
+    /// them to and from the memoset as separate entries, with `entries`.
+    fn arity(&self) -> usize {
+        1
+    }
 }
 
 pub trait CircuitQuery<F: LurkField>
 where
     Self: Sized + Clone,
 {
+    /// Per-evaluation context, created once at the start of `synthesize_eval` and passed through `recurse` to
+    /// `post_recursion`. Use it to carry allocated constants or intermediate wires across the recursion boundary.
+    type Ctx;
+
     fn synthesize_eval<CS: ConstraintSystem<F>>(
         &self,
         cs: &mut CS,
@@ -53,6 +70,22 @@ where
         transcript: &CircuitTranscript<F>,
     ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError>;
 
+    /// Like `synthesize_eval`, returning each of the query's values separately. Queries whose `Query::arity` is above
+    /// one must return that many values.
+    fn synthesize_eval_values<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        store: &Store<F>,
+        scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        acc: &AllocatedPtr<F>,
+        transcript: &CircuitTranscript<F>,
+    ) -> Result<(Vec<AllocatedPtr<F>>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        let (value, acc, transcript) =
+            self.synthesize_eval(cs, g, store, scope, acc, transcript)?;
+        Ok((vec![value], acc, transcript))
+    }
+
     fn symbol(&self) -> Symbol;
 
     fn symbol_ptr(&self, s: &Store<F>) -> Ptr {
@@ -64,21 +97,28 @@ where
     fn dummy_from_index<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, index: usize) -> Self;
 }
 
-pub(crate) trait RecursiveQuery<F: LurkField>: CircuitQuery<F> {
+/// A `CircuitQuery` that may make a subquery of its own kind. `recurse` synthesizes the subquery through the scope, so
+/// implementors only provide `post_recursion` to combine its result.
+pub trait RecursiveQuery<F: LurkField>: CircuitQuery<F> {
+    /// Combines the result of the subquery into this query's result. Defaults to returning it unchanged.
     fn post_recursion<CS: ConstraintSystem<F>>(
         &self,
         _cs: &mut CS,
+        _ctx: &Self::Ctx,
         subquery_result: AllocatedPtr<F>,
     ) -> Result<AllocatedPtr<F>, SynthesisError> {
         Ok(subquery_result)
     }
 
+    /// Makes the subquery `(symbol . args)` when `is_recursive`, returning its post-processed result along with the
+    /// updated accumulator and transcript. Otherwise returns `immediate` unchanged.
     fn recurse<CS: ConstraintSystem<F>>(
         &self,
         cs: &mut CS,
         g: &GlobalAllocator<F>,
         store: &Store<F>,
         scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        ctx: &Self::Ctx,
         args: &AllocatedPtr<F>,
         is_recursive: &Boolean,
         immediate: (&AllocatedPtr<F>, &AllocatedPtr<F>, &CircuitTranscript<F>),
@@ -105,7 +145,7 @@ pub(crate) trait RecursiveQuery<F: LurkField>: CircuitQuery<F> {
         )?;
 
         let (recursive_result, recursive_acc, recursive_transcript) = (
-            self.post_recursion(cs, sub_result)?,
+            self.post_recursion(cs, ctx, sub_result)?,
             new_acc,
             new_transcript,
         );
@@ -133,4 +173,578 @@ pub(crate) trait RecursiveQuery<F: LurkField>: CircuitQuery<F> {
 
         Ok((value, acc, transcript))
     }
+
+    /// Like `recurse`, for subqueries that return as many values as `immediate` has. The values are inserted as
+    /// separate memoset entries and returned separately, and are the immediate ones when `is_recursive` is false.
+    fn recurse_values<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        store: &Store<F>,
+        scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        args: &AllocatedPtr<F>,
+        is_recursive: &Boolean,
+        immediate: (&[AllocatedPtr<F>], &AllocatedPtr<F>, &CircuitTranscript<F>),
+    ) -> Result<(Vec<AllocatedPtr<F>>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        let is_immediate = is_recursive.not();
+
+        let subquery = {
+            let symbol = g.alloc_ptr(
+                &mut cs.namespace(|| "symbol"),
+                &self.symbol_ptr(store),
+                store,
+            );
+            construct_cons(&mut cs.namespace(|| "subquery"), g, store, &symbol, args)?
+        };
+
+        let (sub_values, recursive_acc, recursive_transcript) = scope
+            .synthesize_internal_query_values(
+                &mut cs.namespace(|| "recursive query"),
+                g,
+                store,
+                &subquery,
+                immediate.1,
+                immediate.2,
+                is_recursive,
+                immediate.0.len(),
+            )?;
+
+        let values = immediate
+            .0
+            .iter()
+            .zip(&sub_values)
+            .enumerate()
+            .map(|(i, (immediate, recursive))| {
+                AllocatedPtr::pick(
+                    &mut cs.namespace(|| format!("pick value {i}")),
+                    &is_immediate,
+                    immediate,
+                    recursive,
+                )
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let acc = AllocatedPtr::pick(
+            &mut cs.namespace(|| "pick acc"),
+            &is_immediate,
+            immediate.1,
+            &recursive_acc,
+        )?;
+
+        let transcript = CircuitTranscript::pick(
+            &mut cs.namespace(|| "pick recursive_transcript"),
+            &is_immediate,
+            immediate.2,
+            &recursive_transcript,
+        )?;
+
+        Ok((values, acc, transcript))
+    }
+}
+
+/// Queries that return several values, such as a result along with a hint for the caller, encode them as
+/// `(v1 v2 ... . vn)`: a chain of `n - 1` conses without a terminating `nil`, so a single value is itself and a pair is
+/// one cons. The encoding is only what the scope records and top-level callers see. Circuits never build it: the
+/// values enter the memoset as the separate entries of `entries`, which subqueries receive with
+/// `RecursiveQuery::recurse_values`.
+///
+/// # Panics
+/// Panics if `values` is empty
+pub(crate) fn values<F: LurkField>(s: &Store<F>, values: &[Ptr]) -> Ptr {
+    let (last, init) = values.split_last().expect("at least one value");
+    init.iter()
+        .rev()
+        .fold(*last, |acc, value| s.cons(*value, acc))
+}
+
+/// Decodes `arity` values encoded with `values`, returning `None` if `result` doesn't encode as many
+pub(crate) fn fetch_values<F: LurkField>(
+    s: &Store<F>,
+    result: &Ptr,
+    arity: usize,
+) -> Option<Vec<Ptr>> {
+    assert!(arity > 0, "at least one value");
+    let mut values = Vec::with_capacity(arity);
+    let mut rest = *result;
+    for _ in 1..arity {
+        if !rest.has_tag(&Tag::Expr(ExprTag::Cons)) {
+            return None;
+        }
+        let (value, more) = s.try_car_cdr(&rest).ok()?;
+        values.push(value);
+        rest = more;
+    }
+    values.push(rest);
+    Some(values)
+}
+
+/// In-circuit counterpart of `values`
+pub(crate) fn synthesize_values<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    values: &[AllocatedPtr<F>],
+) -> Result<AllocatedPtr<F>, SynthesisError> {
+    let (last, init) = values.split_last().expect("at least one value");
+    init.iter()
+        .enumerate()
+        .rev()
+        .try_fold(last.clone(), |acc, (i, value)| {
+            construct_cons(
+                &mut cs.namespace(|| format!("value {i}")),
+                g,
+                s,
+                value,
+                &acc,
+            )
+        })
+}
+
+/// The memoset entries of the query `key` with `values`: `(key . v1)`, then each further value consed onto the entry
+/// before it. A query with a single value has the usual entry, `(key . value)`. Each entry carries one value, and the
+/// last one binds the key to all of them, so it's what the transcript records.
+///
+/// # Panics
+/// Panics if `values` is empty
+pub(crate) fn entries<F: LurkField>(s: &Store<F>, key: Ptr, values: &[Ptr]) -> Vec<Ptr> {
+    let (first, rest) = values.split_first().expect("at least one value");
+    let mut entries = vec![Transcript::make_kv(s, key, *first)];
+    for value in rest {
+        let last = *entries.last().expect("at least one entry");
+        entries.push(s.cons(last, *value));
+    }
+    entries
+}
+
+/// The memoset entries of the query `key` whose `arity` values are encoded as `value`, or `None` if it doesn't encode
+/// as many
+pub(crate) fn value_entries<F: LurkField>(
+    s: &Store<F>,
+    key: Ptr,
+    value: Ptr,
+    arity: usize,
+) -> Option<Vec<Ptr>> {
+    Some(entries(s, key, &fetch_values(s, &value, arity)?))
+}
+
+/// In-circuit counterpart of `entries`
+pub(crate) fn synthesize_entries<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    key: &AllocatedPtr<F>,
+    values: &[AllocatedPtr<F>],
+) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+    let (first, rest) = values.split_first().expect("at least one value");
+    let mut entries = vec![CircuitTranscript::make_kv(
+        &mut cs.namespace(|| "kv"),
+        g,
+        s,
+        key,
+        first,
+    )?];
+    for (i, value) in rest.iter().enumerate() {
+        let last = entries.last().expect("at least one entry");
+        let entry = construct_cons(
+            &mut cs.namespace(|| format!("entry {}", i + 1)),
+            g,
+            s,
+            last,
+            value,
+        )?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Interns a compound query key: `(symbol arg1 arg2 ...)`. Note that this is `(symbol . args)` where `args` is a proper
+/// list, so a `RecursiveQuery` can `recurse` on a compound key by passing the arguments built with `construct_list`.
+pub fn compound_key<F: LurkField>(s: &Store<F>, symbol: &Symbol, args: &[Ptr]) -> Ptr {
+    let head = s.intern_symbol(symbol);
+    s.cons(head, s.list(args.to_vec()))
+}
+
+/// Parses a compound query key `(symbol arg1 ... argN)`. Returns the arguments only if the key is a proper list headed
+/// by `symbol` with exactly `arity` arguments.
+pub fn compound_key_args<F: LurkField>(
+    s: &Store<F>,
+    key: &Ptr,
+    symbol: &Symbol,
+    arity: usize,
+) -> Option<Vec<Ptr>> {
+    let Ok((mut elts, None)) = s.try_fetch_list(key) else {
+        return None;
+    };
+    if elts.len() != arity + 1 {
+        return None;
+    }
+    let args = elts.split_off(1);
+    (s.try_fetch_sym(&elts[0]).ok()? == *symbol).then_some(args)
+}
+
+/// In-circuit counterpart of `compound_key_args`. Allocates the elements of `key` and enforces, when `not_dummy` is
+/// true, that they rebuild `key` as a `nil`-terminated list of `Cons`es whose head is `symbol`, followed by exactly
+/// `arity` arguments. Returns the allocated arguments.
+pub fn synthesize_compound_key_args<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    not_dummy: &Boolean,
+    key: &AllocatedPtr<F>,
+    symbol: &Symbol,
+    arity: usize,
+) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+    let values = if not_dummy.get_value() == Some(true) {
+        key.get_value::<Tag>()
+            .and_then(|z| s.try_fetch_list(&s.to_ptr(&z)).ok())
+            .map(|(elts, _)| elts.iter().map(|elt| s.hash_ptr(elt)).collect::<Vec<_>>())
+    } else {
+        None
+    };
+
+    let mut elts = (0..=arity)
+        .map(|i| {
+            AllocatedPtr::alloc_infallible(&mut cs.namespace(|| format!("elt {i}")), || {
+                values
+                    .as_ref()
+                    .and_then(|values| values.get(i))
+                    .copied()
+                    .unwrap_or_else(ZPtr::dummy)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let rebuilt = construct_list(
+        &mut cs.namespace(|| "rebuilt key"),
+        g,
+        s,
+        &elts.iter().collect::<Vec<_>>(),
+        None,
+    )?;
+    rebuilt.implies_ptr_equal(&mut cs.namespace(|| "key matches"), not_dummy, key);
+
+    let head = g.alloc_ptr(cs, &s.intern_symbol(symbol), s);
+    elts[0].implies_ptr_equal(&mut cs.namespace(|| "head matches"), not_dummy, &head);
+
+    Ok(elts.split_off(1))
+}
+
+/// Queries whose provable result may be that no value exists (for example, a key absent from a committed map) encode
+/// that result canonically, so negative facts are attested to just as computed values are: `none` is `(nil . nil)`,
+/// and `some(value)` is `(value . t)`. The `cdr` tells them apart, so `some(nil)` is distinct from `none`. An option is
+/// thus the `values` encoding of two values, the value and whether it's bound, which circuits handle separately.
+pub(crate) fn none<F: LurkField>(s: &Store<F>) -> Ptr {
+    let nil = s.intern_nil();
+    s.cons(nil, nil)
+}
+
+/// The canonical encoding of a present `value`. See `none`.
+pub(crate) fn some<F: LurkField>(s: &Store<F>, value: Ptr) -> Ptr {
+    s.cons(value, s.intern_t())
+}
+
+/// Decodes a result encoded with `none` or `some`, returning `None` if `result` is neither.
+#[cfg(test)]
+pub(crate) fn fetch_option<F: LurkField>(s: &Store<F>, result: &Ptr) -> Option<Option<Ptr>> {
+    if !result.has_tag(&Tag::Expr(ExprTag::Cons)) {
+        return None;
+    }
+    let (value, bound) = s.try_car_cdr(result).ok()?;
+    let nil = s.intern_nil();
+    if s.ptr_eq(&bound, &s.intern_t()) {
+        Some(Some(value))
+    } else if s.ptr_eq(&bound, &nil) && s.ptr_eq(&value, &nil) {
+        Some(None)
+    } else {
+        None
+    }
+}
+
+/// In-circuit counterpart of `none` and `some`: allocates the two values of `some(value)` if `is_some` is true, and of
+/// `none` otherwise.
+pub(crate) fn synthesize_option<F: LurkField, CS: ConstraintSystem<F>>(
+    cs: &mut CS,
+    g: &GlobalAllocator<F>,
+    s: &Store<F>,
+    is_some: &Boolean,
+    value: &AllocatedPtr<F>,
+) -> Result<[AllocatedPtr<F>; 2], SynthesisError> {
+    let nil = g.alloc_ptr(cs, &s.intern_nil(), s);
+    let t = g.alloc_ptr(cs, &s.intern_t(), s);
+
+    let value = AllocatedPtr::pick(&mut cs.namespace(|| "value"), is_some, value, &nil)?;
+    let bound = AllocatedPtr::pick(&mut cs.namespace(|| "bound"), is_some, &t, &nil)?;
+
+    Ok([value, bound])
+}
+
+/// The symbol of version `version` of the query named `name`: `name` followed by a `v<version>` segment, as in
+/// `lurk.user.factorial.v2`. Queries start at version 0, whose symbol is `name` itself, and whose semantics change
+/// should bump their version, so the keys, and hence the memo entries, of different versions never collide.
+pub fn versioned_symbol(name: &Symbol, version: u32) -> Symbol {
+    if version == 0 {
+        return name.clone();
+    }
+    name.direct_child(&format!("v{version}"))
+}
+
+/// The version of a `v<version>` segment. Versions are written without leading zeros, and version 0 has no segment.
+fn parse_version(segment: &str) -> Option<u32> {
+    let digits = segment.strip_prefix('v')?;
+    let version = digits.parse::<u32>().ok()?;
+    (version != 0 && digits == version.to_string()).then_some(version)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum QueryVersionError {
+    #[error("Query {name} has version {found}, but version {expected} is expected")]
+    Mismatch {
+        name: String,
+        expected: u32,
+        found: u32,
+    },
+    #[error("Query {name} has no version, but version {expected} is expected")]
+    Unversioned { name: String, expected: u32 },
+}
+
+/// Checks that `symbol` is the symbol of version `version` of the query named `name`. Returns `Ok(false)` if `symbol`
+/// is unrelated to `name`, so that `Query::from_ptr` can go on to try other queries, and an error if it's `name` with
+/// another version or none at all.
+pub fn check_query_version(
+    symbol: &Symbol,
+    name: &Symbol,
+    version: u32,
+) -> Result<bool, QueryVersionError> {
+    if symbol == name {
+        if version == 0 {
+            return Ok(true);
+        }
+        return Err(QueryVersionError::Unversioned {
+            name: name.fmt_to_string(),
+            expected: version,
+        });
+    }
+    if symbol.direct_parent().as_ref() != Some(name) {
+        return Ok(false);
+    }
+    let found = symbol.name().ok().and_then(parse_version);
+    match found {
+        Some(found) if found == version => Ok(true),
+        Some(found) => Err(QueryVersionError::Mismatch {
+            name: name.fmt_to_string(),
+            expected: version,
+            found,
+        }),
+        // `name` has a child that isn't a version, which is another query.
+        None => Ok(false),
+    }
+}
+
+/// For `Query::from_ptr`: the body of the key `(symbol . body)` if `symbol` is the symbol of version `version` of the
+/// query named `name`. Keys of other versions of the query are rejected with a warning rather than silently mixed with
+/// those of `version`.
+pub fn versioned_query_body<F: LurkField>(
+    s: &Store<F>,
+    ptr: &Ptr,
+    name: &Symbol,
+    version: u32,
+) -> Option<Ptr> {
+    let (head, body) = s.try_car_cdr(ptr).ok()?;
+    let symbol = s.try_fetch_sym(&head).ok()?;
+    match check_query_version(&symbol, name, version) {
+        Ok(true) => Some(body),
+        Ok(false) => None,
+        Err(e) => {
+            warn!("rejecting query key: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr as F;
+
+    #[test]
+    fn test_compound_key() {
+        let s = &Store::<F>::default();
+        let add = Symbol::sym(&["lurk", "user", "add"]);
+        let args = [s.num_u64(1), s.num_u64(2), s.num_u64(3)];
+
+        let key = compound_key(s, &add, &args);
+        let head = s.intern_symbol(&add);
+        assert_eq!(s.list(vec![head, args[0], args[1], args[2]]), key);
+        assert_eq!(Some(args.to_vec()), compound_key_args(s, &key, &add, 3));
+        assert_eq!(None, compound_key_args(s, &key, &add, 2));
+        assert_eq!(
+            None,
+            compound_key_args(s, &key, &Symbol::sym(&["lurk", "user", "sub"]), 3)
+        );
+
+        let improper = s.cons(head, s.num_u64(1));
+        assert_eq!(None, compound_key_args(s, &improper, &add, 1));
+    }
+
+    #[test]
+    fn test_synthesize_compound_key_args() {
+        let s = &Store::<F>::default();
+        let g = &GlobalAllocator::default();
+        let add = Symbol::sym(&["lurk", "user", "add"]);
+        let args = [s.num_u64(1), s.num_u64(2)];
+        let key = compound_key(s, &add, &args);
+
+        let synthesize = |symbol: &Symbol, arity| {
+            let cs = &mut TestConstraintSystem::<F>::new();
+            let allocated_key =
+                AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "key"), || s.hash_ptr(&key));
+            let allocated_args = synthesize_compound_key_args(
+                cs,
+                g,
+                s,
+                &Boolean::Constant(true),
+                &allocated_key,
+                symbol,
+                arity,
+            )
+            .unwrap();
+            let arg_values = allocated_args
+                .iter()
+                .map(|arg| s.to_ptr(&arg.get_value().unwrap()))
+                .collect::<Vec<_>>();
+            (cs.is_satisfied(), arg_values)
+        };
+
+        let (satisfied, arg_values) = synthesize(&add, 2);
+        assert!(satisfied);
+        assert_eq!(args.to_vec(), arg_values);
+
+        assert!(!synthesize(&add, 1).0);
+        assert!(!synthesize(&Symbol::sym(&["lurk", "user", "sub"]), 2).0);
+    }
+
+    #[test]
+    fn test_query_versions() {
+        let s = &Store::<F>::default();
+        let name = Symbol::sym(&["lurk", "user", "factorial"]);
+        let v2 = versioned_symbol(&name, 2);
+        assert_eq!(Symbol::sym(&["lurk", "user", "factorial", "v2"]), v2);
+
+        assert_eq!(Ok(true), check_query_version(&v2, &name, 2));
+        assert_eq!(
+            Err(QueryVersionError::Mismatch {
+                name: name.fmt_to_string(),
+                expected: 3,
+                found: 2
+            }),
+            check_query_version(&v2, &name, 3)
+        );
+        assert!(matches!(
+            check_query_version(&name, &name, 2),
+            Err(QueryVersionError::Unversioned { .. })
+        ));
+        let other = Symbol::sym(&["lurk", "user", "sha256"]);
+        assert_eq!(Ok(false), check_query_version(&other, &name, 2));
+        let child = name.direct_child("memo");
+        assert_eq!(Ok(false), check_query_version(&child, &name, 2));
+        // only canonical segments are versions
+        for segment in ["v02", "v0", "v", "v-1"] {
+            let child = name.direct_child(segment);
+            assert_eq!(Ok(false), check_query_version(&child, &name, 2));
+        }
+
+        // version 0 is the bare name
+        assert_eq!(name, versioned_symbol(&name, 0));
+        assert_eq!(Ok(true), check_query_version(&name, &name, 0));
+        assert!(matches!(
+            check_query_version(&v2, &name, 0),
+            Err(QueryVersionError::Mismatch { found: 2, .. })
+        ));
+
+        let n = s.num_u64(5);
+        let key = |symbol: &Symbol| s.cons(s.intern_symbol(symbol), n);
+        assert_eq!(Some(n), versioned_query_body(s, &key(&v2), &name, 2));
+        assert_eq!(None, versioned_query_body(s, &key(&v2), &name, 1));
+        assert_eq!(None, versioned_query_body(s, &key(&name), &name, 2));
+        assert_eq!(None, versioned_query_body(s, &n, &name, 2));
+    }
+
+    #[test]
+    fn test_values_encoding() {
+        let s = &Store::<F>::default();
+        let g = &GlobalAllocator::default();
+        let [a, b, c] = [s.num_u64(1), s.intern_nil(), s.num_u64(3)];
+        let key = s.read_with_default_state("(key . 1)").unwrap();
+
+        assert_eq!(a, values(s, &[a]));
+        assert_eq!(s.cons(a, b), values(s, &[a, b]));
+        assert_eq!(s.cons(a, s.cons(b, c)), values(s, &[a, b, c]));
+        for vals in [vec![a], vec![a, b], vec![a, b, c], vec![c, b, a]] {
+            let encoded = values(s, &vals);
+            assert_eq!(Some(vals.clone()), fetch_values(s, &encoded, vals.len()));
+
+            let cs = &mut TestConstraintSystem::<F>::new();
+            let allocated = vals
+                .iter()
+                .enumerate()
+                .map(|(i, val)| {
+                    AllocatedPtr::alloc_infallible(&mut cs.namespace(|| format!("{i}")), || {
+                        s.hash_ptr(val)
+                    })
+                })
+                .collect::<Vec<_>>();
+            let synthesized = synthesize_values(cs, g, s, &allocated).unwrap();
+            assert_eq!(Some(s.hash_ptr(&encoded)), synthesized.get_value());
+
+            // each value is its own entry, and the last one binds them all to the key
+            let entries = value_entries(s, key, encoded, vals.len()).unwrap();
+            assert_eq!(vals.len(), entries.len());
+            assert_eq!(s.cons(key, vals[0]), entries[0]);
+            let allocated_key =
+                AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "key"), || s.hash_ptr(&key));
+            let synthesized = synthesize_entries(cs, g, s, &allocated_key, &allocated).unwrap();
+            let synthesized = synthesized
+                .iter()
+                .map(|entry| entry.get_value().map(|z| s.to_ptr(&z)))
+                .collect::<Option<Vec<_>>>();
+            assert_eq!(Some(entries), synthesized);
+            assert!(cs.is_satisfied());
+        }
+        // a pair is also a single value, but not three values
+        assert_eq!(
+            Some(vec![s.cons(a, b)]),
+            fetch_values(s, &values(s, &[a, b]), 1)
+        );
+        assert_eq!(None, fetch_values(s, &values(s, &[a, b]), 3));
+    }
+
+    #[test]
+    fn test_option_encoding() {
+        let s = &Store::<F>::default();
+        let nil = s.intern_nil();
+        let one = s.num_u64(1);
+
+        assert_eq!(Some(None), fetch_option(s, &none(s)));
+        assert_eq!(Some(Some(one)), fetch_option(s, &some(s, one)));
+        assert_eq!(Some(Some(nil)), fetch_option(s, &some(s, nil)));
+        assert_eq!(None, fetch_option(s, &nil));
+        assert_eq!(None, fetch_option(s, &s.cons(one, nil)));
+
+        let g = &GlobalAllocator::default();
+        for (is_some, expected) in [(true, some(s, one)), (false, none(s))] {
+            let cs = &mut TestConstraintSystem::<F>::new();
+            let value =
+                AllocatedPtr::alloc_infallible(&mut cs.namespace(|| "value"), || s.hash_ptr(&one));
+            let option = synthesize_option(cs, g, s, &Boolean::Constant(is_some), &value).unwrap();
+            let option = option
+                .iter()
+                .map(|value| value.get_value().map(|z| s.to_ptr(&z)))
+                .collect::<Option<Vec<_>>>()
+                .unwrap();
+            assert_eq!(expected, values(s, &option));
+            assert!(cs.is_satisfied());
+        }
+    }
 }
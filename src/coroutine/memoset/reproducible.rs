@@ -0,0 +1,226 @@
+//! Reproducible proofs of `Scope`s.
+//!
+//! Everything a `Scope` proof depends on is fixed by its queries: chunks follow the query index and then the order in
+//! which keys were first inserted, the last chunk of each query index is padded with dummy queries rather than
+//! arbitrary ones, and constants are allocated in synthesis order. Two machines proving the same queries with the same
+//! backend and parameters hence produce the same proof, byte for byte.
+//!
+//! A `ProofManifest` records those inputs alongside the proof. Its `seed` is a digest of the whole manifest, so that
+//! matching seeds are enough to tell two proofs were made from the same computation, and `Scope::check_manifest` lets
+//! an auditor find out which input differs when they don't.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{io::CoroutineIO, query::Query, FoldingBackend, LogMemo, Scope, TranscriptScheme};
+use crate::field::LurkField;
+use crate::lem::store::Store;
+
+/// Bump whenever the content of manifests changes
+const MANIFEST_VERSION: u32 = 3;
+
+/// One chunk of a manifest
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkEntry {
+    pub query_index: usize,
+    pub chunk_index: usize,
+    /// The hash of the list of keys proved by the chunk
+    pub keys: String,
+}
+
+/// The inputs a `Scope` proof was made from. Field elements are hex-encoded.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofManifest {
+    pub version: u32,
+    /// The `FoldingBackend::ID` of the backend
+    pub backend: String,
+    /// The key of the public parameters, as in `FoldingParamsCache`
+    pub params_key: String,
+    pub rc: usize,
+    pub transcribe_internal_insertions: bool,
+    pub dedup_toplevel_insertions: bool,
+    pub transcribe_toplevel_insertions: bool,
+    pub transcript_scheme: TranscriptScheme,
+    /// The `Scope::toplevel_commitment` of the proved scope
+    pub toplevel_commitment: String,
+    /// The challenge derived from the transcript
+    pub r: String,
+    /// The chunks, in folding order
+    pub chunks: Vec<ChunkEntry>,
+    pub z0: Vec<String>,
+    pub zi: Vec<String>,
+    /// A digest of every other field
+    pub seed: String,
+}
+
+impl ProofManifest {
+    fn compute_seed(&self) -> String {
+        let unseeded = Self {
+            seed: String::new(),
+            ..self.clone()
+        };
+        let bytes = bincode::serialize(&unseeded).expect("manifests are serializable");
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    /// Whether `seed` is the digest of the rest of the manifest
+    pub fn is_consistent(&self) -> bool {
+        self.seed == self.compute_seed()
+    }
+}
+
+impl<F: LurkField, Q: Query<F> + Send + Sync> Scope<Q, LogMemo<F>> {
+    /// A commitment to the top-level queries of this scope, with their responses
+    pub fn toplevel_commitment(&self, s: &Store<F>) -> F {
+        let kvs = self
+            .toplevel_insertions
+            .iter()
+            .flat_map(|kv| std::iter::repeat(*kv).take(self.toplevel_multiplicity(kv)))
+            .collect();
+        *s.hash_ptr(&s.list(kvs)).value()
+    }
+
+    /// The manifest of a proof of this scope with `B`, finalizing the transcript if necessary
+    pub fn manifest<B: FoldingBackend<F>>(&mut self, s: &Store<F>) -> Result<ProofManifest> {
+        self.ensure_transcript_finalized(s);
+        let r = *self.memoset.r().expect("transcript not finalized");
+        let (specs, final_acc) = self.replay(s);
+        let Some(first) = specs.first() else {
+            bail!("No queries to prove");
+        };
+        let transcript = self
+            .memoset
+            .transcript
+            .get()
+            .expect("transcript not finalized");
+        let z0 =
+            CoroutineIO::chunk(s, first.acc, s.hash_ptr(&first.transcript), r).to_field_elements();
+        let zi =
+            CoroutineIO::chunk(s, final_acc, s.hash_ptr(&transcript.acc), r).to_field_elements();
+        let hex = |fs: Vec<F>| fs.into_iter().map(|f| f.hex_digits()).collect();
+
+        let chunks = specs
+            .iter()
+            .map(|spec| ChunkEntry {
+                query_index: spec.query_index,
+                chunk_index: spec.chunk_index,
+                keys: s.hash_ptr(&s.list(spec.keys.clone())).value().hex_digits(),
+            })
+            .collect();
+        let mut manifest = ProofManifest {
+            version: MANIFEST_VERSION,
+            backend: B::ID.to_owned(),
+            params_key: self.folding_params_key::<B>(s)?,
+            rc: self.default_rc,
+            transcribe_internal_insertions: self.transcribe_internal_insertions,
+            dedup_toplevel_insertions: self.dedup_toplevel_insertions,
+            transcribe_toplevel_insertions: self.transcribe_toplevel_insertions,
+            transcript_scheme: self.transcript_scheme,
+            toplevel_commitment: self.toplevel_commitment(s).hex_digits(),
+            r: r.hex_digits(),
+            chunks,
+            z0: hex(z0),
+            zi: hex(zi),
+            seed: String::new(),
+        };
+        manifest.seed = manifest.compute_seed();
+        Ok(manifest)
+    }
+
+    /// Like `prove_with`, but also returns the manifest of the proof
+    pub fn prove_reproducibly<B: FoldingBackend<F>>(
+        &mut self,
+        s: &Store<F>,
+        backend: &B,
+        pp: &B::PublicParams,
+    ) -> Result<(B::Proof, ProofManifest)> {
+        let manifest = self.manifest::<B>(s)?;
+        let (proof, ..) = self.prove_with(s, backend, pp)?;
+        Ok((proof, manifest))
+    }
+
+    /// Checks that a proof of this scope with `B` would have `manifest`, reporting the first input that differs
+    /// otherwise
+    pub fn check_manifest<B: FoldingBackend<F>>(
+        &mut self,
+        s: &Store<F>,
+        manifest: &ProofManifest,
+    ) -> Result<()> {
+        if !manifest.is_consistent() {
+            bail!("The manifest's seed doesn't match its content");
+        }
+        let expected = self.manifest::<B>(s)?;
+        macro_rules! check {
+            ($($field:ident),*) => {
+                $(if expected.$field != manifest.$field {
+                    bail!("Manifest mismatch in `{}`", stringify!($field));
+                })*
+            };
+        }
+        check!(
+            version,
+            backend,
+            params_key,
+            rc,
+            transcribe_internal_insertions,
+            dedup_toplevel_insertions,
+            transcribe_toplevel_insertions,
+            transcript_scheme,
+            toplevel_commitment,
+            r,
+            chunks,
+            z0,
+            zi
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::{demo::DemoQuery, MockBackend};
+
+    fn scope(s: &Store<F>, ns: &[u64]) -> Scope<DemoQuery<F>, LogMemo<F>> {
+        let mut scope = Scope::new(false, 2);
+        for n in ns {
+            let query = s
+                .read_with_default_state(&format!("(factorial . {n})"))
+                .unwrap();
+            scope.query(s, query);
+        }
+        scope
+    }
+
+    #[test]
+    fn test_reproducible_proofs() {
+        // Separate stores, as on separate machines
+        let (s1, s2) = (&Store::<F>::default(), &Store::<F>::default());
+        let (mut scope1, mut scope2) = (scope(s1, &[3, 5, 4]), scope(s2, &[3, 5, 4]));
+
+        let (proof1, manifest1) = scope1.prove_reproducibly(s1, &MockBackend, &()).unwrap();
+        let (proof2, manifest2) = scope2.prove_reproducibly(s2, &MockBackend, &()).unwrap();
+        assert_eq!(proof1, proof2);
+        assert_eq!(manifest1, manifest2);
+        assert!(manifest1.is_consistent());
+        // factorial 0 through 5, in chunks of 2
+        assert_eq!(3, manifest1.chunks.len());
+        scope2
+            .check_manifest::<MockBackend>(s2, &manifest1)
+            .unwrap();
+
+        let mut tampered = manifest1.clone();
+        tampered.rc = 3;
+        assert!(!tampered.is_consistent());
+        assert!(scope1.check_manifest::<MockBackend>(s1, &tampered).is_err());
+
+        let mut other = scope(s1, &[3, 5, 6]);
+        let err = other
+            .check_manifest::<MockBackend>(s1, &manifest1)
+            .unwrap_err();
+        assert!(err.to_string().contains("toplevel_commitment"));
+    }
+}
@@ -0,0 +1,191 @@
+//! How items are hashed into the transcript.
+//!
+//! By default the transcript is a Lurk list, each item consed onto it with the 4-ary Poseidon hash of conses. A
+//! removal `((key . value) . count)` thus costs two such hashes on top of the one making `(key . value)`, which the
+//! memoset needs anyway. `TranscriptScheme::Packed` hashes the key-value pair, the count and the transcript so far with
+//! a single 6-ary Poseidon instead, roughly halving the cost of transcribing a removal. Packed transcripts are numbers
+//! rather than lists, so they can't be read back as Lurk data.
+
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem, SynthesisError};
+use serde::{Deserialize, Serialize};
+
+use super::{CircuitTranscript, Transcript};
+use crate::circuit::gadgets::data::hash_poseidon;
+use crate::circuit::gadgets::pointer::AllocatedPtr;
+use crate::field::LurkField;
+use crate::lem::circuit::GlobalAllocator;
+use crate::lem::{pointers::Ptr, store::Store};
+use crate::tag::ExprTag;
+
+/// How items are hashed into the transcript
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TranscriptScheme {
+    /// Items are consed onto the transcript, a Lurk list
+    #[default]
+    Cons,
+    /// Each item is hashed with the transcript as `[counted, kv tag, kv hash, count, transcript tag, transcript hash]`,
+    /// where `counted` is 1 for items with a count and 0, with a count of 0, for plain insertions
+    Packed,
+}
+
+impl<F: LurkField> Transcript<F> {
+    /// Adds the insertion of `kv`, or the `count` copies of it of a removal or a deduplicated top-level insertion
+    pub(super) fn add_kv(
+        &mut self,
+        s: &Store<F>,
+        scheme: TranscriptScheme,
+        kv: Ptr,
+        count: Option<usize>,
+    ) {
+        match scheme {
+            TranscriptScheme::Cons => {
+                let item = match count {
+                    Some(count) => Self::make_kv_count(s, kv, count),
+                    None => kv,
+                };
+                self.add(s, item);
+            }
+            TranscriptScheme::Packed => {
+                let kv = s.hash_ptr(&kv);
+                let acc = s.hash_ptr(&self.acc);
+                let (counted, count) = match count {
+                    Some(count) => (F::ONE, F::from_u64(count as u64)),
+                    None => (F::ZERO, F::ZERO),
+                };
+                let hash = s.poseidon_cache.hash6(&[
+                    counted,
+                    kv.tag_field(),
+                    *kv.value(),
+                    count,
+                    acc.tag_field(),
+                    *acc.value(),
+                ]);
+                self.acc = s.num(hash);
+            }
+        }
+    }
+}
+
+impl<F: LurkField> CircuitTranscript<F> {
+    /// Adds the insertion of `kv` in the namespace `name`
+    pub(super) fn add_kv<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        scheme: TranscriptScheme,
+        kv: &AllocatedPtr<F>,
+        name: &'static str,
+    ) -> Result<Self, SynthesisError> {
+        match scheme {
+            TranscriptScheme::Cons => self.add(&mut cs.namespace(|| name), g, s, kv),
+            TranscriptScheme::Packed => self.add_packed(&mut cs.namespace(|| name), g, s, kv, None),
+        }
+    }
+
+    /// Adds `count` copies of `kv` in the namespace `name`. Returns the allocated count along with the transcript.
+    pub(super) fn add_kv_count<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        scheme: TranscriptScheme,
+        kv: &AllocatedPtr<F>,
+        count: u64,
+        name: &'static str,
+    ) -> Result<(Self, AllocatedNum<F>), SynthesisError> {
+        match scheme {
+            TranscriptScheme::Cons => {
+                let (kv_count, count) =
+                    Self::make_kv_count(&mut cs.namespace(|| "kv_count"), g, s, kv, count)?;
+                let transcript = self.add(&mut cs.namespace(|| name), g, s, &kv_count)?;
+                Ok((transcript, count))
+            }
+            TranscriptScheme::Packed => {
+                let count =
+                    AllocatedNum::alloc(&mut cs.namespace(|| "count"), || Ok(F::from_u64(count)))?;
+                let transcript =
+                    self.add_packed(&mut cs.namespace(|| name), g, s, kv, Some(&count))?;
+                Ok((transcript, count))
+            }
+        }
+    }
+
+    fn add_packed<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        kv: &AllocatedPtr<F>,
+        count: Option<&AllocatedNum<F>>,
+    ) -> Result<Self, SynthesisError> {
+        let (counted, count) = match count {
+            Some(count) => (g.alloc_const_cloned(cs, F::ONE), count.clone()),
+            None => {
+                let zero = g.alloc_const_cloned(cs, F::ZERO);
+                (zero.clone(), zero)
+            }
+        };
+        let hash = hash_poseidon(
+            &mut cs.namespace(|| "packed"),
+            vec![
+                counted,
+                kv.tag().clone(),
+                kv.hash().clone(),
+                count,
+                self.acc.tag().clone(),
+                self.acc.hash().clone(),
+            ],
+            s.poseidon_cache.constants.c6(),
+        )?;
+        let tag = g.alloc_tag_cloned(cs, &ExprTag::Num);
+        Ok(Self {
+            acc: AllocatedPtr::from_parts(tag, hash),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::{
+        demo::DemoQuery, FoldingBackend, LogMemo, MemoSet, MockBackend, Scope,
+    };
+
+    #[test]
+    fn test_packed_transcript() {
+        let s = &Store::<F>::default();
+        let scope = |scheme| {
+            let mut scope: Scope<DemoQuery<F>, LogMemo<F>> =
+                Scope::new(false, 2).with_transcript_scheme(scheme);
+            scope.query(s, s.read_with_default_state("(factorial . 4)").unwrap());
+            scope.query(s, s.read_with_default_state("(factorial . 2)").unwrap());
+            scope
+        };
+        let constraints = |scheme| {
+            let cs = &mut TestConstraintSystem::new();
+            let g = &mut GlobalAllocator::default();
+            let mut scope = scope(scheme);
+            scope.synthesize(cs, g, s).unwrap();
+            assert!(cs.is_satisfied());
+            (cs.num_constraints(), *scope.memoset.r().unwrap())
+        };
+
+        let (cons_constraints, cons_r) = constraints(TranscriptScheme::Cons);
+        let (packed_constraints, packed_r) = constraints(TranscriptScheme::Packed);
+        assert_ne!(cons_r, packed_r);
+        assert!(packed_constraints < cons_constraints);
+
+        let mut packed = scope(TranscriptScheme::Packed);
+        let (proof, z0, zi) = packed.prove_with(s, &MockBackend, &()).unwrap();
+        let inputs = packed.public_inputs(s);
+        assert!(MockBackend
+            .verify_scope(&(), &proof, s, &inputs, &z0, &zi)
+            .unwrap());
+        let audit = packed.transcript_audit(s);
+        assert_eq!(packed_r, audit.rederive(s).unwrap());
+    }
+}
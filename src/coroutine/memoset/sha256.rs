@@ -0,0 +1,159 @@
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
+use sha2::{Digest, Sha256};
+use std::marker::PhantomData;
+
+use super::{
+    query::{versioned_query_body, versioned_symbol, CircuitQuery, Query},
+    CircuitScope, CircuitTranscript, LogMemo, LogMemoCircuit, Scope,
+};
+use crate::circuit::gadgets::data::fetch_bytes_or_string;
+use crate::circuit::gadgets::pointer::AllocatedPtr;
+use crate::coprocessor::sha256::synthesize_sha256_bytes;
+use crate::field::LurkField;
+use crate::lem::circuit::GlobalAllocator;
+use crate::lem::{pointers::Ptr, store::Store};
+use crate::symbol::Symbol;
+
+/// The version of `Sha256Query`, which is bumped when its semantics change
+const VERSION: u32 = 0;
+
+fn sha256_name() -> Symbol {
+    Symbol::sym(&["lurk", "user", "sha256"])
+}
+
+fn sha256_symbol() -> Symbol {
+    versioned_symbol(&sha256_name(), VERSION)
+}
+
+/// `(sha256 . input)`, where `input` is a `Bytes` of `N` bytes or a string of `N` characters below 256. Its response
+/// is the SHA-256 digest of `input`, as a `Bytes` of 32 bytes, as with `Sha256BytesCoprocessor`.
+#[derive(Debug, Clone)]
+pub struct Sha256Query<F, const N: usize> {
+    input: Ptr,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField, const N: usize> Sha256Query<F, N> {
+    /// The query of `input`, if it has `N` bytes
+    pub fn new(s: &Store<F>, input: Ptr) -> Option<Self> {
+        (fetch_bytes_or_string(s, &input)?.len() == N).then_some(Self {
+            input,
+            _p: PhantomData,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sha256CircuitQuery<F: LurkField, const N: usize> {
+    input: AllocatedPtr<F>,
+}
+
+impl<F: LurkField, const N: usize> Query<F> for Sha256Query<F, N> {
+    type CQ = Sha256CircuitQuery<F, N>;
+
+    fn eval(&self, s: &Store<F>, _scope: &mut Scope<Self, LogMemo<F>>) -> Ptr {
+        let bytes = fetch_bytes_or_string(s, &self.input).expect("checked by `Sha256Query::new`");
+        s.intern_bytes(&Sha256::digest(bytes))
+    }
+
+    fn symbol(&self) -> Symbol {
+        sha256_symbol()
+    }
+
+    fn from_ptr(s: &Store<F>, ptr: &Ptr) -> Option<Self> {
+        let input = versioned_query_body(s, ptr, &sha256_name(), VERSION)?;
+        Self::new(s, input)
+    }
+
+    fn to_ptr(&self, s: &Store<F>) -> Ptr {
+        s.cons(self.symbol_ptr(s), self.input)
+    }
+
+    fn to_circuit<CS: ConstraintSystem<F>>(&self, cs: &mut CS, s: &Store<F>) -> Self::CQ {
+        Sha256CircuitQuery {
+            input: AllocatedPtr::alloc_infallible(cs, || s.hash_ptr(&self.input)),
+        }
+    }
+
+    fn dummy_from_index(s: &Store<F>, index: usize) -> Self {
+        assert_eq!(index, 0);
+        Self::new(s, s.intern_bytes(&[0; N])).unwrap()
+    }
+
+    fn index(&self) -> usize {
+        0
+    }
+
+    fn count() -> usize {
+        1
+    }
+}
+
+impl<F: LurkField, const N: usize> CircuitQuery<F> for Sha256CircuitQuery<F, N> {
+    // Hashing doesn't recurse.
+    type Ctx = ();
+
+    fn synthesize_eval<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        store: &Store<F>,
+        _scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        acc: &AllocatedPtr<F>,
+        transcript: &CircuitTranscript<F>,
+    ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        let (digest, ok) = synthesize_sha256_bytes(
+            &mut cs.namespace(|| "sha256"),
+            g,
+            store,
+            &Boolean::Constant(true),
+            &self.input,
+            N,
+        )?;
+        Boolean::enforce_equal(
+            &mut cs.namespace(|| "bytes or str"),
+            &ok,
+            &Boolean::Constant(true),
+        )?;
+        Ok((digest, acc.clone(), transcript.clone()))
+    }
+
+    fn from_ptr<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, ptr: &Ptr) -> Option<Self> {
+        Sha256Query::<F, N>::from_ptr(s, ptr).map(|q| q.to_circuit(cs, s))
+    }
+
+    fn dummy_from_index<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, index: usize) -> Self {
+        Sha256Query::<F, N>::dummy_from_index(s, index).to_circuit(cs, s)
+    }
+
+    fn symbol(&self) -> Symbol {
+        sha256_symbol()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+
+    #[test]
+    fn test_sha256_query() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<Sha256Query<F, 5>, LogMemo<F>> = Scope::new(true, 1);
+        let digest = s.intern_bytes(&Sha256::digest(b"hello"));
+        for form in ["(sha256 . \"hello\")", "(sha256 . b\"hello\")"] {
+            let query = s.read_with_default_state(form).unwrap();
+            assert_eq!(digest, scope.query(s, query));
+        }
+        let too_long = s.read_with_default_state("(sha256 . \"hello!\")").unwrap();
+        assert!(Sha256Query::<F, 5>::from_ptr(s, &too_long).is_none());
+
+        scope.finalize_transcript(s);
+        let cs = &mut TestConstraintSystem::new();
+        let g = &mut GlobalAllocator::default();
+        scope.synthesize(cs, g, s).unwrap();
+        assert!(cs.is_satisfied());
+    }
+}
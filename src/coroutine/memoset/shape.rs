@@ -0,0 +1,321 @@
+//! R1CS shapes of `CoroutineCircuit` chunks, and a cache for them.
+//!
+//! A chunk's circuit depends only on the query type, the query index it proves and its `rc`, and on how insertions are
+//! transcribed: unused slots are padded with dummy queries, so the same shape serves every witness. Shapes are
+//! therefore computed once per `ShapeKey`, kept in memory, and persisted on disk so later proving sessions can skip
+//! recomputing them.
+
+use anyhow::{Context, Result};
+use bellpepper_core::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::sync::Arc;
+
+use super::{ChunkSpec, LogMemo, Query, Scope, TranscriptScheme};
+use crate::field::{FWrap, LurkField};
+use crate::lem::store::Store;
+use crate::public_parameters::disk_cache::public_params_dir;
+
+pub(super) type Terms<F> = Vec<(Index, FWrap<F>)>;
+
+/// The R1CS shape of a chunk circuit: its variable counts and, for each constraint, the `(a, b, c)` linear
+/// combinations such that `a * b = c`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct ChunkShape<F: LurkField> {
+    pub num_inputs: usize,
+    pub num_aux: usize,
+    pub constraints: Vec<(Terms<F>, Terms<F>, Terms<F>)>,
+}
+
+impl<F: LurkField> ChunkShape<F> {
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+}
+
+/// A `ConstraintSystem` that records the shape of the circuit synthesized into it and discards the witness.
+pub(super) struct ShapeCS<F: LurkField> {
+    pub(super) shape: ChunkShape<F>,
+}
+
+impl<F: LurkField> ShapeCS<F> {
+    fn terms(lc: LinearCombination<F>) -> Terms<F> {
+        lc.iter()
+            .map(|(var, coeff)| (var.get_unchecked(), FWrap(*coeff)))
+            .collect()
+    }
+}
+
+impl<F: LurkField> ConstraintSystem<F> for ShapeCS<F> {
+    type Root = Self;
+
+    fn new() -> Self {
+        Self {
+            shape: ChunkShape {
+                // The constant `one` is input 0.
+                num_inputs: 1,
+                num_aux: 0,
+                constraints: vec![],
+            },
+        }
+    }
+
+    fn alloc<Fo, A, AR>(&mut self, _: A, f: Fo) -> Result<Variable, SynthesisError>
+    where
+        Fo: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        f()?;
+        self.shape.num_aux += 1;
+        Ok(Variable::new_unchecked(Index::Aux(self.shape.num_aux - 1)))
+    }
+
+    fn alloc_input<Fo, A, AR>(&mut self, _: A, f: Fo) -> Result<Variable, SynthesisError>
+    where
+        Fo: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        f()?;
+        self.shape.num_inputs += 1;
+        Ok(Variable::new_unchecked(Index::Input(
+            self.shape.num_inputs - 1,
+        )))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LB: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LC: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+    {
+        self.shape.constraints.push((
+            Self::terms(a(LinearCombination::zero())),
+            Self::terms(b(LinearCombination::zero())),
+            Self::terms(c(LinearCombination::zero())),
+        ));
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+/// What a chunk's shape depends on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShapeKey {
+    /// The name of the `Query` type
+    pub query_type: &'static str,
+    pub transcribe_internal_insertions: bool,
+    pub transcribe_toplevel_insertions: bool,
+    pub transcript_scheme: TranscriptScheme,
+    pub query_index: usize,
+    pub rc: usize,
+}
+
+/// Caches `ChunkShape`s by `ShapeKey`, in memory and, unless it's `in_memory`, in a directory on disk. Queries of the
+/// same type may still have different circuits (e.g. user queries of different programs), so a directory should only
+/// be shared by scopes whose queries are the same.
+pub struct ChunkShapeCache<F: LurkField> {
+    dir: Option<Utf8PathBuf>,
+    shapes: HashMap<ShapeKey, Arc<ChunkShape<F>>>,
+}
+
+impl<F: LurkField> ChunkShapeCache<F> {
+    pub fn new(dir: &Utf8Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {dir}"))?;
+        Ok(Self {
+            dir: Some(dir.to_owned()),
+            shapes: Default::default(),
+        })
+    }
+
+    /// A cache that only lives as long as it does, and leaves nothing on disk
+    pub fn in_memory() -> Self {
+        Self {
+            dir: None,
+            shapes: Default::default(),
+        }
+    }
+
+    /// The default location, next to the public parameters.
+    pub fn default_dir() -> Utf8PathBuf {
+        public_params_dir().join("coroutine_shapes")
+    }
+
+    fn path(dir: &Utf8Path, key: &ShapeKey) -> Utf8PathBuf {
+        let query_type = Sha256::digest(key.query_type.as_bytes());
+        dir.join(format!(
+            "{}-{}-{}{}-{:?}-{}-{}.shape",
+            F::FIELD,
+            hex::encode(&query_type[..8]),
+            u8::from(key.transcribe_internal_insertions),
+            u8::from(key.transcribe_toplevel_insertions),
+            key.transcript_scheme,
+            key.query_index,
+            key.rc
+        ))
+    }
+
+    /// Returns the cached shape for `key`, computing and persisting it with `compute` on a miss. The shape is written
+    /// to a temporary file first and then renamed, so a crash or a concurrent reader never sees a partial shape.
+    pub fn get_or_compute(
+        &mut self,
+        key: ShapeKey,
+        compute: impl FnOnce() -> Result<ChunkShape<F>>,
+    ) -> Result<Arc<ChunkShape<F>>> {
+        if let Some(shape) = self.shapes.get(&key) {
+            return Ok(shape.clone());
+        }
+
+        let Some(dir) = &self.dir else {
+            let shape = Arc::new(compute()?);
+            self.shapes.insert(key, shape.clone());
+            return Ok(shape);
+        };
+        let path = Self::path(dir, &key);
+        let shape = match File::open(&path) {
+            Ok(file) => bincode::deserialize_from(BufReader::new(file))
+                .with_context(|| format!("reading chunk shape from {path}"))?,
+            Err(_) => {
+                let shape = compute()?;
+                let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+                {
+                    let file = File::create(&tmp).with_context(|| format!("creating {tmp}"))?;
+                    bincode::serialize_into(BufWriter::new(file), &shape)?;
+                }
+                std::fs::rename(&tmp, &path)
+                    .with_context(|| format!("renaming {tmp} to {path}"))?;
+                shape
+            }
+        };
+
+        let shape = Arc::new(shape);
+        self.shapes.insert(key, shape.clone());
+        Ok(shape)
+    }
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// The key of the shape of the chunk circuit proving `rc` queries with index `query_index`
+    pub fn shape_key(&self, query_index: usize, rc: usize) -> ShapeKey {
+        ShapeKey {
+            query_type: std::any::type_name::<Q>(),
+            transcribe_internal_insertions: self.transcribe_internal_insertions,
+            transcribe_toplevel_insertions: self.transcribe_toplevel_insertions,
+            transcript_scheme: self.transcript_scheme,
+            query_index,
+            rc,
+        }
+    }
+
+    /// The shape of the chunk circuit proving `rc` queries with index `query_index`, from `cache` when possible.
+    pub fn chunk_shape(
+        &mut self,
+        s: &Store<F>,
+        cache: &mut ChunkShapeCache<F>,
+        query_index: usize,
+        rc: usize,
+    ) -> Result<Arc<ChunkShape<F>>> {
+        self.ensure_transcript_finalized(s);
+        cache.get_or_compute(self.shape_key(query_index, rc), || {
+            // All-padding chunks have the same shape as any other, and a witness that's always available.
+            let spec = ChunkSpec {
+                query_index,
+                chunk_index: 0,
+                keys: vec![],
+                rc,
+                acc: F::ZERO,
+                transcript: s.intern_nil(),
+            };
+            let mut cs = ShapeCS::new();
+            self.synthesize_chunk(&mut cs, s, &spec)?;
+            Ok(cs.shape)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coroutine::memoset::demo::DemoQuery;
+    use bellpepper_core::{test_cs::TestConstraintSystem, Comparable};
+    use halo2curves::bn256::Fr as F;
+
+    #[test]
+    fn test_chunk_shape_cache() {
+        let s = &Store::<F>::default();
+        let tmp_dir = tempfile::Builder::new().prefix("tmp").tempdir().unwrap();
+        let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        scope.query(s, s.read_with_default_state("(factorial . 3)").unwrap());
+
+        let mut cache = ChunkShapeCache::new(tmp_dir).unwrap();
+        let shape = scope.chunk_shape(s, &mut cache, 0, 2).unwrap();
+        assert!(Arc::ptr_eq(
+            &shape,
+            &scope.chunk_shape(s, &mut cache, 0, 2).unwrap()
+        ));
+
+        // A chunk with real queries has the same shape as the cached, all-padding one.
+        let spec = scope.chunk_specs(s).remove(0);
+        assert_eq!(2, spec.keys.len());
+        let cs = &mut TestConstraintSystem::<F>::new();
+        scope.synthesize_chunk(cs, s, &spec).unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_constraints(), shape.num_constraints());
+        assert_eq!(cs.aux().len(), shape.num_aux);
+
+        // A new session reads the shape from disk rather than recomputing it.
+        let mut cache = ChunkShapeCache::new(tmp_dir).unwrap();
+        let from_disk = cache
+            .get_or_compute(scope.shape_key(0, 2), || panic!("shape should be on disk"))
+            .unwrap();
+        assert_eq!(*shape, *from_disk);
+
+        // Transcribing internal insertions changes the shape, which is then cached apart.
+        let mut transcribed: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(true, 2);
+        assert_ne!(transcribed.shape_key(0, 2), scope.shape_key(0, 2));
+        let transcribed_shape = transcribed.chunk_shape(s, &mut cache, 0, 2).unwrap();
+        assert_ne!(*shape, *transcribed_shape);
+        assert_eq!(
+            2,
+            std::fs::read_dir(tmp_dir)
+                .unwrap()
+                .filter(|entry| entry.as_ref().unwrap().path().extension().unwrap() == "shape")
+                .count()
+        );
+
+        // An in-memory cache still computes each shape once.
+        let mut cache = ChunkShapeCache::in_memory();
+        let in_memory = scope.chunk_shape(s, &mut cache, 0, 2).unwrap();
+        assert_eq!(*shape, *in_memory);
+        assert!(Arc::ptr_eq(
+            &in_memory,
+            &cache
+                .get_or_compute(scope.shape_key(0, 2), || panic!(
+                    "shape should be in memory"
+                ))
+                .unwrap()
+        ));
+    }
+}
@@ -0,0 +1,382 @@
+//! Serializable snapshots of the memoset bookkeeping.
+//!
+//! `Scope`, `LogMemo` and `Transcript` point into a `Store`, so they can't be serialized as they are. A `Snapshot`
+//! replaces every `Ptr` with its `ZPtr` and carries the `ZDag` those `ZPtr`s point into, so that it can be restored
+//! into any store -- in particular, one in another process. Evaluation can hence happen in one process and proving in
+//! another. Circuit allocations are not part of snapshots.
+
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::{multiset::MultiSet, query::Query, LogMemo, Scope, Transcript, TranscriptScheme};
+use crate::{
+    field::LurkField,
+    lem::{
+        pointers::{Ptr, ZPtr},
+        store::Store,
+        tag::Tag,
+        z_dag::ZDag,
+    },
+    tag::ExprTag,
+};
+
+/// The `ZPtr` counterpart of a `Transcript`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TranscriptData<F: LurkField> {
+    acc: ZPtr<F>,
+}
+
+/// The `ZPtr` counterpart of a `LogMemo`. The multiset is sorted by element
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogMemoData<F: LurkField> {
+    multiset: Vec<(ZPtr<F>, usize)>,
+    r: Option<F>,
+    transcript: Option<TranscriptData<F>>,
+}
+
+/// The `ZPtr` counterpart of a `Scope`. Entries of maps are sorted by key, so that equal scopes have equal snapshots
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScopeData<F: LurkField> {
+    memoset: LogMemoData<F>,
+    queries: Vec<(ZPtr<F>, ZPtr<F>)>,
+    /// Queries are recorded with `Query::to_ptr`
+    dependencies: Vec<(ZPtr<F>, Vec<ZPtr<F>>)>,
+    toplevel_insertions: Vec<ZPtr<F>>,
+    internal_insertions: Vec<ZPtr<F>>,
+    unique_inserted_keys: Vec<(usize, Vec<ZPtr<F>>)>,
+    toplevel_multiplicities: Vec<(ZPtr<F>, usize)>,
+    transcribe_internal_insertions: bool,
+    dedup_toplevel_insertions: bool,
+    transcribe_toplevel_insertions: bool,
+    deferred: Vec<ZPtr<F>>,
+    transcript_scheme: TranscriptScheme,
+    default_rc: usize,
+}
+
+/// Memoset bookkeeping `data` made of `ZPtr`s, with the `ZDag` they point into
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot<F: LurkField, T> {
+    pub data: T,
+    z_dag: ZDag<F>,
+}
+
+/// Turns `Ptr`s into `ZPtr`s, collecting the data they point to
+struct Dehydrator<'a, F: LurkField> {
+    store: &'a Store<F>,
+    z_dag: ZDag<F>,
+    cache: HashMap<Ptr, ZPtr<F>>,
+}
+
+impl<'a, F: LurkField> Dehydrator<'a, F> {
+    fn new(store: &'a Store<F>) -> Self {
+        Self {
+            store,
+            z_dag: ZDag::default(),
+            cache: HashMap::default(),
+        }
+    }
+
+    fn z(&mut self, ptr: &Ptr) -> ZPtr<F> {
+        self.z_dag.populate_with(ptr, self.store, &mut self.cache)
+    }
+
+    fn zs<'b>(&mut self, ptrs: impl IntoIterator<Item = &'b Ptr>) -> Vec<ZPtr<F>> {
+        ptrs.into_iter().map(|ptr| self.z(ptr)).collect()
+    }
+
+    fn finish<T>(self, data: T) -> Snapshot<F, T> {
+        Snapshot {
+            data,
+            z_dag: self.z_dag,
+        }
+    }
+}
+
+/// Interns the `ZPtr`s of a snapshot back into a store
+struct Hydrator<'a, F: LurkField> {
+    store: &'a Store<F>,
+    z_dag: &'a ZDag<F>,
+    cache: HashMap<ZPtr<F>, Ptr>,
+}
+
+impl<'a, F: LurkField> Hydrator<'a, F> {
+    fn new<T>(snapshot: &'a Snapshot<F, T>, store: &'a Store<F>) -> Self {
+        Self {
+            store,
+            z_dag: &snapshot.z_dag,
+            cache: HashMap::default(),
+        }
+    }
+
+    fn ptr(&mut self, z_ptr: &ZPtr<F>) -> Result<Ptr> {
+        self.z_dag
+            .populate_store(z_ptr, self.store, &mut self.cache)
+    }
+
+    fn ptrs(&mut self, z_ptrs: &[ZPtr<F>]) -> Result<Vec<Ptr>> {
+        z_ptrs.iter().map(|z_ptr| self.ptr(z_ptr)).collect()
+    }
+
+    /// Like `ptr`, but the pointer must be a key-value pair, as the memoset code takes them apart
+    fn kv(&mut self, z_ptr: &ZPtr<F>) -> Result<Ptr> {
+        let kv = self.ptr(z_ptr)?;
+        if kv.tag() != &Tag::Expr(ExprTag::Cons) {
+            bail!(
+                "Invalid key-value pair: {}",
+                kv.fmt_to_string_simple(self.store)
+            );
+        }
+        Ok(kv)
+    }
+
+    fn kvs(&mut self, z_ptrs: &[ZPtr<F>]) -> Result<Vec<Ptr>> {
+        z_ptrs.iter().map(|z_ptr| self.kv(z_ptr)).collect()
+    }
+}
+
+impl<F: LurkField> Transcript<F> {
+    fn to_data(&self, d: &mut Dehydrator<'_, F>) -> TranscriptData<F> {
+        // transcripts are long lists, which are faster to hash in parallel first
+        d.store.hydrate_ptrs(&[self.acc]);
+        TranscriptData {
+            acc: d.z(&self.acc),
+        }
+    }
+
+    fn from_data(data: &TranscriptData<F>, h: &mut Hydrator<'_, F>) -> Result<Self> {
+        Ok(Self {
+            acc: h.ptr(&data.acc)?,
+            _p: Default::default(),
+        })
+    }
+
+    /// Snapshots the transcript for use in another store
+    pub fn snapshot(&self, s: &Store<F>) -> Snapshot<F, TranscriptData<F>> {
+        let mut d = Dehydrator::new(s);
+        let data = self.to_data(&mut d);
+        d.finish(data)
+    }
+
+    /// Restores a transcript snapshot into `s`
+    pub fn from_snapshot(snapshot: &Snapshot<F, TranscriptData<F>>, s: &Store<F>) -> Result<Self> {
+        Self::from_data(&snapshot.data, &mut Hydrator::new(snapshot, s))
+    }
+}
+
+impl<F: LurkField> LogMemo<F> {
+    fn to_data(&self, d: &mut Dehydrator<'_, F>) -> LogMemoData<F> {
+        let mut multiset = self
+            .multiset
+            .iter()
+            .map(|(kv, count)| (d.z(kv), count))
+            .collect::<Vec<_>>();
+        multiset.sort();
+        LogMemoData {
+            multiset,
+            r: self.r.get().copied(),
+            transcript: self.transcript.get().map(|t| t.to_data(d)),
+        }
+    }
+
+    fn from_data(data: &LogMemoData<F>, h: &mut Hydrator<'_, F>) -> Result<Self> {
+        let mut multiset = MultiSet::new();
+        for (kv, count) in &data.multiset {
+            let kv = h.kv(kv)?;
+            (0..*count).for_each(|_| multiset.add(kv));
+        }
+        let transcript = match &data.transcript {
+            Some(transcript) => OnceCell::with_value(Transcript::from_data(transcript, h)?),
+            None => OnceCell::new(),
+        };
+        if data.r.is_some() != transcript.get().is_some() {
+            bail!("The challenge and the transcript must be finalized together")
+        }
+        Ok(Self {
+            multiset,
+            r: data.r.map_or_else(OnceCell::new, OnceCell::with_value),
+            transcript,
+            allocated_r: OnceCell::new(),
+        })
+    }
+
+    /// Snapshots the memoset for use in another store
+    pub fn snapshot(&self, s: &Store<F>) -> Snapshot<F, LogMemoData<F>> {
+        let mut d = Dehydrator::new(s);
+        let data = self.to_data(&mut d);
+        d.finish(data)
+    }
+
+    /// Restores a memoset snapshot into `s`
+    pub fn from_snapshot(snapshot: &Snapshot<F, LogMemoData<F>>, s: &Store<F>) -> Result<Self> {
+        Self::from_data(&snapshot.data, &mut Hydrator::new(snapshot, s))
+    }
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// Snapshots the scope for use in another store, e.g. to prove in another process what was evaluated in this one
+    pub fn snapshot(&self, s: &Store<F>) -> Snapshot<F, ScopeData<F>> {
+        let mut d = Dehydrator::new(s);
+
+        let mut queries = self
+            .queries
+            .iter()
+            .map(|(k, v)| (d.z(k), d.z(v)))
+            .collect::<Vec<_>>();
+        queries.sort();
+        let mut dependencies = self
+            .dependencies
+            .iter()
+            .map(|(k, children)| {
+                let children = children.iter().map(|q| q.to_ptr(s)).collect::<Vec<_>>();
+                (d.z(k), d.zs(&children))
+            })
+            .collect::<Vec<_>>();
+        dependencies.sort();
+        let mut unique_inserted_keys = self
+            .unique_inserted_keys
+            .iter()
+            .map(|(index, keys)| (*index, d.zs(keys)))
+            .collect::<Vec<_>>();
+        unique_inserted_keys.sort();
+        let mut toplevel_multiplicities = self
+            .toplevel_multiplicities
+            .iter()
+            .map(|(kv, count)| (d.z(kv), *count))
+            .collect::<Vec<_>>();
+        toplevel_multiplicities.sort();
+
+        let data = ScopeData {
+            memoset: self.memoset.to_data(&mut d),
+            queries,
+            dependencies,
+            toplevel_insertions: d.zs(&self.toplevel_insertions),
+            internal_insertions: d.zs(&self.internal_insertions),
+            unique_inserted_keys,
+            toplevel_multiplicities,
+            transcribe_internal_insertions: self.transcribe_internal_insertions,
+            dedup_toplevel_insertions: self.dedup_toplevel_insertions,
+            transcribe_toplevel_insertions: self.transcribe_toplevel_insertions,
+            deferred: d.zs(&self.deferred),
+            transcript_scheme: self.transcript_scheme,
+            default_rc: self.default_rc,
+        };
+        d.finish(data)
+    }
+
+    /// Restores a scope snapshot into `s`. Fails if the snapshot is incomplete or records invalid queries
+    pub fn from_snapshot(snapshot: &Snapshot<F, ScopeData<F>>, s: &Store<F>) -> Result<Self> {
+        let data = &snapshot.data;
+        let mut h = Hydrator::new(snapshot, s);
+
+        let mut queries = HashMap::with_capacity(data.queries.len());
+        for (k, v) in &data.queries {
+            queries.insert(h.ptr(k)?, h.ptr(v)?);
+        }
+        let mut dependencies = HashMap::with_capacity(data.dependencies.len());
+        for (k, children) in &data.dependencies {
+            let children = h
+                .ptrs(children)?
+                .iter()
+                .map(|ptr| {
+                    Q::from_ptr(s, ptr)
+                        .ok_or_else(|| anyhow!("Invalid query: {}", ptr.fmt_to_string_simple(s)))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            dependencies.insert(h.ptr(k)?, children);
+        }
+        let mut unique_inserted_keys = HashMap::with_capacity(data.unique_inserted_keys.len());
+        for (index, keys) in &data.unique_inserted_keys {
+            unique_inserted_keys.insert(*index, h.ptrs(keys)?);
+        }
+        let mut toplevel_multiplicities =
+            HashMap::with_capacity(data.toplevel_multiplicities.len());
+        for (kv, count) in &data.toplevel_multiplicities {
+            toplevel_multiplicities.insert(h.kv(kv)?, *count);
+        }
+
+        Ok(Self {
+            memoset: LogMemo::from_data(&data.memoset, &mut h)?,
+            queries,
+            dependencies,
+            toplevel_insertions: h.kvs(&data.toplevel_insertions)?,
+            internal_insertions: h.ptrs(&data.internal_insertions)?,
+            unique_inserted_keys,
+            toplevel_multiplicities,
+            transcribe_internal_insertions: data.transcribe_internal_insertions,
+            dedup_toplevel_insertions: data.dedup_toplevel_insertions,
+            transcribe_toplevel_insertions: data.transcribe_toplevel_insertions,
+            deferred: h.ptrs(&data.deferred)?.into_iter().collect(),
+            transcript_scheme: data.transcript_scheme,
+            transcript_items: None,
+            default_rc: data.default_rc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::{demo::DemoQuery, MemoSet, MockBackend};
+
+    #[test]
+    fn test_scope_snapshot() {
+        let s1 = &Store::<F>::default();
+        let mut scope1: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        for n in [3, 5, 4] {
+            let query = s1
+                .read_with_default_state(&format!("(factorial . {n})"))
+                .unwrap();
+            scope1.query(s1, query);
+        }
+
+        // before and after the transcript is finalized
+        for finalize in [false, true] {
+            if finalize {
+                scope1.ensure_transcript_finalized(s1);
+            }
+            let snapshot = scope1.snapshot(s1);
+            let bytes = bincode::serialize(&snapshot).unwrap();
+            let snapshot: Snapshot<F, ScopeData<F>> = bincode::deserialize(&bytes).unwrap();
+
+            // as in another process
+            let s2 = &Store::<F>::default();
+            let mut scope2 =
+                Scope::<DemoQuery<F>, LogMemo<F>>::from_snapshot(&snapshot, s2).unwrap();
+            assert_eq!(scope2.memoset.is_finalized(), finalize);
+            assert_eq!(scope2.snapshot(s2).data, snapshot.data);
+
+            let (proof1, manifest1) = scope1.prove_reproducibly(s1, &MockBackend, &()).unwrap();
+            let (proof2, manifest2) = scope2.prove_reproducibly(s2, &MockBackend, &()).unwrap();
+            assert_eq!(proof1, proof2);
+            assert_eq!(manifest1, manifest2);
+        }
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        let query = s.read_with_default_state("(factorial . 2)").unwrap();
+        scope.query(s, query);
+        let other = &Store::<F>::default();
+
+        // a `ZPtr` missing from the dag
+        let mut missing = scope.snapshot(s);
+        missing
+            .data
+            .toplevel_insertions
+            .push(s.hash_ptr(&s.num_u64(42)));
+        assert!(Scope::<DemoQuery<F>, LogMemo<F>>::from_snapshot(&missing, other).is_err());
+
+        // a top-level insertion that isn't a key-value pair
+        let mut not_a_kv = scope.snapshot(s);
+        let (head, _) = s.car_cdr(&query).unwrap();
+        not_a_kv.data.toplevel_insertions.push(s.hash_ptr(&head));
+        let err = Scope::<DemoQuery<F>, LogMemo<F>>::from_snapshot(&not_a_kv, other).unwrap_err();
+        assert!(err.to_string().contains("Invalid key-value pair"), "{err}");
+    }
+}
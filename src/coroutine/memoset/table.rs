@@ -0,0 +1,176 @@
+//! The query table of a `Scope`, as rows and, with the `arrow` feature, as Arrow record batches or Parquet files.
+//!
+//! Every memoized query is a row with its key and value as `ZPtr` digests and the number of times it was inserted in
+//! the memoset. Rendering keys and values as text is optional, since it's by far the most expensive part of large
+//! exports.
+
+use super::{query::Query, LogMemo, MemoSet, Scope, Transcript};
+use crate::{field::LurkField, lem::store::Store};
+
+/// A memoized query. Digests are hex-encoded, as in `ProofManifest`s
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryRow {
+    pub query_index: usize,
+    pub key_tag: u16,
+    pub key: String,
+    pub value_tag: u16,
+    pub value: String,
+    /// How many times the key-value pair was inserted in the memoset
+    pub multiplicity: usize,
+    pub key_text: Option<String>,
+    pub value_text: Option<String>,
+}
+
+impl<F: LurkField, Q: Query<F>> Scope<Q, LogMemo<F>> {
+    /// The rows of the query table, ordered by query index and then by key. Keys and values are rendered as text
+    /// only if `render` is set.
+    pub fn query_table(&self, s: &Store<F>, render: bool) -> Vec<QueryRow> {
+        let mut rows = self
+            .queries
+            .iter()
+            .map(|(key, value)| {
+                let (z_key, z_value) = (s.hash_ptr(key), s.hash_ptr(value));
+                let kv = Transcript::make_kv(s, *key, *value);
+                QueryRow {
+                    query_index: Q::from_ptr(s, key).expect("bad query").index(),
+                    key_tag: (*z_key.tag()).into(),
+                    key: z_key.value().hex_digits(),
+                    value_tag: (*z_value.tag()).into(),
+                    value: z_value.value().hex_digits(),
+                    multiplicity: self.memoset.count(&kv),
+                    key_text: render.then(|| key.fmt_to_string_simple(s)),
+                    value_text: render.then(|| value.fmt_to_string_simple(s)),
+                }
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| (a.query_index, &a.key).cmp(&(b.query_index, &b.key)));
+        rows
+    }
+}
+
+#[cfg(feature = "arrow")]
+mod arrow_export {
+    use anyhow::Result;
+    use arrow::{
+        array::{ArrayRef, StringArray, UInt16Array, UInt64Array},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    };
+    use camino::Utf8Path;
+    use parquet::arrow::ArrowWriter;
+    use std::{fs::File, sync::Arc};
+
+    use super::QueryRow;
+
+    /// The Arrow schema of query tables
+    pub fn query_table_schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("query_index", DataType::UInt64, false),
+            Field::new("key_tag", DataType::UInt16, false),
+            Field::new("key", DataType::Utf8, false),
+            Field::new("value_tag", DataType::UInt16, false),
+            Field::new("value", DataType::Utf8, false),
+            Field::new("multiplicity", DataType::UInt64, false),
+            Field::new("key_text", DataType::Utf8, true),
+            Field::new("value_text", DataType::Utf8, true),
+        ]))
+    }
+
+    /// Splits `rows` into record batches of at most `batch_size` rows
+    pub fn to_record_batches(rows: &[QueryRow], batch_size: usize) -> Result<Vec<RecordBatch>> {
+        assert!(batch_size > 0, "batch_size must be positive");
+        let schema = query_table_schema();
+        rows.chunks(batch_size)
+            .map(|rows| {
+                let u64s = |f: fn(&QueryRow) -> usize| -> ArrayRef {
+                    Arc::new(UInt64Array::from_iter_values(
+                        rows.iter().map(|row| f(row) as u64),
+                    ))
+                };
+                let u16s = |f: fn(&QueryRow) -> u16| -> ArrayRef {
+                    Arc::new(UInt16Array::from_iter_values(rows.iter().map(f)))
+                };
+                let strings = |f: fn(&QueryRow) -> Option<&str>| -> ArrayRef {
+                    Arc::new(rows.iter().map(f).collect::<StringArray>())
+                };
+                let columns = vec![
+                    u64s(|row| row.query_index),
+                    u16s(|row| row.key_tag),
+                    strings(|row| Some(row.key.as_str())),
+                    u16s(|row| row.value_tag),
+                    strings(|row| Some(row.value.as_str())),
+                    u64s(|row| row.multiplicity),
+                    strings(|row| row.key_text.as_deref()),
+                    strings(|row| row.value_text.as_deref()),
+                ];
+                Ok(RecordBatch::try_new(schema.clone(), columns)?)
+            })
+            .collect()
+    }
+
+    /// Writes `rows` to a Parquet file at `path`, in row groups of at most `batch_size` rows
+    pub fn write_parquet(rows: &[QueryRow], batch_size: usize, path: &Utf8Path) -> Result<()> {
+        let file = File::create(path)?;
+        let mut writer = ArrowWriter::try_new(file, query_table_schema(), None)?;
+        for batch in to_record_batches(rows, batch_size)? {
+            writer.write(&batch)?;
+        }
+        writer.close()?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "arrow")]
+pub use arrow_export::{query_table_schema, to_record_batches, write_parquet};
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::demo::DemoQuery;
+
+    #[test]
+    fn test_query_table() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        for n in [3, 3, 2] {
+            let query = s
+                .read_with_default_state(&format!("(factorial . {n})"))
+                .unwrap();
+            scope.query(s, query);
+        }
+
+        let three = s.read_with_default_state("(factorial . 3)").unwrap();
+        let three = s.hash_ptr(&three).value().hex_digits();
+        let rows = scope.query_table(s, true);
+        // factorial 0 through 3
+        assert_eq!(rows.len(), 4);
+        assert!(rows.iter().all(|row| row.key_text.is_some()));
+        let three = rows.iter().find(|row| row.key == three).unwrap();
+        assert_eq!(three.value_text.as_deref(), Some("6"));
+        // queried twice at the top level
+        assert_eq!(three.multiplicity, 2);
+        assert!(scope
+            .query_table(s, false)
+            .iter()
+            .all(|row| row.key_text.is_none()));
+
+        #[cfg(feature = "arrow")]
+        {
+            let batches = to_record_batches(&rows, 3).unwrap();
+            assert_eq!(
+                batches
+                    .iter()
+                    .map(|batch| batch.num_rows())
+                    .collect::<Vec<_>>(),
+                [3, 1]
+            );
+            let dir = tempfile::tempdir().unwrap();
+            let path =
+                camino::Utf8PathBuf::from_path_buf(dir.path().join("queries.parquet")).unwrap();
+            write_parquet(&rows, 3, &path).unwrap();
+            assert!(path.exists());
+        }
+    }
+}
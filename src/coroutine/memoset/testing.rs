@@ -0,0 +1,465 @@
+//! A harness for checking `Query` implementations, meant for the tests of new query types.
+//!
+//! `QueryHarness::check` makes a batch of queries in a fresh `Scope` and checks that:
+//! - every query round-trips through `Query::from_ptr` and `Query::to_ptr`, as do the dummy queries of every index;
+//! - the circuit agrees with native evaluation: each chunk is satisfied and leaves the memoset accumulator and the
+//!   transcript where the native replay of the transcript expects them;
+//! - dummy queries, which pad chunks, are satisfiable and leave the accumulator and the transcript untouched;
+//! - the transcript balances, with the accumulator back to zero once every chunk is proved;
+//! - the number of constraints of a chunk depends only on its query index, not on the queries it proves.
+//!
+//! `AccountingCS` breaks the constraints of a circuit down by namespace, to tell which part of a chunk changed when its
+//! constraint count does.
+//!
+//! The queries are up to the caller, who would typically generate them with `proptest`:
+//! ```ignore
+//! proptest! {
+//!     #[test]
+//!     fn prop_my_query(args in prop::collection::vec(any::<u64>(), 1..4)) {
+//!         let s = &Store::<Fr>::default();
+//!         let queries = args.iter().map(|arg| MyQuery::new(s, *arg).to_ptr(s)).collect::<Vec<_>>();
+//!         QueryHarness::<Fr, MyQuery<Fr>>::default().check(s, &queries).unwrap();
+//!     }
+//! }
+//! ```
+
+use anyhow::{bail, ensure, Result};
+use bellpepper_core::{
+    test_cs::TestConstraintSystem, Comparable, ConstraintSystem, LinearCombination, SynthesisError,
+    Variable,
+};
+use indexmap::IndexMap;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use super::{ChunkSpec, LogMemo, Query, Scope};
+use crate::field::LurkField;
+use crate::lem::{pointers::Ptr, store::Store};
+
+/// What `QueryHarness::check` found out about a query implementation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryReport {
+    /// The number of chunks checked
+    pub chunks: usize,
+    /// query index -> number of constraints of a chunk
+    pub constraints: BTreeMap<usize, usize>,
+}
+
+/// Checks a `Query` implementation against the queries given to `check`. By default chunks prove a single query, so
+/// that failures point at the query responsible, and internal insertions aren't transcribed.
+#[derive(Clone, Debug)]
+pub struct QueryHarness<F, Q> {
+    rc: usize,
+    transcribe_internal_insertions: bool,
+    _p: PhantomData<(F, Q)>,
+}
+
+impl<F, Q> Default for QueryHarness<F, Q> {
+    fn default() -> Self {
+        Self {
+            rc: 1,
+            transcribe_internal_insertions: false,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<F: LurkField, Q: Query<F>> QueryHarness<F, Q> {
+    /// Proves `rc` queries per chunk
+    pub fn with_rc(mut self, rc: usize) -> Self {
+        assert!(rc > 0, "chunks must prove at least one query");
+        self.rc = rc;
+        self
+    }
+
+    /// Transcribes internal insertions, as `Scope::new` does when asked to
+    pub fn with_internal_insertions_transcribed(mut self) -> Self {
+        self.transcribe_internal_insertions = true;
+        self
+    }
+
+    /// Runs every check on `queries`, failing with a description of the first violation found
+    pub fn check(&self, s: &Store<F>, queries: &[Ptr]) -> Result<QueryReport> {
+        ensure!(!queries.is_empty(), "no queries to check");
+        for query in queries {
+            Self::check_roundtrip(s, query)?;
+        }
+        let mut scope = Scope::new(self.transcribe_internal_insertions, self.rc);
+        for query in queries {
+            scope.query(s, *query);
+        }
+        Self::check_scope(s, &mut scope)
+    }
+
+    fn check_roundtrip(s: &Store<F>, query: &Ptr) -> Result<()> {
+        let Some(parsed) = Q::from_ptr(s, query) else {
+            bail!("{} is not a query", query.fmt_to_string_simple(s));
+        };
+        ensure!(
+            s.hash_ptr(&parsed.to_ptr(s)) == s.hash_ptr(query),
+            "{} doesn't round-trip through `from_ptr` and `to_ptr`",
+            query.fmt_to_string_simple(s)
+        );
+        ensure!(
+            parsed.index() < Q::count(),
+            "{} has index {}, but there are only {} query types",
+            query.fmt_to_string_simple(s),
+            parsed.index(),
+            Q::count()
+        );
+        Ok(())
+    }
+
+    fn check_scope(s: &Store<F>, scope: &mut Scope<Q, LogMemo<F>>) -> Result<QueryReport> {
+        scope.ensure_transcript_finalized(s);
+        let (specs, final_acc) = scope.replay(s);
+        let transcript = scope
+            .memoset
+            .transcript
+            .get()
+            .expect("transcript not finalized")
+            .acc;
+
+        let mut constraints = BTreeMap::new();
+        for (i, spec) in specs.iter().enumerate() {
+            let keys = || {
+                spec.keys
+                    .iter()
+                    .map(|key| key.fmt_to_string_simple(s))
+                    .join(", ")
+            };
+            let cs = &mut TestConstraintSystem::<F>::new();
+            let (_, z_out) = scope.synthesize_chunk(cs, s, spec)?;
+            if let Some(constraint) = cs.which_is_unsatisfied() {
+                bail!(
+                    "the chunk proving {} is unsatisfied at {constraint}",
+                    keys()
+                );
+            }
+            let (acc, next_transcript) = specs
+                .get(i + 1)
+                .map_or((final_acc, transcript), |next| (next.acc, next.transcript));
+            ensure!(
+                *z_out.memoset_acc.value() == acc
+                    && z_out.transcript == s.hash_ptr(&next_transcript),
+                "the circuit disagrees with native evaluation on {}",
+                keys()
+            );
+            record_constraints(&mut constraints, spec.query_index, cs, || {
+                format!("the chunk proving {}", keys())
+            })?;
+        }
+
+        ensure!(
+            final_acc == F::ZERO,
+            "the memoset accumulator ends at {final_acc:?} rather than zero"
+        );
+
+        // Dummies are checked from the state of the first chunk, so they'd have something to change.
+        let (acc, transcript) = specs.first().map_or((F::ZERO, s.intern_nil()), |spec| {
+            (spec.acc, spec.transcript)
+        });
+        for index in 0..Q::count() {
+            let dummy = Q::dummy_from_index(s, index);
+            ensure!(
+                dummy.index() == index,
+                "the dummy query of index {index} has index {}",
+                dummy.index()
+            );
+            Self::check_roundtrip(s, &dummy.to_ptr(s))?;
+
+            let spec = ChunkSpec {
+                query_index: index,
+                chunk_index: 0,
+                keys: vec![],
+                rc: scope.rc_for_query(index),
+                acc,
+                transcript,
+            };
+            let cs = &mut TestConstraintSystem::<F>::new();
+            let (z_in, z_out) = scope.synthesize_chunk(cs, s, &spec)?;
+            if let Some(constraint) = cs.which_is_unsatisfied() {
+                bail!("the dummy query of index {index} is unsatisfied at {constraint}");
+            }
+            ensure!(
+                z_in == z_out,
+                "the dummy query of index {index} changes the memoset accumulator or the transcript"
+            );
+            record_constraints(&mut constraints, index, cs, || {
+                format!("a chunk of dummy queries of index {index}")
+            })?;
+        }
+
+        Ok(QueryReport {
+            chunks: specs.len(),
+            constraints,
+        })
+    }
+}
+
+/// Records the number of constraints of a chunk of query index `index`, failing if it differs from that of the chunks
+/// recorded before
+fn record_constraints<F: LurkField>(
+    constraints: &mut BTreeMap<usize, usize>,
+    index: usize,
+    cs: &TestConstraintSystem<F>,
+    chunk: impl FnOnce() -> String,
+) -> Result<()> {
+    let count = cs.num_constraints();
+    let expected = *constraints.entry(index).or_insert(count);
+    ensure!(
+        expected == count,
+        "{} has {count} constraints, but other chunks of query index {index} have {expected}",
+        chunk()
+    );
+    Ok(())
+}
+
+/// Constraint, aux and input counts of a namespace, including those of the namespaces nested in it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NamespaceCounts {
+    pub constraints: usize,
+    pub aux: usize,
+    pub inputs: usize,
+    /// The nested namespaces, in the order they were first entered
+    pub children: IndexMap<String, NamespaceCounts>,
+}
+
+impl NamespaceCounts {
+    /// The counts of the namespace at `path`, relative to this one, with segments separated by `/`, as in
+    /// `query-index-0/chunk-2/internal-0/eval`
+    pub fn get(&self, path: &str) -> Option<&Self> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |counts, segment| counts.children.get(segment))
+    }
+
+    /// Renders the tree of counts, down to `depth` levels of nesting
+    pub fn tree(&self, depth: usize) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, "<root>", 0, depth);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, name: &str, level: usize, depth: usize) {
+        out.push_str(&format!(
+            "{:indent$}{name}: {} constraints, {} aux, {} inputs\n",
+            "",
+            self.constraints,
+            self.aux,
+            self.inputs,
+            indent = 2 * level
+        ));
+        if level < depth {
+            for (child_name, child) in &self.children {
+                child.write_tree(out, child_name, level + 1, depth);
+            }
+        }
+    }
+
+    fn record(&mut self, path: &[String], f: impl Fn(&mut Self)) {
+        f(self);
+        if let Some((head, tail)) = path.split_first() {
+            self.children
+                .entry(head.clone())
+                .or_default()
+                .record(tail, f);
+        }
+    }
+}
+
+impl fmt::Display for NamespaceCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.tree(usize::MAX))
+    }
+}
+
+/// A constraint system attributing the constraints and variables synthesized into it to the namespaces they were
+/// synthesized in, while passing everything on to `CS`. Wrapping a `TestConstraintSystem` keeps its checks, so a
+/// circuit can be checked for satisfiability and broken down at once:
+/// ```ignore
+/// let cs = &mut AccountingCS::wrap(TestConstraintSystem::<Fr>::new());
+/// scope.synthesize(cs, g, s)?;
+/// assert!(cs.inner().is_satisfied());
+/// let breakdown = cs.counts().tree(2);
+/// ```
+pub struct AccountingCS<F: LurkField, CS> {
+    inner: CS,
+    path: Vec<String>,
+    counts: NamespaceCounts,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField, CS: ConstraintSystem<F>> AccountingCS<F, CS> {
+    pub fn wrap(inner: CS) -> Self {
+        Self {
+            inner,
+            path: vec![],
+            counts: NamespaceCounts::default(),
+            _p: PhantomData,
+        }
+    }
+
+    pub fn inner(&self) -> &CS {
+        &self.inner
+    }
+
+    pub fn counts(&self) -> &NamespaceCounts {
+        &self.counts
+    }
+
+    pub fn into_parts(self) -> (CS, NamespaceCounts) {
+        (self.inner, self.counts)
+    }
+}
+
+impl<F: LurkField, CS: ConstraintSystem<F>> ConstraintSystem<F> for AccountingCS<F, CS> {
+    type Root = Self;
+
+    fn new() -> Self {
+        Self::wrap(CS::new())
+    }
+
+    fn alloc<Fo, A, AR>(&mut self, annotation: A, f: Fo) -> Result<Variable, SynthesisError>
+    where
+        Fo: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.counts.record(&self.path, |counts| counts.aux += 1);
+        self.inner.alloc(annotation, f)
+    }
+
+    fn alloc_input<Fo, A, AR>(&mut self, annotation: A, f: Fo) -> Result<Variable, SynthesisError>
+    where
+        Fo: FnOnce() -> Result<F, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.counts.record(&self.path, |counts| counts.inputs += 1);
+        self.inner.alloc_input(annotation, f)
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LB: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+        LC: FnOnce(LinearCombination<F>) -> LinearCombination<F>,
+    {
+        self.counts
+            .record(&self.path, |counts| counts.constraints += 1);
+        self.inner.enforce(annotation, a, b, c)
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        let name = name_fn().into();
+        self.path.push(name.clone());
+        self.inner.get_root().push_namespace(|| name);
+    }
+
+    fn pop_namespace(&mut self) {
+        self.path.pop();
+        self.inner.get_root().pop_namespace();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::coroutine::memoset::demo::DemoQuery;
+    use crate::lem::circuit::GlobalAllocator;
+    use halo2curves::bn256::Fr as F;
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig {
+            cases: 8,
+            .. ProptestConfig::default()
+        })]
+
+        #[test]
+        fn prop_demo_query(ns in prop::collection::vec(0u64..6, 1..4), rc in 1usize..3) {
+            let s = &Store::<F>::default();
+            let queries = ns
+                .iter()
+                .map(|n| DemoQuery::Factorial(s.num_u64(*n)).to_ptr(s))
+                .collect::<Vec<_>>();
+            let report = QueryHarness::<F, DemoQuery<F>>::default()
+                .with_rc(rc)
+                .check(s, &queries)
+                .unwrap();
+            // factorial of n queries factorial of every m < n
+            let unique_keys = *ns.iter().max().unwrap() as usize + 1;
+            prop_assert_eq!(unique_keys.div_ceil(rc), report.chunks);
+            prop_assert_eq!(vec![0], report.constraints.into_keys().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn test_harness_failures() {
+        let s = &Store::<F>::default();
+        let harness = QueryHarness::<F, DemoQuery<F>>::default();
+        let not_a_query = s.read_with_default_state("(fibonacci . 3)").unwrap();
+        assert!(harness.check(s, &[not_a_query]).is_err());
+        assert!(harness.check(s, &[]).is_err());
+
+        // A wrong native result is caught by the circuit, which computes the right one.
+        let fact_2 = DemoQuery::Factorial(s.num_u64(2)).to_ptr(s);
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 1);
+        scope.query(s, DemoQuery::Factorial(s.num_u64(3)).to_ptr(s));
+        scope.queries.insert(fact_2, s.num_u64(3));
+        let err = QueryHarness::<F, DemoQuery<F>>::check_scope(s, &mut scope).unwrap_err();
+        assert!(err.to_string().contains("disagrees"), "{err}");
+    }
+
+    #[test]
+    fn test_accounting_cs() {
+        let s = &Store::<F>::default();
+        let mut scope: Scope<DemoQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        scope.query(s, DemoQuery::Factorial(s.num_u64(3)).to_ptr(s));
+
+        let cs = &mut AccountingCS::wrap(TestConstraintSystem::<F>::new());
+        let g = &mut GlobalAllocator::default();
+        scope.synthesize(cs, g, s).unwrap();
+        assert!(cs.inner().is_satisfied());
+
+        let counts = cs.counts();
+        assert_eq!(cs.inner().num_constraints(), counts.constraints);
+        assert_eq!(cs.inner().aux().len(), counts.aux);
+        // `finalize` constrains the accumulator and the transcript outside of any namespace.
+        assert!(
+            counts.constraints
+                > counts
+                    .children
+                    .values()
+                    .map(|child| child.constraints)
+                    .sum::<usize>()
+        );
+
+        // Both chunks prove two factorials, and so have the same circuit.
+        let chunk_0 = counts.get("query-index-0/chunk-0").unwrap();
+        let chunk_1 = counts.get("query-index-0/chunk-1").unwrap();
+        assert_eq!(chunk_0, chunk_1);
+        assert_eq!(
+            chunk_0.get("internal-0/eval").unwrap().constraints,
+            chunk_0.get("internal-1/eval").unwrap().constraints
+        );
+        assert!(counts.get("query-index-1").is_none());
+        assert!(counts
+            .tree(1)
+            .starts_with(&format!("<root>: {} constraints", counts.constraints)));
+    }
+}
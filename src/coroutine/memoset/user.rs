@@ -0,0 +1,537 @@
+//! Queries defined in Lurk.
+//!
+//! `QueryDef::define` compiles `(<name> (<arg>) <body>)` into a query whose responses are `<body>` with `<arg>` bound
+//! to the number queried, as in `(<name> . <num>)`. The body is an expression over numbers: number literals, `<arg>`,
+//! `+`, `-` and `*`, `(if (= <a> <b>) <then> <else>)`, and `(<query> <expr>)`, a subquery of `<name>` itself or of a
+//! query defined before it.
+//!
+//! The circuit of a query synthesizes every branch of its body, each `if` enabling the subqueries of the branch taken
+//! only. Since `Query` finds queries from their forms alone, definitions are registered process-wide: `UserQuery` has
+//! an index per definition, in the order they're made, and a query can't be redefined with a different body. A proof
+//! is hence checked against the same definitions, made in the same order.
+
+use anyhow::{bail, Context, Result};
+use bellpepper_core::{boolean::Boolean, num::AllocatedNum, ConstraintSystem, SynthesisError};
+use indexmap::IndexMap;
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+
+use super::{
+    query::{CircuitQuery, Query},
+    CircuitScope, CircuitTranscript, LogMemo, LogMemoCircuit, Scope,
+};
+use crate::circuit::gadgets::constraints::{alloc_equal, mul, pick, sub};
+use crate::circuit::gadgets::data::construct_cons;
+use crate::circuit::gadgets::pointer::AllocatedPtr;
+use crate::field::LurkField;
+use crate::lem::circuit::GlobalAllocator;
+use crate::lem::{pointers::Ptr, store::Store, tag::Tag};
+use crate::state::lurk_sym;
+use crate::symbol::Symbol;
+use crate::tag::ExprTag;
+
+/// The definitions of every `UserQuery`, by name, in the order they were made
+static DEFINITIONS: Lazy<RwLock<IndexMap<Symbol, Arc<QueryDef>>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+}
+
+/// The compiled body of a `QueryDef`
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Body {
+    Num(u64),
+    Arg,
+    Op(Op, Box<Body>, Box<Body>),
+    /// `(if (= a b) then else)`
+    If(Box<Body>, Box<Body>, Box<Body>, Box<Body>),
+    /// A subquery of the named query
+    Query(Symbol, Box<Body>),
+}
+
+/// A query defined in Lurk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryDef {
+    name: Symbol,
+    body: Body,
+    /// The definition, printed
+    source: String,
+}
+
+impl QueryDef {
+    /// Compiles the definition `(<name> (<arg>) <body>)` and registers it, or returns the registered one if it's the
+    /// same
+    pub fn define<F: LurkField>(s: &Store<F>, def: &Ptr) -> Result<Arc<Self>> {
+        let Some((elts, None)) = s.fetch_list(def) else {
+            bail!("Query definitions have the form (<name> (<arg>) <body>)")
+        };
+        let [name, args, body] = &elts[..] else {
+            bail!("Query definitions have the form (<name> (<arg>) <body>)")
+        };
+        let name = s.fetch_sym(name).context("Query names must be symbols")?;
+        let arg = match s.fetch_list(args) {
+            Some((args, None)) if args.len() == 1 => s.fetch_sym(&args[0]),
+            _ => None,
+        }
+        .context("Queries take a single argument, a symbol")?;
+
+        let mut definitions = DEFINITIONS.write().expect("poisoned definitions");
+        let def = Self {
+            body: Self::compile(s, &name, &arg, &definitions, body)?,
+            name,
+            source: def.fmt_to_string_simple(s),
+        };
+        match definitions.get(&def.name) {
+            Some(defined) if **defined == def => Ok(defined.clone()),
+            Some(_) => bail!("Query {} is already defined differently", def.name),
+            None => {
+                let def = Arc::new(def);
+                definitions.insert(def.name.clone(), def.clone());
+                Ok(def)
+            }
+        }
+    }
+
+    /// The registered definition of `name`, if any
+    pub fn get(name: &Symbol) -> Option<Arc<Self>> {
+        let definitions = DEFINITIONS.read().expect("poisoned definitions");
+        definitions.get(name).cloned()
+    }
+
+    /// Every registered definition, in the order they were made
+    pub fn all() -> Vec<Arc<Self>> {
+        let definitions = DEFINITIONS.read().expect("poisoned definitions");
+        definitions.values().cloned().collect()
+    }
+
+    #[inline]
+    pub fn name(&self) -> &Symbol {
+        &self.name
+    }
+
+    /// The definition, as `define` reads it back
+    #[inline]
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn compile<F: LurkField>(
+        s: &Store<F>,
+        name: &Symbol,
+        arg: &Symbol,
+        definitions: &IndexMap<Symbol, Arc<QueryDef>>,
+        expr: &Ptr,
+    ) -> Result<Body> {
+        let compile = |expr| Self::compile(s, name, arg, definitions, expr).map(Box::new);
+        match expr.tag() {
+            Tag::Expr(ExprTag::Num) => {
+                let n = s.hash_ptr(expr).value().to_u64();
+                Ok(Body::Num(n.context("Number literals must fit in 64 bits")?))
+            }
+            Tag::Expr(ExprTag::Sym) if s.fetch_sym(expr).as_ref() == Some(arg) => Ok(Body::Arg),
+            Tag::Expr(ExprTag::Cons) => {
+                let Some((elts, None)) = s.fetch_list(expr) else {
+                    bail!("Not a proper list: {}", expr.fmt_to_string_simple(s))
+                };
+                let Some(head) = s.fetch_sym(&elts[0]) else {
+                    bail!("Not a symbol: {}", elts[0].fmt_to_string_simple(s))
+                };
+                let op = |op| match &elts[1..] {
+                    [a, b] => Ok(Body::Op(op, compile(a)?, compile(b)?)),
+                    _ => bail!("{head} takes two arguments"),
+                };
+                if head == lurk_sym("+") {
+                    op(Op::Add)
+                } else if head == lurk_sym("-") {
+                    op(Op::Sub)
+                } else if head == lurk_sym("*") {
+                    op(Op::Mul)
+                } else if head == lurk_sym("if") {
+                    let [cond, then, els] = &elts[1..] else {
+                        bail!("if takes three arguments")
+                    };
+                    match s.fetch_list(cond) {
+                        Some((cond, None))
+                            if cond.len() == 3 && s.fetch_sym(&cond[0]) == Some(lurk_sym("=")) =>
+                        {
+                            Ok(Body::If(
+                                compile(&cond[1])?,
+                                compile(&cond[2])?,
+                                compile(then)?,
+                                compile(els)?,
+                            ))
+                        }
+                        _ => bail!("Conditions have the form (= <a> <b>)"),
+                    }
+                } else if &head == name || definitions.contains_key(&head) {
+                    let [query_arg] = &elts[1..] else {
+                        bail!("Query {head} takes one argument")
+                    };
+                    Ok(Body::Query(head, compile(query_arg)?))
+                } else {
+                    bail!("Unknown query or operator {head}")
+                }
+            }
+            _ => bail!("Unsupported expression: {}", expr.fmt_to_string_simple(s)),
+        }
+    }
+
+    /// The index of every `UserQuery` of this definition
+    fn index(&self) -> usize {
+        let definitions = DEFINITIONS.read().expect("poisoned definitions");
+        definitions
+            .get_index_of(&self.name)
+            .expect("unregistered definition")
+    }
+
+    fn eval_body<F: LurkField>(
+        &self,
+        s: &Store<F>,
+        scope: &mut Scope<UserQuery<F>, LogMemo<F>>,
+        query: &UserQuery<F>,
+        body: &Body,
+    ) -> F {
+        match body {
+            Body::Num(n) => F::from_u64(*n),
+            Body::Arg => *s.hash_ptr(&query.arg).value(),
+            Body::Op(op, a, b) => {
+                let a = self.eval_body(s, scope, query, a);
+                let b = self.eval_body(s, scope, query, b);
+                match op {
+                    Op::Add => a + b,
+                    Op::Sub => a - b,
+                    Op::Mul => a * b,
+                }
+            }
+            Body::If(a, b, then, els) => {
+                let a = self.eval_body(s, scope, query, a);
+                let b = self.eval_body(s, scope, query, b);
+                let branch = if a == b { then } else { els };
+                self.eval_body(s, scope, query, branch)
+            }
+            Body::Query(name, arg) => {
+                let arg = self.eval_body(s, scope, query, arg);
+                let def = Self::get(name).expect("unregistered definition");
+                let subquery = UserQuery::new(def, s.num(arg));
+                let response = query.recursive_eval(scope, s, subquery);
+                *s.hash_ptr(&response).value()
+            }
+        }
+    }
+
+    /// Synthesizes `body`, whose subqueries are only made when `active` is true
+    #[allow(clippy::too_many_arguments)]
+    fn synthesize_body<F: LurkField, CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        arg: &AllocatedNum<F>,
+        active: &Boolean,
+        body: &Body,
+        acc: &AllocatedPtr<F>,
+        transcript: &CircuitTranscript<F>,
+    ) -> Result<(AllocatedNum<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        match body {
+            Body::Num(n) => Ok((
+                g.alloc_const_cloned(cs, F::from_u64(*n)),
+                acc.clone(),
+                transcript.clone(),
+            )),
+            Body::Arg => Ok((arg.clone(), acc.clone(), transcript.clone())),
+            Body::Op(op, a, b) => {
+                let (a, acc, transcript) = self.synthesize_body(
+                    &mut cs.namespace(|| "a"),
+                    g,
+                    s,
+                    scope,
+                    arg,
+                    active,
+                    a,
+                    acc,
+                    transcript,
+                )?;
+                let (b, acc, transcript) = self.synthesize_body(
+                    &mut cs.namespace(|| "b"),
+                    g,
+                    s,
+                    scope,
+                    arg,
+                    active,
+                    b,
+                    &acc,
+                    &transcript,
+                )?;
+                let res = match op {
+                    Op::Add => a.add(cs.namespace(|| "add"), &b)?,
+                    Op::Sub => sub(cs.namespace(|| "sub"), &a, &b)?,
+                    Op::Mul => mul(cs.namespace(|| "mul"), &a, &b)?,
+                };
+                Ok((res, acc, transcript))
+            }
+            Body::If(a, b, then, els) => {
+                let (a, acc, transcript) = self.synthesize_body(
+                    &mut cs.namespace(|| "a"),
+                    g,
+                    s,
+                    scope,
+                    arg,
+                    active,
+                    a,
+                    acc,
+                    transcript,
+                )?;
+                let (b, acc, transcript) = self.synthesize_body(
+                    &mut cs.namespace(|| "b"),
+                    g,
+                    s,
+                    scope,
+                    arg,
+                    active,
+                    b,
+                    &acc,
+                    &transcript,
+                )?;
+                let equal = alloc_equal(cs.namespace(|| "equal"), &a, &b)?;
+                let then_active = Boolean::and(cs.namespace(|| "then_active"), active, &equal)?;
+                let else_active =
+                    Boolean::and(cs.namespace(|| "else_active"), active, &equal.not())?;
+                let (then, acc, transcript) = self.synthesize_body(
+                    &mut cs.namespace(|| "then"),
+                    g,
+                    s,
+                    scope,
+                    arg,
+                    &then_active,
+                    then,
+                    &acc,
+                    &transcript,
+                )?;
+                let (els, acc, transcript) = self.synthesize_body(
+                    &mut cs.namespace(|| "else"),
+                    g,
+                    s,
+                    scope,
+                    arg,
+                    &else_active,
+                    els,
+                    &acc,
+                    &transcript,
+                )?;
+                let res = pick(cs.namespace(|| "pick"), &equal, &then, &els)?;
+                Ok((res, acc, transcript))
+            }
+            Body::Query(name, query_arg) => {
+                let (query_arg, acc, transcript) = self.synthesize_body(
+                    &mut cs.namespace(|| "arg"),
+                    g,
+                    s,
+                    scope,
+                    arg,
+                    active,
+                    query_arg,
+                    acc,
+                    transcript,
+                )?;
+                let symbol = g.alloc_ptr(cs, &s.intern_symbol(name), s);
+                let num_tag = g.alloc_tag_cloned(cs, &ExprTag::Num);
+                let key = construct_cons(
+                    &mut cs.namespace(|| "key"),
+                    g,
+                    s,
+                    &symbol,
+                    &AllocatedPtr::from_parts(num_tag, query_arg),
+                )?;
+                let (value, new_acc, new_transcript) = scope.synthesize_internal_query(
+                    &mut cs.namespace(|| "query"),
+                    g,
+                    s,
+                    &key,
+                    &acc,
+                    &transcript,
+                    active,
+                )?;
+                let acc =
+                    AllocatedPtr::pick(&mut cs.namespace(|| "pick acc"), active, &new_acc, &acc)?;
+                let transcript = CircuitTranscript::pick(
+                    &mut cs.namespace(|| "pick transcript"),
+                    active,
+                    &new_transcript,
+                    &transcript,
+                )?;
+                Ok((value.hash().clone(), acc, transcript))
+            }
+        }
+    }
+}
+
+/// `(name . n)`, a query of a `QueryDef`
+#[derive(Debug, Clone)]
+pub struct UserQuery<F> {
+    def: Arc<QueryDef>,
+    arg: Ptr,
+    _p: std::marker::PhantomData<F>,
+}
+
+impl<F> UserQuery<F> {
+    #[inline]
+    pub fn new(def: Arc<QueryDef>, arg: Ptr) -> Self {
+        Self {
+            def,
+            arg,
+            _p: Default::default(),
+        }
+    }
+}
+
+/// The circuit of `UserQuery`
+#[derive(Debug, Clone)]
+pub struct UserCircuitQuery<F: LurkField> {
+    def: Arc<QueryDef>,
+    arg: AllocatedNum<F>,
+    not_dummy: bool,
+}
+
+impl<F: LurkField> Query<F> for UserQuery<F> {
+    type CQ = UserCircuitQuery<F>;
+
+    fn eval(&self, s: &Store<F>, scope: &mut Scope<Self, LogMemo<F>>) -> Ptr {
+        s.num(self.def.eval_body(s, scope, self, &self.def.body))
+    }
+
+    fn symbol(&self) -> Symbol {
+        self.def.name.clone()
+    }
+
+    fn from_ptr(s: &Store<F>, ptr: &Ptr) -> Option<Self> {
+        let (head, arg) = s.try_car_cdr(ptr).ok()?;
+        let def = QueryDef::get(&s.fetch_sym(&head)?)?;
+        arg.has_tag(&Tag::Expr(ExprTag::Num))
+            .then(|| Self::new(def, arg))
+    }
+
+    fn to_ptr(&self, s: &Store<F>) -> Ptr {
+        s.cons(self.symbol_ptr(s), self.arg)
+    }
+
+    fn to_circuit<CS: ConstraintSystem<F>>(&self, cs: &mut CS, s: &Store<F>) -> Self::CQ {
+        UserCircuitQuery {
+            def: self.def.clone(),
+            arg: AllocatedNum::alloc_infallible(cs.namespace(|| "arg"), || {
+                *s.hash_ptr(&self.arg).value()
+            }),
+            not_dummy: true,
+        }
+    }
+
+    fn dummy_from_index(s: &Store<F>, index: usize) -> Self {
+        let definitions = DEFINITIONS.read().expect("poisoned definitions");
+        let (_, def) = definitions.get_index(index).expect("no such query index");
+        Self::new(def.clone(), s.num(F::ZERO))
+    }
+
+    fn index(&self) -> usize {
+        self.def.index()
+    }
+
+    fn count() -> usize {
+        DEFINITIONS.read().expect("poisoned definitions").len()
+    }
+}
+
+impl<F: LurkField> CircuitQuery<F> for UserCircuitQuery<F> {
+    type Ctx = ();
+
+    fn synthesize_eval<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        store: &Store<F>,
+        scope: &mut CircuitScope<F, LogMemoCircuit<F>>,
+        acc: &AllocatedPtr<F>,
+        transcript: &CircuitTranscript<F>,
+    ) -> Result<(AllocatedPtr<F>, AllocatedPtr<F>, CircuitTranscript<F>), SynthesisError> {
+        // Dummies make none of their subqueries, which have no responses to allocate.
+        let (res, acc, transcript) = self.def.synthesize_body(
+            &mut cs.namespace(|| "body"),
+            g,
+            store,
+            scope,
+            &self.arg,
+            &Boolean::Constant(self.not_dummy),
+            &self.def.body,
+            acc,
+            transcript,
+        )?;
+        let num_tag = g.alloc_tag_cloned(cs, &ExprTag::Num);
+        Ok((AllocatedPtr::from_parts(num_tag, res), acc, transcript))
+    }
+
+    fn from_ptr<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, ptr: &Ptr) -> Option<Self> {
+        UserQuery::from_ptr(s, ptr).map(|q| q.to_circuit(cs, s))
+    }
+
+    fn dummy_from_index<CS: ConstraintSystem<F>>(cs: &mut CS, s: &Store<F>, index: usize) -> Self {
+        Self {
+            not_dummy: false,
+            ..UserQuery::dummy_from_index(s, index).to_circuit(cs, s)
+        }
+    }
+
+    fn symbol(&self) -> Symbol {
+        self.def.name.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::{FoldingBackend, MockBackend};
+    use crate::state::user_sym;
+
+    const FIB: &str =
+        "(test-fib (n) (if (= n 0) 0 (if (= n 1) 1 (+ (test-fib (- n 1)) (test-fib (- n 2))))))";
+
+    // Definitions are process-wide, so every one of them is made here.
+    #[test]
+    fn test_user_queries() {
+        let s = &Store::<F>::default();
+        let define = |def| QueryDef::define(s, &s.read_with_default_state(def).unwrap());
+
+        let fib = define(FIB).unwrap();
+        // redefining with the same body is fine, with another one isn't
+        assert!(Arc::ptr_eq(&fib, &define(FIB).unwrap()));
+        assert!(define("(test-fib (n) n)").is_err());
+        // a query can make subqueries of the ones defined before it
+        define("(test-sq-fib (n) (* (test-fib n) (test-fib n)))").unwrap();
+        assert!(define("(test-bad (n) (test-undefined n))").is_err());
+        assert!(define("(test-bad (n) (if n 1 2))").is_err());
+        assert!(define("(test-bad (n m) n)").is_err());
+
+        let query = |name, n| s.cons(s.intern_symbol(&user_sym(name)), s.num_u64(n));
+        let mut scope: Scope<UserQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        assert_eq!(scope.query(s, query("test-fib", 10)), s.num_u64(55));
+        assert_eq!(scope.query(s, query("test-sq-fib", 6)), s.num_u64(64));
+        assert!(UserQuery::<F>::from_ptr(s, &query("test-undefined", 1)).is_none());
+
+        let cs = &mut TestConstraintSystem::new();
+        let g = &mut GlobalAllocator::default();
+        scope.synthesize(cs, g, s).unwrap();
+        assert!(cs.is_satisfied());
+
+        let mut scope: Scope<UserQuery<F>, LogMemo<F>> = Scope::new(false, 2);
+        scope.query(s, query("test-fib", 7));
+        let (proof, z0, zi) = scope.prove_with(s, &MockBackend, &()).unwrap();
+        let inputs = scope.public_inputs(s);
+        assert!(MockBackend
+            .verify_scope(&(), &proof, s, &inputs, &z0, &zi)
+            .unwrap());
+    }
+}
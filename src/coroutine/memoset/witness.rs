@@ -0,0 +1,451 @@
+//! A disk cache of chunk witnesses, so that proving a `Scope` again only synthesizes the chunks it hasn't seen.
+//!
+//! A chunk's witness is determined by its circuit, the keys it proves and the IO it starts from. The IO depends on the
+//! whole transcript, through the challenge `r` and the accumulator and transcript left by earlier chunks, so keying
+//! witnesses by it would miss every chunk as soon as a query is added. Witnesses are therefore keyed by a digest of the
+//! R1CS shape of the chunk circuit of the query index and of the keys only. A cached witness is re-bound to the IO of
+//! the chunk being proved by solving for the variables that depend on the parts of the IO that changed, constraint by
+//! constraint, which costs a pass over the constraints rather than a synthesis. Every witness read, re-bound or not, is
+//! checked to satisfy the shape of its chunk circuit before it's used, and is synthesized again otherwise.
+//!
+//! The cache keeps at most `max_entries` witnesses, evicting the least recently written ones.
+
+use anyhow::{Context, Result};
+use bellpepper::util_cs::witness_cs::WitnessCS;
+use bellpepper_core::{num::AllocatedNum, ConstraintSystem, Index, SynthesisError};
+use camino::{Utf8Path, Utf8PathBuf};
+use rayon::prelude::*;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{hash_map::Entry, HashMap};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::marker::PhantomData;
+use tracing::{info, warn};
+
+use super::{
+    check_chunks_chain,
+    shape::{ChunkShape, ShapeCS, Terms},
+    ChunkSpec, ChunkWitness, CoroutineIO, LogMemo, MemoSet, Query, Scope,
+};
+use crate::circuit::gadgets::pointer::AllocatedPtr;
+use crate::field::LurkField;
+use crate::lem::{store::Store, tag::Tag};
+use crate::public_parameters::disk_cache::public_params_dir;
+use crate::z_ptr::ZPtr;
+
+/// Bump whenever the layout of cached witnesses changes
+const FORMAT_VERSION: u32 = 3;
+
+/// The number of witnesses a cache keeps by default
+const DEFAULT_MAX_ENTRIES: usize = 1024;
+
+/// The witness of a chunk, as cached
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CachedChunkWitness<F: LurkField> {
+    pub z_in: CoroutineIO<ZPtr<Tag, F>>,
+    pub z_out: CoroutineIO<ZPtr<Tag, F>>,
+    /// The auxiliary variables holding `z_in`, a tag and a hash per pointer
+    pub z_in_vars: Vec<usize>,
+    /// The auxiliary variables holding `z_out`, a tag and a hash per pointer
+    pub z_out_vars: Vec<usize>,
+    /// The auxiliary variables of the chunk circuit, in allocation order
+    pub aux: Vec<F>,
+}
+
+impl<F: LurkField> CachedChunkWitness<F> {
+    /// This witness, re-bound to start from `z_in`. The variables depending on the parts of `z_in` that changed are
+    /// solved for in constraint order, each from the first constraint that has it as its latest variable along with a
+    /// variable that changed. Returns `None` if that fails or if the result doesn't satisfy `shape`.
+    fn rebind(&self, shape: &ChunkShape<F>, z_in: &CoroutineIO<ZPtr<Tag, F>>) -> Option<Self> {
+        // the only input of chunk circuits is the constant `one`
+        if shape.num_inputs != 1
+            || self.aux.len() != shape.num_aux
+            || self.z_in_vars.len() != 2 * CoroutineIO::<()>::ARITY
+        {
+            return None;
+        }
+        let mut aux = self.aux.clone();
+        let mut stale = vec![false; aux.len()];
+        for (&var, value) in self.z_in_vars.iter().zip(z_in.to_field_elements()) {
+            let slot = aux.get_mut(var)?;
+            if *slot != value {
+                *slot = value;
+                stale[var] = true;
+            }
+        }
+        for (a, b, c) in &shape.constraints {
+            let vars = || {
+                a.iter()
+                    .chain(b)
+                    .chain(c)
+                    .filter_map(|(index, _)| match index {
+                        Index::Aux(i) => Some(*i),
+                        Index::Input(_) => None,
+                    })
+            };
+            let Some(latest) = vars().max() else {
+                continue;
+            };
+            if !stale[latest] && vars().any(|var| stale[var]) {
+                aux[latest] = solve(a, b, c, &aux, latest)?;
+                stale[latest] = true;
+            }
+        }
+        if !is_satisfied(shape, &aux) {
+            return None;
+        }
+        let z_out = self
+            .z_out_vars
+            .iter()
+            .map(|&var| aux.get(var).copied())
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            z_in: z_in.clone(),
+            z_out: CoroutineIO::from_field_elements(&z_out).ok()?,
+            z_in_vars: self.z_in_vars.clone(),
+            z_out_vars: self.z_out_vars.clone(),
+            aux,
+        })
+    }
+}
+
+/// The value of `terms` under the assignment `aux`, leaving out `var`, along with the coefficient of `var`
+fn split<F: LurkField>(terms: &Terms<F>, aux: &[F], var: usize) -> (F, F) {
+    terms.iter().fold(
+        (F::ZERO, F::ZERO),
+        |(value, coeff), (index, c)| match index {
+            Index::Aux(i) if *i == var => (value, coeff + c.0),
+            Index::Aux(i) => (value + c.0 * aux[*i], coeff),
+            Index::Input(_) => (value + c.0, coeff),
+        },
+    )
+}
+
+/// Solves `a * b = c` for `var`, which must appear in only one of them
+fn solve<F: LurkField>(
+    a: &Terms<F>,
+    b: &Terms<F>,
+    c: &Terms<F>,
+    aux: &[F],
+    var: usize,
+) -> Option<F> {
+    let (a, a_coeff) = split(a, aux, var);
+    let (b, b_coeff) = split(b, aux, var);
+    let (c, c_coeff) = split(c, aux, var);
+    let inv = |f: F| Option::<F>::from(f.invert());
+    match (a_coeff == F::ZERO, b_coeff == F::ZERO, c_coeff == F::ZERO) {
+        (true, true, false) => Some((a * b - c) * inv(c_coeff)?),
+        (false, true, true) => Some((c * inv(b)? - a) * inv(a_coeff)?),
+        (true, false, true) => Some((c * inv(a)? - b) * inv(b_coeff)?),
+        _ => None,
+    }
+}
+
+fn is_satisfied<F: LurkField>(shape: &ChunkShape<F>, aux: &[F]) -> bool {
+    let eval = |terms: &Terms<F>| {
+        terms
+            .iter()
+            .map(|(index, c)| match index {
+                Index::Aux(i) => c.0 * aux[*i],
+                Index::Input(_) => c.0,
+            })
+            .sum::<F>()
+    };
+    shape
+        .constraints
+        .par_iter()
+        .all(|(a, b, c)| eval(a) * eval(b) == eval(c))
+}
+
+/// The index of the auxiliary variable of `num`, if it's one
+fn aux_index<F: LurkField>(num: &AllocatedNum<F>) -> Option<usize> {
+    match num.get_variable().get_unchecked() {
+        Index::Aux(i) => Some(i),
+        Index::Input(_) => None,
+    }
+}
+
+/// A directory of chunk witnesses, one file per key.
+pub struct ChunkWitnessCache<F: LurkField> {
+    dir: Utf8PathBuf,
+    max_entries: usize,
+    _p: PhantomData<F>,
+}
+
+impl<F: LurkField + Serialize + DeserializeOwned> ChunkWitnessCache<F> {
+    pub fn new(dir: &Utf8Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {dir}"))?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            _p: PhantomData,
+        })
+    }
+
+    /// Keeps at most `max_entries` witnesses, rather than `DEFAULT_MAX_ENTRIES`.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// The default location, next to the public parameters.
+    pub fn default_dir() -> Utf8PathBuf {
+        public_params_dir().join("coroutine_witnesses")
+    }
+
+    fn path(&self, key: &str) -> Utf8PathBuf {
+        self.dir.join(format!("{key}.witness"))
+    }
+
+    /// The key of the witness of a chunk proving `keys` with the circuit of digest `circuit_digest`
+    pub fn key(circuit_digest: &[u8], keys: &[ZPtr<Tag, F>]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(FORMAT_VERSION.to_le_bytes());
+        hasher.update(F::FIELD.to_string().as_bytes());
+        hasher.update(circuit_digest);
+        hasher.update((keys.len() as u64).to_le_bytes());
+        for key in keys {
+            hasher.update(key.tag_field().to_bytes());
+            hasher.update(key.value().to_bytes());
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// Reads the witness for `key`, if present. Unreadable files are ignored, to be overwritten.
+    pub fn read(&self, key: &str) -> Option<CachedChunkWitness<F>> {
+        let path = self.path(key);
+        let file = File::open(&path).ok()?;
+        match bincode::deserialize_from(BufReader::new(file)) {
+            Ok(witness) => Some(witness),
+            Err(e) => {
+                warn!("ignoring unreadable witness {path}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Writes the witness for `key` to a temporary file that is then renamed, so concurrent readers never see a
+    /// partially written file.
+    pub fn write(&self, key: &str, witness: &CachedChunkWitness<F>) -> Result<()> {
+        let path = self.path(key);
+        let tmp = self.dir.join(format!("{key}.{}.tmp", std::process::id()));
+        {
+            let file = File::create(&tmp).with_context(|| format!("creating {tmp}"))?;
+            bincode::serialize_into(BufWriter::new(file), witness)?;
+        }
+        std::fs::rename(&tmp, &path).with_context(|| format!("renaming {tmp} to {path}"))?;
+        Ok(())
+    }
+
+    /// Removes the least recently written witnesses beyond `max_entries`. Returns how many were removed.
+    pub fn evict(&self) -> Result<usize> {
+        let mut entries = std::fs::read_dir(&self.dir)
+            .with_context(|| format!("reading {}", self.dir))?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let path = Utf8PathBuf::from_path_buf(entry.path()).ok()?;
+                if path.extension() != Some("witness") {
+                    return None;
+                }
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((modified, path))
+            })
+            .collect::<Vec<_>>();
+        let excess = entries.len().saturating_sub(self.max_entries);
+        entries.sort();
+        for (_, path) in &entries[..excess] {
+            // another process may have removed it already
+            if let Err(e) = std::fs::remove_file(path) {
+                warn!("couldn't evict witness {path}: {e}");
+            }
+        }
+        Ok(excess)
+    }
+}
+
+impl<F: LurkField + Serialize + DeserializeOwned, Q: Query<F> + Send + Sync> Scope<Q, LogMemo<F>> {
+    /// The R1CS shape of the chunk circuit of `query_index`
+    fn chunk_shape(&self, s: &Store<F>, query_index: usize) -> Result<ChunkShape<F>> {
+        let spec = ChunkSpec {
+            query_index,
+            chunk_index: 0,
+            keys: vec![],
+            rc: self.rc_for_query(query_index),
+            acc: F::ZERO,
+            transcript: s.intern_nil(),
+        };
+        let mut cs = ShapeCS::new();
+        self.synthesize_chunk(&mut cs, s, &spec)?;
+        Ok(cs.shape)
+    }
+
+    /// Like `synthesize_chunks_parallel`, but reuses the witnesses of chunks found in `cache`, which is updated with
+    /// the chunks that had to be synthesized or re-bound. Returns the chunks along with how many were reused.
+    pub fn synthesize_chunks_cached(
+        &mut self,
+        s: &Store<F>,
+        cache: &ChunkWitnessCache<F>,
+    ) -> Result<(Vec<ChunkWitness<F>>, usize)> {
+        self.ensure_transcript_finalized(s);
+        let r = *self.memoset.r().expect("transcript not finalized");
+
+        let scope: &Self = self;
+        let specs = scope.chunk_specs(s);
+        let mut shapes = HashMap::new();
+        for spec in &specs {
+            if let Entry::Vacant(entry) = shapes.entry(spec.query_index) {
+                let shape = scope.chunk_shape(s, spec.query_index)?;
+                let digest = Sha256::digest(bincode::serialize(&shape)?).to_vec();
+                entry.insert((shape, digest));
+            }
+        }
+
+        let chunks = specs
+            .into_par_iter()
+            .map(|spec| -> Result<(ChunkWitness<F>, bool)> {
+                let (shape, digest) = &shapes[&spec.query_index];
+                let keys = spec
+                    .keys
+                    .iter()
+                    .map(|key| s.hash_ptr(key))
+                    .collect::<Vec<_>>();
+                let key = ChunkWitnessCache::key(digest, &keys);
+                let z_in = scope.chunk_z_in(s, &spec);
+                let chunk = |z_out, witness| ChunkWitness {
+                    query_index: spec.query_index,
+                    chunk_index: spec.chunk_index,
+                    z_in: z_in.clone(),
+                    z_out,
+                    witness,
+                };
+
+                if let Some(cached) = cache.read(&key) {
+                    match cached.rebind(shape, &z_in) {
+                        Some(rebound) => {
+                            if rebound.z_in != cached.z_in {
+                                cache.write(&key, &rebound)?;
+                            }
+                            let mut witness = WitnessCS::new();
+                            witness.extend_aux(&rebound.aux);
+                            return Ok((chunk(rebound.z_out, witness), true));
+                        }
+                        None => warn!("ignoring witness {key}, which doesn't satisfy its chunk"),
+                    }
+                }
+
+                let mut witness = WitnessCS::new();
+                let (z_in_vars, z_out_vars) =
+                    scope.synthesize_chunk_allocated(&mut witness, s, &spec)?;
+                let z_out = z_out_vars
+                    .get_value()
+                    .ok_or(SynthesisError::AssignmentMissing)?;
+                let vars = |z: CoroutineIO<AllocatedPtr<F>>| -> Option<Vec<usize>> {
+                    z.into_nums().iter().map(aux_index).collect()
+                };
+                if let (Some(z_in_vars), Some(z_out_vars)) = (vars(z_in_vars), vars(z_out_vars)) {
+                    let cached = CachedChunkWitness {
+                        z_in: z_in.clone(),
+                        z_out: z_out.clone(),
+                        z_in_vars,
+                        z_out_vars,
+                        aux: witness.aux_slice().to_vec(),
+                    };
+                    cache.write(&key, &cached)?;
+                }
+                Ok((chunk(z_out, witness), false))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let reused = chunks.iter().filter(|(_, reused)| *reused).count();
+        let chunks = chunks
+            .into_iter()
+            .map(|(chunk, _)| chunk)
+            .collect::<Vec<_>>();
+        check_chunks_chain(&chunks, r, scope.deferred_acc(s))
+            .context("chunk witnesses don't chain together")?;
+        let evicted = cache.evict()?;
+        info!(
+            "reused {reused} of {} chunk witnesses, evicted {evicted}",
+            chunks.len()
+        );
+        Ok((chunks, reused))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use halo2curves::bn256::Fr as F;
+
+    use super::*;
+    use crate::coroutine::memoset::demo::DemoQuery;
+
+    fn scope(s: &Store<F>, ns: &[u64]) -> Scope<DemoQuery<F>, LogMemo<F>> {
+        let mut scope = Scope::new(false, 2);
+        for n in ns {
+            let query = s
+                .read_with_default_state(&format!("(factorial . {n})"))
+                .unwrap();
+            scope.query(s, query);
+        }
+        scope
+    }
+
+    #[test]
+    fn test_chunk_witness_cache() {
+        let s = &Store::<F>::default();
+        let tmp_dir = tempfile::Builder::new().prefix("tmp").tempdir().unwrap();
+        let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+        let cache = ChunkWitnessCache::new(tmp_dir).unwrap();
+
+        let (first, reused) = scope(s, &[3, 5])
+            .synthesize_chunks_cached(s, &cache)
+            .unwrap();
+        assert_eq!(0, reused);
+        assert_eq!(3, first.len());
+
+        // A later session with the same queries synthesizes nothing.
+        let (second, reused) = scope(s, &[3, 5])
+            .synthesize_chunks_cached(s, &cache)
+            .unwrap();
+        assert_eq!(3, reused);
+        let parallel = scope(s, &[3, 5]).synthesize_chunks_parallel(s).unwrap();
+        for ((a, b), c) in first.iter().zip(&second).zip(&parallel) {
+            assert_eq!(a.z_out, b.z_out);
+            assert_eq!(a.witness.aux_slice(), b.witness.aux_slice());
+            assert_eq!(c.witness.aux_slice(), b.witness.aux_slice());
+        }
+
+        // Other queries change the transcript, and hence the IO of every chunk, but the chunks proving the same keys
+        // are re-bound to it.
+        let (third, reused) = scope(s, &[3, 4])
+            .synthesize_chunks_cached(s, &cache)
+            .unwrap();
+        assert!(reused > 0);
+        let parallel = scope(s, &[3, 4]).synthesize_chunks_parallel(s).unwrap();
+        assert_eq!(parallel.len(), third.len());
+        for (a, b) in third.iter().zip(&parallel) {
+            assert_eq!(a.z_in, b.z_in);
+            assert_eq!(a.z_out, b.z_out);
+        }
+
+        // A witness that doesn't satisfy its chunk circuit is synthesized again.
+        let mut entries = std::fs::read_dir(tmp_dir).unwrap();
+        let path = entries.next().unwrap().unwrap().path();
+        let mut witness: CachedChunkWitness<F> =
+            bincode::deserialize(&std::fs::read(&path).unwrap()).unwrap();
+        witness.aux.iter_mut().for_each(|f| *f += F::ONE);
+        std::fs::write(&path, bincode::serialize(&witness).unwrap()).unwrap();
+        let (fourth, _) = scope(s, &[3, 4])
+            .synthesize_chunks_cached(s, &cache)
+            .unwrap();
+        for (a, b) in fourth.iter().zip(&parallel) {
+            assert_eq!(a.z_out, b.z_out);
+        }
+
+        // Older witnesses are evicted beyond the bound.
+        let cache = ChunkWitnessCache::new(tmp_dir).unwrap().with_max_entries(1);
+        cache.evict().unwrap();
+        assert_eq!(1, std::fs::read_dir(tmp_dir).unwrap().count());
+    }
+}
@@ -1 +1,2 @@
 pub mod memoset;
+pub mod prelude;
@@ -0,0 +1,35 @@
+//! The types needed to define queries and prove them with a `Scope`, re-exported in one place.
+//!
+//! Everything re-exported here is part of the stable API of the coroutine crate: it follows semver, so removing or
+//! changing any of it is a breaking change. Items only reachable through `coroutine::memoset` may still change between
+//! minor versions.
+//!
+//! A typical user implements `Query` and `CircuitQuery` for their queries, plus `RecursiveQuery` for those making
+//! subqueries of their own kind, makes them through `Scope::query` during evaluation, and then proves the scope with
+//! one of the `FoldingBackend`s. With the `examples` feature, `DemoQuery` is a minimal query to start from.
+
+// Defining queries
+pub use super::memoset::{
+    check_query_version, versioned_query_body, versioned_symbol, CircuitQuery, Query,
+    QueryVersionError, RecursiveQuery,
+};
+// Their circuits
+pub use super::memoset::{CircuitScope, CircuitTranscript, CoroutineCircuit, LogMemoCircuit};
+pub use crate::circuit::gadgets::pointer::AllocatedPtr;
+pub use crate::lem::circuit::GlobalAllocator;
+pub use crate::lem::{pointers::Ptr, store::Store};
+pub use crate::Symbol;
+
+// Making queries
+pub use super::memoset::{ConsistencyIssue, ConsistencyReport};
+pub use super::memoset::{LogMemo, MemoSet, Scope, ScopeStats};
+
+// Proving them
+pub use super::memoset::{
+    ChunkCircuit, ChunkWitness, CoroutineIO, FoldingBackend, MockBackend, NovaBackend,
+    SuperNovaBackend,
+};
+pub use super::memoset::{CompressedProof, CoroutineProof, CoroutinePublicInputs};
+
+#[cfg(feature = "examples")]
+pub use super::memoset::{DemoCircuitQuery, DemoQuery};
@@ -15,6 +15,8 @@ pub enum ProofError {
     Synthesis(#[from] SynthesisError),
     #[error("Reduction error: {0}")]
     Reduction(#[from] ReductionError),
+    #[error("Proving was cancelled")]
+    Cancelled,
 }
 
 impl From<store::Error> for ProofError {
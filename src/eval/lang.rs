@@ -1,3 +1,4 @@
+use bellpepper_core::{boolean::Boolean, ConstraintSystem, SynthesisError};
 use indexmap::IndexMap;
 use lurk_macros::Coproc;
 use serde::{Deserialize, Serialize};
@@ -5,9 +6,10 @@ use std::{fmt::Debug, marker::PhantomData};
 
 use crate::{
     self as lurk,
-    coprocessor::{CoCircuit, Coprocessor},
+    circuit::gadgets::pointer::AllocatedPtr,
+    coprocessor::{batch::BatchCoprocessor, CoCircuit, Coprocessor},
     field::LurkField,
-    lem::{pointers::Ptr, store::Store},
+    lem::{circuit::GlobalAllocator, pointers::Ptr, store::Store},
     symbol::Symbol,
 };
 
@@ -76,6 +78,12 @@ pub enum Coproc<F: LurkField> {
 pub struct Lang<F, C = Coproc<F>> {
     /// An IndexMap that stores coprocessors with their associated `Sym` keys.
     coprocessors: IndexMap<Symbol, C>,
+    /// How many calls the batched version of each batchable coprocessor takes, or 0 if they aren't batched.
+    #[serde(default)]
+    batch_size: usize,
+    /// Whether the step function counts its iterations, as `set_metered` describes.
+    #[serde(default)]
+    metered: bool,
     _p: PhantomData<F>,
 }
 
@@ -84,6 +92,8 @@ impl<F: LurkField, C> Lang<F, C> {
     pub fn new() -> Self {
         Self {
             coprocessors: IndexMap::default(),
+            batch_size: 0,
+            metered: false,
             _p: PhantomData,
         }
     }
@@ -104,12 +114,42 @@ impl<F: LurkField, C> Lang<F, C> {
                 let name = coprocessor.0.path().join("-");
                 key += name.as_str()
             }
+            if self.batch_size > 0 {
+                key += &format!("-batch-{}", self.batch_size);
+            }
         } else {
             key += "none"
         }
+        if self.metered {
+            key += "-metered";
+        }
         key
     }
 
+    /// Batches calls to the batchable coprocessors by up to `batch_size`. The batched version of the coprocessor
+    /// named `name` is named `name.batch` and takes a list of calls, each a list of arguments, as a
+    /// `BatchCoprocessor` does. A `batch_size` of 0 doesn't batch them.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size;
+    }
+
+    #[inline]
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Makes the step function count the iterations of the evaluation in a fourth input and output, after the
+    /// expression, environment and continuation. The count starts at 0, so the final one is the exact number of
+    /// iterations proved, and `(current-step)` evaluates to the number of iterations taken before it's reduced.
+    pub fn set_metered(&mut self, metered: bool) {
+        self.metered = metered;
+    }
+
+    #[inline]
+    pub fn is_metered(&self) -> bool {
+        self.metered
+    }
+
     pub fn add_coprocessor<T: Into<C>, S: Into<Symbol>>(&mut self, name: S, cproc: T) {
         let name = name.into();
         self.coprocessors.insert(name, cproc.into());
@@ -137,17 +177,89 @@ impl<F: LurkField, C> Lang<F, C> {
 
     #[inline]
     pub fn is_default(&self) -> bool {
-        !self.has_coprocessors()
+        !self.has_coprocessors() && !self.metered
+    }
+}
+
+/// A coprocessor a `Lang` can call: one of its own, or the batched version of a batchable one
+pub enum LangCoproc<'a, F: LurkField, C> {
+    Single(&'a C),
+    Batched(BatchCoprocessor<F, C>),
+}
+
+impl<'a, F: LurkField, C: Coprocessor<F>> LangCoproc<'a, F, C> {
+    pub fn has_circuit(&self) -> bool {
+        match self {
+            Self::Single(c) => c.has_circuit(),
+            Self::Batched(c) => c.has_circuit(),
+        }
+    }
+
+    pub fn evaluate_internal(&self, s: &Store<F>, ptrs: &[Ptr]) -> Vec<Ptr> {
+        match self {
+            Self::Single(c) => c.evaluate_internal(s, ptrs),
+            Self::Batched(c) => c.evaluate_internal(s, ptrs),
+        }
+    }
+
+    pub fn synthesize_internal<CS: ConstraintSystem<F>>(
+        &self,
+        cs: &mut CS,
+        g: &GlobalAllocator<F>,
+        s: &Store<F>,
+        not_dummy: &Boolean,
+        ptrs: &[AllocatedPtr<F>],
+    ) -> Result<Vec<AllocatedPtr<F>>, SynthesisError> {
+        match self {
+            Self::Single(c) => c.synthesize_internal(cs, g, s, not_dummy, ptrs),
+            Self::Batched(c) => c.synthesize_internal(cs, g, s, not_dummy, ptrs),
+        }
+    }
+}
+
+impl<F: LurkField, C: Coprocessor<F>> Lang<F, C> {
+    /// The names of the batched versions of the batchable coprocessors, in the order of the coprocessors
+    fn batched(&self) -> impl Iterator<Item = (Symbol, &C)> {
+        self.coprocessors
+            .iter()
+            .filter(|(_, c)| self.batch_size > 0 && c.batchable())
+            .map(|(name, c)| (name.direct_child("batch"), c))
+    }
+
+    /// The names of the coprocessors the `Lang` can call, the batched ones last, along with their arities
+    pub fn callable(&self) -> Vec<(Symbol, usize)> {
+        let batched = self.batched().map(|(name, _)| (name, 1));
+        self.coprocessors
+            .iter()
+            .map(|(name, c)| (name.clone(), c.arity()))
+            .chain(batched)
+            .collect()
+    }
+
+    /// The coprocessor named `sym`, which may be the batched version of a batchable one
+    pub fn lookup_callable(&self, sym: &Symbol) -> Option<LangCoproc<'_, F, C>> {
+        if let Some(c) = self.coprocessors.get(sym) {
+            return Some(LangCoproc::Single(c));
+        }
+        self.batched()
+            .find(|(name, _)| name == sym)
+            .map(|(_, c)| LangCoproc::Batched(BatchCoprocessor::new(c.clone(), self.batch_size)))
     }
 
+    /// The number of coprocessors the `Lang` can call, batched ones included
     #[inline]
     pub fn coprocessor_count(&self) -> usize {
-        self.coprocessors.len()
+        self.coprocessors.len() + self.batched().count()
     }
 
+    /// The index of `sym` among the coprocessors the `Lang` can call, as in `callable`
     #[inline]
     pub fn get_index_by_symbol(&self, sym: &Symbol) -> Option<usize> {
-        self.coprocessors.get_index_of(sym)
+        self.coprocessors.get_index_of(sym).or_else(|| {
+            self.batched()
+                .position(|(name, _)| &name == sym)
+                .map(|i| self.coprocessors.len() + i)
+        })
     }
 }
 
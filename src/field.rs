@@ -90,6 +90,26 @@ pub trait LurkField: PrimeField + PrimeFieldBits {
         s
     }
 
+    /// Parses the output of `hex_digits`, with or without a `0x` prefix
+    fn from_hex_digits(s: &str) -> Option<Self> {
+        let mut bytes = hex::decode(s.strip_prefix("0x").unwrap_or(s)).ok()?;
+        if bytes.len() != Self::default().to_repr().as_ref().len() {
+            return None;
+        }
+        bytes.reverse();
+        Self::from_bytes(&bytes)
+    }
+
+    /// Interprets `bs` as a little-endian integer and reduces it modulo the
+    /// order of the field. Unlike `from_bytes`, this never fails, so it can turn
+    /// arbitrary randomness (e.g. 32-byte salts) into field elements
+    fn from_le_bytes_reduced(bs: &[u8]) -> Self {
+        let base = Self::from_u64(256);
+        bs.iter().rev().fold(Self::ZERO, |acc, b| {
+            acc * base + Self::from_u64(u64::from(*b))
+        })
+    }
+
     /// Converts the field to a variable-length hex string
     fn trimmed_hex_digits(self) -> String {
         let hex_digits = self.hex_digits();
@@ -435,6 +455,19 @@ pub mod tests {
           assert_eq!(x, tag)
       }
 
+      #[test]
+      fn prop_hex_digits_roundtrip(x in any::<FWrap<Fr>>()) {
+          assert_eq!(Fr::from_hex_digits(&x.0.hex_digits()), Some(x.0));
+          assert_eq!(Fr::from_hex_digits(&format!("0x{}", x.0.hex_digits())), Some(x.0));
+      }
+
+      #[test]
+      fn prop_le_bytes_reduced(x in any::<FWrap<Fr>>(), salt in any::<[u8; 32]>()) {
+          // canonical bytes are already reduced
+          assert_eq!(Fr::from_le_bytes_reduced(&x.0.to_bytes()), x.0);
+          assert_eq!(Fr::from_le_bytes_reduced(&salt), from_le_bytes_canonical::<Fr>(&salt));
+      }
+
       #[test]
       fn prop_ser_de(x in any::<FWrap<Fr>>()) {
             let bytes = to_z_data(x).unwrap();
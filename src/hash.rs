@@ -1,7 +1,9 @@
 use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use crate::field::{FWrap, LurkField};
+use crate::telemetry::HASHES;
 use elsa::{sync::index_map::FrozenIndexMap, sync::FrozenMap};
 
 use generic_array::typenum::{U3, U4, U6, U8};
@@ -89,11 +91,24 @@ pub struct PoseidonCache<F: LurkField> {
     a4: Arc<FrozenMap<CacheKey<F, 4>, F>>,
     a6: Arc<FrozenMap<CacheKey<F, 6>, F>>,
     a8: Arc<FrozenMap<CacheKey<F, 8>, F>>,
+    /// Hashes computed since the last `record_hashes`, by arity 3, 4, 6 and 8
+    computed: Arc<[AtomicU64; 4]>,
 
     pub constants: HashConstants<F>,
 }
 
 impl<F: LurkField> PoseidonCache<F> {
+    /// Adds the hashes computed since the last call to the `lurk_hashes_total` counter. Hashing only bumps an atomic,
+    /// so that the metrics recorder stays out of the hot path.
+    pub fn record_hashes(&self) {
+        for (computed, arity) in self.computed.iter().zip(["3", "4", "6", "8"]) {
+            let count = computed.swap(0, Ordering::Relaxed);
+            if count > 0 {
+                metrics::counter!(HASHES, "arity" => arity).increment(count);
+            }
+        }
+    }
+
     pub fn compute_hash<const ARITY: usize>(&self, preimage: [F; ARITY]) -> F {
         macro_rules! hash {
             ($hash_name:ident, $n:expr) => {{
@@ -184,24 +199,28 @@ impl<F: LurkField, const N: usize> Hash for CacheKey<F, N> {
 impl<F: LurkField> PoseidonCache<F> {
     pub fn hash3(&self, preimage: &[F; 3]) -> F {
         self.a3.get_copy_or_insert_with(CacheKey(*preimage), || {
+            self.computed[0].fetch_add(1, Ordering::Relaxed);
             Poseidon::new_with_preimage(preimage, self.constants.c3()).hash()
         })
     }
 
     pub fn hash4(&self, preimage: &[F; 4]) -> F {
         self.a4.get_copy_or_insert_with(CacheKey(*preimage), || {
+            self.computed[1].fetch_add(1, Ordering::Relaxed);
             Poseidon::new_with_preimage(preimage, self.constants.c4()).hash()
         })
     }
 
     pub fn hash6(&self, preimage: &[F; 6]) -> F {
         self.a6.get_copy_or_insert_with(CacheKey(*preimage), || {
+            self.computed[2].fetch_add(1, Ordering::Relaxed);
             Poseidon::new_with_preimage(preimage, self.constants.c6()).hash()
         })
     }
 
     pub fn hash8(&self, preimage: &[F; 8]) -> F {
         self.a8.get_copy_or_insert_with(CacheKey(*preimage), || {
+            self.computed[3].fetch_add(1, Ordering::Relaxed);
             Poseidon::new_with_preimage(preimage, self.constants.c8()).hash()
         })
     }
@@ -0,0 +1,440 @@
+//! The append-only tables where a `Store` interns field elements and tuples of
+//! `RawPtr`s.
+//!
+//! A table either lives in memory or in a memory-mapped file, which is what
+//! lets a `Store` persist across runs (see `Store::open`). A mapped table
+//! `<name>` is made of two files:
+//! * `<name>.data` has a header (a magic number, the field of the store, the
+//!   size of the entries and their count) followed by the fixed-size encodings of the entries, in the
+//!   order they were interned. Thus the index of an entry is its position;
+//! * `<name>.idx` is an open-addressing hash index from encodings to positions.
+//!   It's derived from the data file and is rebuilt when it's missing or out of
+//!   sync, e.g. after a crash.
+//!
+//! Entries of a mapped table are decoded on their first access and cached, so
+//! a run only keeps in memory the data it actually touches. A mapped table must
+//! not be opened by more than one process at a time.
+
+use anyhow::{bail, ensure, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use elsa::sync::{index_set::FrozenIndexSet, FrozenMap};
+use memmap2::MmapMut;
+use std::{
+    fs::{File, OpenOptions},
+    hash::Hash,
+    sync::RwLock,
+};
+
+use crate::field::{FWrap, LanguageField, LurkField};
+
+use super::pointers::RawPtr;
+
+/// Values with a fixed-size binary encoding
+pub(crate) trait Record: Sized {
+    /// The size of every encoding, in bytes
+    fn size() -> usize;
+    fn encode(&self, buf: &mut [u8]);
+    fn decode(buf: &[u8]) -> Option<Self>;
+}
+
+impl<F: LurkField> Record for FWrap<F> {
+    fn size() -> usize {
+        F::ZERO.to_bytes().len()
+    }
+
+    fn encode(&self, buf: &mut [u8]) {
+        buf.copy_from_slice(&self.0.to_bytes())
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        F::from_bytes(buf).map(FWrap)
+    }
+}
+
+/// Each `RawPtr` is a little-endian `u64` whose lowest two bits tell its kind
+/// and whose remaining bits hold its index
+impl<const N: usize> Record for [RawPtr; N] {
+    fn size() -> usize {
+        8 * N
+    }
+
+    fn encode(&self, buf: &mut [u8]) {
+        for (ptr, chunk) in self.iter().zip(buf.chunks_mut(8)) {
+            let (kind, idx) = match ptr {
+                RawPtr::Atom(idx) => (0, idx),
+                RawPtr::Hash4(idx) => (1, idx),
+                RawPtr::Hash6(idx) => (2, idx),
+                RawPtr::Hash8(idx) => (3, idx),
+            };
+            chunk.copy_from_slice(&((*idx as u64) << 2 | kind).to_le_bytes());
+        }
+    }
+
+    fn decode(buf: &[u8]) -> Option<Self> {
+        let mut ptrs = [RawPtr::Atom(0); N];
+        for (ptr, chunk) in ptrs.iter_mut().zip(buf.chunks(8)) {
+            let word = u64::from_le_bytes(chunk.try_into().ok()?);
+            let idx = usize::try_from(word >> 2).ok()?;
+            *ptr = match word & 3 {
+                0 => RawPtr::Atom(idx),
+                1 => RawPtr::Hash4(idx),
+                2 => RawPtr::Hash6(idx),
+                _ => RawPtr::Hash8(idx),
+            };
+        }
+        Some(ptrs)
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum Arena<T> {
+    Memory(FrozenIndexSet<Box<T>>),
+    Mapped(MappedArena<T>),
+}
+
+impl<T: Eq + Hash> Default for Arena<T> {
+    fn default() -> Self {
+        Self::Memory(FrozenIndexSet::default())
+    }
+}
+
+impl<T: Record + Eq + Hash> Arena<T> {
+    /// Interns `value`, returning its index and whether it's new
+    #[inline]
+    pub(crate) fn insert_probe(&self, value: T) -> (usize, bool) {
+        match self {
+            Self::Memory(set) => set.insert_probe(Box::new(value)),
+            Self::Mapped(arena) => arena.insert_probe(value),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get_index(&self, idx: usize) -> Option<&T> {
+        match self {
+            Self::Memory(set) => set.get_index(idx),
+            Self::Mapped(arena) => arena.get_index(idx),
+        }
+    }
+
+    /// Writes pending changes of a mapped table to disk
+    pub(crate) fn flush(&self) -> Result<()> {
+        match self {
+            Self::Memory(..) => Ok(()),
+            Self::Mapped(arena) => arena.flush(),
+        }
+    }
+}
+
+const MAGIC: &[u8; 8] = b"LURKTBL2";
+/// Magic number, field, entry size and entry count
+const DATA_HEADER_SIZE: usize = 32;
+/// The entry count the index was built for
+const INDEX_HEADER_SIZE: usize = 8;
+const MIN_CAPACITY: usize = 1024;
+
+#[inline]
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().expect("8 bytes"))
+}
+
+/// How the field of the store is recorded in the header
+fn field_code(field: LanguageField) -> u64 {
+    match field {
+        LanguageField::BN256 => 1,
+        LanguageField::Grumpkin => 2,
+        LanguageField::Pallas => 3,
+        LanguageField::Vesta => 4,
+    }
+}
+
+/// FNV-1a, which is stable across platforms and Rust versions, unlike `std`'s
+/// hashers
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// The index capacity for `len` entries, keeping the load factor at most 1/2
+#[inline]
+fn index_capacity(len: usize) -> usize {
+    (2 * len).next_power_of_two().max(MIN_CAPACITY)
+}
+
+fn open_file(path: &Utf8Path) -> Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)
+        .with_context(|| format!("opening {path}"))
+}
+
+/// Resizes `file` to `len` bytes and maps it
+fn map(file: &File, len: usize) -> Result<MmapMut> {
+    file.set_len(len as u64)?;
+    // Safety: the file is only meant to be modified through this mapping
+    Ok(unsafe { MmapMut::map_mut(file) }?)
+}
+
+#[derive(Debug)]
+struct Files {
+    data_file: File,
+    data: MmapMut,
+    index_file: File,
+    index: MmapMut,
+    len: usize,
+    size: usize,
+}
+
+impl Files {
+    #[inline]
+    fn record(&self, idx: usize) -> &[u8] {
+        let start = DATA_HEADER_SIZE + idx * self.size;
+        &self.data[start..start + self.size]
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        (self.index.len() - INDEX_HEADER_SIZE) / 8
+    }
+
+    #[inline]
+    fn slot(&self, i: usize) -> u64 {
+        read_u64(&self.index[INDEX_HEADER_SIZE + 8 * i..])
+    }
+
+    #[inline]
+    fn set_slot(&mut self, i: usize, entry: u64) {
+        let start = INDEX_HEADER_SIZE + 8 * i;
+        self.index[start..start + 8].copy_from_slice(&entry.to_le_bytes());
+    }
+
+    /// The index of the entry encoded as `bytes`, or the free slot where it
+    /// should be indexed. Slots hold indices plus one, so zero means free.
+    fn probe(&self, bytes: &[u8]) -> Result<usize, usize> {
+        let mask = self.capacity() - 1;
+        let mut i = fnv1a(bytes) as usize & mask;
+        loop {
+            match self.slot(i) {
+                0 => return Err(i),
+                entry => {
+                    let idx = entry as usize - 1;
+                    if self.record(idx) == bytes {
+                        return Ok(idx);
+                    }
+                }
+            }
+            i = (i + 1) & mask;
+        }
+    }
+
+    fn index_is_valid(&self) -> bool {
+        self.index.len() >= INDEX_HEADER_SIZE + 8 * index_capacity(self.len)
+            && self.capacity().is_power_of_two()
+            && read_u64(&self.index) == self.len as u64
+    }
+
+    fn rebuild_index(&mut self, capacity: usize) -> Result<()> {
+        self.index = map(&self.index_file, INDEX_HEADER_SIZE + 8 * capacity)?;
+        self.index.fill(0);
+        for idx in 0..self.len {
+            match self.probe(self.record(idx)) {
+                Err(slot) => self.set_slot(slot, idx as u64 + 1),
+                Ok(_) => bail!("duplicate entry {idx}"),
+            }
+        }
+        self.index[..8].copy_from_slice(&(self.len as u64).to_le_bytes());
+        Ok(())
+    }
+
+    fn push(&mut self, bytes: &[u8]) -> Result<usize> {
+        let idx = self.len;
+        let end = DATA_HEADER_SIZE + (idx + 1) * self.size;
+        if end > self.data.len() {
+            self.data = map(
+                &self.data_file,
+                DATA_HEADER_SIZE + 2 * (idx + 1) * self.size,
+            )?;
+        }
+        self.data[end - self.size..end].copy_from_slice(bytes);
+        // the entry is only visible after its content is written
+        self.len += 1;
+        self.data[24..32].copy_from_slice(&(self.len as u64).to_le_bytes());
+
+        if 2 * self.len > self.capacity() {
+            self.rebuild_index(2 * self.capacity())?;
+        } else {
+            let slot = self.probe(bytes).expect_err("the entry is new");
+            self.set_slot(slot, idx as u64 + 1);
+            self.index[..8].copy_from_slice(&(self.len as u64).to_le_bytes());
+        }
+        Ok(idx)
+    }
+}
+
+/// A table backed by memory-mapped files
+#[derive(Debug)]
+pub(crate) struct MappedArena<T> {
+    path: Utf8PathBuf,
+    files: RwLock<Files>,
+    decoded: FrozenMap<usize, Box<T>>,
+}
+
+impl<T: Record> MappedArena<T> {
+    /// Opens the table `name` of a store over `field` in the directory `dir`,
+    /// creating it if needed
+    pub(crate) fn open(dir: &Utf8Path, name: &str, field: LanguageField) -> Result<Self> {
+        let size = T::size();
+        let data_path = dir.join(format!("{name}.data"));
+        let data_file = open_file(&data_path)?;
+        if data_file.metadata()?.len() == 0 {
+            let mut data = map(&data_file, DATA_HEADER_SIZE + MIN_CAPACITY * size)?;
+            data[..8].copy_from_slice(MAGIC);
+            data[8..16].copy_from_slice(&field_code(field).to_le_bytes());
+            data[16..24].copy_from_slice(&(size as u64).to_le_bytes());
+            data.flush()?;
+        }
+        // Safety: the file is only meant to be modified through this mapping
+        let data = unsafe { MmapMut::map_mut(&data_file) }?;
+        ensure!(
+            data.len() >= DATA_HEADER_SIZE && &data[..8] == MAGIC,
+            "{data_path} isn't a Lurk store table"
+        );
+        ensure!(
+            read_u64(&data[8..]) == field_code(field),
+            "{data_path} belongs to a store over another field than {field}"
+        );
+        ensure!(
+            read_u64(&data[16..]) == size as u64,
+            "{data_path} has entries of {} bytes, but {size} were expected",
+            read_u64(&data[16..])
+        );
+        let len = read_u64(&data[24..]) as usize;
+        ensure!(
+            DATA_HEADER_SIZE + len * size <= data.len(),
+            "{data_path} is truncated"
+        );
+
+        let index_path = dir.join(format!("{name}.idx"));
+        let index_file = open_file(&index_path)?;
+        let index_len = index_file.metadata()?.len() as usize;
+        let index = map(&index_file, index_len.max(INDEX_HEADER_SIZE))?;
+        let mut files = Files {
+            data_file,
+            data,
+            index_file,
+            index,
+            len,
+            size,
+        };
+        if !files.index_is_valid() {
+            files
+                .rebuild_index(index_capacity(len))
+                .with_context(|| format!("rebuilding {index_path}"))?;
+        }
+
+        Ok(Self {
+            path: data_path,
+            files: RwLock::new(files),
+            decoded: FrozenMap::default(),
+        })
+    }
+
+    /// Like `Arena::insert_probe`. I/O errors while growing the files are
+    /// unrecoverable, much like running out of memory.
+    fn insert_probe(&self, value: T) -> (usize, bool) {
+        let mut bytes = vec![0; T::size()];
+        value.encode(&mut bytes);
+        if let Ok(idx) = self.files.read().unwrap().probe(&bytes) {
+            return (idx, false);
+        }
+        let mut files = self.files.write().unwrap();
+        // another thread may have interned the same value in the meantime
+        if let Ok(idx) = files.probe(&bytes) {
+            return (idx, false);
+        }
+        let idx = files
+            .push(&bytes)
+            .unwrap_or_else(|e| panic!("failed to grow {}: {e}", self.path));
+        (idx, true)
+    }
+
+    fn get_index(&self, idx: usize) -> Option<&T> {
+        if let Some(value) = self.decoded.get(&idx) {
+            return Some(value);
+        }
+        let value = {
+            let files = self.files.read().unwrap();
+            if idx >= files.len {
+                return None;
+            }
+            T::decode(files.record(idx))?
+        };
+        Some(self.decoded.insert(idx, Box::new(value)))
+    }
+
+    fn flush(&self) -> Result<()> {
+        let files = self.files.read().unwrap();
+        files.data.flush()?;
+        files.index.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+
+    #[test]
+    fn test_record_roundtrip() {
+        let ptrs = [
+            RawPtr::Atom(0),
+            RawPtr::Hash4(1),
+            RawPtr::Hash6(1 << 40),
+            RawPtr::Hash8(7),
+        ];
+        let mut buf = vec![0; <[RawPtr; 4]>::size()];
+        ptrs.encode(&mut buf);
+        assert_eq!(Some(ptrs), <[RawPtr; 4]>::decode(&buf));
+
+        let f = FWrap(Fr::from(42));
+        let mut buf = vec![0; FWrap::<Fr>::size()];
+        f.encode(&mut buf);
+        assert_eq!(Some(f), FWrap::<Fr>::decode(&buf));
+    }
+
+    #[test]
+    fn test_mapped_arena() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+        let n = 3 * MIN_CAPACITY;
+        let ptrs = |i: usize| [RawPtr::Atom(i), RawPtr::Hash4(i + 1)];
+        {
+            let arena = MappedArena::<[RawPtr; 2]>::open(dir, "test", Fr::FIELD).unwrap();
+            for i in 0..n {
+                assert_eq!((i, true), arena.insert_probe(ptrs(i)));
+            }
+            assert_eq!((5, false), arena.insert_probe(ptrs(5)));
+            arena.flush().unwrap();
+        }
+
+        // a lost index is rebuilt
+        std::fs::remove_file(dir.join("test.idx")).unwrap();
+        let arena = MappedArena::<[RawPtr; 2]>::open(dir, "test", Fr::FIELD).unwrap();
+        assert_eq!(Some(&ptrs(n - 1)), arena.get_index(n - 1));
+        assert_eq!(None, arena.get_index(n));
+        assert_eq!((n - 1, false), arena.insert_probe(ptrs(n - 1)));
+        assert_eq!((n, true), arena.insert_probe(ptrs(n)));
+
+        // tables with a different entry size or field are rejected
+        assert!(MappedArena::<[RawPtr; 4]>::open(dir, "test", Fr::FIELD).is_err());
+        drop(arena);
+        let err = MappedArena::<[RawPtr; 2]>::open(dir, "test", LanguageField::Pallas)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("another field than Pallas"), "{err}");
+    }
+}
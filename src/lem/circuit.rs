@@ -40,7 +40,7 @@ use crate::{
             enforce_selector_with_premise, implies_equal, implies_equal_const, implies_pack,
             implies_u64, implies_unequal_const, mul, or, pick, sub,
         },
-        data::{allocate_constant, hash_poseidon},
+        data::{allocate_constant, hash_poseidon, implies_tag},
         pointer::AllocatedPtr,
     },
     coprocessor::Coprocessor,
@@ -579,7 +579,7 @@ fn synthesize_block<F: LurkField, CS: ConstraintSystem<F>, C: Coprocessor<F>>(
             Op::Cproc(out, sym, inp) => {
                 let cproc = ctx
                     .lang
-                    .lookup_by_sym(sym)
+                    .lookup_callable(sym)
                     .ok_or_else(|| anyhow!("Coprocessor for {sym} not found"))?;
                 let not_dummy_and_not_blank = not_dummy.get_value() == Some(true) && !ctx.blank;
                 let collected_z_ptrs = if not_dummy_and_not_blank {
@@ -717,13 +717,11 @@ fn synthesize_block<F: LurkField, CS: ConstraintSystem<F>, C: Coprocessor<F>>(
 
                 // For each component of the preimage, add implication constraints
                 // for its tag and hash
-                let sym_tag = ctx.global_allocator.alloc_tag_cloned(&mut cs, &Sym);
-                let env_tag = ctx.global_allocator.alloc_tag_cloned(&mut cs, &Env);
-                implies_equal(
+                implies_tag(
                     &mut cs.namespace(|| format!("implies equal {}.tag", preimg[0])),
                     not_dummy,
-                    sym.tag(),
-                    &sym_tag,
+                    sym,
+                    &Sym,
                 );
                 implies_equal(
                     &mut cs.namespace(|| format!("implies equal {}.hash", preimg[0])),
@@ -743,11 +741,11 @@ fn synthesize_block<F: LurkField, CS: ConstraintSystem<F>, C: Coprocessor<F>>(
                     val.hash(),
                     &preallocated_preimg[2],
                 );
-                implies_equal(
+                implies_tag(
                     &mut cs.namespace(|| format!("implies equal {}.tag", preimg[2])),
                     not_dummy,
-                    env.tag(),
-                    &env_tag,
+                    env,
+                    &Env,
                 );
                 implies_equal(
                     &mut cs.namespace(|| format!("implies equal {}.hash", preimg[2])),
@@ -1083,17 +1081,16 @@ fn synthesize_block<F: LurkField, CS: ConstraintSystem<F>, C: Coprocessor<F>>(
             Op::Hide(tgt, sec, pay) => {
                 let sec = bound_allocations.get_ptr(sec)?;
                 let pay = bound_allocations.get_ptr(pay)?;
-                let sec_tag = ctx.global_allocator.alloc_tag(&mut cs, &Num);
                 let (preallocated_preimg, hash) =
                     &ctx.commitment_slots[next_slot.consume_commitment()];
                 let AllocatedVal::Number(hash) = hash else {
                     panic!("Excepted number")
                 };
-                implies_equal(
+                implies_tag(
                     &mut cs.namespace(|| "implies equal secret.tag"),
                     not_dummy,
-                    sec.tag(),
-                    sec_tag,
+                    sec,
+                    &Num,
                 );
                 implies_equal(
                     &mut cs.namespace(|| "implies equal secret.hash"),
@@ -1121,15 +1118,14 @@ fn synthesize_block<F: LurkField, CS: ConstraintSystem<F>, C: Coprocessor<F>>(
                 let comm = bound_allocations.get_ptr(comm)?;
                 let (preallocated_preimg, com_hash) =
                     &ctx.commitment_slots[next_slot.consume_commitment()];
-                let comm_tag = ctx.global_allocator.alloc_tag(&mut cs, &Comm);
                 let AllocatedVal::Number(com_hash) = com_hash else {
                     panic!("Excepted number")
                 };
-                implies_equal(
+                implies_tag(
                     &mut cs.namespace(|| "implies equal comm.tag"),
                     not_dummy,
-                    comm.tag(),
-                    comm_tag,
+                    comm,
+                    &Comm,
                 );
                 implies_equal(
                     &mut cs.namespace(|| "implies equal comm.hash "),
@@ -1320,12 +1316,11 @@ fn synthesize_block<F: LurkField, CS: ConstraintSystem<F>, C: Coprocessor<F>>(
             )?;
 
             // Now we enforce `MatchSymbol`'s tag
-            let sym_tag = ctx.global_allocator.alloc_tag(cs, &Sym);
-            implies_equal(
+            implies_tag(
                 &mut cs.namespace(|| format!("implies equal {match_var}.tag")),
                 not_dummy,
-                match_var_ptr.tag(),
-                sym_tag,
+                &match_var_ptr,
+                &Sym,
             );
 
             // The number of slots the match used is the max number of slots of each branch
@@ -0,0 +1,712 @@
+//! A textual syntax for LEM, parsed at runtime.
+//!
+//! The syntax is the one accepted by the `func!` macro, so LEM programs can be
+//! moved between Rust code and `.lem` files verbatim. A source file contains a
+//! sequence of function definitions
+//!
+//! ```text
+//! // comments run until the end of the line
+//! double(x): 1 => {
+//!     let y = add(x, x);
+//!     return (y)
+//! }
+//!
+//! step(expr, env, cont): 3 => {
+//!     match expr.tag {
+//!         Expr::Num => {
+//!             let (expr) = double(expr);
+//!             let cont: Cont::Terminal;
+//!             return (expr, env, cont)
+//!         }
+//!     };
+//!     let cont: Cont::Error;
+//!     return (expr, env, cont)
+//! }
+//! ```
+//!
+//! and each function can call the functions defined before it. The last one is
+//! the entry point returned by `parse_func` and `load_func`, which can be used
+//! as a step function with `evaluate`, or synthesized, like any other `Func`.
+
+use anyhow::{anyhow, bail, Result};
+use camino::Utf8Path;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use strum::IntoEnumIterator;
+
+use crate::{
+    state::lurk_sym,
+    tag::{ContTag, ExprTag, Op1, Op2},
+};
+
+use super::{tag::Tag, Block, Ctrl, Func, Lit, Op, Var};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Num(u128),
+    Str(String),
+    Punct(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Ident(s) => write!(f, "`{s}`"),
+            Token::Num(n) => write!(f, "`{n}`"),
+            Token::Str(s) => write!(f, "{s:?}"),
+            Token::Punct(p) => write!(f, "`{p}`"),
+        }
+    }
+}
+
+const PUNCTS: [&str; 13] = [
+    "::", "=>", "(", ")", "{", "}", ",", ";", ":", "=", "|", "!", ".",
+];
+
+/// Splits `src` into tokens, each paired with its line number
+fn tokenize(src: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = vec![];
+    for (line_idx, mut line) in src.lines().enumerate() {
+        let line_num = line_idx + 1;
+        if let Some(idx) = line.find("//") {
+            line = &line[..idx];
+        }
+        let mut rest = line.trim_start();
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap();
+            let (token, len) = if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (Token::Ident(rest[..len].to_string()), len)
+            } else if c.is_ascii_digit() {
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let num = rest[..len]
+                    .parse()
+                    .map_err(|e| anyhow!("line {line_num}: invalid number: {e}"))?;
+                (Token::Num(num), len)
+            } else if c == '"' {
+                let mut string = String::new();
+                let mut chars = rest.char_indices().skip(1);
+                let len = loop {
+                    match chars.next() {
+                        None => bail!("line {line_num}: unterminated string"),
+                        Some((idx, '"')) => break idx + 1,
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => string.push('\n'),
+                            Some((_, c @ ('"' | '\\'))) => string.push(c),
+                            _ => bail!("line {line_num}: invalid escape sequence"),
+                        },
+                        Some((_, c)) => string.push(c),
+                    }
+                };
+                (Token::Str(string), len)
+            } else if let Some(punct) = PUNCTS.iter().find(|p| rest.starts_with(**p)) {
+                (Token::Punct(*punct), punct.len())
+            } else {
+                bail!("line {line_num}: unexpected character `{c}`")
+            };
+            tokens.push((token, line_num));
+            rest = rest[len..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_tag(kind: &str, name: &str) -> Option<Tag> {
+    fn find<T: IntoEnumIterator + std::fmt::Debug>(name: &str) -> Option<T> {
+        T::iter().find(|t| format!("{t:?}") == name)
+    }
+    match kind {
+        "Expr" => find::<ExprTag>(name).map(Tag::Expr),
+        "Cont" => find::<ContTag>(name).map(Tag::Cont),
+        "Op1" => find::<Op1>(name).map(Tag::Op1),
+        "Op2" => find::<Op2>(name).map(Tag::Op2),
+        _ => None,
+    }
+}
+
+/// The arguments of a function-like operation
+enum Arg {
+    Var(Var),
+    Num(u128),
+    Str(String),
+    Tag(Tag),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    funcs: HashMap<String, Func>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error<T>(&self, msg: &str) -> Result<T> {
+        match self.peek() {
+            Some(token) => bail!("line {}: {msg}, found {token}", self.line()),
+            None => bail!("line {}: {msg}, found the end of the input", self.line()),
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(token, _)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s == keyword)
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        let is_punct = self.is_punct(punct);
+        if is_punct {
+            self.pos += 1;
+        }
+        is_punct
+    }
+
+    fn expect_punct(&mut self, punct: &str) -> Result<()> {
+        if !self.eat_punct(punct) {
+            return self.error(&format!("expected `{punct}`"));
+        }
+        Ok(())
+    }
+
+    fn expect_ident(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Ident(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => self.error("expected an identifier"),
+        }
+    }
+
+    fn expect_num(&mut self) -> Result<u128> {
+        match self.peek() {
+            Some(Token::Num(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => self.error("expected a number"),
+        }
+    }
+
+    fn expect_str(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Str(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => self.error("expected a string"),
+        }
+    }
+
+    fn tag(&mut self) -> Result<Tag> {
+        let kind = self.expect_ident()?;
+        self.expect_punct("::")?;
+        let name = self.expect_ident()?;
+        parse_tag(&kind, &name)
+            .ok_or_else(|| anyhow!("line {}: unknown tag {kind}::{name}", self.line()))
+    }
+
+    /// Parses `(x, y, ...)` with `item`, allowing a trailing comma
+    fn parens<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        self.expect_punct("(")?;
+        let mut items = vec![];
+        while !self.eat_punct(")") {
+            items.push(item(self)?);
+            if !self.eat_punct(",") {
+                self.expect_punct(")")?;
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    fn var(&mut self) -> Result<Var> {
+        Ok(Var::new(&self.expect_ident()?))
+    }
+
+    fn arg(&mut self) -> Result<Arg> {
+        match self.peek() {
+            Some(Token::Num(_)) => Ok(Arg::Num(self.expect_num()?)),
+            Some(Token::Str(_)) => Ok(Arg::Str(self.expect_str()?)),
+            Some(Token::Ident(_)) => {
+                if matches!(self.tokens.get(self.pos + 1), Some((Token::Punct("::"), _))) {
+                    Ok(Arg::Tag(self.tag()?))
+                } else {
+                    Ok(Arg::Var(self.var()?))
+                }
+            }
+            _ => self.error("expected an argument"),
+        }
+    }
+
+    /// Parses the operation starting with `let`, after it
+    fn let_op(&mut self) -> Result<Op> {
+        let line = self.line();
+        let tgts = if self.is_punct("(") {
+            self.parens(Self::var)?
+        } else {
+            vec![self.var()?]
+        };
+        if self.eat_punct(":") {
+            let [tgt] = <[Var; 1]>::try_from(tgts)
+                .map_err(|_| anyhow!("line {line}: only single variables can be annotated"))?;
+            let tag = self.tag()?;
+            if !self.eat_punct("=") {
+                return Ok(Op::Zero(tgt, tag));
+            }
+            let name = self.expect_ident()?;
+            return match name.as_str() {
+                "HASH_3_ZEROS" => Ok(Op::Hash3Zeros(tgt, tag)),
+                "HASH_4_ZEROS" => Ok(Op::Hash4Zeros(tgt, tag)),
+                "HASH_6_ZEROS" => Ok(Op::Hash6Zeros(tgt, tag)),
+                "HASH_8_ZEROS" => Ok(Op::Hash8Zeros(tgt, tag)),
+                "cons2" => Ok(Op::Cons2(tgt, tag, self.var_args(line)?)),
+                "cons3" => Ok(Op::Cons3(tgt, tag, self.var_args(line)?)),
+                "cons4" => Ok(Op::Cons4(tgt, tag, self.var_args(line)?)),
+                _ => bail!("line {line}: unknown tagged operation `{name}`"),
+            };
+        }
+        self.expect_punct("=")?;
+        let name = self.expect_ident()?;
+        let args = self.parens(Self::arg)?;
+        let arity_error = || anyhow!("line {line}: wrong arguments or targets for `{name}`");
+        let vars = |args: Vec<Arg>| {
+            args.into_iter()
+                .map(|arg| match arg {
+                    Arg::Var(var) => Some(var),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(arity_error)
+        };
+        macro_rules! fixed {
+            ($xs:expr) => {
+                $xs.try_into().map_err(|_| arity_error())?
+            };
+        }
+        match name.as_str() {
+            "Num" | "String" | "Symbol" => {
+                let [tgt] = fixed!(tgts);
+                let lit = match (name.as_str(), fixed!(args)) {
+                    ("Num", [Arg::Num(n)]) => Lit::Num(n),
+                    ("String", [Arg::Str(s)]) => Lit::String(s),
+                    ("Symbol", [Arg::Str(s)]) => Lit::Symbol(lurk_sym(&s)),
+                    _ => return Err(arity_error()),
+                };
+                Ok(Op::Lit(tgt, lit))
+            }
+            "cast" => {
+                let [tgt] = fixed!(tgts);
+                match fixed!(args) {
+                    [Arg::Var(src), Arg::Tag(tag)] => Ok(Op::Cast(tgt, tag, src)),
+                    _ => Err(arity_error()),
+                }
+            }
+            "truncate" => {
+                let [tgt] = fixed!(tgts);
+                match fixed!(args) {
+                    [Arg::Var(src), Arg::Num(n)] => {
+                        let n = u32::try_from(n).map_err(|_| arity_error())?;
+                        Ok(Op::Trunc(tgt, src, n))
+                    }
+                    _ => Err(arity_error()),
+                }
+            }
+            "not" => {
+                let [tgt] = fixed!(tgts);
+                let [a] = fixed!(vars(args)?);
+                Ok(Op::Not(tgt, a))
+            }
+            "eq_tag" | "eq_val" | "and" | "or" | "add" | "sub" | "mul" | "div" | "lt" | "hide" => {
+                let [tgt] = fixed!(tgts);
+                let [a, b] = fixed!(vars(args)?);
+                Ok(match name.as_str() {
+                    "eq_tag" => Op::EqTag(tgt, a, b),
+                    "eq_val" => Op::EqVal(tgt, a, b),
+                    "and" => Op::And(tgt, a, b),
+                    "or" => Op::Or(tgt, a, b),
+                    "add" => Op::Add(tgt, a, b),
+                    "sub" => Op::Sub(tgt, a, b),
+                    "mul" => Op::Mul(tgt, a, b),
+                    "div" => Op::Div(tgt, a, b),
+                    "lt" => Op::Lt(tgt, a, b),
+                    _ => Op::Hide(tgt, a, b),
+                })
+            }
+            "div_rem64" => {
+                let [a, b] = fixed!(vars(args)?);
+                Ok(Op::DivRem64(fixed!(tgts), a, b))
+            }
+            "decons2" => Ok(Op::Decons2(fixed!(tgts), fixed!(vars(args)?))),
+            "decons3" => Ok(Op::Decons3(fixed!(tgts), fixed!(vars(args)?))),
+            "decons4" => Ok(Op::Decons4(fixed!(tgts), fixed!(vars(args)?))),
+            "push_binding" => {
+                let [tgt] = fixed!(tgts);
+                Ok(Op::PushBinding(tgt, fixed!(vars(args)?)))
+            }
+            "pop_binding" => {
+                let [src] = fixed!(vars(args)?);
+                Ok(Op::PopBinding(fixed!(tgts), src))
+            }
+            "open" => {
+                let [sec, src] = fixed!(tgts);
+                let [hash] = fixed!(vars(args)?);
+                Ok(Op::Open(sec, src, hash))
+            }
+            _ => {
+                let Some(func) = self.funcs.get(&name) else {
+                    bail!("line {line}: unknown function `{name}`")
+                };
+                Ok(Op::Call(tgts, Box::new(func.clone()), vars(args)?))
+            }
+        }
+    }
+
+    fn var_args<const N: usize>(&mut self, line: usize) -> Result<[Var; N]> {
+        let vars = self.parens(Self::var)?;
+        vars.try_into()
+            .map_err(|_| anyhow!("line {line}: expected {N} arguments"))
+    }
+
+    /// Parses the cases of a `match` up to its closing brace
+    fn cases<K: std::hash::Hash + Eq>(
+        &mut self,
+        mut key: impl FnMut(&mut Self) -> Result<K>,
+        sep: &str,
+    ) -> Result<IndexMap<K, Block>> {
+        self.expect_punct("{")?;
+        let mut cases = IndexMap::new();
+        while !self.eat_punct("}") {
+            let line = self.line();
+            let mut keys = vec![key(self)?];
+            while self.eat_punct(sep) {
+                keys.push(key(self)?);
+            }
+            self.expect_punct("=>")?;
+            let block = self.braced_block()?;
+            for key in keys {
+                if cases.insert(key, block.clone()).is_some() {
+                    bail!("line {line}: repeated case on `match`");
+                }
+            }
+        }
+        Ok(cases)
+    }
+
+    /// The default block of a `match`, which are the statements following it
+    fn default_block(&mut self) -> Result<Option<Box<Block>>> {
+        if self.eat_punct(";") && !self.is_punct("}") {
+            Ok(Some(Box::new(self.block()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn braced_block(&mut self) -> Result<Block> {
+        self.expect_punct("{")?;
+        let block = self.block()?;
+        self.expect_punct("}")?;
+        Ok(block)
+    }
+
+    /// Parses a sequence of operations followed by a control, stopping before
+    /// the closing brace of the enclosing block
+    fn block(&mut self) -> Result<Block> {
+        let mut ops = vec![];
+        loop {
+            let op = if self.is_keyword("let") {
+                self.pos += 1;
+                self.let_op()?
+            } else if self.is_keyword("emit") {
+                self.pos += 1;
+                let [v] = self.var_args(self.line())?;
+                Op::Emit(v)
+            } else {
+                break;
+            };
+            self.expect_punct(";")?;
+            ops.push(op);
+        }
+        let ctrl = match self.next() {
+            Some(Token::Ident(keyword)) if keyword == "return" => {
+                let rets = self.parens(Self::var)?;
+                self.eat_punct(";");
+                Ctrl::Return(rets)
+            }
+            Some(Token::Ident(keyword)) if keyword == "match" => {
+                if self.is_keyword("symbol") {
+                    self.pos += 1;
+                    let var = self.var()?;
+                    let cases = self.cases(|p| p.expect_str().map(|s| lurk_sym(&s)), ",")?;
+                    Ctrl::MatchSymbol(var, cases, self.default_block()?)
+                } else {
+                    let var = self.var()?;
+                    self.expect_punct(".")?;
+                    if self.expect_ident()? != "tag" {
+                        bail!("line {}: expected `.tag`", self.line());
+                    }
+                    let cases = self.cases(Self::tag, "|")?;
+                    Ctrl::MatchTag(var, cases, self.default_block()?)
+                }
+            }
+            Some(Token::Ident(keyword)) if keyword == "if" => {
+                let negated = self.eat_punct("!");
+                let var = self.var()?;
+                let true_block = Box::new(self.braced_block()?);
+                let false_block = Box::new(self.block()?);
+                if negated {
+                    Ctrl::If(var, false_block, true_block)
+                } else {
+                    Ctrl::If(var, true_block, false_block)
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                return self.error("expected an operation, `return`, `match` or `if`");
+            }
+        };
+        if !self.is_punct("}") {
+            return self.error("expected the end of the block");
+        }
+        Ok(Block { ops, ctrl })
+    }
+
+    fn func(&mut self) -> Result<Func> {
+        let line = self.line();
+        let name = self.expect_ident()?;
+        let input_params = self.parens(Self::var)?;
+        self.expect_punct(":")?;
+        let output_size = self.expect_num()?;
+        self.expect_punct("=>")?;
+        let body = self.braced_block()?;
+        Func::new(name.clone(), input_params, output_size as usize, body)
+            .map_err(|e| anyhow!("line {line}: invalid function `{name}`: {e}"))
+    }
+}
+
+/// Parses all the functions defined in `src`, in order
+pub fn parse_funcs(src: &str) -> Result<Vec<Func>> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+        funcs: HashMap::default(),
+    };
+    let mut funcs = vec![];
+    while parser.peek().is_some() {
+        let func = parser.func()?;
+        parser.funcs.insert(func.name.clone(), func.clone());
+        funcs.push(func);
+    }
+    Ok(funcs)
+}
+
+/// Parses the last function defined in `src`
+pub fn parse_func(src: &str) -> Result<Func> {
+    parse_funcs(src)?
+        .pop()
+        .ok_or_else(|| anyhow!("No function defined"))
+}
+
+/// Parses the last function defined in the file at `path`
+pub fn load_func(path: &Utf8Path) -> Result<Func> {
+    let src = std::fs::read_to_string(path)?;
+    parse_func(&src).map_err(|e| anyhow!("{path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use bellpepper_core::test_cs::TestConstraintSystem;
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::{
+        eval::lang::{DummyCoprocessor, Lang},
+        func,
+        lem::{eval::evaluate_simple, store::Store},
+    };
+
+    const STEP: &str = r#"
+        double(x): 1 => {
+            let y = add(x, x);
+            return (y)
+        }
+
+        // doubles numbers and errors on anything else
+        step(expr, env, cont): 3 => {
+            match expr.tag {
+                Expr::Num => {
+                    let (expr) = double(expr);
+                    let cont: Cont::Terminal;
+                    return (expr, env, cont)
+                }
+                Expr::Sym | Expr::Key => {
+                    match symbol expr {
+                        "nil", "t" => {
+                            let cont: Cont::Terminal;
+                            return (expr, env, cont)
+                        }
+                    };
+                    let cont: Cont::Error;
+                    return (expr, env, cont)
+                }
+            };
+            let cont: Cont::Error;
+            return (expr, env, cont)
+        }
+    "#;
+
+    #[test]
+    fn test_matches_macro() {
+        let double = func!(double(x): 1 => {
+            let y = add(x, x);
+            return (y)
+        });
+        let step = func!(step(expr, env, cont): 3 => {
+            match expr.tag {
+                Expr::Num => {
+                    let (expr) = double(expr);
+                    let cont: Cont::Terminal;
+                    return (expr, env, cont)
+                }
+                Expr::Sym | Expr::Key => {
+                    match symbol expr {
+                        "nil", "t" => {
+                            let cont: Cont::Terminal;
+                            return (expr, env, cont)
+                        }
+                    };
+                    let cont: Cont::Error;
+                    return (expr, env, cont)
+                }
+            };
+            let cont: Cont::Error;
+            return (expr, env, cont)
+        });
+        assert_eq!(step, parse_func(STEP).unwrap());
+
+        let ops = func!(ops(a, b): 2 => {
+            let x: Expr::Num;
+            let h: Expr::Cons = HASH_4_ZEROS;
+            let n = Num(42);
+            let s = String("a \"quoted\" string");
+            let c = cast(a, Expr::Char);
+            let p: Expr::Cons = cons2(a, b);
+            let (p1, p2) = decons2(p);
+            let e = eq_val(p1, p2);
+            let e = not(e);
+            let t = truncate(n, 8);
+            let (q, r) = div_rem64(n, t);
+            let z = hide(q, s);
+            let (sec, _pay) = open(z);
+            emit(c);
+            if !e {
+                return (x, h)
+            }
+            return (r, sec)
+        });
+        let src = r#"
+            ops(a, b): 2 => {
+                let x: Expr::Num;
+                let h: Expr::Cons = HASH_4_ZEROS;
+                let n = Num(42);
+                let s = String("a \"quoted\" string");
+                let c = cast(a, Expr::Char);
+                let p: Expr::Cons = cons2(a, b);
+                let (p1, p2) = decons2(p);
+                let e = eq_val(p1, p2);
+                let e = not(e);
+                let t = truncate(n, 8);
+                let (q, r) = div_rem64(n, t);
+                let z = hide(q, s);
+                let (sec, _pay) = open(z);
+                emit(c);
+                if !e {
+                    return (x, h)
+                }
+                return (r, sec)
+            }
+        "#;
+        assert_eq!(ops, parse_func(src).unwrap());
+    }
+
+    #[test]
+    fn test_evaluate_and_synthesize() {
+        let step = parse_func(STEP).unwrap();
+        let store = Store::<Fr>::default();
+        let lang = Lang::<Fr, DummyCoprocessor<Fr>>::new();
+
+        let expr = store.num_u64(21);
+        let (output, ..) = evaluate_simple(Some((&step, &[], &lang)), expr, &store, 10).unwrap();
+        assert_eq!(output[0], store.num_u64(42));
+
+        let nil = store.intern_nil();
+        let outermost = store.cont_outermost();
+        let num_constraints = step.num_constraints::<Fr>(&store);
+        for expr in [
+            expr,
+            store.intern_lurk_symbol("t"),
+            store.intern_string("x"),
+        ] {
+            let frame = step
+                .call(
+                    &[expr, nil, outermost],
+                    &store,
+                    Default::default(),
+                    &mut vec![],
+                    &lang,
+                    0,
+                )
+                .unwrap();
+            let mut cs = TestConstraintSystem::<Fr>::new();
+            step.synthesize_frame_aux(&mut cs, &store, &frame, &lang)
+                .unwrap();
+            assert!(cs.is_satisfied());
+            assert_eq!(num_constraints, cs.num_constraints());
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |src: &str| parse_func(src).unwrap_err().to_string();
+        assert_eq!(
+            "line 1: unknown tag Expr::Foo",
+            err("f(x): 1 => { let y: Expr::Foo; return (y) }")
+        );
+        assert_eq!(
+            "line 2: unknown function `g`",
+            err("f(x): 1 => {\n let (y) = g(x); return (y) }")
+        );
+        assert_eq!(
+            "line 1: expected `;`, found `return`",
+            err("f(x): 1 => { let y = add(x, x) return (y) }")
+        );
+        assert!(err("f(x): 2 => { return (x) }").starts_with("line 1: invalid function `f`"));
+        assert_eq!("No function defined", err("// nothing here"));
+    }
+}
@@ -1,6 +1,7 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use indexmap::IndexMap;
 use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     coprocessor::Coprocessor,
@@ -16,12 +17,12 @@ use crate::{
         ContTag::{Error, Terminal},
         ExprTag::Cproc,
     },
-    Symbol,
+    telemetry, Symbol,
 };
 
 use super::{
     interpreter::{Frame, Hints},
-    pointers::{Ptr, RawPtr},
+    pointers::{Ptr, RawPtr, ZPtr},
     store::{fetch_ptrs, Store},
     Ctrl, Func, Op, Tag, Var,
 };
@@ -77,6 +78,11 @@ fn compute_frame<F: LurkField, C: Coprocessor<F>>(
 }
 
 // Builds frames for IVC or NIVC scheme
+#[tracing::instrument(
+    skip_all,
+    name = "eval::build_frames",
+    fields(limit = limit, iterations = tracing::field::Empty)
+)]
 fn build_frames<
     F: LurkField,
     C: Coprocessor<F>,
@@ -90,7 +96,7 @@ fn build_frames<
     lang: &Lang<F, C>,
     log_fmt: LogFmt,
 ) -> Result<Vec<Frame>> {
-    let mut pc = 0;
+    let mut pc = get_pc(&input[0], store, lang);
     let mut frames = vec![];
     let mut iterations = 0;
     tracing::info!("{}", &log_fmt(0, &input, &[], store));
@@ -110,10 +116,16 @@ fn build_frames<
         }
         pc = get_pc(&expr, store, lang);
     }
+    record_iterations(iterations, store);
     Ok(frames)
 }
 
 /// Faster version of `build_frames` that doesn't accumulate frames
+#[tracing::instrument(
+    skip_all,
+    name = "eval::traverse_frames",
+    fields(limit = limit, iterations = tracing::field::Empty)
+)]
 fn traverse_frames<F: LurkField, C: Coprocessor<F>>(
     lurk_step: &Func,
     cprocs: &[Func],
@@ -122,7 +134,7 @@ fn traverse_frames<F: LurkField, C: Coprocessor<F>>(
     limit: usize,
     lang: &Lang<F, C>,
 ) -> Result<(Vec<Ptr>, usize, Vec<Ptr>)> {
-    let mut pc = 0;
+    let mut pc = get_pc(&input[0], store, lang);
     let mut iterations = 0;
     let mut emitted = vec![];
     for _ in 0..limit {
@@ -137,9 +149,18 @@ fn traverse_frames<F: LurkField, C: Coprocessor<F>>(
         }
         pc = get_pc(&frame.output[0], store, lang);
     }
+    record_iterations(iterations, store);
     Ok((input, iterations, emitted))
 }
 
+/// Records the iterations of an evaluation on the current span and in the `lurk_eval_iterations_total` counter, along
+/// with the hashes the store computed in the meantime
+fn record_iterations<F: LurkField>(iterations: usize, store: &Store<F>) {
+    tracing::Span::current().record("iterations", iterations);
+    metrics::counter!(telemetry::EVAL_ITERATIONS).increment(iterations as u64);
+    store.poseidon_cache.record_hashes();
+}
+
 pub fn evaluate_with_env_and_cont<F: LurkField, C: Coprocessor<F>>(
     lang_setup: Option<(&Func, &[Func], &Lang<F, C>)>,
     expr: Ptr,
@@ -156,25 +177,72 @@ pub fn evaluate_with_env_and_cont<F: LurkField, C: Coprocessor<F>>(
             inp[1].fmt_to_string(store, state),
             inp[2].fmt_to_string(store, state)
         );
+        if let Some(steps) = inp.get(3) {
+            out.push_str(&format!("\n\tStep: {}", steps.fmt_to_string(store, state)));
+        }
         if let Some(ptr) = emit.first() {
             out.push_str(&format!("\n\tEmtd: {}", ptr.fmt_to_string(store, state)));
         }
         out
     };
 
-    let input = vec![expr, env, cont];
-
     match lang_setup {
         None => {
             let lang: Lang<F, C> = Lang::new();
+            let input = vec![expr, env, cont];
             build_frames(eval_step(), &[], input, store, limit, &lang, log_fmt)
         }
         Some((lurk_step, cprocs, lang)) => {
+            let input = initial_input(lurk_step, expr, env, cont, store);
             build_frames(lurk_step, cprocs, input, store, limit, lang, log_fmt)
         }
     }
 }
 
+/// The input of `lurk_step` evaluating `expr` in `env` with `cont`, with the count of a metered step function at 0
+fn initial_input<F: LurkField>(
+    lurk_step: &Func,
+    expr: Ptr,
+    env: Ptr,
+    cont: Ptr,
+    store: &Store<F>,
+) -> Vec<Ptr> {
+    let mut input = vec![expr, env, cont];
+    if is_metered(lurk_step) {
+        input.push(store.num_u64(0));
+    }
+    input
+}
+
+/// The input of the first frame evaluating `expr` in `env`, from which an evaluation can be stepped through with
+/// `next_frame`
+pub fn first_input<F: LurkField>(
+    lurk_step: &Func,
+    expr: Ptr,
+    env: Ptr,
+    store: &Store<F>,
+) -> Vec<Ptr> {
+    initial_input(lurk_step, expr, env, store.cont_outermost(), store)
+}
+
+/// Computes the frame reducing `input`, which is the output of the previous frame. Returns it along with whether the
+/// evaluation ends there
+pub fn next_frame<F: LurkField, C: Coprocessor<F>>(
+    lang_setup: (&Func, &[Func], &Lang<F, C>),
+    input: &[Ptr],
+    store: &Store<F>,
+) -> Result<(Frame, bool)> {
+    let (lurk_step, cprocs, lang) = lang_setup;
+    let pc = get_pc(&input[0], store, lang);
+    compute_frame(lurk_step, cprocs, input, store, lang, &mut vec![], pc)
+}
+
+/// Tells whether `lurk_step` was made by `metered`, counting the iterations in a fourth input and output
+#[inline]
+pub fn is_metered(lurk_step: &Func) -> bool {
+    lurk_step.input_params.len() == 4
+}
+
 #[inline]
 pub fn evaluate_with_env<F: LurkField, C: Coprocessor<F>>(
     lang_setup: Option<(&Func, &[Func], &Lang<F, C>)>,
@@ -210,13 +278,15 @@ pub fn evaluate_simple_with_env<F: LurkField, C: Coprocessor<F>>(
     store: &Store<F>,
     limit: usize,
 ) -> Result<(Vec<Ptr>, usize, Vec<Ptr>)> {
-    let input = vec![expr, env, store.cont_outermost()];
+    let cont = store.cont_outermost();
     match lang_setup {
         None => {
             let lang: Lang<F, C> = Lang::new();
+            let input = vec![expr, env, cont];
             traverse_frames(eval_step(), &[], input, store, limit, &lang)
         }
         Some((lurk_step, cprocs, lang)) => {
+            let input = initial_input(lurk_step, expr, env, cont, store);
             traverse_frames(lurk_step, cprocs, input, store, limit, lang)
         }
     }
@@ -232,6 +302,106 @@ pub fn evaluate_simple<F: LurkField, C: Coprocessor<F>>(
     evaluate_simple_with_env(lang_setup, expr, store.intern_empty_env(), store, limit)
 }
 
+/// How far a partial evaluation got
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// The evaluation finished with a result
+    Terminal,
+    /// The evaluation finished with an error
+    Error,
+    /// The evaluation ran out of iterations and can be resumed
+    Incomplete,
+}
+
+/// The content-addressed state of an evaluation, which can be resumed later with `resume`, from a store that has the
+/// data it points to (such as one loaded from a dump of the store it was taken from)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot<F: LurkField> {
+    pub expr: ZPtr<F>,
+    pub env: ZPtr<F>,
+    pub cont: ZPtr<F>,
+}
+
+impl<F: LurkField> Snapshot<F> {
+    fn new(output: &[Ptr], store: &Store<F>) -> Self {
+        Self {
+            expr: store.hash_ptr(&output[0]),
+            env: store.hash_ptr(&output[1]),
+            cont: store.hash_ptr(&output[2]),
+        }
+    }
+
+    /// The expression, environment and continuation to evaluate from, such as
+    /// for building the frames to prove the next part of the evaluation
+    pub fn to_ptrs(&self, store: &Store<F>) -> [Ptr; 3] {
+        [
+            store.to_ptr(&self.expr),
+            store.to_ptr(&self.env),
+            store.to_ptr(&self.cont),
+        ]
+    }
+
+    pub fn status(&self) -> Status {
+        match self.cont.tag() {
+            Tag::Cont(Terminal) => Status::Terminal,
+            Tag::Cont(Error) => Status::Error,
+            _ => Status::Incomplete,
+        }
+    }
+}
+
+/// Evaluates `expr` in `env` for at most `max_iterations`, returning how far it
+/// got along with a snapshot to resume from. Splitting a long evaluation this
+/// way allows proving it piecewise, one proof per part. Snapshots don't carry
+/// iteration counts, so metered step functions aren't supported
+pub fn evaluate_partial<F: LurkField, C: Coprocessor<F>>(
+    lang_setup: Option<(&Func, &[Func], &Lang<F, C>)>,
+    expr: Ptr,
+    env: Ptr,
+    store: &Store<F>,
+    max_iterations: usize,
+) -> Result<(Status, Snapshot<F>)> {
+    let input = vec![expr, env, store.cont_outermost()];
+    evaluate_partial_aux(lang_setup, input, store, max_iterations)
+}
+
+/// Continues the evaluation `snapshot` was taken from for at most `max_iterations`
+pub fn resume<F: LurkField, C: Coprocessor<F>>(
+    lang_setup: Option<(&Func, &[Func], &Lang<F, C>)>,
+    snapshot: &Snapshot<F>,
+    store: &Store<F>,
+    max_iterations: usize,
+) -> Result<(Status, Snapshot<F>)> {
+    let input = snapshot.to_ptrs(store).to_vec();
+    evaluate_partial_aux(lang_setup, input, store, max_iterations)
+}
+
+fn evaluate_partial_aux<F: LurkField, C: Coprocessor<F>>(
+    lang_setup: Option<(&Func, &[Func], &Lang<F, C>)>,
+    input: Vec<Ptr>,
+    store: &Store<F>,
+    max_iterations: usize,
+) -> Result<(Status, Snapshot<F>)> {
+    if matches!(lang_setup, Some((lurk_step, ..)) if is_metered(lurk_step)) {
+        bail!("Partial evaluations can't be metered");
+    }
+    let snapshot = Snapshot::new(&input, store);
+    if snapshot.status() != Status::Incomplete {
+        return Ok((snapshot.status(), snapshot));
+    }
+    let (output, ..) = match lang_setup {
+        None => {
+            let lang: Lang<F, C> = Lang::new();
+            traverse_frames(eval_step(), &[], input, store, max_iterations, &lang)?
+        }
+        Some((lurk_step, cprocs, lang)) => {
+            traverse_frames(lurk_step, cprocs, input, store, max_iterations, lang)?
+        }
+    };
+    let snapshot = Snapshot::new(&output, store);
+    Ok((snapshot.status(), snapshot))
+}
+
 pub struct EvalConfig<'a, F, C> {
     lang: &'a Lang<F, C>,
     folding_mode: FoldingMode,
@@ -275,14 +445,19 @@ impl<'a, F, C> EvalConfig<'a, F, C> {
 pub fn make_eval_step_from_config<F: LurkField, C: Coprocessor<F>>(
     ec: &EvalConfig<'_, F, C>,
 ) -> Func {
-    make_eval_step(
-        &ec.lang
-            .coprocessors()
+    let callable = ec.lang.callable();
+    let step = make_eval_step(
+        &callable
             .iter()
-            .map(|(s, c)| (s, c.arity()))
+            .map(|(s, arity)| (s, *arity))
             .collect::<Vec<_>>(),
         ec.is_ivc(),
-    )
+    );
+    if ec.lang.is_metered() {
+        metered(&step)
+    } else {
+        step
+    }
 }
 
 fn make_eval_step(cprocs: &[(&Symbol, usize)], ivc: bool) -> Func {
@@ -298,6 +473,56 @@ fn make_eval_step(cprocs: &[(&Symbol, usize)], ivc: bool) -> Func {
     })
 }
 
+/// Wraps a step function `step(expr, env, cont): 3` in one that also counts
+/// the iterations of the evaluation as a fourth input and output. Iterations
+/// from a terminal or error continuation, such as the ones padding the last
+/// step of a proof, aren't counted.
+fn metered(step: &Func) -> Func {
+    let tick = tick();
+    func!(metered(expr, env, cont, steps): 4 => {
+        let (expr, steps) = tick(expr, cont, steps);
+        let (expr, env, cont) = step(expr, env, cont);
+        return (expr, env, cont, steps)
+    })
+}
+
+/// Increments the iteration count `steps` unless `cont` is terminal or an
+/// error, rewriting `expr` into the count before the increment if it's
+/// `(current-step)`
+fn tick() -> Func {
+    func!(tick(expr, cont, steps): 2 => {
+        match cont.tag {
+            Cont::Terminal | Cont::Error => {
+                return (expr, steps)
+            }
+        };
+        let one = Num(1);
+        let next = add(steps, one);
+        match expr.tag {
+            Expr::Cons => {
+                let (head, rest) = decons2(expr);
+                match rest.tag {
+                    Expr::Nil => {
+                        match head.tag {
+                            Expr::Sym => {
+                                match symbol head {
+                                    "current-step" => {
+                                        return (steps, next)
+                                    }
+                                };
+                                return (expr, next)
+                            }
+                        };
+                        return (expr, next)
+                    }
+                };
+                return (expr, next)
+            }
+        };
+        return (expr, next)
+    })
+}
+
 /// Simpler version of `car_cdr` that doesn't deconstruct strings to save some
 /// constraints
 fn car_cdr() -> Func {
@@ -448,13 +673,21 @@ fn run_cproc(cproc_sym: Symbol, arity: usize) -> Func {
 }
 
 /// Creates the `Func`s used to call coprocessors in the NIVC scenario. Each
-/// coprocessor in the `Lang` will have its own specialized `Func`
+/// coprocessor in the `Lang` will have its own specialized `Func`, metered
+/// along with the step function if the `Lang` is
 pub fn make_cprocs_funcs_from_lang<F: LurkField, C: Coprocessor<F>>(
     lang: &Lang<F, C>,
 ) -> Vec<Func> {
-    lang.coprocessors()
-        .iter()
-        .map(|(name, c)| run_cproc(name.clone(), c.arity()))
+    lang.callable()
+        .into_iter()
+        .map(|(name, arity)| {
+            let func = run_cproc(name, arity);
+            if lang.is_metered() {
+                metered(&func)
+            } else {
+                func
+            }
+        })
         .collect()
 }
 
@@ -1057,6 +1290,14 @@ fn reduce(cprocs: &[(&Symbol, usize)]) -> Func {
                                 };
                                 return (expr, env, err, errctrl)
                             }
+                            "match" => {
+                                if rest_is_nil {
+                                    return (expr, env, err, errctrl)
+                                }
+                                let (scrutinee, clauses) = decons2(rest);
+                                let cont: Cont::Match = cons4(clauses, env, cont, foo);
+                                return (scrutinee, env, cont, ret)
+                            }
                         };
                         // unops
                         let (op) = get_unop(head);
@@ -1200,6 +1441,7 @@ fn apply_cont(cprocs: &[(&Symbol, usize)], ivc: bool) -> Func {
                 let nil = Symbol("nil");
                 let nil = cast(nil, Expr::Nil);
                 let empty_env: Expr::Env;
+                let quote = Symbol("quote");
                 let empty_str = String("");
                 let zero = Num(0);
                 let foo: Expr::Nil;
@@ -1690,6 +1932,126 @@ fn apply_cont(cprocs: &[(&Symbol, usize)], ivc: bool) -> Func {
                             }
                         }
                     }
+                    Cont::Match => {
+                        // `result` is the evaluated scrutinee, tried against each clause in turn
+                        let (clauses, saved_env, continuation, _foo) = decons4(cont);
+                        match clauses.tag {
+                            Expr::Nil => {
+                                // no clause matched
+                                return (result, saved_env, err, errctrl)
+                            }
+                            Expr::Cons => {
+                                let (clause, rest_clauses) = decons2(clauses);
+                                match clause.tag {
+                                    Expr::Cons => {
+                                        let (pattern, body_rest) = decons2(clause);
+                                        match body_rest.tag {
+                                            Expr::Cons => {
+                                                let (body, end) = decons2(body_rest);
+                                                match end.tag {
+                                                    Expr::Nil => {
+                                                        let frame: Cont::Match =
+                                                            cons4(rest_clauses, saved_env, continuation, result);
+                                                        let cont: Cont::MatchPattern =
+                                                            cons4(pattern, result, body, frame);
+                                                        return (result, saved_env, cont, makethunk)
+                                                    }
+                                                };
+                                                return (clause, saved_env, err, errctrl)
+                                            }
+                                        };
+                                        return (clause, saved_env, err, errctrl)
+                                    }
+                                };
+                                return (clause, saved_env, err, errctrl)
+                            }
+                        };
+                        return (clauses, saved_env, err, errctrl)
+                    }
+                    Cont::MatchPattern => {
+                        // Matches `value` against `pattern`, binding variables in `env`. `next` is either the
+                        // following pattern of the clause or its body and `frame` is the `Match` continuation
+                        // trying the remaining clauses, resumed with the scrutinee if this one fails.
+                        let (pattern, value, next, frame) = decons4(cont);
+                        let pattern_is_sym = eq_tag(pattern, t);
+                        let pattern_is_t = eq_val(pattern, t);
+                        let pattern_is_not_t = not(pattern_is_t);
+                        let pattern_is_var = and(pattern_is_sym, pattern_is_not_t);
+                        if pattern_is_var {
+                            let env = push_binding(pattern, value, env);
+                            match next.tag {
+                                Cont::MatchPattern => {
+                                    return (value, env, next, makethunk)
+                                }
+                            };
+                            // all patterns matched, so evaluate the body in the extended env
+                            let (_clauses, _saved_env, continuation, _scrutinee) = decons4(frame);
+                            return (next, env, continuation, ret)
+                        }
+                        match pattern.tag {
+                            Expr::Cons => {
+                                let (head, tail) = decons2(pattern);
+                                let head_is_sym = eq_tag(head, quote);
+                                let head_is_quote = eq_val(head, quote);
+                                let head_is_quote = and(head_is_sym, head_is_quote);
+                                if head_is_quote {
+                                    match tail.tag {
+                                        Expr::Cons => {
+                                            let (literal, end) = decons2(tail);
+                                            match end.tag {
+                                                Expr::Nil => {
+                                                    let same_tag = eq_tag(literal, value);
+                                                    let same_val = eq_val(literal, value);
+                                                    let same = and(same_tag, same_val);
+                                                    if same {
+                                                        match next.tag {
+                                                            Cont::MatchPattern => {
+                                                                return (value, env, next, makethunk)
+                                                            }
+                                                        };
+                                                        let (_clauses, _saved_env, continuation, _scrutinee) =
+                                                            decons4(frame);
+                                                        return (next, env, continuation, ret)
+                                                    }
+                                                    let (_clauses, saved_env, _continuation, scrutinee) =
+                                                        decons4(frame);
+                                                    return (scrutinee, saved_env, frame, makethunk)
+                                                }
+                                            };
+                                            return (pattern, env, err, errctrl)
+                                        }
+                                    };
+                                    return (pattern, env, err, errctrl)
+                                }
+                                match value.tag {
+                                    Expr::Cons => {
+                                        // destructure the value once, matching its head then its tail
+                                        let (value_head, value_tail) = decons2(value);
+                                        let next: Cont::MatchPattern = cons4(tail, value_tail, next, frame);
+                                        let cont: Cont::MatchPattern = cons4(head, value_head, next, frame);
+                                        return (value, env, cont, makethunk)
+                                    }
+                                };
+                                let (_clauses, saved_env, _continuation, scrutinee) = decons4(frame);
+                                return (scrutinee, saved_env, frame, makethunk)
+                            }
+                        };
+                        // any other pattern is a literal
+                        let same_tag = eq_tag(pattern, value);
+                        let same_val = eq_val(pattern, value);
+                        let same = and(same_tag, same_val);
+                        if same {
+                            match next.tag {
+                                Cont::MatchPattern => {
+                                    return (value, env, next, makethunk)
+                                }
+                            };
+                            let (_clauses, _saved_env, continuation, _scrutinee) = decons4(frame);
+                            return (next, env, continuation, ret)
+                        }
+                        let (_clauses, saved_env, _continuation, scrutinee) = decons4(frame);
+                        return (scrutinee, saved_env, frame, makethunk)
+                    }
                 }
             }
         };
@@ -1742,8 +2104,8 @@ mod tests {
         expect_eq(func.slots_count.commitment, expect!["1"]);
         expect_eq(func.slots_count.bit_decomp, expect!["3"]);
         expect_eq(cs.num_inputs(), expect!["1"]);
-        expect_eq(cs.aux().len(), expect!["9095"]);
-        expect_eq(cs.num_constraints(), expect!["11025"]);
+        expect_eq(cs.aux().len(), expect!["9167"]);
+        expect_eq(cs.num_constraints(), expect!["11352"]);
         assert_eq!(func.num_constraints(&store), cs.num_constraints());
     }
 }
@@ -148,7 +148,7 @@ impl Block {
                 Op::Cproc(out, sym, inp) => {
                     let inp_ptrs = bindings.get_many_ptr(inp)?;
                     let cproc = lang
-                        .lookup_by_sym(sym)
+                        .lookup_callable(sym)
                         .ok_or_else(|| anyhow!("Coprocessor for {sym} not found"))?;
                     let out_ptrs = cproc.evaluate_internal(store, &inp_ptrs);
                     if out.len() != out_ptrs.len() {
@@ -59,16 +59,21 @@
 //! 6. We also check for variables that are not used. If intended they should
 //!    be prefixed by "_"
 
+mod arena;
 pub mod circuit;
+pub mod dsl;
 pub mod eval;
 pub(crate) mod interpreter;
 mod macros;
 pub mod multiframe;
 pub mod pointers;
+pub mod printer;
 mod slot;
 pub mod store;
 pub mod tag;
+pub mod trace;
 mod var_map;
+pub mod z_dag;
 
 use anyhow::{bail, Result};
 use indexmap::IndexMap;
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use bellpepper::util_cs::witness_cs::WitnessCS;
+use bellpepper::util_cs::{metric_cs::MetricCS, witness_cs::WitnessCS, Comparable};
 use bellpepper_core::{num::AllocatedNum, Circuit, ConstraintSystem, SynthesisError};
 use elsa::sync::FrozenMap;
 use nova::supernova::NonUniformCircuit;
@@ -369,6 +369,21 @@ impl<'a, F: LurkField, C: Coprocessor<F>> MultiFrame<'a, F, C> {
     pub fn program_counter(&self) -> usize {
         self.pc
     }
+
+    /// The number of constraints of the circuit of this `MultiFrame`, found by synthesizing a blank one
+    pub fn num_constraints(&self) -> usize {
+        self.circuit_size().0
+    }
+
+    /// The numbers of constraints and of auxiliary variables of the circuit of this `MultiFrame`, found by
+    /// synthesizing a blank one
+    pub fn circuit_size(&self) -> (usize, usize) {
+        let mut cs = MetricCS::new();
+        Self::blank(self.folding_config.clone(), self.pc)
+            .synthesize(&mut cs)
+            .expect("failed to synthesize blank");
+        (cs.num_constraints(), cs.aux().len())
+    }
 }
 
 impl CEKState<Ptr> for Vec<Ptr> {
@@ -775,7 +790,7 @@ impl<'a, F: LurkField, C: Coprocessor<F>> Circuit<F> for MultiFrame<'a, F, C> {
         } else {
             assert!(self.frames.is_none());
             let store = Store::default();
-            let dummy_io = [store.dummy(); 3];
+            let dummy_io = vec![store.dummy(); self.lurk_step.input_params.len()];
             let blank_frame = Frame::blank(self.get_func(), self.pc, &store);
             let frames = vec![blank_frame; self.num_frames];
             synth(&store, &frames, &dummy_io, &dummy_io)
@@ -821,6 +836,7 @@ impl<'a, F: LurkField, C: Coprocessor<F>> nova::traits::circuit::StepCircuit<F>
         2 * self.lurk_step.input_params.len()
     }
 
+    #[tracing::instrument(skip_all, name = "multiframe::synthesize", fields(pc = self.pc))]
     fn synthesize<CS>(
         &self,
         cs: &mut CS,
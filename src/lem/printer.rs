@@ -0,0 +1,393 @@
+//! A printer for `Ptr`s that's safe to use on data of any size.
+//!
+//! `Ptr::fmt_to_string` recurses on the structure of the data and expands
+//! shared subtrees every time they occur, so deep data can overflow the stack
+//! and DAGs with a lot of sharing (e.g. transcripts) print exponentially large
+//! strings. `Ptr::fmt_to_string_with` traverses the data with an explicit stack
+//! instead and can bound its output:
+//! * compound data nested deeper than `max_depth` is elided as `...`, or as its
+//!   `ZPtr` digest if `digests` is set;
+//! * lists and environments are cut after `max_length` elements;
+//! * with `label_shared`, compound data that occurs more than once is printed
+//!   in full only once, Common Lisp style: `#1=(a b)` the first time and `#1#`
+//!   afterwards.
+//!
+//! Atoms, strings and symbols are printed just like `fmt_to_string` does.
+
+use std::collections::HashMap;
+
+use crate::{
+    field::LurkField,
+    state::State,
+    tag::{ContTag, ExprTag},
+};
+
+use super::{
+    pointers::Ptr,
+    store::{fetch_ptrs, Store},
+    tag::Tag,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrintConfig {
+    /// How many levels of compound data are printed. Unlimited if `None`
+    pub max_depth: Option<usize>,
+    /// How many elements of each list or environment are printed. Unlimited if
+    /// `None`
+    pub max_length: Option<usize>,
+    /// Whether to label compound data that occurs more than once
+    pub label_shared: bool,
+    /// Whether to print the digests of subtrees elided by `max_depth`
+    pub digests: bool,
+}
+
+enum Piece {
+    Text(String),
+    Child(Ptr),
+}
+
+use Piece::{Child, Text};
+
+/// The pieces of a compound pointer, whose children are printed after their
+/// parents are broken down. Returns `None` for the pointers printed by
+/// `fmt_to_string` as a whole.
+fn layout<F: LurkField>(
+    ptr: &Ptr,
+    store: &Store<F>,
+    max_length: Option<usize>,
+) -> Option<Vec<Piece>> {
+    let text = |s: &str| Text(s.to_string());
+    let fields = |name: &str, names: &[&str], idx: usize| {
+        let children = fetch_ptrs!(store, 4, idx)?;
+        let mut pieces = vec![Text(format!("{name}{{ "))];
+        for (i, (field, child)) in names.iter().zip(children).enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            pieces.push(Text(format!("{sep}{field}: ")));
+            pieces.push(Child(child));
+        }
+        pieces.push(text(" }"));
+        Some(pieces)
+    };
+    match ptr.tag() {
+        Tag::Expr(ExprTag::Cons) => {
+            let mut pieces = vec![text("(")];
+            let mut ptr = *ptr;
+            let mut len = 0;
+            while let Tag::Expr(ExprTag::Cons) = ptr.tag() {
+                let [car, cdr] = fetch_ptrs!(store, 2, ptr.raw().get_hash4()?)?;
+                if len > 0 {
+                    pieces.push(text(" "));
+                }
+                if max_length.is_some_and(|max| len >= max) {
+                    pieces.push(text("..."));
+                    ptr = store.intern_nil();
+                    break;
+                }
+                pieces.push(Child(car));
+                len += 1;
+                ptr = cdr;
+            }
+            if !ptr.is_nil() {
+                pieces.push(text(" . "));
+                pieces.push(Child(ptr));
+            }
+            pieces.push(text(")"));
+            Some(pieces)
+        }
+        Tag::Expr(tag @ (ExprTag::Fun | ExprTag::Rec)) => {
+            let [vars, body, _, _] = fetch_ptrs!(store, 4, ptr.raw().get_hash8()?)?;
+            let name = if *tag == ExprTag::Fun {
+                "FUNCTION"
+            } else {
+                "REC_FUNCTION"
+            };
+            let vars = match vars.tag() {
+                Tag::Expr(ExprTag::Nil) => text("()"),
+                Tag::Expr(ExprTag::Cons) => Child(vars),
+                _ => return None,
+            };
+            Some(vec![
+                Text(format!("<{name} ")),
+                vars,
+                text(" "),
+                Child(body),
+                text(">"),
+            ])
+        }
+        Tag::Expr(ExprTag::Thunk) => {
+            let [val, cont] = fetch_ptrs!(store, 2, ptr.raw().get_hash4()?)?;
+            Some(vec![
+                text("Thunk{ value: "),
+                Child(val),
+                text(" => cont: "),
+                Child(cont),
+                text(" }"),
+            ])
+        }
+        Tag::Expr(ExprTag::Cproc) => {
+            let [name, args] = fetch_ptrs!(store, 2, ptr.raw().get_hash4()?)?;
+            Some(vec![
+                text("<COPROC "),
+                Child(name),
+                text(" "),
+                Child(args),
+                text(">"),
+            ])
+        }
+        Tag::Expr(ExprTag::Env) => {
+            let env = store.fetch_env(ptr)?;
+            let mut pieces = vec![text("<ENV (")];
+            for (i, (sym, val)) in env.into_iter().enumerate() {
+                if i > 0 {
+                    pieces.push(text(" "));
+                }
+                if max_length.is_some_and(|max| i >= max) {
+                    pieces.push(text("..."));
+                    break;
+                }
+                pieces.extend([text("("), Child(sym), text(" . "), Child(val), text(")")]);
+            }
+            pieces.push(text(")>"));
+            Some(pieces)
+        }
+        Tag::Cont(tag) => {
+            let idx = ptr.raw().get_hash8()?;
+            match tag {
+                ContTag::Call0 => fields("Call0", &["saved_env", "continuation"], idx),
+                ContTag::Call => {
+                    fields("Call", &["unevaled_arg", "saved_env", "continuation"], idx)
+                }
+                ContTag::Call2 => fields("Call2", &["function", "saved_env", "continuation"], idx),
+                ContTag::Tail => fields("Tail", &["saved_env", "continuation"], idx),
+                ContTag::Lookup => fields("Lookup", &["saved_env", "continuation"], idx),
+                ContTag::Unop => fields("Unop", &["saved_env", "continuation"], idx),
+                ContTag::Binop => fields(
+                    "Binop",
+                    &["operator", "saved_env", "unevaled_args", "continuation"],
+                    idx,
+                ),
+                ContTag::Binop2 => {
+                    fields("Binop2", &["operator", "evaled_arg", "continuation"], idx)
+                }
+                ContTag::If => fields("If", &["unevaled_args", "continuation"], idx),
+                ContTag::Let => fields("Let", &["var", "saved_env", "body", "continuation"], idx),
+                ContTag::LetRec => {
+                    fields("LetRec", &["var", "saved_env", "body", "continuation"], idx)
+                }
+                ContTag::Cproc => fields(
+                    "Cproc",
+                    &["name", "unevaled_args", "evaled_args", "continuation"],
+                    idx,
+                ),
+                ContTag::Match => fields("Match", &["clauses", "saved_env", "continuation"], idx),
+                ContTag::MatchPattern => fields(
+                    "MatchPattern",
+                    &["pattern", "value", "next", "continuation"],
+                    idx,
+                ),
+                ContTag::Outermost
+                | ContTag::Dummy
+                | ContTag::Error
+                | ContTag::Terminal
+                | ContTag::Emit => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+struct Printer<'a, F: LurkField> {
+    store: &'a Store<F>,
+    state: &'a State,
+    config: &'a PrintConfig,
+    /// How many times each compound pointer is reached, without entering the
+    /// ones that were reached before
+    occurrences: HashMap<Ptr, usize>,
+    labels: HashMap<Ptr, usize>,
+}
+
+impl<'a, F: LurkField> Printer<'a, F> {
+    fn elided(&self, ptr: &Ptr) -> String {
+        if self.config.digests {
+            let z_ptr = self.store.hash_ptr(ptr);
+            format!("<... {} 0x{}>", z_ptr.tag(), z_ptr.value().hex_digits())
+        } else {
+            "...".into()
+        }
+    }
+
+    /// Traverses `root` in printing order. When `out` is `None`, it only counts
+    /// the occurrences of compound pointers, which must be done beforehand when
+    /// labeling shared data.
+    fn walk(&mut self, root: &Ptr, mut out: Option<&mut String>) {
+        enum Work {
+            Text(String),
+            Visit(Ptr, usize),
+        }
+        fn emit(out: &mut Option<&mut String>, s: &str) {
+            if let Some(out) = out {
+                out.push_str(s);
+            }
+        }
+
+        let mut stack = vec![Work::Visit(*root, 0)];
+        while let Some(work) = stack.pop() {
+            let (ptr, depth) = match work {
+                Work::Text(s) => {
+                    emit(&mut out, &s);
+                    continue;
+                }
+                Work::Visit(ptr, depth) => (ptr, depth),
+            };
+            let Some(pieces) = layout(&ptr, self.store, self.config.max_length) else {
+                if out.is_some() {
+                    emit(&mut out, &ptr.fmt_to_string(self.store, self.state));
+                }
+                continue;
+            };
+            if self.config.max_depth.is_some_and(|max| depth >= max) {
+                if out.is_some() {
+                    emit(&mut out, &self.elided(&ptr));
+                }
+                continue;
+            }
+            if self.config.label_shared {
+                if out.is_none() {
+                    let occurrences = self.occurrences.entry(ptr).or_insert(0);
+                    *occurrences += 1;
+                    if *occurrences > 1 {
+                        continue;
+                    }
+                } else if self.occurrences.get(&ptr).is_some_and(|n| *n > 1) {
+                    if let Some(label) = self.labels.get(&ptr) {
+                        emit(&mut out, &format!("#{label}#"));
+                        continue;
+                    }
+                    let label = self.labels.len() + 1;
+                    self.labels.insert(ptr, label);
+                    emit(&mut out, &format!("#{label}="));
+                }
+            }
+            for piece in pieces.into_iter().rev() {
+                stack.push(match piece {
+                    Text(s) => Work::Text(s),
+                    Child(child) => Work::Visit(child, depth + 1),
+                });
+            }
+        }
+    }
+}
+
+impl Ptr {
+    /// Like `fmt_to_string`, but without recursion and with the limits and
+    /// options of `config`
+    pub fn fmt_to_string_with<F: LurkField>(
+        &self,
+        store: &Store<F>,
+        state: &State,
+        config: &PrintConfig,
+    ) -> String {
+        let mut printer = Printer {
+            store,
+            state,
+            config,
+            occurrences: HashMap::default(),
+            labels: HashMap::default(),
+        };
+        if config.label_shared {
+            printer.walk(self, None);
+        }
+        let mut out = String::new();
+        printer.walk(self, Some(&mut out));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use expect_test::expect;
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::state::initial_lurk_state;
+
+    fn print(store: &Store<Fr>, ptr: &Ptr, config: PrintConfig) -> String {
+        ptr.fmt_to_string_with(store, initial_lurk_state(), &config)
+    }
+
+    #[test]
+    fn test_unlimited_matches_fmt_to_string() {
+        let store = Store::<Fr>::default();
+        for src in [
+            "(1 (2 . 3) \"abc\" nil)",
+            "(lambda (x y) (+ x y))",
+            "(a b . c)",
+            "sym",
+        ] {
+            let ptr = store.read_with_default_state(src).unwrap();
+            assert_eq!(
+                ptr.fmt_to_string_simple(&store),
+                print(&store, &ptr, PrintConfig::default())
+            );
+        }
+        let fun = store.intern_fun(
+            store.read_with_default_state("(x)").unwrap(),
+            store.read_with_default_state("(+ x 1)").unwrap(),
+            store.intern_empty_env(),
+        );
+        assert_eq!(
+            fun.fmt_to_string_simple(&store),
+            print(&store, &fun, PrintConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let store = Store::<Fr>::default();
+        let ptr = store
+            .read_with_default_state("(1 (2 (3 (4))) 5 6 7)")
+            .unwrap();
+        let config = PrintConfig {
+            max_depth: Some(2),
+            max_length: Some(3),
+            ..Default::default()
+        };
+        expect!["(1 (2 ...) 5 ...)"].assert_eq(&print(&store, &ptr, config));
+
+        let digests = PrintConfig {
+            max_depth: Some(0),
+            digests: true,
+            ..Default::default()
+        };
+        let z_ptr = store.hash_ptr(&ptr);
+        assert_eq!(
+            format!("<... {} 0x{}>", z_ptr.tag(), z_ptr.value().hex_digits()),
+            print(&store, &ptr, digests)
+        );
+    }
+
+    #[test]
+    fn test_shared_and_deep() {
+        let store = Store::<Fr>::default();
+        // a DAG whose tree would have 2^64 leaves
+        let mut ptr = store.num_u64(0);
+        for _ in 0..64 {
+            ptr = store.list(vec![ptr, ptr]);
+        }
+        let config = PrintConfig {
+            label_shared: true,
+            ..Default::default()
+        };
+        let printed = print(&store, &ptr, config);
+        assert!(printed.starts_with("(#1=(#2=(#3=("));
+        assert!(printed.ends_with(" #2#) #1#)"));
+
+        // deep enough to overflow the stack if printed recursively
+        let mut ptr = store.intern_nil();
+        for _ in 0..100_000 {
+            ptr = store.list(vec![ptr]);
+        }
+        let printed = print(&store, &ptr, PrintConfig::default());
+        assert_eq!(200_000 + 3, printed.len());
+    }
+}
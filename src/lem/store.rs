@@ -1,32 +1,55 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use arc_swap::ArcSwap;
 use bellpepper::util_cs::witness_cs::SizedWitness;
-use elsa::{
-    sync::index_set::FrozenIndexSet,
-    sync::{FrozenMap, FrozenVec},
-};
-use indexmap::IndexSet;
+use camino::Utf8Path;
+use elsa::sync::{FrozenMap, FrozenVec};
 use neptune::Poseidon;
 use nom::{sequence::preceded, Parser};
+use num_bigint::BigUint;
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use thiserror::Error;
 
 use crate::{
-    field::{FWrap, LurkField},
+    field::{FWrap, LanguageField, LurkField},
     hash::{InversePoseidonCache, PoseidonCache},
     lem::Tag,
-    parser::{syntax, Error, Span},
-    state::{lurk_sym, user_sym, State},
+    package::SymbolRef,
+    parser::{position::Pos, syntax, Error, Span},
+    state::{initial_lurk_state, lurk_sym, user_sym, State},
     symbol::Symbol,
-    syntax::Syntax,
+    syntax::{bytes_literal, Syntax},
     tag::ContTag::{
         self, Binop, Binop2, Call, Call0, Call2, Dummy, Emit, If, Let, LetRec, Lookup, Outermost,
         Tail, Terminal, Unop,
     },
-    tag::ExprTag::{Char, Comm, Cons, Cproc, Env, Fun, Key, Nil, Num, Rec, Str, Sym, Thunk, U64},
+    tag::ExprTag::{
+        self, Bytes, Char, Comm, Cons, Cproc, Env, Fun, Key, Nil, Num, Rec, Str, Sym, Thunk, I64,
+        U64,
+    },
+    Ratio,
 };
 
-use super::pointers::{Ptr, RawPtr, ZPtr};
+use super::{
+    arena::{Arena, MappedArena},
+    pointers::{Ptr, RawPtr, ZPtr},
+    printer::PrintConfig,
+};
+
+/// How many bytes of a `Bytes` pointer are packed into each field element. The
+/// capacity of every supported field is above 248 bits.
+pub const BYTES_CHUNK_SIZE: usize = 31;
 
 /// The `Store` is a crucial part of Lurk's implementation and tries to be a
 /// vesatile data structure for many parts of Lurk's data pipeline.
@@ -38,12 +61,30 @@ use super::pointers::{Ptr, RawPtr, ZPtr};
 ///
 /// The `Store` also provides an infra to speed up interning strings and symbols.
 /// This data is saved in `string_ptr_cache` and `symbol_ptr_cache`.
+///
+/// # Concurrency
+///
+/// A `&Store` can be shared by multiple threads (e.g. rayon workers), which may
+/// intern data and hash pointers concurrently:
+/// * every table is append-only and synchronized internally, so references
+///   returned by the store stay valid while other threads intern data;
+/// * interning is content-addressed, so the same data interned by different
+///   threads results in the same `Ptr`, no matter the order. Indices of new
+///   `RawPtr`s do depend on the interleaving, though;
+/// * hashes are deterministic, so threads racing to hash the same pointer
+///   cache the same value;
+/// * the dehydrated queue is swapped out by `hydrate_z_cache` rather than
+///   locked, so interning and hashing never wait for it. A pointer enqueued
+///   on a queue that was just swapped out may be missed, which only means
+///   it's hashed on demand by `hash_raw_ptr` instead.
+///
+/// Operations that rebuild the store, such as `gc`, take `&mut self`.
 #[derive(Debug)]
 pub struct Store<F: LurkField> {
-    f_elts: FrozenIndexSet<Box<FWrap<F>>>,
-    hash4: FrozenIndexSet<Box<[RawPtr; 4]>>,
-    hash6: FrozenIndexSet<Box<[RawPtr; 6]>>,
-    hash8: FrozenIndexSet<Box<[RawPtr; 8]>>,
+    f_elts: Arena<FWrap<F>>,
+    hash4: Arena<[RawPtr; 4]>,
+    hash6: Arena<[RawPtr; 6]>,
+    hash8: Arena<[RawPtr; 8]>,
 
     string_ptr_cache: FrozenMap<String, Box<Ptr>>,
     symbol_ptr_cache: FrozenMap<Symbol, Box<Ptr>>,
@@ -57,6 +98,8 @@ pub struct Store<F: LurkField> {
     pub inverse_poseidon_cache: InversePoseidonCache<F>,
 
     dehydrated: ArcSwap<FrozenVec<Box<RawPtr>>>,
+
+    counters: InternCounters,
     z_cache: FrozenMap<RawPtr, Box<FWrap<F>>>,
     inverse_z_cache: FrozenMap<FWrap<F>, Box<RawPtr>>,
 
@@ -67,8 +110,47 @@ pub struct Store<F: LurkField> {
     pub hash8zeros_idx: usize,
 }
 
+/// The entries of an association list, indexed by the hashes of their keys
+#[derive(Debug)]
+struct AlistIndex<F: LurkField> {
+    entries: Vec<(Ptr, Ptr)>,
+    /// The list starting at each entry, followed by the end of the list
+    cells: Vec<Ptr>,
+    /// The position of the first entry for each key
+    first: HashMap<ZPtr<F>, usize>,
+}
+
+impl<F: LurkField> AlistIndex<F> {
+    fn find(&self, key: &ZPtr<F>) -> Option<usize> {
+        self.first.get(key).copied()
+    }
+}
+
+// `Store` must remain shareable across threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Store<halo2curves::bn256::Fr>>();
+};
+
 impl<F: LurkField> Default for Store<F> {
     fn default() -> Self {
+        Self::with_arenas(
+            Arena::default(),
+            Arena::default(),
+            Arena::default(),
+            Arena::default(),
+        )
+        .expect("empty arenas")
+    }
+}
+
+impl<F: LurkField> Store<F> {
+    fn with_arenas(
+        f_elts: Arena<FWrap<F>>,
+        hash4: Arena<[RawPtr; 4]>,
+        hash6: Arena<[RawPtr; 6]>,
+        hash8: Arena<[RawPtr; 8]>,
+    ) -> Result<Self> {
         let poseidon_cache = PoseidonCache::default();
         let hash3zeros = poseidon_cache.hash3(&[F::ZERO; 3]);
         let hash4zeros = poseidon_cache.hash4(&[F::ZERO; 4]);
@@ -78,24 +160,24 @@ impl<F: LurkField> Default for Store<F> {
         // Since tags are used very often, we will allocate them at the beginning
         // in order, so that we do not need to use the `f_elts` when we have a tag
         // This is similar to the `hashNzeros` optimization
-        let f_elts = FrozenIndexSet::default();
         let mut i = 0;
         while let Some(tag) = Tag::pos(i) {
-            let (j, _) = f_elts.insert_probe(FWrap(tag.to_field()).into());
-            // This is to make sure the indices are ordered
-            assert_eq!(i, j);
+            let (j, _) = f_elts.insert_probe(FWrap(tag.to_field()));
+            // This is to make sure the indices are ordered. Persisted arenas
+            // created with a different set of tags fail here
+            ensure!(i == j, "the store was created with different tags");
             i += 1;
         }
-        let (hash3zeros_idx, _) = f_elts.insert_probe(FWrap(hash3zeros).into());
-        let (hash4zeros_idx, _) = f_elts.insert_probe(FWrap(hash4zeros).into());
-        let (hash6zeros_idx, _) = f_elts.insert_probe(FWrap(hash6zeros).into());
-        let (hash8zeros_idx, _) = f_elts.insert_probe(FWrap(hash8zeros).into());
+        let (hash3zeros_idx, _) = f_elts.insert_probe(FWrap(hash3zeros));
+        let (hash4zeros_idx, _) = f_elts.insert_probe(FWrap(hash4zeros));
+        let (hash6zeros_idx, _) = f_elts.insert_probe(FWrap(hash6zeros));
+        let (hash8zeros_idx, _) = f_elts.insert_probe(FWrap(hash8zeros));
 
-        Self {
+        Ok(Self {
             f_elts,
-            hash4: Default::default(),
-            hash6: Default::default(),
-            hash8: Default::default(),
+            hash4,
+            hash6,
+            hash8,
             string_ptr_cache: Default::default(),
             symbol_ptr_cache: Default::default(),
             ptr_string_cache: Default::default(),
@@ -104,13 +186,43 @@ impl<F: LurkField> Default for Store<F> {
             poseidon_cache,
             inverse_poseidon_cache: Default::default(),
             dehydrated: Default::default(),
+            counters: InternCounters::new(i),
             z_cache: Default::default(),
             inverse_z_cache: Default::default(),
             hash3zeros_idx,
             hash4zeros_idx,
             hash6zeros_idx,
             hash8zeros_idx,
-        }
+        })
+    }
+
+    /// Opens a store whose interning tables live in memory-mapped files in the
+    /// directory `dir`, creating them if needed. Data interned by previous runs
+    /// keeps its pointers, so multi-gigabyte datasets don't need to be read and
+    /// interned again on every run.
+    ///
+    /// Only the tables are persisted: hashes are recomputed on demand, and
+    /// commitment openings and the caches of strings and symbols start empty
+    /// (strings and symbols are still found in the tables when interned again).
+    /// Call `Store::flush` to make sure new data reaches the disk.
+    pub fn open(dir: &Utf8Path) -> Result<Self> {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {dir}"))?;
+        Self::with_arenas(
+            Arena::Mapped(MappedArena::open(dir, "f_elts", F::FIELD)?),
+            Arena::Mapped(MappedArena::open(dir, "hash4", F::FIELD)?),
+            Arena::Mapped(MappedArena::open(dir, "hash6", F::FIELD)?),
+            Arena::Mapped(MappedArena::open(dir, "hash8", F::FIELD)?),
+        )
+        .with_context(|| format!("opening the store in {dir}"))
+    }
+
+    /// Writes the data interned so far to disk, if the store was opened with
+    /// `Store::open`. Otherwise it does nothing.
+    pub fn flush(&self) -> Result<()> {
+        self.f_elts.flush()?;
+        self.hash4.flush()?;
+        self.hash6.flush()?;
+        self.hash8.flush()
     }
 }
 
@@ -152,6 +264,143 @@ macro_rules! expect_ptrs {
 }
 pub(crate) use expect_ptrs;
 
+/// Identifies store images, ahead of their header
+const STORE_IMAGE_MAGIC: [u8; 8] = *b"LURKIMG\0";
+/// The version of the `StoreImage` format, to be bumped on incompatible changes
+const STORE_IMAGE_VERSION: u32 = 1;
+
+/// What a store image starts with, after `STORE_IMAGE_MAGIC`
+#[derive(Serialize, Deserialize)]
+struct StoreImageHeader {
+    version: u32,
+    field: LanguageField,
+}
+
+/// The entire interned content of a `Store`, as written by `Store::dump`. The
+/// interning tables are listed in index order, so that loading them back in the
+/// same order reproduces every `RawPtr`
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+struct StoreImage<F: LurkField> {
+    f_elts: Vec<FWrap<F>>,
+    hash4: Vec<[RawPtr; 4]>,
+    hash6: Vec<[RawPtr; 6]>,
+    hash8: Vec<[RawPtr; 8]>,
+    strings: Vec<(String, Ptr)>,
+    symbols: Vec<(Symbol, Ptr)>,
+    comms: Vec<(FWrap<F>, FWrap<F>, Ptr)>,
+    z_cache: Vec<(RawPtr, FWrap<F>)>,
+}
+
+/// Counters updated while interning, for `StoreMetrics`
+#[derive(Debug)]
+struct InternCounters {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    /// New entries per tag, indexed by `Tag::index`
+    by_tag: Vec<AtomicUsize>,
+}
+
+impl InternCounters {
+    fn new(num_tags: usize) -> Self {
+        Self {
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            by_tag: (0..num_tags).map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    #[inline]
+    fn record(&self, inserted: bool) {
+        let counter = if inserted { &self.misses } else { &self.hits };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn record_tag(&self, tag: Tag) {
+        self.by_tag[tag.index()].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A snapshot of the size of a `Store` and of how it's been used, to help
+/// diagnosing memory blowups
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreMetrics {
+    /// How many new entries were created by interning data with each tag. Tags
+    /// without entries are omitted.
+    pub interned_by_tag: Vec<(Tag, usize)>,
+    /// Number of interned field elements
+    pub num_f_elts: usize,
+    /// Number of interned tuples of 4, 6 and 8 `RawPtr`s, respectively
+    pub num_hash4: usize,
+    pub num_hash6: usize,
+    pub num_hash8: usize,
+    /// Approximate size in bytes of the interning tables
+    pub arena_bytes: usize,
+    /// Number of pointers whose hashes are cached
+    pub num_hashed: usize,
+    /// Number of pointers waiting for `hydrate_z_cache`
+    pub num_dehydrated: usize,
+    /// Interning calls that found the data already interned
+    pub intern_hits: usize,
+    /// Interning calls that created new entries
+    pub intern_misses: usize,
+}
+
+impl StoreMetrics {
+    /// The fraction of interning calls that found the data already interned
+    pub fn intern_hit_rate(&self) -> f64 {
+        let total = self.intern_hits + self.intern_misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.intern_hits as f64 / total as f64
+        }
+    }
+}
+
+/// Errors from the fallible (`try_`) accessors of `Store`, with the offending
+/// pointer rendered (and truncated if it's too long)
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
+pub enum StoreError {
+    #[error("expected {expected}, found {found}")]
+    Unexpected {
+        expected: &'static str,
+        found: String,
+    },
+    #[error("malformed or missing data for {0}")]
+    Malformed(String),
+}
+
+/// Maps pointers of a `Store` to the pointers with the same content in another
+/// `Store`, which is what's needed to keep using held pointers after content is
+/// moved between stores (e.g. by `Store::gc`)
+#[derive(Debug, Default)]
+pub struct PtrMapping(HashMap<RawPtr, RawPtr>);
+
+impl PtrMapping {
+    #[inline]
+    pub fn get_raw(&self, raw: &RawPtr) -> Option<RawPtr> {
+        self.0.get(raw).copied()
+    }
+
+    /// The pointer corresponding to `ptr`, if it was mapped
+    #[inline]
+    pub fn get(&self, ptr: &Ptr) -> Option<Ptr> {
+        Some(Ptr::new(*ptr.tag(), self.get_raw(ptr.raw())?))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 impl<F: LurkField> Store<F> {
     /// Cost of poseidon hash with arity 3, including the input
     #[inline]
@@ -235,7 +484,9 @@ impl<F: LurkField> Store<F> {
 
     #[inline]
     pub fn intern_f(&self, f: F) -> (usize, bool) {
-        self.f_elts.insert_probe(Box::new(FWrap(f)))
+        let (idx, inserted) = self.f_elts.insert_probe(FWrap(f));
+        self.counters.record(inserted);
+        (idx, inserted)
     }
 
     /// Creates an atom `RawPtr` which points to a cached element of the finite
@@ -246,17 +497,27 @@ impl<F: LurkField> Store<F> {
     }
 
     pub fn intern_atom(&self, tag: Tag, f: F) -> Ptr {
-        Ptr::new(tag, self.intern_raw_atom(f))
+        let (idx, inserted) = self.intern_f(f);
+        if inserted {
+            self.counters.record_tag(tag);
+        }
+        Ptr::new(tag, RawPtr::Atom(idx))
     }
 
     /// Creates a `RawPtr` that's a parent of `N` children
+    #[inline]
     pub fn intern_raw_ptrs<const N: usize>(&self, ptrs: [RawPtr; N]) -> RawPtr {
+        self.intern_raw_ptrs_probe::<N>(ptrs).0
+    }
+
+    /// Like `intern_raw_ptrs`, but also returns whether the pointer is new
+    fn intern_raw_ptrs_probe<const N: usize>(&self, ptrs: [RawPtr; N]) -> (RawPtr, bool) {
         let (ptr, inserted) = self.intern_raw_ptrs_internal::<N>(ptrs);
         if inserted {
             // this is for `hydrate_z_cache`
             self.dehydrated.load().push(Box::new(ptr));
         }
-        ptr
+        (ptr, inserted)
     }
 
     /// Similar to `intern_raw_ptrs` but doesn't add the resulting pointer to
@@ -278,22 +539,27 @@ impl<F: LurkField> Store<F> {
         macro_rules! intern {
             ($Hash:ident, $hash:ident, $n:expr) => {{
                 let ptrs = unsafe { std::mem::transmute::<&[RawPtr; N], &[RawPtr; $n]>(&ptrs) };
-                let (idx, inserted) = self.$hash.insert_probe(Box::new(*ptrs));
+                let (idx, inserted) = self.$hash.insert_probe(*ptrs);
                 (RawPtr::$Hash(idx), inserted)
             }};
         }
-        match N {
+        let (ptr, inserted) = match N {
             4 => intern!(Hash4, hash4, 4),
             6 => intern!(Hash6, hash6, 6),
             8 => intern!(Hash8, hash8, 8),
             _ => unimplemented!(),
-        }
+        };
+        self.counters.record(inserted);
+        (ptr, inserted)
     }
 
     /// Creates a `Ptr` that's a parent of `N` children
     pub fn intern_ptrs<const N: usize, const P: usize>(&self, tag: Tag, ptrs: [Ptr; P]) -> Ptr {
         let raw_ptrs = self.ptrs_to_raw_ptrs::<N, P>(&ptrs);
-        let payload = self.intern_raw_ptrs::<N>(raw_ptrs);
+        let (payload, inserted) = self.intern_raw_ptrs_probe::<N>(raw_ptrs);
+        if inserted {
+            self.counters.record_tag(tag);
+        }
         Ptr::new(tag, payload)
     }
 
@@ -378,8 +644,15 @@ impl<F: LurkField> Store<F> {
     pub fn push_binding(&self, sym: Ptr, val: Ptr, env: Ptr) -> Ptr {
         assert_eq!(*sym.tag(), Tag::Expr(Sym));
         assert_eq!(*env.tag(), Tag::Expr(Env));
-        let raw =
-            self.intern_raw_ptrs::<4>([*sym.raw(), self.tag(*val.tag()), *val.raw(), *env.raw()]);
+        let (raw, inserted) = self.intern_raw_ptrs_probe::<4>([
+            *sym.raw(),
+            self.tag(*val.tag()),
+            *val.raw(),
+            *env.raw(),
+        ]);
+        if inserted {
+            self.counters.record_tag(Tag::Expr(Env));
+        }
         Ptr::new(Tag::Expr(Env), raw)
     }
 
@@ -415,6 +688,26 @@ impl<F: LurkField> Store<F> {
         self.intern_atom(Tag::Expr(U64), F::from_u64(u))
     }
 
+    /// Interns a signed 64-bit integer, represented by its two's-complement bits
+    #[inline]
+    pub fn i64(&self, i: i64) -> Ptr {
+        self.intern_atom(Tag::Expr(I64), F::from_u64(i as u64))
+    }
+
+    pub fn fetch_u64(&self, ptr: &Ptr) -> Option<u64> {
+        if *ptr.tag() != Tag::Expr(U64) {
+            return None;
+        }
+        self.fetch_f(ptr.raw().get_atom()?)?.to_u64()
+    }
+
+    pub fn fetch_i64(&self, ptr: &Ptr) -> Option<i64> {
+        if *ptr.tag() != Tag::Expr(I64) {
+            return None;
+        }
+        Some(self.fetch_f(ptr.raw().get_atom()?)?.to_u64()? as i64)
+    }
+
     #[inline]
     pub fn char(&self, c: char) -> Ptr {
         self.intern_atom(Tag::Expr(Char), F::from_char(c))
@@ -454,6 +747,108 @@ impl<F: LurkField> Store<F> {
         self.intern_atom(*z.tag(), *z.value())
     }
 
+    /// Interns `bytes` as a pair of their length and the list of their chunks of `BYTES_CHUNK_SIZE` bytes, each
+    /// packed into a `Num` in little-endian order
+    pub fn intern_bytes(&self, bytes: &[u8]) -> Ptr {
+        let chunks = bytes
+            .chunks(BYTES_CHUNK_SIZE)
+            .map(|chunk| {
+                let mut repr = F::ZERO.to_bytes();
+                repr[..chunk.len()].copy_from_slice(chunk);
+                self.num(F::from_bytes(&repr).expect("chunk fits in a field element"))
+            })
+            .collect();
+        intern_ptrs!(
+            self,
+            Tag::Expr(Bytes),
+            self.num_u64(bytes.len() as u64),
+            self.list(chunks)
+        )
+    }
+
+    pub fn fetch_bytes(&self, ptr: &Ptr) -> Option<Vec<u8>> {
+        if *ptr.tag() != Tag::Expr(Bytes) {
+            return None;
+        }
+        let [len, chunks] = fetch_ptrs!(self, 2, ptr.raw().get_hash4()?)?;
+        let fetch_num = |ptr: &Ptr| {
+            if *ptr.tag() != Tag::Expr(Num) {
+                return None;
+            }
+            self.fetch_f(ptr.raw().get_atom()?)
+        };
+        let len = usize::try_from(fetch_num(&len)?.to_u64()?).ok()?;
+        let (chunks, None) = self.fetch_list(&chunks)? else {
+            return None;
+        };
+        if chunks.len() != len.div_ceil(BYTES_CHUNK_SIZE) {
+            return None;
+        }
+        let mut bytes = Vec::with_capacity(chunks.len() * BYTES_CHUNK_SIZE);
+        for chunk in &chunks {
+            bytes.extend_from_slice(&fetch_num(chunk)?.to_bytes()[..BYTES_CHUNK_SIZE]);
+        }
+        bytes.truncate(len);
+        Some(bytes)
+    }
+
+    /// Interns a natural number as the little-endian list of its `U64` limbs,
+    /// without trailing zero limbs. In particular, zero is `nil`
+    pub fn intern_biguint(&self, n: &BigUint) -> Ptr {
+        self.list(n.iter_u64_digits().map(|limb| self.u64(limb)).collect())
+    }
+
+    pub fn fetch_biguint(&self, ptr: &Ptr) -> Option<BigUint> {
+        let (limbs, None) = self.fetch_list(ptr)? else {
+            return None;
+        };
+        let mut n = BigUint::default();
+        for (i, limb) in limbs.iter().rev().enumerate() {
+            if *limb.tag() != Tag::Expr(U64) {
+                return None;
+            }
+            let limb = self.fetch_f(limb.raw().get_atom()?)?.to_u64()?;
+            if i == 0 && limb == 0 {
+                return None;
+            }
+            n = (n << 64) + limb;
+        }
+        Some(n)
+    }
+
+    /// Interns a ratio as its sign (a `U64` that's `1` iff it's negative) and
+    /// the magnitudes of its numerator and denominator, as in `intern_biguint`
+    pub fn intern_ratio(&self, r: &Ratio) -> Ptr {
+        let (negative, numer, denom) = r.to_parts();
+        intern_ptrs!(
+            self,
+            Tag::Expr(ExprTag::Ratio),
+            self.u64(u64::from(negative)),
+            self.intern_biguint(&numer),
+            self.intern_biguint(&denom)
+        )
+    }
+
+    pub fn fetch_ratio(&self, ptr: &Ptr) -> Option<Ratio> {
+        if *ptr.tag() != Tag::Expr(ExprTag::Ratio) {
+            return None;
+        }
+        let [sign, numer, denom] = fetch_ptrs!(self, 3, ptr.raw().get_hash6()?)?;
+        if *sign.tag() != Tag::Expr(U64) {
+            return None;
+        }
+        let negative = match self.fetch_f(sign.raw().get_atom()?)?.to_u64()? {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        Ratio::from_parts(
+            negative,
+            self.fetch_biguint(&numer)?,
+            self.fetch_biguint(&denom)?,
+        )
+    }
+
     pub fn intern_string(&self, s: &str) -> Ptr {
         if let Some(ptr) = self.string_ptr_cache.get(s) {
             *ptr
@@ -656,6 +1051,14 @@ impl<F: LurkField> Store<F> {
         (hash, z_ptr)
     }
 
+    /// Hides `payload` with the secret obtained by reducing `salt`, read as a
+    /// little-endian integer, modulo the order of the field. Other systems that
+    /// derive secrets the same way produce commitments that open in Lurk.
+    #[inline]
+    pub fn hide_with_salt(&self, salt: &[u8; 32], payload: Ptr) -> Ptr {
+        self.hide(F::from_le_bytes_reduced(salt), payload)
+    }
+
     #[inline]
     pub fn commit(&self, payload: Ptr) -> Ptr {
         self.hide(F::NON_HIDING_COMMITMENT_SECRET, payload)
@@ -691,44 +1094,99 @@ impl<F: LurkField> Store<F> {
         Ptr::new(Tag::Cont(Terminal), RawPtr::Atom(self.hash8zeros_idx))
     }
 
+    /// Renders a pointer for error messages, cutting nested and long data short so that the rendering stays small
+    /// whatever the size of the data
+    fn render(&self, ptr: &Ptr) -> String {
+        const MAX_LEN: usize = 80;
+        let config = PrintConfig {
+            max_depth: Some(4),
+            max_length: Some(8),
+            ..Default::default()
+        };
+        let rendered = ptr.fmt_to_string_with(self, initial_lurk_state(), &config);
+        if rendered.chars().count() > MAX_LEN {
+            format!("{}...", rendered.chars().take(MAX_LEN).collect::<String>())
+        } else {
+            rendered
+        }
+    }
+
+    fn unexpected(&self, expected: &'static str, ptr: &Ptr) -> StoreError {
+        StoreError::Unexpected {
+            expected,
+            found: self.render(ptr),
+        }
+    }
+
+    fn malformed(&self, ptr: &Ptr) -> StoreError {
+        StoreError::Malformed(self.render(ptr))
+    }
+
+    #[inline]
     pub fn car_cdr(&self, ptr: &Ptr) -> Result<(Ptr, Ptr)> {
+        Ok(self.try_car_cdr(ptr)?)
+    }
+
+    pub fn try_car_cdr(&self, ptr: &Ptr) -> Result<(Ptr, Ptr), StoreError> {
+        let fetch_pair = || {
+            let [car, cdr] = ptr
+                .raw()
+                .get_hash4()
+                .and_then(|idx| fetch_ptrs!(self, 2, idx))
+                .ok_or_else(|| self.malformed(ptr))?;
+            Ok((car, cdr))
+        };
         match ptr.tag() {
             Tag::Expr(Nil) => {
                 let nil = self.intern_nil();
                 Ok((nil, nil))
             }
-            Tag::Expr(Cons) => {
-                let Some(idx) = ptr.raw().get_hash4() else {
-                    bail!("malformed cons pointer")
-                };
-                match self.fetch_raw_ptrs(idx) {
-                    Some([car_tag, car, cdr_tag, cdr]) => {
-                        let car_ptr = self.raw_to_ptr(car_tag, car).context("Not a pointer")?;
-                        let cdr_ptr = self.raw_to_ptr(cdr_tag, cdr).context("Not a pointer")?;
-                        Ok((car_ptr, cdr_ptr))
-                    }
-                    None => bail!("car/cdr not found"),
-                }
-            }
+            Tag::Expr(Cons) => fetch_pair(),
             Tag::Expr(Str) => {
                 if self.is_zero(ptr.raw()) {
                     let empty_str = Ptr::new(Tag::Expr(Str), self.raw_zero());
                     Ok((self.intern_nil(), empty_str))
                 } else {
-                    let Some(idx) = ptr.raw().get_hash4() else {
-                        bail!("malformed str pointer")
-                    };
-                    match self.fetch_raw_ptrs(idx) {
-                        Some([car_tag, car, cdr_tag, cdr]) => {
-                            let car_ptr = self.raw_to_ptr(car_tag, car).context("Not a pointer")?;
-                            let cdr_ptr = self.raw_to_ptr(cdr_tag, cdr).context("Not a pointer")?;
-                            Ok((car_ptr, cdr_ptr))
-                        }
-                        None => bail!("car/cdr not found"),
-                    }
+                    fetch_pair()
                 }
             }
-            _ => bail!("invalid pointer to extract car/cdr from"),
+            _ => Err(self.unexpected("cons, nil or string", ptr)),
+        }
+    }
+
+    pub fn try_fetch_sym(&self, ptr: &Ptr) -> Result<Symbol, StoreError> {
+        if ptr.tag() != &Tag::Expr(Sym) {
+            return Err(self.unexpected("symbol", ptr));
+        }
+        self.fetch_symbol(ptr).ok_or_else(|| self.malformed(ptr))
+    }
+
+    pub fn try_fetch_string(&self, ptr: &Ptr) -> Result<String, StoreError> {
+        if ptr.tag() != &Tag::Expr(Str) {
+            return Err(self.unexpected("string", ptr));
+        }
+        self.fetch_string(ptr).ok_or_else(|| self.malformed(ptr))
+    }
+
+    pub fn try_fetch_bytes(&self, ptr: &Ptr) -> Result<Vec<u8>, StoreError> {
+        if ptr.tag() != &Tag::Expr(Bytes) {
+            return Err(self.unexpected("bytes", ptr));
+        }
+        self.fetch_bytes(ptr).ok_or_else(|| self.malformed(ptr))
+    }
+
+    pub fn try_fetch_ratio(&self, ptr: &Ptr) -> Result<Ratio, StoreError> {
+        if ptr.tag() != &Tag::Expr(ExprTag::Ratio) {
+            return Err(self.unexpected("ratio", ptr));
+        }
+        self.fetch_ratio(ptr).ok_or_else(|| self.malformed(ptr))
+    }
+
+    pub fn try_fetch_list(&self, ptr: &Ptr) -> Result<(Vec<Ptr>, Option<Ptr>), StoreError> {
+        match ptr.tag() {
+            Tag::Expr(Nil) if *ptr != self.intern_nil() => Err(self.malformed(ptr)),
+            Tag::Expr(Nil | Cons) => self.fetch_list(ptr).ok_or_else(|| self.malformed(ptr)),
+            _ => Err(self.unexpected("list", ptr)),
         }
     }
 
@@ -809,25 +1267,199 @@ impl<F: LurkField> Store<F> {
         Some(list)
     }
 
+    /// Interns an association list: a proper list of `(key . value)` conses
+    pub fn alist(&self, entries: &[(Ptr, Ptr)]) -> Ptr {
+        self.list(entries.iter().map(|(k, v)| self.cons(*k, *v)).collect())
+    }
+
+    /// Fetches an association list. Returns `None` if `ptr` isn't a proper list or if any of its elements isn't a
+    /// cons
+    pub fn fetch_alist(&self, ptr: &Ptr) -> Option<Vec<(Ptr, Ptr)>> {
+        let (entries, None) = self.try_fetch_list(ptr).ok()? else {
+            return None;
+        };
+        entries
+            .iter()
+            .map(|entry| {
+                if *entry.tag() != Tag::Expr(Cons) {
+                    return None;
+                }
+                self.try_car_cdr(entry).ok()
+            })
+            .collect()
+    }
+
+    /// The entries of `alist`, indexed by key. Building the index walks the list once, so every operation on an
+    /// association list takes time linear in its length. Returns `None` if `alist` isn't an association list
+    fn alist_index(&self, alist: &Ptr) -> Option<AlistIndex<F>> {
+        let entries = self.fetch_alist(alist)?;
+        let mut cells = Vec::with_capacity(entries.len() + 1);
+        let mut cell = *alist;
+        for _ in &entries {
+            cells.push(cell);
+            cell = self.try_car_cdr(&cell).ok()?.1;
+        }
+        cells.push(cell);
+        let mut first = HashMap::with_capacity(entries.len());
+        for (i, (key, _)) in entries.iter().enumerate() {
+            first.entry(self.hash_ptr(key)).or_insert(i);
+        }
+        Some(AlistIndex {
+            entries,
+            cells,
+            first,
+        })
+    }
+
+    /// Conses the entries of `alist` before the `i`th onto `tail`, sharing the rest of the list
+    fn alist_splice(&self, index: &AlistIndex<F>, i: usize, entry: Option<Ptr>, tail: Ptr) -> Ptr {
+        let tail = match entry {
+            Some(entry) => self.cons(entry, tail),
+            None => tail,
+        };
+        index.entries[..i]
+            .iter()
+            .rev()
+            .fold(tail, |acc, (k, v)| self.cons(self.cons(*k, *v), acc))
+    }
+
+    /// The first entry of `alist` whose key is `key`, or `nil` if there's none. Returns `None` if `alist` isn't an
+    /// association list
+    pub fn assoc(&self, key: &Ptr, alist: &Ptr) -> Option<Ptr> {
+        let index = self.alist_index(alist)?;
+        Some(match index.find(&self.hash_ptr(key)) {
+            Some(i) => {
+                let (k, v) = index.entries[i];
+                self.cons(k, v)
+            }
+            None => self.intern_nil(),
+        })
+    }
+
+    /// Binds `key` to `val` in `alist`, replacing the value of its first entry for `key` in place or adding an entry
+    /// in front if there's none. Returns `None` if `alist` isn't an association list
+    pub fn alist_insert(&self, key: Ptr, val: Ptr, alist: &Ptr) -> Option<Ptr> {
+        let index = self.alist_index(alist)?;
+        let entry = self.cons(key, val);
+        Some(match index.find(&self.hash_ptr(&key)) {
+            Some(i) => self.alist_splice(&index, i, Some(entry), index.cells[i + 1]),
+            None => self.cons(entry, *alist),
+        })
+    }
+
+    /// Removes the first entry for `key` from `alist`. Returns `None` if `alist` isn't an association list
+    pub fn alist_remove(&self, key: &Ptr, alist: &Ptr) -> Option<Ptr> {
+        let index = self.alist_index(alist)?;
+        Some(match index.find(&self.hash_ptr(key)) {
+            Some(i) => self.alist_splice(&index, i, None, index.cells[i + 1]),
+            None => *alist,
+        })
+    }
+
+    /// A hashmap with the bindings of `alist`: the association list without the entries shadowed by an earlier one for
+    /// the same key. `alist-insert` and `alist-remove` keep the keys of a hashmap unique. Returns `None` if `alist`
+    /// isn't an association list
+    pub fn hashmap(&self, alist: &Ptr) -> Option<Ptr> {
+        let index = self.alist_index(alist)?;
+        if index.first.len() == index.entries.len() {
+            return Some(*alist);
+        }
+        let entries = index
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(i, (key, _))| index.find(&self.hash_ptr(key)) == Some(*i))
+            .map(|(_, entry)| *entry)
+            .collect::<Vec<_>>();
+        Some(self.alist(&entries))
+    }
+
+    /// The value bound to `key` in `map`, or `nil` if there's none. Returns `None` if `map` isn't an association list
+    pub fn hashmap_get(&self, key: &Ptr, map: &Ptr) -> Option<Ptr> {
+        let index = self.alist_index(map)?;
+        Some(match index.find(&self.hash_ptr(key)) {
+            Some(i) => index.entries[i].1,
+            None => self.intern_nil(),
+        })
+    }
+
+    /// The bindings of an environment that aren't shadowed, innermost first, as an association list
+    pub fn env_alist(&self, env: &Ptr) -> Option<Ptr> {
+        let mut entries: Vec<(Ptr, Ptr)> = vec![];
+        for (var, val) in self.fetch_env(env)? {
+            if !entries.iter().any(|(k, _)| self.ptr_eq(k, &var)) {
+                entries.push((var, val));
+            }
+        }
+        Some(self.alist(&entries))
+    }
+
     pub fn intern_syntax(&self, syn: Syntax<F>) -> Ptr {
-        match syn {
+        self.intern_syntax_with_pos(syn, &mut |_, _| ())
+    }
+
+    /// Like `intern_syntax`, but calling `on_node` with the `Ptr` and the source
+    /// position of every node, children first
+    pub fn intern_syntax_with_pos(
+        &self,
+        syn: Syntax<F>,
+        on_node: &mut impl FnMut(Ptr, Pos),
+    ) -> Ptr {
+        self.intern_syntax_aux(syn, false, on_node)
+    }
+
+    /// Quasiquotes are expanded into the code that builds their templates, unless
+    /// they are `quoted`
+    fn intern_syntax_aux(
+        &self,
+        syn: Syntax<F>,
+        quoted: bool,
+        on_node: &mut impl FnMut(Ptr, Pos),
+    ) -> Ptr {
+        let pos = *syn.get_pos();
+        let ptr = match syn {
             Syntax::Num(_, x) => self.num(x.into_scalar()),
             Syntax::UInt(_, x) => self.u64(x.into()),
+            Syntax::I64(_, x) => self.i64(x),
             Syntax::Char(_, x) => self.char(x),
             Syntax::Symbol(_, x) => self.intern_symbol(&x),
             Syntax::String(_, x) => self.intern_string(&x),
+            Syntax::Bytes(_, x) => self.intern_bytes(&x),
             Syntax::Quote(_, x) => self.list(vec![
                 self.intern_symbol(&lurk_sym("quote")),
-                self.intern_syntax(*x),
+                self.intern_syntax_aux(*x, true, on_node),
             ]),
-            Syntax::List(_, xs) => {
-                self.list(xs.into_iter().map(|x| self.intern_syntax(x)).collect())
+            Syntax::Quasiquote(_, x) if !quoted => {
+                self.intern_syntax_aux(x.expand_quasiquote(1), false, on_node)
             }
-            Syntax::Improper(_, xs, y) => self.improper_list(
-                xs.into_iter().map(|x| self.intern_syntax(x)).collect(),
-                self.intern_syntax(*y),
+            Syntax::Quasiquote(_, x) => self.list(vec![
+                self.intern_symbol(&lurk_sym("quasiquote")),
+                self.intern_syntax_aux(*x, quoted, on_node),
+            ]),
+            Syntax::Unquote(_, x) => self.list(vec![
+                self.intern_symbol(&lurk_sym("unquote")),
+                self.intern_syntax_aux(*x, quoted, on_node),
+            ]),
+            Syntax::UnquoteSplicing(_, x) => self.list(vec![
+                self.intern_symbol(&lurk_sym("unquote-splicing")),
+                self.intern_syntax_aux(*x, quoted, on_node),
+            ]),
+            Syntax::List(_, xs) => self.list(
+                xs.into_iter()
+                    .map(|x| self.intern_syntax_aux(x, quoted, on_node))
+                    .collect(),
             ),
-        }
+            Syntax::Improper(_, xs, y) => {
+                let xs = xs
+                    .into_iter()
+                    .map(|x| self.intern_syntax_aux(x, quoted, on_node))
+                    .collect();
+                let y = self.intern_syntax_aux(*y, quoted, on_node);
+                self.improper_list(xs, y)
+            }
+        };
+        on_node(ptr, pos);
+        ptr
     }
 
     pub fn read(&self, state: Rc<RefCell<State>>, input: &str) -> Result<Ptr> {
@@ -842,10 +1474,41 @@ impl<F: LurkField> Store<F> {
         }
     }
 
+    /// Like `read`, but resolving symbols w.r.t. the package `package_name`,
+    /// whereas the current package of `state` is left unchanged
+    pub fn read_in_package(
+        &self,
+        state: Rc<RefCell<State>>,
+        package_name: &SymbolRef,
+        input: &str,
+    ) -> Result<Ptr> {
+        let saved_package = state.borrow().get_current_package_name().clone();
+        state
+            .borrow_mut()
+            .set_current_package(package_name.clone())?;
+        let res = self.read(state.clone(), input);
+        state
+            .borrow_mut()
+            .set_current_package(saved_package)
+            .expect("previous package is available");
+        res
+    }
+
     pub fn read_maybe_meta<'a>(
         &self,
         state: Rc<RefCell<State>>,
         input: &'a str,
+    ) -> Result<(usize, Span<'a>, Ptr, bool), Error> {
+        self.read_maybe_meta_with_pos(state, input, &mut |_, _| ())
+    }
+
+    /// Like `read_maybe_meta`, but calling `on_node` as `intern_syntax_with_pos`
+    /// does. Positions are relative to `input`
+    pub fn read_maybe_meta_with_pos<'a>(
+        &self,
+        state: Rc<RefCell<State>>,
+        input: &'a str,
+        on_node: &mut impl FnMut(Ptr, Pos),
     ) -> Result<(usize, Span<'a>, Ptr, bool), Error> {
         match preceded(syntax::parse_space, syntax::parse_maybe_meta(state, false))
             .parse(input.into())
@@ -855,7 +1518,12 @@ impl<F: LurkField> Store<F> {
                     .get_pos()
                     .get_from_offset()
                     .expect("Parsed syntax should have its Pos set");
-                Ok((from_offset, i, self.intern_syntax(x), is_meta))
+                Ok((
+                    from_offset,
+                    i,
+                    self.intern_syntax_with_pos(x, on_node),
+                    is_meta,
+                ))
             }
             Ok((_, None)) => Err(Error::NoInput),
             Err(e) => Err(Error::Syntax(format!("{}", e))),
@@ -916,10 +1584,13 @@ impl<F: LurkField> Store<F> {
     }
 
     /// Hashes enqueued `RawPtr` trees from the bottom to the top, avoiding deep
-    /// recursions in `hash_raw_ptr`. Resets the `dehydrated` queue afterwards.
+    /// recursions in `hash_raw_ptr`. The `dehydrated` queue is swapped for an
+    /// empty one first, so other threads keep interning onto the new queue
+    /// without waiting for the hashing.
     pub fn hydrate_z_cache(&self) {
-        self.hydrate_z_cache_with_ptrs(&self.dehydrated.load().iter().collect::<Vec<_>>());
-        self.dehydrated.swap(Arc::new(FrozenVec::default()));
+        let dehydrated = self.dehydrated.swap(Arc::new(FrozenVec::default()));
+        self.hydrate_z_cache_with_ptrs(&dehydrated.iter().collect::<Vec<_>>());
+        self.poseidon_cache.record_hashes();
     }
 
     /// Whether the length of the dehydrated queue is within the safe limit.
@@ -935,53 +1606,88 @@ impl<F: LurkField> Store<F> {
         }
     }
 
+    /// The children of a `RawPtr`, which are tags or payloads
+    #[inline]
+    fn raw_children(&self, ptr: &RawPtr) -> &[RawPtr] {
+        match ptr {
+            RawPtr::Atom(..) => &[],
+            RawPtr::Hash4(idx) => self.expect_raw_ptrs::<4>(*idx),
+            RawPtr::Hash6(idx) => self.expect_raw_ptrs::<6>(*idx),
+            RawPtr::Hash8(idx) => self.expect_raw_ptrs::<8>(*idx),
+        }
+    }
+
+    #[inline]
+    fn is_dehydrated(&self, ptr: &RawPtr) -> bool {
+        ptr.is_hash() && self.z_cache.get(ptr).is_none()
+    }
+
+    /// Hashes every `RawPtr` reachable from `roots` whose hash isn't cached yet,
+    /// from the bottom of the DAG to the top. Pointers are grouped by their height
+    /// over the already hashed data, and each group is hashed in parallel since
+    /// its hashes only depend on the previous groups. This also means that no
+    /// deep recursion happens.
+    pub fn hydrate_raw_ptrs(&self, roots: &[RawPtr]) {
+        let mut heights: HashMap<RawPtr, usize> = HashMap::default();
+        let mut stack = roots
+            .iter()
+            .filter(|ptr| self.is_dehydrated(ptr))
+            .map(|ptr| (*ptr, false))
+            .collect::<Vec<_>>();
+        while let Some((ptr, children_visited)) = stack.pop() {
+            if heights.contains_key(&ptr) {
+                continue;
+            }
+            let children = self.raw_children(&ptr);
+            if children_visited {
+                // children hashed by other threads in the meantime weren't visited
+                let height = children
+                    .iter()
+                    .filter_map(|child| heights.get(child))
+                    .map(|height| height + 1)
+                    .max()
+                    .unwrap_or(0);
+                heights.insert(ptr, height);
+            } else {
+                stack.push((ptr, true));
+                for child in children {
+                    if self.is_dehydrated(child) && !heights.contains_key(child) {
+                        stack.push((*child, false));
+                    }
+                }
+            }
+        }
+
+        let mut groups: Vec<Vec<RawPtr>> = vec![];
+        for (ptr, height) in heights {
+            if groups.len() <= height {
+                groups.resize_with(height + 1, Vec::new);
+            }
+            groups[height].push(ptr);
+        }
+        for group in groups {
+            group.par_iter().for_each(|ptr| {
+                self.hash_raw_ptr_unsafe(ptr);
+            });
+        }
+    }
+
+    /// Pre-hydrates the hash cache for `roots`, such that hashing them and any
+    /// of their descendants afterwards is just a cache lookup
+    pub fn hydrate_ptrs(&self, roots: &[Ptr]) {
+        self.hydrate_raw_ptrs(&roots.iter().map(|ptr| *ptr.raw()).collect::<Vec<_>>());
+    }
+
     /// Safe version of `hash_raw_ptr_unsafe` that doesn't hit a stack overflow by
-    /// precomputing the pointers that need to be hashed in order to hash the
-    /// provided `ptr`
+    /// hydrating the pointers that need to be hashed in order to hash the
+    /// provided `ptr` beforehand
     pub fn hash_raw_ptr(&self, ptr: &RawPtr) -> FWrap<F> {
         if self.is_below_safe_threshold() {
             // just run `hash_raw_ptr_unsafe` for extra speed when the dehydrated
             // queue is small enough
             return self.hash_raw_ptr_unsafe(ptr);
         }
-        let mut ptrs: IndexSet<&RawPtr> = IndexSet::default();
-        let mut stack = vec![ptr];
-        macro_rules! feed_loop {
-            ($x:expr) => {
-                if $x.is_hash() {
-                    if self.z_cache.get($x).is_none() {
-                        if ptrs.insert($x) {
-                            stack.push($x);
-                        }
-                    }
-                }
-            };
-        }
-        while let Some(ptr) = stack.pop() {
-            match ptr {
-                RawPtr::Atom(..) => (),
-                RawPtr::Hash4(idx) => {
-                    let ptrs = self.expect_raw_ptrs::<4>(*idx);
-                    for ptr in ptrs {
-                        feed_loop!(ptr)
-                    }
-                }
-                RawPtr::Hash6(idx) => {
-                    let ptrs = self.expect_raw_ptrs::<6>(*idx);
-                    for ptr in ptrs {
-                        feed_loop!(ptr)
-                    }
-                }
-                RawPtr::Hash8(idx) => {
-                    let ptrs = self.expect_raw_ptrs::<8>(*idx);
-                    for ptr in ptrs {
-                        feed_loop!(ptr)
-                    }
-                }
-            }
-        }
-        ptrs.reverse();
-        self.hydrate_z_cache_with_ptrs(&ptrs.into_iter().collect::<Vec<_>>());
+        self.hydrate_raw_ptrs(&[*ptr]);
         // Now it's okay to call `hash_raw_ptr_unsafe`
         self.hash_raw_ptr_unsafe(ptr)
     }
@@ -1037,6 +1743,333 @@ impl<F: LurkField> Store<F> {
     pub fn to_ptr(&self, z_ptr: &ZPtr<F>) -> Ptr {
         Ptr::new(*z_ptr.tag(), self.to_raw_ptr(&FWrap(*z_ptr.value())))
     }
+
+    /// Writes the entire interned content of the store to `path`, including the
+    /// hashes computed so far, such that `Store::load` can restore it without
+    /// reading or hashing any Lurk source
+    pub fn dump(&self, path: &Utf8Path) -> Result<()> {
+        let file = File::create(path).with_context(|| format!("creating {path}"))?;
+        self.dump_into(BufWriter::new(file))
+    }
+
+    /// Like `Store::dump`, but writes to `writer`, which may already hold other
+    /// data
+    pub fn dump_into<W: Write>(&self, mut writer: W) -> Result<()> {
+        let image = StoreImage {
+            f_elts: (0..)
+                .map_while(|idx| self.fetch_f(idx))
+                .map(|f| FWrap(*f))
+                .collect(),
+            hash4: (0..)
+                .map_while(|idx| self.fetch_raw_ptrs(idx))
+                .copied()
+                .collect(),
+            hash6: (0..)
+                .map_while(|idx| self.fetch_raw_ptrs(idx))
+                .copied()
+                .collect(),
+            hash8: (0..)
+                .map_while(|idx| self.fetch_raw_ptrs(idx))
+                .copied()
+                .collect(),
+            strings: self
+                .string_ptr_cache
+                .keys_cloned()
+                .into_iter()
+                .map(|string| {
+                    let ptr = *self
+                        .string_ptr_cache
+                        .get(&string)
+                        .expect("string is cached");
+                    (string, ptr)
+                })
+                .collect(),
+            symbols: self
+                .symbol_ptr_cache
+                .keys_cloned()
+                .into_iter()
+                .map(|sym| {
+                    let ptr = *self.symbol_ptr_cache.get(&sym).expect("symbol is cached");
+                    (sym, ptr)
+                })
+                .collect(),
+            comms: self
+                .comms
+                .keys_cloned()
+                .into_iter()
+                .map(|hash| {
+                    let (secret, payload) = self.comms.get(&hash).expect("commitment is known");
+                    (hash, FWrap(*secret), *payload)
+                })
+                .collect(),
+            z_cache: self
+                .z_cache
+                .keys_cloned()
+                .into_iter()
+                .map(|ptr| (ptr, *self.z_cache.get(&ptr).expect("hash is cached")))
+                .collect(),
+        };
+        writer.write_all(&STORE_IMAGE_MAGIC)?;
+        let header = StoreImageHeader {
+            version: STORE_IMAGE_VERSION,
+            field: F::FIELD,
+        };
+        bincode::serialize_into(&mut writer, &header)?;
+        bincode::serialize_into(writer, &image)?;
+        Ok(())
+    }
+
+    /// Restores a store written by `Store::dump`. Pointers from the dumped store
+    /// remain valid in the loaded one.
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let file = File::open(path).with_context(|| format!("opening {path}"))?;
+        Self::load_from(BufReader::new(file))
+            .with_context(|| format!("reading store image from {path}"))
+    }
+
+    /// Restores a store written by `Store::dump_into`
+    pub fn load_from<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader
+            .read_exact(&mut magic)
+            .context("reading the store image magic number")?;
+        ensure!(magic == STORE_IMAGE_MAGIC, "not a Lurk store image");
+        let header: StoreImageHeader = bincode::deserialize_from(&mut reader)?;
+        ensure!(
+            header.version == STORE_IMAGE_VERSION,
+            "unsupported store image version {} (expected {STORE_IMAGE_VERSION})",
+            header.version
+        );
+        ensure!(
+            header.field == F::FIELD,
+            "store image over {} can't be loaded into a store over {}",
+            header.field,
+            F::FIELD
+        );
+        let image: StoreImage<F> = bincode::deserialize_from(reader)?;
+
+        let store = Self::default();
+        for (idx, f) in image.f_elts.into_iter().enumerate() {
+            let (interned_idx, _) = store.f_elts.insert_probe(f);
+            ensure!(idx == interned_idx, "malformed store image");
+        }
+        let mut hash_ptrs = vec![];
+        macro_rules! load_hashes {
+            ($Hash:ident, $hash:ident, $n:expr) => {
+                for (idx, ptrs) in image.$hash.into_iter().enumerate() {
+                    let (ptr, _) = store.intern_raw_ptrs_internal::<$n>(ptrs);
+                    ensure!(ptr == RawPtr::$Hash(idx), "malformed store image");
+                    hash_ptrs.push(ptr);
+                }
+            };
+        }
+        load_hashes!(Hash4, hash4, 4);
+        load_hashes!(Hash6, hash6, 6);
+        load_hashes!(Hash8, hash8, 8);
+
+        for (ptr, z) in image.z_cache {
+            store.z_cache.insert(ptr, Box::new(z));
+            store.inverse_z_cache.insert(z, Box::new(ptr));
+        }
+        // what wasn't hashed before dumping is still pending
+        for ptr in hash_ptrs {
+            if store.z_cache.get(&ptr).is_none() {
+                store.dehydrated.load().push(Box::new(ptr));
+            }
+        }
+
+        for (string, ptr) in image.strings {
+            store.ptr_string_cache.insert(ptr, string.clone());
+            store.string_ptr_cache.insert(string, Box::new(ptr));
+        }
+        for (sym, ptr) in image.symbols {
+            store.ptr_symbol_cache.insert(ptr, Box::new(sym.clone()));
+            store.symbol_ptr_cache.insert(sym, Box::new(ptr));
+        }
+        for (hash, secret, payload) in image.comms {
+            store.add_comm(hash.0, secret.0, payload);
+        }
+        Ok(store)
+    }
+
+    /// Reports the current size of the store and interning statistics. Counting
+    /// the interned data takes time linear in the size of the tables.
+    pub fn metrics(&self) -> StoreMetrics {
+        let num_f_elts = (0..).take_while(|idx| self.fetch_f(*idx).is_some()).count();
+        let num_hash4 = (0..)
+            .take_while(|idx| self.fetch_raw_ptrs::<4>(*idx).is_some())
+            .count();
+        let num_hash6 = (0..)
+            .take_while(|idx| self.fetch_raw_ptrs::<6>(*idx).is_some())
+            .count();
+        let num_hash8 = (0..)
+            .take_while(|idx| self.fetch_raw_ptrs::<8>(*idx).is_some())
+            .count();
+        let arena_bytes = num_f_elts * std::mem::size_of::<FWrap<F>>()
+            + num_hash4 * std::mem::size_of::<[RawPtr; 4]>()
+            + num_hash6 * std::mem::size_of::<[RawPtr; 6]>()
+            + num_hash8 * std::mem::size_of::<[RawPtr; 8]>();
+        let interned_by_tag = self
+            .counters
+            .by_tag
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, count)| {
+                let count = count.load(Ordering::Relaxed);
+                (count > 0).then(|| (Tag::pos(idx).expect("valid tag index"), count))
+            })
+            .collect();
+        StoreMetrics {
+            interned_by_tag,
+            num_f_elts,
+            num_hash4,
+            num_hash6,
+            num_hash8,
+            arena_bytes,
+            num_hashed: self.z_cache.len(),
+            num_dehydrated: self.dehydrated.load().len(),
+            intern_hits: self.counters.hits.load(Ordering::Relaxed),
+            intern_misses: self.counters.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Interns the content of `raw` in `into`, registering every visited pointer
+    /// in `mapping`. Already hydrated pointers are interned along with their
+    /// hashes. The traversal is iterative, so long lists don't blow up the stack.
+    fn copy_raw_ptr(&self, into: &Store<F>, raw: &RawPtr, mapping: &mut PtrMapping) -> RawPtr {
+        let mut stack = vec![(*raw, false)];
+        macro_rules! copy {
+            ($n:expr, $ptr:expr, $idx:expr, $children_copied:expr) => {{
+                let children = self.expect_raw_ptrs::<$n>($idx);
+                if $children_copied {
+                    let children = children.map(|child| mapping.0[&child]);
+                    let copied = match self.z_cache.get(&$ptr) {
+                        Some(z) => into.intern_raw_ptrs_hydrated::<$n>(children, *z),
+                        None => into.intern_raw_ptrs::<$n>(children),
+                    };
+                    mapping.0.insert($ptr, copied);
+                } else {
+                    stack.push(($ptr, true));
+                    for child in children {
+                        if !mapping.0.contains_key(child) {
+                            stack.push((*child, false));
+                        }
+                    }
+                }
+            }};
+        }
+        while let Some((ptr, children_copied)) = stack.pop() {
+            if mapping.0.contains_key(&ptr) {
+                continue;
+            }
+            match ptr {
+                RawPtr::Atom(idx) => {
+                    let copied = into.intern_raw_atom(*self.expect_f(idx));
+                    mapping.0.insert(ptr, copied);
+                }
+                RawPtr::Hash4(idx) => copy!(4, ptr, idx, children_copied),
+                RawPtr::Hash6(idx) => copy!(6, ptr, idx, children_copied),
+                RawPtr::Hash8(idx) => copy!(8, ptr, idx, children_copied),
+            }
+        }
+        mapping.0[raw]
+    }
+
+    /// Interns in `into` the payloads of the commitments whose hashes are among
+    /// the atoms already in `mapping`. Since payloads may contain commitments
+    /// themselves, this is repeated until no new commitment is reached.
+    fn copy_reachable_comms(&self, into: &Store<F>, mapping: &mut PtrMapping) {
+        let mut pending = self.comms.keys_cloned();
+        loop {
+            let reached = mapping
+                .0
+                .keys()
+                .filter_map(RawPtr::get_atom)
+                .map(|idx| FWrap(*self.expect_f(idx)))
+                .collect::<HashSet<_>>();
+            let (reachable, unreachable): (Vec<_>, Vec<_>) =
+                pending.into_iter().partition(|hash| reached.contains(hash));
+            if reachable.is_empty() {
+                return;
+            }
+            for hash in reachable {
+                let (secret, payload) = self.comms.get(&hash).expect("commitment is known");
+                let payload_raw = self.copy_raw_ptr(into, payload.raw(), mapping);
+                into.add_comm(hash.0, *secret, Ptr::new(*payload.tag(), payload_raw));
+            }
+            pending = unreachable;
+        }
+    }
+
+    /// Garbage collects the store, retaining only the data reachable from
+    /// `roots`, the interned symbols and the openings of reachable commitments.
+    /// Internal tables are compacted, so previously held pointers become invalid
+    /// and must be rewritten with the returned mapping, which is defined for
+    /// every retained pointer (including the children of the roots).
+    ///
+    /// Hashes that were already computed are preserved. The compacted store
+    /// lives in memory, even if this one was opened with `Store::open`.
+    pub fn gc(&mut self, roots: &[Ptr]) -> PtrMapping {
+        let compacted = Store::default();
+        let mut mapping = PtrMapping::default();
+        for sym in self.symbol_ptr_cache.keys_cloned() {
+            let sym_ptr = self.symbol_ptr_cache.get(&sym).expect("symbol is cached");
+            self.copy_raw_ptr(&compacted, sym_ptr.raw(), &mut mapping);
+            compacted.intern_symbol(&sym);
+        }
+        for root in roots {
+            self.copy_raw_ptr(&compacted, root.raw(), &mut mapping);
+        }
+        self.copy_reachable_comms(&compacted, &mut mapping);
+        *self = compacted;
+        mapping
+    }
+
+    /// Merges the entire content of `other` into this store, deduplicating data
+    /// that's structurally equal. Stores built separately (e.g. one per worker
+    /// thread) can be combined this way, rewriting their pointers with the
+    /// returned mapping. Hashes, cached strings and symbols and commitments are
+    /// merged as well.
+    pub fn absorb(&self, other: &Store<F>) -> PtrMapping {
+        let mut mapping = PtrMapping::default();
+        let atoms = (0..)
+            .take_while(|idx| other.fetch_f(*idx).is_some())
+            .map(RawPtr::Atom);
+        let hash4 = (0..)
+            .take_while(|idx| other.fetch_raw_ptrs::<4>(*idx).is_some())
+            .map(RawPtr::Hash4);
+        let hash6 = (0..)
+            .take_while(|idx| other.fetch_raw_ptrs::<6>(*idx).is_some())
+            .map(RawPtr::Hash6);
+        let hash8 = (0..)
+            .take_while(|idx| other.fetch_raw_ptrs::<8>(*idx).is_some())
+            .map(RawPtr::Hash8);
+        for raw in atoms.chain(hash4).chain(hash6).chain(hash8) {
+            other.copy_raw_ptr(self, &raw, &mut mapping);
+        }
+
+        let mapped = |ptr: &Ptr| mapping.get(ptr).expect("all pointers were copied");
+        for string in other.string_ptr_cache.keys_cloned() {
+            let ptr = mapped(
+                other
+                    .string_ptr_cache
+                    .get(&string)
+                    .expect("string is cached"),
+            );
+            self.ptr_string_cache.insert(ptr, string.clone());
+            self.string_ptr_cache.insert(string, Box::new(ptr));
+        }
+        for sym in other.symbol_ptr_cache.keys_cloned() {
+            let ptr = mapped(other.symbol_ptr_cache.get(&sym).expect("symbol is cached"));
+            self.ptr_symbol_cache.insert(ptr, Box::new(sym.clone()));
+            self.symbol_ptr_cache.insert(sym, Box::new(ptr));
+        }
+        for hash in other.comms.keys_cloned() {
+            let (secret, payload) = other.comms.get(&hash).expect("commitment is known");
+            self.add_comm(hash.0, *secret, mapped(payload));
+        }
+        mapping
+    }
 }
 
 impl Ptr {
@@ -1125,6 +2158,26 @@ impl Ptr {
                         "<Malformed U64>".into()
                     }
                 }
+                I64 => {
+                    if let Some(u) = self
+                        .raw()
+                        .get_atom()
+                        .map(|idx| store.expect_f(idx))
+                        .and_then(F::to_u64)
+                    {
+                        format!("{}i64", u as i64)
+                    } else {
+                        "<Malformed I64>".into()
+                    }
+                }
+                Bytes => match store.fetch_bytes(self) {
+                    Some(bytes) => bytes_literal(&bytes),
+                    None => "<Opaque Bytes>".into(),
+                },
+                ExprTag::Ratio => match store.fetch_ratio(self) {
+                    Some(r) => format!("<RATIO {r}>"),
+                    None => "<Opaque Ratio>".into(),
+                },
                 Fun => match self.raw().get_hash8() {
                     None => "<Malformed Fun>".into(),
                     Some(idx) => {
@@ -1263,6 +2316,15 @@ impl Ptr {
                     store,
                     state,
                 ),
+                ContTag::Match => {
+                    self.fmt_cont3_to_string("Match", ("clauses", "saved_env"), store, state)
+                }
+                ContTag::MatchPattern => self.fmt_cont4_to_string(
+                    "MatchPattern",
+                    ("pattern", "value", "next"),
+                    store,
+                    state,
+                ),
             },
             Tag::Op1(op) => op.to_string(),
             Tag::Op2(op) => op.to_string(),
@@ -1353,18 +2415,22 @@ mod tests {
     use expect_test::expect;
     use ff::Field;
     use halo2curves::bn256::Fr;
+    use num_bigint::BigUint;
     use proptest::prelude::*;
 
     use crate::{
         field::LurkField,
         lem::Tag,
+        package::{Package, SymbolRef},
         parser::position::Pos,
-        state::{initial_lurk_state, lurk_sym},
+        state::{initial_lurk_state, lurk_sym, State},
         syntax::Syntax,
         tag::{ExprTag, Tag as TagTrait},
-        Num, Symbol,
+        Num, Ratio, Symbol,
     };
 
+    use camino::Utf8Path;
+
     use super::{Ptr, RawPtr, Store};
 
     #[test]
@@ -1570,6 +2636,12 @@ mod tests {
                 Pos::No,
                 crate::UInt::U64(store.expect_f(*idx).to_u64_unchecked()),
             ),
+            (Tag::Expr(ExprTag::I64), RawPtr::Atom(idx)) => {
+                Syntax::I64(Pos::No, store.expect_f(*idx).to_u64_unchecked() as i64)
+            }
+            (Tag::Expr(ExprTag::Bytes), RawPtr::Hash4(_)) => {
+                Syntax::Bytes(Pos::No, store.fetch_bytes(&ptr).unwrap())
+            }
             (Tag::Expr(ExprTag::Sym | ExprTag::Key), RawPtr::Atom(_) | RawPtr::Hash4(_)) => {
                 Syntax::Symbol(Pos::No, store.fetch_symbol(&ptr).unwrap().into())
             }
@@ -1595,6 +2667,346 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_gc() {
+        let mut store = Store::<Fr>::default();
+        let foo = store.intern_user_symbol("foo");
+        let hidden = store.list(vec![store.num_u64(4), store.num_u64(5)]);
+        let comm = store.commit(hidden);
+        let root = store.list(vec![store.num_u64(1), store.intern_string("bar"), comm]);
+        let root_z = store.hash_ptr(&root);
+        let garbage = store.list((6..100).map(|i| store.num_u64(i)).collect());
+        let garbage_idx = garbage.raw().get_hash4().unwrap();
+
+        let mapping = store.gc(&[root]);
+
+        // the root and its children are retained, keeping their hashes
+        let root = mapping.get(&root).unwrap();
+        assert_eq!(root_z, store.hash_ptr(&root));
+        let (elts, None) = store.fetch_list(&root).unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(Some("bar".to_string()), store.fetch_string(&elts[1]));
+        assert_eq!(mapping.get(&comm), Some(elts[2]));
+
+        // so are symbols and the openings of reachable commitments
+        assert_eq!(mapping.get(&foo), Some(store.intern_user_symbol("foo")));
+        let hash = *store.hash_ptr(&elts[2]).value();
+        let (_, opening) = store.open(hash).unwrap();
+        assert_eq!(mapping.get(&hidden), Some(*opening));
+
+        // but unreachable data is dropped
+        assert!(mapping.get(&garbage).is_none());
+        assert!(store.fetch_raw_ptrs::<4>(garbage_idx).is_none());
+    }
+
+    #[test]
+    fn test_dump_load() {
+        let store = Store::<Fr>::default();
+        let hidden = store.intern_string("secret");
+        let comm = store.hide(Fr::from_u64(42), hidden);
+        let expr = store
+            .read_with_default_state("(let ((foo (lambda (x) (* x 2)))) (foo 21))")
+            .unwrap();
+        let expr_z = store.hash_ptr(&expr);
+        let unhashed = store.cons(expr, comm);
+
+        let tmp_dir = tempfile::Builder::new().prefix("tmp").tempdir().unwrap();
+        let path = Utf8Path::from_path(tmp_dir.path()).unwrap().join("store");
+        store.dump(&path).unwrap();
+        let loaded = Store::<Fr>::load(&path).unwrap();
+
+        // pointers are valid across stores and hashes are already cached
+        assert!(loaded.z_cache.get(expr.raw()).is_some());
+        assert!(loaded.z_cache.get(unhashed.raw()).is_none());
+        assert_eq!(expr_z, loaded.hash_ptr(&expr));
+        assert_eq!(store.hash_ptr(&unhashed), loaded.hash_ptr(&unhashed));
+
+        assert_eq!(
+            expr,
+            loaded
+                .read_with_default_state("(let ((foo (lambda (x) (* x 2)))) (foo 21))")
+                .unwrap()
+        );
+        assert_eq!(Some("secret".to_string()), loaded.fetch_string(&hidden));
+        let hash = *loaded.hash_ptr(&comm).value();
+        assert_eq!(Some(&(Fr::from_u64(42), hidden)), loaded.open(hash));
+
+        // images of another field, version or format are rejected
+        fn err<T>(res: Result<T>) -> String {
+            format!("{:#}", res.err().expect("an error"))
+        }
+        assert!(err(Store::<pasta_curves::pallas::Scalar>::load(&path))
+            .contains("store image over BN256 can't be loaded into a store over Pallas"));
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[8] += 1;
+        assert!(
+            err(Store::<Fr>::load_from(&bytes[..])).contains("unsupported store image version 2")
+        );
+        bytes[0] = b'X';
+        assert!(err(Store::<Fr>::load_from(&bytes[..])).contains("not a Lurk store image"));
+    }
+
+    #[test]
+    fn test_concurrent_interning() {
+        use rayon::prelude::*;
+
+        let list = |store: &Store<Fr>, n: u64| {
+            let elts = (0..n).map(|i| store.num_u64(i)).collect();
+            store.cons(store.intern_user_symbol("list"), store.list(elts))
+        };
+
+        let sequential = Store::<Fr>::default();
+        let expected = (0..64)
+            .map(|n| sequential.hash_ptr(&list(&sequential, n)))
+            .collect::<Vec<_>>();
+
+        let store = Store::<Fr>::default();
+        let ptrs = (0..64)
+            .into_par_iter()
+            .map(|n| {
+                let ptr = list(&store, n);
+                if n % 8 == 0 {
+                    store.hydrate_z_cache();
+                }
+                (ptr, store.hash_ptr(&ptr))
+            })
+            .collect::<Vec<_>>();
+        for (n, (ptr, z)) in ptrs.iter().enumerate() {
+            assert_eq!(expected[n], *z);
+            // the same data interned again results in the same pointer
+            assert_eq!(*ptr, list(&store, n as u64));
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let store = Store::<Fr>::default();
+        let nums =
+            |store: &Store<Fr>| -> Vec<Ptr> { (1000..1010).map(|i| store.num_u64(i)).collect() };
+        let list = store.list(nums(&store));
+        let metrics = store.metrics();
+        let count = |metrics: &super::StoreMetrics, tag| {
+            metrics
+                .interned_by_tag
+                .iter()
+                .find_map(|(t, count)| (*t == tag).then_some(*count))
+                .unwrap_or(0)
+        };
+        assert_eq!(10, count(&metrics, Tag::Expr(ExprTag::Cons)));
+        assert_eq!(10, count(&metrics, Tag::Expr(ExprTag::Num)));
+        assert!(metrics.num_dehydrated >= 10);
+        assert!(metrics.arena_bytes > 0);
+
+        // interning the same data again only hits
+        store.list(nums(&store));
+        let again = store.metrics();
+        assert_eq!(metrics.intern_misses, again.intern_misses);
+        assert!(again.intern_hits >= metrics.intern_hits + 20);
+        assert_eq!(metrics.num_hash4, again.num_hash4);
+
+        store.hydrate_z_cache();
+        store.hash_ptr(&list);
+        let hydrated = store.metrics();
+        assert_eq!(0, hydrated.num_dehydrated);
+        assert!(hydrated.num_hashed >= 10);
+    }
+
+    #[test]
+    fn test_hydrate_ptrs() {
+        let store = Store::<Fr>::default();
+        let long_list = store.list((0..10000).map(|i| store.num_u64(i)).collect());
+        let tree = (0..12).fold(store.num_u64(0), |acc, _| store.cons(acc, acc));
+        let roots = [long_list, tree];
+        store.hydrate_ptrs(&roots);
+        for root in &roots {
+            assert!(store.z_cache.get(root.raw()).is_some());
+        }
+
+        // hydrating in parallel results in the same hashes
+        let other = Store::<Fr>::default();
+        let long_list2 = other.list((0..10000).map(|i| other.num_u64(i)).collect());
+        other.hydrate_z_cache();
+        assert_eq!(store.hash_ptr(&long_list), other.hash_ptr(&long_list2));
+    }
+
+    #[test]
+    fn test_try_accessors() {
+        let store = Store::<Fr>::default();
+        let one = store.num_u64(1);
+        let a = store.char('a');
+        let one_a = store.cons(one, a);
+
+        assert_eq!(Ok((one, a)), store.try_car_cdr(&one_a));
+        expect!["expected cons, nil or string, found 1"]
+            .assert_eq(&store.try_car_cdr(&one).unwrap_err().to_string());
+        expect!["expected symbol, found 'a'"]
+            .assert_eq(&store.try_fetch_sym(&a).unwrap_err().to_string());
+        expect!["expected string, found (1 . 'a')"]
+            .assert_eq(&store.try_fetch_string(&one_a).unwrap_err().to_string());
+        assert_eq!(Ok((vec![one], Some(a))), store.try_fetch_list(&one_a));
+
+        // a malformed cons
+        let opaque = one.cast(Tag::Expr(ExprTag::Cons));
+        assert!(matches!(
+            store.try_car_cdr(&opaque),
+            Err(super::StoreError::Malformed(_))
+        ));
+
+        // long data is cut short
+        let long = store.list((0..100).map(|i| store.num_u64(i)).collect());
+        expect!["expected string, found (0 1 2 3 4 5 6 7 ...)"]
+            .assert_eq(&store.try_fetch_string(&long).unwrap_err().to_string());
+        let long = store.intern_string(&"a".repeat(200));
+        let err = store.try_fetch_sym(&long).unwrap_err().to_string();
+        assert!(err.ends_with("..."));
+        assert!(err.len() < 120);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let store = Store::<Fr>::default();
+        for len in [0, 1, 30, 31, 32, 62, 63, 100] {
+            let bytes = (0..len).map(|i| (255 - i) as u8).collect::<Vec<_>>();
+            let ptr = store.intern_bytes(&bytes);
+            assert_eq!(Some(bytes.clone()), store.fetch_bytes(&ptr));
+            assert_eq!(ptr, store.intern_bytes(&bytes));
+        }
+
+        // trailing zeros are part of the content
+        assert_ne!(store.intern_bytes(&[1]), store.intern_bytes(&[1, 0]));
+
+        let ptr = store.read_with_default_state("b\"a\\\"b\\u{0}\"").unwrap();
+        assert_eq!(Some(b"a\"b\0".to_vec()), store.fetch_bytes(&ptr));
+        expect![[r#"b"a\"b\u{0}""#]].assert_eq(&ptr.fmt_to_string_simple(&store));
+
+        expect!["expected bytes, found \"ab\""].assert_eq(
+            &store
+                .try_fetch_bytes(&store.intern_string("ab"))
+                .unwrap_err()
+                .to_string(),
+        );
+    }
+
+    #[test]
+    fn test_ratio() {
+        let store = Store::<Fr>::default();
+        let big = BigUint::from(u64::MAX) * 3u64 + 1u64;
+        for (negative, numer, denom) in [
+            (false, 0u64.into(), 1u64.into()),
+            (true, 1u64.into(), 2u64.into()),
+            (false, big.clone(), 7u64.into()),
+            (true, 5u64.into(), big),
+        ] {
+            let ratio = Ratio::from_parts(negative, numer, denom).unwrap();
+            let ptr = store.intern_ratio(&ratio);
+            assert_eq!(Some(ratio.clone()), store.fetch_ratio(&ptr));
+            assert_eq!(ptr, store.intern_ratio(&ratio));
+        }
+
+        let half = Ratio::new((-1).into(), 2.into()).unwrap();
+        expect!["<RATIO -1/2>"].assert_eq(&store.intern_ratio(&half).fmt_to_string_simple(&store));
+
+        // non-normalized parts are rejected
+        let two = store.intern_biguint(&2u64.into());
+        let ptr = intern_ptrs!(store, Tag::Expr(ExprTag::Ratio), store.u64(0), two, two);
+        assert!(store.fetch_ratio(&ptr).is_none());
+        assert!(store.try_fetch_ratio(&store.num_u64(1)).is_err());
+    }
+
+    #[test]
+    fn test_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = Utf8Path::from_path(dir.path()).unwrap();
+        let src = "(let ((x (cons 1 \"abc\"))) (cons x x))";
+        let (ptr, z_ptr) = {
+            let store = Store::<Fr>::open(dir).unwrap();
+            let ptr = store.read_with_default_state(src).unwrap();
+            store.flush().unwrap();
+            (ptr, store.hash_ptr(&ptr))
+        };
+
+        let store = Store::<Fr>::open(dir).unwrap();
+        assert_eq!(z_ptr, store.hash_ptr(&ptr));
+        assert_eq!(ptr, store.read_with_default_state(src).unwrap());
+        expect![[r#"(let ((x (cons 1 "abc"))) (cons x x))"#]]
+            .assert_eq(&ptr.fmt_to_string_simple(&store));
+    }
+
+    #[test]
+    fn test_read_in_package() {
+        let store = Store::<Fr>::default();
+        let state = State::init_lurk_state().rccell();
+        let app_name = SymbolRef::new(Symbol::sym(&["app"]));
+        let lurk = SymbolRef::new(Symbol::sym(&["lurk"]));
+        let mut app = Package::new(app_name.clone());
+        app.use_package(state.borrow().get_package(&lurk).unwrap())
+            .unwrap();
+        state.borrow_mut().define_package(app).unwrap();
+        assert!(state
+            .borrow_mut()
+            .define_package(Package::new(app_name.clone()))
+            .is_err());
+
+        let ptr = store
+            .read_in_package(state.clone(), &app_name, "(cons factorial 1)")
+            .unwrap();
+        let expected = store.list(vec![
+            store.intern_symbol(&lurk_sym("cons")),
+            store.intern_symbol(&Symbol::sym(&["app", "factorial"])),
+            store.num_u64(1),
+        ]);
+        assert_eq!(expected, ptr);
+        assert_eq!(
+            &SymbolRef::new(Symbol::sym(&["lurk", "user"])),
+            state.borrow().get_current_package_name()
+        );
+    }
+
+    #[test]
+    fn test_absorb() {
+        use rayon::prelude::*;
+
+        let sources = [
+            "(cons 1 (cons 2 nil))",
+            "(let ((x 1)) (cons x \"shared\"))",
+            "(lambda (y) \"shared\")",
+        ];
+        let built = sources
+            .as_slice()
+            .par_iter()
+            .map(|source| {
+                let store = Store::<Fr>::default();
+                let ptr = store.read_with_default_state(source).unwrap();
+                let comm = store.commit(ptr);
+                store.hydrate_z_cache();
+                (store, ptr, comm)
+            })
+            .collect::<Vec<_>>();
+
+        let merged = Store::<Fr>::default();
+        let shared = merged.intern_string("shared");
+        for (store, ptr, comm) in &built {
+            let mapping = merged.absorb(store);
+            let merged_ptr = mapping.get(ptr).unwrap();
+            assert_eq!(store.hash_ptr(ptr), merged.hash_ptr(&merged_ptr));
+            assert_eq!(
+                ptr.fmt_to_string_simple(store),
+                merged_ptr.fmt_to_string_simple(&merged)
+            );
+            let hash = *merged.hash_ptr(&mapping.get(comm).unwrap()).value();
+            assert_eq!(merged_ptr, merged.open(hash).unwrap().1);
+        }
+
+        // structurally equal data is deduplicated
+        assert_eq!(shared, merged.intern_string("shared"));
+        let (_, ptr, _) = &built[0];
+        assert_eq!(
+            merged.read_with_default_state(sources[0]).unwrap(),
+            merged.absorb(&built[0].0).get(ptr).unwrap()
+        );
+    }
+
     proptest! {
         #[test]
         fn syntax_roundtrip(x in any::<Syntax<Fr>>()) {
@@ -7,7 +7,8 @@ use crate::{
     eval::lang::{Coproc, Lang},
     lem::{
         eval::{
-            evaluate_simple, make_cprocs_funcs_from_lang, make_eval_step_from_config, EvalConfig,
+            evaluate_partial, evaluate_simple, make_cprocs_funcs_from_lang,
+            make_eval_step_from_config, resume, EvalConfig, Status,
         },
         pointers::Ptr,
         store::Store,
@@ -3859,6 +3860,88 @@ fn test_trie_lang() {
         &expect!["13"],
         &Some(&lang),
     );
+
+    let expr7 =
+        "(.lurk.trie.delete 0x21ad1dd339f26bb824ab861dbcf110c1bcb3b7658eea4b5e84780a3b4958bf95 123)";
+    let res7 = s
+        .read_with_default_state(
+            "0x2bfc4f437d5ca652511d67e06201b4fdf95c314c85ea987988746a253071bed6",
+        )
+        .unwrap();
+
+    test_aux_with_state(
+        s,
+        state.clone(),
+        expr7,
+        Some(res7),
+        None,
+        None,
+        None,
+        &expect!["3"],
+        &Some(&lang),
+    );
+}
+
+#[test]
+fn test_i64_lang() {
+    use crate::coprocessor::int::{install, I64Coproc};
+
+    let s = &Store::<Fr>::default();
+    let state = State::init_lurk_state().rccell();
+    let mut lang = Lang::<Fr, I64Coproc<Fr>>::new();
+
+    install(&state, &mut lang);
+
+    test_aux_with_state(
+        s,
+        state.clone(),
+        "(.lurk.i64.add 1i64 -3i64)",
+        Some(s.i64(-2)),
+        None,
+        None,
+        None,
+        &expect!["3"],
+        &Some(&lang),
+    );
+
+    test_aux_with_state(
+        s,
+        state,
+        "(.lurk.i64.lt -9223372036854775808i64 0i64)",
+        Some(s.intern_t()),
+        None,
+        None,
+        None,
+        &expect!["3"],
+        &Some(&lang),
+    );
+}
+
+#[test]
+fn test_ratio_lang() {
+    use crate::{
+        coprocessor::ratio::{install, RatioCoproc},
+        Ratio,
+    };
+
+    let s = &Store::<Fr>::default();
+    let state = State::init_lurk_state().rccell();
+    let mut lang = Lang::<Fr, RatioCoproc<Fr>>::new();
+
+    install(&state, &mut lang);
+
+    let half = Ratio::new((-1).into(), 2.into()).unwrap();
+    test_aux_with_state(
+        s,
+        state,
+        "(.lurk.ratio.new 3i64 -6i64)",
+        Some(s.intern_ratio(&half)),
+        None,
+        None,
+        None,
+        &expect!["3"],
+        &Some(&lang),
+    );
 }
 
 #[test]
@@ -3888,3 +3971,165 @@ fn test_terminator_lang() {
         &Some(&lang),
     );
 }
+
+#[test]
+fn test_quasiquote() {
+    let s = &Store::<Fr>::default();
+    let eval = |expr: &str| {
+        let expr = s.read_with_default_state(expr).unwrap();
+        let (output, ..) = evaluate_simple::<Fr, Coproc<Fr>>(None, expr, s, 100000).unwrap();
+        assert_eq!(output[2], s.cont_terminal());
+        output[0]
+    };
+
+    let cases = [
+        ("`(a b)", "'(a b)"),
+        ("(let ((x 1)) `(a ,x))", "'(a 1)"),
+        ("(let ((x 1)) `(a ,x . ,(+ x 1)))", "'(a 1 . 2)"),
+        ("(let ((xs '(1 2))) `(a ,@xs b ,@xs))", "'(a 1 2 b 1 2)"),
+        ("`(a ,@nil)", "'(a)"),
+        ("`(factorial . ,(* 2 3))", "'(factorial . 6)"),
+        // quoted quasiquotes are data
+        ("'`(a ,b)", "'(quasiquote (a (unquote b)))"),
+        // only the innermost unquote is at depth 1
+        (
+            "(let ((x 1)) `(a `(b ,(c ,x))))",
+            "'(a (quasiquote (b (unquote (c 1)))))",
+        ),
+        (
+            "(let ((x 1)) `(a `(b ,,x)))",
+            "'(a (quasiquote (b (unquote 1))))",
+        ),
+    ];
+    for (expr, expected) in cases {
+        let expected = eval(expected);
+        let result = eval(expr);
+        assert!(
+            s.ptr_eq(&result, &expected),
+            "{expr}: {} != {}",
+            result.fmt_to_string_simple(s),
+            expected.fmt_to_string_simple(s)
+        );
+    }
+
+    // unquote-splicing outside of a list
+    let expr = s.read_with_default_state("`,@'(1 2)").unwrap();
+    let (output, ..) = evaluate_simple::<Fr, Coproc<Fr>>(None, expr, s, 100000).unwrap();
+    assert_eq!(output[2], s.cont_error());
+}
+
+#[test]
+fn test_match() {
+    let s = &Store::<Fr>::default();
+    let run = |expr: &str| {
+        let expr = s.read_with_default_state(expr).unwrap();
+        let (output, ..) = evaluate_simple::<Fr, Coproc<Fr>>(None, expr, s, 100000).unwrap();
+        output
+    };
+    let eval = |expr: &str| {
+        let output = run(expr);
+        assert_eq!(output[2], s.cont_terminal(), "{expr}");
+        output[0]
+    };
+    let assert_eval = |expr: &str, expected: &str| {
+        let (result, expected) = (eval(expr), eval(expected));
+        assert!(
+            s.ptr_eq(&result, &expected),
+            "{expr}: {} != {}",
+            result.fmt_to_string_simple(s),
+            expected.fmt_to_string_simple(s)
+        );
+    };
+
+    let shape = "(lambda (x)
+                   (.lurk.match x
+                     (nil 'empty)
+                     (0 'zero)
+                     (\"s\" 'string)
+                     ('sym 'symbol)
+                     ((a) `(one ,a))
+                     ((a b . _) `(two-or-more ,a ,b))
+                     ((a . 7) `(pair ,a))
+                     (_ 'other)))";
+    let cases = [
+        ("nil", "'empty"),
+        ("0", "'zero"),
+        ("\"s\"", "'string"),
+        ("'sym", "'symbol"),
+        ("'(1)", "'(one 1)"),
+        ("'(1 2)", "'(two-or-more 1 2)"),
+        ("'(1 (2 3) 4)", "'(two-or-more 1 (2 3))"),
+        ("'(1 . 7)", "'(pair 1)"),
+        ("'(1 . 8)", "'other"),
+        ("\"abc\"", "'other"),
+        ("'other-sym", "'other"),
+    ];
+    for (arg, expected) in cases {
+        assert_eval(&format!("({shape} {arg})"), expected);
+    }
+
+    // nested patterns and variables bound in the enclosing scope
+    assert_eval(
+        "(let ((y 10)) (.lurk.match '((1 . 2) 3) (((a . b) c) `(,a ,b ,c ,y))))",
+        "'(1 2 3 10)",
+    );
+    // the bindings of a clause that fails don't leak into the next one
+    assert_eval(
+        "(let ((a 5)) (.lurk.match '(1 2) ((a 3) a) ((b c) `(,a ,c))))",
+        "'(5 2)",
+    );
+    // quoted patterns are literals, even inside a cons pattern
+    assert_eval("(.lurk.match '(1 a) (('1 'b) 'no) (('1 'a) 'yes))", "'yes");
+
+    // `match` isn't in the `lurk` package by default, so its symbol remains free for user code
+    assert_eval("(let ((match (lambda (x) (+ x 1)))) (match 1))", "2");
+
+    // no clause matches, and malformed forms and clauses
+    for expr in [
+        "(.lurk.match 1 (2 'two))",
+        "(.lurk.match 1)",
+        "(.lurk.match 1 2)",
+        "(.lurk.match 1 (1))",
+        "(.lurk.match 1 (1 2 3))",
+        "(.lurk.match 1 (2 2) . 3)",
+        "(.lurk.match 1 ((quote 1 2) 'x))",
+    ] {
+        assert_eq!(run(expr)[2], s.cont_error(), "{expr}");
+    }
+}
+
+#[test]
+fn test_evaluate_partial() {
+    let s = &Store::<Fr>::default();
+    let expr = s
+        .read_with_default_state(
+            "(letrec ((fact (lambda (n) (if (= n 0) 1 (* n (fact (- n 1)))))))
+               (fact 5))",
+        )
+        .unwrap();
+    let (output, iterations, _) = evaluate_simple::<Fr, Coproc<Fr>>(None, expr, s, 100000).unwrap();
+
+    let max_iterations = 7;
+    let (mut status, mut snapshot) =
+        evaluate_partial::<Fr, Coproc<Fr>>(None, expr, s.intern_empty_env(), s, max_iterations)
+            .unwrap();
+    let mut parts = 1;
+    while status == Status::Incomplete {
+        (status, snapshot) = resume::<Fr, Coproc<Fr>>(None, &snapshot, s, max_iterations).unwrap();
+        parts += 1;
+    }
+    assert_eq!(status, Status::Terminal);
+    assert_eq!(parts, iterations.div_ceil(max_iterations));
+    assert_eq!(snapshot.to_ptrs(s).to_vec(), output);
+
+    // resuming a finished evaluation doesn't change it
+    let (status, resumed) = resume::<Fr, Coproc<Fr>>(None, &snapshot, s, max_iterations).unwrap();
+    assert_eq!(status, Status::Terminal);
+    assert_eq!(resumed, snapshot);
+
+    let expr = s.read_with_default_state("(car 1)").unwrap();
+    let (status, snapshot) =
+        evaluate_partial::<Fr, Coproc<Fr>>(None, expr, s.intern_empty_env(), s, 10).unwrap();
+    assert_eq!(status, Status::Error);
+    assert_eq!(snapshot.status(), Status::Error);
+}
@@ -91,3 +91,36 @@ fn test_nivc_steps() {
     let cproc_input = vec![new_expr, env, cont];
     assert!(cproc.call_simple(&cproc_input, &store, &lang, 0).is_err());
 }
+
+#[test]
+fn test_batched_nivc_steps() {
+    let mut lang = Lang::<Fr, DumbCoprocessor<Fr>>::new();
+    lang.add_coprocessor(user_sym("cproc-dumb"), DumbCoprocessor::new());
+    lang.set_batch_size(2);
+
+    // the batchable coprocessor gets a batched version, last
+    let batch = user_sym("cproc-dumb").direct_child("batch");
+    assert_eq!(lang.coprocessor_count(), 2);
+    assert_eq!(lang.get_index_by_symbol(&batch), Some(1));
+    let lurk_step = make_eval_step_from_config(&EvalConfig::new_nivc(&lang));
+    let cprocs = make_cprocs_funcs_from_lang(&lang);
+    assert_eq!(cprocs.len(), 2);
+
+    // 9^2 + 8 = 89 and 1^2 + 2 = 3
+    let store = Store::<Fr>::default();
+    let expr = store
+        .read_with_default_state("(.lurk.user.cproc-dumb.batch '((9 8) (1 2)))")
+        .unwrap();
+    let expected = store.list(vec![store.num_u64(89), store.num_u64(3)]);
+
+    let frames = evaluate(Some((&lurk_step, &cprocs, &lang)), expr, &store, 10).unwrap();
+    assert!(frames.iter().any(|frame| frame.pc == 2));
+    let output = &frames.last().unwrap().output;
+    assert_eq!(output[0], expected);
+    assert!(matches!(output[2].tag(), Tag::Cont(ContTag::Terminal)));
+
+    // the IVC step calls it too
+    let lurk_step = make_eval_step_from_config(&EvalConfig::new_ivc(&lang));
+    let frames = evaluate(Some((&lurk_step, &[], &lang)), expr, &store, 10).unwrap();
+    assert_eq!(frames.last().unwrap().output[0], expected);
+}
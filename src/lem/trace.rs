@@ -0,0 +1,194 @@
+//! Traces of evaluations, reporting how many frames an evaluation takes and how deep its continuation gets.
+//!
+//! The evaluator is properly tail-recursive: the body of an applied function, the branches of an `if` and the bodies
+//! of `let` and `letrec` are evaluated with the continuation of the expression they stand for, so nothing is pushed
+//! for calls in tail position. An iterative program, one that only recurses in tail position, thus runs with a
+//! continuation of bounded depth however many iterations it makes, and its number of frames (the steps to prove)
+//! grows with its iterations only.
+
+use anyhow::Result;
+
+use crate::{coprocessor::Coprocessor, eval::lang::Lang, field::LurkField, tag::ContTag};
+
+use super::{
+    eval::evaluate,
+    interpreter::Frame,
+    pointers::Ptr,
+    store::{fetch_ptrs, Store},
+    Func, Tag,
+};
+
+/// Where the parent continuation is among the four pointers of a continuation, or `None` if it has no parent
+pub(crate) fn parent_position(tag: ContTag) -> Option<usize> {
+    match tag {
+        ContTag::Emit => Some(0),
+        ContTag::Unop => Some(1),
+        ContTag::Call | ContTag::Binop2 | ContTag::If | ContTag::Match => Some(2),
+        ContTag::Call2
+        | ContTag::Let
+        | ContTag::LetRec
+        | ContTag::Binop
+        | ContTag::Cproc
+        | ContTag::MatchPattern => Some(3),
+        // `Call0`, `Tail` and `Lookup` aren't built by the evaluator
+        ContTag::Outermost
+        | ContTag::Terminal
+        | ContTag::Error
+        | ContTag::Dummy
+        | ContTag::Call0
+        | ContTag::Tail
+        | ContTag::Lookup => None,
+    }
+}
+
+/// The number of pending continuation frames in `cont`, not counting the outermost one
+pub fn cont_depth<F: LurkField>(cont: &Ptr, store: &Store<F>) -> usize {
+    let mut depth = 0;
+    let mut cont = *cont;
+    while let Tag::Cont(tag) = cont.tag() {
+        let Some(position) = parent_position(*tag) else {
+            break;
+        };
+        let Some(ptrs) = cont
+            .raw()
+            .get_hash8()
+            .and_then(|idx| fetch_ptrs!(store, 4, idx))
+        else {
+            break;
+        };
+        depth += 1;
+        cont = ptrs[position];
+    }
+    depth
+}
+
+/// A step of an evaluation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The tag of the continuation the step produced
+    pub cont_tag: ContTag,
+    /// The number of pending continuation frames after the step
+    pub cont_depth: usize,
+}
+
+/// The steps of an evaluation, one per frame
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl EvalTrace {
+    pub fn from_frames<F: LurkField>(frames: &[Frame], store: &Store<F>) -> Self {
+        let steps = frames
+            .iter()
+            .map(|frame| {
+                let (expr, cont) = (&frame.output[0], &frame.output[2]);
+                let Tag::Cont(cont_tag) = *cont.tag() else {
+                    panic!("the third output of a frame must be a continuation")
+                };
+                // a thunk holds the continuation the evaluation resumes with
+                let thunk_cont = (cont_tag == ContTag::Dummy)
+                    .then(|| {
+                        expr.raw()
+                            .get_hash4()
+                            .and_then(|idx| fetch_ptrs!(store, 2, idx))
+                    })
+                    .flatten()
+                    .map(|[_, cont]| cont);
+                TraceStep {
+                    cont_tag,
+                    cont_depth: cont_depth(thunk_cont.as_ref().unwrap_or(cont), store),
+                }
+            })
+            .collect();
+        Self { steps }
+    }
+
+    /// The number of frames of the evaluation, which is the number of steps to prove
+    pub fn frame_count(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// The deepest continuation reached by the evaluation
+    pub fn max_cont_depth(&self) -> usize {
+        self.steps
+            .iter()
+            .map(|step| step.cont_depth)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Evaluates `expr` as `evaluate` does, returning the trace of the evaluation
+pub fn evaluate_traced<F: LurkField, C: Coprocessor<F>>(
+    lang_setup: Option<(&Func, &[Func], &Lang<F, C>)>,
+    expr: Ptr,
+    store: &Store<F>,
+    limit: usize,
+) -> Result<EvalTrace> {
+    let frames = evaluate(lang_setup, expr, store, limit)?;
+    Ok(EvalTrace::from_frames(&frames, store))
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr;
+
+    use super::*;
+    use crate::eval::lang::Coproc;
+
+    fn trace(store: &Store<Fr>, src: &str) -> EvalTrace {
+        let expr = store.read_with_default_state(src).unwrap();
+        let trace = evaluate_traced::<Fr, Coproc<Fr>>(None, expr, store, 100000).unwrap();
+        assert_eq!(
+            trace.steps.last().map(|step| step.cont_tag),
+            Some(ContTag::Terminal)
+        );
+        trace
+    }
+
+    #[test]
+    fn test_tail_calls_dont_grow_the_continuation() {
+        let store = Store::<Fr>::default();
+        let tail = |n| {
+            trace(
+                &store,
+                &format!(
+                    "(letrec ((loop (lambda (n acc) (if (= n 0) acc (loop (- n 1) (+ acc 1))))))
+                       (loop {n} 0))"
+                ),
+            )
+        };
+        let non_tail = |n| {
+            trace(
+                &store,
+                &format!(
+                    "(letrec ((sum (lambda (n) (if (= n 0) 0 (+ n (sum (- n 1)))))))
+                       (sum {n}))"
+                ),
+            )
+        };
+
+        let (tail_10, tail_40) = (tail(10), tail(40));
+        assert_eq!(tail_10.max_cont_depth(), tail_40.max_cont_depth());
+        // frames grow linearly with the iterations
+        let per_iteration = (tail_40.frame_count() - tail_10.frame_count()) / 30;
+        assert_eq!(
+            tail_10.frame_count() - 10 * per_iteration,
+            tail_40.frame_count() - 40 * per_iteration
+        );
+
+        let (non_tail_10, non_tail_40) = (non_tail(10), non_tail(40));
+        assert!(non_tail_40.max_cont_depth() >= non_tail_10.max_cont_depth() + 30);
+    }
+
+    #[test]
+    fn test_cont_depth() {
+        let store = Store::<Fr>::default();
+        assert_eq!(0, cont_depth(&store.cont_outermost(), &store));
+        assert_eq!(0, cont_depth(&store.cont_terminal(), &store));
+        // `(+ 1 2)` is evaluated with the outer addition pending, and `1` with both additions pending
+        let trace = trace(&store, "(+ (+ 1 2) 3)");
+        assert_eq!(trace.max_cont_depth(), 2);
+    }
+}
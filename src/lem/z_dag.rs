@@ -1,23 +1,28 @@
+//! `ZDag`s, portable graphs of Lurk data keyed by `ZPtr`s.
+//!
+//! A `ZDag` holds every node reachable from the `ZPtr`s it was populated with, without any reference to `Store`
+//! indices, so it can be serialized, shipped and interned again in another store (see `ZDag::extract` and
+//! `ZDag::import`).
+
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 use crate::{
-    field::{FWrap, LurkField},
-    lem::{
-        pointers::{Ptr, RawPtr, ZPtr},
-        store::{expect_ptrs, intern_ptrs_hydrated, Store},
-        tag::Tag,
-    },
+    field::LurkField,
     tag::ExprTag::{Env, Sym},
 };
 
-use super::field_data::HasFieldModulus;
+use super::{
+    pointers::{Ptr, RawPtr, ZPtr},
+    store::{expect_ptrs, intern_ptrs_hydrated, Store},
+    tag::Tag,
+};
 
 /// `ZPtrType` holds information about the `Ptr` that originated a certain `ZPtr`.
 /// If the `Ptr` was not atomic, `ZPtrType` can refer to its children once they
 /// have already been turned into `ZPtr`s.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) enum ZPtrType<F: LurkField> {
     Atom,
     Tuple2(ZPtr<F>, ZPtr<F>),
@@ -26,11 +31,108 @@ pub(crate) enum ZPtrType<F: LurkField> {
     Env(ZPtr<F>, ZPtr<F>, ZPtr<F>),
 }
 
-/// Holds a mapping from `ZPtr`s to their `ZPtrType`s
+impl<F: LurkField> ZPtrType<F> {
+    fn children(&self) -> Vec<&ZPtr<F>> {
+        match self {
+            Self::Atom => vec![],
+            Self::Tuple2(a, b) => vec![a, b],
+            Self::Tuple3(a, b, c) | Self::Env(a, b, c) => vec![a, b, c],
+            Self::Tuple4(a, b, c, d) => vec![a, b, c, d],
+        }
+    }
+}
+
+/// The digests of the nodes of a `ZDag`, against which deltas are computed.
+/// It's much smaller than the dag itself, so it can be kept around by whoever
+/// already has the base data.
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ZDagManifest<F: LurkField>(BTreeSet<ZPtr<F>>);
+
+impl<F: LurkField> ZDagManifest<F> {
+    #[inline]
+    pub fn contains(&self, z_ptr: &ZPtr<F>) -> bool {
+        self.0.contains(z_ptr)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Holds a mapping from `ZPtr`s to their `ZPtrType`s. Since it doesn't refer to
+/// any `Store` indices, it's a portable representation of Lurk data that can be
+/// exchanged across machines (e.g. between provers and verifiers).
 #[derive(Debug, Default, Serialize, Deserialize)]
-pub(crate) struct ZDag<F: LurkField>(BTreeMap<ZPtr<F>, ZPtrType<F>>);
+pub struct ZDag<F: LurkField>(pub(crate) BTreeMap<ZPtr<F>, ZPtrType<F>>);
 
 impl<F: LurkField> ZDag<F> {
+    /// Extracts the closed subgraph of the data reachable from `z_ptrs`, which
+    /// should have been hashed by `store`. Data unknown to `store` is extracted as
+    /// opaque atoms.
+    pub fn extract(store: &Store<F>, z_ptrs: &[ZPtr<F>]) -> Self {
+        let mut z_dag = Self::default();
+        let mut cache = HashMap::default();
+        for z_ptr in z_ptrs {
+            z_dag.populate_with(&store.to_ptr(z_ptr), store, &mut cache);
+        }
+        z_dag
+    }
+
+    /// Interns the data reachable from `z_ptr` in `store`, returning its `Ptr`.
+    /// Fails if `z_ptr` isn't in the dag.
+    pub fn import(&self, z_ptr: &ZPtr<F>, store: &Store<F>) -> Result<Ptr> {
+        self.populate_store(z_ptr, store, &mut HashMap::default())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The digests of every node in the dag
+    pub fn manifest(&self) -> ZDagManifest<F> {
+        ZDagManifest(self.0.keys().copied().collect())
+    }
+
+    /// The nodes of the dag that aren't in `baseline`. The result isn't closed:
+    /// it's meant to be layered, with `ZDag::layer`, onto a dag with the nodes
+    /// of `baseline`.
+    pub fn delta(&self, baseline: &ZDagManifest<F>) -> Self {
+        Self(
+            self.0
+                .iter()
+                .filter(|(z_ptr, _)| !baseline.contains(z_ptr))
+                .map(|(z_ptr, z_ptr_type)| (*z_ptr, z_ptr_type.clone()))
+                .collect(),
+        )
+    }
+
+    /// Adds the nodes of `delta` to the dag. Fails, leaving the dag untouched, if
+    /// a node of `delta` refers to data that's in neither of them, which happens
+    /// when `delta` was computed against a baseline with data missing here.
+    pub fn layer(&mut self, delta: Self) -> Result<()> {
+        for z_ptr_type in delta.0.values() {
+            for child in z_ptr_type.children() {
+                if !self.0.contains_key(child) && !delta.0.contains_key(child) {
+                    bail!("The delta refers to data missing from the base dag")
+                }
+            }
+        }
+        self.0.extend(delta.0);
+        Ok(())
+    }
+
     pub(crate) fn populate_with(
         &mut self,
         ptr: &Ptr,
@@ -138,7 +240,7 @@ impl<F: LurkField> ZDag<F> {
         recurse(ptr)
     }
 
-    fn get_type(&self, z_ptr: &ZPtr<F>) -> Option<&ZPtrType<F>> {
+    pub(crate) fn get_type(&self, z_ptr: &ZPtr<F>) -> Option<&ZPtrType<F>> {
         self.0.get(z_ptr)
     }
 
@@ -253,147 +355,14 @@ impl<F: LurkField> ZDag<F> {
     }
 }
 
-/// A `ZStore` is a stable IO format for `Store`, without index-based references
-#[derive(Debug, Default, Serialize, Deserialize)]
-pub(crate) struct ZStore<F: LurkField> {
-    z_dag: ZDag<F>,
-    comms: BTreeMap<FWrap<F>, (F, ZPtr<F>)>,
-}
-
-impl<F: LurkField> HasFieldModulus for ZStore<F> {
-    fn field_modulus() -> String {
-        F::MODULUS.to_owned()
-    }
-}
-
-impl<F: LurkField> ZStore<F> {
-    #[inline]
-    pub(crate) fn add_comm(&mut self, hash: F, secret: F, payload: ZPtr<F>) {
-        self.comms.insert(FWrap(hash), (secret, payload));
-    }
-
-    #[inline]
-    pub(crate) fn open(&self, hash: F) -> Option<&(F, ZPtr<F>)> {
-        self.comms.get(&FWrap(hash))
-    }
-
-    pub(crate) fn to_store(&self) -> Result<Store<F>> {
-        let store = Store::default();
-        let mut cache = HashMap::default();
-        for z_ptr in self.z_dag.0.keys() {
-            self.populate_store(z_ptr, &store, &mut cache)?;
-        }
-        for (hash, (secret, z_payload)) in &self.comms {
-            let payload = self.populate_store(z_payload, &store, &mut cache)?;
-            store.add_comm(hash.0, *secret, payload);
-        }
-        Ok(store)
-    }
-
-    #[inline]
-    pub(crate) fn populate_with(
-        &mut self,
-        ptr: &Ptr,
-        store: &Store<F>,
-        cache: &mut HashMap<Ptr, ZPtr<F>>,
-    ) -> ZPtr<F> {
-        self.z_dag.populate_with(ptr, store, cache)
-    }
-
-    pub(crate) fn populate_store(
-        &self,
-        z_ptr: &ZPtr<F>,
-        store: &Store<F>,
-        cache: &mut HashMap<ZPtr<F>, Ptr>,
-    ) -> Result<Ptr> {
-        self.z_dag.populate_store(z_ptr, store, cache)
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use halo2curves::bn256::Fr as Bn;
-    use rand::{rngs::StdRng, Rng};
-    use rand_core::SeedableRng;
-    use rayon::prelude::{IntoParallelIterator, ParallelIterator};
     use std::collections::HashMap;
 
-    use crate::{
-        field::LurkField,
-        lem::{
-            pointers::Ptr,
-            store::{intern_ptrs, Store},
-            tag::Tag,
-        },
-        tag::{ContTag, ExprTag, Op1, Op2},
-    };
-
-    use super::{ZDag, ZStore};
-
-    /// helper function that interns random data into a store
-    fn rng_interner(rng: &mut StdRng, max_depth: usize, store: &Store<Bn>) -> Ptr {
-        let rnd = rng.gen::<u64>();
-        let tag = match rnd % 4 {
-            0 => Tag::Expr(ExprTag::try_from((rnd % 11) as u16).unwrap()),
-            1 => Tag::Cont(ContTag::try_from((rnd % 17) as u16 + 4096).unwrap()),
-            2 => Tag::Op1(Op1::try_from((rnd % 12) as u16 + 8192).unwrap()),
-            3 => Tag::Op2(Op2::try_from((rnd % 16) as u16 + 12288).unwrap()),
-            _ => unreachable!(),
-        };
-        if max_depth == 0 {
-            store.intern_atom(tag, Bn::from_u64(rnd))
-        } else {
-            match rnd % 4 {
-                0 => store.intern_atom(tag, Bn::from_u64(rnd)),
-                1 => intern_ptrs!(
-                    store,
-                    tag,
-                    rng_interner(rng, max_depth - 1, store),
-                    rng_interner(rng, max_depth - 1, store)
-                ),
-                2 => intern_ptrs!(
-                    store,
-                    tag,
-                    rng_interner(rng, max_depth - 1, store),
-                    rng_interner(rng, max_depth - 1, store),
-                    rng_interner(rng, max_depth - 1, store)
-                ),
-                3 => intern_ptrs!(
-                    store,
-                    tag,
-                    rng_interner(rng, max_depth - 1, store),
-                    rng_interner(rng, max_depth - 1, store),
-                    rng_interner(rng, max_depth - 1, store),
-                    rng_interner(rng, max_depth - 1, store)
-                ),
-                _ => unreachable!(),
-            }
-        }
-    }
+    use crate::lem::store::Store;
 
-    #[test]
-    fn test_z_store_roundtrip() {
-        const NUM_TESTS: u64 = 192;
-        const MAX_DEPTH: usize = 10;
-
-        (0..NUM_TESTS).into_par_iter().for_each(|seed| {
-            let mut rng = StdRng::seed_from_u64(seed);
-            let store1 = Store::default();
-            let ptr1 = rng_interner(&mut rng, MAX_DEPTH, &store1);
-
-            let mut z_store = ZStore::default();
-            let mut cache_into = HashMap::default();
-            let z_ptr = z_store.populate_with(&ptr1, &store1, &mut cache_into);
-
-            let mut cache_from = HashMap::default();
-            let store2 = Store::default();
-            let ptr2 = z_store
-                .populate_store(&z_ptr, &store2, &mut cache_from)
-                .unwrap();
-
-            assert_eq!(store1.hash_ptr(&ptr1), store2.hash_ptr(&ptr2))
-        });
-    }
+    use super::{ZDag, ZDagManifest};
 
     #[test]
     fn test_filtered_dag() {
@@ -417,4 +386,62 @@ mod tests {
         // but not in `z_dag_new`
         assert!(z_dag_new.get_type(&z_two_thr).is_none());
     }
+
+    #[test]
+    fn test_extract_import() {
+        let store1 = Store::<Bn>::default();
+        let expr = store1
+            .read_with_default_state("(letrec ((f (lambda (x) (cons x \"f\")))) (f 1))")
+            .unwrap();
+        let other = store1.read_with_default_state("(+ 1 2)").unwrap();
+        let z_expr = store1.hash_ptr(&expr);
+        let z_other = store1.hash_ptr(&other);
+
+        let z_dag = ZDag::extract(&store1, &[z_expr]);
+        assert!(z_dag.get_type(&z_other).is_none());
+
+        // the dag can be shipped and imported elsewhere
+        let bytes = bincode::serialize(&z_dag).unwrap();
+        let z_dag: ZDag<Bn> = bincode::deserialize(&bytes).unwrap();
+        let store2 = Store::<Bn>::default();
+        let imported = z_dag.import(&z_expr, &store2).unwrap();
+        assert_eq!(z_expr, store2.hash_ptr(&imported));
+        assert_eq!(
+            expr.fmt_to_string_simple(&store1),
+            imported.fmt_to_string_simple(&store2)
+        );
+        assert!(z_dag.import(&z_other, &store2).is_err());
+    }
+
+    #[test]
+    fn test_delta_layer() {
+        let store = Store::<Bn>::default();
+        let base_env = store
+            .read_with_default_state("((a . 1) (b . 2) (c . \"three\"))")
+            .unwrap();
+        let extended = store.cons(store.num_u64(4), base_env);
+        let (z_base_env, z_extended) = (store.hash_ptr(&base_env), store.hash_ptr(&extended));
+
+        let base = ZDag::extract(&store, &[z_base_env]);
+        let full = ZDag::extract(&store, &[z_extended]);
+        let delta = full.delta(&base.manifest());
+        // only the new cons and the new number
+        assert_eq!(delta.len(), 2);
+
+        let bytes = bincode::serialize(&delta).unwrap();
+        let delta: ZDag<Bn> = bincode::deserialize(&bytes).unwrap();
+        let mut layered = ZDag::extract(&store, &[z_base_env]);
+        layered.layer(delta).unwrap();
+        assert_eq!(layered.manifest(), full.manifest());
+        let store2 = Store::<Bn>::default();
+        let imported = layered.import(&z_extended, &store2).unwrap();
+        assert_eq!(store2.hash_ptr(&imported), z_extended);
+
+        // a delta can't be layered onto a dag missing its baseline
+        let mut empty = ZDag::default();
+        assert!(empty.layer(full.delta(&base.manifest())).is_err());
+        assert!(empty.is_empty());
+        // but a delta against an empty baseline is the whole dag
+        assert_eq!(full.delta(&ZDagManifest::default()).len(), full.len());
+    }
 }
@@ -1,6 +1,8 @@
 #![doc = include_str!("../README.md")]
 #![deny(unreachable_pub)]
 
+#[cfg(feature = "capi")]
+pub mod capi;
 #[macro_use]
 pub mod circuit;
 pub mod cli;
@@ -17,14 +19,18 @@ mod package;
 pub mod parser;
 pub mod proof;
 pub mod public_parameters;
+mod ratio;
 pub mod state;
 mod symbol;
 mod syntax;
 mod syntax_macros;
 mod tag;
+pub mod telemetry;
 mod uint;
+pub mod verifier;
 pub mod z_data;
 pub use num::Num;
+pub use ratio::Ratio;
 pub use symbol::Symbol;
 pub use uint::UInt;
 
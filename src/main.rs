@@ -2,10 +2,31 @@ use anyhow::Result;
 use tracing_subscriber::{fmt, prelude::*, EnvFilter, Registry};
 use tracing_texray::TeXRayLayer;
 
+/// Serves metrics to Prometheus if `LURK_PROMETHEUS_ADDR` is set, returning whether it is
+#[cfg(feature = "prometheus")]
+fn install_prometheus_from_env() -> Result<bool> {
+    use anyhow::Context;
+    let Ok(addr) = std::env::var("LURK_PROMETHEUS_ADDR") else {
+        return Ok(false);
+    };
+    let addr = addr.parse().context("invalid LURK_PROMETHEUS_ADDR")?;
+    lurk::telemetry::install_prometheus(addr)?;
+    Ok(true)
+}
+
+#[cfg(not(feature = "prometheus"))]
+fn install_prometheus_from_env() -> Result<bool> {
+    Ok(false)
+}
+
 fn main() -> Result<()> {
     // this handle should be held until the end of the program,
     // do not replace by let _ = ...
-    let _metrics_handle = lurk_metrics::MetricsSink::init();
+    let _metrics_handle = if install_prometheus_from_env()? {
+        None
+    } else {
+        Some(lurk_metrics::MetricsSink::init())
+    };
 
     let subscriber = Registry::default()
         .with(fmt::layer().pretty())
@@ -14,6 +14,7 @@ pub struct Package {
     symbols: HashMap<String, SymbolRef>,
     names: HashMap<SymbolRef, String>,
     local: HashSet<SymbolRef>,
+    exports: Option<HashSet<SymbolRef>>,
 }
 
 impl Package {
@@ -24,6 +25,7 @@ impl Package {
             symbols: Default::default(),
             names: Default::default(),
             local: Default::default(),
+            exports: None,
         }
     }
 
@@ -79,9 +81,47 @@ impl Package {
         Ok(())
     }
 
-    /// Import the local symbols of another package
+    /// Adds accessible symbols to the export list of the package, which
+    /// restricts what other packages get when they use it. Packages without an
+    /// export list export all of their local symbols.
+    pub fn export<A: AsRef<str>>(&mut self, symbols_names: &[A]) -> Result<()> {
+        let mut symbols = Vec::with_capacity(symbols_names.len());
+        for symbol_name in symbols_names {
+            let symbol_name = symbol_name.as_ref();
+            let Some(symbol) = self.resolve(symbol_name) else {
+                bail!("{symbol_name} is not accessible in {}", self.name)
+            };
+            symbols.push(symbol.clone());
+        }
+        self.exports
+            .get_or_insert_with(Default::default)
+            .extend(symbols);
+        Ok(())
+    }
+
+    /// The names of the symbols that can be written without their package path
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.symbols.keys()
+    }
+
+    /// The symbols interned in this package, as opposed to imported
+    pub fn local_symbols(&self) -> impl Iterator<Item = &SymbolRef> {
+        self.local.iter()
+    }
+
+    /// The symbols made accessible by `use_package`
+    pub fn exported(&self) -> Vec<SymbolRef> {
+        self.exports
+            .as_ref()
+            .unwrap_or(&self.local)
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Import the exported symbols of another package
     pub fn use_package(&mut self, package: &Package) -> Result<()> {
-        self.import(&package.local.iter().cloned().collect::<Vec<_>>())
+        self.import(&package.exported())
     }
 
     pub fn fmt_to_string(&self, symbol: &SymbolRef) -> String {
@@ -91,3 +131,28 @@ impl Package {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_exports() {
+        let mut app = Package::new(SymbolRef::new(Symbol::sym(&["app"])));
+        let factorial = app.intern("factorial");
+        app.intern("helper");
+        assert!(app.export(&["missing"]).is_err());
+        app.export(&["factorial"]).unwrap();
+        assert_eq!(vec![factorial.clone()], app.exported());
+
+        let mut client = Package::new(SymbolRef::new(Symbol::sym(&["client"])));
+        client.use_package(&app).unwrap();
+        assert_eq!(Some(&factorial), client.resolve("factorial"));
+        assert_eq!(None, client.resolve("helper"));
+
+        // a local symbol with the same name collides with the export
+        let mut other = Package::new(SymbolRef::new(Symbol::sym(&["other"])));
+        other.intern("factorial");
+        assert!(other.use_package(&app).is_err());
+    }
+}
@@ -14,6 +14,8 @@ pub enum ParseErrorKind<F: LurkField> {
     UnknownBaseCode,
     ParseIntErr(ParseIntError),
     InvalidChar(String),
+    InvalidByte(char),
+    InvalidHexBytes(String),
     Nom(ErrorKind),
     InterningError(String),
 }
@@ -27,6 +29,12 @@ impl<F: LurkField> fmt::Display for ParseErrorKind<F> {
             Self::ParseIntErr(e) => {
                 write!(f, "Error parsing number: {e}")
             }
+            Self::InvalidByte(c) => {
+                write!(f, "Character {c:?} doesn't fit in a byte.")
+            }
+            Self::InvalidHexBytes(s) => {
+                write!(f, "{s:?} isn't an even number of hex digits.")
+            }
             e => write!(f, "internal parser error {e:?}"),
         }
     }
@@ -0,0 +1,396 @@
+//! Incremental reading of top-level forms from `io::Read` streams.
+//!
+//! `FormReader` buffers only as much input as the form being read needs, so arbitrarily large files can be interned
+//! form by form. Forms are delimited by a light scan that tracks parentheses, strings, `|symbols|`, escapes and
+//! comments, and are then parsed by the regular reader.
+
+use std::{cell::RefCell, io::Read, rc::Rc};
+use thiserror::Error;
+
+use crate::{
+    field::LurkField,
+    lem::{pointers::Ptr, store::Store},
+    state::State,
+};
+
+/// How much is read from the stream at a time
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Where a form starts in a stream. Lines and columns start at 1, and columns count bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StreamPos {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for StreamPos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum StreamError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("{0}: invalid UTF-8")]
+    Utf8(StreamPos),
+    #[error("{0}: {1}")]
+    Syntax(StreamPos, String),
+}
+
+/// A top-level form read from a stream
+#[derive(Clone, Copy, Debug)]
+pub struct StreamForm {
+    pub ptr: Ptr,
+    /// Whether the form was prefixed by `!`
+    pub is_meta: bool,
+    pub pos: StreamPos,
+}
+
+enum Scan {
+    /// Only whitespace and comments are left
+    Done,
+    /// The buffer ends before the next form does
+    More,
+    /// The next form spans `start..end`
+    Form { start: usize, end: usize },
+}
+
+/// The scan for the next form, kept across reads so that each byte of the stream is scanned once
+#[derive(Default)]
+struct Scanner {
+    /// The next byte to scan
+    i: usize,
+    /// Where the form starts, once its first byte is found
+    start: Option<usize>,
+    depth: usize,
+    /// The closing delimiter of the string or `|symbol|` being scanned
+    delimiter: Option<u8>,
+    /// Whether a comment is being scanned
+    comment: bool,
+}
+
+impl Scanner {
+    fn at(i: usize) -> Self {
+        Self {
+            i,
+            ..Self::default()
+        }
+    }
+
+    /// Moves the positions back by the `n` bytes dropped from the front of the buffer
+    fn shift(&mut self, n: usize) {
+        self.i -= n;
+        if let Some(start) = &mut self.start {
+            *start -= n;
+        }
+    }
+
+    /// Finds the next form in `bytes`, scanning on from where the last call stopped. Delimiters are ASCII, so
+    /// scanning bytes is safe for UTF-8 input.
+    fn scan(&mut self, bytes: &[u8], eof: bool) -> Scan {
+        loop {
+            let Some(&b) = bytes.get(self.i) else {
+                return match (eof, self.start) {
+                    (false, _) => Scan::More,
+                    (true, None) => Scan::Done,
+                    // a form cut by the end of the input is handed to the parser, which reports the error
+                    (true, Some(start)) => Scan::Form {
+                        start,
+                        end: bytes.len(),
+                    },
+                };
+            };
+            if self.comment {
+                match bytes[self.i..].iter().position(|b| *b == b'\n') {
+                    Some(n) => {
+                        self.i += n + 1;
+                        self.comment = false;
+                    }
+                    None => self.i = bytes.len(),
+                }
+                continue;
+            }
+            if let Some(delimiter) = self.delimiter {
+                match b {
+                    b'\\' => self.i += 2,
+                    b if b == delimiter => {
+                        self.i += 1;
+                        self.delimiter = None;
+                    }
+                    _ => self.i += 1,
+                }
+                continue;
+            }
+            let Some(start) = self.start else {
+                match b {
+                    b';' => {
+                        self.i += 1;
+                        self.comment = true;
+                    }
+                    b if b.is_ascii_whitespace() => self.i += 1,
+                    _ => self.start = Some(self.i),
+                }
+                continue;
+            };
+            match b {
+                b'"' | b'|' => {
+                    self.i += 1;
+                    self.delimiter = Some(b);
+                }
+                b'\\' => self.i += 2,
+                // the rest of a whitespace character may not be read yet
+                b'\'' if !eof && self.i + 2 >= bytes.len() => return Scan::More,
+                // a whitespace character, as in `' '`
+                b'\''
+                    if bytes.get(self.i + 2) == Some(&b'\'')
+                        && bytes.get(self.i + 1).is_some_and(u8::is_ascii_whitespace) =>
+                {
+                    self.i += 3
+                }
+                b';' if self.depth == 0 => return Scan::Form { start, end: self.i },
+                b';' => {
+                    self.i += 1;
+                    self.comment = true;
+                }
+                b'(' => {
+                    self.depth += 1;
+                    self.i += 1;
+                }
+                b')' => {
+                    self.i += 1;
+                    if self.depth <= 1 {
+                        return Scan::Form { start, end: self.i };
+                    }
+                    self.depth -= 1;
+                }
+                b if self.depth == 0 && b.is_ascii_whitespace() => {
+                    return Scan::Form { start, end: self.i }
+                }
+                _ => self.i += 1,
+            }
+        }
+    }
+}
+
+/// Reads the top-level forms of a stream one at a time, interning them in a store
+pub struct FormReader<'a, R, F: LurkField> {
+    reader: R,
+    store: &'a Store<F>,
+    state: Rc<RefCell<State>>,
+    buf: Vec<u8>,
+    /// How many bytes at the front of `buf` were already read, to be dropped before the next fill
+    head: usize,
+    scanner: Scanner,
+    eof: bool,
+    /// The position of `buf[head]`
+    pos: StreamPos,
+}
+
+impl<'a, R: Read, F: LurkField> FormReader<'a, R, F> {
+    /// Symbols are interned relative to the current package of `state`, which is updated by the forms read (e.g.
+    /// after an `in-package`) only if the caller evaluates them in between.
+    pub fn new(reader: R, store: &'a Store<F>, state: Rc<RefCell<State>>) -> Self {
+        Self {
+            reader,
+            store,
+            state,
+            buf: vec![],
+            head: 0,
+            scanner: Scanner::default(),
+            eof: false,
+            pos: StreamPos {
+                offset: 0,
+                line: 1,
+                column: 1,
+            },
+        }
+    }
+
+    fn fill(&mut self) -> std::io::Result<()> {
+        if self.head > 0 {
+            self.buf.drain(..self.head);
+            self.scanner.shift(self.head);
+            self.head = 0;
+        }
+        let len = self.buf.len();
+        self.buf.resize(len + CHUNK_SIZE, 0);
+        let read = loop {
+            match self.reader.read(&mut self.buf[len..]) {
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                result => break result,
+            }
+        };
+        self.buf.truncate(len + *read.as_ref().unwrap_or(&0));
+        self.eof = read? == 0;
+        Ok(())
+    }
+
+    /// Moves past the buffered bytes up to `end`
+    fn consume(&mut self, end: usize) {
+        for b in &self.buf[self.head..end] {
+            self.pos.offset += 1;
+            if *b == b'\n' {
+                self.pos.line += 1;
+                self.pos.column = 1;
+            } else {
+                self.pos.column += 1;
+            }
+        }
+        self.head = end;
+    }
+
+    fn read_form(&mut self) -> Result<Option<StreamForm>, StreamError> {
+        let (start, end) = loop {
+            match self.scanner.scan(&self.buf, self.eof) {
+                Scan::Done => {
+                    self.consume(self.buf.len());
+                    self.scanner = Scanner::at(self.head);
+                    return Ok(None);
+                }
+                Scan::More => self.fill()?,
+                Scan::Form { start, end } => break (start, end),
+            }
+        };
+        self.consume(start);
+        self.scanner = Scanner::at(end);
+        let pos = self.pos;
+        let Ok(source) = std::str::from_utf8(&self.buf[start..end]) else {
+            self.consume(end);
+            return Err(StreamError::Utf8(pos));
+        };
+        let result = match self.store.read_maybe_meta(self.state.clone(), source) {
+            Ok((_, rest, ptr, is_meta)) if rest.fragment().trim().is_empty() => {
+                Ok(Some(StreamForm { ptr, is_meta, pos }))
+            }
+            Ok((_, rest, ..)) => Err(StreamError::Syntax(
+                pos,
+                format!("unexpected input: {}", rest.fragment()),
+            )),
+            Err(e) => Err(StreamError::Syntax(pos, e.to_string())),
+        };
+        self.consume(end);
+        result
+    }
+}
+
+impl<'a, R: Read, F: LurkField> Iterator for FormReader<'a, R, F> {
+    type Item = Result<StreamForm, StreamError>;
+
+    /// The next form, or an error after which reading resumes at the following form
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_form().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr;
+    use std::io::Read;
+
+    use super::{FormReader, StreamError, StreamPos, CHUNK_SIZE};
+    use crate::{lem::store::Store, state::State};
+
+    /// Returns one byte per read, so that forms straddle every possible buffer boundary
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.split_first() {
+                Some((b, rest)) if !buf.is_empty() => {
+                    buf[0] = *b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    const SOURCE: &str = "; data
+(cons 1 \"a (string\") 42
+  'sym
+!(def x '(1 . |b ) c|)) ; trailing
+(list #\\( '\\)' 'é' ' ')
+";
+
+    #[test]
+    fn test_stream_forms() {
+        let store = Store::<Fr>::default();
+        let forms = FormReader::new(
+            Trickle(SOURCE.as_bytes()),
+            &store,
+            State::init_lurk_state().rccell(),
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+        let expected = [
+            "(cons 1 \"a (string\")",
+            "42",
+            "'sym",
+            "(def x '(1 . |b ) c|))",
+            "(list #\\( '\\)' 'é' ' ')",
+        ];
+        assert_eq!(forms.len(), expected.len());
+        for (form, source) in forms.iter().zip(expected) {
+            assert_eq!(form.ptr, store.read_with_default_state(source).unwrap());
+        }
+        assert!(forms
+            .iter()
+            .map(|form| form.is_meta)
+            .eq([false, false, false, true, false]));
+        assert_eq!(
+            forms[1].pos,
+            StreamPos {
+                offset: 28,
+                line: 2,
+                column: 22
+            }
+        );
+        assert_eq!((forms[2].pos.line, forms[2].pos.column), (3, 3));
+        assert_eq!((forms[4].pos.line, forms[4].pos.column), (5, 1));
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let store = Store::<Fr>::default();
+        let mut reader = FormReader::new(
+            "(1 2) 3)\n(4 5".as_bytes(),
+            &store,
+            State::init_lurk_state().rccell(),
+        );
+        assert_eq!(
+            reader.next().unwrap().unwrap().ptr,
+            store.read_with_default_state("(1 2)").unwrap()
+        );
+        // a stray parenthesis
+        assert!(matches!(reader.next(), Some(Err(StreamError::Syntax(..)))));
+        // a form cut by the end of the input
+        let Some(Err(StreamError::Syntax(pos, _))) = reader.next() else {
+            panic!("expected a syntax error")
+        };
+        assert_eq!((pos.line, pos.column), (2, 1));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_stream_chunks() {
+        // forms and a string spanning several chunks
+        let string = "x".repeat(3 * CHUNK_SIZE);
+        let source = format!("{} \"{string}\" ; end", "(1 . 2) ".repeat(CHUNK_SIZE / 4));
+        let store = Store::<Fr>::default();
+        let forms = FormReader::new(source.as_bytes(), &store, State::init_lurk_state().rccell())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(forms.len(), CHUNK_SIZE / 4 + 1);
+        assert_eq!(
+            forms[0].ptr,
+            store.read_with_default_state("(1 . 2)").unwrap()
+        );
+        assert_eq!(forms[CHUNK_SIZE / 4].ptr, store.intern_string(&string));
+        assert_eq!(forms[CHUNK_SIZE / 4].pos.offset, 2 * CHUNK_SIZE);
+    }
+}
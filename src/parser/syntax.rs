@@ -133,7 +133,7 @@ pub fn parse_relative_symbol<F: LurkField>(
     create_unknown_packages: bool,
 ) -> impl Fn(Span<'_>) -> ParseResult<'_, F, SymbolRef> {
     move |from: Span<'_>| {
-        let (i, _) = peek(none_of(",~#(){}[]1234567890."))(from)?;
+        let (i, _) = peek(none_of(",~#(){}[]1234567890.`"))(from)?;
         let (upto, path) = parse_symbol_limbs()(i)?;
         intern_path(&state, upto, &path, None, create_unknown_packages)
     }
@@ -208,6 +208,30 @@ pub fn parse_uint<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syn
     }
 }
 
+/// Signed integers: -1i64, 0x7fi64, -0b101i64
+pub fn parse_int<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syntax<F>> {
+    move |from: Span<'_>| {
+        let (i, neg) = opt(tag("-"))(from)?;
+        let (i, base) = alt((
+            preceded(tag("0"), base::parse_litbase_code()),
+            success(base::LitBase::Dec),
+        ))(i)?;
+        let (i, digits) = base::parse_litbase_digits(base)(i)?;
+        let (upto, _) = tag("i64")(i)?;
+        // the sign goes into the digits so that `i64::MIN` is within range
+        let digits = if neg.is_some() {
+            format!("-{digits}")
+        } else {
+            digits
+        };
+        let (_, x) = ParseError::res(i64::from_str_radix(&digits, base.radix()), from, |e| {
+            ParseErrorKind::ParseIntErr(e)
+        })?;
+        let pos = Pos::from_upto(from, upto);
+        Ok((upto, Syntax::I64(pos, x)))
+    }
+}
+
 fn f_from_le_bytes<F: LurkField>(bs: &[u8]) -> F {
     let mut res = F::ZERO;
     let mut bs = bs.iter().rev().peekable();
@@ -277,6 +301,66 @@ pub fn parse_string<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, S
     }
 }
 
+/// Byte strings, whose characters must be in the range `\u{0}` to `\u{ff}`:
+/// b"abc", b"\u{ff}\n"
+pub fn parse_bytes<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syntax<F>> {
+    move |from: Span<'_>| {
+        let (i, _) = tag("b")(from)?;
+        let (upto, s) = string::parse_string('"')(i)?;
+        let bytes = s
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| c))
+            .collect::<Result<Vec<_>, _>>();
+        let (_, bytes) = ParseError::res(bytes, from, ParseErrorKind::InvalidByte)?;
+        let pos = Pos::from_upto(from, upto);
+        Ok((upto, Syntax::Bytes(pos, bytes)))
+    }
+}
+
+/// Hex byte strings, whose digits can be separated by whitespace:
+/// #x"deadbeef", #x"00 ff"
+pub fn parse_hex_bytes<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syntax<F>> {
+    move |from: Span<'_>| {
+        let (i, _) = tag("#x\"")(from)?;
+        let (i, digits) = take_till(|c| c == '"')(i)?;
+        let (upto, _) = tag("\"")(i)?;
+        let compact = digits
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>();
+        let bytes = hex::decode(compact).map_err(|_| digits.fragment().to_string());
+        let (_, bytes) = ParseError::res(bytes, from, ParseErrorKind::InvalidHexBytes)?;
+        let pos = Pos::from_upto(from, upto);
+        Ok((upto, Syntax::Bytes(pos, bytes)))
+    }
+}
+
+/// Byte vectors, whose elements are bytes in any base: #u8(1 2 0xff)
+pub fn parse_byte_vector<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syntax<F>> {
+    move |from: Span<'_>| {
+        let (i, _) = tag("#u8(")(from)?;
+        let (i, bytes) = many0(preceded(parse_space, parse_byte()))(i)?;
+        let (i, _) = parse_space(i)?;
+        let (upto, _) = tag(")")(i)?;
+        let pos = Pos::from_upto(from, upto);
+        Ok((upto, Syntax::Bytes(pos, bytes)))
+    }
+}
+
+fn parse_byte<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, u8> {
+    move |from: Span<'_>| {
+        let (i, base) = alt((
+            preceded(tag("0"), base::parse_litbase_code()),
+            success(base::LitBase::Dec),
+        ))(from)?;
+        let (upto, digits) = base::parse_litbase_digits(base)(i)?;
+        let (_, x) = ParseError::res(u8::from_str_radix(&digits, base.radix()), from, |e| {
+            ParseErrorKind::ParseIntErr(e)
+        })?;
+        Ok((upto, x))
+    }
+}
+
 // hash syntax for chars
 pub fn parse_hash_char<F: LurkField>() -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syntax<F>> {
     |from: Span<'_>| {
@@ -374,6 +458,36 @@ pub fn parse_quote<F: LurkField>(
     }
 }
 
+/// Quasiquoted templates, which can contain unquotes and unquote-splicings:
+/// `(1 ,x ,@xs)
+pub fn parse_quasiquote<F: LurkField>(
+    state: Rc<RefCell<State>>,
+    create_unknown_packages: bool,
+) -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syntax<F>> {
+    move |from: Span<'_>| {
+        let (i, _) = tag("`")(from)?;
+        let (upto, s) = parse_syntax(state.clone(), false, create_unknown_packages)(i)?;
+        let pos = Pos::from_upto(from, upto);
+        Ok((upto, Syntax::Quasiquote(pos, Box::new(s))))
+    }
+}
+
+pub fn parse_unquote<F: LurkField>(
+    state: Rc<RefCell<State>>,
+    create_unknown_packages: bool,
+) -> impl Fn(Span<'_>) -> ParseResult<'_, F, Syntax<F>> {
+    move |from: Span<'_>| {
+        let (i, splicing) = alt((value(true, tag(",@")), value(false, tag(","))))(from)?;
+        let (upto, s) = parse_syntax(state.clone(), false, create_unknown_packages)(i)?;
+        let pos = Pos::from_upto(from, upto);
+        if splicing {
+            Ok((upto, Syntax::UnquoteSplicing(pos, Box::new(s))))
+        } else {
+            Ok((upto, Syntax::Unquote(pos, Box::new(s))))
+        }
+    }
+}
+
 // top-level syntax parser
 pub fn parse_syntax<F: LurkField>(
     state: Rc<RefCell<State>>,
@@ -388,13 +502,25 @@ pub fn parse_syntax<F: LurkField>(
                 parse_list(state.clone(), meta, create_unknown_packages),
             ),
             parse_uint(),
+            parse_int(),
             parse_num(),
+            parse_bytes(),
+            parse_hex_bytes(),
+            parse_byte_vector(),
             context(
                 "symbol",
                 parse_symbol(state.clone(), create_unknown_packages),
             ),
             parse_string(),
             context("quote", parse_quote(state.clone(), create_unknown_packages)),
+            context(
+                "quasiquote",
+                parse_quasiquote(state.clone(), create_unknown_packages),
+            ),
+            context(
+                "unquote",
+                parse_unquote(state.clone(), create_unknown_packages),
+            ),
             parse_hash_char(),
         ))(from)
     }
@@ -816,6 +942,41 @@ pub mod tests {
         ));
     }
 
+    #[test]
+    fn unit_parse_quasiquote() {
+        let state_ = State::default().rccell();
+        let state = || state_.clone();
+        let boxed = |x: &str| Box::new(symbol!([x]));
+        assert!(test(
+            parse_syntax(state(), false, true),
+            "`(a ,b ,@c)",
+            Some(Syntax::Quasiquote(
+                Pos::No,
+                Box::new(list!([
+                    symbol!(["a"]),
+                    Syntax::Unquote(Pos::No, boxed("b")),
+                    Syntax::UnquoteSplicing(Pos::No, boxed("c"))
+                ]))
+            ))
+        ));
+        assert!(test(
+            parse_syntax(state(), false, true),
+            "``,,a",
+            Some(Syntax::Quasiquote(
+                Pos::No,
+                Box::new(Syntax::Quasiquote(
+                    Pos::No,
+                    Box::new(Syntax::Unquote(
+                        Pos::No,
+                        Box::new(Syntax::Unquote(Pos::No, boxed("a")))
+                    ))
+                ))
+            ))
+        ));
+        assert!(test(parse_syntax(state(), false, true), "`", None));
+        assert!(test(parse_syntax(state(), false, true), ",@", None));
+    }
+
     #[test]
     fn unit_parse_num() {
         assert!(test(parse_num(), "0", Some(num!(0))));
@@ -866,6 +1027,68 @@ pub mod tests {
         assert!(test(parse_num(), "-1/2", Some(Syntax::Num(Pos::No, tmp))));
     }
 
+    #[test]
+    fn unit_parse_int() {
+        let int = |x| Some(Syntax::I64(Pos::No, x));
+        assert!(test(parse_int(), "0i64", int(0)));
+        assert!(test(parse_int(), "-0i64", int(0)));
+        assert!(test(parse_int(), "42i64", int(42)));
+        assert!(test(parse_int(), "-42i64", int(-42)));
+        assert!(test(parse_int(), "0xffi64", int(255)));
+        assert!(test(parse_int(), "-0b101i64", int(-5)));
+        assert!(test(parse_int(), "9223372036854775807i64", int(i64::MAX)));
+        assert!(test(parse_int(), "-9223372036854775808i64", int(i64::MIN)));
+        assert!(test(parse_int(), "9223372036854775808i64", None));
+        assert!(test(parse_int(), "42", None));
+        assert!(test(parse_int(), "42u64", None));
+    }
+
+    #[test]
+    fn unit_parse_bytes() {
+        let bytes = |x: &[u8]| Some(Syntax::Bytes(Pos::No, x.to_vec()));
+        assert!(test(parse_bytes(), "b\"\"", bytes(b"")));
+        assert!(test(parse_bytes(), "b\"abc\"", bytes(b"abc")));
+        assert!(test(parse_bytes(), "b\"a\\\"\\n\"", bytes(b"a\"\n")));
+        assert!(test(parse_bytes(), "b\"\\u{0}\\u{ff}\"", bytes(&[0, 255])));
+        assert!(test(parse_bytes(), "b\"\\u{100}\"", None));
+        assert!(test(parse_bytes(), "b\"λ\"", None));
+        assert!(test(parse_bytes(), "\"abc\"", None));
+    }
+
+    #[test]
+    fn unit_parse_hex_bytes() {
+        let bytes = |x: &[u8]| Some(Syntax::Bytes(Pos::No, x.to_vec()));
+        assert!(test(parse_hex_bytes(), "#x\"\"", bytes(b"")));
+        assert!(test(
+            parse_hex_bytes(),
+            "#x\"deadBEEF\"",
+            bytes(&[0xde, 0xad, 0xbe, 0xef])
+        ));
+        assert!(test(
+            parse_hex_bytes(),
+            "#x\"00 ff\n 10\"",
+            bytes(&[0, 255, 16])
+        ));
+        assert!(test(parse_hex_bytes(), "#x\"abc\"", None));
+        assert!(test(parse_hex_bytes(), "#x\"zz\"", None));
+        assert!(test(parse_hex_bytes(), "#x\"ab", None));
+    }
+
+    #[test]
+    fn unit_parse_byte_vector() {
+        let bytes = |x: &[u8]| Some(Syntax::Bytes(Pos::No, x.to_vec()));
+        assert!(test(parse_byte_vector(), "#u8()", bytes(b"")));
+        assert!(test(
+            parse_byte_vector(),
+            "#u8(1 0xff\n 0b10 0o10)",
+            bytes(&[1, 255, 2, 8])
+        ));
+        assert!(test(parse_byte_vector(), "#u8( 42 )", bytes(&[42])));
+        assert!(test(parse_byte_vector(), "#u8(256)", None));
+        assert!(test(parse_byte_vector(), "#u8(-1)", None));
+        assert!(test(parse_byte_vector(), "#u8(1 'a)", None));
+    }
+
     #[test]
     fn unit_parse_syntax_misc() {
         let vec: Vec<u8> = vec![
@@ -918,6 +1141,11 @@ pub mod tests {
             "11242421860377074631u64",
             Some(uint!(11242421860377074631))
         ));
+        assert!(test(
+            parse_syntax(state(), false, true),
+            "(1u64 -1i64 1)",
+            Some(list!([uint!(1), Syntax::I64(Pos::No, -1), num!(1)]))
+        ));
         assert!(test(
             parse_syntax(state(), false, true),
             ":\u{ae}\u{60500}\u{87}..)",
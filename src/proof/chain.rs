@@ -0,0 +1,262 @@
+//! Chains of proofs over functional commitments, also known as microchains.
+//!
+//! A chain starts at a commitment to a function which, called with some arguments, returns a pair whose first component
+//! is a result and whose second component is a commitment to the function to be called next. Every link of the chain
+//! is a Nova proof of one such call, made on the commitment returned by the previous link. Since a committed function
+//! can close over anything, this is enough to build stateful applications, such as counters, games or registries,
+//! whose whole history is checked by verifying the head of the chain.
+//!
+//! Committed functions must be available in the store used to prove a call, as they are after evaluating the `commit`
+//! that made them or after `Store::add_comm`. Verifying only needs the arguments and the final IO of every call.
+
+use nova::errors::NovaError;
+use thiserror::Error;
+
+use crate::{
+    coprocessor::Coprocessor,
+    error::ProofError,
+    field::LurkField,
+    lem::{
+        eval::EvalConfig,
+        pointers::{Ptr, RawPtr},
+        store::Store,
+        tag::Tag,
+    },
+    proof::{
+        nova::{CurveCycleEquipped, NovaProver, Proof, PublicParams, C1LEM},
+        RecursiveSNARKTrait,
+    },
+    tag::{ContTag, ExprTag},
+};
+
+/// The ways a link can fail to extend a chain
+#[derive(Error, Debug)]
+pub enum ChainError {
+    /// The call didn't terminate with a pair whose second component is a commitment
+    #[error("Link {0}: the call didn't return a result and a commitment")]
+    MalformedOutput(usize),
+    /// The proof isn't a proof of the call on the head of the chain
+    #[error("Link {0}: the proof doesn't prove the call")]
+    InvalidProof(usize),
+    /// The proof couldn't be checked
+    #[error("Link {0}: verification error: {1}")]
+    Verification(usize, NovaError),
+}
+
+/// A call to the function committed to by the head of a chain, with the final IO of its evaluation
+#[derive(Clone, Debug)]
+pub struct ChainCall {
+    /// The arguments applied to the function
+    pub args: Vec<Ptr>,
+    /// The expression, env and continuation the evaluation ended with
+    pub output: Vec<Ptr>,
+}
+
+/// A proven call, which took the commitment `callable` to the commitment it returned
+pub struct ChainLink<'a, F: CurveCycleEquipped, C: Coprocessor<F>> {
+    /// The hash of the commitment that was called
+    pub callable: F,
+    /// The call
+    pub call: ChainCall,
+    /// The proof of the call
+    pub proof: Proof<F, C1LEM<'a, F, C>>,
+}
+
+/// A chain of proofs, each consuming the commitment returned by the previous one
+pub struct Chain<'a, F: CurveCycleEquipped, C: Coprocessor<F>> {
+    store: &'a Store<F>,
+    pp: &'a PublicParams<F>,
+    genesis: F,
+    links: Vec<ChainLink<'a, F, C>>,
+}
+
+/// The expression `((open <callable>) <args>...)`
+fn call_expr<F: LurkField>(store: &Store<F>, callable: F, args: &[Ptr]) -> Ptr {
+    let open = store.list(vec![store.intern_lurk_symbol("open"), store.num(callable)]);
+    let mut expr = Vec::with_capacity(args.len() + 1);
+    expr.push(open);
+    expr.extend_from_slice(args);
+    store.list(expr)
+}
+
+impl<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a> Chain<'a, F, C> {
+    /// An empty chain, whose head is the commitment `genesis`. Links are proved and verified with `pp`
+    pub fn new(store: &'a Store<F>, pp: &'a PublicParams<F>, genesis: F) -> Self {
+        Self {
+            store,
+            pp,
+            genesis,
+            links: vec![],
+        }
+    }
+
+    /// The hash of the commitment the chain started with
+    #[inline]
+    pub fn genesis(&self) -> F {
+        self.genesis
+    }
+
+    /// The links of the chain, oldest first
+    #[inline]
+    pub fn links(&self) -> &[ChainLink<'a, F, C>] {
+        &self.links
+    }
+
+    /// The hash of the commitment to be called next
+    pub fn head(&self) -> F {
+        self.links.last().map_or(self.genesis, |link| {
+            Self::next_commitment(self.store, &link.call.output)
+                .expect("links are checked when extending")
+        })
+    }
+
+    /// The commitment returned by a call ending with `output`, if it terminated with a pair whose second component is
+    /// a commitment
+    fn next_commitment(store: &Store<F>, output: &[Ptr]) -> Option<F> {
+        let [expr, _env, cont] = output else {
+            return None;
+        };
+        if cont.tag() != &Tag::Cont(ContTag::Terminal) || expr.tag() != &Tag::Expr(ExprTag::Cons) {
+            return None;
+        }
+        let (_, next) = store.car_cdr(expr).ok()?;
+        let (Tag::Expr(ExprTag::Comm), RawPtr::Atom(idx)) = next.parts() else {
+            return None;
+        };
+        Some(*store.expect_f(*idx))
+    }
+
+    /// Evaluates the call of the head of the chain on `args` and proves it with `prover`. The result can be given to
+    /// `extend`
+    pub fn prove_call(
+        &self,
+        prover: &NovaProver<'a, F, C>,
+        args: Vec<Ptr>,
+        limit: usize,
+    ) -> Result<(Proof<F, C1LEM<'a, F, C>>, ChainCall), ProofError> {
+        let expr = call_expr(self.store, self.head(), &args);
+        let eval_config = EvalConfig::new_ivc(prover.lang());
+        let frames = C1LEM::<'a, F, C>::build_frames(
+            expr,
+            self.store.intern_empty_env(),
+            self.store,
+            limit,
+            &eval_config,
+        )?;
+        let output = frames.last().expect("evaluation has frames").output.clone();
+        let (proof, ..) = prover.prove_from_frames(self.pp, &frames, self.store)?;
+        Ok((proof, ChainCall { args, output }))
+    }
+
+    fn check_link(
+        &self,
+        index: usize,
+        callable: F,
+        call: &ChainCall,
+        proof: &Proof<F, C1LEM<'a, F, C>>,
+    ) -> Result<F, ChainError> {
+        let next = Self::next_commitment(self.store, &call.output)
+            .ok_or(ChainError::MalformedOutput(index))?;
+        let input = [
+            call_expr(self.store, callable, &call.args),
+            self.store.intern_empty_env(),
+            self.store.cont_outermost(),
+        ];
+        self.store.hydrate_z_cache();
+        let z0 = self.store.to_scalar_vector(&input);
+        let zi = self.store.to_scalar_vector(&call.output);
+        match proof.verify(self.pp, &z0, &zi) {
+            Ok(true) => Ok(next),
+            Ok(false) => Err(ChainError::InvalidProof(index)),
+            Err(e) => Err(ChainError::Verification(index, e)),
+        }
+    }
+
+    /// Appends the proof of `new_call` on the head of the chain, which then moves to the commitment returned by the
+    /// call. The chain is left untouched if the proof doesn't check out.
+    pub fn extend(
+        &mut self,
+        proof: Proof<F, C1LEM<'a, F, C>>,
+        new_call: ChainCall,
+    ) -> Result<(), ChainError> {
+        let callable = self.head();
+        self.check_link(self.links.len(), callable, &new_call, &proof)?;
+        self.links.push(ChainLink {
+            callable,
+            call: new_call,
+            proof,
+        });
+        Ok(())
+    }
+
+    /// Verifies every link of the chain, and that each one calls the commitment returned by the previous one, starting
+    /// from the genesis commitment. Returns the hash of the head commitment.
+    pub fn verify_head(&self) -> Result<F, ChainError> {
+        let mut head = self.genesis;
+        for (index, link) in self.links.iter().enumerate() {
+            if link.callable != head {
+                return Err(ChainError::InvalidProof(index));
+            }
+            head = self.check_link(index, head, &link.call, &link.proof)?;
+        }
+        Ok(head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr;
+    use std::sync::Arc;
+
+    use super::{Chain, ChainError};
+    use crate::{
+        eval::lang::{Coproc, Lang},
+        lem::{eval::evaluate_simple, pointers::RawPtr, store::Store},
+        proof::nova::{public_params, NovaProver},
+    };
+
+    const COUNTER: &str = "(letrec ((add (lambda (counter x)
+                                            (let ((counter (+ counter x)))
+                                              (cons counter (commit (add counter)))))))
+                             (add 0))";
+
+    #[test]
+    fn test_counter_chain() {
+        let store = Store::<Fr>::default();
+        let lang = Arc::new(Lang::<Fr, Coproc<Fr>>::new());
+        let pp = public_params(1, lang.clone());
+        let prover = NovaProver::new(1, lang);
+
+        let counter = store.read_with_default_state(COUNTER).unwrap();
+        let (output, ..) = evaluate_simple::<Fr, Coproc<Fr>>(None, counter, &store, 1000).unwrap();
+        let genesis = store.commit(output[0]);
+        let RawPtr::Atom(idx) = genesis.raw() else {
+            unreachable!()
+        };
+        let genesis = *store.expect_f(*idx);
+
+        let mut chain = Chain::new(&store, &pp, genesis);
+        for (n, total) in [(2, 2), (3, 5)] {
+            let (proof, call) = chain
+                .prove_call(&prover, vec![store.num_u64(n)], 1000)
+                .unwrap();
+            let (result, _) = store.car_cdr(&call.output[0]).unwrap();
+            assert_eq!(result, store.num_u64(total));
+            chain.extend(proof, call).unwrap();
+        }
+        assert_eq!(chain.links().len(), 2);
+        assert_ne!(chain.head(), genesis);
+        assert_eq!(chain.verify_head().unwrap(), chain.head());
+
+        // a proof of a call on another commitment doesn't extend the chain
+        let other = Chain::<'_, Fr, Coproc<Fr>>::new(&store, &pp, genesis);
+        let (proof, call) = other
+            .prove_call(&prover, vec![store.num_u64(1)], 1000)
+            .unwrap();
+        assert!(matches!(
+            chain.extend(proof, call),
+            Err(ChainError::InvalidProof(2) | ChainError::Verification(2, _))
+        ));
+        assert_eq!(chain.links().len(), 2);
+    }
+}
@@ -0,0 +1,127 @@
+//! Progress reporting and cancellation for long proving runs.
+//!
+//! A [`ProverEvents`] implementation is notified before and after each folding step, and a [`CancellationToken`] is
+//! checked between steps. Cancelling makes proving stop with `ProofError::Cancelled` once the step being folded
+//! completes, so a service can abort a run without killing the process.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{coprocessor::Coprocessor, error::ProofError, field::LurkField};
+
+use super::nova::C1LEM;
+
+/// What is being folded
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StepInfo {
+    /// The index of the step, which is also the index of the chunk of frames it proves
+    pub index: usize,
+    /// The total number of steps of the run
+    pub num_steps: usize,
+    /// The index of the circuit of the step. Always 0 with Nova.
+    pub circuit_index: usize,
+    /// The number of constraints of the step's circuit
+    pub num_constraints: usize,
+}
+
+/// Callbacks notified as proving progresses. They're called from the proving thread, so they should return quickly.
+pub trait ProverEvents: Send + Sync {
+    /// Called before folding a step
+    fn step_started(&self, _step: &StepInfo) {}
+
+    /// Called after folding a step, which took `elapsed`
+    fn step_finished(&self, _step: &StepInfo, _elapsed: Duration) {}
+}
+
+/// Ignores every event
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoEvents;
+
+impl ProverEvents for NoEvents {}
+
+/// A flag shared between a proving run and whoever may want to abort it
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// A token that isn't cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks every run holding a clone of this token to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether `cancel` was called
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), ProofError> {
+        if self.is_cancelled() {
+            return Err(ProofError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Reports the steps of one run to `events`, if any, stopping it when `cancel` is cancelled
+pub(crate) struct StepObserver<'a> {
+    events: Option<&'a dyn ProverEvents>,
+    cancel: &'a CancellationToken,
+    num_steps: usize,
+    /// The number of constraints of each circuit index, computed on first use
+    num_constraints: HashMap<usize, usize>,
+}
+
+impl<'a> StepObserver<'a> {
+    pub(crate) fn new(
+        events: Option<&'a dyn ProverEvents>,
+        cancel: &'a CancellationToken,
+        num_steps: usize,
+    ) -> Self {
+        Self {
+            events,
+            cancel,
+            num_steps,
+            num_constraints: HashMap::new(),
+        }
+    }
+
+    /// Folds `step` with `fold`, notifying the events around it. Fails without folding if the run was cancelled.
+    pub(crate) fn observe<F: LurkField, C: Coprocessor<F>, T>(
+        &mut self,
+        index: usize,
+        step: &C1LEM<'_, F, C>,
+        fold: impl FnOnce() -> Result<T, ProofError>,
+    ) -> Result<T, ProofError> {
+        self.cancel.check()?;
+        let Some(events) = self.events else {
+            return fold();
+        };
+        let circuit_index = step.program_counter();
+        let num_constraints = *self
+            .num_constraints
+            .entry(circuit_index)
+            .or_insert_with(|| step.num_constraints());
+        let info = StepInfo {
+            index,
+            num_steps: self.num_steps,
+            circuit_index,
+            num_constraints,
+        };
+        events.step_started(&info);
+        let start = Instant::now();
+        let result = fold()?;
+        events.step_finished(&info, start.elapsed());
+        Ok(result)
+    }
+}
@@ -12,6 +12,18 @@ pub mod nova;
 /// An adapter to a SuperNova proving system implementation.
 pub mod supernova;
 
+/// A stable binary format for proofs.
+pub mod wire;
+
+/// A pipelined executor for folding.
+pub mod pipeline;
+
+/// Progress reporting and cancellation for proving.
+pub mod events;
+
+/// Chains of proofs over functional commitments.
+pub mod chain;
+
 #[cfg(test)]
 mod tests;
 
@@ -25,6 +37,7 @@ use crate::{
     field::LurkField,
     lem::{eval::EvalConfig, pointers::Ptr, store::Store},
     proof::nova::Dual,
+    tag::{ExprTag, Tag},
 };
 
 use self::{nova::CurveCycleEquipped, supernova::FoldingConfig};
@@ -148,6 +161,20 @@ impl FoldingMode {
     }
 }
 
+/// The exact number of iterations of the evaluation proved with public input `z0` and output `zi`, read from the count
+/// of a metered step function (see `Lang::set_metered`), which comes after the expression, environment and
+/// continuation. `None` if the IO isn't metered or the count doesn't start at 0.
+pub fn proved_iterations<F: LurkField>(z0: &[F], zi: &[F]) -> Option<usize> {
+    let count = |z: &[F]| match z {
+        [_, _, _, _, _, _, tag, count] if *tag == ExprTag::Num.to_field::<F>() => count.to_u64(),
+        _ => None,
+    };
+    if count(z0)? != 0 {
+        return None;
+    }
+    count(zi)?.try_into().ok()
+}
+
 /// A trait for a prover that works with a field `F`.
 pub trait Prover<'a, F: CurveCycleEquipped, M: FrameLike<Ptr, FrameIO = Vec<Ptr>>> {
     /// Associated type for public parameters
@@ -16,18 +16,29 @@ use pasta_curves::pallas;
 use serde::{Deserialize, Serialize};
 use std::{
     marker::PhantomData,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 use tracing::info;
 
 use crate::{
     config::lurk_config,
     coprocessor::Coprocessor,
-    error::ProofError,
+    error::{ProofError, ReductionError},
     eval::lang::Lang,
     field::LurkField,
     lem::{interpreter::Frame, multiframe::MultiFrame, pointers::Ptr, store::Store},
-    proof::{supernova::FoldingConfig, FrameLike, Prover},
+    proof::{
+        events::{CancellationToken, ProverEvents, StepObserver},
+        pipeline::{self, PipelineConfig, PipelineMetrics},
+        proved_iterations,
+        supernova::FoldingConfig,
+        wire::{self, ProofKind, WireError},
+        FrameLike, Prover,
+    },
+    telemetry,
 };
 
 use super::{FoldingMode, RecursiveSNARKTrait};
@@ -39,11 +50,12 @@ pub trait CurveCycleEquipped: LurkField {
     /// ## Why the next 2 types?
 
     /// In theory it would be sufficient to abstract over the two group types of the curve cycle, but in practice Nova is a
-    /// bit idiosyncratic in the [`nova::traits::evaluation::EvaluationEngineTrait<G>`], (PCS) it uses on these (its multilinear IPA : [`nova::provider::ipa_pc::EvaluationEngine<G>`])
-    /// *and* that implementation requires an additional trait bound `CommitmentKeyExtTrait` for this type.
+    /// bit idiosyncratic in the [`nova::traits::evaluation::EvaluationEngineTrait<G>`], (PCS) it uses on these (its
+    /// multilinear IPA : [`nova::provider::ipa_pc::EvaluationEngine<G>`]) *and* that implementation requires an
+    /// additional trait bound `CommitmentKeyExtTrait` for this type.
     ///
-    /// The following abstracts over curve cycle groups for which there exists an implementation of [`nova::traits::evaluation::EvaluationEngineTrait<G>`],
-    /// encapsulating these idiosyncrasies within Nova.
+    /// The following abstracts over curve cycle groups for which there exists an implementation of
+    /// [`nova::traits::evaluation::EvaluationEngineTrait<G>`], encapsulating these idiosyncrasies within Nova.
     type E1: NovaCurveCycleEquipped<Scalar = Self>;
 
     /// a concrete implementation of an [`nova::traits::evaluation::EvaluationEngineTrait<G>`] for G1,
@@ -67,7 +79,8 @@ impl CurveCycleEquipped for Bn256Scalar {
 
     type E1 = Bn256EngineKZG;
 }
-// The impl CurveCycleEquipped for grumpkin::Scalar is academically possible, but voluntarily omitted to avoid confusion.
+// The impl CurveCycleEquipped for grumpkin::Scalar is academically possible, but voluntarily omitted to avoid
+// confusion.
 
 /// Convenience alias for the primary group type pegged to a LurkField through a CurveCycleEquipped type.
 pub type E1<F> = <F as CurveCycleEquipped>::E1;
@@ -175,6 +188,45 @@ pub enum Proof<F: CurveCycleEquipped, S> {
     ),
 }
 
+impl<F: CurveCycleEquipped, S> Proof<F, S> {
+    fn kind(&self) -> ProofKind {
+        match self {
+            Self::Recursive(..) => ProofKind::NovaRecursive,
+            Self::Compressed(..) => ProofKind::NovaCompressed,
+        }
+    }
+
+    /// The number of steps folded by the proof, which verification checks
+    pub fn num_steps(&self) -> usize {
+        match self {
+            Self::Recursive(_, num_steps, _) | Self::Compressed(_, num_steps, _) => *num_steps,
+        }
+    }
+
+    /// Encodes the proof in the format of [`crate::proof::wire`], tagged with the digest of `pp` and the reduction
+    /// count `rc` it was made with
+    pub fn to_bytes(&self, pp: &PublicParams<F>, rc: usize) -> Result<Vec<u8>, WireError> {
+        wire::encode(self.kind(), rc, pp.pp.digest(), self)
+    }
+
+    /// Decodes a proof encoded by `to_bytes`, failing if it was made for other public parameters or reduction count
+    pub fn from_bytes(bytes: &[u8], pp: &PublicParams<F>, rc: usize) -> Result<Self, WireError> {
+        Self::from_bytes_with_digest(bytes, pp.pp.digest(), rc)
+    }
+
+    /// Like `from_bytes`, given only the digest of the public parameters
+    pub fn from_bytes_with_digest(bytes: &[u8], digest: F, rc: usize) -> Result<Self, WireError> {
+        let (kind, proof): (_, Self) = wire::decode(bytes, ProofKind::NovaRecursive, rc, digest)?;
+        if proof.kind() != kind {
+            return Err(WireError::PayloadKindMismatch {
+                header: kind,
+                payload: proof.kind(),
+            });
+        }
+        Ok(proof)
+    }
+}
+
 /// Computes a cache key of the primary circuit. The point is that if a circuit
 /// changes in any way but has the same `rc`/`Lang`, then we still want the
 /// public params to stay in sync with the changes.
@@ -206,6 +258,11 @@ pub fn public_params<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a>(
         &*commitment_size_hint1,
         &*commitment_size_hint2,
     );
+    let (primary_constraints, secondary_constraints) = pp.num_constraints();
+    metrics::gauge!(telemetry::CIRCUIT_CONSTRAINTS, "circuit" => "primary")
+        .set(primary_constraints as f64);
+    metrics::gauge!(telemetry::CIRCUIT_CONSTRAINTS, "circuit" => "secondary")
+        .set(secondary_constraints as f64);
     PublicParams {
         pp,
         pk_and_vk: OnceCell::new(),
@@ -260,6 +317,80 @@ impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<
         z0: &[F],
         steps: Vec<C1LEM<'a, F, C>>,
         store: &Store<F>,
+    ) -> Result<Self, ProofError> {
+        Self::prove_recursively_observed(pp, z0, steps, store, None, &CancellationToken::new())
+    }
+
+    fn compress(self, pp: &PublicParams<F>) -> Result<Self, ProofError> {
+        match self {
+            Self::Recursive(recursive_snark, num_steps, _phantom) => Ok(Self::Compressed(
+                Box::new(CompressedSNARK::<_, SS1<F>, SS2<F>>::prove(
+                    &pp.pp,
+                    pp.pk(),
+                    &recursive_snark,
+                )?),
+                num_steps,
+                PhantomData,
+            )),
+            Self::Compressed(..) => Ok(self),
+        }
+    }
+
+    fn verify(&self, pp: &Self::PublicParams, z0: &[F], zi: &[F]) -> Result<bool, Self::ErrorType> {
+        let (z0_primary, zi_primary) = (z0, zi);
+        let z0_secondary = Self::z0_secondary();
+        let zi_secondary = &z0_secondary;
+
+        let (zi_primary_verified, zi_secondary_verified) = match self {
+            Self::Recursive(p, num_steps, _phantom) => {
+                p.verify(&pp.pp, *num_steps, z0_primary, &z0_secondary)?
+            }
+            Self::Compressed(p, num_steps, _phantom) => {
+                p.verify(pp.vk(), *num_steps, z0_primary, &z0_secondary)?
+            }
+        };
+
+        Ok(zi_primary == zi_primary_verified && zi_secondary == &zi_secondary_verified)
+    }
+}
+
+impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> Proof<F, C1LEM<'a, F, C>> {
+    /// Verifies the proof, as `verify` does, and that the evaluation it proves took at most `budget` iterations. Only
+    /// metered evaluations carry their iteration count, so the proofs of others don't verify
+    pub fn verify_within_budget(
+        &self,
+        pp: &PublicParams<F>,
+        z0: &[F],
+        zi: &[F],
+        budget: usize,
+    ) -> Result<bool, NovaError> {
+        match proved_iterations(z0, zi) {
+            Some(iterations) if iterations <= budget => self.verify(pp, z0, zi),
+            _ => Ok(false),
+        }
+    }
+
+    /// Like `prove_recursively`, but reports every step to `events` and stops with `ProofError::Cancelled` once
+    /// `cancel` is cancelled.
+    #[tracing::instrument(skip_all, name = "nova::prove_recursively_with_events")]
+    pub fn prove_recursively_with_events(
+        pp: &PublicParams<F>,
+        z0: &[F],
+        steps: Vec<C1LEM<'a, F, C>>,
+        store: &Store<F>,
+        events: &dyn ProverEvents,
+        cancel: &CancellationToken,
+    ) -> Result<Self, ProofError> {
+        Self::prove_recursively_observed(pp, z0, steps, store, Some(events), cancel)
+    }
+
+    fn prove_recursively_observed(
+        pp: &PublicParams<F>,
+        z0: &[F],
+        steps: Vec<C1LEM<'a, F, C>>,
+        store: &Store<F>,
+        events: Option<&dyn ProverEvents>,
+        cancel: &CancellationToken,
     ) -> Result<Self, ProofError> {
         let debug = false;
         assert_eq!(steps[0].arity(), z0.len());
@@ -269,57 +400,83 @@ impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<
         let num_steps = steps.len();
         info!("proving {num_steps} steps");
 
+        let mut observer = StepObserver::new(events, cancel, num_steps);
         let mut recursive_snark_option: Option<RecursiveSNARK<E1<F>>> = None;
 
-        let prove_step =
-            |i: usize, step: &C1LEM<'a, F, C>, rs: &mut Option<RecursiveSNARK<E1<F>>>| {
-                if debug {
-                    debug_step(step, store).unwrap();
-                }
-                let mut recursive_snark = rs.take().unwrap_or_else(|| {
-                    RecursiveSNARK::new(&pp.pp, step, &secondary_circuit, z0, &Self::z0_secondary())
-                        .expect("failed to construct initial recursive SNARK")
-                });
-                info!("prove_step {i}");
-                recursive_snark
-                    .prove_step(&pp.pp, step, &secondary_circuit)
-                    .unwrap();
+        let mut prove_step = |i: usize,
+                              step: &C1LEM<'a, F, C>,
+                              rs: &mut Option<RecursiveSNARK<E1<F>>>|
+         -> Result<(), ProofError> {
+            if debug {
+                debug_step(step, store).unwrap();
+            }
+            observer.observe(i, step, || {
+                let mut recursive_snark = match rs.take() {
+                    Some(recursive_snark) => recursive_snark,
+                    None => RecursiveSNARK::new(
+                        &pp.pp,
+                        step,
+                        &secondary_circuit,
+                        z0,
+                        &Self::z0_secondary(),
+                    )?,
+                };
+                telemetry::fold_step(i, 0, || {
+                    recursive_snark.prove_step(&pp.pp, step, &secondary_circuit)
+                })?;
                 *rs = Some(recursive_snark);
-            };
+                Ok(())
+            })
+        };
 
-        recursive_snark_option = if lurk_config(None, None)
+        if lurk_config(None, None)
             .perf
             .parallelism
             .recursive_steps
             .is_parallel()
         {
             let cc = steps.into_iter().map(Mutex::new).collect::<Vec<_>>();
+            // Tells the witness thread to stop early when proving fails
+            let stop = AtomicBool::new(false);
+            // Where the witness thread reports a failure to cache a witness
+            let (witness_errors, witness_error) = std::sync::mpsc::channel();
 
             std::thread::scope(|s| {
                 s.spawn(|| {
                     // Skip the very first circuit's witness, so `prove_step` can begin immediately.
                     // That circuit's witness will not be cached and will just be computed on-demand.
-                    cc.iter().skip(1).for_each(|mf| {
-                        mf.lock()
-                            .unwrap()
-                            .cache_witness(store)
-                            .expect("witness caching failed");
-                    });
+                    for mf in cc.iter().skip(1) {
+                        if stop.load(Ordering::Relaxed) || cancel.is_cancelled() {
+                            break;
+                        }
+                        if let Err(e) = mf.lock().unwrap().cache_witness(store) {
+                            // The receiver outlives this thread
+                            witness_errors.send(e).unwrap();
+                            break;
+                        }
+                    }
                 });
 
                 for (i, step) in cc.iter().enumerate() {
+                    if let Ok(e) = witness_error.try_recv() {
+                        stop.store(true, Ordering::Relaxed);
+                        return Err(e.into());
+                    }
                     let mut step = step.lock().unwrap();
-                    prove_step(i, &step, &mut recursive_snark_option);
+                    let result = prove_step(i, &step, &mut recursive_snark_option);
                     step.clear_cached_witness();
+                    if result.is_err() {
+                        stop.store(true, Ordering::Relaxed);
+                        return result;
+                    }
                 }
-                recursive_snark_option
-            })
+                Ok(())
+            })?;
         } else {
             for (i, step) in steps.iter().enumerate() {
-                prove_step(i, step, &mut recursive_snark_option);
+                prove_step(i, step, &mut recursive_snark_option)?;
             }
-            recursive_snark_option
-        };
+        }
 
         Ok(Self::Recursive(
             Box::new(recursive_snark_option.expect("RecursiveSNARK missing")),
@@ -328,36 +485,77 @@ impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<
         ))
     }
 
-    fn compress(self, pp: &PublicParams<F>) -> Result<Self, ProofError> {
-        match self {
-            Self::Recursive(recursive_snark, num_steps, _phantom) => Ok(Self::Compressed(
-                Box::new(CompressedSNARK::<_, SS1<F>, SS2<F>>::prove(
-                    &pp.pp,
-                    pp.pk(),
-                    &recursive_snark,
-                )?),
-                num_steps,
-                PhantomData,
-            )),
-            Self::Compressed(..) => Ok(self),
-        }
+    /// Like `prove_recursively`, but pulls `steps` lazily and drops each step as soon as it's folded. The witness of
+    /// the next step is generated on another thread while the current one is folded, so at most two steps are alive
+    /// at any time and peak memory doesn't grow with the number of steps. This is `prove_pipelined` with a single
+    /// witness worker.
+    #[tracing::instrument(skip_all, name = "nova::prove_streaming")]
+    pub fn prove_streaming<I>(
+        pp: &PublicParams<F>,
+        z0: &[F],
+        steps: I,
+        store: &'a Store<F>,
+    ) -> Result<Self, ProofError>
+    where
+        I: IntoIterator<Item = C1LEM<'a, F, C>>,
+        I::IntoIter: Send,
+    {
+        let (proof, _) = Self::prove_pipelined(pp, z0, steps, store, &PipelineConfig::new(1))?;
+        Ok(proof)
     }
 
-    fn verify(&self, pp: &Self::PublicParams, z0: &[F], zi: &[F]) -> Result<bool, Self::ErrorType> {
-        let (z0_primary, zi_primary) = (z0, zi);
-        let z0_secondary = Self::z0_secondary();
-        let zi_secondary = &z0_secondary;
-
-        let (zi_primary_verified, zi_secondary_verified) = match self {
-            Self::Recursive(p, num_steps, _phantom) => {
-                p.verify(&pp.pp, *num_steps, z0_primary, &z0_secondary)?
-            }
-            Self::Compressed(p, num_steps, _phantom) => {
-                p.verify(pp.vk(), *num_steps, z0_primary, &z0_secondary)?
-            }
-        };
+    /// Like `prove_streaming`, but witnesses are synthesized by a pool of `config.witness_workers` threads, several
+    /// steps ahead of the folder. Returns the throughput metrics of the run along with the proof.
+    #[tracing::instrument(skip_all, name = "nova::prove_pipelined")]
+    pub fn prove_pipelined<I>(
+        pp: &PublicParams<F>,
+        z0: &[F],
+        steps: I,
+        store: &'a Store<F>,
+        config: &PipelineConfig,
+    ) -> Result<(Self, PipelineMetrics), ProofError>
+    where
+        I: IntoIterator<Item = C1LEM<'a, F, C>>,
+        I::IntoIter: Send,
+    {
+        let secondary_circuit = TrivialCircuit::default();
+        let mut recursive_snark_option: Option<RecursiveSNARK<E1<F>>> = None;
 
-        Ok(zi_primary == zi_primary_verified && zi_secondary == &zi_secondary_verified)
+        let metrics = pipeline::run(
+            config,
+            steps.into_iter(),
+            |step: &mut C1LEM<'a, F, C>| Ok(step.cache_witness(store)?),
+            |i, step| {
+                let mut recursive_snark = match recursive_snark_option.take() {
+                    Some(recursive_snark) => recursive_snark,
+                    None => RecursiveSNARK::new(
+                        &pp.pp,
+                        &step,
+                        &secondary_circuit,
+                        z0,
+                        &Self::z0_secondary(),
+                    )?,
+                };
+                telemetry::fold_step(i, 0, || {
+                    recursive_snark.prove_step(&pp.pp, &step, &secondary_circuit)
+                })?;
+                recursive_snark_option = Some(recursive_snark);
+                Ok(())
+            },
+        )?;
+        info!(
+            "folded {} steps at {:.2} steps/s, fold utilization {:.2}, witness utilization {:.2}",
+            metrics.num_steps,
+            metrics.steps_per_second(),
+            metrics.fold_utilization(),
+            metrics.witness_utilization()
+        );
+
+        let recursive_snark = recursive_snark_option.ok_or_else(|| {
+            ProofError::Reduction(ReductionError::Misc("no steps to prove".into()))
+        })?;
+        let proof = Self::Recursive(Box::new(recursive_snark), metrics.num_steps, PhantomData);
+        Ok((proof, metrics))
     }
 }
 
@@ -397,8 +595,69 @@ impl<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a> NovaProver<'a, F, C> {
         self.prove(pp, steps, store)
     }
 
+    /// Like `prove_from_frames`, but builds the steps from `frames` as they are needed and proves them with
+    /// `Proof::prove_streaming`, for computations too long to hold the witnesses of every step in memory.
+    pub fn prove_from_frames_streaming(
+        &self,
+        pp: &PublicParams<F>,
+        frames: &[Frame],
+        store: &'a Store<F>,
+    ) -> Result<(Proof<F, C1LEM<'a, F, C>>, Vec<F>, Vec<F>, usize), ProofError> {
+        let (Some(first), Some(last)) = (frames.first(), frames.last()) else {
+            return Err(ProofError::Reduction(ReductionError::Misc(
+                "no frames to prove".into(),
+            )));
+        };
+        store.hydrate_z_cache();
+        let z0 = store.to_scalar_vector(&first.input);
+        let zi = store.to_scalar_vector(&last.output);
+
+        let folding_config: Arc<_> = self
+            .folding_mode()
+            .folding_config(self.lang().clone(), self.reduction_count())
+            .into();
+        let num_steps = self.expected_num_steps(frames.len());
+        let steps = frames
+            .chunks(self.reduction_count())
+            .flat_map(|chunk| C1LEM::<'a, F, C>::from_frames(chunk, store, &folding_config));
+
+        let proof = Proof::prove_streaming(pp, &z0, steps, store)?;
+        Ok((proof, z0, zi, num_steps))
+    }
+
+    /// Like `prove_from_frames_streaming`, but proves with `Proof::prove_pipelined`. Also returns the throughput
+    /// metrics of the run.
+    pub fn prove_from_frames_pipelined(
+        &self,
+        pp: &PublicParams<F>,
+        frames: &[Frame],
+        store: &'a Store<F>,
+        config: &PipelineConfig,
+    ) -> Result<(Proof<F, C1LEM<'a, F, C>>, Vec<F>, Vec<F>, PipelineMetrics), ProofError> {
+        let (Some(first), Some(last)) = (frames.first(), frames.last()) else {
+            return Err(ProofError::Reduction(ReductionError::Misc(
+                "no frames to prove".into(),
+            )));
+        };
+        store.hydrate_z_cache();
+        let z0 = store.to_scalar_vector(&first.input);
+        let zi = store.to_scalar_vector(&last.output);
+
+        let folding_config: Arc<_> = self
+            .folding_mode()
+            .folding_config(self.lang().clone(), self.reduction_count())
+            .into();
+        let steps = frames
+            .chunks(self.reduction_count())
+            .flat_map(|chunk| C1LEM::<'a, F, C>::from_frames(chunk, store, &folding_config));
+
+        let (proof, metrics) = Proof::prove_pipelined(pp, &z0, steps, store, config)?;
+        Ok((proof, z0, zi, metrics))
+    }
+
+    /// The `Lang` proved by this prover
     #[inline]
-    fn lang(&self) -> &Arc<Lang<F, C>> {
+    pub fn lang(&self) -> &Arc<Lang<F, C>> {
         &self.lang
     }
 }
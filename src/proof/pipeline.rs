@@ -0,0 +1,263 @@
+//! A pipelined executor for folding.
+//!
+//! Folding a step needs the accumulator produced by the previous one, so folds run one after the other. Preparing a
+//! step, i.e. building it and synthesizing its witness, only depends on the step itself, and usually costs as much as
+//! folding it. The executor hence runs two stages: a pool of witness workers preparing upcoming steps in parallel, and
+//! a single folder consuming them in order. At most `max_in_flight` prepared steps wait for the folder, which bounds
+//! memory regardless of the number of steps.
+
+use std::{
+    collections::BTreeMap,
+    sync::{mpsc::sync_channel, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::config::{lurk_config, Flow};
+
+/// Worker counts of the pipeline stages
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PipelineConfig {
+    /// The number of threads synthesizing witnesses. Folding always runs on a single thread.
+    pub witness_workers: usize,
+    /// The maximum number of steps being prepared or waiting to be folded
+    pub max_in_flight: usize,
+}
+
+impl PipelineConfig {
+    /// A pipeline with `witness_workers` workers, each allowed one prepared step ahead of the folder
+    pub fn new(witness_workers: usize) -> Self {
+        let witness_workers = witness_workers.max(1);
+        Self {
+            witness_workers,
+            max_in_flight: 2 * witness_workers,
+        }
+    }
+}
+
+impl Default for PipelineConfig {
+    /// Follows the `recursive_steps` setting of the Lurk configuration, leaving a core to the folder
+    fn default() -> Self {
+        let witness_workers = match lurk_config(None, None).perf.parallelism.recursive_steps {
+            Flow::Sequential => 1,
+            Flow::Parallel => {
+                std::thread::available_parallelism().map_or(1, |n| n.get().saturating_sub(1))
+            }
+            Flow::ParallelN(n) => n,
+        };
+        Self::new(witness_workers)
+    }
+}
+
+/// Throughput metrics of a pipelined run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineMetrics {
+    /// The number of folded steps
+    pub num_steps: usize,
+    /// The number of witness workers used
+    pub witness_workers: usize,
+    /// The time spent synthesizing witnesses, summed over all workers
+    pub witness_time: Duration,
+    /// The time spent folding
+    pub fold_time: Duration,
+    /// The duration of the whole run
+    pub wall_time: Duration,
+}
+
+impl PipelineMetrics {
+    /// Folded steps per second
+    pub fn steps_per_second(&self) -> f64 {
+        self.num_steps as f64 / self.wall_time.as_secs_f64()
+    }
+
+    /// The fraction of the run the folder was busy. Close to 1 means folding is the bottleneck, while lower values
+    /// call for more witness workers.
+    pub fn fold_utilization(&self) -> f64 {
+        self.fold_time.as_secs_f64() / self.wall_time.as_secs_f64()
+    }
+
+    /// The fraction of the run the witness workers were busy, on average
+    pub fn witness_utilization(&self) -> f64 {
+        self.witness_time.as_secs_f64()
+            / (self.wall_time.as_secs_f64() * self.witness_workers as f64)
+    }
+}
+
+/// A counting semaphore that can be closed to wake up and release every waiter
+struct Permits {
+    state: Mutex<(usize, bool)>,
+    cond: Condvar,
+}
+
+impl Permits {
+    fn new(available: usize) -> Self {
+        Self {
+            state: Mutex::new((available, false)),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Waits for a permit, returning `false` if closed in the meantime
+    fn acquire(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while state.0 == 0 && !state.1 {
+            state = self.cond.wait(state).unwrap();
+        }
+        if state.1 {
+            return false;
+        }
+        state.0 -= 1;
+        true
+    }
+
+    fn release(&self) {
+        self.state.lock().unwrap().0 += 1;
+        self.cond.notify_one();
+    }
+
+    fn close(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.cond.notify_all();
+    }
+}
+
+/// Runs `prepare` on every step of `steps` on the witness workers, and `fold` on the prepared steps, in order, on the
+/// calling thread. Stops at the first error of either stage.
+pub(crate) fn run<T, E, I, P, G>(
+    config: &PipelineConfig,
+    steps: I,
+    prepare: P,
+    mut fold: G,
+) -> Result<PipelineMetrics, E>
+where
+    T: Send,
+    E: Send,
+    I: Iterator<Item = T> + Send,
+    P: Fn(&mut T) -> Result<(), E> + Sync,
+    G: FnMut(usize, T) -> Result<(), E>,
+{
+    let witness_workers = config.witness_workers.max(1);
+    let start = Instant::now();
+    let source = Mutex::new(steps.enumerate());
+    let permits = Permits::new(config.max_in_flight.max(1));
+    let witness_time = Mutex::new(Duration::ZERO);
+    let mut fold_time = Duration::ZERO;
+    let mut num_steps = 0;
+
+    std::thread::scope(|s| {
+        let (sender, receiver) = sync_channel(config.max_in_flight.max(1));
+        for _ in 0..witness_workers {
+            let sender = sender.clone();
+            let (source, permits, prepare, witness_time) =
+                (&source, &permits, &prepare, &witness_time);
+            s.spawn(move || {
+                while permits.acquire() {
+                    let Some((i, mut step)) = source.lock().unwrap().next() else {
+                        // Let the other workers find out there are no steps left
+                        permits.release();
+                        break;
+                    };
+                    let now = Instant::now();
+                    let prepared = prepare(&mut step).map(|()| step);
+                    *witness_time.lock().unwrap() += now.elapsed();
+                    let failed = prepared.is_err();
+                    // The receiver is gone if folding failed
+                    if sender.send((i, prepared)).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // Workers may finish out of order, so prepared steps wait here until their turn
+        let mut pending = BTreeMap::new();
+        let fold_all = || -> Result<(), E> {
+            for (i, prepared) in receiver {
+                pending.insert(i, prepared?);
+                while let Some(step) = pending.remove(&num_steps) {
+                    let now = Instant::now();
+                    fold(num_steps, step)?;
+                    fold_time += now.elapsed();
+                    num_steps += 1;
+                    permits.release();
+                }
+            }
+            Ok(())
+        };
+        let result = fold_all();
+        if result.is_err() {
+            permits.close();
+        }
+        result
+    })?;
+
+    Ok(PipelineMetrics {
+        num_steps,
+        witness_workers,
+        witness_time: witness_time.into_inner().unwrap(),
+        fold_time,
+        wall_time: start.elapsed(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folds_in_order() {
+        for witness_workers in [1, 2, 5] {
+            let config = PipelineConfig::new(witness_workers);
+            let mut folded = vec![];
+            let metrics = run(
+                &config,
+                (0..50u64).map(|i| (i, 0)),
+                |(i, square): &mut (u64, u64)| {
+                    // make later steps faster so workers finish out of order
+                    std::thread::sleep(Duration::from_micros(50 - *i));
+                    *square = *i * *i;
+                    Ok::<_, ()>(())
+                },
+                |index, (i, square)| {
+                    assert_eq!(index as u64, i);
+                    folded.push(square);
+                    Ok(())
+                },
+            )
+            .unwrap();
+            assert_eq!((0..50).map(|i| i * i).collect::<Vec<_>>(), folded);
+            assert_eq!(50, metrics.num_steps);
+            assert_eq!(witness_workers, metrics.witness_workers);
+            assert!(metrics.fold_time <= metrics.wall_time);
+        }
+    }
+
+    #[test]
+    fn test_stops_at_errors() {
+        let config = PipelineConfig::new(3);
+        let prepare_err = run(
+            &config,
+            0..100,
+            |i: &mut i32| if *i == 10 { Err("prepare") } else { Ok(()) },
+            |_, _| Ok(()),
+        );
+        assert_eq!(Err("prepare"), prepare_err);
+
+        let mut folded = 0;
+        let fold_err = run(
+            &config,
+            0..100,
+            |_: &mut i32| Ok(()),
+            |_, i| {
+                folded += 1;
+                if i == 10 {
+                    Err("fold")
+                } else {
+                    Ok(())
+                }
+            },
+        );
+        assert_eq!(Err("fold"), fold_err);
+        assert_eq!(11, folded);
+    }
+}
@@ -16,7 +16,10 @@ use serde::{Deserialize, Serialize};
 use std::{
     marker::PhantomData,
     ops::Index,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
 };
 use tracing::info;
 
@@ -28,9 +31,12 @@ use crate::{
     field::LurkField,
     lem::{interpreter::Frame, pointers::Ptr, store::Store},
     proof::{
+        events::{CancellationToken, ProverEvents, StepObserver},
         nova::{debug_step, CurveCycleEquipped, Dual, NovaCircuitShape, E1},
+        wire::{self, ProofKind, WireError},
         Prover, RecursiveSNARKTrait,
     },
+    telemetry,
 };
 
 use super::{nova::C1LEM, FoldingMode};
@@ -45,11 +51,13 @@ pub type SuperNovaAuxParams<F> = AuxParams<E1<F>>;
 pub type SuperNovaPublicParams<F> = supernova::PublicParams<E1<F>>;
 
 /// A struct that contains public parameters for the SuperNova proving system.
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct PublicParams<F: CurveCycleEquipped> {
     /// Public params for SuperNova.
     pub pp: SuperNovaPublicParams<F>,
     /// Prover key and Verifier key for SuperNova
-    // TODO: mark as #[serde(skip)] when serializing
+    #[serde(skip)]
     pub pk_and_vk: OnceCell<(
         ProverKey<E1<F>, SS1<F>, SS2<F>>,
         VerifierKey<E1<F>, SS1<F>, SS2<F>>,
@@ -145,94 +153,93 @@ pub enum Proof<F: CurveCycleEquipped, S> {
     Compressed(Box<CompressedSNARK<E1<F>, SS1<F>, SS2<F>>>, PhantomData<S>),
 }
 
-/// A struct for the Nova prover that operates on field elements of type `F`.
-#[derive(Debug)]
-pub struct SuperNovaProver<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a> {
-    /// The number of small-step reductions performed in each recursive step of
-    /// the primary Lurk circuit.
-    reduction_count: usize,
-    lang: Arc<Lang<F, C>>,
-    folding_mode: FoldingMode,
-    _phantom: PhantomData<&'a ()>,
-}
-
-impl<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a> SuperNovaProver<'a, F, C> {
-    /// Create a new SuperNovaProver with a reduction count and a `Lang`
-    #[inline]
-    pub fn new(reduction_count: usize, lang: Arc<Lang<F, C>>) -> Self {
-        Self {
-            reduction_count,
-            lang,
-            folding_mode: FoldingMode::NIVC,
-            _phantom: PhantomData,
+impl<F: CurveCycleEquipped, S> Proof<F, S> {
+    fn kind(&self) -> ProofKind {
+        match self {
+            Self::Recursive(..) => ProofKind::SuperNovaRecursive,
+            Self::Compressed(..) => ProofKind::SuperNovaCompressed,
         }
     }
 
-    /// Generate a proof from a sequence of frames
-    pub fn prove_from_frames(
-        &self,
-        pp: &PublicParams<F>,
-        frames: &[Frame],
-        store: &'a Store<F>,
-    ) -> Result<(Proof<F, C1LEM<'a, F, C>>, Vec<F>, Vec<F>, usize), ProofError> {
-        let folding_config = self
-            .folding_mode()
-            .folding_config(self.lang().clone(), self.reduction_count());
-        let steps = C1LEM::<'a, F, C>::from_frames(frames, store, &folding_config.into());
-        self.prove(pp, steps, store)
+    /// Encodes the proof in the format of [`crate::proof::wire`], tagged with the digest of `pp` and the reduction
+    /// count `rc` it was made with
+    pub fn to_bytes(&self, pp: &PublicParams<F>, rc: usize) -> Result<Vec<u8>, WireError> {
+        wire::encode(self.kind(), rc, pp.digest(), self)
     }
 
-    #[inline]
-    fn lang(&self) -> &Arc<Lang<F, C>> {
-        &self.lang
+    /// Decodes a proof encoded by `to_bytes`, failing if it was made for other public parameters or reduction count
+    pub fn from_bytes(bytes: &[u8], pp: &PublicParams<F>, rc: usize) -> Result<Self, WireError> {
+        let (kind, proof): (_, Self) =
+            wire::decode(bytes, ProofKind::SuperNovaRecursive, rc, pp.digest())?;
+        if proof.kind() != kind {
+            return Err(WireError::PayloadKindMismatch {
+                header: kind,
+                payload: proof.kind(),
+            });
+        }
+        Ok(proof)
     }
 }
 
-impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<'a, F, C>>
-    for Proof<F, C1LEM<'a, F, C>>
-{
-    type PublicParams = PublicParams<F>;
-
-    type ErrorType = SuperNovaError;
+impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> Proof<F, C1LEM<'a, F, C>> {
+    /// Like `prove_recursively`, but reports every step to `events` and stops with `ProofError::Cancelled` once
+    /// `cancel` is cancelled.
+    #[tracing::instrument(skip_all, name = "supernova::prove_recursively_with_events")]
+    pub fn prove_recursively_with_events(
+        pp: &PublicParams<F>,
+        z0: &[F],
+        steps: Vec<C1LEM<'a, F, C>>,
+        store: &Store<F>,
+        events: &dyn ProverEvents,
+        cancel: &CancellationToken,
+    ) -> Result<Self, ProofError> {
+        Self::prove_recursively_observed(pp, z0, steps, store, Some(events), cancel)
+    }
 
-    #[tracing::instrument(skip_all, name = "supernova::prove_recursively")]
-    fn prove_recursively(
+    fn prove_recursively_observed(
         pp: &PublicParams<F>,
         z0: &[F],
         steps: Vec<C1LEM<'a, F, C>>,
         store: &Store<F>,
+        events: Option<&dyn ProverEvents>,
+        cancel: &CancellationToken,
     ) -> Result<Self, ProofError> {
         let debug = false;
 
         info!("proving {} steps", steps.len());
 
+        let mut observer = StepObserver::new(events, cancel, steps.len());
         let mut recursive_snark_option: Option<RecursiveSNARK<E1<F>>> = None;
 
-        let prove_step =
-            |i: usize, step: &C1LEM<'a, F, C>, rs: &mut Option<RecursiveSNARK<E1<F>>>| {
-                if debug {
-                    debug_step(step, store).unwrap();
-                }
+        let mut prove_step = |i: usize,
+                              step: &C1LEM<'a, F, C>,
+                              rs: &mut Option<RecursiveSNARK<E1<F>>>|
+         -> Result<(), ProofError> {
+            if debug {
+                debug_step(step, store).unwrap();
+            }
+            observer.observe(i, step, || {
                 let secondary_circuit = step.secondary_circuit();
-                let mut recursive_snark = rs.take().unwrap_or_else(|| {
-                    RecursiveSNARK::new(
+                let mut recursive_snark = match rs.take() {
+                    Some(recursive_snark) => recursive_snark,
+                    None => RecursiveSNARK::new(
                         &pp.pp,
                         step,
                         step,
                         &secondary_circuit,
                         z0,
                         &Self::z0_secondary(),
-                    )
-                    .expect("failed to construct initial recursive SNARK")
-                });
-                info!("prove_step {i}");
-                recursive_snark
-                    .prove_step(&pp.pp, step, &secondary_circuit)
-                    .unwrap();
+                    )?,
+                };
+                telemetry::fold_step(i, step.program_counter(), || {
+                    recursive_snark.prove_step(&pp.pp, step, &secondary_circuit)
+                })?;
                 *rs = Some(recursive_snark);
-            };
+                Ok(())
+            })
+        };
 
-        recursive_snark_option = if lurk_config(None, None)
+        if lurk_config(None, None)
             .perf
             .parallelism
             .recursive_steps
@@ -242,6 +249,18 @@ impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<
                 .into_iter()
                 .map(|mf| (mf.program_counter() == 0, Mutex::new(mf)))
                 .collect::<Vec<_>>();
+            // Tells the witness thread to stop early when proving fails
+            let stop = AtomicBool::new(false);
+            let keep_going = || !stop.load(Ordering::Relaxed) && !cancel.is_cancelled();
+            // Where the witness thread reports a failure to cache a witness
+            let (witness_errors, witness_error) = std::sync::mpsc::channel();
+            let cache_witness = |mf: &Mutex<C1LEM<'a, F, C>>| {
+                if let Err(e) = mf.lock().unwrap().cache_witness(store) {
+                    stop.store(true, Ordering::Relaxed);
+                    // The receiver outlives the witness thread
+                    witness_errors.send(e).unwrap();
+                }
+            };
 
             std::thread::scope(|s| {
                 s.spawn(|| {
@@ -253,12 +272,8 @@ impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<
                     cc.iter()
                         .skip(1)
                         .filter(|(is_zero_pc, _)| *is_zero_pc)
-                        .for_each(|(_, mf)| {
-                            mf.lock()
-                                .unwrap()
-                                .cache_witness(store)
-                                .expect("witness caching failed");
-                        });
+                        .take_while(|_| keep_going())
+                        .for_each(|(_, mf)| cache_witness(mf));
 
                     // There shouldn't be as many MultiFrames with PC != 0 and they only have one inner frame, each with
                     // poor internal parallelism for witness generation, so we can generate their witnesses in parallel.
@@ -267,34 +282,97 @@ impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<
                     // the non-parallel one above (and getting rid of the filters) is better
                     cc.par_iter()
                         .skip(1)
-                        .filter(|(is_zero_pc, _)| !*is_zero_pc)
-                        .for_each(|(_, mf)| {
-                            mf.lock()
-                                .unwrap()
-                                .cache_witness(store)
-                                .expect("witness caching failed");
-                        });
+                        .filter(|(is_zero_pc, _)| !*is_zero_pc && keep_going())
+                        .for_each(|(_, mf)| cache_witness(mf));
                 });
 
                 for (i, (_, step)) in cc.iter().enumerate() {
+                    if let Ok(e) = witness_error.try_recv() {
+                        stop.store(true, Ordering::Relaxed);
+                        return Err(e.into());
+                    }
                     let mut step = step.lock().unwrap();
-                    prove_step(i, &step, &mut recursive_snark_option);
+                    let result = prove_step(i, &step, &mut recursive_snark_option);
                     step.clear_cached_witness();
+                    if result.is_err() {
+                        stop.store(true, Ordering::Relaxed);
+                        return result;
+                    }
                 }
-                recursive_snark_option
-            })
+                Ok(())
+            })?;
         } else {
             for (i, step) in steps.iter().enumerate() {
-                prove_step(i, step, &mut recursive_snark_option);
+                prove_step(i, step, &mut recursive_snark_option)?;
             }
-            recursive_snark_option
-        };
+        }
 
         Ok(Self::Recursive(
             Box::new(recursive_snark_option.expect("RecursiveSNARK missing")),
             PhantomData,
         ))
     }
+}
+
+/// A struct for the Nova prover that operates on field elements of type `F`.
+#[derive(Debug)]
+pub struct SuperNovaProver<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a> {
+    /// The number of small-step reductions performed in each recursive step of
+    /// the primary Lurk circuit.
+    reduction_count: usize,
+    lang: Arc<Lang<F, C>>,
+    folding_mode: FoldingMode,
+    _phantom: PhantomData<&'a ()>,
+}
+
+impl<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a> SuperNovaProver<'a, F, C> {
+    /// Create a new SuperNovaProver with a reduction count and a `Lang`
+    #[inline]
+    pub fn new(reduction_count: usize, lang: Arc<Lang<F, C>>) -> Self {
+        Self {
+            reduction_count,
+            lang,
+            folding_mode: FoldingMode::NIVC,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Generate a proof from a sequence of frames
+    pub fn prove_from_frames(
+        &self,
+        pp: &PublicParams<F>,
+        frames: &[Frame],
+        store: &'a Store<F>,
+    ) -> Result<(Proof<F, C1LEM<'a, F, C>>, Vec<F>, Vec<F>, usize), ProofError> {
+        let folding_config = self
+            .folding_mode()
+            .folding_config(self.lang().clone(), self.reduction_count());
+        let steps = C1LEM::<'a, F, C>::from_frames(frames, store, &folding_config.into());
+        self.prove(pp, steps, store)
+    }
+
+    #[inline]
+    fn lang(&self) -> &Arc<Lang<F, C>> {
+        &self.lang
+    }
+}
+
+impl<'a, F: CurveCycleEquipped, C: Coprocessor<F>> RecursiveSNARKTrait<F, C1LEM<'a, F, C>>
+    for Proof<F, C1LEM<'a, F, C>>
+{
+    type PublicParams = PublicParams<F>;
+
+    type ErrorType = SuperNovaError;
+
+    #[tracing::instrument(skip_all, name = "supernova::prove_recursively")]
+    fn prove_recursively(
+        pp: &PublicParams<F>,
+        z0: &[F],
+        steps: Vec<C1LEM<'a, F, C>>,
+        store: &Store<F>,
+    ) -> Result<Self, ProofError> {
+        Self::prove_recursively_observed(pp, z0, steps, store, None, &CancellationToken::new())
+    }
 
     fn compress(self, pp: &PublicParams<F>) -> Result<Self, ProofError> {
         match &self {
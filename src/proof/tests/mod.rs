@@ -135,6 +135,57 @@ fn nova_test_full_aux2<'a, F: CurveCycleEquipped, C: Coprocessor<F> + 'a>(
         let res2 = compressed.verify(&pp, &z0, &zi);
 
         assert!(res2.unwrap());
+
+        match crate::proof::proved_iterations(&z0, &zi) {
+            Some(iterations) => {
+                assert!(lang.is_metered());
+                assert!(compressed
+                    .verify_within_budget(&pp, &z0, &zi, iterations)
+                    .unwrap());
+                assert!(!compressed
+                    .verify_within_budget(&pp, &z0, &zi, iterations - 1)
+                    .unwrap());
+            }
+            None => {
+                assert!(!lang.is_metered());
+                assert!(!compressed
+                    .verify_within_budget(&pp, &z0, &zi, usize::MAX)
+                    .unwrap());
+            }
+        }
+
+        let (streamed, z0_streamed, zi_streamed, num_steps) = nova_prover
+            .prove_from_frames_streaming(&pp, &frames, s)
+            .unwrap();
+        assert_eq!(z0, z0_streamed);
+        assert_eq!(zi, zi_streamed);
+        assert!(streamed.verify(&pp, &z0, &zi).unwrap());
+
+        let pipeline_config = crate::proof::pipeline::PipelineConfig::new(2);
+        let (pipelined, z0_pipelined, zi_pipelined, metrics) = nova_prover
+            .prove_from_frames_pipelined(&pp, &frames, s, &pipeline_config)
+            .unwrap();
+        assert_eq!(z0, z0_pipelined);
+        assert_eq!(zi, zi_pipelined);
+        assert_eq!(num_steps, metrics.num_steps);
+        assert!(pipelined.verify(&pp, &z0, &zi).unwrap());
+
+        let bytes = compressed.to_bytes(&pp, reduction_count).unwrap();
+        let decoded = crate::proof::nova::Proof::<F, C1LEM<'a, F, C>>::from_bytes(
+            &bytes,
+            &pp,
+            reduction_count,
+        )
+        .unwrap();
+        assert!(decoded.verify(&pp, &z0, &zi).unwrap());
+        assert!(matches!(
+            crate::proof::nova::Proof::<F, C1LEM<'a, F, C>>::from_bytes(
+                &bytes,
+                &pp,
+                reduction_count + 1
+            ),
+            Err(crate::proof::wire::WireError::ReductionCountMismatch { .. })
+        ));
     }
 
     let folding_config = Arc::new(FoldingConfig::new_ivc(lang, nova_prover.reduction_count()));
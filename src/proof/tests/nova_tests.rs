@@ -128,6 +128,40 @@ fn test_prove_arithmetic_let() {
     );
 }
 
+#[test]
+fn test_prove_metered() {
+    let s = &Store::<Fr>::default();
+    let mut lang = Lang::<Fr, Coproc<Fr>>::new();
+    lang.set_metered(true);
+    let lang = Some(Arc::new(lang));
+    let terminal = s.cont_terminal();
+    // `(current-step)` is the number of iterations taken before it's reduced
+    nova_test_full_aux::<_, Coproc<_>>(
+        s,
+        "(+ (current-step) (current-step))",
+        Some(s.num_u64(3)),
+        None,
+        Some(terminal),
+        None,
+        &expect!["3"],
+        DEFAULT_REDUCTION_COUNT,
+        true,
+        None,
+        &lang,
+    );
+    // without metering, `current-step` is unbound
+    test_aux::<_, Coproc<_>>(
+        s,
+        "(current-step)",
+        None,
+        None,
+        Some(s.cont_error()),
+        None,
+        &expect!["2"],
+        &None,
+    );
+}
+
 #[test]
 #[ignore]
 fn test_prove_eq() {
@@ -4371,3 +4405,85 @@ fn test_letrec_sequencing() {
         &None,
     );
 }
+
+#[test]
+fn test_prover_events_and_cancellation() {
+    use std::sync::Mutex;
+
+    use crate::{
+        error::ProofError,
+        lem::eval::EvalConfig,
+        proof::{
+            events::{CancellationToken, ProverEvents, StepInfo},
+            nova::{public_params, Proof, C1LEM},
+            supernova::FoldingConfig,
+            RecursiveSNARKTrait,
+        },
+    };
+
+    #[derive(Default)]
+    struct Recorder {
+        finished: Mutex<Vec<StepInfo>>,
+        cancel_after_first: Option<CancellationToken>,
+    }
+
+    impl ProverEvents for Recorder {
+        fn step_finished(&self, step: &StepInfo, _elapsed: std::time::Duration) {
+            self.finished.lock().unwrap().push(step.clone());
+            if let Some(cancel) = &self.cancel_after_first {
+                cancel.cancel();
+            }
+        }
+    }
+
+    let s = &Store::<Fr>::default();
+    let lang = Arc::new(Lang::<Fr, Coproc<Fr>>::new());
+    let expr = s.read_with_default_state("(+ 1 (+ 2 (+ 3 4)))").unwrap();
+    let frames = C1LEM::build_frames(
+        expr,
+        s.intern_empty_env(),
+        s,
+        100,
+        &EvalConfig::new_ivc(&lang),
+    )
+    .unwrap();
+    let folding_config = Arc::new(FoldingConfig::new_ivc(lang.clone(), 2));
+    let steps = C1LEM::from_frames(&frames, s, &folding_config);
+    let num_steps = steps.len();
+    assert!(num_steps > 1);
+    let pp = public_params(2, lang.clone());
+    s.hydrate_z_cache();
+    let z0 = s.to_scalar_vector(&frames[0].input);
+    let zi = s.to_scalar_vector(&frames.last().unwrap().output);
+
+    let recorder = Recorder::default();
+    let proof = Proof::prove_recursively_with_events(
+        &pp,
+        &z0,
+        steps.clone(),
+        s,
+        &recorder,
+        &CancellationToken::new(),
+    )
+    .unwrap();
+    assert!(proof.verify(&pp, &z0, &zi).unwrap());
+    let finished = recorder.finished.into_inner().unwrap();
+    assert_eq!(num_steps, finished.len());
+    for (i, step) in finished.iter().enumerate() {
+        assert_eq!(i, step.index);
+        assert_eq!(num_steps, step.num_steps);
+        assert_eq!(0, step.circuit_index);
+        assert!(step.num_constraints > 0);
+    }
+
+    let cancel = CancellationToken::new();
+    let recorder = Recorder {
+        cancel_after_first: Some(cancel.clone()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        Proof::prove_recursively_with_events(&pp, &z0, steps, s, &recorder, &cancel),
+        Err(ProofError::Cancelled)
+    ));
+    assert_eq!(1, recorder.finished.into_inner().unwrap().len());
+}
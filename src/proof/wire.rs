@@ -0,0 +1,307 @@
+//! A stable binary format for Nova and SuperNova proofs.
+//!
+//! Every encoded proof starts with a fixed header, followed by the bincode-serialized proof:
+//!
+//! | bytes       | content                                        |
+//! |-------------|------------------------------------------------|
+//! | 4           | magic bytes `LRKP`                             |
+//! | 2           | format version, little-endian                  |
+//! | 1           | field id                                       |
+//! | 1           | proof kind                                     |
+//! | 8           | reduction count, little-endian                 |
+//! | 1 + n       | length and bytes of the public parameters digest |
+//! | rest        | payload                                        |
+//!
+//! Decoding checks the header against what the caller expects before touching the payload, so that a proof made for
+//! another field, circuit or format version is reported as such rather than as a garbled payload. The header itself is
+//! read and written by `lurk_core::wire`, which doesn't need `std`.
+
+use ff::PrimeField;
+use lurk_core::wire::{HeaderError, RawHeader};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+
+use crate::field::{LanguageField, LurkField};
+
+pub use lurk_core::wire::{ProofKind, FORMAT_VERSION, MAGIC};
+
+fn field_id(field: LanguageField) -> u8 {
+    match field {
+        LanguageField::BN256 => 0,
+        LanguageField::Grumpkin => 1,
+        LanguageField::Pallas => 2,
+        LanguageField::Vesta => 3,
+    }
+}
+
+fn field_from_id(id: u8) -> Option<LanguageField> {
+    match id {
+        0 => Some(LanguageField::BN256),
+        1 => Some(LanguageField::Grumpkin),
+        2 => Some(LanguageField::Pallas),
+        3 => Some(LanguageField::Vesta),
+        _ => None,
+    }
+}
+
+/// Errors found when decoding a proof. Header errors name the header field at fault.
+#[derive(Error, Debug)]
+pub enum WireError {
+    /// The bytes don't start with `MAGIC`
+    #[error("Not an encoded Lurk proof: the header doesn't start with `LRKP`")]
+    BadMagic,
+    /// The proof was encoded with another version of the format
+    #[error("Proof header `version` is {found}, expected {FORMAT_VERSION}")]
+    UnsupportedVersion {
+        /// The version found in the header
+        found: u16,
+    },
+    /// The field id isn't known
+    #[error("Proof header `field` has the unknown id {id}")]
+    UnknownField {
+        /// The id found in the header
+        id: u8,
+    },
+    /// The proof is over another field
+    #[error("Proof header `field` is {found}, expected {expected}")]
+    FieldMismatch {
+        /// The field the proof was expected to be over
+        expected: LanguageField,
+        /// The field found in the header
+        found: LanguageField,
+    },
+    /// The proof kind id isn't known
+    #[error("Proof header `kind` has the unknown id {id}")]
+    UnknownKind {
+        /// The id found in the header
+        id: u8,
+    },
+    /// The proof is from another proving system
+    #[error(
+        "Proof header `kind` is {found:?}, expected a proof from the same system as {expected:?}"
+    )]
+    KindMismatch {
+        /// The expected kind
+        expected: ProofKind,
+        /// The kind found in the header
+        found: ProofKind,
+    },
+    /// The payload is another kind of proof than the header says
+    #[error("Proof header `kind` is {header:?}, but the payload is a {payload:?} proof")]
+    PayloadKindMismatch {
+        /// The kind found in the header
+        header: ProofKind,
+        /// The kind of the decoded payload
+        payload: ProofKind,
+    },
+    /// The proof was made with another reduction count
+    #[error("Proof header `rc` is {found}, expected {expected}")]
+    ReductionCountMismatch {
+        /// The expected reduction count
+        expected: usize,
+        /// The reduction count found in the header
+        found: u64,
+    },
+    /// The proof was made for another circuit
+    #[error("Proof header `digest` is {found}, expected {expected}: the proof was made for other public parameters")]
+    DigestMismatch {
+        /// The digest of the public parameters given, in hex
+        expected: String,
+        /// The digest found in the header, in hex
+        found: String,
+    },
+    /// The header is incomplete
+    #[error("Truncated proof header: `{field}` is missing")]
+    Truncated {
+        /// The first header field missing
+        field: &'static str,
+    },
+    /// The payload can't be (de)serialized
+    #[error("Proof payload error: {0}")]
+    Payload(#[from] bincode::Error),
+}
+
+/// The header of an encoded proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofHeader {
+    /// The format version
+    pub version: u16,
+    /// The field the proof is over
+    pub field: LanguageField,
+    /// The kind of proof
+    pub kind: ProofKind,
+    /// The reduction count of the proved circuit
+    pub rc: u64,
+    /// The digest of the public parameters, as the bytes of a field element
+    pub digest: Vec<u8>,
+}
+
+impl ProofHeader {
+    /// Reads the header of `bytes`, returning it along with the remaining payload
+    pub fn read(bytes: &[u8]) -> Result<(Self, &[u8]), WireError> {
+        let (raw, rest) = RawHeader::read(bytes).map_err(|e| match e {
+            HeaderError::BadMagic => WireError::BadMagic,
+            HeaderError::UnsupportedVersion(found) => WireError::UnsupportedVersion { found },
+            HeaderError::UnknownKind(id) => WireError::UnknownKind { id },
+            HeaderError::Truncated(field) => WireError::Truncated { field },
+        })?;
+        let field =
+            field_from_id(raw.field_id).ok_or(WireError::UnknownField { id: raw.field_id })?;
+        let header = Self {
+            version: raw.version,
+            field,
+            kind: raw.kind,
+            rc: raw.rc,
+            digest: raw.digest,
+        };
+        Ok((header, rest))
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        RawHeader {
+            version: self.version,
+            field_id: field_id(self.field),
+            kind: self.kind,
+            rc: self.rc,
+            digest: self.digest.clone(),
+        }
+        .write(bytes)
+    }
+}
+
+/// Encodes `payload` with a header for a proof of kind `kind` over `F`
+pub(crate) fn encode<F: LurkField, T: Serialize>(
+    kind: ProofKind,
+    rc: usize,
+    digest: F,
+    payload: &T,
+) -> Result<Vec<u8>, WireError> {
+    let header = ProofHeader {
+        version: FORMAT_VERSION,
+        field: F::FIELD,
+        kind,
+        rc: rc as u64,
+        digest: digest.to_repr().as_ref().to_vec(),
+    };
+    let mut bytes = vec![];
+    header.write(&mut bytes);
+    bincode::serialize_into(&mut bytes, payload)?;
+    Ok(bytes)
+}
+
+/// Decodes a payload encoded by `encode`, checking that the header matches a proof over `F` from the same proving
+/// system as `kind`, with the given reduction count and public parameters digest. Returns the kind found in the header
+/// along with the payload.
+pub(crate) fn decode<F: LurkField, T: DeserializeOwned>(
+    bytes: &[u8],
+    kind: ProofKind,
+    rc: usize,
+    digest: F,
+) -> Result<(ProofKind, T), WireError> {
+    let (header, payload) = ProofHeader::read(bytes)?;
+    if header.field != F::FIELD {
+        return Err(WireError::FieldMismatch {
+            expected: F::FIELD,
+            found: header.field,
+        });
+    }
+    if !header.kind.same_system(&kind) {
+        return Err(WireError::KindMismatch {
+            expected: kind,
+            found: header.kind,
+        });
+    }
+    if header.rc != rc as u64 {
+        return Err(WireError::ReductionCountMismatch {
+            expected: rc,
+            found: header.rc,
+        });
+    }
+    if header.digest != digest.to_repr().as_ref() {
+        return Err(WireError::DigestMismatch {
+            expected: hex::encode(digest.to_repr()),
+            found: hex::encode(&header.digest),
+        });
+    }
+    Ok((header.kind, bincode::deserialize(payload)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use halo2curves::bn256::Fr;
+    use pasta_curves::pallas;
+
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_and_mismatches() {
+        let payload = vec![1u64, 2, 3];
+        let digest = Fr::from_u64(42);
+        let bytes = encode(ProofKind::NovaCompressed, 10, digest, &payload).unwrap();
+
+        let (header, _) = ProofHeader::read(&bytes).unwrap();
+        assert_eq!(FORMAT_VERSION, header.version);
+        assert_eq!(LanguageField::BN256, header.field);
+        assert_eq!(ProofKind::NovaCompressed, header.kind);
+        assert_eq!(10, header.rc);
+
+        let (kind, decoded): (_, Vec<u64>) =
+            decode(&bytes, ProofKind::NovaRecursive, 10, digest).unwrap();
+        assert_eq!(ProofKind::NovaCompressed, kind);
+        assert_eq!(payload, decoded);
+
+        let decode_err = |bytes: &[u8], kind, rc, digest| {
+            decode::<_, Vec<u64>>(bytes, kind, rc, digest).unwrap_err()
+        };
+        assert!(matches!(
+            decode_err(&bytes, ProofKind::SuperNovaRecursive, 10, digest),
+            WireError::KindMismatch { .. }
+        ));
+        assert!(matches!(
+            decode_err(&bytes, ProofKind::NovaRecursive, 5, digest),
+            WireError::ReductionCountMismatch { .. }
+        ));
+        assert!(matches!(
+            decode_err(&bytes, ProofKind::NovaRecursive, 10, Fr::ONE),
+            WireError::DigestMismatch { .. }
+        ));
+        let err = decode::<_, Vec<u64>>(&bytes, ProofKind::NovaRecursive, 10, pallas::Scalar::ONE)
+            .unwrap_err();
+        assert_eq!(
+            "Proof header `field` is BN256, expected Pallas",
+            err.to_string()
+        );
+        assert_eq!(
+            "Proof header `rc` is 10, expected 5",
+            decode_err(&bytes, ProofKind::NovaRecursive, 5, digest).to_string()
+        );
+        assert!(matches!(
+            decode_err(&bytes[..10], ProofKind::NovaRecursive, 10, digest),
+            WireError::Truncated { field: "rc" }
+        ));
+        let mut unknown_field = bytes.clone();
+        unknown_field[6] = 9;
+        assert!(matches!(
+            decode_err(&unknown_field, ProofKind::NovaRecursive, 10, digest),
+            WireError::UnknownField { id: 9 }
+        ));
+        let mut unknown_kind = bytes.clone();
+        unknown_kind[7] = 9;
+        assert!(matches!(
+            decode_err(&unknown_kind, ProofKind::NovaRecursive, 10, digest),
+            WireError::UnknownKind { id: 9 }
+        ));
+
+        let mut other_version = bytes.clone();
+        other_version[4] += 1;
+        assert!(matches!(
+            decode_err(&other_version, ProofKind::NovaRecursive, 10, digest),
+            WireError::UnsupportedVersion { found: 2 }
+        ));
+        assert!(matches!(
+            decode_err(b"not a proof", ProofKind::NovaRecursive, 10, digest),
+            WireError::BadMagic
+        ));
+    }
+}
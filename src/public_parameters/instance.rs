@@ -141,7 +141,7 @@ impl<F: CurveCycleEquipped, C: Coprocessor<F>> Instance<F, C> {
             matches!(self.kind, Kind::SuperNovaAuxParams),
             "not a supernova instance"
         );
-        let num_circuits = self.lang().coprocessors().len() + 1;
+        let num_circuits = self.lang().coprocessor_count() + 1;
         (0..num_circuits)
             .map(|circuit_index| {
                 Instance::new(
@@ -0,0 +1,179 @@
+use num_bigint::{BigInt, BigUint, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+use std::{cmp::Ordering, fmt::Display};
+
+/// Exact rational number type for Lurk, with arbitrary-precision numerator and denominator.
+///
+/// Ratios are always normalized: the denominator is positive and coprime to the numerator, and zero is `0/1`. Hence
+/// equal ratios have equal representations.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Ratio {
+    numer: BigInt,
+    denom: BigUint,
+}
+
+impl Ratio {
+    /// Returns `numer / denom`, or `None` if `denom` is zero
+    pub fn new(numer: BigInt, denom: BigInt) -> Option<Self> {
+        if denom.is_zero() {
+            return None;
+        }
+        let gcd = numer.gcd(&denom);
+        let (mut numer, denom) = (numer / &gcd, denom / &gcd);
+        if denom.is_negative() {
+            numer = -numer;
+        }
+        Some(Self {
+            numer,
+            denom: denom.into_parts().1,
+        })
+    }
+
+    pub fn from_integer(n: BigInt) -> Self {
+        Self {
+            numer: n,
+            denom: BigUint::one(),
+        }
+    }
+
+    #[inline]
+    pub fn numer(&self) -> &BigInt {
+        &self.numer
+    }
+
+    #[inline]
+    pub fn denom(&self) -> &BigUint {
+        &self.denom
+    }
+
+    /// Assembles a ratio from its sign, numerator magnitude and denominator, or returns `None` if those aren't
+    /// normalized
+    pub fn from_parts(negative: bool, numer: BigUint, denom: BigUint) -> Option<Self> {
+        let sign = match (negative, numer.is_zero()) {
+            (true, true) => return None,
+            (false, true) => Sign::NoSign,
+            (false, false) => Sign::Plus,
+            (true, false) => Sign::Minus,
+        };
+        if denom.is_zero() || !numer.gcd(&denom).is_one() {
+            return None;
+        }
+        Some(Self {
+            numer: BigInt::from_biguint(sign, numer),
+            denom,
+        })
+    }
+
+    /// The sign, the numerator magnitude and the denominator
+    pub fn to_parts(&self) -> (bool, BigUint, BigUint) {
+        (
+            self.numer.is_negative(),
+            self.numer.magnitude().clone(),
+            self.denom.clone(),
+        )
+    }
+
+    #[inline]
+    fn denom_int(&self) -> BigInt {
+        BigInt::from(self.denom.clone())
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numer.is_zero()
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        let numer = &self.numer * other.denom_int() + &other.numer * self.denom_int();
+        Self::new(numer, self.denom_int() * other.denom_int()).expect("nonzero denominator")
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        let numer = &self.numer * other.denom_int() - &other.numer * self.denom_int();
+        Self::new(numer, self.denom_int() * other.denom_int()).expect("nonzero denominator")
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self::new(
+            &self.numer * &other.numer,
+            self.denom_int() * other.denom_int(),
+        )
+        .expect("nonzero denominator")
+    }
+
+    /// Returns `self / other`, or `None` if `other` is zero
+    pub fn div(&self, other: &Self) -> Option<Self> {
+        Self::new(
+            &self.numer * other.denom_int(),
+            self.denom_int() * &other.numer,
+        )
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.numer * other.denom_int()).cmp(&(&other.numer * self.denom_int()))
+    }
+}
+
+impl Display for Ratio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.numer, self.denom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn ratio(n: i64, d: i64) -> Ratio {
+        Ratio::new(n.into(), d.into()).unwrap()
+    }
+
+    #[test]
+    fn unit_ratio_normalization() {
+        assert_eq!(ratio(1, 2), ratio(-3, -6));
+        assert_eq!(ratio(-1, 2), ratio(3, -6));
+        assert_eq!(ratio(0, 1), ratio(0, -5));
+        assert_eq!("-1/2", ratio(4, -8).to_string());
+        assert!(Ratio::new(1.into(), 0.into()).is_none());
+        assert!(Ratio::from_parts(false, 2u64.into(), 4u64.into()).is_none());
+        assert!(Ratio::from_parts(true, 0u64.into(), 1u64.into()).is_none());
+        assert!(Ratio::from_parts(false, 0u64.into(), 2u64.into()).is_none());
+    }
+
+    #[test]
+    fn unit_ratio_arithmetic() {
+        let (a, b) = (ratio(1, 3), ratio(-1, 6));
+        assert_eq!(ratio(1, 6), a.add(&b));
+        assert_eq!(ratio(1, 2), a.sub(&b));
+        assert_eq!(ratio(-1, 18), a.mul(&b));
+        assert_eq!(Some(ratio(-2, 1)), a.div(&b));
+        assert_eq!(None, a.div(&ratio(0, 1)));
+        assert!(b < a);
+
+        // no wraparound
+        let big = Ratio::from_integer(BigInt::from(u64::MAX));
+        assert_eq!(
+            "340282366920938463426481119284349108225/1",
+            big.mul(&big).to_string()
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn prop_ratio_parts(n in any::<i64>(), d in any::<i64>()) {
+            prop_assume!(d != 0);
+            let r = ratio(n, d);
+            let (negative, numer, denom) = r.to_parts();
+            prop_assert_eq!(Some(r), Ratio::from_parts(negative, numer, denom));
+        }
+    }
+}
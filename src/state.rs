@@ -41,6 +41,22 @@ impl State {
         self.symbol_packages.insert(package.name().clone(), package);
     }
 
+    /// Adds a new package to a state, failing if a package with the same name
+    /// already exists
+    pub fn define_package(&mut self, package: Package) -> Result<()> {
+        if self.symbol_packages.contains_key(package.name()) {
+            bail!("Package {} is already defined", package.name())
+        }
+        self.add_package(package);
+        Ok(())
+    }
+
+    /// Returns the package with a given name
+    #[inline]
+    pub fn get_package(&self, package_name: &SymbolRef) -> Option<&Package> {
+        self.symbol_packages.get(package_name)
+    }
+
     /// Sets the current package of the state
     pub fn set_current_package(&mut self, package_name: SymbolRef) -> Result<()> {
         if self.symbol_packages.contains_key(&package_name) {
@@ -92,11 +108,52 @@ impl State {
         self.get_current_package_mut().use_package(package)
     }
 
+    /// Imports the exported symbols of the package with a given name
+    pub fn use_package_named(&mut self, package_name: &SymbolRef) -> Result<()> {
+        let Some(package) = self.symbol_packages.get(package_name) else {
+            bail!("Package {package_name} not found")
+        };
+        let symbols = package.exported();
+        self.import(&symbols)
+    }
+
+    /// Exports symbols accessible in the current package
+    pub fn export<A: AsRef<str>>(&mut self, symbols_names: &[A]) -> Result<()> {
+        self.get_current_package_mut().export(symbols_names)
+    }
+
     /// Formats a symbol to string w.r.t. the current package
     pub fn fmt_to_string(&self, symbol: &SymbolRef) -> String {
         self.get_current_package().fmt_to_string(symbol)
     }
 
+    /// The symbols that can be written starting with `prefix`, sorted. A prefix starting with a
+    /// `.` is completed with the full paths of the symbols of every package, and any other prefix
+    /// with the names known by the current package
+    pub fn completions(&self, prefix: &str) -> Vec<String> {
+        let mut completions: Vec<String> = if prefix.starts_with('.') {
+            self.symbol_packages
+                .values()
+                .flat_map(|package| {
+                    package
+                        .local_symbols()
+                        .map(|symbol| symbol.fmt_to_string())
+                        .chain([package.name().fmt_to_string()])
+                })
+                .filter(|path| path.starts_with(prefix))
+                .collect()
+        } else {
+            self.get_current_package()
+                .names()
+                .map(|name| Symbol::fmt_path_component_to_string(name))
+                .filter(|name| name.starts_with(prefix))
+                .collect()
+        };
+        completions.sort();
+        completions.dedup();
+        completions
+    }
+
     /// Sequentially intern a symbol into the potentially nested packages according
     /// to its path
     fn intern_fold<A: AsRef<str>>(
@@ -215,7 +272,7 @@ const LURK_PACKAGE_SYMBOL_NAME: &str = "lurk";
 const USER_PACKAGE_SYMBOL_NAME: &str = "user";
 const META_PACKAGE_SYMBOL_NAME: &str = "meta";
 
-const LURK_PACKAGE_SYMBOLS_NAMES: [&str; 36] = [
+const LURK_PACKAGE_SYMBOLS_NAMES: [&str; 40] = [
     "atom",
     "begin",
     "car",
@@ -225,6 +282,7 @@ const LURK_PACKAGE_SYMBOLS_NAMES: [&str; 36] = [
     "commit",
     "cons",
     "current-env",
+    "current-step",
     "emit",
     "empty-env",
     "eval",
@@ -238,10 +296,13 @@ const LURK_PACKAGE_SYMBOLS_NAMES: [&str; 36] = [
     "num",
     "u64",
     "open",
+    "quasiquote",
     "quote",
     "secret",
     "strcons",
     "t",
+    "unquote",
+    "unquote-splicing",
     "+",
     "-",
     "*",
@@ -300,6 +361,20 @@ pub mod test {
         assert_eq!(state.fmt_to_string(symbol), expected.to_string());
     }
 
+    #[test]
+    fn test_completions() {
+        let mut state = State::init_lurk_state();
+        state.intern("my-sym");
+        state.intern("my-other-sym");
+
+        assert_eq!(vec!["my-other-sym", "my-sym"], state.completions("my-"));
+        assert!(state.completions("lamb").contains(&"lambda".to_string()));
+        assert!(state
+            .completions(".lurk.lamb")
+            .contains(&".lurk.lambda".to_string()));
+        assert!(state.completions("nothing-like-this").is_empty());
+    }
+
     #[test]
     fn test_lurk_state_printing() {
         let mut state = State::init_lurk_state();
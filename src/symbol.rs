@@ -14,7 +14,7 @@ use serde::{Deserialize, Serialize};
 pub(crate) const KEYWORD_MARKER: char = ':';
 pub(crate) const SYM_SEPARATOR: char = '.';
 pub(crate) const SYM_MARKER: char = '.';
-pub(crate) const ESCAPE_CHARS: &str = "|(){}[],.:'\\\"";
+pub(crate) const ESCAPE_CHARS: &str = "|(){}[],.:'`\\\"";
 
 #[derive(Clone, Debug, Eq, PartialEq, PartialOrd, Serialize, Deserialize, Hash)]
 #[cfg_attr(not(target_arch = "wasm32"), derive(Arbitrary))]
@@ -4,6 +4,7 @@ use crate::field::LurkField;
 use crate::num::Num;
 use crate::package::SymbolRef;
 use crate::parser::position::Pos;
+use crate::state::lurk_sym;
 use crate::uint::UInt;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -16,6 +17,10 @@ pub enum Syntax<F: LurkField> {
     Num(Pos, Num<F>),
     /// A u64 integer: 1u64, 0xffu64
     UInt(Pos, UInt),
+    /// A signed 64-bit integer: -1i64, 0xffi64
+    I64(Pos, i64),
+    /// A byte string literal: b"abc", b"\u{ff}\n"
+    Bytes(Pos, Vec<u8>),
     /// A hierarchical symbol: foo, foo.bar.baz or keyword :foo
     Symbol(Pos, SymbolRef),
     /// A string literal: "foobar", "foo\nbar"
@@ -24,6 +29,12 @@ pub enum Syntax<F: LurkField> {
     Char(Pos, char),
     /// A quoted expression: 'a, '(1 2)
     Quote(Pos, Box<Syntax<F>>),
+    /// A quasiquoted template: `a, `(1 ,x)
+    Quasiquote(Pos, Box<Syntax<F>>),
+    /// An expression evaluated within a template: ,x
+    Unquote(Pos, Box<Syntax<F>>),
+    /// A list spliced into a template: ,@xs
+    UnquoteSplicing(Pos, Box<Syntax<F>>),
     /// A nil-terminated cons-list of expressions: (1 2 3)
     List(Pos, Vec<Syntax<F>>),
     /// An improper cons-list of expressions: (1 2 . 3)
@@ -36,14 +47,127 @@ impl<F: LurkField> Syntax<F> {
         match self {
             Self::Num(pos, _)
             | Self::UInt(pos, _)
+            | Self::I64(pos, _)
+            | Self::Bytes(pos, _)
             | Self::Symbol(pos, _)
             | Self::String(pos, _)
             | Self::Char(pos, _)
             | Self::Quote(pos, _)
+            | Self::Quasiquote(pos, _)
+            | Self::Unquote(pos, _)
+            | Self::UnquoteSplicing(pos, _)
             | Self::List(pos, _)
             | Self::Improper(pos, ..) => pos,
         }
     }
+
+    fn lurk_symbol(name: &str) -> Self {
+        Self::Symbol(Pos::No, lurk_sym(name).into())
+    }
+
+    fn quote(self) -> Self {
+        Self::Quote(*self.get_pos(), Box::new(self))
+    }
+
+    fn cons(car: Self, cdr: Self) -> Self {
+        Self::List(Pos::No, vec![Self::lurk_symbol("cons"), car, cdr])
+    }
+
+    /// Code for the list `(head x)`, where `head` is quoted
+    fn tagged(head: &str, x: Self) -> Self {
+        Self::cons(
+            Self::lurk_symbol(head).quote(),
+            Self::cons(x, Self::lurk_symbol("nil")),
+        )
+    }
+
+    /// Code appending the list `xs` evaluates to to the list `ys` evaluates to
+    fn append(xs: Self, ys: Self) -> Self {
+        let sym = Self::lurk_symbol;
+        let list = |xs| Self::List(Pos::No, xs);
+        let append = || sym("quasiquote-append");
+        let (a, b) = (|| sym("quasiquote-xs"), || sym("quasiquote-ys"));
+        let body = list(vec![
+            sym("if"),
+            a(),
+            Self::cons(
+                list(vec![sym("car"), a()]),
+                list(vec![append(), list(vec![sym("cdr"), a()]), b()]),
+            ),
+            b(),
+        ]);
+        let lambda = list(vec![sym("lambda"), list(vec![a(), b()]), body]);
+        list(vec![
+            sym("letrec"),
+            list(vec![list(vec![append(), lambda])]),
+            list(vec![append(), xs, ys]),
+        ])
+    }
+
+    /// Whether the template has unquotes at quasiquotation depth `depth`
+    fn has_unquotes(&self, depth: usize) -> bool {
+        match self {
+            Self::Unquote(_, x) | Self::UnquoteSplicing(_, x) => {
+                depth == 1 || x.has_unquotes(depth - 1)
+            }
+            Self::Quasiquote(_, x) => x.has_unquotes(depth + 1),
+            Self::Quote(_, x) => x.has_unquotes(depth),
+            Self::List(_, xs) => xs.iter().any(|x| x.has_unquotes(depth)),
+            Self::Improper(_, xs, y) => {
+                xs.iter().any(|x| x.has_unquotes(depth)) || y.has_unquotes(depth)
+            }
+            _ => false,
+        }
+    }
+
+    /// The code that builds the quasiquoted template `self` at quasiquotation depth `depth`, which starts at 1.
+    /// Nested quasiquotes increase the depth and unquotes decrease it, so only the unquotes at depth 1 are evaluated.
+    /// Unquote-splicing at depth 1 outside of a list is left as is, and fails to evaluate.
+    pub fn expand_quasiquote(self, depth: usize) -> Self {
+        if !self.has_unquotes(depth) {
+            return self.quote();
+        }
+        match self {
+            Self::Unquote(_, x) if depth == 1 => *x,
+            Self::Unquote(_, x) => Self::tagged("unquote", x.expand_quasiquote(depth - 1)),
+            Self::UnquoteSplicing(pos, x) if depth == 1 => {
+                Self::List(pos, vec![Self::lurk_symbol("unquote-splicing"), *x])
+            }
+            Self::UnquoteSplicing(_, x) => {
+                Self::tagged("unquote-splicing", x.expand_quasiquote(depth - 1))
+            }
+            Self::Quasiquote(_, x) => Self::tagged("quasiquote", x.expand_quasiquote(depth + 1)),
+            Self::Quote(_, x) => Self::tagged("quote", x.expand_quasiquote(depth)),
+            Self::List(_, xs) => Self::expand_list(xs, Self::lurk_symbol("nil"), depth),
+            Self::Improper(_, xs, y) => {
+                let y = y.expand_quasiquote(depth);
+                Self::expand_list(xs, y, depth)
+            }
+            _ => unreachable!("atoms have no unquotes"),
+        }
+    }
+
+    fn expand_list(xs: Vec<Self>, tail: Self, depth: usize) -> Self {
+        xs.into_iter().rev().fold(tail, |acc, x| match x {
+            Self::UnquoteSplicing(_, x) if depth == 1 => Self::append(*x, acc),
+            x => Self::cons(x.expand_quasiquote(depth), acc),
+        })
+    }
+}
+
+/// Prints `bytes` as a byte string literal, escaping everything but printable ASCII
+pub(crate) fn bytes_literal(bytes: &[u8]) -> String {
+    let mut res = String::from("b\"");
+    for b in bytes {
+        match b {
+            b'"' => res.push_str("\\\""),
+            b'\\' => res.push_str("\\\\"),
+            0x20..=0x7e => res.push(char::from(*b)),
+            _ => res.push_str(&format!("\\u{{{b:x}}}")),
+        }
+    }
+    res.push('"');
+    res
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -56,6 +180,8 @@ impl<Fr: LurkField> Arbitrary for Syntax<Fr> {
         let leaf = prop_oneof![
             any::<Num<Fr>>().prop_map(|x| Syntax::Num(Pos::No, x)),
             any::<UInt>().prop_map(|x| Syntax::UInt(Pos::No, x)),
+            any::<i64>().prop_map(|x| Syntax::I64(Pos::No, x)),
+            prop::collection::vec(any::<u8>(), 0..64).prop_map(|x| Syntax::Bytes(Pos::No, x)),
             any::<Symbol>().prop_map(|x| Syntax::Symbol(Pos::No, x.into())),
             any::<String>().prop_map(|x| Syntax::String(Pos::No, x)),
             any::<char>().prop_map(|x| Syntax::Char(Pos::No, x))
@@ -81,6 +207,8 @@ impl<F: LurkField> fmt::Display for Syntax<F> {
         match self {
             Self::Num(_, x) => write!(f, "{x}"),
             Self::UInt(_, x) => write!(f, "{x}u64"),
+            Self::I64(_, x) => write!(f, "{x}i64"),
+            Self::Bytes(_, x) => write!(f, "{}", bytes_literal(x)),
             Self::Symbol(_, x) => write!(f, "{x}"),
             Self::String(_, x) => write!(f, "\"{}\"", x.escape_default()),
             Self::Char(_, x) => {
@@ -91,6 +219,9 @@ impl<F: LurkField> fmt::Display for Syntax<F> {
                 }
             }
             Self::Quote(_, x) => write!(f, "'{x}"),
+            Self::Quasiquote(_, x) => write!(f, "`{x}"),
+            Self::Unquote(_, x) => write!(f, ",{x}"),
+            Self::UnquoteSplicing(_, x) => write!(f, ",@{x}"),
             Self::List(_, xs) => {
                 let mut iter = xs.iter().peekable();
                 write!(f, "(")?;
@@ -53,6 +53,9 @@ pub enum ExprTag {
     Cproc,
     Env,
     Rec,
+    I64,
+    Bytes,
+    Ratio,
 }
 
 impl From<ExprTag> for u16 {
@@ -84,6 +87,9 @@ impl fmt::Display for ExprTag {
             ExprTag::Cproc => write!(f, "cproc#"),
             ExprTag::Env => write!(f, "env#"),
             ExprTag::Rec => write!(f, "rec#"),
+            ExprTag::I64 => write!(f, "i64#"),
+            ExprTag::Bytes => write!(f, "bytes#"),
+            ExprTag::Ratio => write!(f, "ratio#"),
         }
     }
 }
@@ -140,6 +146,8 @@ pub enum ContTag {
     Terminal,
     Emit,
     Cproc,
+    Match,
+    MatchPattern,
 }
 
 impl From<ContTag> for u16 {
@@ -191,6 +199,8 @@ impl fmt::Display for ContTag {
             ContTag::Terminal => write!(f, "terminal#"),
             ContTag::Emit => write!(f, "emit#"),
             ContTag::Cproc => write!(f, "cproc#"),
+            ContTag::Match => write!(f, "match#"),
+            ContTag::MatchPattern => write!(f, "match-pattern#"),
         }
     }
 }
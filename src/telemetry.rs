@@ -0,0 +1,74 @@
+//! Tracing spans and metrics of evaluation and proving.
+//!
+//! ## Spans
+//!
+//! | span                                                  | fields                   |
+//! |-------------------------------------------------------|--------------------------|
+//! | `eval::build_frames`, `eval::traverse_frames`         | `limit`, `iterations`    |
+//! | `multiframe::synthesize`                              | `pc`                     |
+//! | `nova::prove_*`, `supernova::prove_*`                 |                          |
+//! | `fold_step`                                           | `index`, `circuit_index` |
+//! | `memoset::finalize_transcript`, `memoset::synthesize` |                          |
+//!
+//! ## Metrics
+//!
+//! Metrics go through the [metrics](https://docs.rs/metrics) facade, to whichever recorder is installed: the `lurk`
+//! binary logs them periodically with `lurk-metrics`, or, when built with the `prometheus` feature and run with
+//! `LURK_PROMETHEUS_ADDR` set to a socket address, serves them to Prometheus at `http://<address>/metrics`. Nothing is
+//! recorded when no recorder is installed.
+//!
+//! | name                         | type      | labels    | description                                          |
+//! |------------------------------|-----------|-----------|------------------------------------------------------|
+//! | `lurk_eval_iterations_total` | counter   |           | iterations of the evaluator                          |
+//! | `lurk_hashes_total`          | counter   | `arity`   | Poseidon hashes computed by stores, minus cache hits |
+//! | `lurk_circuit_constraints`   | gauge     | `circuit` | constraints of the last public parameters generated  |
+//! | `lurk_fold_duration_seconds` | histogram | `circuit` | time spent folding a step, by circuit index          |
+//!
+//! Hashes are recorded after each evaluation and hydration. Constraints are those of the `primary` and `secondary`
+//! circuits of the last Nova public parameters generated.
+
+use std::time::Instant;
+
+/// Counter of evaluator iterations
+pub const EVAL_ITERATIONS: &str = "lurk_eval_iterations_total";
+/// Counter of Poseidon hashes computed by stores
+pub const HASHES: &str = "lurk_hashes_total";
+/// Gauge of the number of constraints of a circuit
+pub const CIRCUIT_CONSTRAINTS: &str = "lurk_circuit_constraints";
+/// Histogram of the time spent folding a step
+pub const FOLD_DURATION: &str = "lurk_fold_duration_seconds";
+
+/// Folds step `index`, of circuit `circuit_index`, with `fold`, within a `fold_step` span, and records how long it took
+pub(crate) fn fold_step<T, E>(
+    index: usize,
+    circuit_index: usize,
+    fold: impl FnOnce() -> Result<T, E>,
+) -> Result<T, E> {
+    let _span = tracing::info_span!("fold_step", index, circuit_index).entered();
+    let start = Instant::now();
+    let result = fold()?;
+    metrics::histogram!(FOLD_DURATION, "circuit" => circuit_index.to_string())
+        .record(start.elapsed().as_secs_f64());
+    Ok(result)
+}
+
+/// Installs a Prometheus recorder for every metric, served at `http://<addr>/metrics`. Fails if a recorder is already
+/// installed.
+#[cfg(feature = "prometheus")]
+pub fn install_prometheus(addr: std::net::SocketAddr) -> anyhow::Result<()> {
+    metrics_exporter_prometheus::PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .install()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fold_step;
+
+    #[test]
+    fn test_fold_step() {
+        assert_eq!(Ok::<_, ()>(3), fold_step(0, 1, || Ok(3)));
+        assert_eq!(Err::<(), _>("unsat"), fold_step(1, 0, || Err("unsat")));
+    }
+}
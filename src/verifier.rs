@@ -0,0 +1,143 @@
+//! Verification of compressed Nova proofs without the evaluation machinery.
+//!
+//! Verifying a proof made with [`crate::proof::nova::NovaProver`] normally goes through the public parameters, which
+//! are expensive to generate and large to load. A compressed proof, however, only needs the verifier key of its final
+//! SNARK. This module packs that key into a [`VerifyingKey`], which can be extracted once by the prover and shipped to
+//! verifiers, and checks [`crate::proof::wire`]-encoded proofs against it. Nothing here depends on a `Store`, a `Lang`
+//! or the circuits themselves.
+//!
+//! ## Transcript
+//!
+//! Folding hashes with Poseidon, but the Fiat-Shamir transcript of the final Spartan SNARK has its own hash, fixed by
+//! the `TE` transcript engine of the curve cycle. The engines of this crate use the Keccak-256 transcript of
+//! `nova::provider::keccak::Keccak256Transcript`, whose byte-level format any other verifier has to recompute:
+//! - the transcript is created with a label, and keeps a 64-byte state along with a round counter;
+//! - absorbing a value appends its label and then its transcript bytes (field elements little-endian, group elements
+//!   as their compressed encoding) to a running Keccak-256 hasher;
+//! - squeezing a challenge hashes a domain separator, the little-endian round counter, the previous state and the
+//!   challenge's label into a new 64-byte state, reduced into a scalar, after which the hasher is reset.
+
+use ff::Field;
+use nova::errors::NovaError;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::proof::nova::{CurveCycleEquipped, Dual, Proof, PublicParams, E1, SS1, SS2};
+use crate::proof::wire::{ProofHeader, ProofKind, WireError};
+
+/// Everything needed to verify compressed Nova proofs for one circuit
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct VerifyingKey<F: CurveCycleEquipped> {
+    vk: nova::VerifierKey<E1<F>, SS1<F>, SS2<F>>,
+    digest: F,
+    rc: usize,
+}
+
+impl<F: CurveCycleEquipped> VerifyingKey<F> {
+    /// Extracts the verifying key from the public parameters proofs are made with, for the reduction count `rc`
+    pub fn new(pp: &PublicParams<F>, rc: usize) -> Self {
+        Self {
+            vk: pp.vk().clone(),
+            digest: pp.pp.digest(),
+            rc,
+        }
+    }
+
+    /// The digest of the public parameters the key was extracted from
+    pub fn digest(&self) -> F {
+        self.digest
+    }
+
+    /// The reduction count of the proofs the key verifies
+    pub fn rc(&self) -> usize {
+        self.rc
+    }
+}
+
+/// The public inputs and outputs of a proof
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PublicIO<F> {
+    /// The inputs of the first step
+    pub z0: Vec<F>,
+    /// The outputs of the last step
+    pub zi: Vec<F>,
+}
+
+#[derive(Error, Debug)]
+pub enum VerifierError {
+    #[error(transparent)]
+    Wire(#[from] WireError),
+    #[error("Only compressed Nova proofs can be verified with a verifying key")]
+    NotCompressed,
+    #[error("Nova error: {0}")]
+    Nova(#[from] NovaError),
+}
+
+/// Verifies `proof_bytes`, a compressed Nova proof encoded with `Proof::to_bytes`, against `public_io`. Returns
+/// `Ok(false)` if the proof is well-formed but doesn't prove `public_io`.
+pub fn verify<F: CurveCycleEquipped>(
+    proof_bytes: &[u8],
+    public_io: &PublicIO<F>,
+    vk: &VerifyingKey<F>,
+) -> Result<bool, VerifierError> {
+    let (header, _) = ProofHeader::read(proof_bytes)?;
+    if header.kind != ProofKind::NovaCompressed {
+        return Err(VerifierError::NotCompressed);
+    }
+    let proof: Proof<F, ()> = Proof::from_bytes_with_digest(proof_bytes, vk.digest, vk.rc)?;
+    let Proof::Compressed(snark, num_steps, _) = proof else {
+        return Err(VerifierError::NotCompressed);
+    };
+    let z0_secondary = [Dual::<F>::ZERO];
+    match snark.verify(&vk.vk, num_steps, &public_io.z0, &z0_secondary) {
+        Ok((zi, zi_secondary)) => Ok(zi == public_io.zi && zi_secondary == z0_secondary),
+        Err(e @ NovaError::InvalidInputLength) => Err(e.into()),
+        Err(_) => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::bn256::Fr;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::eval::lang::{Coproc, Lang};
+    use crate::lem::store::Store;
+    use crate::proof::{nova::public_params, nova::NovaProver, Prover, RecursiveSNARKTrait};
+
+    #[test]
+    fn test_verify_without_store() {
+        let rc = 3;
+        let lang = Arc::new(Lang::<Fr, Coproc<Fr>>::new());
+        let pp = public_params(rc, lang.clone());
+        let (proof_bytes, public_io) = {
+            let store = Store::<Fr>::default();
+            let expr = store.read_with_default_state("(+ 1 2)").unwrap();
+            let prover = NovaProver::new(rc, lang);
+            let (proof, z0, zi, _) = prover
+                .evaluate_and_prove(&pp, expr, store.intern_empty_env(), &store, 100)
+                .unwrap();
+            let proof = proof.compress(&pp).unwrap();
+            (proof.to_bytes(&pp, rc).unwrap(), PublicIO { z0, zi })
+        };
+
+        // the verifying key survives serialization
+        let vk = VerifyingKey::new(&pp, rc);
+        let vk: VerifyingKey<Fr> = bincode::deserialize(&bincode::serialize(&vk).unwrap()).unwrap();
+        assert!(verify(&proof_bytes, &public_io, &vk).unwrap());
+
+        let mut wrong_io = public_io.clone();
+        wrong_io.zi[0] += Fr::ONE;
+        assert!(!verify(&proof_bytes, &wrong_io, &vk).unwrap());
+
+        let other_rc = VerifyingKey::new(&pp, rc + 1);
+        assert!(matches!(
+            verify(&proof_bytes, &public_io, &other_rc),
+            Err(VerifierError::Wire(
+                WireError::ReductionCountMismatch { .. }
+            ))
+        ));
+    }
+}
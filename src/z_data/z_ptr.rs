@@ -4,7 +4,7 @@ use base32ct::{Base32Unpadded, Encoding};
 use lurk_macros::serde_test;
 #[cfg(not(target_arch = "wasm32"))]
 use proptest_derive::Arbitrary;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 use std::fmt::{Display, Formatter};
 use std::hash::Hash;
@@ -123,6 +123,64 @@ impl<E: Tag, F: LurkField> ZPtr<E, F> {
     }
 }
 
+/// The version of the canonical byte encoding of `ZPtr`s. See `ZPtr::to_bytes`.
+pub const ZPTR_ENCODING_VERSION: u8 = 1;
+
+impl<E: Tag, F: LurkField> ZPtr<E, F> {
+    /// Canonical byte encoding, meant for persisting `ZPtr`s: a version byte (`ZPTR_ENCODING_VERSION`), the tag's
+    /// `u16` id in little-endian and the canonical representation of the value.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![ZPTR_ENCODING_VERSION];
+        bytes.extend(self.0.into().to_le_bytes());
+        bytes.extend(self.1.to_repr().as_ref());
+        bytes
+    }
+
+    /// Parses the encoding produced by `to_bytes`. Should tag ids ever change, the encoding version is bumped and
+    /// older versions keep being parsed by translating their tag ids here.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        let Some((&version, rest)) = bytes.split_first() else {
+            return Err(anyhow!("Empty ZPtr encoding"));
+        };
+        if version != ZPTR_ENCODING_VERSION {
+            return Err(anyhow!("Unsupported ZPtr encoding version {version}"));
+        }
+        let repr_len = F::ZERO.to_repr().as_ref().len();
+        if rest.len() != 2 + repr_len {
+            return Err(anyhow!(
+                "Expected {} bytes of ZPtr encoding, found {}",
+                3 + repr_len,
+                bytes.len()
+            ));
+        }
+        let (tag_bytes, val_bytes) = rest.split_at(2);
+        let tag = E::try_from(u16::from_le_bytes([tag_bytes[0], tag_bytes[1]]))
+            .map_err(|e| anyhow!(format!("Failed to decode tag: {}", e)))?;
+        let val = F::from_bytes(val_bytes).ok_or_else(|| anyhow!("Failed to decode field"))?;
+        Ok(Self::from_parts(tag, val))
+    }
+}
+
+/// Serde support through the canonical versioned encoding of `ZPtr`s, to be used as
+/// `#[serde(with = "lurk::z_ptr::versioned")]` on fields that must stay readable across releases.
+pub mod versioned {
+    use super::*;
+
+    pub fn serialize<E: Tag, F: LurkField, S: Serializer>(
+        z_ptr: &ZPtr<E, F>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&z_ptr.to_bytes())
+    }
+
+    pub fn deserialize<'de, E: Tag, F: LurkField, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<ZPtr<E, F>, D::Error> {
+        let bytes = Vec::<u8>::deserialize(deserializer)?;
+        ZPtr::from_bytes(&bytes).map_err(serde::de::Error::custom)
+    }
+}
+
 /// Alias for an expression pointer
 pub type ZExprPtr<F> = ZPtr<ExprTag, F>;
 
@@ -141,6 +199,48 @@ mod tests {
         }
     }
 
+    proptest! {
+        #[test]
+        fn prop_bytes_z_expr_ptr(x in any::<ZExprPtr<Scalar>>()) {
+            assert_eq!(x, ZPtr::from_bytes(&x.to_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn unit_bytes_z_ptr() {
+        let zptr = ZExprPtr::from_parts(ExprTag::Num, Scalar::from_u64(3));
+        let bytes = zptr.to_bytes();
+        assert_eq!(1 + 2 + 32, bytes.len());
+        assert_eq!(
+            [ZPTR_ENCODING_VERSION, ExprTag::Num as u16 as u8, 0],
+            bytes[..3]
+        );
+        assert_eq!(3, bytes[3]);
+
+        assert!(ZExprPtr::<Scalar>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        let mut future = bytes.clone();
+        future[0] = ZPTR_ENCODING_VERSION + 1;
+        assert!(ZExprPtr::<Scalar>::from_bytes(&future).is_err());
+        let mut bad_tag = bytes;
+        bad_tag[2] = 0xff;
+        assert!(ZExprPtr::<Scalar>::from_bytes(&bad_tag).is_err());
+    }
+
+    #[test]
+    fn unit_versioned_serde() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Persisted {
+            #[serde(with = "versioned")]
+            z_ptr: ZExprPtr<Scalar>,
+        }
+
+        let persisted = Persisted {
+            z_ptr: ZPtr::from_parts(ExprTag::Cons, Scalar::from_u64(42)),
+        };
+        let bytes = bincode::serialize(&persisted).unwrap();
+        assert_eq!(persisted, bincode::deserialize(&bytes).unwrap());
+    }
+
     #[test]
     fn unit_base32_z_expr_ptr() {
         let zptr = ZExprPtr::from_parts(ExprTag::Nil, Scalar::zero());
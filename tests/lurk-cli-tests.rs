@@ -86,3 +86,336 @@ fn test_repl_panic() {
     cmd.arg(lurk_file.into_string());
     cmd.assert().failure();
 }
+
+#[test]
+fn test_prove_queries() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let lurk_file = tmp_dir.join("queries.lurk");
+
+    let mut file = File::create(lurk_file.clone()).unwrap();
+    file.write_all(b"!(defquery factorial)\n").unwrap();
+    file.write_all(b"!(query '(factorial . 4))\n").unwrap();
+    file.write_all(b"!(query (cons 'factorial 3))\n").unwrap();
+    file.write_all(b"!(query-stats)\n").unwrap();
+    file.write_all(b"!(prove-queries)\n").unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(lurk_file.into_string());
+    cmd.assert().success();
+}
+
+#[test]
+fn test_verify_coroutine_proof() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let public_param_dir = tmp_dir.join("public_params");
+    let lurk_file = tmp_dir.join("queries.lurk");
+    let proof_file = tmp_dir.join("fact.proof");
+    let inputs_file = tmp_dir.join("fact.inputs");
+
+    let mut file = File::create(lurk_file.clone()).unwrap();
+    file.write_all(b"!(defquery factorial)\n").unwrap();
+    file.write_all(b"!(query '(factorial . 4))\n").unwrap();
+    file.write_all(format!("!(prove-queries \"{proof_file}\" \"{inputs_file}\")\n").as_bytes())
+        .unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(lurk_file.into_string());
+    cmd.arg("--public-params-dir");
+    cmd.arg(&public_param_dir);
+    cmd.assert().success();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("verify");
+    cmd.arg(&proof_file);
+    cmd.arg("--public-inputs");
+    cmd.arg(&inputs_file);
+    cmd.arg("--public-params-dir");
+    cmd.arg(&public_param_dir);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"verified\":true"));
+
+    // fields SuperNova doesn't support are reported as errors
+    let mut cmd = lurk_cmd();
+    cmd.arg("verify");
+    cmd.arg(&proof_file);
+    cmd.arg("--public-inputs");
+    cmd.arg(&inputs_file);
+    cmd.arg("--field");
+    cmd.arg("vesta");
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("incompatible with field"));
+
+    // the public inputs of another computation are rejected
+    let mut file = File::create(tmp_dir.join("other.lurk")).unwrap();
+    file.write_all(b"!(defquery factorial)\n").unwrap();
+    file.write_all(b"!(query '(factorial . 3))\n").unwrap();
+    file.write_all(
+        format!(
+            "!(prove-queries \"{}\" \"{inputs_file}\")\n",
+            tmp_dir.join("other.proof")
+        )
+        .as_bytes(),
+    )
+    .unwrap();
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(tmp_dir.join("other.lurk").into_string());
+    cmd.arg("--public-params-dir");
+    cmd.arg(&public_param_dir);
+    cmd.assert().success();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("verify");
+    cmd.arg(&proof_file);
+    cmd.arg("--public-inputs");
+    cmd.arg(&inputs_file);
+    cmd.arg("--public-params-dir");
+    cmd.arg(&public_param_dir);
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("\"public_inputs_match\":false"));
+}
+
+#[test]
+fn test_step_debugger() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let lurk_file = tmp_dir.join("debug.lurk");
+
+    let mut file = File::create(lurk_file.clone()).unwrap();
+    file.write_all(b"!(def square (lambda (x) (* x x)))\n")
+        .unwrap();
+    file.write_all(b"!(step (+ 1 (square 2)))\n").unwrap();
+    file.write_all(b"!(step)\n").unwrap();
+    file.write_all(b"!(break square)\n").unwrap();
+    file.write_all(b"!(frames)\n").unwrap();
+    file.write_all(b"!(frames 0)\n").unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(lurk_file.into_string());
+    cmd.assert().success();
+}
+
+#[test]
+fn test_constraint_count() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let public_param_dir = tmp_dir.join("public_params");
+    let lurk_file = tmp_dir.join("count.lurk");
+
+    let mut file = File::create(lurk_file.clone()).unwrap();
+    file.write_all(b"!(constraint-count (+ 1 2))\n").unwrap();
+    file.write_all(b"!(defquery factorial)\n").unwrap();
+    file.write_all(b"!(query '(factorial . 3))\n").unwrap();
+    file.write_all(b"!(constraint-count)\n").unwrap();
+    // the scope isn't finalized, so it still takes queries
+    file.write_all(b"!(query '(factorial . 4))\n").unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(lurk_file.into_string());
+    cmd.arg("--public-params-dir");
+    cmd.arg(&public_param_dir);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Lurk step: 1 step(s) of"));
+    assert!(stdout.contains("Query index 0"));
+    assert!(stdout.contains("Estimated proving time"));
+}
+
+#[test]
+fn test_save_and_load_store() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let store_file = tmp_dir.join("session.store");
+    let save_file = tmp_dir.join("save.lurk");
+    let load_file = tmp_dir.join("load.lurk");
+
+    let mut file = File::create(save_file.clone()).unwrap();
+    file.write_all(b"!(def x (commit 42))\n").unwrap();
+    file.write_all(format!("!(save-store \"{store_file}\")\n").as_bytes())
+        .unwrap();
+
+    let mut file = File::create(load_file.clone()).unwrap();
+    file.write_all(format!("!(load-store \"{store_file}\")\n").as_bytes())
+        .unwrap();
+    file.write_all(b"!(assert-eq (open x) 42)\n").unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(save_file.into_string());
+    cmd.assert().success();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(load_file.into_string());
+    cmd.assert().success();
+}
+
+#[test]
+fn test_inspect_proof() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let public_param_dir = tmp_dir.join("public_params");
+    let proof_dir = tmp_dir.join("proofs");
+    let commit_dir = tmp_dir.join("commits");
+    let lurk_file = tmp_dir.join("prove.lurk");
+
+    let mut file = File::create(lurk_file.clone()).unwrap();
+    file.write_all(b"!(prove (+ 1 1))\n").unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.env("LURK_PERF", "max-parallel-simple");
+    cmd.arg("load");
+    cmd.arg(lurk_file.into_string());
+    cmd.arg("--public-params-dir");
+    cmd.arg(public_param_dir);
+    cmd.arg("--proofs-dir");
+    cmd.arg(&proof_dir);
+    cmd.arg("--commits-dir");
+    cmd.arg(commit_dir);
+    cmd.assert().success();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("inspect-proof");
+    cmd.arg(proof_dir.join(
+        "Nova_BN256_10_18748ce7ba3dd0e7560ec64983d6b01d84a6303880b3b0b24878133aa1b4a6bb.proof",
+    ));
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Reduction count: 10"));
+    assert!(stdout.contains("Expr: (+ 1 1)"));
+    assert!(stdout.contains("Expr: 2"));
+}
+
+#[test]
+fn test_bench() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let public_param_dir = tmp_dir.join("public_params");
+    let lurk_file = tmp_dir.join("bench.lurk");
+
+    let mut file = File::create(lurk_file.clone()).unwrap();
+    file.write_all(b"(+ 1 1)\n(* 2 3)\n!(defquery factorial)\n!(query '(factorial . 3))\n")
+        .unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.env("LURK_PERF", "max-parallel-simple");
+    cmd.arg("bench");
+    cmd.arg(lurk_file.into_string());
+    cmd.arg("--public-params-dir");
+    cmd.arg(public_param_dir);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let report = stdout.lines().last().unwrap();
+    assert!(report.starts_with('{'));
+    assert!(report.contains("\"rc\":10"));
+    // Both evaluations, with synthesis and folding timed apart
+    assert_eq!(2, report.matches("\"expr\":").count());
+    assert!(report.contains("\"synthesis_ms\":"));
+    assert!(report.contains("\"folding_ms\":"));
+    assert!(report.contains("\"queries\":{\"query_type\":\"factorial\""));
+    assert!(report.contains("\"proof_size\":"));
+}
+
+#[test]
+fn test_serve() {
+    use serde_json::{json, Value};
+    use std::{net::TcpListener, thread, time::Duration};
+
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let public_param_dir = tmp_dir.join("public_params");
+    let proof_dir = tmp_dir.join("proofs");
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let addr = format!("127.0.0.1:{port}");
+
+    let mut cmd = lurk_cmd();
+    cmd.env("LURK_PERF", "max-parallel-simple");
+    cmd.arg("serve");
+    cmd.arg("--addr");
+    cmd.arg(&addr);
+    cmd.arg("--public-params-dir");
+    cmd.arg(&public_param_dir);
+    cmd.arg("--proofs-dir");
+    cmd.arg(&proof_dir);
+    let mut server = cmd.spawn().unwrap();
+
+    let client = reqwest::blocking::Client::new();
+    let call = |method: &str, params: Value| -> Option<Value> {
+        let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+        let response = client
+            .post(format!("http://{addr}"))
+            .json(&request)
+            .send()
+            .ok()?;
+        response.json().ok()
+    };
+
+    let mut reply = None;
+    for _ in 0..100 {
+        reply = call("submit_program", json!({ "program": "(+ 1 1)" }));
+        if reply.is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    let job = reply.expect("server didn't start")["result"]["job"].clone();
+
+    let mut status = Value::Null;
+    for _ in 0..600 {
+        status = call("job_status", json!({ "job": job })).unwrap()["result"]["status"].clone();
+        if status == "done" || status == "failed" {
+            break;
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+    assert_eq!(status, "done");
+    let proof = call("get_proof", json!({ "job": job })).unwrap()["result"].clone();
+    server.kill().unwrap();
+
+    assert_eq!(proof["kind"], "program");
+    let mut cmd = lurk_cmd();
+    cmd.arg("verify");
+    cmd.arg(proof["proof_key"].as_str().unwrap());
+    cmd.arg("--public-params-dir");
+    cmd.arg(&public_param_dir);
+    cmd.arg("--proofs-dir");
+    cmd.arg(&proof_dir);
+    cmd.assert().success();
+}
+
+#[test]
+fn test_prove_defined_queries() {
+    let tmp_dir = Builder::new().prefix("tmp").tempdir().unwrap();
+    let tmp_dir = Utf8Path::from_path(tmp_dir.path()).unwrap();
+    let lurk_file = tmp_dir.join("queries.lurk");
+
+    let mut file = File::create(lurk_file.clone()).unwrap();
+    file.write_all(
+        b"!(defquery fib (n) (if (= n 0) 0 (if (= n 1) 1 (+ (fib (- n 1)) (fib (- n 2))))))\n",
+    )
+    .unwrap();
+    file.write_all(b"!(query '(fib . 10))\n").unwrap();
+    file.write_all(b"!(query-stats)\n").unwrap();
+    file.write_all(b"!(prove-queries)\n").unwrap();
+
+    let mut cmd = lurk_cmd();
+    cmd.arg("load");
+    cmd.arg(lurk_file.into_string());
+    cmd.assert().success();
+}